@@ -19,6 +19,7 @@ use std::io::{BufWriter, Write};
 use std::fs::File;
 
 use ltac::ltac::{LtacInstr, LtacArg};
+use ltac::abi::stack_arg_slot;
 use crate::utils::*;
 
 // Builds function/sytem call arguments
@@ -26,9 +27,19 @@ pub fn aarch64_build_pusharg(writer : &mut BufWriter<File>, code : &LtacInstr, i
     let mut reg32 = aarch64_arg_reg32(code.arg2_val);
     let mut reg64 = aarch64_arg_reg64(code.arg2_val);
 
+    let mut slot = None;
+
     if is_karg {
         reg32 = aarch64_karg_reg32(code.arg2_val);
         reg64 = aarch64_karg_reg64(code.arg2_val);
+    } else {
+        slot = stack_arg_slot(code, ARG_REGS);
+    }
+    
+    // Past the registers, we build the argument in x15 and store it on the stack
+    if slot.is_some() {
+        reg32 = "w15".to_string();
+        reg64 = "x15".to_string();
     }
 
     let mut line = String::new();
@@ -40,11 +51,25 @@ pub fn aarch64_build_pusharg(writer : &mut BufWriter<File>, code : &LtacInstr, i
         
             line.push_str("  ldr ");
             line.push_str(&reg32);
-            line.push_str(", [sp, ");
+            line.push_str(", [x29, ");
             line.push_str(&pos.to_string());
             line.push_str("]\n");
         },
         
+        LtacArg::GlobalMem(ref name) => {
+            line.push_str(&aarch64_global_addr(name));
+            line.push_str("  ldr ");
+            line.push_str(&reg32);
+            line.push_str(", [x14]\n");
+        },
+        
+        LtacArg::GlobalPtr(ref name) => {
+            line.push_str(&aarch64_global_addr(name));
+            line.push_str("  ldr ");
+            line.push_str(&reg64);
+            line.push_str(", [x14]\n");
+        },
+        
         LtacArg::I32(val) => {
             line.push_str("  mov ");
             line.push_str(&reg32);
@@ -71,6 +96,12 @@ pub fn aarch64_build_pusharg(writer : &mut BufWriter<File>, code : &LtacInstr, i
 
         _ => {},
     }
+    
+    if let Some(slot) = slot {
+        line.push_str("  str x15, [sp, ");
+        line.push_str(&(slot * 8).to_string());
+        line.push_str("]\n");
+    }
 
     writer.write(&line.into_bytes())
         .expect("[AArch64_build_pusharg] Write failed.");
@@ -78,10 +109,34 @@ pub fn aarch64_build_pusharg(writer : &mut BufWriter<File>, code : &LtacInstr, i
 
 // Builds a function call
 // Param: name
-pub fn aarch64_build_call(writer : &mut BufWriter<File>, code : &LtacInstr) {
-    let mut line = "  bl ".to_string();
-    line.push_str(&code.name);
-    line.push_str("\n\n");
+// Indirect calls have no name; the address is loaded from arg1 into x15
+pub fn aarch64_build_call(writer : &mut BufWriter<File>, code : &LtacInstr, stack_size : i32) {
+    let mut line = String::new();
+    
+    match &code.arg1 {
+        LtacArg::Mem(val) => {
+            let pos = stack_size - (*val);
+            
+            line.push_str("  ldr x15, [x29, ");
+            line.push_str(&pos.to_string());
+            line.push_str("]\n");
+        },
+        
+        LtacArg::GlobalMem(ref name) => {
+            line.push_str(&aarch64_global_addr(name));
+            line.push_str("  ldr x15, [x14]\n");
+        },
+        
+        _ => {},
+    }
+    
+    if code.name.len() == 0 {
+        line.push_str("  blr x15\n\n");
+    } else {
+        line.push_str("  bl ");
+        line.push_str(&code.name);
+        line.push_str("\n\n");
+    }
     
     writer.write(&line.into_bytes())
         .expect("[AArch64_build_call] Write failed.");
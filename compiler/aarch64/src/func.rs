@@ -18,7 +18,9 @@
 use std::io::{BufWriter, Write};
 use std::fs::File;
 
-use ltac::ltac::{LtacInstr};
+use ltac::ltac::{LtacInstr, LtacType, LtacArg};
+use ltac::abi::stack_arg_slot;
+use crate::utils::*;
 
 // Builds an extern declaration
 pub fn aarch64_build_extern(writer : &mut BufWriter<File>, code : &LtacInstr) {
@@ -42,7 +44,9 @@ pub fn aarch64_build_label(writer : &mut BufWriter<File>, code : &LtacInstr) {
 }
 
 // Builds a function
-pub fn aarch64_build_func(writer : &mut BufWriter<File>, code : &LtacInstr) -> i32 {
+// The stack arguments for our calls go below the frame, so sp moves down by arg_space.
+// The variables are addressed from x29, which stays put.
+pub fn aarch64_build_func(writer : &mut BufWriter<File>, code : &LtacInstr, arg_space : i32) -> i32 {
     let mut stack_size = code.arg1_val;
     if stack_size > 0 && stack_size < 32 {
         stack_size = 32;
@@ -60,7 +64,15 @@ pub fn aarch64_build_func(writer : &mut BufWriter<File>, code : &LtacInstr) -> i
     line.push_str(&stack_size.to_string());
     line.push_str("]!\n");
 
-    line.push_str("  mov x29, sp\n\n");
+    line.push_str("  mov x29, sp\n");
+    
+    if arg_space > 0 {
+        line.push_str("  sub sp, sp, ");
+        line.push_str(&arg_space.to_string());
+        line.push_str("\n");
+    }
+    
+    line.push_str("\n");
 
     writer.write(&line.into_bytes())
         .expect("[AArch64_build_func] Write failed.");
@@ -69,8 +81,13 @@ pub fn aarch64_build_func(writer : &mut BufWriter<File>, code : &LtacInstr) -> i
 }
 
 // Builds a return statement
-pub fn aarch64_build_ret(writer : &mut BufWriter<File>, stack_size : i32) {
+pub fn aarch64_build_ret(writer : &mut BufWriter<File>, stack_size : i32, arg_space : i32) {
     let mut line = String::new();
+    
+    if arg_space > 0 {
+        line.push_str("  mov sp, x29\n");
+    }
+    
     line.push_str("  ldp x29, x30, [sp], ");
     line.push_str(&stack_size.to_string());
     line.push_str("\n");
@@ -80,3 +97,45 @@ pub fn aarch64_build_ret(writer : &mut BufWriter<File>, stack_size : i32) {
     writer.write(&line.into_bytes())
         .expect("[AArch64_build_ret] Write failed.");
 }
+
+// Stores a function argument to its variable
+// Past the registers, the caller left it on the stack, just above our frame
+pub fn aarch64_build_ldarg(writer : &mut BufWriter<File>, code : &LtacInstr, stack_size : i32) {
+    let (store, is_64) = match code.instr_type {
+        LtacType::LdArgI8 | LtacType::LdArgU8 => ("strb", false),
+        LtacType::LdArgI16 | LtacType::LdArgU16 => ("strh", false),
+        LtacType::LdArgI32 | LtacType::LdArgU32 => ("str", false),
+        _ => ("str", true),
+    };
+    
+    let mut reg = if is_64 {
+        aarch64_arg_reg64(code.arg2_val)
+    } else {
+        aarch64_arg_reg32(code.arg2_val)
+    };
+    
+    let mut line = String::new();
+    
+    if let Some(slot) = stack_arg_slot(code, ARG_REGS) {
+        reg = if is_64 { "x15".to_string() } else { "w15".to_string() };
+        
+        line.push_str("  ldr ");
+        line.push_str(&reg);
+        line.push_str(", [x29, ");
+        line.push_str(&(stack_size + slot * 8).to_string());
+        line.push_str("]\n");
+    }
+    
+    if let LtacArg::Mem(val) = code.arg1 {
+        line.push_str("  ");
+        line.push_str(store);
+        line.push_str(" ");
+        line.push_str(&reg);
+        line.push_str(", [x29, ");
+        line.push_str(&(stack_size - val).to_string());
+        line.push_str("]\n");
+    }
+    
+    writer.write(&line.into_bytes())
+        .expect("[AArch64_build_ldarg] Write failed.");
+}
@@ -19,9 +19,15 @@ use std::io;
 use std::io::prelude::*;
 use std::io::BufWriter;
 use std::fs::File;
-use std::process::Command;
 
-use ltac::ltac::{LtacFile, LtacData, LtacDataType, LtacType, LtacInstr, LtacArg};
+use ltac::dwarf;
+use ltac::dwarf::DwarfTarget;
+use ltac::abi;
+use parser::Arch;
+use ltac::ltac::{LtacFile, LtacData, LtacDataType, LtacType, LtacInstr, LtacArg, escape_string};
+use ltac::backend::{Backend, Pass, Feature, CodeOptions};
+use ltac::toolchain::TargetInfo;
+use ltac::workspace::Workspace;
 
 mod call;
 mod func;
@@ -31,108 +37,84 @@ use call::*;
 use func::*;
 use utils::*;
 
-pub fn compile(ltac_file : &LtacFile) -> io::Result<()> {
-    let mut name = "/tmp/".to_string();
-    name.push_str(&ltac_file.name);
-    name.push_str(".asm");
-    
-    // Write it out
-    let file = File::create(&name)?;
-    let mut writer = BufWriter::new(file);
-    
-    write_data(&mut writer, &ltac_file.data);
-    write_code(&mut writer, &ltac_file.code);
+// The frame pointer (x29) points at the saved x29 and x30, and the variables are above it
+fn aarch64_var_offset(stack_size : i32, pos : i32) -> i32 {
+    let mut stack_size = stack_size;
+    if stack_size > 0 && stack_size < 32 {
+        stack_size = 32;
+    }
     
-    Ok(())
+    stack_size - pos
 }
 
-// Assemble a file
-pub fn build_asm(name : &String, no_link : bool) {
-    // Create all the names
-    let mut asm_name = "/tmp/".to_string();
-    asm_name.push_str(name);
-    asm_name.push_str(".asm");
+const DWARF_TARGET : DwarfTarget = DwarfTarget {
+    frame_reg : 29,
+    var_offset : aarch64_var_offset,
+};
+
+pub struct AArch64Backend;
+
+impl Backend for AArch64Backend {
+    fn name(&self) -> &'static str {
+        "aarch64"
+    }
     
-    let mut obj_name = "/tmp/".to_string();
-    if no_link {
-        obj_name = "./".to_string();
+    fn arch(&self) -> Arch {
+        Arch::AArch64
     }
     
-    obj_name.push_str(name);
-    obj_name.push_str(".o");
-
-    // Assemble
-    let asm = Command::new("as")
-        .args(&[&asm_name, "-o", &obj_name])
-        .output()
-        .expect("Fatal: Assembly failed.");
-        
-    if !asm.status.success() {
-        io::stdout().write_all(&asm.stdout).unwrap();
-        io::stderr().write_all(&asm.stderr).unwrap();
+    // Arithmetic only works on registers, so memory operands are split into loads and stores
+    fn passes(&self) -> Vec<Pass> {
+        vec![Pass::Risc]
     }
-}
- 
-// Link everything
-pub fn link(all_names : &Vec<String>, output : &String, use_c : bool, is_lib : bool) {
-    let mut names : Vec<String> = Vec::new();
-    let mut libs : Vec<String> = Vec::new();
     
-    for name in all_names.iter() {
-        if name.ends_with(".o") {
-            names.push(name.clone());
-        } else if name.starts_with("-l") {
-            libs.push(name.clone());
-        } else {
-            let mut obj_name = "/tmp/".to_string();
-            obj_name.push_str(&name);
-            obj_name.push_str(".o");
-            names.push(obj_name);
+    // Position independent code is not done yet
+    fn supports(&self, feature : Feature) -> bool {
+        match feature {
+            Feature::DebugInfo | Feature::SharedLib => return true,
+            Feature::Pic => return false,
         }
     }
     
-    // Link
-    //let ld : Output;
-    let mut args : Vec<&str> = Vec::new();
-    args.push("-L./");
+    fn compile(&self, ltac_file : &LtacFile, options : &CodeOptions, ws : &Workspace, name : &String) -> io::Result<()> {
+        compile(ltac_file, &ws.file(name, "asm"), options.debug)
+    }
     
-    if use_c {
-        if !is_lib {
-            args.push("/usr/lib/aarch64-linux-gnu/crti.o");
-            args.push("/usr/lib/aarch64-linux-gnu/crtn.o");
-            args.push("/usr/lib/aarch64-linux-gnu/crt1.o");
+    fn target(&self) -> TargetInfo {
+        TargetInfo {
+            prefixes : &["aarch64-linux-gnu"],
+            crt_dir : "/usr/lib/aarch64-linux-gnu",
+            dynamic_linker : "/lib/ld-linux-aarch64.so.1",
+            has_runtime : false,
         }
-        
-        args.push("-lc");
     }
+}
+
+fn compile(ltac_file : &LtacFile, name : &String, debug : bool) -> io::Result<()> {
+    // Write it out
+    let file = File::create(&name)?;
+    let mut writer = BufWriter::new(file);
     
-    args.push("-dynamic-linker");
-    args.push("/lib/ld-linux-aarch64.so.1");
-        
-    for name in names.iter() {
-        args.push(&name);
-    }
-        
-    if is_lib {
-        args.push("-shared");
-    }
+    write_data(&mut writer, &ltac_file.data);
+    
+    let mut line = ".text\n".to_string();
     
-    for lib in libs.iter() {
-        args.push(lib);
+    if debug {
+        line.push_str(&dwarf::write_text_start(ltac_file));
     }
-        
-    args.push("-o");
-    args.push(output);
     
-    let ld = Command::new("ld")
-        .args(args.as_slice())
-        .output()
-        .expect("Fatal: Linking failed.");
+    writer.write(&line.into_bytes())
+        .expect("[_code] Write failed");
+    
+    write_code(&mut writer, &ltac_file.code, debug);
     
-    if !ld.status.success() {
-        io::stdout().write_all(&ld.stdout).unwrap();
-        io::stderr().write_all(&ld.stderr).unwrap();
+    if debug {
+        let line = dwarf::write_text_end(ltac_file, &DWARF_TARGET);
+        writer.write(&line.into_bytes())
+            .expect("[_code] Write failed");
     }
+    
+    Ok(())
 }
 
 // Write the data section
@@ -145,7 +127,7 @@ fn write_data(writer : &mut BufWriter<File>, data : &Vec<LtacData>) {
             LtacDataType::StringL => {
                 line.push_str(&data.name);
                 line.push_str(": .string \"");
-                line.push_str(&data.val);
+                line.push_str(&escape_string(&data.val));
                 line.push_str("\"\n");
             },
             
@@ -162,44 +144,160 @@ fn write_data(writer : &mut BufWriter<File>, data : &Vec<LtacData>) {
                 line.push_str(&data.val);
                 line.push_str("\n");
             },
+            
+            // Globals and tables are written below
+            _ => {},
         }
     }
     
+    line.push_str(&aarch64_write_globals(data));
+    line.push_str(&aarch64_write_tables(data));
     line.push_str("\n");
     
     writer.write(&line.into_bytes())
         .expect("[AARCH64_data] Write failed in .data");
 }
 
+// Writes the global variables
+fn aarch64_write_globals(data : &Vec<LtacData>) -> String {
+    let mut line = String::new();
+    let mut bss = String::new();
+    
+    for data in data.iter() {
+        let (directive, size) = match &data.data_type {
+            LtacDataType::GlobalB => (".byte", 1),
+            LtacDataType::GlobalW => (".hword", 2),
+            LtacDataType::GlobalDW => (".word", 4),
+            LtacDataType::GlobalQW | LtacDataType::GlobalStr => (".xword", 8),
+            LtacDataType::GlobalBss => (".zero", data.val.parse::<i32>().unwrap()),
+            _ => continue,
+        };
+        
+        let mut entry = String::new();
+        entry.push_str(".global ");
+        entry.push_str(&data.name);
+        entry.push_str("\n.type ");
+        entry.push_str(&data.name);
+        entry.push_str(", %object\n.size ");
+        entry.push_str(&data.name);
+        entry.push_str(", ");
+        entry.push_str(&size.to_string());
+        entry.push_str("\n.balign ");
+        entry.push_str(&size.to_string());
+        entry.push_str("\n");
+        entry.push_str(&data.name);
+        entry.push_str(": ");
+        entry.push_str(directive);
+        entry.push_str(" ");
+        entry.push_str(&data.val);
+        entry.push_str("\n");
+        
+        if data.data_type == LtacDataType::GlobalBss {
+            bss.push_str(&entry);
+        } else {
+            line.push_str(&entry);
+        }
+    }
+    
+    if bss.len() > 0 {
+        line.push_str("\n.bss\n");
+        line.push_str(&bss);
+    }
+    
+    line
+}
+
+// Writes the constant tables
+// Tables of strings hold addresses, so they are only read-only once they are relocated
+fn aarch64_write_tables(data : &Vec<LtacData>) -> String {
+    let mut line = String::new();
+    let mut relro = String::new();
+    
+    for data in data.iter() {
+        let (directive, size) = match &data.data_type {
+            LtacDataType::TableB => (".byte", 1),
+            LtacDataType::TableW => (".hword", 2),
+            LtacDataType::TableDW => (".word", 4),
+            LtacDataType::TableQW | LtacDataType::TableStr => (".xword", 8),
+            _ => continue,
+        };
+        
+        let mut entry = String::new();
+        entry.push_str(".balign ");
+        entry.push_str(&size.to_string());
+        entry.push_str("\n");
+        entry.push_str(&data.name);
+        entry.push_str(": ");
+        entry.push_str(directive);
+        entry.push_str(" ");
+        entry.push_str(&data.val);
+        entry.push_str("\n");
+        
+        if data.data_type == LtacDataType::TableStr {
+            relro.push_str(&entry);
+        } else {
+            line.push_str(&entry);
+        }
+    }
+    
+    if line.len() > 0 {
+        line.insert_str(0, "\n.section .rodata\n");
+    }
+    
+    if relro.len() > 0 {
+        line.push_str("\n.section .data.rel.ro\n");
+        line.push_str(&relro);
+    }
+    
+    line
+}
+
 // Write the code section
-fn write_code(writer : &mut BufWriter<File>, code : &Vec<LtacInstr>) {
-    let line = ".text\n".to_string();
-    writer.write(&line.into_bytes())
-        .expect("[_code] Write failed");
+// With debugging on, each change in the source line gets a .loc directive, and each function
+// gets an end label for its address range
+fn write_code(writer : &mut BufWriter<File>, code : &Vec<LtacInstr>, debug : bool) {
+    let mut line_no = 0;
+    let mut func_name = String::new();
 
     let mut stack_size = 0;
+    let mut arg_space = 0;
+    
+    let all_arg_space = abi::stack_arg_space(code, ARG_REGS);
 
     for code in code.iter() {
+        if debug && code.instr_type == LtacType::Func {
+            write_func_end(writer, &func_name);
+            func_name = code.name.clone();
+            line_no = 0;
+        }
+
+        if debug && code.line_no > 0 && code.line_no != line_no {
+            line_no = code.line_no;
+            writer.write(&dwarf::write_loc(line_no).into_bytes())
+                .expect("[_code] Write failed");
+        }
+
         match &code.instr_type {
         
             // Basic function instructions
             LtacType::Extern => aarch64_build_extern(writer, &code),
             LtacType::Label => aarch64_build_label(writer, &code),
-            LtacType::Func => stack_size = aarch64_build_func(writer, &code),
-            LtacType::Ret => aarch64_build_ret(writer, stack_size),
+            LtacType::Func => {
+                arg_space = *all_arg_space.get(&code.name).unwrap_or(&0);
+                stack_size = aarch64_build_func(writer, &code, arg_space);
+            },
+            
+            LtacType::Ret => aarch64_build_ret(writer, stack_size, arg_space),
             
             // Used to load function arguments
-            LtacType::LdArgI8 => {},
-            LtacType::LdArgU8 => {},
-            LtacType::LdArgI16 => {},
-            LtacType::LdArgU16 => {},
-            LtacType::LdArgI32 => {},
-            LtacType::LdArgU32 => {},
-            LtacType::LdArgI64 => {},
-            LtacType::LdArgU64 => {},
+            LtacType::LdArgI8 | LtacType::LdArgU8
+            | LtacType::LdArgI16 | LtacType::LdArgU16
+            | LtacType::LdArgI32 | LtacType::LdArgU32
+            | LtacType::LdArgI64 | LtacType::LdArgU64
+            | LtacType::LdArgPtr => aarch64_build_ldarg(writer, &code, stack_size),
+            
             LtacType::LdArgF32 => {},
             LtacType::LdArgF64 => {},
-            LtacType::LdArgPtr => {},
             
             // All the move instructions
             LtacType::MovB => {},
@@ -220,7 +318,7 @@ fn write_code(writer : &mut BufWriter<File>, code : &Vec<LtacInstr>) {
             // Argument and function call instructions
             LtacType::PushArg => aarch64_build_pusharg(writer, &code, false, stack_size),
             LtacType::KPushArg => aarch64_build_pusharg(writer, &code, true, stack_size),
-            LtacType::Call => aarch64_build_call(writer, &code),
+            LtacType::Call => aarch64_build_call(writer, &code, stack_size),
             LtacType::Syscall => {},
             
             // Comparison instructons
@@ -248,6 +346,14 @@ fn write_code(writer : &mut BufWriter<File>, code : &Vec<LtacInstr>) {
             LtacType::Bge => {},
             LtacType::Bfg => {},
             LtacType::Bfge => {},
+            LtacType::Bul => {},
+            LtacType::Bule => {},
+            LtacType::Bug => {},
+            LtacType::Buge => {},
+            
+            // Jump tables
+            LtacType::BrTable => {},
+            LtacType::TableEntry => {},
             
             // Signed byte math operations
             LtacType::I8Add => {},
@@ -359,6 +465,23 @@ fn write_code(writer : &mut BufWriter<File>, code : &Vec<LtacInstr>) {
             _ => aarch64_build_instr(writer, &code),
         }
     }
+
+    if debug {
+        write_func_end(writer, &func_name);
+    }
+}
+
+// Marks the end of a function for the debugger
+fn write_func_end(writer : &mut BufWriter<File>, name : &String) {
+    if name.len() == 0 {
+        return;
+    }
+
+    let mut line = dwarf::func_end_label(name);
+    line.push_str(":\n");
+
+    writer.write(&line.into_bytes())
+        .expect("[_code] Write failed");
 }
 
 // Builds the load-store instructions
@@ -388,10 +511,16 @@ fn aarch64_build_ld_str(writer : &mut BufWriter<File>, code : &LtacInstr, stack_
     match &code.arg1 {
         LtacArg::Mem(val) => {
             let pos = stack_size - (*val);
-            line.push_str("[sp, ");
+            line.push_str("[x29, ");
             line.push_str(&pos.to_string());
             line.push_str("]");
         },
+        
+        LtacArg::GlobalMem(ref name) => {
+            let addr = aarch64_global_addr(name);
+            line.insert_str(0, &addr);
+            line.push_str("[x14]");
+        },
 
         _ => {},
     }
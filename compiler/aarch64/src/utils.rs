@@ -15,6 +15,21 @@
 // with this program; if not, write to the Free Software Foundation, Inc.,
 // 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
 
+use ltac::abi::ArgRegs;
+
+// Loads the address of a global variable into x14
+pub fn aarch64_global_addr(name : &String) -> String {
+    let mut line = "  adrp x14, ".to_string();
+    line.push_str(name);
+    line.push_str("\n");
+    
+    line.push_str("  add x14, x14, :lo12:");
+    line.push_str(name);
+    line.push_str("\n");
+    
+    line
+}
+
 // Gets a register based on position
 // Kernel argument registers
 pub fn aarch64_karg_reg32(pos : i32) -> String {
@@ -44,6 +59,9 @@ pub fn aarch64_karg_reg64(pos : i32) -> String {
 }
 
 // Function argument registers
+// Past these, the arguments go on the stack
+pub const ARG_REGS : ArgRegs = ArgRegs { ints : 8, floats : 8 };
+
 pub fn aarch64_arg_reg32(pos : i32) -> String {
     match pos {
         1 => return "w0".to_string(),
@@ -52,6 +70,8 @@ pub fn aarch64_arg_reg32(pos : i32) -> String {
         4 => return "w3".to_string(),
         5 => return "w4".to_string(),
         6 => return "w5".to_string(),
+        7 => return "w6".to_string(),
+        8 => return "w7".to_string(),
         _ => return String::new(),
     };
 }
@@ -64,6 +84,8 @@ pub fn aarch64_arg_reg64(pos : i32) -> String {
         4 => return "x3".to_string(),
         5 => return "x4".to_string(),
         6 => return "x5".to_string(),
+        7 => return "x6".to_string(),
+        8 => return "x7".to_string(),
         _ => return String::new(),
     };
 }
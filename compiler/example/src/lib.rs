@@ -18,13 +18,55 @@ use std::io::prelude::*;
 use std::io::BufWriter;
 use std::fs::File;
 
+use parser::Arch;
 use ltac::ltac::{LtacFile, LtacData, LtacDataType, LtacType, LtacInstr};
+use ltac::backend::{Backend, Pass, Feature, CodeOptions};
+use ltac::toolchain::TargetInfo;
+use ltac::workspace::Workspace;
 
-pub fn compile(ltac_file : &LtacFile) -> io::Result<()> {
-    let mut name = "./".to_string();
-    name.push_str(&ltac_file.name);
-    name.push_str(".asm");
+// This is what the driver sees. Add it to the list in src/targets.rs to use it.
+pub struct ExampleBackend;
+
+impl Backend for ExampleBackend {
+    // The name for -march=
+    fn name(&self) -> &'static str {
+        "example"
+    }
+    
+    // Add your architecture to the Arch enum in the parser
+    fn arch(&self) -> Arch {
+        Arch::X86_64
+    }
+    
+    // Load-store machines will want Pass::Risc
+    fn passes(&self) -> Vec<Pass> {
+        Vec::new()
+    }
+    
+    fn supports(&self, feature : Feature) -> bool {
+        match feature {
+            Feature::Pic | Feature::DebugInfo | Feature::SharedLib => return false,
+        }
+    }
+    
+    // Write the assembly to the path the workspace gives you
+    fn compile(&self, ltac_file : &LtacFile, _options : &CodeOptions, ws : &Workspace, name : &String) -> io::Result<()> {
+        compile(ltac_file, &ws.file(name, "asm"))
+    }
     
+    // Where the tools and the system files are. The toolchain assembles and links with
+    // these; override assemble and link if your target needs something else.
+    fn target(&self) -> TargetInfo {
+        TargetInfo {
+            prefixes : &["example-linux-gnu"],
+            crt_dir : "/usr/lib/example-linux-gnu",
+            dynamic_linker : "/lib/ld-linux-example.so.1",
+            has_runtime : false,
+        }
+    }
+}
+
+fn compile(ltac_file : &LtacFile, name : &String) -> io::Result<()> {
     // Write it out
     let file = File::create(&name)?;
     let mut writer = BufWriter::new(file);
@@ -39,12 +81,23 @@ pub fn compile(ltac_file : &LtacFile) -> io::Result<()> {
 fn write_data(writer : &mut BufWriter<File>, data : &Vec<LtacData>) {
     let mut line = String::new();
     line.push_str(".data\n");
-
+    
     for data in data.iter() {
         match &data.data_type {
             LtacDataType::StringL => {},
             LtacDataType::FloatL => {},
             LtacDataType::DoubleL => {},
+            LtacDataType::GlobalB => {},
+            LtacDataType::GlobalW => {},
+            LtacDataType::GlobalDW => {},
+            LtacDataType::GlobalQW => {},
+            LtacDataType::GlobalStr => {},
+            LtacDataType::GlobalBss => {},
+            LtacDataType::TableB => {},
+            LtacDataType::TableW => {},
+            LtacDataType::TableDW => {},
+            LtacDataType::TableQW => {},
+            LtacDataType::TableStr => {},
         }
     }
     
@@ -59,10 +112,10 @@ fn write_code(writer : &mut BufWriter<File>, code : &Vec<LtacInstr>) {
     let line = ".text\n".to_string();
     writer.write(&line.into_bytes())
         .expect("[_code] Write failed");
-
+    
     for code in code.iter() {
         match &code.instr_type {
-        
+            
             // Basic function instructions
             LtacType::Extern => {},
             LtacType::Label => {},
@@ -132,6 +185,14 @@ fn write_code(writer : &mut BufWriter<File>, code : &Vec<LtacInstr>) {
             LtacType::Bge => {},
             LtacType::Bfg => {},
             LtacType::Bfge => {},
+            LtacType::Bul => {},
+            LtacType::Bule => {},
+            LtacType::Bug => {},
+            LtacType::Buge => {},
+            
+            // Jump tables
+            LtacType::BrTable => {},
+            LtacType::TableEntry => {},
             
             // Signed byte math operations
             LtacType::I8Add => {},
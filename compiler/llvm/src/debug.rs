@@ -0,0 +1,170 @@
+
+// This file is part of the Lila compiler
+// Copyright (C) 2020-2021 Patrick Flynn
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; version 2.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, write to the Free Software Foundation, Inc.,
+// 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+
+extern crate llvm_sys as llvm;
+
+use std::ptr;
+use std::path::Path;
+use llvm::*;
+use llvm::core::*;
+use llvm::debuginfo::*;
+
+use parser::llir::LLirDataType;
+use crate::*;
+
+// Kreas la DIBuilder, la dosieron, kaj la kompilan unuon
+pub unsafe fn llvm_debug_init(builder : &mut Builder, source : &String) {
+    let path = Path::new(source);
+    let file_name = match path.file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => source.clone(),
+    };
+    
+    // La dosierujo estas absoluta, por ke la erarserĉilo trovu la fonton
+    let mut dir = match path.parent() {
+        Some(dir) => dir.to_string_lossy().into_owned(),
+        None => String::new(),
+    };
+    
+    if !path.is_absolute() {
+        let cwd = std::env::current_dir().unwrap_or_default().to_string_lossy().into_owned();
+        dir = if dir.is_empty() { cwd } else { format!("{}/{}", cwd, dir) };
+    }
+    
+    let di_builder = LLVMCreateDIBuilder(builder.module);
+    let file = LLVMDIBuilderCreateFile(di_builder, file_name.as_ptr() as *const _, file_name.len(),
+                    dir.as_ptr() as *const _, dir.len());
+    
+    let producer = "lilac";
+    let flags = "";
+    
+    LLVMDIBuilderCreateCompileUnit(di_builder, LLVMDWARFSourceLanguage::LLVMDWARFSourceLanguageC99, file,
+        producer.as_ptr() as *const _, producer.len(), 0, flags.as_ptr() as *const _, 0, 0,
+        flags.as_ptr() as *const _, 0, LLVMDWARFEmissionKind::LLVMDWARFEmissionKindFull, 0, 0, 0,
+        flags.as_ptr() as *const _, 0, flags.as_ptr() as *const _, 0);
+    
+    // Sen ĉi tiuj flagoj, LLVM forĵetas la informojn
+    let i32_type = LLVMInt32TypeInContext(builder.context);
+    let version = LLVMValueAsMetadata(LLVMConstInt(i32_type, LLVMDebugMetadataVersion() as u64, 0));
+    let dwarf = LLVMValueAsMetadata(LLVMConstInt(i32_type, 4, 0));
+    
+    let key = "Debug Info Version";
+    LLVMAddModuleFlag(builder.module, LLVMModuleFlagBehavior::LLVMModuleFlagBehaviorWarning,
+        key.as_ptr() as *const _, key.len(), version);
+    
+    let key = "Dwarf Version";
+    LLVMAddModuleFlag(builder.module, LLVMModuleFlagBehavior::LLVMModuleFlagBehaviorWarning,
+        key.as_ptr() as *const _, key.len(), dwarf);
+    
+    builder.di_builder = di_builder;
+    builder.di_file = file;
+}
+
+// Finas la informojn; ĉi tio devas okazi antaŭ la kontrolo de la modulo
+pub unsafe fn llvm_debug_finish(builder : &mut Builder) {
+    if builder.di_builder.is_null() {
+        return;
+    }
+    
+    LLVMDIBuilderFinalize(builder.di_builder);
+    LLVMDisposeDIBuilder(builder.di_builder);
+    builder.di_builder = ptr::null_mut();
+}
+
+// Kreas la subprogramon de la nuna funkcio
+pub unsafe fn llvm_debug_func(builder : &mut Builder, name : &String, line_no : i32) {
+    if builder.di_builder.is_null() {
+        return;
+    }
+    
+    let func_type = LLVMDIBuilderCreateSubroutineType(builder.di_builder, builder.di_file,
+                        ptr::null_mut(), 0, LLVMDIFlagZero);
+    
+    let scope = LLVMDIBuilderCreateFunction(builder.di_builder, builder.di_file,
+                    name.as_ptr() as *const _, name.len(), name.as_ptr() as *const _, name.len(),
+                    builder.di_file, line_no as u32, func_type, 0, 1, line_no as u32, LLVMDIFlagZero, 0);
+    
+    LLVMSetSubprogram(builder.func, scope);
+    builder.di_scope = scope;
+    
+    // Ĉiu instrukcio en la funkcio bezonas lokon, eĉ la eniraj
+    builder.line_no = 0;
+    llvm_debug_loc(builder, line_no);
+}
+
+// Ŝanĝas la nunan linion
+pub unsafe fn llvm_debug_loc(builder : &mut Builder, line_no : i32) {
+    if builder.di_scope.is_null() || line_no <= 0 || line_no == builder.line_no {
+        return;
+    }
+    
+    let loc = LLVMDIBuilderCreateDebugLocation(builder.context, line_no as u32, 0, builder.di_scope, ptr::null_mut());
+    LLVMSetCurrentDebugLocation2(builder.builder, loc);
+    builder.line_no = line_no;
+}
+
+// Priskribas lokan variablon sur la stako
+// Tabeloj kaj rekordoj estas montriloj al siaj eroj
+pub unsafe fn llvm_debug_var(builder : &mut Builder, var : LLVMValueRef, name : &String, data_type : &LLirDataType, is_ptr : bool) {
+    if builder.di_scope.is_null() {
+        return;
+    }
+    
+    let mut var_type = llvm_debug_type(builder, data_type);
+    
+    if is_ptr {
+        var_type = LLVMDIBuilderCreatePointerType(builder.di_builder, var_type, 64, 0, 0, ptr::null(), 0);
+    }
+    
+    let info = LLVMDIBuilderCreateAutoVariable(builder.di_builder, builder.di_scope,
+                    name.as_ptr() as *const _, name.len(), builder.di_file, builder.line_no as u32,
+                    var_type, 1, LLVMDIFlagZero, 0);
+    
+    let expr = LLVMDIBuilderCreateExpression(builder.di_builder, ptr::null_mut(), 0);
+    let loc = LLVMDIBuilderCreateDebugLocation(builder.context, builder.line_no as u32, 0, builder.di_scope, ptr::null_mut());
+    
+    LLVMDIBuilderInsertDeclareAtEnd(builder.di_builder, var, info, expr, loc, builder.entry);
+}
+
+// Redonas la bazan tipon por LLIR tipo
+unsafe fn llvm_debug_type(builder : &mut Builder, data_type : &LLirDataType) -> LLVMMetadataRef {
+    // DW_ATE_*
+    let (name, size, encoding) = match &data_type {
+        LLirDataType::Byte => ("byte", 8, 6),
+        LLirDataType::UByte => ("ubyte", 8, 8),
+        LLirDataType::Word => ("short", 16, 5),
+        LLirDataType::UWord => ("ushort", 16, 7),
+        LLirDataType::Int => ("int", 32, 5),
+        LLirDataType::UInt => ("uint", 32, 7),
+        LLirDataType::Int64 => ("int64", 64, 5),
+        LLirDataType::UInt64 => ("uint64", 64, 7),
+        LLirDataType::Float => ("float", 32, 4),
+        LLirDataType::Double => ("double", 64, 4),
+        
+        LLirDataType::Str => {
+            let char_type = llvm_debug_type(builder, &LLirDataType::UByte);
+            return LLVMDIBuilderCreatePointerType(builder.di_builder, char_type, 64, 0, 0, ptr::null(), 0);
+        },
+        
+        LLirDataType::Ptr | LLirDataType::Void => {
+            let byte_type = llvm_debug_type(builder, &LLirDataType::Byte);
+            return LLVMDIBuilderCreatePointerType(builder.di_builder, byte_type, 64, 0, 0, ptr::null(), 0);
+        },
+    };
+    
+    LLVMDIBuilderCreateBasicType(builder.di_builder, name.as_ptr() as *const _, name.len(), size, encoding, LLVMDIFlagZero)
+}
@@ -0,0 +1,171 @@
+
+// This file is part of the Lila compiler
+// Copyright (C) 2020-2021 Patrick Flynn
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; version 2.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, write to the Free Software Foundation, Inc.,
+// 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+
+extern crate llvm_sys as llvm;
+
+use std::ffi::CString;
+use llvm::LLVMIntPredicate;
+use llvm::LLVMRealPredicate;
+use llvm::core::*;
+
+use parser::llir::{LLirInstr, LLirArg, LLirType};
+use crate::*;
+
+// Redonas la blokon por etikedo, kreante ĝin se necese
+unsafe fn llvm_get_block(builder : &mut Builder, name : &String) -> LLVMBasicBlockRef {
+    if let Some(block) = builder.blocks.get(name) {
+        return *block;
+    }
+    
+    let c_name = CString::new(name.clone()).unwrap();
+    let block = LLVMAppendBasicBlockInContext(builder.context, builder.func, c_name.as_ptr() as *const _);
+    builder.blocks.insert(name.clone(), block);
+    
+    block
+}
+
+// LLVM blokoj devas fini per unu salto aŭ reveno
+// Se la nuna bloko jam finiĝis, la sekva kodo iras en novan (neatingeblan) blokon
+pub unsafe fn llvm_check_block(builder : &mut Builder) {
+    if builder.func.is_null() {
+        return;
+    }
+    
+    let current = LLVMGetInsertBlock(builder.builder);
+    
+    if !LLVMGetBasicBlockTerminator(current).is_null() {
+        let c_name = CString::new("").unwrap();
+        let block = LLVMAppendBasicBlockInContext(builder.context, builder.func, c_name.as_ptr() as *const _);
+        LLVMPositionBuilderAtEnd(builder.builder, block);
+    }
+}
+
+// Komencas novan blokon; la antaŭa bloko falas en ĝin
+pub unsafe fn llvm_build_label(builder : &mut Builder, line : &LLirInstr) {
+    let name = match &line.arg1 {
+        LLirArg::Label(name) => name.clone(),
+        _ => return,
+    };
+    
+    let block = llvm_get_block(builder, &name);
+    let current = LLVMGetInsertBlock(builder.builder);
+    
+    if LLVMGetBasicBlockTerminator(current).is_null() {
+        LLVMBuildBr(builder.builder, block);
+    }
+    
+    LLVMPositionBuilderAtEnd(builder.builder, block);
+}
+
+pub unsafe fn llvm_build_jmp(builder : &mut Builder, line : &LLirInstr) {
+    let name = match &line.arg1 {
+        LLirArg::Label(name) => name.clone(),
+        _ => return,
+    };
+    
+    let block = llvm_get_block(builder, &name);
+    LLVMBuildBr(builder.builder, block);
+}
+
+// Saltotabelo iĝas switch; la indekso de ĉiu etikedo estas ĝia valoro
+pub unsafe fn llvm_build_jmp_table(builder : &mut Builder, line : &LLirInstr) {
+    let (labels, default) = match (&line.arg2, &line.arg3) {
+        (LLirArg::ArgList(labels), LLirArg::Label(default)) => (labels, default.clone()),
+        _ => return,
+    };
+    
+    let index_type = llvm_type(builder, &line.data_type);
+    let index = llvm_build_value(builder, &line.arg1, index_type);
+    let default_block = llvm_get_block(builder, &default);
+    
+    let switch = LLVMBuildSwitch(builder.builder, index, default_block, labels.len() as u32);
+    
+    for (i, label) in labels.iter().enumerate() {
+        if let LLirArg::Label(name) = label {
+            let block = llvm_get_block(builder, name);
+            LLVMAddCase(switch, LLVMConstInt(index_type, i as u64, 0), block);
+        }
+    }
+}
+
+// Konstruas komparon kaj saltas al la etikedo se ĝi estas vera
+pub unsafe fn llvm_build_cmp(builder : &mut Builder, line : &LLirInstr) {
+    let name = match &line.arg1 {
+        LLirArg::Label(name) => name.clone(),
+        _ => return,
+    };
+    
+    let op_type = llvm_type(builder, &line.data_type);
+    let lval = llvm_build_value(builder, &line.arg2, op_type);
+    let rval = llvm_build_value(builder, &line.arg3, op_type);
+    
+    let c_name = CString::new("").unwrap();
+    
+    let cond = match &line.instr_type {
+        LLirType::CeqF32 | LLirType::CeqF64
+        | LLirType::CneqF32 | LLirType::CneqF64
+        | LLirType::CltF32 | LLirType::CltF64
+        | LLirType::CleF32 | LLirType::CleF64
+        | LLirType::CgtF32 | LLirType::CgtF64
+        | LLirType::CgeF32 | LLirType::CgeF64 => {
+            let pred = llvm_real_predicate(&line.instr_type);
+            LLVMBuildFCmp(builder.builder, pred, lval, rval, c_name.as_ptr() as *const _)
+        },
+        
+        _ => {
+            let pred = llvm_int_predicate(&line.instr_type);
+            LLVMBuildICmp(builder.builder, pred, lval, rval, c_name.as_ptr() as *const _)
+        },
+    };
+    
+    let true_block = llvm_get_block(builder, &name);
+    let false_block = LLVMAppendBasicBlockInContext(builder.context, builder.func, c_name.as_ptr() as *const _);
+    
+    LLVMBuildCondBr(builder.builder, cond, true_block, false_block);
+    LLVMPositionBuilderAtEnd(builder.builder, false_block);
+}
+
+// Redonas la predikaton por glitkoma komparo
+// "Ne egala" ankaŭ estas vera por NaN, kiel en C
+fn llvm_real_predicate(instr_type : &LLirType) -> LLVMRealPredicate {
+    match &instr_type {
+        LLirType::CeqF32 | LLirType::CeqF64 => LLVMRealPredicate::LLVMRealOEQ,
+        LLirType::CneqF32 | LLirType::CneqF64 => LLVMRealPredicate::LLVMRealUNE,
+        LLirType::CltF32 | LLirType::CltF64 => LLVMRealPredicate::LLVMRealOLT,
+        LLirType::CleF32 | LLirType::CleF64 => LLVMRealPredicate::LLVMRealOLE,
+        LLirType::CgtF32 | LLirType::CgtF64 => LLVMRealPredicate::LLVMRealOGT,
+        _ => LLVMRealPredicate::LLVMRealOGE,
+    }
+}
+
+// Redonas la predikaton por entjera komparo
+fn llvm_int_predicate(instr_type : &LLirType) -> LLVMIntPredicate {
+    match &instr_type {
+        LLirType::CeqB | LLirType::CeqW | LLirType::CeqDW | LLirType::CeqQW => LLVMIntPredicate::LLVMIntEQ,
+        LLirType::CneqB | LLirType::CneqW | LLirType::CneqDW | LLirType::CneqQW => LLVMIntPredicate::LLVMIntNE,
+        
+        LLirType::CsltB | LLirType::CsltW | LLirType::CsltDW | LLirType::CsltQW => LLVMIntPredicate::LLVMIntSLT,
+        LLirType::CultB | LLirType::CultW | LLirType::CultDW | LLirType::CultQW => LLVMIntPredicate::LLVMIntULT,
+        LLirType::CsleB | LLirType::CsleW | LLirType::CsleDW | LLirType::CsleQW => LLVMIntPredicate::LLVMIntSLE,
+        LLirType::CuleB | LLirType::CuleW | LLirType::CuleDW | LLirType::CuleQW => LLVMIntPredicate::LLVMIntULE,
+        
+        LLirType::CsgtB | LLirType::CsgtW | LLirType::CsgtDW | LLirType::CsgtQW => LLVMIntPredicate::LLVMIntSGT,
+        LLirType::CugtB | LLirType::CugtW | LLirType::CugtDW | LLirType::CugtQW => LLVMIntPredicate::LLVMIntUGT,
+        LLirType::CsgeB | LLirType::CsgeW | LLirType::CsgeDW | LLirType::CsgeQW => LLVMIntPredicate::LLVMIntSGE,
+        _ => LLVMIntPredicate::LLVMIntUGE,
+    }
+}
@@ -18,154 +18,276 @@
 extern crate llvm_sys as llvm;
 
 use std::ffi::CString;
+use std::ptr;
 use llvm::*;
 use llvm::core::*;
 
-use parser::llir::{LLirInstr, LLirArg, LLirDataType};
+use parser::llir;
+use parser::llir::{LLirInstr, LLirArg, LLirType};
 use crate::*;
+use crate::debug::*;
 
-// Konstrui LLVM funkiojn kaj eksterjan funkiojn
-pub fn llvm_build_func(builder : &mut Builder, line : &LLirInstr, is_extern : bool) {
-    unsafe {
-        // Krei la funkcion
+// Deklaras ĉiujn funkciojn antaŭ la kodo, por ke alvokoj povu trovi funkciojn difinitajn poste
+pub unsafe fn llvm_declare_funcs(builder : &mut Builder, code : &Vec<LLirInstr>) {
+    for line in code.iter() {
+        if line.instr_type != LLirType::Func && line.instr_type != LLirType::Extern {
+            continue;
+        }
+        
         let func_name = match &line.arg1 {
             LLirArg::Label(name) => name.clone(),
-            _ => String::new(),
+            _ => continue,
         };
         
-        let func_type : LLVMTypeRef;
-        match &line.data_type {
-            LLirDataType::Int => func_type = LLVMInt32TypeInContext(builder.context),
-            _ => func_type = LLVMVoidTypeInContext(builder.context),
+        if builder.funcs.contains_key(&func_name) {
+            continue;
         }
         
-        let mut args = [];
-        let function_type = LLVMFunctionType(func_type, args.as_mut_ptr(), args.len() as u32, 0);
+        let mut args : Vec<LLVMTypeRef> = Vec::new();
+        
+        if let LLirArg::TypeList(types) = &line.arg2 {
+            for data_type in types.iter() {
+                args.push(llvm_type(builder, data_type));
+            }
+        }
         
-        let c_str = CString::new(func_name.clone()).unwrap();
-        let func = LLVMAddFunction(builder.module, c_str.as_ptr() as *const _, function_type);
-        LLVMSetLinkage(func, LLVMLinkage::LLVMExternalLinkage);
+        let is_variadic = line.arg3 != LLirArg::None;
+        let ret_type = llvm_type(builder, &line.data_type);
         
-        builder.funcs.insert(func_name.clone(), func);
+        let func = llvm_add_func(builder, &func_name, ret_type, &mut args, is_variadic);
+        builder.funcs.insert(func_name, func);
         
-        if !is_extern {
-            // Agordi la funkcion blokon
-            let mut bb_name = "bb_".to_string();
-            bb_name.push_str(&func_name);
-            let block_name = CString::new(bb_name).unwrap();
-            
-            let func_block = LLVMAppendBasicBlockInContext(builder.context, func, block_name.as_ptr() as *const _);
-            LLVMPositionBuilderAtEnd(builder.builder, func_block);
+        // Sen la C biblioteko, LLVM ne rajtas anstataŭigi alvokojn per C funkcioj (ekz. printf -> puts)
+        if line.instr_type == LLirType::Func && !builder.use_c {
+            let key = "no-builtins";
+            let attr = LLVMCreateStringAttribute(builder.context, key.as_ptr() as *const _, key.len() as u32, ptr::null(), 0);
+            LLVMAddAttributeAtIndex(func, LLVMAttributeFunctionIndex, attr);
         }
     }
 }
 
+// Aldonas funkcion al la modulo
+pub unsafe fn llvm_add_func(builder : &mut Builder, name : &String, ret_type : LLVMTypeRef, args : &mut Vec<LLVMTypeRef>, is_variadic : bool) -> LLVMValueRef {
+    let function_type = LLVMFunctionType(ret_type, args.as_mut_ptr(), args.len() as u32, is_variadic as i32);
+    
+    let c_str = CString::new(name.clone()).unwrap();
+    let func = LLVMAddFunction(builder.module, c_str.as_ptr() as *const _, function_type);
+    LLVMSetLinkage(func, LLVMLinkage::LLVMExternalLinkage);
+    
+    func
+}
+
+// Komencas la korpon de LLVM funkcio
+pub unsafe fn llvm_build_func(builder : &mut Builder, line : &LLirInstr) {
+    llvm_finish_func(builder);
+    
+    let func_name = match &line.arg1 {
+        LLirArg::Label(name) => name.clone(),
+        _ => String::new(),
+    };
+    
+    let func = match builder.funcs.get(&func_name) {
+        Some(f) => *f,
+        None => return,
+    };
+    
+    builder.func = func;
+    builder.ret_type = llvm_type(builder, &line.data_type);
+    builder.vars.clear();
+    builder.blocks.clear();
+    
+    llvm_debug_func(builder, &func_name, line.line_no);
+    
+    // La enira bloko tenas la lokajn variablojn, kaj saltas al la kodo kiam la funkcio finiĝas
+    let entry_name = CString::new("entry").unwrap();
+    builder.entry = LLVMAppendBasicBlockInContext(builder.context, func, entry_name.as_ptr() as *const _);
+    
+    let mut bb_name = "bb_".to_string();
+    bb_name.push_str(&func_name);
+    let block_name = CString::new(bb_name).unwrap();
+    
+    let func_block = LLVMAppendBasicBlockInContext(builder.context, func, block_name.as_ptr() as *const _);
+    LLVMPositionBuilderAtEnd(builder.builder, func_block);
+}
+
+// Finas la nunan funkcion
+pub unsafe fn llvm_finish_func(builder : &mut Builder) {
+    if builder.func.is_null() {
+        return;
+    }
+    
+    let current = LLVMGetInsertBlock(builder.builder);
+    
+    if LLVMGetBasicBlockTerminator(current).is_null() {
+        llvm_build_ret_void(builder);
+    }
+    
+    let body = LLVMGetNextBasicBlock(builder.entry);
+    LLVMPositionBuilderAtEnd(builder.builder, builder.entry);
+    LLVMBuildBr(builder.builder, body);
+    
+    builder.func = ptr::null_mut();
+    builder.di_scope = ptr::null_mut();
+    LLVMSetCurrentDebugLocation2(builder.builder, ptr::null_mut());
+}
+
+// Revenas sen valoro
+// Ne-malplenaj funkcioj ĉiam revenas kun valoro (vidu la tipkontrolilon), do la fino ne estas atingebla
+unsafe fn llvm_build_ret_void(builder : &mut Builder) {
+    if LLVMGetTypeKind(builder.ret_type) == LLVMTypeKind::LLVMVoidTypeKind {
+        LLVMBuildRetVoid(builder.builder);
+    } else {
+        LLVMBuildUnreachable(builder.builder);
+    }
+}
+
+// Ŝarĝas parametron al loka variablo
+pub unsafe fn llvm_build_ld_arg(builder : &mut Builder, line : &LLirInstr) {
+    let pos = match &line.arg2 {
+        LLirArg::ArgReg(pos) => *pos as u32,
+        _ => return,
+    };
+    
+    let var = llvm_build_ptr(builder, &line.arg1);
+    let param = LLVMGetParam(builder.func, pos);
+    LLVMBuildStore(builder.builder, param, var);
+}
+
 // Konstrui LLVM funkion alvokon
+// Nekonataj funkcioj estas deklaritaj kiel variadaj C funkcioj
+// Nerektaj alvokoj havas la adreson en arg1 anstataŭ nomo
 pub unsafe fn llvm_build_call(builder : &mut Builder, line : &LLirInstr) {
+    let direct = match &line.arg1 {
+        LLirArg::Label(func_name) => match builder.funcs.get(func_name) {
+            Some(f) => Some(*f),
+            None => {
+                let ret_type = llvm_type(builder, &line.data_type);
+                let func = llvm_add_func(builder, func_name, ret_type, &mut Vec::new(), true);
+                builder.funcs.insert(func_name.clone(), func);
+                Some(func)
+            },
+        },
+        
+        _ => None,
+    };
+    
+    let mut param_types : Vec<LLVMTypeRef> = Vec::new();
+    
+    if let Some(func) = direct {
+        let func_type = LLVMGetElementType(LLVMTypeOf(func));
+        let param_count = LLVMCountParamTypes(func_type) as usize;
+        param_types = vec![ptr::null_mut(); param_count];
+        LLVMGetParamTypes(func_type, param_types.as_mut_ptr());
+    }
+    
     let call_args = match &line.arg2 {
-        LLirArg::ArgList(list) => list,
-        _ => return,
+        LLirArg::ArgList(list) => list.clone(),
+        _ => Vec::new(),
     };
     
     let mut args : Vec<LLVMValueRef> = Vec::new();
     
-    for arg in call_args {
-        match &arg {
-            // TODO: This should really be in another function
-            LLirArg::StrLiteral(val) => {
-                let mut str_name : String = "STR".to_string();
-                str_name.push_str(&builder.str_pos.to_string());
-                builder.str_pos += 1;
-                let c_str_name = CString::new(str_name).unwrap();
-                
-                let mut new_str = String::new();
-                let mut skip_next = false;
-                
-                for i in 0 .. val.len() {
-                    if skip_next {
-                        skip_next = false;
-                        continue;
-                    }
-                    
-                    let c = val.chars().nth(i).unwrap();
-                    
-                    if c == '\\' && i + 1 < val.len() {
-                        let c2 = val.chars().nth(i+1).unwrap();
-                        
-                        match c2 {
-                            'n' => new_str.push('\n'),
-                            _ => {
-                                new_str.push(c);
-                                continue;
-                            },
-                        }
-                        
-                        skip_next = true;
-                    } else {
-                        new_str.push(c);
-                    }
-                }
-                
-                let c_str = CString::new(new_str).unwrap();
-                let str_ref = LLVMBuildGlobalString(builder.builder, c_str.as_ptr() as *const _, c_str_name.as_ptr() as *const _);
-                args.push(str_ref);
+    // La variada parto kaj la argumentoj de nerektaj alvokoj jam havas la ĝustajn tipojn
+    for (i, arg) in call_args.iter().enumerate() {
+        let arg_type = match param_types.get(i) {
+            Some(t) => *t,
+            None => match &arg {
+                LLirArg::Reg(pos) if builder.regs.contains_key(pos) => LLVMTypeOf(builder.regs[pos]),
+                LLirArg::StrLiteral(_) => LLVMPointerType(LLVMInt8TypeInContext(builder.context), 0),
+                LLirArg::Float(_) => LLVMDoubleTypeInContext(builder.context),
+                _ => LLVMInt32TypeInContext(builder.context),
             },
-            
-            LLirArg::Mem(name) => {
-                let reg = llvm_build_local_load(builder, name.to_string());
-                args.push(reg);
-            },
-            
-            LLirArg::UInt(val) => {
-                let const_type = LLVMInt32TypeInContext(builder.context);
-                let val = LLVMConstInt(const_type, *val as u64, 0);
-                args.push(val);
-            },
-            
-            _ => {},
-        }
+        };
+        
+        args.push(llvm_build_value(builder, arg, arg_type));
     }
     
-    // La funkio
-    let func_name = match &line.arg1 {
-        LLirArg::Label(val) => val.clone(),
-        _ => String::new(),
+    // La tipo de nerekta alvoko venas de la argumentoj kaj la revena tipo
+    let func = match direct {
+        Some(func) => func,
+        None => {
+            let ret_type = llvm_type(builder, &line.data_type);
+            let mut arg_types : Vec<LLVMTypeRef> = args.iter().map(|a| LLVMTypeOf(*a)).collect();
+            let func_type = LLVMFunctionType(ret_type, arg_types.as_mut_ptr(), arg_types.len() as u32, 0);
+            
+            let i8_ptr = LLVMPointerType(LLVMInt8TypeInContext(builder.context), 0);
+            let addr = llvm_build_value(builder, &line.arg1, i8_ptr);
+            llvm_convert(builder, addr, LLVMPointerType(func_type, 0), false)
+        },
     };
     
-    let func = match &builder.funcs.get(&func_name) {
-        Some(v) => *v.clone(),
+    // Malplenaj valoroj ne povas havi nomon
+    let mut reg_name = CString::new("").unwrap();
+    
+    if let LLirArg::Reg(pos) = &line.arg3 {
+        reg_name = CString::new(pos.to_string()).unwrap();
+    }
+    
+    let value = LLVMBuildCall(builder.builder, func, args.as_mut_ptr(), args.len() as u32, reg_name.as_ptr() as *const _);
+    
+    if let LLirArg::Reg(pos) = &line.arg3 {
+        builder.regs.insert(*pos, value);
+    }
+}
+
+// Konstruas sistemvokon per enlinia asemblero
+// La numero kaj la argumentoj iras en la registrojn de la Linux x86-64 sistemvoko
+pub unsafe fn llvm_build_syscall(builder : &mut Builder, line : &LLirInstr) {
+    let call_args = match &line.arg2 {
+        LLirArg::ArgList(list) => list.clone(),
         _ => return,
     };
     
-    // La alvoko
-    LLVMBuildCall(builder.builder, func, args.as_mut_ptr(), args.len() as u32, func_name.as_ptr() as *const _);
+    let regs = ["rax", "rdi", "rsi", "rdx", "r10", "r8", "r9"];
+    let i64_type = LLVMInt64TypeInContext(builder.context);
+    
+    let mut args : Vec<LLVMValueRef> = Vec::new();
+    let mut arg_types : Vec<LLVMTypeRef> = Vec::new();
+    let mut constraints = "={rax}".to_string();
+    
+    for (i, arg) in call_args.iter().take(regs.len()).enumerate() {
+        args.push(llvm_build_value(builder, arg, i64_type));
+        arg_types.push(i64_type);
+        
+        constraints.push_str(",{");
+        constraints.push_str(regs[i]);
+        constraints.push_str("}");
+    }
+    
+    constraints.push_str(",~{rcx},~{r11},~{memory}");
+    
+    let asm_type = LLVMFunctionType(i64_type, arg_types.as_mut_ptr(), arg_types.len() as u32, 0);
+    let mut asm_str = "syscall".to_string();
+    let asm = LLVMGetInlineAsm(asm_type, asm_str.as_mut_ptr() as *mut _, asm_str.len(),
+                constraints.as_mut_ptr() as *mut _, constraints.len(), 1, 0, LLVMInlineAsmDialect::LLVMInlineAsmDialectATT);
+    
+    let c_name = CString::new("").unwrap();
+    let value = LLVMBuildCall(builder.builder, asm, args.as_mut_ptr(), args.len() as u32, c_name.as_ptr() as *const _);
+    
+    if let LLirArg::Reg(pos) = &line.arg3 {
+        builder.regs.insert(*pos, value);
+    }
+}
+
+// Finas la programon
+// Kun la C biblioteko, exit() skribas la bufrojn de stdout antaŭ ol fini
+pub unsafe fn llvm_build_exit(builder : &mut Builder, line : &LLirInstr) {
+    let name = if builder.use_c { "exit" } else { "sys_exit" };
+    
+    let mut call = llir::create_instr(LLirType::Call);
+    call.arg1 = LLirArg::Label(name.to_string());
+    call.arg2 = LLirArg::ArgList(vec![line.arg1.clone()]);
+    llvm_build_call(builder, &call);
 }
 
 // Konstrui LLVM funkion revenon
-pub fn llvm_build_return(builder : &mut Builder, line : &LLirInstr) {
-    unsafe {
-        match &line.arg1 {
-            LLirArg::None => {LLVMBuildRetVoid(builder.builder);},
-            
-            // TODO: Pli tipdetekton?
-            LLirArg::Int(val) => {
-                let i32_type = LLVMInt32TypeInContext(builder.context);
-                let i32_val = LLVMConstInt(i32_type, *val as u64, 1);
-                
-                LLVMBuildRet(builder.builder, i32_val);
-            },
-            
-            LLirArg::Reg(val) => {
-                let reg = match &builder.regs.get(val) {
-                    Some(v) => *v.clone(),
-                    _ => return,
-                };
-                
-                LLVMBuildRet(builder.builder, reg);
-            },
-            
-            _ => {},
-        }
+pub unsafe fn llvm_build_return(builder : &mut Builder, line : &LLirInstr) {
+    match &line.arg1 {
+        LLirArg::None => llvm_build_ret_void(builder),
+        
+        _ => {
+            let value = llvm_build_value(builder, &line.arg1, builder.ret_type);
+            LLVMBuildRet(builder.builder, value);
+        },
     }
 }
-
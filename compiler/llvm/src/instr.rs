@@ -18,72 +18,360 @@
 extern crate llvm_sys as llvm;
 
 use std::ffi::CString;
+use llvm::LLVMTypeKind;
 use llvm::core::*;
 
-use parser::llir::{LLirInstr, LLirArg};
+use parser::llir::{LLirInstr, LLirArg, LLirDataType};
 use crate::*;
 
-pub unsafe fn llvm_build_local_load(builder : &mut Builder, var_name : String) -> LLVMValueRef {
-    let var = match builder.vars.get(&var_name) {
-        Some(v) => v.clone(),
-        _ => MaybeUninit::uninit().assume_init(),
-    };
-    
+// Redonas nomon por nova registro
+pub fn llvm_reg_name(builder : &mut Builder) -> CString {
     let mut reg_str = "reg".to_string();
     reg_str.push_str(&builder.reg_pos.to_string());
     builder.reg_pos += 1;
     
-    let reg_name = CString::new(reg_str).unwrap();
-    let reg = LLVMBuildLoad(builder.builder, var, reg_name.as_ptr() as *const _);
-    return reg;
+    CString::new(reg_str).unwrap()
 }
 
-pub unsafe fn llvm_build_arith(builder : &mut Builder, line : &LLirInstr) {
-    // TODO: Tipdeketo
-    let op_type = LLVMInt32TypeInContext(builder.context);
+// Kreas konstantan ĉenon kaj redonas montrilon al ĝi
+pub unsafe fn llvm_build_string(builder : &mut Builder, val : &String) -> LLVMValueRef {
+    let mut str_name : String = "STR".to_string();
+    str_name.push_str(&builder.str_pos.to_string());
+    builder.str_pos += 1;
+    let c_str_name = CString::new(str_name).unwrap();
+    
+    let i8_type = LLVMInt8TypeInContext(builder.context);
+    let str_type = LLVMArrayType(i8_type, (val.len() + 1) as u32);
+    let str_val = LLVMConstStringInContext(builder.context, val.as_ptr() as *const _, val.len() as u32, 0);
+    
+    let str_ref = LLVMAddGlobal(builder.module, str_type, c_str_name.as_ptr() as *const _);
+    LLVMSetInitializer(str_ref, str_val);
+    LLVMSetGlobalConstant(str_ref, 1);
+    LLVMSetLinkage(str_ref, llvm::LLVMLinkage::LLVMPrivateLinkage);
+    
+    LLVMConstBitCast(str_ref, LLVMPointerType(i8_type, 0))
+}
+
+pub unsafe fn llvm_type(builder : &mut Builder, data_type : &LLirDataType) -> LLVMTypeRef {
+    match &data_type {
+        LLirDataType::Byte | LLirDataType::UByte => LLVMInt8TypeInContext(builder.context),
+        LLirDataType::Word | LLirDataType::UWord => LLVMInt16TypeInContext(builder.context),
+        LLirDataType::Int | LLirDataType::UInt => LLVMInt32TypeInContext(builder.context),
+        LLirDataType::Int64 | LLirDataType::UInt64 => LLVMInt64TypeInContext(builder.context),
+        LLirDataType::Float => LLVMFloatTypeInContext(builder.context),
+        LLirDataType::Double => LLVMDoubleTypeInContext(builder.context),
+        
+        LLirDataType::Str | LLirDataType::Ptr => {
+            let i8_type = LLVMInt8TypeInContext(builder.context);
+            LLVMPointerType(i8_type, 0)
+        },
+        
+        LLirDataType::Void => LLVMVoidTypeInContext(builder.context),
+    }
+}
+
+pub unsafe fn is_float_type(value_type : LLVMTypeRef) -> bool {
+    match LLVMGetTypeKind(value_type) {
+        LLVMTypeKind::LLVMFloatTypeKind | LLVMTypeKind::LLVMDoubleTypeKind => return true,
+        _ => return false,
+    }
+}
+
+// Kreas konstanton de la donita tipo
+pub unsafe fn llvm_build_const(builder : &mut Builder, arg : &LLirArg, value_type : LLVMTypeRef) -> LLVMValueRef {
+    let (int_val, float_val) = match &arg {
+        LLirArg::Int(val) => (*val as u64, *val as f64),
+        LLirArg::UInt(val) => (*val, *val as f64),
+        LLirArg::Float(val) => (*val as i64 as u64, *val),
+        
+        LLirArg::StrLiteral(val) => {
+            let str_ref = llvm_build_string(builder, val);
+            
+            if LLVMGetTypeKind(value_type) == LLVMTypeKind::LLVMPointerTypeKind {
+                return LLVMConstBitCast(str_ref, value_type);
+            }
+            
+            return LLVMConstPtrToInt(str_ref, value_type);
+        },
+        
+        _ => return LLVMConstNull(value_type),
+    };
+    
+    match LLVMGetTypeKind(value_type) {
+        LLVMTypeKind::LLVMFloatTypeKind | LLVMTypeKind::LLVMDoubleTypeKind => {
+            return LLVMConstReal(value_type, float_val);
+        },
+        
+        LLVMTypeKind::LLVMPointerTypeKind => {
+            let i64_type = LLVMInt64TypeInContext(builder.context);
+            return LLVMConstIntToPtr(LLVMConstInt(i64_type, int_val, 0), value_type);
+        },
+        
+        _ => return LLVMConstInt(value_type, int_val, 1),
+    }
+}
+
+// Redonas la valoron de operando, konvertitan al la donita tipo
+pub unsafe fn llvm_build_value(builder : &mut Builder, arg : &LLirArg, value_type : LLVMTypeRef) -> LLVMValueRef {
+    let value = match &arg {
+        LLirArg::Reg(pos) => match builder.regs.get(pos) {
+            Some(v) => *v,
+            None => return LLVMGetUndef(value_type),
+        },
+        
+        LLirArg::Mem(_) | LLirArg::FieldMem(_, _, _) | LLirArg::ElementMem(_, _, _) => {
+            let ptr = llvm_build_ptr(builder, arg);
+            let reg_name = llvm_reg_name(builder);
+            LLVMBuildLoad(builder.builder, ptr, reg_name.as_ptr() as *const _)
+        },
+        
+        _ => return llvm_build_const(builder, arg, value_type),
+    };
+    
+    llvm_convert(builder, value, value_type, true)
+}
+
+// Konvertas valoron al alia tipo
+pub unsafe fn llvm_convert(builder : &mut Builder, value : LLVMValueRef, to : LLVMTypeRef, signed : bool) -> LLVMValueRef {
+    let from = LLVMTypeOf(value);
+    
+    if from == to {
+        return value;
+    }
+    
+    let name = CString::new("").unwrap();
+    let name = name.as_ptr() as *const _;
+    let b = builder.builder;
+    
+    match (LLVMGetTypeKind(from), LLVMGetTypeKind(to)) {
+        (LLVMTypeKind::LLVMIntegerTypeKind, LLVMTypeKind::LLVMIntegerTypeKind) => {
+            if LLVMGetIntTypeWidth(from) > LLVMGetIntTypeWidth(to) {
+                return LLVMBuildTrunc(b, value, to, name);
+            } else if signed {
+                return LLVMBuildSExt(b, value, to, name);
+            }
+            
+            return LLVMBuildZExt(b, value, to, name);
+        },
+        
+        (LLVMTypeKind::LLVMIntegerTypeKind, LLVMTypeKind::LLVMPointerTypeKind) => return LLVMBuildIntToPtr(b, value, to, name),
+        (LLVMTypeKind::LLVMPointerTypeKind, LLVMTypeKind::LLVMIntegerTypeKind) => return LLVMBuildPtrToInt(b, value, to, name),
+        (LLVMTypeKind::LLVMPointerTypeKind, LLVMTypeKind::LLVMPointerTypeKind) => return LLVMBuildBitCast(b, value, to, name),
+        
+        (LLVMTypeKind::LLVMFloatTypeKind, LLVMTypeKind::LLVMDoubleTypeKind) => return LLVMBuildFPExt(b, value, to, name),
+        (LLVMTypeKind::LLVMDoubleTypeKind, LLVMTypeKind::LLVMFloatTypeKind) => return LLVMBuildFPTrunc(b, value, to, name),
+        
+        (LLVMTypeKind::LLVMIntegerTypeKind, _) if is_float_type(to) && signed => return LLVMBuildSIToFP(b, value, to, name),
+        (LLVMTypeKind::LLVMIntegerTypeKind, _) if is_float_type(to) => return LLVMBuildUIToFP(b, value, to, name),
+        (_, LLVMTypeKind::LLVMIntegerTypeKind) if is_float_type(from) && signed => return LLVMBuildFPToSI(b, value, to, name),
+        (_, LLVMTypeKind::LLVMIntegerTypeKind) if is_float_type(from) => return LLVMBuildFPToUI(b, value, to, name),
+        
+        _ => return value,
+    }
+}
+
+// Redonas montrilon al variablo, kampo, aŭ tabela ero
+pub unsafe fn llvm_build_ptr(builder : &mut Builder, arg : &LLirArg) -> LLVMValueRef {
+    match &arg {
+        LLirArg::Mem(name) => {
+            if let Some(var) = builder.vars.get(name) {
+                return *var;
+            }
+            
+            if let Some(var) = builder.globals.get(name) {
+                return *var;
+            }
+            
+            let i8_ptr = LLVMPointerType(LLVMInt8TypeInContext(builder.context), 0);
+            return LLVMGetUndef(LLVMPointerType(i8_ptr, 0));
+        },
+        
+        LLirArg::FieldMem(_, _, _) => return llvm_build_field_ptr(builder, arg),
+        LLirArg::ElementMem(_, _, _) => return llvm_build_element_ptr(builder, arg),
+        
+        // Funkcioj estas jam montriloj
+        LLirArg::Label(name) if builder.funcs.contains_key(name) => return builder.funcs[name],
+        
+        _ => {
+            let i8_ptr = LLVMPointerType(LLVMInt8TypeInContext(builder.context), 0);
+            return LLVMGetUndef(LLVMPointerType(i8_ptr, 0));
+        },
+    }
+}
+
+// Kalkulas la adreson de rekorda kampo
+pub unsafe fn llvm_build_field_ptr(builder : &mut Builder, arg : &LLirArg) -> LLVMValueRef {
+    let (var_name, offset, data_type) = match &arg {
+        LLirArg::FieldMem(name, offset, data_type) => (name.clone(), *offset, data_type.clone()),
+        _ => return ptr::null_mut(),
+    };
     
-    let lval = match &line.arg2 {
-        LLirArg::Int(val) => LLVMConstInt(op_type, *val as u64, 1),
-        LLirArg::Mem(val) => llvm_build_local_load(builder, val.to_string()),
+    let i8_ptr = LLVMPointerType(LLVMInt8TypeInContext(builder.context), 0);
+    let base = llvm_build_value(builder, &LLirArg::Mem(var_name), i8_ptr);
+    
+    let i32_type = LLVMInt32TypeInContext(builder.context);
+    let mut indices = [LLVMConstInt(i32_type, offset as u64, 0)];
+    
+    let c_name = CString::new("").unwrap();
+    let addr = LLVMBuildGEP(builder.builder, base, indices.as_mut_ptr(), 1, c_name.as_ptr() as *const _);
+    
+    let field_type = llvm_type(builder, &data_type);
+    let field_ptr = LLVMPointerType(field_type, 0);
+    LLVMBuildBitCast(builder.builder, addr, field_ptr, c_name.as_ptr() as *const _)
+}
+
+// Kalkulas la adreson de tabela ero
+// La tabela variablo tenas montrilon al la eroj
+pub unsafe fn llvm_build_element_ptr(builder : &mut Builder, arg : &LLirArg) -> LLVMValueRef {
+    let (var_name, index, data_type) = match &arg {
+        LLirArg::ElementMem(name, index, data_type) => (name.clone(), index.clone(), data_type.clone()),
+        _ => return ptr::null_mut(),
+    };
+    
+    let elem_type = llvm_type(builder, &data_type);
+    let elem_ptr = LLVMPointerType(elem_type, 0);
+    let base = llvm_build_value(builder, &LLirArg::Mem(var_name), elem_ptr);
+    
+    let i64_type = LLVMInt64TypeInContext(builder.context);
+    let mut indices = [llvm_build_value(builder, &index, i64_type)];
+    
+    let c_name = CString::new("").unwrap();
+    LLVMBuildGEP(builder.builder, base, indices.as_mut_ptr(), 1, c_name.as_ptr() as *const _)
+}
+
+// Kreas lokan variablon en la enira bloko
+// Tiel, variabloj en bukloj ne kreskigas la stakon
+pub unsafe fn llvm_build_alloca(builder : &mut Builder, var_type : LLVMTypeRef, name : &String) -> LLVMValueRef {
+    let current = LLVMGetInsertBlock(builder.builder);
+    LLVMPositionBuilderAtEnd(builder.builder, builder.entry);
+    
+    let c_name = CString::new(name.clone()).unwrap();
+    let var = LLVMBuildAlloca(builder.builder, var_type, c_name.as_ptr() as *const _);
+    
+    LLVMPositionBuilderAtEnd(builder.builder, current);
+    var
+}
+
+// Konvertas valoron al la tipo de la instrukcio
+pub unsafe fn llvm_build_convert(builder : &mut Builder, line : &LLirInstr) {
+    let dest_pos = match &line.arg1 {
+        LLirArg::Reg(pos) => *pos,
         _ => return,
     };
     
-    let rval = match &line.arg3 {
-        LLirArg::Int(val) => LLVMConstInt(op_type, *val as u64, 1),
-        LLirArg::Mem(val) => llvm_build_local_load(builder, val.to_string()),
+    let signed = line.instr_type != LLirType::MovZX;
+    let to = llvm_type(builder, &line.data_type);
+    
+    let value = match &line.arg2 {
+        LLirArg::Reg(pos) => match builder.regs.get(pos) {
+            Some(v) => *v,
+            None => return,
+        },
+        
+        _ => llvm_build_value(builder, &line.arg2, to),
+    };
+    
+    let dest = llvm_convert(builder, value, to, signed);
+    builder.regs.insert(dest_pos, dest);
+}
+
+// Ŝarĝas konstanton al registro
+pub unsafe fn llvm_build_li(builder : &mut Builder, line : &LLirInstr) {
+    let dest_pos = match &line.arg1 {
+        LLirArg::Reg(pos) => *pos,
         _ => return,
     };
     
+    let value_type = llvm_type(builder, &line.data_type);
+    let value = llvm_build_value(builder, &line.arg2, value_type);
+    builder.regs.insert(dest_pos, value);
+}
+
+pub unsafe fn llvm_build_arith(builder : &mut Builder, line : &LLirInstr) {
+    let op_type = llvm_type(builder, &line.data_type);
+    
+    let lval = llvm_build_value(builder, &line.arg2, op_type);
+    let rval = llvm_build_value(builder, &line.arg3, op_type);
+    
     let dest_pos = match &line.arg1 {
         LLirArg::Reg(pos) => *pos,
         _ => 0,
     };
     
     let c_dest_name = CString::new(dest_pos.to_string()).unwrap();
+    let name = c_dest_name.as_ptr() as *const _;
+    let b = builder.builder;
     
     let dest : LLVMValueRef = match &line.instr_type {
-        LLirType::Add => LLVMBuildAdd(builder.builder, lval, rval, c_dest_name.as_ptr() as *const _),
-        LLirType::Sub => LLVMBuildSub(builder.builder, lval, rval, c_dest_name.as_ptr() as *const _),
+        LLirType::Add => LLVMBuildAdd(b, lval, rval, name),
+        LLirType::Sub => LLVMBuildSub(b, lval, rval, name),
         
-        LLirType::Mul | LLirType::UMul
-            => LLVMBuildMul(builder.builder, lval, rval, c_dest_name.as_ptr() as *const _),
-            
-        LLirType::Div => LLVMBuildSDiv(builder.builder, lval, rval, c_dest_name.as_ptr() as *const _),
-        LLirType::UDiv => LLVMBuildUDiv(builder.builder, lval, rval, c_dest_name.as_ptr() as *const _),
+        LLirType::Mul | LLirType::UMul => LLVMBuildMul(b, lval, rval, name),
+        
+        LLirType::Div => LLVMBuildSDiv(b, lval, rval, name),
+        LLirType::UDiv => LLVMBuildUDiv(b, lval, rval, name),
         
-        LLirType::Rem => LLVMBuildSRem(builder.builder, lval, rval, c_dest_name.as_ptr() as *const _),
-        LLirType::URem => LLVMBuildURem(builder.builder, lval, rval, c_dest_name.as_ptr() as *const _),
+        LLirType::Rem => LLVMBuildSRem(b, lval, rval, name),
+        LLirType::URem => LLVMBuildURem(b, lval, rval, name),
         
-        LLirType::And => LLVMBuildAnd(builder.builder, lval, rval, c_dest_name.as_ptr() as *const _),
-        LLirType::Or => LLVMBuildOr(builder.builder, lval, rval, c_dest_name.as_ptr() as *const _),
-        LLirType::Xor => LLVMBuildXor(builder.builder, lval, rval, c_dest_name.as_ptr() as *const _),
+        LLirType::And => LLVMBuildAnd(b, lval, rval, name),
+        LLirType::Or => LLVMBuildOr(b, lval, rval, name),
+        LLirType::Xor => LLVMBuildXor(b, lval, rval, name),
         
-        LLirType::Lsh => LLVMBuildShl(builder.builder, lval, rval, c_dest_name.as_ptr() as *const _),
-        LLirType::Rsh => LLVMBuildLShr(builder.builder, lval, rval, c_dest_name.as_ptr() as *const _),
+        LLirType::Lsh => LLVMBuildShl(b, lval, rval, name),
+        LLirType::Rsh => LLVMBuildLShr(b, lval, rval, name),
         
-        _ => MaybeUninit::uninit().assume_init(),
+        LLirType::AddF32 | LLirType::AddF64 => LLVMBuildFAdd(b, lval, rval, name),
+        LLirType::SubF32 | LLirType::SubF64 => LLVMBuildFSub(b, lval, rval, name),
+        LLirType::MulF32 | LLirType::MulF64 => LLVMBuildFMul(b, lval, rval, name),
+        LLirType::DivF32 | LLirType::DivF64 => LLVMBuildFDiv(b, lval, rval, name),
+        
+        _ => return,
     };
     
     builder.regs.insert(dest_pos, dest);
 }
 
+// Redonas montrilon al ok eroj
+pub unsafe fn llvm_build_vector_ptr(builder : &mut Builder, arg : &LLirArg, vector_type : LLVMTypeRef) -> LLVMValueRef {
+    let vector_ptr = LLVMPointerType(vector_type, 0);
+    
+    match &arg {
+        LLirArg::Mem(_) => return llvm_build_value(builder, arg, vector_ptr),
+        
+        _ => {
+            let ptr = llvm_build_ptr(builder, arg);
+            return llvm_convert(builder, ptr, vector_ptr, false);
+        },
+    }
+}
+
+// Adicias ok erojn samtempe
+// La tabeloj eble ne estas vicigitaj, do ni uzas la vicigon de unu ero
+pub unsafe fn llvm_build_vector_add(builder : &mut Builder, line : &LLirInstr) {
+    let elem_type = llvm_type(builder, &line.data_type);
+    let vector_type = LLVMVectorType(elem_type, 8);
+    let align = LLVMABISizeOfType(LLVMGetModuleDataLayout(builder.module), elem_type) as u32;
+    
+    let dest = llvm_build_vector_ptr(builder, &line.arg1, vector_type);
+    let lptr = llvm_build_vector_ptr(builder, &line.arg2, vector_type);
+    let rptr = llvm_build_vector_ptr(builder, &line.arg3, vector_type);
+    
+    let c_name = CString::new("").unwrap();
+    let name = c_name.as_ptr() as *const _;
+    
+    let lval = LLVMBuildLoad(builder.builder, lptr, name);
+    LLVMSetAlignment(lval, align);
+    
+    let rval = LLVMBuildLoad(builder.builder, rptr, name);
+    LLVMSetAlignment(rval, align);
+    
+    let result = if is_float_type(elem_type) {
+        LLVMBuildFAdd(builder.builder, lval, rval, name)
+    } else {
+        LLVMBuildAdd(builder.builder, lval, rval, name)
+    };
+    
+    let store = LLVMBuildStore(builder.builder, result, dest);
+    LLVMSetAlignment(store, align);
+}
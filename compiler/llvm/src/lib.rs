@@ -17,25 +17,51 @@
 
 extern crate llvm_sys as llvm;
 
-use std::io;
-use std::mem::MaybeUninit;
-use std::ffi::CString;
+use std::ptr;
+use std::ffi::{CStr, CString};
 use std::collections::HashMap;
 use std::os::raw::c_char;
+use std::sync::Once;
 
 use llvm::prelude::*;
 use llvm::core::*;
+use llvm::analysis::*;
+use llvm::bit_writer::*;
 use llvm::target::*;
 use llvm::target_machine::*;
 use llvm::support::*;
 
-use parser::llir::{LLirFile, LLirInstr, LLirType, LLirArg};
+use parser::llir::{LLirFile, LLirInstr, LLirType, LLirArg, LLirDataType};
 
+mod debug;
+mod flow;
 mod func;
 mod instr;
+mod opt;
 
+use crate::debug::*;
+use crate::flow::*;
 use crate::func::*;
 use crate::instr::*;
+use crate::opt::*;
+
+// The output formats
+#[derive(Clone, Copy, PartialEq)]
+pub enum Emit {
+    LlvmIr,
+    LlvmBc,
+    Asm,
+    Obj,
+}
+
+pub struct Options {
+    pub emit : Emit,
+    pub output : String,
+    pub opt_level : u32,
+    pub use_c : bool,
+    pub debug_info : bool,
+    pub debug : bool,
+}
 
 pub struct Builder {
     context : LLVMContextRef,
@@ -43,13 +69,30 @@ pub struct Builder {
     builder : LLVMBuilderRef,
     
     funcs : HashMap<String, LLVMValueRef>,
+    globals : HashMap<String, LLVMValueRef>,
     vars : HashMap<String, LLVMValueRef>,
     regs : HashMap<i32, LLVMValueRef>,
+    blocks : HashMap<String, LLVMBasicBlockRef>,
+    
+    // La nuna funkcio; la eniro bloko tenas ĉiujn alloca instrukciojn
+    func : LLVMValueRef,
+    ret_type : LLVMTypeRef,
+    entry : LLVMBasicBlockRef,
     
     reg_pos : i32,
     str_pos : i32,
+    use_c : bool,
+    
+    // La informoj por la erarserĉilo; nulaj sen -g
+    di_builder : LLVMDIBuilderRef,
+    di_file : LLVMMetadataRef,
+    di_scope : LLVMMetadataRef,
+    line_no : i32,
 }
 
+// The options can only be parsed once, and the driver may compile several modules
+static INIT_OPTIONS : Once = Once::new();
+
 // I HATE HATE HATE the AT&T syntax
 unsafe fn use_intel_syntax() {
     let mut argv : Vec<String> = Vec::new();
@@ -59,21 +102,21 @@ unsafe fn use_intel_syntax() {
     let cstr_argv: Vec<_> = argv.iter()
             .map(|arg| CString::new(arg.as_str()).unwrap())
             .collect();
-
+    
     let mut p_argv: Vec<_> = cstr_argv.iter()
             .map(|arg| arg.as_ptr())
             .collect();
-
+    
     p_argv.push(std::ptr::null());
-
+    
     let p: *const *const c_char = p_argv.as_ptr();
     
     LLVMParseCommandLineOptions(2, p, 0 as *const _);
 }
 
-pub fn compile(llir_file : &LLirFile, llvm_debug : bool) -> io::Result<()> {
+pub fn compile(llir_file : &LLirFile, options : &Options) -> Result<(), String> {
     unsafe {
-        use_intel_syntax();
+        INIT_OPTIONS.call_once(|| use_intel_syntax());
         
         let c_name = CString::new(llir_file.name.clone()).unwrap();
         
@@ -87,105 +130,172 @@ pub fn compile(llir_file : &LLirFile, llvm_debug : bool) -> io::Result<()> {
             module : module,
             builder : builder,
             funcs : HashMap::new(),
+            globals : HashMap::new(),
             vars : HashMap::new(),
             regs : HashMap::new(),
+            blocks : HashMap::new(),
+            func : ptr::null_mut(),
+            ret_type : ptr::null_mut(),
+            entry : ptr::null_mut(),
             reg_pos : 0,
             str_pos : 0,
+            use_c : options.use_c,
+            di_builder : ptr::null_mut(),
+            di_file : ptr::null_mut(),
+            di_scope : ptr::null_mut(),
+            line_no : 0,
         };
-        write_code(&mut builder_struct, &llir_file.code);
-        
-        // Create a function
-        /*let i32t = LLVMInt32TypeInContext(context);
         
-        let mut args = [i32t, i32t, i32t];
-        let function_type = LLVMFunctionType(i32t, args.as_mut_ptr(), args.len() as u32, 0);
-        let function = LLVMAddFunction(module, b"main\0".as_ptr() as *const _, function_type);
-        
-        // Create the block
-        let block = LLVMAppendBasicBlockInContext(context, function, b"entry\0".as_ptr() as *const _);
-        LLVMPositionBuilderAtEnd(builder, block);
-        
-        // Load arguments
-        let x = LLVMGetParam(function, 0);
-        let y = LLVMGetParam(function, 1);
-        let z = LLVMGetParam(function, 2);
-        
-        let sum = LLVMBuildAdd(builder, x, y, b"sum.1\0".as_ptr() as *const _);
-        let sum = LLVMBuildAdd(builder, sum, z, b"sum.2\0".as_ptr() as *const _);
+        if options.debug_info {
+            llvm_debug_init(&mut builder_struct, &llir_file.source);
+        }
         
-        LLVMBuildRet(builder, sum);*/
+        llvm_declare_funcs(&mut builder_struct, &llir_file.code);
+        write_code(&mut builder_struct, &llir_file.code);
+        llvm_debug_finish(&mut builder_struct);
+        LLVMDisposeBuilder(builder);
         
         // Dump module
-        if llvm_debug {
+        if options.debug {
             LLVMDumpModule(module);
         }
         
-        // Setup the machine
-        LLVM_InitializeAllTargetInfos();
-        LLVM_InitializeAllTargets();
-        LLVM_InitializeAllTargetMCs();
-        LLVM_InitializeAllAsmParsers();
-        LLVM_InitializeAllAsmPrinters();
+        let result = emit_module(module, options);
+        LLVMContextDispose(context);
         
-        let triple = LLVMGetDefaultTargetTriple();
+        result
+    }
+}
 
-        let mut target : LLVMTargetRef = MaybeUninit::uninit().assume_init();
-        let mut err = MaybeUninit::uninit().assume_init();
-        LLVMGetTargetFromTriple(triple, &mut target, &mut err);
-        
-        let cpu = LLVMGetHostCPUName();
-        let features = LLVMGetHostCPUFeatures();
-        let opt = LLVMCodeGenOptLevel::LLVMCodeGenLevelNone;
-        let reloc = LLVMRelocMode::LLVMRelocDefault;
-        let code = LLVMCodeModel::LLVMCodeModelDefault;
-        
-        let machine = LLVMCreateTargetMachine(target, triple, cpu, features, opt, reloc, code);
-        
-        // Generate the assembly
-        let mut asm_path = "/tmp/".to_string();
-        asm_path.push_str(&llir_file.name);
-        asm_path.push_str(".asm");
-        
-        let c_asm_path = CString::new(asm_path).unwrap();
-        
-        LLVMTargetMachineEmitToFile(machine, module, c_asm_path.as_ptr() as *mut _, LLVMCodeGenFileType::LLVMAssemblyFile, &mut err);
+// Checks, optimizes, and writes the module in the requested format
+unsafe fn emit_module(module : LLVMModuleRef, options : &Options) -> Result<(), String> {
+    let mut err : *mut c_char = ptr::null_mut();
+    
+    // Catch bad code from us before LLVM crashes on it
+    if LLVMVerifyModule(module, LLVMVerifierFailureAction::LLVMReturnStatusAction, &mut err) != 0 {
+        let msg = take_message(&mut err);
+        return Err(format!("LLVM module verification failed:\n{}", msg.trim_end()));
+    }
+    
+    take_message(&mut err);
+    
+    // Setup the machine
+    LLVM_InitializeAllTargetInfos();
+    LLVM_InitializeAllTargets();
+    LLVM_InitializeAllTargetMCs();
+    LLVM_InitializeAllAsmParsers();
+    LLVM_InitializeAllAsmPrinters();
+    
+    let triple = LLVMGetDefaultTargetTriple();
+    
+    let mut target : LLVMTargetRef = ptr::null_mut();
+    
+    if LLVMGetTargetFromTriple(triple, &mut target, &mut err) != 0 {
+        LLVMDisposeMessage(triple);
+        return Err(take_message(&mut err));
+    }
+    
+    let cpu = LLVMGetHostCPUName();
+    let features = LLVMGetHostCPUFeatures();
+    let opt = codegen_level(options.opt_level);
+    let reloc = LLVMRelocMode::LLVMRelocDefault;
+    let code = LLVMCodeModel::LLVMCodeModelDefault;
+    
+    let machine = LLVMCreateTargetMachine(target, triple, cpu, features, opt, reloc, code);
+    LLVMSetTarget(module, triple);
+    
+    LLVMDisposeMessage(cpu);
+    LLVMDisposeMessage(features);
+    LLVMDisposeMessage(triple);
+    
+    let mut result = llvm_optimize(module, machine, options.opt_level);
+    
+    if result.is_ok() {
+        let c_path = CString::new(options.output.clone()).unwrap();
         
-        /*let err_str = CStr::from_ptr(err).to_string_lossy().into_owned();
-        println!("{:?}", err_str);*/
+        let failed = match options.emit {
+            Emit::LlvmIr => LLVMPrintModuleToFile(module, c_path.as_ptr(), &mut err) != 0,
+            Emit::LlvmBc => LLVMWriteBitcodeToFile(module, c_path.as_ptr()) != 0,
+            
+            Emit::Asm => LLVMTargetMachineEmitToFile(machine, module, c_path.as_ptr() as *mut _,
+                            LLVMCodeGenFileType::LLVMAssemblyFile, &mut err) != 0,
+            
+            Emit::Obj => LLVMTargetMachineEmitToFile(machine, module, c_path.as_ptr() as *mut _,
+                            LLVMCodeGenFileType::LLVMObjectFile, &mut err) != 0,
+        };
         
-        LLVMDisposeMessage(cpu);
-        LLVMDisposeMessage(features);
-        LLVMDisposeMessage(triple);
-        LLVMDisposeTargetMachine(machine);
+        let msg = take_message(&mut err);
         
-        // Clean up
-        //LLVMDumpModule(module);
-        LLVMDisposeBuilder(builder);
-        LLVMContextDispose(context);
+        if failed {
+            result = Err(format!("Unable to write {}: {}", options.output, msg));
+        }
     }
     
-    Ok(())
+    LLVMDisposeTargetMachine(machine);
+    result
+}
+
+// Converts an LLVM message to a string and frees it
+// The pointer is cleared, since LLVM does not always set it
+unsafe fn take_message(msg : &mut *mut c_char) -> String {
+    if msg.is_null() {
+        return String::new();
+    }
+    
+    let msg_str = CStr::from_ptr(*msg).to_string_lossy().into_owned();
+    LLVMDisposeMessage(*msg);
+    *msg = ptr::null_mut();
+    msg_str
 }
 
 pub unsafe fn write_code(builder : &mut Builder, code : &Vec<LLirInstr>) {
     for ln in code {
         match ln.instr_type {
-            LLirType::Extern => llvm_build_func(builder, ln, true),
-            LLirType::Func => llvm_build_func(builder, ln, false),
+            LLirType::Global | LLirType::ExternGlobal | LLirType::Table
+            | LLirType::Extern | LLirType::Func
+            | LLirType::Label => {},
+            
+            // Kodo post salto aŭ reveno bezonas novan blokon
+            _ => llvm_check_block(builder),
+        }
+        
+        // La funkcio ŝanĝas la linion mem, post kiam ĝi finas la antaŭan funkcion
+        if ln.instr_type != LLirType::Func {
+            llvm_debug_loc(builder, ln.line_no);
+        }
+        
+        match ln.instr_type {
+            LLirType::Global => llvm_build_global(builder, ln, false),
+            LLirType::ExternGlobal => llvm_build_global(builder, ln, true),
+            LLirType::Table => llvm_build_table(builder, ln),
+            LLirType::Extern => {},
+            LLirType::Func => llvm_build_func(builder, ln),
             LLirType::Call => llvm_build_call(builder, ln),
+            LLirType::Syscall => llvm_build_syscall(builder, ln),
             LLirType::Ret => llvm_build_return(builder, ln),
+            LLirType::Exit => llvm_build_exit(builder, ln),
             
             LLirType::Add | LLirType::Sub
             | LLirType::Mul | LLirType::UMul
             | LLirType::Div | LLirType::UDiv
             | LLirType::Rem | LLirType::URem
             | LLirType::And | LLirType::Or | LLirType::Xor
-            | LLirType::Lsh | LLirType::Rsh => llvm_build_arith(builder, ln),
+            | LLirType::Lsh | LLirType::Rsh
+            | LLirType::AddF32 | LLirType::AddF64
+            | LLirType::SubF32 | LLirType::SubF64
+            | LLirType::MulF32 | LLirType::MulF64
+            | LLirType::DivF32 | LLirType::DivF64 => llvm_build_arith(builder, ln),
+            
+            LLirType::VAddDW => llvm_build_vector_add(builder, ln),
+            
+            LLirType::Mov | LLirType::MovSX | LLirType::MovZX => llvm_build_convert(builder, ln),
+            LLirType::Li => llvm_build_li(builder, ln),
             
-            LLirType::AllocArr
-            | LLirType::AllocB | LLirType::AllocW
+            LLirType::AllocB | LLirType::AllocW
             | LLirType::AllocDW | LLirType::AllocQW
             | LLirType::AllocF32 | LLirType::AllocF64 => llvm_build_alloc(builder, ln),
+            LLirType::AllocArr => llvm_build_array_alloc(builder, ln),
+            LLirType::AllocRec => llvm_build_record_alloc(builder, ln),
             
             LLirType::LdB | LLirType::UldB
             | LLirType::LdW | LLirType::UldW
@@ -201,89 +311,222 @@ pub unsafe fn write_code(builder : &mut Builder, code : &Vec<LLirInstr>) {
             | LLirType::StrF32
             | LLirType::StrF64 => llvm_build_store(builder, ln),
             
-            _ => {},
+            LLirType::LdAddr => llvm_build_ld_addr(builder, ln),
+            
+            LLirType::LdArgB | LLirType::ULdArgB
+            | LLirType::LdArgW | LLirType::ULdArgW
+            | LLirType::LdArgDW | LLirType::ULdArgDW
+            | LLirType::LdArgQW | LLirType::ULdArgQW
+            | LLirType::LdArgF32
+            | LLirType::LdArgF64 => llvm_build_ld_arg(builder, ln),
+            
+            LLirType::Label => llvm_build_label(builder, ln),
+            LLirType::Jmp => llvm_build_jmp(builder, ln),
+            LLirType::JmpTable => llvm_build_jmp_table(builder, ln),
+            
+            LLirType::Nop | LLirType::PushArg | LLirType::KPushArg => {},
+            
+            // Ĉiuj aliaj estas komparoj
+            _ => llvm_build_cmp(builder, ln),
         }
     }
+    
+    llvm_finish_func(builder);
 }
 
-// Konstruas alloc instrukcion
-pub unsafe fn llvm_build_alloc(builder : &mut Builder, line : &LLirInstr) {
-    let var_type : LLVMTypeRef;
+// Konstruas mallokan variablon
+// Eksterja variablo havas neniun komencan valoron; alia modulo posedas ĝin
+pub unsafe fn llvm_build_global(builder : &mut Builder, line : &LLirInstr, is_extern : bool) {
+    let name = match &line.arg1 {
+        LLirArg::Label(name) => name.clone(),
+        _ => return,
+    };
     
-    match &line.instr_type {
-        LLirType::AllocB => var_type = LLVMInt8TypeInContext(builder.context),
-        LLirType::AllocW => var_type = LLVMInt16TypeInContext(builder.context),
-        LLirType::AllocDW => var_type = LLVMInt32TypeInContext(builder.context),
-        LLirType::AllocQW => var_type = LLVMInt64TypeInContext(builder.context),
-        
+    let var_type = llvm_type(builder, &line.data_type);
+    let c_name = CString::new(name.clone()).unwrap();
+    let var = LLVMAddGlobal(builder.module, var_type, c_name.as_ptr() as *const _);
+    
+    if !is_extern {
+        let value = llvm_build_const(builder, &line.arg2, var_type);
+        LLVMSetInitializer(var, value);
+    }
+    
+    builder.globals.insert(name, var);
+}
+
+// Konstruas konstantan tabelon
+// La eroj estas en privata konstanto; la variablo montras al ili, same kiel lokaj tabeloj
+pub unsafe fn llvm_build_table(builder : &mut Builder, line : &LLirInstr) {
+    let name = match &line.arg1 {
+        LLirArg::Label(name) => name.clone(),
         _ => return,
+    };
+    
+    let values = match &line.arg2 {
+        LLirArg::ArgList(values) => values.clone(),
+        _ => return,
+    };
+    
+    let elem_type = llvm_type(builder, &line.data_type);
+    let mut elements : Vec<LLVMValueRef> = Vec::new();
+    
+    for value in values.iter() {
+        elements.push(llvm_build_const(builder, value, elem_type));
     }
     
+    let count = elements.len() as u32;
+    let arr_type = LLVMArrayType(elem_type, count);
+    
+    let mut data_name = name.clone();
+    data_name.push_str(".data");
+    
+    let c_data_name = CString::new(data_name).unwrap();
+    let data = LLVMAddGlobal(builder.module, arr_type, c_data_name.as_ptr() as *const _);
+    LLVMSetInitializer(data, LLVMConstArray(elem_type, elements.as_mut_ptr(), count));
+    LLVMSetGlobalConstant(data, 1);
+    LLVMSetLinkage(data, llvm::LLVMLinkage::LLVMPrivateLinkage);
+    
+    // La montrilo al la eroj
+    let i8_ptr = LLVMPointerType(LLVMInt8TypeInContext(builder.context), 0);
+    let c_name = CString::new(name.clone()).unwrap();
+    let var = LLVMAddGlobal(builder.module, i8_ptr, c_name.as_ptr() as *const _);
+    LLVMSetInitializer(var, LLVMConstBitCast(data, i8_ptr));
+    LLVMSetGlobalConstant(var, 1);
+    LLVMSetLinkage(var, llvm::LLVMLinkage::LLVMInternalLinkage);
+    
+    // La grandeco
+    let i32_type = LLVMInt32TypeInContext(builder.context);
+    let mut size_name = name.clone();
+    size_name.push_str(".size");
+    
+    let c_size_name = CString::new(size_name.clone()).unwrap();
+    let size = LLVMAddGlobal(builder.module, i32_type, c_size_name.as_ptr() as *const _);
+    LLVMSetInitializer(size, LLVMConstInt(i32_type, count as u64, 0));
+    LLVMSetGlobalConstant(size, 1);
+    LLVMSetLinkage(size, llvm::LLVMLinkage::LLVMInternalLinkage);
+    
+    builder.globals.insert(name, var);
+    builder.globals.insert(size_name, size);
+}
+
+// Konstruas alloc instrukcion
+pub unsafe fn llvm_build_alloc(builder : &mut Builder, line : &LLirInstr) {
     let name = match &line.arg1 {
         LLirArg::Label(name) => name.clone(),
         _ => String::new(),
     };
     
-    let c_str = CString::new(name.clone()).unwrap();
-    let var = LLVMBuildAlloca(builder.builder, var_type, c_str.as_ptr() as *const _);
+    let var_type = llvm_type(builder, &line.data_type);
+    let var = llvm_build_alloca(builder, var_type, &name);
+    llvm_debug_var(builder, var, &name, &line.data_type, false);
     
     builder.vars.insert(name, var);
 }
 
-// Konstruas ŝarĝo instrukcion
-pub unsafe fn llvm_build_load(builder : &mut Builder, line : &LLirInstr) {
-    let name = match &line.arg2 {
-        LLirArg::Mem(name) => name.clone(),
-        _ => String::new(),
+// Konstruas tabelon sur la stako
+// La variablo estas montrilo al la eroj, same kiel la rekordoj
+pub unsafe fn llvm_build_array_alloc(builder : &mut Builder, line : &LLirInstr) {
+    let name = match &line.arg1 {
+        LLirArg::Label(name) => name.clone(),
+        _ => return,
+    };
+    
+    let elem_type = llvm_type(builder, &line.data_type);
+    let i8_ptr = LLVMPointerType(LLVMInt8TypeInContext(builder.context), 0);
+    
+    let mut data_name = name.clone();
+    data_name.push_str(".data");
+    
+    // Konstantaj grandecoj iras en la eniran blokon; aliaj estas kreitaj ĉi tie
+    let data = match &line.arg2 {
+        LLirArg::Int(size) => {
+            let arr_type = LLVMArrayType(elem_type, *size as u32);
+            llvm_build_alloca(builder, arr_type, &data_name)
+        },
+        
+        _ => {
+            let i32_type = LLVMInt32TypeInContext(builder.context);
+            let size = llvm_build_value(builder, &line.arg2, i32_type);
+            
+            let c_data_name = CString::new(data_name).unwrap();
+            LLVMBuildArrayAlloca(builder.builder, elem_type, size, c_data_name.as_ptr() as *const _)
+        },
+    };
+    
+    let var = llvm_build_alloca(builder, i8_ptr, &name);
+    llvm_debug_var(builder, var, &name, &line.data_type, true);
+    
+    let c_addr_name = CString::new("").unwrap();
+    let addr = LLVMBuildBitCast(builder.builder, data, i8_ptr, c_addr_name.as_ptr() as *const _);
+    LLVMBuildStore(builder.builder, addr, var);
+    
+    builder.vars.insert(name, var);
+}
+
+// Konstruas rekordon sur la stako
+// La variablo estas montrilo al la memoro de la rekordo, same kiel en LTAC
+pub unsafe fn llvm_build_record_alloc(builder : &mut Builder, line : &LLirInstr) {
+    let name = match &line.arg1 {
+        LLirArg::Label(name) => name.clone(),
+        _ => return,
     };
     
-    let var = match &builder.vars.get(&name) {
-        Some(v) => *v.clone(),
+    let size = match &line.arg2 {
+        LLirArg::Int(size) => *size as u32,
         _ => return,
     };
     
-    // The register to load to
+    let i8_type = LLVMInt8TypeInContext(builder.context);
+    let i8_ptr = LLVMPointerType(i8_type, 0);
+    let rec_type = LLVMArrayType(i8_type, size);
+    
+    let mut data_name = name.clone();
+    data_name.push_str(".data");
+    let data = llvm_build_alloca(builder, rec_type, &data_name);
+    
+    let var = llvm_build_alloca(builder, i8_ptr, &name);
+    llvm_debug_var(builder, var, &name, &LLirDataType::Ptr, false);
+    
+    let c_addr_name = CString::new("").unwrap();
+    let addr = LLVMBuildBitCast(builder.builder, data, i8_ptr, c_addr_name.as_ptr() as *const _);
+    LLVMBuildStore(builder.builder, addr, var);
+    
+    builder.vars.insert(name, var);
+}
+
+// Konstruas ŝarĝo instrukcion
+pub unsafe fn llvm_build_load(builder : &mut Builder, line : &LLirInstr) {
     let reg_no = match &line.arg1 {
         LLirArg::Reg(val) => *val,
-        _ => -1,
+        _ => return,
     };
     
-    let c_str = CString::new(reg_no.to_string()).unwrap();
-    let reg = LLVMBuildLoad(builder.builder, var, c_str.as_ptr() as *const _);
+    let data_type = llvm_type(builder, &line.data_type);
+    let reg = llvm_build_value(builder, &line.arg2, data_type);
     
     builder.regs.insert(reg_no, reg);
 }
 
 // Konstruas vendejo instrukcion
+// La valoro prenas la tipon de la memoro
 pub unsafe fn llvm_build_store(builder : &mut Builder, line : &LLirInstr) {
-    let val = match &line.arg2 {
-        LLirArg::Int(val) if line.instr_type == LLirType::StrDW => {
-            let i32_t = LLVMInt32TypeInContext(builder.context);
-            LLVMConstInt(i32_t, *val as u64, 1)
-        },
-        
-        LLirArg::Reg(pos) => {
-            let var = match &builder.regs.get(pos) {
-                Some(v) => *v.clone(),
-                _ => return,
-            };
-            
-            var
-        },
-        
-        _ => return,
-    };
-    
-    let name = match &line.arg1 {
-        LLirArg::Mem(name) => name.clone(),
-        _ => String::new(),
-    };
+    let var = llvm_build_ptr(builder, &line.arg1);
+    let var_type = LLVMGetElementType(LLVMTypeOf(var));
+    let val = llvm_build_value(builder, &line.arg2, var_type);
     
-    let var = match &builder.vars.get(&name) {
-        Some(v) => *v.clone(),
+    LLVMBuildStore(builder.builder, val, var);
+}
+
+// Ŝarĝas la adreson de variablo
+pub unsafe fn llvm_build_ld_addr(builder : &mut Builder, line : &LLirInstr) {
+    let reg_no = match &line.arg1 {
+        LLirArg::Reg(val) => *val,
         _ => return,
     };
     
-    LLVMBuildStore(builder.builder, val, var as LLVMValueRef);
+    let var = llvm_build_ptr(builder, &line.arg2);
+    let data_type = llvm_type(builder, &line.data_type);
+    let reg = llvm_convert(builder, var, data_type, false);
+    
+    builder.regs.insert(reg_no, reg);
 }
-
@@ -0,0 +1,70 @@
+
+// This file is part of the Lila compiler
+// Copyright (C) 2020-2021 Patrick Flynn
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; version 2.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, write to the Free Software Foundation, Inc.,
+// 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+
+extern crate llvm_sys as llvm;
+
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+
+use llvm::prelude::*;
+use llvm::error::*;
+use llvm::target_machine::*;
+
+// La nova "pass manager" aperis en la C API de LLVM 13, do llvm-sys 110 ankoraŭ ne havas ĝin
+#[allow(non_camel_case_types)]
+pub enum LLVMOpaquePassBuilderOptions {}
+
+type LLVMPassBuilderOptionsRef = *mut LLVMOpaquePassBuilderOptions;
+
+extern "C" {
+    fn LLVMRunPasses(M : LLVMModuleRef, Passes : *const c_char, TM : LLVMTargetMachineRef, Options : LLVMPassBuilderOptionsRef) -> LLVMErrorRef;
+    fn LLVMCreatePassBuilderOptions() -> LLVMPassBuilderOptionsRef;
+    fn LLVMDisposePassBuilderOptions(Options : LLVMPassBuilderOptionsRef);
+}
+
+// Redonas la nivelon por la kodgenerilo
+pub fn codegen_level(opt_level : u32) -> LLVMCodeGenOptLevel {
+    match opt_level {
+        0 => return LLVMCodeGenOptLevel::LLVMCodeGenLevelNone,
+        1 => return LLVMCodeGenOptLevel::LLVMCodeGenLevelLess,
+        2 => return LLVMCodeGenOptLevel::LLVMCodeGenLevelDefault,
+        _ => return LLVMCodeGenOptLevel::LLVMCodeGenLevelAggressive,
+    }
+}
+
+// Rulas la normajn optimigojn de LLVM sur la modulo
+// Ĉe -O0 ni faras nenion, por ke la IR restu kiel la LLIR
+pub unsafe fn llvm_optimize(module : LLVMModuleRef, machine : LLVMTargetMachineRef, opt_level : u32) -> Result<(), String> {
+    if opt_level == 0 {
+        return Ok(());
+    }
+    
+    let passes = CString::new(format!("default<O{}>", opt_level)).unwrap();
+    let options = LLVMCreatePassBuilderOptions();
+    let err = LLVMRunPasses(module, passes.as_ptr(), machine, options);
+    LLVMDisposePassBuilderOptions(options);
+    
+    if err.is_null() {
+        return Ok(());
+    }
+    
+    let msg = LLVMGetErrorMessage(err);
+    let msg_str = CStr::from_ptr(msg).to_string_lossy().into_owned();
+    LLVMDisposeErrorMessage(msg);
+    
+    Err(format!("Optimization failed: {}", msg_str))
+}
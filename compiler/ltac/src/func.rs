@@ -120,9 +120,27 @@ pub fn ltac_build_ldarg(writer : &mut BufWriter<File>, code : &LtacInstr) {
         .expect("[LTAC_build_ldarg] Write failed.");
 }
 
+// Indirect calls have no name, so we print the variable holding the address
 pub fn ltac_build_call(writer : &mut BufWriter<File>, code : &LtacInstr) {
     let mut line = "  call ".to_string();
     line.push_str(&code.name);
+    
+    match &code.arg1 {
+        LtacArg::Mem(pos) => {
+            line.push_str("[bp-");
+            line.push_str(&pos.to_string());
+            line.push_str("]");
+        },
+        
+        LtacArg::GlobalMem(ref name) => {
+            line.push_str("[");
+            line.push_str(name);
+            line.push_str("]");
+        },
+        
+        _ => {},
+    }
+    
     line.push_str("\n\n");
     
     writer.write(&line.into_bytes())
@@ -20,17 +20,14 @@ use std::io::prelude::*;
 use std::io::BufWriter;
 use std::fs::File;
 
-use ltac::ltac::{LtacFile, LtacData, LtacDataType, LtacType, LtacInstr, LtacArg};
+use ltac::ltac::{LtacFile, LtacData, LtacDataType, LtacType, LtacInstr, LtacArg, escape_string};
 
 mod func;
 
 use func::*;
 
-pub fn compile(ltac_file : &LtacFile) -> io::Result<()> {
-    let mut name = "./".to_string();
-    name.push_str(&ltac_file.name);
-    name.push_str(".asm");
-    
+// Writes the LTAC code to a file
+pub fn compile(ltac_file : &LtacFile, name : &String) -> io::Result<()> {
     // Write it out
     let file = File::create(&name)?;
     let mut writer = BufWriter::new(file);
@@ -45,7 +42,7 @@ pub fn compile(ltac_file : &LtacFile) -> io::Result<()> {
 fn write_data(writer : &mut BufWriter<File>, data : &Vec<LtacData>) {
     let mut line = String::new();
     line.push_str(".data\n");
-
+    
     for data in data.iter() {
         line.push_str(&data.name);
         
@@ -53,7 +50,7 @@ fn write_data(writer : &mut BufWriter<File>, data : &Vec<LtacData>) {
             LtacDataType::StringL => {
                 line.push_str(" .string ");
                 line.push_str("\"");
-                line.push_str(&data.val);
+                line.push_str(&escape_string(&data.val));
                 line.push_str("\"");
             },
             
@@ -66,6 +63,61 @@ fn write_data(writer : &mut BufWriter<File>, data : &Vec<LtacData>) {
                 line.push_str(" .double ");
                 line.push_str(&data.val);
             },
+            
+            LtacDataType::GlobalB => {
+                line.push_str(" .global.i8 ");
+                line.push_str(&data.val);
+            },
+            
+            LtacDataType::GlobalW => {
+                line.push_str(" .global.i16 ");
+                line.push_str(&data.val);
+            },
+            
+            LtacDataType::GlobalDW => {
+                line.push_str(" .global.i32 ");
+                line.push_str(&data.val);
+            },
+            
+            LtacDataType::GlobalQW => {
+                line.push_str(" .global.i64 ");
+                line.push_str(&data.val);
+            },
+            
+            LtacDataType::GlobalStr => {
+                line.push_str(" .global.str ");
+                line.push_str(&data.val);
+            },
+            
+            LtacDataType::GlobalBss => {
+                line.push_str(" .global.bss ");
+                line.push_str(&data.val);
+            },
+            
+            LtacDataType::TableB => {
+                line.push_str(" .table.i8 ");
+                line.push_str(&data.val);
+            },
+            
+            LtacDataType::TableW => {
+                line.push_str(" .table.i16 ");
+                line.push_str(&data.val);
+            },
+            
+            LtacDataType::TableDW => {
+                line.push_str(" .table.i32 ");
+                line.push_str(&data.val);
+            },
+            
+            LtacDataType::TableQW => {
+                line.push_str(" .table.i64 ");
+                line.push_str(&data.val);
+            },
+            
+            LtacDataType::TableStr => {
+                line.push_str(" .table.str ");
+                line.push_str(&data.val);
+            },
         }
         
         line.push_str("\n");
@@ -82,7 +134,7 @@ fn write_code(writer : &mut BufWriter<File>, code : &Vec<LtacInstr>) {
     let line = ".text\n".to_string();
     writer.write(&line.into_bytes())
         .expect("[LTAC_code] Write failed");
-
+    
     for code in code.iter() {
         match &code.instr_type {
             LtacType::Extern => ltac_build_extern(writer, code),
@@ -114,6 +166,13 @@ fn write_code(writer : &mut BufWriter<File>, code : &Vec<LtacInstr>) {
             LtacType::Bge => ltac_build_jump(writer, code),
             LtacType::Bfg => ltac_build_jump(writer, code),
             LtacType::Bfge => ltac_build_jump(writer, code),
+            LtacType::Bul => ltac_build_jump(writer, code),
+            LtacType::Bule => ltac_build_jump(writer, code),
+            LtacType::Bug => ltac_build_jump(writer, code),
+            LtacType::Buge => ltac_build_jump(writer, code),
+            
+            LtacType::BrTable => ltac_build_table(writer, code),
+            LtacType::TableEntry => ltac_build_table(writer, code),
             
             // These are intrinsics if you will; they should never get down to a code generation layer
             LtacType::Exit => ltac_build_cmd(writer, code),
@@ -128,7 +187,7 @@ fn write_code(writer : &mut BufWriter<File>, code : &Vec<LtacInstr>) {
 // Commands (may or may not map directly to an instruction)
 fn ltac_build_cmd(writer : &mut BufWriter<File>, code : &LtacInstr) {
     let mut line = String::new();
-
+    
     match &code.instr_type {
         LtacType::Exit => line.push_str("  exit"),
         LtacType::Malloc => line.push_str("  malloc"),
@@ -166,6 +225,10 @@ fn ltac_build_jump(writer : &mut BufWriter<File>, code : &LtacInstr) {
         LtacType::Bge => line.push_str("  bge "),
         LtacType::Bfg => line.push_str("  bfg "),
         LtacType::Bfge => line.push_str("  bfge "),
+        LtacType::Bul => line.push_str("  bul "),
+        LtacType::Bule => line.push_str("  bule "),
+        LtacType::Bug => line.push_str("  bug "),
+        LtacType::Buge => line.push_str("  buge "),
         
         _ => {},
     }
@@ -177,6 +240,49 @@ fn ltac_build_jump(writer : &mut BufWriter<File>, code : &LtacInstr) {
         .expect("[LTAC_build_jump] Write failed.");
 }
 
+// Builds a jump table branch, or one of its entries
+fn ltac_build_table(writer : &mut BufWriter<File>, code : &LtacInstr) {
+    let mut line = String::new();
+    
+    if code.instr_type == LtacType::BrTable {
+        line.push_str("  br.table ");
+        
+        match &code.arg1 {
+            LtacArg::Reg8(val) => {
+                line.push_str("i8.r");
+                line.push_str(&val.to_string());
+            },
+            
+            LtacArg::Reg16(val) => {
+                line.push_str("i16.r");
+                line.push_str(&val.to_string());
+            },
+            
+            LtacArg::Reg32(val) => {
+                line.push_str("i32.r");
+                line.push_str(&val.to_string());
+            },
+            
+            LtacArg::Reg64(val) => {
+                line.push_str("i64.r");
+                line.push_str(&val.to_string());
+            },
+            
+            _ => {},
+        }
+        
+        line.push_str(", ");
+    } else {
+        line.push_str("  .entry ");
+    }
+    
+    line.push_str(&code.name);
+    line.push_str("\n");
+    
+    writer.write(&line.into_bytes())
+        .expect("[LTAC_build_table] Write failed.");
+}
+
 // Builds common instructions
 fn ltac_build_instr(writer : &mut BufWriter<File>, code : &LtacInstr) {
     let mut line = String::new();
@@ -420,6 +526,18 @@ fn ltac_build_instr(writer : &mut BufWriter<File>, code : &LtacInstr) {
             },
             
             LtacArg::PtrLcl(val) => line.push_str(&val.to_string()),
+            
+            LtacArg::GlobalMem(val) => {
+                line.push_str("[");
+                line.push_str(&val.to_string());
+                line.push_str("]");
+            },
+            
+            LtacArg::GlobalPtr(val) => {
+                line.push_str("[");
+                line.push_str(&val.to_string());
+                line.push_str("]");
+            },
         }
         
         match &code.arg2 {
@@ -560,6 +678,18 @@ fn ltac_build_instr(writer : &mut BufWriter<File>, code : &LtacInstr) {
                 line.push_str(", ");
                 line.push_str(&val.to_string());
             },
+            
+            LtacArg::GlobalMem(val) => {
+                line.push_str(", [");
+                line.push_str(&val.to_string());
+                line.push_str("]");
+            },
+            
+            LtacArg::GlobalPtr(val) => {
+                line.push_str(", [");
+                line.push_str(&val.to_string());
+                line.push_str("]");
+            },
         }
     
     line.push_str("\n");
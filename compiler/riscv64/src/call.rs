@@ -18,15 +18,25 @@ use std::io::{BufWriter, Write};
 use std::fs::File;
 
 use ltac::ltac::{LtacInstr, LtacArg};
+use ltac::abi::stack_arg_slot;
 use crate::utils::*;
 
 // Builds function/sytem call arguments
 pub fn riscv64_build_pusharg(writer : &mut BufWriter<File>, code : &LtacInstr, is_karg : bool, stack_top : i32) {
     let mut reg = riscv64_arg_reg(code.arg2_val);
-    let freg = riscv64_arg_freg(code.arg2_val);
+    let mut freg = riscv64_arg_freg(code.arg2_val);
+    let mut slot = None;
     
     if is_karg {
         reg = riscv64_karg_reg(code.arg2_val);
+    } else {
+        slot = stack_arg_slot(code, ARG_REGS);
+    }
+    
+    // Past the registers, we build the argument in s3 (or fs3) and store it on the stack
+    if slot.is_some() {
+        reg = "s3".to_string();
+        freg = "fs3".to_string();
     }
 
     let mut line = String::new();
@@ -73,6 +83,11 @@ pub fn riscv64_build_pusharg(writer : &mut BufWriter<File>, code : &LtacInstr, i
                     line.push_str("  flw ");
                     reg = freg;
                 },
+                
+                LtacArg::FltReg64(_v) => {
+                    line.push_str("  fld ");
+                    reg = freg;
+                },
 
                 _ => line.push_str("  lw "),
             }
@@ -83,6 +98,24 @@ pub fn riscv64_build_pusharg(writer : &mut BufWriter<File>, code : &LtacInstr, i
             line.push_str("(s0)\n");
         },
 
+        LtacArg::GlobalMem(ref name) => {
+            line.push_str("  la s2, ");
+            line.push_str(name);
+            line.push_str("\n");
+            
+            match &code.arg2 {
+                LtacArg::Byte(_v) => line.push_str("  lb "),
+                LtacArg::UByte(_v) => line.push_str("  lbu "),
+                LtacArg::I16(_v) => line.push_str("  lh "),
+                LtacArg::U16(_v) => line.push_str("  lhu "),
+                LtacArg::I64(_) | LtacArg::U64(_) => line.push_str("  ld "),
+                _ => line.push_str("  lw "),
+            }
+            
+            line.push_str(&reg);
+            line.push_str(", 0(s2)\n");
+        },
+
         LtacArg::Ptr(val) => {
             let pos = stack_top - (*val) + 8;
 
@@ -92,6 +125,16 @@ pub fn riscv64_build_pusharg(writer : &mut BufWriter<File>, code : &LtacInstr, i
             line.push_str(&pos.to_string());
             line.push_str("(s0)\n");
         },
+        
+        LtacArg::GlobalPtr(ref name) => {
+            line.push_str("  la s2, ");
+            line.push_str(name);
+            line.push_str("\n");
+            
+            line.push_str("  ld ");
+            line.push_str(&reg);
+            line.push_str(", 0(s2)\n");
+        },
 
         // TODO: Clean this up
         LtacArg::Byte(val) => {
@@ -171,19 +214,30 @@ pub fn riscv64_build_pusharg(writer : &mut BufWriter<File>, code : &LtacInstr, i
         },
     
         LtacArg::PtrLcl(ref val) => {
-            line.push_str("  lui a5, %hi(");
+            line.push_str("  lui s2, %hi(");
             line.push_str(val);
             line.push_str(")\n");
 
             line.push_str("  addi ");
             line.push_str(&reg);
-            line.push_str(", a5, %lo(");
+            line.push_str(", s2, %lo(");
             line.push_str(val);
             line.push_str(")\n");
         },
 
         _ => {},
     }
+    
+    if let Some(slot) = slot {
+        match (&code.arg1, &code.arg2) {
+            (_, LtacArg::FltReg64(_)) => line.push_str("  fsd fs3, "),
+            (LtacArg::F32(_), _) | (_, LtacArg::FltReg(_)) => line.push_str("  fsw fs3, "),
+            _ => line.push_str("  sd s3, "),
+        }
+        
+        line.push_str(&(slot * 8).to_string());
+        line.push_str("(sp)\n");
+    }
 
     writer.write(&line.into_bytes())
         .expect("[RISCV64_build_pusharg] Write failed.");
@@ -191,10 +245,39 @@ pub fn riscv64_build_pusharg(writer : &mut BufWriter<File>, code : &LtacInstr, i
 
 // Builds a function call
 // Param: name
-pub fn riscv64_build_call(writer : &mut BufWriter<File>, code : &LtacInstr) {
-    let mut line = "  call ".to_string();
-    line.push_str(&code.name);
-    line.push_str("\n\n");
+// Indirect calls have no name; the address is loaded from arg1 into s3
+pub fn riscv64_build_call(writer : &mut BufWriter<File>, code : &LtacInstr, stack_top : i32) {
+    let mut line = String::new();
+    
+    match &code.arg1 {
+        LtacArg::Mem(val) => {
+            let mut pos = stack_top - (*val);
+            
+            if pos + 8 == stack_top {
+                pos += 8;
+            }
+            
+            line.push_str("  ld s3, -");
+            line.push_str(&pos.to_string());
+            line.push_str("(s0)\n");
+        },
+        
+        LtacArg::GlobalMem(ref name) => {
+            line.push_str("  la s2, ");
+            line.push_str(name);
+            line.push_str("\n  ld s3, 0(s2)\n");
+        },
+        
+        _ => {},
+    }
+    
+    if code.name.len() == 0 {
+        line.push_str("  jalr s3\n\n");
+    } else {
+        line.push_str("  call ");
+        line.push_str(&code.name);
+        line.push_str("\n\n");
+    }
     
     writer.write(&line.into_bytes())
         .expect("[RISCV64_build_call] Write failed.");
@@ -148,6 +148,11 @@ pub fn riscv64_build_cond_jump(writer : &mut BufWriter<File>, cmp : &LtacInstr,
         LtacType::Bge if riscv64_signed_cmp(&cmp.instr_type) => line.push_str("  bge "),
         LtacType::Bge if !riscv64_signed_cmp(&cmp.instr_type) => line.push_str("  bgeu "),
 
+        LtacType::Bul => line.push_str("  bltu "),
+        LtacType::Bule => line.push_str("  bleu "),
+        LtacType::Bug => line.push_str("  bgtu "),
+        LtacType::Buge => line.push_str("  bgeu "),
+
         _ => {},
     }
 
@@ -196,3 +201,43 @@ pub fn riscv64_build_cond_jump(writer : &mut BufWriter<File>, cmp : &LtacInstr,
     writer.write(&line.into_bytes())
         .expect("[RISCV64_build_cond_jump] Write failed.");
 }
+
+// Builds a jump through a table
+// Each table entry is the offset from itself to the label, so we load it (s3) and add it
+// to the entry address (s2)
+pub fn riscv64_build_br_table(writer : &mut BufWriter<File>, code : &LtacInstr) {
+    let index = match &code.arg1 {
+        LtacArg::Reg8(pos) | LtacArg::Reg16(pos)
+        | LtacArg::Reg32(pos) | LtacArg::Reg64(pos) => riscv64_op_reg(*pos),
+        _ => return,
+    };
+    
+    let mut line = "  lla s2, ".to_string();
+    line.push_str(&code.name);
+    line.push_str("\n");
+    
+    line.push_str("  slli s3, ");
+    line.push_str(&index);
+    line.push_str(", 2\n");
+    
+    line.push_str("  add s2, s2, s3\n");
+    line.push_str("  lw s3, 0(s2)\n");
+    line.push_str("  add s2, s2, s3\n");
+    line.push_str("  jr s2\n");
+    
+    // The entries are words; the table must stay aligned
+    line.push_str("  .balign 4\n\n");
+
+    writer.write(&line.into_bytes())
+        .expect("[RISCV64_build_br_table] Write failed.");
+}
+
+// Builds a single jump table entry
+pub fn riscv64_build_table_entry(writer : &mut BufWriter<File>, code : &LtacInstr) {
+    let mut line = "  .word ".to_string();
+    line.push_str(&code.name);
+    line.push_str("-.\n");
+
+    writer.write(&line.into_bytes())
+        .expect("[RISCV64_build_table_entry] Write failed.");
+}
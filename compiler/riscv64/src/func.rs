@@ -18,6 +18,7 @@ use std::io::{BufWriter, Write};
 use std::fs::File;
 
 use ltac::ltac::{LtacInstr, LtacType, LtacArg};
+use ltac::abi::stack_arg_slot;
 use crate::utils::*;
 
 // Builds an extern declaration
@@ -42,8 +43,9 @@ pub fn riscv64_build_label(writer : &mut BufWriter<File>, code : &LtacInstr) {
 }
 
 // Builds a function
-pub fn riscv64_build_func(writer : &mut BufWriter<File>, code : &LtacInstr) {
-    let stack_size = code.arg1_val + 16;
+// The stack arguments for our calls go at the bottom of the frame, below the variables
+pub fn riscv64_build_func(writer : &mut BufWriter<File>, code : &LtacInstr, arg_space : i32) {
+    let stack_size = code.arg1_val + 16 + arg_space;
     let ra = stack_size - 8;
     let s0 = stack_size - 16;
 
@@ -111,19 +113,42 @@ pub fn riscv64_build_ldarg(writer : &mut BufWriter<File>, code : &LtacInstr, sta
         LtacType::LdArgI32 | LtacType::LdArgU32 => line.push_str("  sw "),
         LtacType::LdArgI64 | LtacType::LdArgU64 => line.push_str("  sd "),
         LtacType::LdArgF32 => line.push_str("  fsw "),
+        LtacType::LdArgF64 => line.push_str("  fsd "),
         LtacType::LdArgPtr => line.push_str("  sd "),
 
         _ => {},
     }
 
-    if code.instr_type == LtacType::LdArgF32 || code.instr_type == LtacType::LdArgF64 {
-        let reg = riscv64_arg_freg(code.arg2_val);
-        line.push_str(&reg);
+    let is_float = code.instr_type == LtacType::LdArgF32 || code.instr_type == LtacType::LdArgF64;
+    let mut reg = if is_float {
+        riscv64_arg_freg(code.arg2_val)
     } else {
-        let reg = riscv64_arg_reg(code.arg2_val);
-        line.push_str(&reg);
+        riscv64_arg_reg(code.arg2_val)
+    };
+    
+    // Past the registers, the caller left it on the stack, where s0 points
+    if let Some(slot) = stack_arg_slot(code, ARG_REGS) {
+        let load = match code.instr_type {
+            LtacType::LdArgF32 => "flw",
+            LtacType::LdArgF64 => "fld",
+            _ => "ld",
+        };
+        
+        reg = if is_float { "fs3".to_string() } else { "s3".to_string() };
+        
+        let mut load_line = "  ".to_string();
+        load_line.push_str(load);
+        load_line.push_str(" ");
+        load_line.push_str(&reg);
+        load_line.push_str(", ");
+        load_line.push_str(&(slot * 8).to_string());
+        load_line.push_str("(s0)\n");
+        
+        line.insert_str(0, &load_line);
     }
     
+    line.push_str(&reg);
+    
     line.push_str(", ");
 
     match code.arg1 {
@@ -116,6 +116,14 @@ pub fn riscv64_build_ld_str(writer : &mut BufWriter<File>, code : &LtacInstr, st
             line.push_str("(s0)");
         },
 
+        LtacArg::GlobalMem(ref name) => {
+            full_line.push_str("  la s2, ");
+            full_line.push_str(name);
+            full_line.push_str("\n");
+            
+            line.push_str("0(s2)");
+        },
+
         LtacArg::MemOffsetImm(pos, offset) => {
             // Load the array
             let array_pos = stack_top - (*pos) + 8;
@@ -245,6 +253,7 @@ pub fn riscv64_build_mov(writer : &mut BufWriter<File>, code : &LtacInstr) {
             match &code.arg2 {
                 LtacArg::I64(_v) => line.push_str("  li "),
                 LtacArg::U64(_v) => line.push_str("  li "),
+                LtacArg::PtrLcl(_v) => line.push_str("  la "),
                 _ => line.push_str("  mv "),
             }
         },
@@ -324,6 +333,8 @@ pub fn riscv64_build_mov(writer : &mut BufWriter<File>, code : &LtacInstr) {
         LtacArg::I64(val) => line.push_str(&val.to_string()),
         LtacArg::U64(val) => line.push_str(&val.to_string()),
 
+        LtacArg::PtrLcl(ref val) => line.push_str(&val),
+
         LtacArg::F32(ref val) => {
             line.push_str("%lo(");
             line.push_str(&val);
@@ -14,6 +14,8 @@
 // with this program; if not, write to the Free Software Foundation, Inc.,
 // 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
 
+use ltac::abi::ArgRegs;
+
 // Gets a register based on position
 // Kernel argument registers
 pub fn riscv64_karg_reg(pos : i32) -> String {
@@ -30,6 +32,10 @@ pub fn riscv64_karg_reg(pos : i32) -> String {
 }
 
 // Function argument registers
+// Function argument registers
+// Past these, the arguments go on the stack
+pub const ARG_REGS : ArgRegs = ArgRegs { ints : 8, floats : 8 };
+
 pub fn riscv64_arg_reg(pos : i32) -> String {
     match pos {
         1 => return "a0".to_string(),
@@ -38,6 +44,8 @@ pub fn riscv64_arg_reg(pos : i32) -> String {
         4 => return "a3".to_string(),
         5 => return "a4".to_string(),
         6 => return "a5".to_string(),
+        7 => return "a6".to_string(),
+        8 => return "a7".to_string(),
         _ => return String::new(),
     };
 }
@@ -50,6 +58,8 @@ pub fn riscv64_arg_freg(pos : i32) -> String {
         4 => return "fa3".to_string(),
         5 => return "fa4".to_string(),
         6 => return "fa5".to_string(),
+        7 => return "fa6".to_string(),
+        8 => return "fa7".to_string(),
         _ => return String::new(),
     };
 }
@@ -17,6 +17,8 @@
 
 // This represents x86-64 instructions
 
+use ltac::abi::ArgRegs;
+
 #[derive(Clone, PartialEq)]
 #[allow(dead_code)]
 pub enum X86Type {
@@ -24,7 +26,8 @@ pub enum X86Type {
     Global,
     Type,
     Label,
-
+    Loc,        // Source line for the debugger; arg1 is the line
+    
     Nop,
     
     Push,
@@ -32,6 +35,7 @@ pub enum X86Type {
     Mov,
     MovZX,
     MovSX,
+    MovSXD,
     MovSS,
     MovSD,
     Lea,
@@ -63,6 +67,8 @@ pub enum X86Type {
     Ucomiss,
     Ucomisd,
     
+    CvtSS2SD,
+    
     Jmp,
     Je, Jne,
     Jl, Jle,
@@ -70,6 +76,8 @@ pub enum X86Type {
     Ja, Jae,
     Jb, Jbe,
     
+    Long,       // A jump table entry; the name is the label
+    
     Call,
     Syscall,
     Leave,
@@ -86,7 +94,9 @@ pub enum X86Arg {
     WordMem(X86Reg, i32, bool),
     QwordMem(X86Reg, i32, bool),
     LclMem(String, bool),
+    GotMem(String),
     ScaleMem(i32, X86Reg, i32, bool),
+    IndexMem(X86Reg, X86Reg, i32),      // Base, index, scale
     
     Imm32(i32),
     Imm64(i64),
@@ -282,6 +292,9 @@ pub fn amd64_karg_reg64(pos : i32) -> X86Arg {
 }
 
 // Function argument registers
+// Past these, the arguments go on the stack
+pub const ARG_REGS : ArgRegs = ArgRegs { ints : 6, floats : 8 };
+
 pub fn amd64_arg_reg8(pos : i32) -> X86Arg {
     match pos {
         1 => return X86Arg::Reg8(X86Reg::RDI),
@@ -330,7 +343,7 @@ pub fn amd64_arg_reg64(pos : i32) -> X86Arg {
     };
 }
 
-/*pub fn amd64_arg_flt(pos : i32) -> X86Arg {
+pub fn amd64_arg_flt(pos : i32) -> X86Arg {
     match pos {
         1 => return X86Arg::Xmm(0),
         2 => return X86Arg::Xmm(1),
@@ -342,7 +355,7 @@ pub fn amd64_arg_reg64(pos : i32) -> X86Arg {
         8 => return X86Arg::Xmm(7),
         _ => return X86Arg::Empty,
     };
-}*/
+}
 
 // Operation registers
 // EAX -> Return register
@@ -391,22 +404,24 @@ pub fn amd64_op_reg64(pos : i32) -> X86Arg {
     };
 }
 
-// xmm0 and xmm1 are reserved for internal operations
-/*pub fn amd64_op_flt(pos : i32) -> String {
+// Float operation registers
+// xmm0 to xmm7 hold the arguments, and xmm15 is reserved for internal operations
+pub fn amd64_op_flt(pos : i32) -> X86Arg {
     match pos {
-        0 => return "xmm10".to_string(),
-        1 => return "xmm11".to_string(),
-        2 => return "xmm12".to_string(),
-        3 => return "xmm13".to_string(),
-        4 => return "xmm14".to_string(),
-        5 => return "xmm15".to_string(),
-        _ => return String::new(),
-    }
+        0 => return X86Arg::Xmm(8),
+        1 => return X86Arg::Xmm(9),
+        2 => return X86Arg::Xmm(10),
+        3 => return X86Arg::Xmm(11),
+        4 => return X86Arg::Xmm(12),
+        5 => return X86Arg::Xmm(13),
+        6 => return X86Arg::Xmm(14),
+        _ => return X86Arg::Empty,
+    };
 }
 
 // Vector registers
 // ymm0 and ymm1 are reserved for internal operations
-pub fn amd64_vector_i32(pos : i32) -> String {
+/*pub fn amd64_vector_i32(pos : i32) -> String {
     match pos {
         0 => return "ymm3".to_string(),
         1 => return "ymm4".to_string(),
@@ -17,17 +17,40 @@
 
 use ltac::ltac::{LtacInstr, LtacArg};
 use crate::asm::*;
+use crate::instr::amd64_mem_base;
+use crate::float::amd64_build_pusharg_float;
+use ltac::abi::stack_arg_slot;
 
 // Builds a function argument
 pub fn amd64_build_pusharg(x86_code : &mut Vec<X86Instr>, code : &LtacInstr, is_karg : bool, is_pic : bool) {
+    // Float arguments have their own registers
+    match (&code.arg1, &code.arg2) {
+        (LtacArg::F32(_), _) | (LtacArg::F64(_), _)
+        | (_, LtacArg::FltReg(_)) | (_, LtacArg::FltReg64(_)) => {
+            amd64_build_pusharg_float(x86_code, code, is_pic);
+            return;
+        },
+        
+        _ => {},
+    }
+    
     // Get the argument registers
     let mut reg32 = amd64_arg_reg32(code.arg2_val);
     let mut reg64 = amd64_arg_reg64(code.arg2_val);
-    //let mut reg_flt = amd64_arg_flt(code.arg2_val);
+    
+    let mut slot = None;
     
     if is_karg {
         reg32 = amd64_karg_reg32(code.arg2_val);
         reg64 = amd64_karg_reg64(code.arg2_val);
+    } else {
+        slot = stack_arg_slot(code, ARG_REGS);
+    }
+    
+    // Past the registers, we build the argument in rax and store it on the stack
+    if slot.is_some() {
+        reg32 = X86Arg::Reg32(X86Reg::RAX);
+        reg64 = X86Arg::Reg64(X86Reg::RAX);
     }
     
     // Determine move type
@@ -35,8 +58,6 @@ pub fn amd64_build_pusharg(x86_code : &mut Vec<X86Instr>, code : &LtacInstr, is_
     
     match &code.arg1 {
         LtacArg::Reg8(_p) => mov_type = X86Type::MovZX,
-        LtacArg::F32(_p) => mov_type = X86Type::MovSS,
-        LtacArg::F64(_p) => mov_type = X86Type::MovSD,
         LtacArg::PtrLcl(_p) if is_pic => mov_type = X86Type::Lea,
         _ => {},
     }
@@ -47,17 +68,6 @@ pub fn amd64_build_pusharg(x86_code : &mut Vec<X86Instr>, code : &LtacInstr, is_
         LtacArg::I16(_v) => mov_type = X86Type::MovSX,
         LtacArg::U16(_v) => mov_type = X86Type::MovZX,
         
-        // TODO: What do those second lines for the float registers do?
-        LtacArg::FltReg(_pos) => {
-            mov_type = X86Type::MovSS;
-            //reg_flt = amd64_arg_flt(pos);
-        },
-        
-        LtacArg::FltReg64(_pos) => {
-            mov_type = X86Type::MovSD;
-            //reg_flt = amd64_arg_flt(pos);
-        },
-        
         _ => {},
     }
     
@@ -87,41 +97,43 @@ pub fn amd64_build_pusharg(x86_code : &mut Vec<X86Instr>, code : &LtacInstr, is_
             instr.arg2 = amd64_op_reg64(*pos);
         },
         
-        LtacArg::Mem(pos) => {
+        LtacArg::Mem(_) | LtacArg::GlobalMem(_) => {
+            let (base, pos) = amd64_mem_base(x86_code, &code.arg1, is_pic);
+            
             match code.arg2 {
                 LtacArg::Byte(_v) => {
                     instr.arg1 = reg32;
-                    instr.arg2 = X86Arg::BwordMem(X86Reg::RBP, *pos, is_pic);
+                    instr.arg2 = X86Arg::BwordMem(base.clone(), pos, is_pic);
                 },
                 
                 LtacArg::UByte(_v) => {
                     instr.arg1 = reg32;
-                    instr.arg2 = X86Arg::BwordMem(X86Reg::RBP, *pos, is_pic);
+                    instr.arg2 = X86Arg::BwordMem(base.clone(), pos, is_pic);
                 },
                 
                 LtacArg::I16(_v) => {
                     instr.arg1 = reg32;
-                    instr.arg2 = X86Arg::WordMem(X86Reg::RBP, *pos, is_pic);
+                    instr.arg2 = X86Arg::WordMem(base.clone(), pos, is_pic);
                 },
                 
                 LtacArg::U16(_v) => {
                     instr.arg1 = reg32;
-                    instr.arg2 = X86Arg::WordMem(X86Reg::RBP, *pos, is_pic);
+                    instr.arg2 = X86Arg::WordMem(base.clone(), pos, is_pic);
                 },
                 
                 LtacArg::I64(_v) => {
                     instr.arg1 = reg64;
-                    instr.arg2 = X86Arg::QwordMem(X86Reg::RBP, *pos, is_pic);
+                    instr.arg2 = X86Arg::QwordMem(base.clone(), pos, is_pic);
                 },
                 
                 LtacArg::U64(_v) => {
                     instr.arg1 = reg64;
-                    instr.arg2 = X86Arg::QwordMem(X86Reg::RBP, *pos, is_pic);
+                    instr.arg2 = X86Arg::QwordMem(base.clone(), pos, is_pic);
                 },
-            
+                
                 _ => {
                     instr.arg1 = reg32;
-                    instr.arg2 = X86Arg::DwordMem(X86Reg::RBP, *pos, is_pic);
+                    instr.arg2 = X86Arg::DwordMem(base.clone(), pos, is_pic);
                 },
             }
         },
@@ -147,9 +159,22 @@ pub fn amd64_build_pusharg(x86_code : &mut Vec<X86Instr>, code : &LtacInstr, is_
             instr.arg2 = X86Arg::Imm32(*val as i32);
         },
         
-        LtacArg::Ptr(pos) => {
+        // A move to a 64-bit register takes the full immediate (movabs)
+        LtacArg::I64(val) => {
             instr.arg1 = reg64;
-            instr.arg2 = X86Arg::QwordMem(X86Reg::RBP, *pos, is_pic);
+            instr.arg2 = X86Arg::Imm64(*val);
+        },
+        
+        LtacArg::U64(val) => {
+            instr.arg1 = reg64;
+            instr.arg2 = X86Arg::Imm64(*val as i64);
+        },
+        
+        LtacArg::Ptr(_) | LtacArg::GlobalPtr(_) => {
+            let (base, pos) = amd64_mem_base(x86_code, &code.arg1, is_pic);
+            
+            instr.arg1 = reg64;
+            instr.arg2 = X86Arg::QwordMem(base, pos, is_pic);
         },
         
         LtacArg::PtrLcl(ref val) => {
@@ -159,16 +184,37 @@ pub fn amd64_build_pusharg(x86_code : &mut Vec<X86Instr>, code : &LtacInstr, is_
         
         _ => {},
     }
-        
+    
     x86_code.push(instr);
+    
+    if let Some(slot) = slot {
+        let mut store = create_x86instr(X86Type::Mov);
+        store.arg1 = X86Arg::QwordMem(X86Reg::RSP, -(slot * 8), is_pic);
+        store.arg2 = X86Arg::Reg64(X86Reg::RAX);
+        x86_code.push(store);
+    }
 }
 
 // Builds a function call
 // Param: name
-pub fn amd64_build_call(x86_code : &mut Vec<X86Instr>, code : &LtacInstr) {
+// Variadic calls have the number of vector registers used in arg2, which goes in al
+// Indirect calls have no name, and arg1 is the variable holding the address
+pub fn amd64_build_call(x86_code : &mut Vec<X86Instr>, code : &LtacInstr, is_pic : bool) {
+    if let LtacArg::I32(count) = code.arg2 {
+        let mut instr = create_x86instr(X86Type::Mov);
+        instr.arg1 = X86Arg::Reg32(X86Reg::RAX);
+        instr.arg2 = X86Arg::Imm32(count);
+        x86_code.push(instr);
+    }
+    
     let mut instr = create_x86instr(X86Type::Call);
     instr.name = code.name.clone();
     
+    if code.name.len() == 0 {
+        let (base, pos) = amd64_mem_base(x86_code, &code.arg1, is_pic);
+        instr.arg1 = X86Arg::QwordMem(base, pos, is_pic);
+    }
+    
     x86_code.push(instr);
 }
 
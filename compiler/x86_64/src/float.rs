@@ -0,0 +1,167 @@
+
+// This file is part of the Lila compiler
+// Copyright (C) 2020-2021 Patrick Flynn
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; version 2.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, write to the Free Software Foundation, Inc.,
+// 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+
+// Floating-point instructions, which use the SSE registers
+// The LTAC float registers are xmm8 and up, and xmm15 is our scratch register. Float
+// arguments go in xmm0 to xmm7 and the return value in xmm0, so the low registers are
+// left for calls.
+
+use ltac::ltac::{LtacInstr, LtacType, LtacArg};
+use ltac::abi::stack_arg_slot;
+use crate::asm::*;
+use crate::instr::{amd64_mem_base, amd64_build_offset_mem, amd64_build_offset_reg};
+
+pub const SCRATCH_XMM : i32 = 15;
+
+// Builds a float move, arithmetic, comparison or conversion
+pub fn amd64_build_float(x86_code : &mut Vec<X86Instr>, code : &LtacInstr, is_pic : bool) {
+    let (instr_type, src_double, dest_double) = match &code.instr_type {
+        LtacType::MovF32 => (X86Type::MovSS, false, false),
+        LtacType::F32Add => (X86Type::AddSS, false, false),
+        LtacType::F32Sub => (X86Type::SubSS, false, false),
+        LtacType::F32Mul => (X86Type::MulSS, false, false),
+        LtacType::F32Div => (X86Type::DivSS, false, false),
+        LtacType::F32Cmp => (X86Type::Ucomiss, false, false),
+        
+        LtacType::MovF64 => (X86Type::MovSD, true, true),
+        LtacType::F64Add => (X86Type::AddSD, true, true),
+        LtacType::F64Sub => (X86Type::SubSD, true, true),
+        LtacType::F64Mul => (X86Type::MulSD, true, true),
+        LtacType::F64Div => (X86Type::DivSD, true, true),
+        LtacType::F64Cmp => (X86Type::Ucomisd, true, true),
+        
+        LtacType::CvtF32F64 => (X86Type::CvtSS2SD, false, true),
+        
+        _ => return,
+    };
+    
+    // Only one operand can be in memory, so a value stored to memory goes through
+    // the scratch register. The source is loaded first, since building either
+    // address may use r14 and r15.
+    let mut src = amd64_float_arg(x86_code, &code.arg2, src_double, is_pic);
+    
+    if !amd64_is_flt_reg(&code.arg1) && !amd64_is_xmm(&src) {
+        let mut load = create_x86instr(if src_double { X86Type::MovSD } else { X86Type::MovSS });
+        load.arg1 = X86Arg::Xmm(SCRATCH_XMM);
+        load.arg2 = src;
+        x86_code.push(load);
+        
+        src = X86Arg::Xmm(SCRATCH_XMM);
+    }
+    
+    let mut instr = create_x86instr(instr_type);
+    instr.arg1 = amd64_float_arg(x86_code, &code.arg1, dest_double, is_pic);
+    instr.arg2 = src;
+    x86_code.push(instr);
+}
+
+// Builds a float function argument
+// These are counted apart from the integer arguments, so the position is the xmm register.
+// Past the registers, the value goes through the scratch register to the stack.
+pub fn amd64_build_pusharg_float(x86_code : &mut Vec<X86Instr>, code : &LtacInstr, is_pic : bool) {
+    let is_double = match (&code.arg1, &code.arg2) {
+        (LtacArg::F64(_), _) | (_, LtacArg::FltReg64(_)) => true,
+        _ => false,
+    };
+    
+    let mut instr = create_x86instr(if is_double { X86Type::MovSD } else { X86Type::MovSS });
+    instr.arg2 = amd64_float_arg(x86_code, &code.arg1, is_double, is_pic);
+    instr.arg1 = amd64_arg_flt(code.arg2_val);
+    
+    if let Some(slot) = stack_arg_slot(code, ARG_REGS) {
+        if !amd64_is_xmm(&instr.arg2) {
+            instr.arg1 = X86Arg::Xmm(SCRATCH_XMM);
+            x86_code.push(instr.clone());
+            
+            instr.arg2 = X86Arg::Xmm(SCRATCH_XMM);
+        }
+        
+        if is_double {
+            instr.arg1 = X86Arg::QwordMem(X86Reg::RSP, -(slot * 8), is_pic);
+        } else {
+            instr.arg1 = X86Arg::DwordMem(X86Reg::RSP, -(slot * 8), is_pic);
+        }
+    }
+    
+    x86_code.push(instr);
+}
+
+// Checks whether an LTAC argument is a float register
+fn amd64_is_flt_reg(arg : &LtacArg) -> bool {
+    match arg {
+        LtacArg::FltReg(_) | LtacArg::FltReg64(_)
+        | LtacArg::RetRegF32 | LtacArg::RetRegF64 => return true,
+        _ => return false,
+    }
+}
+
+fn amd64_is_xmm(arg : &X86Arg) -> bool {
+    match arg {
+        X86Arg::Xmm(_) => return true,
+        _ => return false,
+    }
+}
+
+// Returns the x86 operand for a float value
+// Literals are read from the data section relative to rip, which works with and without --pic
+fn amd64_float_arg(x86_code : &mut Vec<X86Instr>, arg : &LtacArg, is_double : bool, is_pic : bool) -> X86Arg {
+    let sized = |base : X86Reg, pos : i32| -> X86Arg {
+        if is_double {
+            return X86Arg::QwordMem(base, pos, is_pic);
+        }
+        
+        X86Arg::DwordMem(base, pos, is_pic)
+    };
+    
+    match arg {
+        LtacArg::FltReg(pos) | LtacArg::FltReg64(pos) => return amd64_op_flt(*pos),
+        LtacArg::RetRegF32 | LtacArg::RetRegF64 => return X86Arg::Xmm(0),
+        
+        LtacArg::F32(name) | LtacArg::F64(name) => return X86Arg::LclMem(name.to_string(), true),
+        
+        LtacArg::Mem(_) | LtacArg::GlobalMem(_) => {
+            let (base, pos) = amd64_mem_base(x86_code, arg, is_pic);
+            return sized(base, pos);
+        },
+        
+        LtacArg::MemOffsetImm(pos, offset) => {
+            let mut instr = create_x86instr(X86Type::Mov);
+            instr.arg1 = X86Arg::Reg64(X86Reg::R15);
+            instr.arg2 = X86Arg::QwordMem(X86Reg::RBP, *pos, is_pic);
+            x86_code.push(instr);
+            
+            instr = create_x86instr(X86Type::Add);
+            instr.arg1 = X86Arg::Reg64(X86Reg::R15);
+            instr.arg2 = X86Arg::Imm32(*offset);
+            x86_code.push(instr);
+            
+            return sized(X86Reg::R15, 0);
+        },
+        
+        LtacArg::MemOffsetMem(pos, offset, size) => {
+            amd64_build_offset_mem(x86_code, *pos, *offset, *size, is_pic);
+            return sized(X86Reg::R15, 0);
+        },
+        
+        LtacArg::MemOffsetReg(pos, reg, size) => {
+            amd64_build_offset_reg(x86_code, *pos, *reg, *size, is_pic);
+            return sized(X86Reg::R15, 0);
+        },
+        
+        _ => return X86Arg::Empty,
+    }
+}
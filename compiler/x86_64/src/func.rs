@@ -16,7 +16,10 @@
 // 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
 
 use ltac::ltac::{LtacInstr, LtacType, LtacArg};
+use ltac::dwarf;
+use ltac::abi::stack_arg_slot;
 use crate::asm::*;
+use crate::float::SCRATCH_XMM;
 
 // Builds an extern declaration
 pub fn amd64_build_extern(x86_code : &mut Vec<X86Instr>, code : &LtacInstr) {
@@ -34,10 +37,17 @@ pub fn amd64_build_label(x86_code : &mut Vec<X86Instr>, code : &LtacInstr) {
     x86_code.push(instr);
 }
 
+// The callee-saved registers we use as scratch registers
+// These are saved below the local variables, so code from other compilers (like LLVM)
+// can keep values in them across calls to Lila functions.
+const SAVED_REGS : [X86Reg; 5] = [X86Reg::RBX, X86Reg::R12, X86Reg::R13, X86Reg::R14, X86Reg::R15];
+const SAVED_SIZE : i32 = 48;        // Rounded up to keep the stack aligned
+
 // Builds a function
 // Params: name -> function name
 //         arg1_val -> stack size
-pub fn amd64_build_func(x86_code : &mut Vec<X86Instr>, code : &LtacInstr, is_pic : bool) {
+//         arg_space -> the space for stack arguments to calls, at the bottom of the frame
+pub fn amd64_build_func(x86_code : &mut Vec<X86Instr>, code : &LtacInstr, arg_space : i32, is_pic : bool) {
     let mut instr = create_x86instr(X86Type::Global);
     instr.name = code.name.clone();
     x86_code.push(instr.clone());
@@ -55,7 +65,8 @@ pub fn amd64_build_func(x86_code : &mut Vec<X86Instr>, code : &LtacInstr, is_pic
     // Setup the stack
     // push rbp
     // mov rbp, rsp
-    // sub rsp, stack_size
+    // sub rsp, stack_size + saved registers + stack arguments
+    // mov [rbp-x], <saved reg> ...
     //
     
     instr = create_x86instr(X86Type::Push);
@@ -69,12 +80,38 @@ pub fn amd64_build_func(x86_code : &mut Vec<X86Instr>, code : &LtacInstr, is_pic
     
     instr = create_x86instr(X86Type::Sub);
     instr.arg1 = X86Arg::Reg64(X86Reg::RSP);
-    instr.arg2 = X86Arg::Imm32(code.arg1_val);
+    instr.arg2 = X86Arg::Imm32(code.arg1_val + SAVED_SIZE + arg_space);
+    x86_code.push(instr);
+    
+    for (i, reg) in SAVED_REGS.iter().enumerate() {
+        instr = create_x86instr(X86Type::Mov);
+        instr.arg1 = X86Arg::QwordMem(X86Reg::RBP, code.arg1_val + (i as i32 + 1) * 8, false);
+        instr.arg2 = X86Arg::Reg64(reg.clone());
+        x86_code.push(instr);
+    }
+}
+
+// Marks the end of a function for the debugger
+pub fn amd64_build_func_end(x86_code : &mut Vec<X86Instr>, name : &String) {
+    if name.len() == 0 {
+        return;
+    }
+    
+    let mut instr = create_x86instr(X86Type::Label);
+    instr.name = dwarf::func_end_label(name);
     x86_code.push(instr);
 }
 
 // Builds a return statement
-pub fn amd64_build_ret(x86_code : &mut Vec<X86Instr>) {
+// Params: stack_size -> the stack size of the current function
+pub fn amd64_build_ret(x86_code : &mut Vec<X86Instr>, stack_size : i32) {
+    for (i, reg) in SAVED_REGS.iter().enumerate() {
+        let mut instr = create_x86instr(X86Type::Mov);
+        instr.arg1 = X86Arg::Reg64(reg.clone());
+        instr.arg2 = X86Arg::QwordMem(X86Reg::RBP, stack_size + (i as i32 + 1) * 8, false);
+        x86_code.push(instr);
+    }
+    
     let mut instr = create_x86instr(X86Type::Leave);
     x86_code.push(instr.clone());
     
@@ -82,9 +119,16 @@ pub fn amd64_build_ret(x86_code : &mut Vec<X86Instr>) {
     x86_code.push(instr);
 }
 
+// Returns the address of an argument the caller passed on the stack
+// These are above the return address and the saved rbp.
+pub fn amd64_stack_arg(slot : i32, is_pic : bool) -> (X86Reg, i32, bool) {
+    (X86Reg::RBP, -(16 + slot * 8), is_pic)
+}
+
 // Load a function argument to a variable
 // In the LtacInstr:
-//      -> arg1_val = memory location
+//      -> arg1 = the variable
+//      -> arg1_val = position in the call
 //      -> arg2_val = register position
 pub fn amd64_build_ldarg(x86_code : &mut Vec<X86Instr>, code : &LtacInstr, is_pic : bool) {
     let mut instr = create_x86instr(X86Type::Mov);
@@ -110,6 +154,78 @@ pub fn amd64_build_ldarg(x86_code : &mut Vec<X86Instr>, code : &LtacInstr, is_pi
         _ => {},
     }
     
+    // Past the registers, we load it into rax first
+    if let Some(slot) = stack_arg_slot(code, ARG_REGS) {
+        let (base, pos, pic) = amd64_stack_arg(slot, is_pic);
+        let mut load = create_x86instr(X86Type::Mov);
+        
+        match code.instr_type {
+            LtacType::LdArgI8 | LtacType::LdArgU8 => {
+                load.arg1 = X86Arg::Reg8(X86Reg::RAX);
+                load.arg2 = X86Arg::BwordMem(base, pos, pic);
+            },
+            
+            LtacType::LdArgI16 | LtacType::LdArgU16 => {
+                load.arg1 = X86Arg::Reg16(X86Reg::RAX);
+                load.arg2 = X86Arg::WordMem(base, pos, pic);
+            },
+            
+            LtacType::LdArgI32 | LtacType::LdArgU32 => {
+                load.arg1 = X86Arg::Reg32(X86Reg::RAX);
+                load.arg2 = X86Arg::DwordMem(base, pos, pic);
+            },
+            
+            _ => {
+                load.arg1 = X86Arg::Reg64(X86Reg::RAX);
+                load.arg2 = X86Arg::QwordMem(base, pos, pic);
+            },
+        }
+        
+        instr.arg2 = load.arg1.clone();
+        x86_code.push(load);
+    }
+    
     x86_code.push(instr);
 }
 
+
+// Loads a float argument
+// The position counts only the float arguments, so it maps straight to the xmm register
+pub fn amd64_build_ldarg_float(x86_code : &mut Vec<X86Instr>, code : &LtacInstr, is_pic : bool) {
+    let mut instr = create_x86instr(X86Type::MovSS);
+    
+    if code.instr_type == LtacType::LdArgF64 {
+        instr = create_x86instr(X86Type::MovSD);
+    }
+    
+    instr.arg2 = amd64_arg_flt(code.arg2_val);
+    
+    // Past the registers, we load it into the scratch register first
+    if let Some(slot) = stack_arg_slot(code, ARG_REGS) {
+        let (base, pos, pic) = amd64_stack_arg(slot, is_pic);
+        let mut load = instr.clone();
+        load.arg1 = X86Arg::Xmm(SCRATCH_XMM);
+        
+        if code.instr_type == LtacType::LdArgF64 {
+            load.arg2 = X86Arg::QwordMem(base, pos, pic);
+        } else {
+            load.arg2 = X86Arg::DwordMem(base, pos, pic);
+        }
+        
+        instr.arg2 = load.arg1.clone();
+        x86_code.push(load);
+    }
+    
+    match &code.arg1 {
+        LtacArg::Mem(pos) if code.instr_type == LtacType::LdArgF64 => {
+            instr.arg1 = X86Arg::QwordMem(X86Reg::RBP, *pos, is_pic);
+        },
+        
+        LtacArg::Mem(pos) => instr.arg1 = X86Arg::DwordMem(X86Reg::RBP, *pos, is_pic),
+        LtacArg::FltReg(pos) | LtacArg::FltReg64(pos) => instr.arg1 = amd64_op_flt(*pos),
+        
+        _ => {},
+    }
+    
+    x86_code.push(instr);
+}
@@ -33,6 +33,10 @@ pub fn amd64_build_jump(x86_code : &mut Vec<X86Instr>, code : &LtacInstr) {
         LtacType::Bge => instr_type = X86Type::Jge,
         LtacType::Bfg => instr_type = X86Type::Ja,
         LtacType::Bfge => instr_type = X86Type::Jae,
+        LtacType::Bul => instr_type = X86Type::Jb,
+        LtacType::Bule => instr_type = X86Type::Jbe,
+        LtacType::Bug => instr_type = X86Type::Ja,
+        LtacType::Buge => instr_type = X86Type::Jae,
         _ => instr_type = X86Type::Jmp,
     }
     
@@ -41,6 +45,68 @@ pub fn amd64_build_jump(x86_code : &mut Vec<X86Instr>, code : &LtacInstr) {
     x86_code.push(instr);
 }
 
+// Builds a jump through a table
+// Each entry holds the offset from itself to its label, so the table works in
+// position-independent code too
+pub fn amd64_build_br_table(x86_code : &mut Vec<X86Instr>, code : &LtacInstr) {
+    let mut instr : X86Instr;
+    
+    // Zero-extend the index
+    match &code.arg1 {
+        LtacArg::Reg8(pos) => {
+            instr = create_x86instr(X86Type::MovZX);
+            instr.arg1 = X86Arg::Reg32(X86Reg::R15);
+            instr.arg2 = amd64_op_reg8(*pos);
+        },
+        
+        LtacArg::Reg16(pos) => {
+            instr = create_x86instr(X86Type::MovZX);
+            instr.arg1 = X86Arg::Reg32(X86Reg::R15);
+            instr.arg2 = amd64_op_reg16(*pos);
+        },
+        
+        LtacArg::Reg64(pos) => {
+            instr = create_x86instr(X86Type::Mov);
+            instr.arg1 = X86Arg::Reg64(X86Reg::R15);
+            instr.arg2 = amd64_op_reg64(*pos);
+        },
+        
+        LtacArg::Reg32(pos) => {
+            instr = create_x86instr(X86Type::Mov);
+            instr.arg1 = X86Arg::Reg32(X86Reg::R15);
+            instr.arg2 = amd64_op_reg32(*pos);
+        },
+        
+        _ => return,
+    }
+    
+    x86_code.push(instr);
+    
+    instr = create_x86instr(X86Type::Lea);
+    instr.arg1 = X86Arg::Reg64(X86Reg::R14);
+    instr.arg2 = X86Arg::LclMem(code.name.clone(), true);
+    x86_code.push(instr);
+    
+    instr = create_x86instr(X86Type::Lea);
+    instr.arg1 = X86Arg::Reg64(X86Reg::R14);
+    instr.arg2 = X86Arg::IndexMem(X86Reg::R14, X86Reg::R15, 4);
+    x86_code.push(instr);
+    
+    instr = create_x86instr(X86Type::MovSXD);
+    instr.arg1 = X86Arg::Reg64(X86Reg::R15);
+    instr.arg2 = X86Arg::DwordMem(X86Reg::R14, 0, false);
+    x86_code.push(instr);
+    
+    instr = create_x86instr(X86Type::Add);
+    instr.arg1 = X86Arg::Reg64(X86Reg::R15);
+    instr.arg2 = X86Arg::Reg64(X86Reg::R14);
+    x86_code.push(instr);
+    
+    instr = create_x86instr(X86Type::Jmp);
+    instr.arg1 = X86Arg::Reg64(X86Reg::R15);
+    x86_code.push(instr);
+}
+
 // Builds a string comparison
 pub fn amd64_build_strcmp(x86_code : &mut Vec<X86Instr>) {
     let mut instr2 = create_x86instr(X86Type::Call);
@@ -53,7 +119,35 @@ pub fn amd64_build_strcmp(x86_code : &mut Vec<X86Instr>) {
     x86_code.push(instr2);
 }
 
-fn amd64_build_offset_mem(x86_code : &mut Vec<X86Instr>, pos : i32, offset : i32, size : i32, is_pic : bool) {
+// Returns the base register and position for a memory operand
+// Globals have their address loaded into r14 first; position-independent code
+// has to go through the GOT
+pub fn amd64_mem_base(x86_code : &mut Vec<X86Instr>, arg : &LtacArg, is_pic : bool) -> (X86Reg, i32) {
+    match arg {
+        LtacArg::GlobalMem(ref name) | LtacArg::GlobalPtr(ref name) => {
+            let mut instr : X86Instr;
+            
+            if is_pic {
+                instr = create_x86instr(X86Type::Mov);
+                instr.arg2 = X86Arg::GotMem(name.to_string());
+            } else {
+                instr = create_x86instr(X86Type::Lea);
+                instr.arg2 = X86Arg::LclMem(name.to_string(), true);
+            }
+            
+            instr.arg1 = X86Arg::Reg64(X86Reg::R14);
+            x86_code.push(instr);
+            
+            return (X86Reg::R14, 0);
+        },
+        
+        LtacArg::Mem(pos) | LtacArg::Ptr(pos) => return (X86Reg::RBP, *pos),
+        
+        _ => return (X86Reg::RBP, 0),
+    }
+}
+
+pub fn amd64_build_offset_mem(x86_code : &mut Vec<X86Instr>, pos : i32, offset : i32, size : i32, is_pic : bool) {
     // Load the variable
     let mut instr2 = create_x86instr(X86Type::Mov);
     instr2.arg1 = X86Arg::Reg32(X86Reg::R15);
@@ -79,10 +173,10 @@ fn amd64_build_offset_mem(x86_code : &mut Vec<X86Instr>, pos : i32, offset : i32
     x86_code.push(instr2.clone());
 }
 
-fn amd64_build_offset_reg(x86_code : &mut Vec<X86Instr>, pos : i32, reg : i32, size : i32, is_pic : bool) {
+pub fn amd64_build_offset_reg(x86_code : &mut Vec<X86Instr>, pos : i32, reg : i32, size : i32, is_pic : bool) {
     // Determine the right register
     let src_reg : X86Reg;
-    
+        
         match reg {
             0 => src_reg = X86Reg::RBX,
             1 => src_reg = X86Reg::RCX,
@@ -165,22 +259,6 @@ pub fn amd64_build_instr(x86_code : &mut Vec<X86Instr>, code : &LtacInstr, is_pi
             instr = create_x86instr(X86Type::Mov);
         },
         
-        LtacType::MovF32 => {
-            match &code.arg1 {
-                LtacArg::MemOffsetImm(_p, _o) => instr = create_x86instr(X86Type::Mov),
-                LtacArg::MemOffsetMem(_p, _o, _s) |
-                LtacArg::MemOffsetReg(_p, _o, _s) => instr = create_x86instr(X86Type::Mov),
-                _ => instr = create_x86instr(X86Type::MovSS),
-            }
-        },
-        LtacType::MovF64 => {
-            match &code.arg1 {
-                LtacArg::MemOffsetImm(_p, _o) => instr = create_x86instr(X86Type::Mov),
-                LtacArg::MemOffsetMem(_p, _o, _s) |
-                LtacArg::MemOffsetReg(_p, _o, _s) => instr = create_x86instr(X86Type::Mov),
-                _ => instr = create_x86instr(X86Type::MovSD),
-            }
-        },
         
         LtacType::LdAddr => instr = create_x86instr(X86Type::Lea),
         
@@ -197,16 +275,6 @@ pub fn amd64_build_instr(x86_code : &mut Vec<X86Instr>, code : &LtacInstr, is_pi
         LtacType::U16Mul | LtacType::U32Mul |
         LtacType::U64Mul => instr = create_x86instr(X86Type::IMul),     // TODO: Should be "mul"
         
-        LtacType::F32Add => instr = create_x86instr(X86Type::AddSS),
-        LtacType::F32Sub => instr = create_x86instr(X86Type::SubSS),
-        LtacType::F32Mul => instr = create_x86instr(X86Type::MulSS),
-        LtacType::F32Div => instr = create_x86instr(X86Type::DivSS),
-        
-        LtacType::F64Add => instr = create_x86instr(X86Type::AddSD),
-        LtacType::F64Sub => instr = create_x86instr(X86Type::SubSD),
-        LtacType::F64Mul => instr = create_x86instr(X86Type::MulSD),
-        LtacType::F64Div => instr = create_x86instr(X86Type::DivSD),
-        
         LtacType::And => instr = create_x86instr(X86Type::And),
         LtacType::Or => instr = create_x86instr(X86Type::Or),
         LtacType::Xor => instr = create_x86instr(X86Type::Xor),
@@ -217,12 +285,10 @@ pub fn amd64_build_instr(x86_code : &mut Vec<X86Instr>, code : &LtacInstr, is_pi
         LtacType::I16Cmp | LtacType::U16Cmp |
         LtacType::I32Cmp | LtacType::U32Cmp |
         LtacType::I64Cmp | LtacType::U64Cmp => instr = create_x86instr(X86Type::Cmp),
-        LtacType::F32Cmp => instr = create_x86instr(X86Type::Ucomiss),
-        LtacType::F64Cmp => instr = create_x86instr(X86Type::Ucomisd),
         
         _ => instr = create_x86instr(X86Type::Nop),
     }
-
+    
     // The arguments
     match &code.arg1 {
         
@@ -236,21 +302,21 @@ pub fn amd64_build_instr(x86_code : &mut Vec<X86Instr>, code : &LtacInstr, is_pi
         LtacArg::RetRegI32 | LtacArg::RetRegU32 => instr.arg1 = X86Arg::Reg32(X86Reg::RAX),
         LtacArg::RetRegI64 | LtacArg::RetRegU64 => instr.arg1 = X86Arg::Reg64(X86Reg::RAX),
         
-        LtacArg::RetRegF32 | LtacArg::RetRegF64 => instr.arg1 = X86Arg::Xmm(0),
-        
-        LtacArg::Mem(pos) => {
+        LtacArg::Mem(_) | LtacArg::GlobalMem(_) => {
+            let (base, pos) = amd64_mem_base(x86_code, &code.arg1, is_pic);
+            
             match &code.arg2 {
-                LtacArg::Byte(_v) => instr.arg1 = X86Arg::BwordMem(X86Reg::RBP, *pos, is_pic),
-                LtacArg::UByte(_v) => instr.arg1 = X86Arg::BwordMem(X86Reg::RBP, *pos, is_pic),
-                LtacArg::I16(_v) => instr.arg1 = X86Arg::WordMem(X86Reg::RBP, *pos, is_pic),
-                LtacArg::U16(_v) => instr.arg1 = X86Arg::WordMem(X86Reg::RBP, *pos, is_pic),
-                LtacArg::I32(_v) => instr.arg1 = X86Arg::DwordMem(X86Reg::RBP, *pos, is_pic),
-                LtacArg::U32(_v) => instr.arg1 = X86Arg::DwordMem(X86Reg::RBP, *pos, is_pic),
-                LtacArg::I64(_v) => instr.arg1 = X86Arg::QwordMem(X86Reg::RBP, *pos, is_pic),
-                LtacArg::U64(_v) => instr.arg1 = X86Arg::QwordMem(X86Reg::RBP, *pos, is_pic),
-                LtacArg::PtrLcl(_v) => instr.arg1 = X86Arg::QwordMem(X86Reg::RBP, *pos, is_pic),
-                LtacArg::Ptr(_v) => instr.arg1 = X86Arg::QwordMem(X86Reg::RBP, *pos, is_pic),
-                _ => instr.arg1 = X86Arg::Mem(X86Reg::RBP, *pos, is_pic),
+                LtacArg::Byte(_v) => instr.arg1 = X86Arg::BwordMem(base.clone(), pos, is_pic),
+                LtacArg::UByte(_v) => instr.arg1 = X86Arg::BwordMem(base.clone(), pos, is_pic),
+                LtacArg::I16(_v) => instr.arg1 = X86Arg::WordMem(base.clone(), pos, is_pic),
+                LtacArg::U16(_v) => instr.arg1 = X86Arg::WordMem(base.clone(), pos, is_pic),
+                LtacArg::I32(_v) => instr.arg1 = X86Arg::DwordMem(base.clone(), pos, is_pic),
+                LtacArg::U32(_v) => instr.arg1 = X86Arg::DwordMem(base.clone(), pos, is_pic),
+                LtacArg::I64(_v) => instr.arg1 = X86Arg::QwordMem(base.clone(), pos, is_pic),
+                LtacArg::U64(_v) => instr.arg1 = X86Arg::QwordMem(base.clone(), pos, is_pic),
+                LtacArg::PtrLcl(_v) => instr.arg1 = X86Arg::QwordMem(base.clone(), pos, is_pic),
+                LtacArg::Ptr(_v) => instr.arg1 = X86Arg::QwordMem(base.clone(), pos, is_pic),
+                _ => instr.arg1 = X86Arg::Mem(base.clone(), pos, is_pic),
             }
         },
         
@@ -318,9 +384,10 @@ pub fn amd64_build_instr(x86_code : &mut Vec<X86Instr>, code : &LtacInstr, is_pi
         LtacArg::RetRegI32 | LtacArg::RetRegU32 => instr.arg2 = X86Arg::Reg32(X86Reg::RAX),
         LtacArg::RetRegI64 | LtacArg::RetRegU64 => instr.arg2 = X86Arg::Reg64(X86Reg::RAX),
         
-        LtacArg::RetRegF32 | LtacArg::RetRegF64 => instr.arg2 = X86Arg::Xmm(0),
-        
-        LtacArg::Mem(pos) => instr.arg2 = X86Arg::Mem(X86Reg::RBP, *pos, is_pic),
+        LtacArg::Mem(_) | LtacArg::GlobalMem(_) => {
+            let (base, pos) = amd64_mem_base(x86_code, &code.arg2, is_pic);
+            instr.arg2 = X86Arg::Mem(base, pos, is_pic);
+        },
         
         LtacArg::MemOffsetImm(pos, offset) => {
             let mut instr2 = create_x86instr(X86Type::Mov);
@@ -369,6 +436,21 @@ pub fn amd64_build_instr(x86_code : &mut Vec<X86Instr>, code : &LtacInstr, is_pi
         _ => {},
     }
     
+    // Only a move to a register can take an immediate wider than 32 bits,
+    // so anything else goes through r14 first
+    if let X86Arg::Imm64(val) = instr.arg2 {
+        let to_reg = matches!(instr.arg1, X86Arg::Reg64(_)) && instr.instr_type == X86Type::Mov;
+        
+        if val != (val as i32) as i64 && !to_reg {
+            let mut instr2 = create_x86instr(X86Type::Mov);
+            instr2.arg1 = X86Arg::Reg64(X86Reg::R14);
+            instr2.arg2 = X86Arg::Imm64(val);
+            x86_code.push(instr2);
+            
+            instr.arg2 = X86Arg::Reg64(X86Reg::R14);
+        }
+    }
+    
     // Special cases
     // Bytes
     if code.arg1 == LtacArg::RetRegI8 {
@@ -381,7 +463,7 @@ pub fn amd64_build_instr(x86_code : &mut Vec<X86Instr>, code : &LtacInstr, is_pi
         instr2.arg1 = X86Arg::Reg32(X86Reg::RAX);
         instr2.arg2 = X86Arg::Reg8(X86Reg::RAX);
         x86_code.push(instr2);
-        
+    
     // Short
     } else if code.arg1 == LtacArg::RetRegI16 {
         let mut instr2 = create_x86instr(X86Type::MovSX);
@@ -394,10 +476,10 @@ pub fn amd64_build_instr(x86_code : &mut Vec<X86Instr>, code : &LtacInstr, is_pi
         instr2.arg2 = X86Arg::Reg16(X86Reg::RAX);
         x86_code.push(instr2);
     }
-        
+   
    x86_code.push(instr);
 }
-    
+
 // A utility function for build immediate moves    
 pub fn amd64_build_imm(x86_code : &mut Vec<X86Instr>, val : i32, size : i32) -> X86Arg {
     let arg : X86Arg;
@@ -426,7 +508,7 @@ pub fn amd64_build_div(x86_code : &mut Vec<X86Instr>, code : &LtacInstr, is_pic
     xor.arg1 = X86Arg::Reg64(X86Reg::RDX);
     xor.arg2 = X86Arg::Reg64(X86Reg::RDX);
     x86_code.push(xor);
-
+    
     // Create and build the instruction
     let mut instr = create_x86instr(X86Type::IDiv);
     let mut dest_instr = create_x86instr(X86Type::Mov);
@@ -486,15 +568,17 @@ pub fn amd64_build_div(x86_code : &mut Vec<X86Instr>, code : &LtacInstr, is_pic
         LtacArg::Reg32(pos) => instr.arg1 = amd64_op_reg32(*pos),
         LtacArg::Reg64(pos) => instr.arg1 = amd64_op_reg64(*pos),
         
-        LtacArg::Mem(pos) => {
+        LtacArg::Mem(_) | LtacArg::GlobalMem(_) => {
+            let (base, pos) = amd64_mem_base(x86_code, &code.arg2, is_pic);
+            
             if code.instr_type == LtacType::I8Div || code.instr_type == LtacType::I8Mod {
-                instr.arg1 = X86Arg::BwordMem(X86Reg::RBP, *pos, is_pic);
+                instr.arg1 = X86Arg::BwordMem(base, pos, is_pic);
             } else if code.instr_type == LtacType::I16Div || code.instr_type == LtacType::I16Mod {
-                instr.arg1 = X86Arg::WordMem(X86Reg::RBP, *pos, is_pic);
+                instr.arg1 = X86Arg::WordMem(base, pos, is_pic);
             } else if code.instr_type == LtacType::I64Div || code.instr_type == LtacType::I64Mod {
-                instr.arg1 = X86Arg::QwordMem(X86Reg::RBP, *pos, is_pic);
+                instr.arg1 = X86Arg::QwordMem(base, pos, is_pic);
             } else {
-                instr.arg1 = X86Arg::DwordMem(X86Reg::RBP, *pos, is_pic);
+                instr.arg1 = X86Arg::DwordMem(base, pos, is_pic);
             }
         },
         
@@ -539,7 +623,7 @@ pub fn amd64_build_byte_mul(x86_code : &mut Vec<X86Instr>, code : &LtacInstr, is
     xor.arg1 = X86Arg::Reg32(X86Reg::RAX);
     xor.arg2 = X86Arg::Reg32(X86Reg::RAX);
     x86_code.push(xor);
-
+    
     // Create and build the instruction
     let mut instr = create_x86instr(X86Type::IMul8);
     
@@ -565,7 +649,10 @@ pub fn amd64_build_byte_mul(x86_code : &mut Vec<X86Instr>, code : &LtacInstr, is
     
     match &code.arg2 {
         LtacArg::Reg8(pos) => instr.arg1 = amd64_op_reg8(*pos),
-        LtacArg::Mem(pos) => instr.arg1 = X86Arg::BwordMem(X86Reg::RBP, *pos, is_pic),
+        LtacArg::Mem(_) | LtacArg::GlobalMem(_) => {
+            let (base, pos) = amd64_mem_base(x86_code, &code.arg2, is_pic);
+            instr.arg1 = X86Arg::BwordMem(base, pos, is_pic);
+        },
         
         LtacArg::Byte(val) => instr.arg1 = amd64_build_imm(x86_code, *val as i32, 8),
         LtacArg::UByte(val) => instr.arg1 = amd64_build_imm(x86_code, *val as i32, 8),
@@ -22,30 +22,81 @@ use std::io::prelude::*;
 use std::io::BufWriter;
 use std::fs::File;
 
-use ltac::ltac::{LtacFile, LtacData, LtacDataType, LtacType, LtacInstr};
+use parser::Arch;
+use ltac::ltac::{LtacFile, LtacData, LtacDataType, LtacType, LtacInstr, escape_string};
+use ltac::backend::{Backend, Pass, Feature, CodeOptions};
+use ltac::toolchain::TargetInfo;
+use ltac::workspace::Workspace;
+use ltac::dwarf;
+use ltac::dwarf::DwarfTarget;
+use ltac::abi;
 
 // Import and use local modules
 mod asm;
 mod call;
 mod func;
 mod instr;
+mod float;
 
 use asm::*;
 use call::*;
 use func::*;
 use instr::*;
+use float::*;
+
+pub struct X86Backend;
+
+impl Backend for X86Backend {
+    fn name(&self) -> &'static str {
+        "x86_64"
+    }
+    
+    fn arch(&self) -> Arch {
+        Arch::X86_64
+    }
+    
+    // x86 can use memory operands directly, so it does not need the RISC pass
+    fn passes(&self) -> Vec<Pass> {
+        Vec::new()
+    }
+    
+    fn supports(&self, feature : Feature) -> bool {
+        match feature {
+            Feature::Pic | Feature::DebugInfo | Feature::SharedLib => return true,
+        }
+    }
+    
+    fn compile(&self, ltac_file : &LtacFile, options : &CodeOptions, ws : &Workspace, name : &String) -> io::Result<()> {
+        compile(ltac_file, &ws.file(name, "asm"), options.pic, options.debug)
+    }
+    
+    fn target(&self) -> TargetInfo {
+        TargetInfo {
+            prefixes : &["x86_64-linux-gnu"],
+            crt_dir : "/usr/lib/x86_64-linux-gnu",
+            dynamic_linker : "/lib64/ld-linux-x86-64.so.2",
+            has_runtime : true,
+        }
+    }
+}
+
+// Variables are below the base pointer (rbp is DWARF register 6)
+const DWARF_TARGET : DwarfTarget = DwarfTarget {
+    frame_reg : 6,
+    var_offset : amd64_var_offset,
+};
+
+fn amd64_var_offset(_stack_size : i32, pos : i32) -> i32 {
+    -pos
+}
 
 // The entry point
-pub fn compile(ltac_file : &LtacFile, pic : bool) -> io::Result<()> {
+fn compile(ltac_file : &LtacFile, name : &String, pic : bool, debug : bool) -> io::Result<()> {
     // First, translate
     let mut x86_code : Vec<X86Instr> = Vec::new();
-    translate_code(&mut x86_code, &ltac_file.code, pic);
+    translate_code(&mut x86_code, &ltac_file.code, pic, debug);
     
     // Write it out
-    let mut name = "/tmp/".to_string();
-    name.push_str(&ltac_file.name);
-    name.push_str(".asm");
-    
     let file = File::create(&name)?;
     let mut writer = BufWriter::new(file);
     
@@ -54,8 +105,24 @@ pub fn compile(ltac_file : &LtacFile, pic : bool) -> io::Result<()> {
         .expect("[AMD64_setup] Write failed.");
     
     write_data(&mut writer, &ltac_file.data, pic);
+    
+    let mut line = ".text\n".to_string();
+    
+    if debug {
+        line.push_str(&dwarf::write_text_start(ltac_file));
+    }
+    
+    writer.write(&line.into_bytes())
+        .expect("[AMD64_code] Write failed");
+    
     write_code(&mut writer, &x86_code);
     
+    if debug {
+        let line = dwarf::write_text_end(ltac_file, &DWARF_TARGET);
+        writer.write(&line.into_bytes())
+            .expect("[AMD64_debug] Write failed.");
+    }
+    
     Ok(())
 }
 
@@ -66,13 +133,13 @@ fn write_data(writer : &mut BufWriter<File>, data : &Vec<LtacData>, pic : bool)
     if !pic {
         line.push_str(".data\n");
     }
-
+    
     for data in data.iter() {
         match &data.data_type {
             LtacDataType::StringL => {
                 line.push_str(&data.name);
                 line.push_str(": .string \"");
-                line.push_str(&data.val);
+                line.push_str(&escape_string(&data.val));
                 line.push_str("\"\n");
             },
             
@@ -89,30 +156,161 @@ fn write_data(writer : &mut BufWriter<File>, data : &Vec<LtacData>, pic : bool)
                 line.push_str(&data.val);
                 line.push_str("\n");
             },
+            
+            // Globals and tables are written below
+            _ => {},
         }
     }
     
+    line.push_str(&amd64_write_globals(data));
+    line.push_str(&amd64_write_tables(data));
     line.push_str("\n");
     
     writer.write(&line.into_bytes())
         .expect("[AMD64_data] Write failed in .data");
 }
 
+// Writes the global variables
+// These always go in the writable sections, even for position-independent code
+fn amd64_write_globals(data : &Vec<LtacData>) -> String {
+    let mut line = String::new();
+    let mut bss = String::new();
+    
+    for data in data.iter() {
+        let (directive, size) = match &data.data_type {
+            LtacDataType::GlobalB => (".byte", 1),
+            LtacDataType::GlobalW => (".word", 2),
+            LtacDataType::GlobalDW => (".long", 4),
+            LtacDataType::GlobalQW | LtacDataType::GlobalStr => (".quad", 8),
+            LtacDataType::GlobalBss => (".zero", data.val.parse::<i32>().unwrap()),
+            _ => continue,
+        };
+        
+        let mut entry = String::new();
+        entry.push_str(".global ");
+        entry.push_str(&data.name);
+        entry.push_str("\n.type ");
+        entry.push_str(&data.name);
+        entry.push_str(", @object\n.size ");
+        entry.push_str(&data.name);
+        entry.push_str(", ");
+        entry.push_str(&size.to_string());
+        entry.push_str("\n.balign ");
+        entry.push_str(&size.to_string());
+        entry.push_str("\n");
+        entry.push_str(&data.name);
+        entry.push_str(": ");
+        entry.push_str(directive);
+        entry.push_str(" ");
+        entry.push_str(&data.val);
+        entry.push_str("\n");
+        
+        if data.data_type == LtacDataType::GlobalBss {
+            bss.push_str(&entry);
+        } else {
+            line.push_str(&entry);
+        }
+    }
+    
+    if line.len() > 0 {
+        line.insert_str(0, "\n.data\n");
+    }
+    
+    if bss.len() > 0 {
+        line.push_str("\n.bss\n");
+        line.push_str(&bss);
+    }
+    
+    line
+}
+
+// Writes the constant tables
+// Tables of strings hold addresses, so they are only read-only once they are relocated
+fn amd64_write_tables(data : &Vec<LtacData>) -> String {
+    let mut line = String::new();
+    let mut relro = String::new();
+    
+    for data in data.iter() {
+        let (directive, size) = match &data.data_type {
+            LtacDataType::TableB => (".byte", 1),
+            LtacDataType::TableW => (".word", 2),
+            LtacDataType::TableDW => (".long", 4),
+            LtacDataType::TableQW | LtacDataType::TableStr => (".quad", 8),
+            _ => continue,
+        };
+        
+        let mut entry = String::new();
+        entry.push_str(".balign ");
+        entry.push_str(&size.to_string());
+        entry.push_str("\n");
+        entry.push_str(&data.name);
+        entry.push_str(": ");
+        entry.push_str(directive);
+        entry.push_str(" ");
+        entry.push_str(&data.val);
+        entry.push_str("\n");
+        
+        if data.data_type == LtacDataType::TableStr {
+            relro.push_str(&entry);
+        } else {
+            line.push_str(&entry);
+        }
+    }
+    
+    if line.len() > 0 {
+        line.insert_str(0, "\n.section .rodata\n");
+    }
+    
+    if relro.len() > 0 {
+        line.push_str("\n.section .data.rel.ro\n");
+        line.push_str(&relro);
+    }
+    
+    line
+}
+
 // Translates the LTAC code section to x86 code
-fn translate_code(x86_code : &mut Vec<X86Instr>, code : &Vec<LtacInstr>, is_pic : bool) {
+// With debugging on, each change in the source line gets a .loc directive, and each function
+// gets an end label for its address range
+fn translate_code(x86_code : &mut Vec<X86Instr>, code : &Vec<LtacInstr>, is_pic : bool, debug : bool) {
+    let mut stack_size = 0;
+    let mut line_no = 0;
+    let mut func_name = String::new();
+    
+    let arg_space = abi::stack_arg_space(code, ARG_REGS);
+    
     for code in code.iter() {
+        if debug && code.instr_type == LtacType::Func {
+            amd64_build_func_end(x86_code, &func_name);
+            func_name = code.name.clone();
+            line_no = 0;
+        }
+        
+        if debug && code.line_no > 0 && code.line_no != line_no {
+            line_no = code.line_no;
+            
+            let mut instr = create_x86instr(X86Type::Loc);
+            instr.arg1 = X86Arg::Imm32(line_no);
+            x86_code.push(instr);
+        }
+        
         match &code.instr_type {
             LtacType::Extern => amd64_build_extern(x86_code, &code),
             LtacType::Label => amd64_build_label(x86_code, &code),
-            LtacType::Func => amd64_build_func(x86_code, &code, is_pic),
-            LtacType::Ret => amd64_build_ret(x86_code),
+            LtacType::Func => {
+                stack_size = code.arg1_val;
+                
+                let space = *arg_space.get(&code.name).unwrap_or(&0);
+                amd64_build_func(x86_code, &code, space, is_pic);
+            },
+            
+            LtacType::Ret => amd64_build_ret(x86_code, stack_size),
             
             LtacType::LdArgI8 | LtacType::LdArgU8 => amd64_build_ldarg(x86_code, &code, is_pic),
             LtacType::LdArgI16 | LtacType::LdArgU16 => amd64_build_ldarg(x86_code, &code, is_pic),
             LtacType::LdArgI32 | LtacType::LdArgU32 => amd64_build_ldarg(x86_code, &code, is_pic),
             LtacType::LdArgI64 | LtacType::LdArgU64 => amd64_build_ldarg(x86_code, &code, is_pic),
-            //LtacType::LdArgF32 => amd64_build_ldarg_float(writer, &code),
-            //LtacType::LdArgF64 => amd64_build_ldarg_float(writer, &code),
+            LtacType::LdArgF32 | LtacType::LdArgF64 => amd64_build_ldarg_float(x86_code, &code, is_pic),
             LtacType::LdArgPtr => amd64_build_ldarg(x86_code, &code, is_pic),
             
             // TODO: Combine this to reduce lines
@@ -122,10 +320,19 @@ fn translate_code(x86_code : &mut Vec<X86Instr>, code : &Vec<LtacInstr>, is_pic
             LtacType::Bfl | LtacType::Bfle => amd64_build_jump(x86_code, &code),
             LtacType::Bg | LtacType::Bge => amd64_build_jump(x86_code, &code),
             LtacType::Bfg | LtacType::Bfge => amd64_build_jump(x86_code, &code),
+            LtacType::Bul | LtacType::Bule => amd64_build_jump(x86_code, &code),
+            LtacType::Bug | LtacType::Buge => amd64_build_jump(x86_code, &code),
+            
+            LtacType::BrTable => amd64_build_br_table(x86_code, &code),
+            LtacType::TableEntry => {
+                let mut instr = create_x86instr(X86Type::Long);
+                instr.name = code.name.clone();
+                x86_code.push(instr);
+            },
             
             LtacType::PushArg => amd64_build_pusharg(x86_code, &code, false, is_pic),
             LtacType::KPushArg => amd64_build_pusharg(x86_code, &code, true, is_pic),
-            LtacType::Call => amd64_build_call(x86_code, &code),
+            LtacType::Call => amd64_build_call(x86_code, &code, is_pic),
             LtacType::Syscall => amd64_build_syscall(x86_code),
             
             LtacType::StrCmp => amd64_build_strcmp(x86_code),
@@ -143,29 +350,45 @@ fn translate_code(x86_code : &mut Vec<X86Instr>, code : &Vec<LtacInstr>, is_pic
             LtacType::I64Div | LtacType::U64Div => amd64_build_div(x86_code, &code, is_pic),
             LtacType::I64Mod | LtacType::U64Mod => amd64_build_div(x86_code, &code, is_pic),
             
+            LtacType::MovF32 | LtacType::MovF64 => amd64_build_float(x86_code, &code, is_pic),
+            LtacType::F32Add | LtacType::F32Sub |
+            LtacType::F32Mul | LtacType::F32Div => amd64_build_float(x86_code, &code, is_pic),
+            LtacType::F64Add | LtacType::F64Sub |
+            LtacType::F64Mul | LtacType::F64Div => amd64_build_float(x86_code, &code, is_pic),
+            LtacType::F32Cmp | LtacType::F64Cmp
+            | LtacType::CvtF32F64 => amd64_build_float(x86_code, &code, is_pic),
+            
             // Everything else uses the common build instruction function
             _ => amd64_build_instr(x86_code, &code, is_pic),
         }
     }
+    
+    if debug {
+        amd64_build_func_end(x86_code, &func_name);
+    }
 }
 
 // Writes the .text section
 fn write_code(writer : &mut BufWriter<File>, code : &Vec<X86Instr>) {
-    let line = ".text\n".to_string();
-    writer.write(&line.into_bytes())
-        .expect("[AMD64_code] Write failed");
-
     for code in code.iter() {
         match &code.instr_type {
+            X86Type::Loc => amd64_write_loc(writer, &code),
+            
+            // Jumps through a register have an operand instead of a name
+            X86Type::Jmp if code.name.len() == 0 => amd64_write_instr(writer, &code, 1),
+            
             X86Type::Extern | X86Type::Global
             | X86Type::Type | X86Type::Label
-            | X86Type::Jmp
+            | X86Type::Long | X86Type::Jmp
             | X86Type::Je | X86Type::Jne
             | X86Type::Jl | X86Type::Jle
             | X86Type::Jg | X86Type::Jge
             | X86Type::Ja | X86Type::Jae
-            | X86Type::Jb | X86Type::Jbe
-            | X86Type::Call => amd64_write_named(writer, &code),
+            | X86Type::Jb | X86Type::Jbe => amd64_write_named(writer, &code),
+            
+            // Indirect calls have an operand instead of a name
+            X86Type::Call if code.name.len() == 0 => amd64_write_instr(writer, &code, 1),
+            X86Type::Call => amd64_write_named(writer, &code),
             
             X86Type::Leave | X86Type::Ret 
             | X86Type::Syscall => amd64_write_instr(writer, &code, 0),
@@ -189,6 +412,7 @@ fn amd64_write_named(writer : &mut BufWriter<File>, code : &X86Instr) {
         X86Type::Global => line.push_str("\n.global "),
         X86Type::Type => line.push_str(".type "),
         X86Type::Call => line.push_str("  call "),
+        X86Type::Long => line.push_str("  .long "),
         
         X86Type::Jmp => line.push_str("  jmp "),
         X86Type::Je => line.push_str("  je "),
@@ -211,6 +435,8 @@ fn amd64_write_named(writer : &mut BufWriter<File>, code : &X86Instr) {
         line.push_str(":");
     } else if code.instr_type == X86Type::Type {
         line.push_str(", @function");
+    } else if code.instr_type == X86Type::Long {
+        line.push_str("-.");
     }
     
     line.push_str("\n");
@@ -219,6 +445,17 @@ fn amd64_write_named(writer : &mut BufWriter<File>, code : &X86Instr) {
         .expect("[AMD64_build_extern] Write failed.");
 }
 
+// Writes a source line directive
+fn amd64_write_loc(writer : &mut BufWriter<File>, code : &X86Instr) {
+    let line_no = match &code.arg1 {
+        X86Arg::Imm32(val) => *val,
+        _ => return,
+    };
+    
+    writer.write(&dwarf::write_loc(line_no).into_bytes())
+        .expect("[AMD64_write_loc] Write failed.");
+}
+
 // Writes an x86-instruction
 fn amd64_write_instr(writer : &mut BufWriter<File>, code : &X86Instr, op_count : i32) {
     let mut line = "  ".to_string();
@@ -229,10 +466,15 @@ fn amd64_write_instr(writer : &mut BufWriter<File>, code : &X86Instr, op_count :
         X86Type::Syscall => line.push_str("syscall"),
         
         X86Type::Push => line.push_str("push"),
+        X86Type::Call => line.push_str("call"),
+        X86Type::Jmp => line.push_str("jmp"),
         X86Type::Lea => line.push_str("lea"),
         X86Type::Mov => line.push_str("mov"),
         X86Type::MovZX => line.push_str("movzx"),
         X86Type::MovSX => line.push_str("movsx"),
+        X86Type::MovSXD => line.push_str("movsxd"),
+        X86Type::MovSS => line.push_str("movss"),
+        X86Type::MovSD => line.push_str("movsd"),
         
         X86Type::Add => line.push_str("add"),
         X86Type::Sub => line.push_str("sub"),
@@ -247,7 +489,21 @@ fn amd64_write_instr(writer : &mut BufWriter<File>, code : &X86Instr, op_count :
         X86Type::Shl => line.push_str("shl"),
         X86Type::Shr => line.push_str("shr"),
         
+        X86Type::AddSS => line.push_str("addss"),
+        X86Type::SubSS => line.push_str("subss"),
+        X86Type::MulSS => line.push_str("mulss"),
+        X86Type::DivSS => line.push_str("divss"),
+        
+        X86Type::AddSD => line.push_str("addsd"),
+        X86Type::SubSD => line.push_str("subsd"),
+        X86Type::MulSD => line.push_str("mulsd"),
+        X86Type::DivSD => line.push_str("divsd"),
+        
         X86Type::Cmp => line.push_str("cmp"),
+        X86Type::Ucomiss => line.push_str("ucomiss"),
+        X86Type::Ucomisd => line.push_str("ucomisd"),
+        
+        X86Type::CvtSS2SD => line.push_str("cvtss2sd"),
         
         _ => {},
     }
@@ -337,6 +593,11 @@ fn amd64_write_operand(arg : &X86Arg) -> String {
              line.push_str(&reg_str);
         },
         
+        X86Arg::Xmm(pos) => {
+            line.push_str("xmm");
+            line.push_str(&pos.to_string());
+        },
+        
         X86Arg::Imm32(val) => line.push_str(&val.to_string()),
         X86Arg::Imm64(val) => line.push_str(&val.to_string()),
         
@@ -375,6 +636,12 @@ fn amd64_write_operand(arg : &X86Arg) -> String {
             }
         },
         
+        X86Arg::GotMem(ref val) => {
+            line.push_str("QWORD PTR ");
+            line.push_str(&val);
+            line.push_str("@GOTPCREL[rip]");
+        },
+        
         X86Arg::ScaleMem(base, reg, scale, is_pic) => {
             let reg_str = reg2str(&reg, 64);
             
@@ -391,6 +658,16 @@ fn amd64_write_operand(arg : &X86Arg) -> String {
             line.push_str("]");
         },
         
+        X86Arg::IndexMem(base, reg, scale) => {
+            line.push_str("[");
+            line.push_str(&reg2str(&base, 64));
+            line.push_str("+");
+            line.push_str(&reg2str(&reg, 64));
+            line.push_str("*");
+            line.push_str(&scale.to_string());
+            line.push_str("]");
+        },
+        
         _ => {},
     }
     
@@ -1,10 +1,12 @@
 #!/bin/bash
 
 cargo build --release
+cargo build --release -p lila-lsp
 
 ./build-stdlib.sh
 
 sudo cp target/release/lilac /usr/bin
+sudo cp target/release/lila-lsp /usr/bin
 
 sudo cp share/lila.lang /usr/share/gtksourceview-2.0/language-specs/
 sudo cp share/lila.lang /usr/share/gtksourceview-3.0/language-specs/
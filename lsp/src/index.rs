@@ -0,0 +1,222 @@
+// This file is part of the Lila compiler
+// Copyright (C) 2020-2021 Patrick Flynn
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; version 2.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, write to the Free Software Foundation, Inc.,
+// 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+
+// The symbol index
+// This finds the declarations in a source file and in every module it uses.
+// It works from the source text rather than the AST, so it still works while the
+// file being edited has errors in it.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use parser::module;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SymbolKind {
+    Function,
+    Constant,
+    Enum,
+    EnumValue,
+}
+
+// Lines and columns start at 0, like in the protocol
+#[derive(Clone)]
+pub struct Symbol {
+    pub name : String,
+    pub kind : SymbolKind,
+    pub file : String,
+    pub line : usize,
+    pub col : usize,
+    pub detail : String,
+}
+
+pub struct Index {
+    pub symbols : Vec<Symbol>,
+}
+
+impl Index {
+    // Symbols from the current file come first, so they win over anything in a module
+    pub fn find(&self, name : &str) -> Option<&Symbol> {
+        self.symbols.iter().find(|s| s.name == name)
+    }
+}
+
+// Builds the index for a source file
+// The text is passed in since the editor may have changes that are not saved yet
+pub fn build_index(path : &str, text : &str, include_core : bool) -> Index {
+    let mut index = Index {
+        symbols : Vec::new(),
+    };
+    
+    index_source(path, text, &mut index.symbols);
+    
+    let mut to_load = find_uses(text);
+    let mut loaded : HashSet<String> = HashSet::new();
+    
+    if include_core {
+        for name in module::CORE_MODULES.iter() {
+            to_load.push(name.to_string());
+        }
+    }
+    
+    while to_load.len() > 0 {
+        let name = to_load.remove(0);
+        let module_path = module::get_module_path(&name);
+        
+        if loaded.contains(&module_path) {
+            continue;
+        }
+        
+        loaded.insert(module_path.clone());
+        
+        let contents = match fs::read_to_string(&module_path) {
+            Ok(c) => c,
+            Err(_e) => continue,
+        };
+        
+        let full_path = match fs::canonicalize(Path::new(&module_path)) {
+            Ok(p) => p.to_string_lossy().to_string(),
+            Err(_e) => module_path.clone(),
+        };
+        
+        index_source(&full_path, &contents, &mut index.symbols);
+        to_load.append(&mut find_uses(&contents));
+    }
+    
+    index
+}
+
+// Finds the declarations in one file
+fn index_source(path : &str, text : &str, symbols : &mut Vec<Symbol>) {
+    for (line_no, line) in text.lines().enumerate() {
+        let decl = match line.find('#') {
+            Some(pos) => line[..pos].trim(),
+            None => line.trim(),
+        };
+        
+        let mut add = |name : &str, kind : SymbolKind, after : usize| {
+            if name.len() == 0 {
+                return;
+            }
+            
+            let col = match line[after..].find(name) {
+                Some(pos) => line[..after + pos].chars().count(),
+                None => 0,
+            };
+            
+            symbols.push(Symbol {
+                name : name.to_string(),
+                kind : kind,
+                file : path.to_string(),
+                line : line_no,
+                col : col,
+                detail : decl.trim_end_matches(';').to_string(),
+            });
+        };
+        
+        let indent = line.len() - line.trim_start().len();
+        
+        if decl.starts_with("func ") || decl.starts_with("extern func ") {
+            let start = decl.find("func ").unwrap() + 5;
+            add(read_name(&decl[start..]), SymbolKind::Function, indent + start);
+        
+        // Syntax: const <type> <name> = <value>; or const <name> : <type> = <value>;
+        } else if decl.starts_with("const ") {
+            let rest = decl[6..].trim_start();
+            let name = read_name(rest);
+            
+            if rest[name.len()..].trim_start().starts_with(':') {
+                add(name, SymbolKind::Constant, indent + 6);
+            } else if let Some(part) = rest.split_whitespace().nth(1) {
+                add(read_name(part), SymbolKind::Constant, indent + 6);
+            }
+        
+        // Syntax: enum <name> = <value>, <value>, ...;
+        } else if decl.starts_with("enum ") {
+            add(read_name(decl[5..].trim_start()), SymbolKind::Enum, indent + 5);
+            
+            if let Some(pos) = decl.find('=') {
+                for value in decl[pos + 1..].split(',') {
+                    add(read_name(value.trim()), SymbolKind::EnumValue, indent + pos);
+                }
+            }
+        }
+    }
+}
+
+// Returns the modules named in "use" statements
+// Statements for other architectures are skipped, just like the compiler does
+fn find_uses(text : &str) -> Vec<String> {
+    let mut uses : Vec<String> = Vec::new();
+    
+    for line in text.lines() {
+        let line = line.trim();
+        
+        if !line.starts_with("use ") {
+            continue;
+        }
+        
+        let mut parts = line[4..].trim_end_matches(';').split_whitespace();
+        let name = match parts.next() {
+            Some(n) => n.trim_end_matches(';'),
+            None => continue,
+        };
+        
+        if parts.next() == Some("if") {
+            let arch = parts.next().unwrap_or("").trim_matches('"');
+            
+            if arch != std::env::consts::ARCH {
+                continue;
+            }
+        }
+        
+        uses.push(name.to_string());
+    }
+    
+    uses
+}
+
+// Reads an identifier from the start of a string
+fn read_name(input : &str) -> &str {
+    let end = input.find(|c : char| !is_name_char(c)).unwrap_or(input.len());
+    &input[..end]
+}
+
+pub fn is_name_char(c : char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// Returns the identifier under the cursor
+pub fn word_at(text : &str, line : usize, character : usize) -> Option<String> {
+    let chars : Vec<char> = text.lines().nth(line)?.chars().collect();
+    let mut start = character.min(chars.len());
+    let mut end = start;
+    
+    while start > 0 && is_name_char(chars[start - 1]) {
+        start -= 1;
+    }
+    
+    while end < chars.len() && is_name_char(chars[end]) {
+        end += 1;
+    }
+    
+    if start == end {
+        return None;
+    }
+    
+    Some(chars[start..end].iter().collect())
+}
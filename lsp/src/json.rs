@@ -0,0 +1,363 @@
+// This file is part of the Lila compiler
+// Copyright (C) 2020-2021 Patrick Flynn
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; version 2.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, write to the Free Software Foundation, Inc.,
+// 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+
+// A small JSON reader and writer
+// The language server only needs enough JSON to speak JSON-RPC, so this avoids
+// pulling in any outside crates.
+
+use std::fmt;
+
+use parser::syntax::json_string;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    // Returns a member of an object
+    pub fn get(&self, key : &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => {
+                for (name, value) in members.iter() {
+                    if name == key {
+                        return Some(value);
+                    }
+                }
+                
+                return None;
+            },
+            
+            _ => return None,
+        }
+    }
+    
+    // Follows a path of object members, such as ["textDocument", "uri"]
+    pub fn path(&self, keys : &[&str]) -> Option<&Json> {
+        let mut current = self;
+        
+        for key in keys.iter() {
+            current = current.get(key)?;
+        }
+        
+        Some(current)
+    }
+    
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::Str(s) => return Some(s),
+            _ => return None,
+        }
+    }
+    
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Json::Number(n) => return Some(*n as i64),
+            _ => return None,
+        }
+    }
+    
+    pub fn as_array(&self) -> Option<&Vec<Json>> {
+        match self {
+            Json::Array(items) => return Some(items),
+            _ => return None,
+        }
+    }
+}
+
+// Helpers for building objects
+pub fn object(members : Vec<(&str, Json)>) -> Json {
+    let mut list : Vec<(String, Json)> = Vec::new();
+    
+    for (name, value) in members {
+        list.push((name.to_string(), value));
+    }
+    
+    Json::Object(list)
+}
+
+pub fn string(value : &str) -> Json {
+    Json::Str(value.to_string())
+}
+
+pub fn number(value : i64) -> Json {
+    Json::Number(value as f64)
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            
+            // Whole numbers are written without a fraction, since most clients expect integers
+            Json::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{}", *n as i64),
+            Json::Number(n) => write!(f, "{}", n),
+            
+            Json::Str(s) => write!(f, "{}", json_string(s)),
+            
+            Json::Array(items) => {
+                write!(f, "[")?;
+                
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    
+                    write!(f, "{}", item)?;
+                }
+                
+                write!(f, "]")
+            },
+            
+            Json::Object(members) => {
+                write!(f, "{{")?;
+                
+                for (i, (name, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    
+                    write!(f, "{}:{}", json_string(name), value)?;
+                }
+                
+                write!(f, "}}")
+            },
+        }
+    }
+}
+
+// Parses a JSON document
+pub fn parse(input : &str) -> Result<Json, String> {
+    let mut reader = Reader {
+        chars : input.chars().collect(),
+        pos : 0,
+    };
+    
+    let value = reader.parse_value()?;
+    reader.skip_whitespace();
+    
+    if reader.pos < reader.chars.len() {
+        return Err("Unexpected data after JSON value.".to_string());
+    }
+    
+    Ok(value)
+}
+
+struct Reader {
+    chars : Vec<char>,
+    pos : usize,
+}
+
+impl Reader {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+    
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.pos += 1;
+        c
+    }
+    
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            
+            self.pos += 1;
+        }
+    }
+    
+    fn expect_word(&mut self, word : &str, value : Json) -> Result<Json, String> {
+        for c in word.chars() {
+            if self.next() != Some(c) {
+                return Err(format!("Invalid literal; expected \"{}\".", word));
+            }
+        }
+        
+        Ok(value)
+    }
+    
+    fn parse_value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        
+        match self.peek() {
+            Some('n') => return self.expect_word("null", Json::Null),
+            Some('t') => return self.expect_word("true", Json::Bool(true)),
+            Some('f') => return self.expect_word("false", Json::Bool(false)),
+            Some('"') => return Ok(Json::Str(self.parse_string()?)),
+            Some('[') => return self.parse_array(),
+            Some('{') => return self.parse_object(),
+            Some(c) if c == '-' || c.is_ascii_digit() => return self.parse_number(),
+            Some(c) => return Err(format!("Unexpected character: {}", c)),
+            None => return Err("Unexpected end of input.".to_string()),
+        }
+    }
+    
+    fn parse_number(&mut self) -> Result<Json, String> {
+        let start = self.pos;
+        
+        while let Some(c) = self.peek() {
+            match c {
+                '0' ..= '9' | '-' | '+' | '.' | 'e' | 'E' => self.pos += 1,
+                _ => break,
+            }
+        }
+        
+        let text : String = self.chars[start..self.pos].iter().collect();
+        
+        match text.parse::<f64>() {
+            Ok(n) => return Ok(Json::Number(n)),
+            Err(_e) => return Err(format!("Invalid number: {}", text)),
+        }
+    }
+    
+    fn parse_hex4(&mut self) -> Result<u32, String> {
+        let mut value = 0;
+        
+        for _i in 0..4 {
+            let digit = match self.next().and_then(|c| c.to_digit(16)) {
+                Some(d) => d,
+                None => return Err("Invalid unicode escape.".to_string()),
+            };
+            
+            value = value * 16 + digit;
+        }
+        
+        Ok(value)
+    }
+    
+    fn parse_string(&mut self) -> Result<String, String> {
+        let mut output = String::new();
+        self.pos += 1;
+        
+        loop {
+            match self.next() {
+                Some('"') => break,
+                
+                Some('\\') => {
+                    match self.next() {
+                        Some('"') => output.push('"'),
+                        Some('\\') => output.push('\\'),
+                        Some('/') => output.push('/'),
+                        Some('b') => output.push('\u{8}'),
+                        Some('f') => output.push('\u{c}'),
+                        Some('n') => output.push('\n'),
+                        Some('r') => output.push('\r'),
+                        Some('t') => output.push('\t'),
+                        
+                        // Characters outside the basic plane come as surrogate pairs
+                        Some('u') => {
+                            let mut code = self.parse_hex4()?;
+                            
+                            if code >= 0xD800 && code < 0xDC00 {
+                                if self.next() != Some('\\') || self.next() != Some('u') {
+                                    return Err("Invalid surrogate pair.".to_string());
+                                }
+                                
+                                let low = self.parse_hex4()?;
+                                
+                                if low < 0xDC00 || low > 0xDFFF {
+                                    return Err("Invalid surrogate pair.".to_string());
+                                }
+                                
+                                code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                            }
+                            
+                            output.push(std::char::from_u32(code).unwrap_or('\u{FFFD}'));
+                        },
+                        
+                        _ => return Err("Invalid escape sequence.".to_string()),
+                    }
+                },
+                
+                Some(c) => output.push(c),
+                None => return Err("Unterminated string.".to_string()),
+            }
+        }
+        
+        Ok(output)
+    }
+    
+    fn parse_array(&mut self) -> Result<Json, String> {
+        let mut items : Vec<Json> = Vec::new();
+        self.pos += 1;
+        self.skip_whitespace();
+        
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(Json::Array(items));
+        }
+        
+        loop {
+            items.push(self.parse_value()?);
+            self.skip_whitespace();
+            
+            match self.next() {
+                Some(',') => continue,
+                Some(']') => break,
+                _ => return Err("Expected ',' or ']' in array.".to_string()),
+            }
+        }
+        
+        Ok(Json::Array(items))
+    }
+    
+    fn parse_object(&mut self) -> Result<Json, String> {
+        let mut members : Vec<(String, Json)> = Vec::new();
+        self.pos += 1;
+        self.skip_whitespace();
+        
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(Json::Object(members));
+        }
+        
+        loop {
+            self.skip_whitespace();
+            
+            if self.peek() != Some('"') {
+                return Err("Expected member name in object.".to_string());
+            }
+            
+            let name = self.parse_string()?;
+            self.skip_whitespace();
+            
+            if self.next() != Some(':') {
+                return Err("Expected ':' in object.".to_string());
+            }
+            
+            let value = self.parse_value()?;
+            members.push((name, value));
+            self.skip_whitespace();
+            
+            match self.next() {
+                Some(',') => continue,
+                Some('}') => break,
+                _ => return Err("Expected ',' or '}' in object.".to_string()),
+            }
+        }
+        
+        Ok(Json::Object(members))
+    }
+}
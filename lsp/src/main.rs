@@ -0,0 +1,109 @@
+// This file is part of the Lila compiler
+// Copyright (C) 2020-2021 Patrick Flynn
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; version 2.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, write to the Free Software Foundation, Inc.,
+// 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+
+// The Lila language server
+// This speaks JSON-RPC over stdin and stdout. Each message has a Content-Length
+// header, a blank line, and then the JSON body.
+
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::fs::File;
+use std::os::unix::io::FromRawFd;
+use std::process;
+
+mod index;
+mod json;
+mod server;
+
+// Reads one message; returns None at the end of input
+fn read_message(reader : &mut dyn BufRead) -> Option<String> {
+    let mut length : usize = 0;
+    
+    loop {
+        let mut header = String::new();
+        
+        if reader.read_line(&mut header).ok()? == 0 {
+            return None;
+        }
+        
+        let header = header.trim();
+        
+        if header.len() == 0 {
+            break;
+        }
+        
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse().unwrap_or(0);
+        }
+    }
+    
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).ok()?;
+    
+    Some(String::from_utf8_lossy(&body).to_string())
+}
+
+fn write_message(writer : &mut dyn Write, msg : &str) -> io::Result<()> {
+    write!(writer, "Content-Length: {}\r\n\r\n{}", msg.len(), msg)?;
+    writer.flush()
+}
+
+extern "C" {
+    fn dup(fd : i32) -> i32;
+    fn dup2(old_fd : i32, new_fd : i32) -> i32;
+}
+
+// Takes over stdout for the messages, and points the old stdout at stderr
+// The parser runs in this process, so anything it prints can't end up between two messages
+fn take_stdout() -> Option<File> {
+    unsafe {
+        let fd = dup(1);
+        
+        if fd < 0 || dup2(2, 1) < 0 {
+            return None;
+        }
+        
+        Some(File::from_raw_fd(fd))
+    }
+}
+
+fn main() {
+    let stdin = io::stdin();
+    let mut reader = stdin.lock();
+    
+    let mut writer = match take_stdout() {
+        Some(file) => BufWriter::new(file),
+        None => process::exit(1),
+    };
+    
+    let mut server = server::create_server();
+    
+    while let Some(msg) = read_message(&mut reader) {
+        for reply in server.handle(&msg) {
+            if write_message(&mut writer, &reply.to_string()).is_err() {
+                process::exit(1);
+            }
+        }
+        
+        if let Some(code) = server.exit_code {
+            process::exit(code);
+        }
+    }
+    
+    // The client went away without asking us to exit
+    process::exit(1);
+}
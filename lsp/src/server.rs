@@ -0,0 +1,446 @@
+// This file is part of the Lila compiler
+// Copyright (C) 2020-2021 Patrick Flynn
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; version 2.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, write to the Free Software Foundation, Inc.,
+// 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+
+use std::collections::HashMap;
+use std::fs;
+use std::panic;
+
+use parser::Arch;
+use parser::syntax;
+use parser::syntax::{Diagnostic, Severity};
+
+use crate::index;
+use crate::index::{Symbol, SymbolKind};
+use crate::json;
+use crate::json::{Json, object, string, number};
+
+// JSON-RPC error codes
+const PARSE_ERROR : i64 = -32700;
+const METHOD_NOT_FOUND : i64 = -32601;
+const INVALID_REQUEST : i64 = -32600;
+
+#[cfg(target_arch = "x86_64")]
+fn get_arch() -> Arch {
+    Arch::X86_64
+}
+
+#[cfg(target_arch = "aarch64")]
+fn get_arch() -> Arch {
+    Arch::AArch64
+}
+
+#[cfg(target_arch = "riscv64")]
+fn get_arch() -> Arch {
+    Arch::Riscv64
+}
+
+pub struct Server {
+    documents : HashMap<String, String>,
+    published : HashMap<String, Vec<String>>,   // The files each document has diagnostics in
+    shutdown : bool,
+    pub exit_code : Option<i32>,
+}
+
+pub fn create_server() -> Server {
+    Server {
+        documents : HashMap::new(),
+        published : HashMap::new(),
+        shutdown : false,
+        exit_code : None,
+    }
+}
+
+impl Server {
+    
+    // Handles one message from the client, and returns the messages to send back
+    pub fn handle(&mut self, input : &str) -> Vec<Json> {
+        let msg = match json::parse(input) {
+            Ok(m) => m,
+            Err(e) => return vec![error_response(Json::Null, PARSE_ERROR, &e)],
+        };
+        
+        let method = match msg.get("method").and_then(|m| m.as_str()) {
+            Some(m) => m.to_string(),
+            None => return Vec::new(),      // Responses to our own requests; we never send any
+        };
+        
+        let params = msg.get("params").cloned().unwrap_or(Json::Null);
+        
+        // Notifications have no id, and never get a response
+        let id = match msg.get("id") {
+            Some(id) => id.clone(),
+            None => return self.handle_notification(&method, &params),
+        };
+        
+        if self.shutdown && method != "exit" {
+            return vec![error_response(id, INVALID_REQUEST, "The server is shutting down.")];
+        }
+        
+        let result = match method.as_str() {
+            "initialize" => initialize(),
+            "shutdown" => {
+                self.shutdown = true;
+                Json::Null
+            },
+            
+            "textDocument/definition" => self.definition(&params),
+            "textDocument/hover" => self.hover(&params),
+            "textDocument/completion" => self.completion(&params),
+            
+            _ => return vec![error_response(id, METHOD_NOT_FOUND, &format!("Unknown method: {}", method))],
+        };
+        
+        vec![object(vec![
+            ("jsonrpc", string("2.0")),
+            ("id", id),
+            ("result", result),
+        ])]
+    }
+    
+    fn handle_notification(&mut self, method : &str, params : &Json) -> Vec<Json> {
+        let uri = params.path(&["textDocument", "uri"]).and_then(|u| u.as_str()).unwrap_or("").to_string();
+        
+        match method {
+            "exit" => {
+                self.exit_code = Some(if self.shutdown { 0 } else { 1 });
+            },
+            
+            "textDocument/didOpen" => {
+                let text = params.path(&["textDocument", "text"]).and_then(|t| t.as_str()).unwrap_or("");
+                self.documents.insert(uri.clone(), text.to_string());
+                return self.check_document(&uri);
+            },
+            
+            // We only ask for full document changes, so the last change is the new text
+            "textDocument/didChange" => {
+                let changes = params.get("contentChanges").and_then(|c| c.as_array());
+                
+                if let Some(change) = changes.and_then(|c| c.last()) {
+                    let text = change.get("text").and_then(|t| t.as_str()).unwrap_or("");
+                    self.documents.insert(uri, text.to_string());
+                }
+            },
+            
+            "textDocument/didSave" => return self.check_document(&uri),
+            
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                
+                let mut output : Vec<Json> = Vec::new();
+                
+                if let Some(files) = self.published.remove(&uri) {
+                    for file_uri in files.iter() {
+                        output.push(publish(file_uri, Vec::new()));
+                    }
+                }
+                
+                return output;
+            },
+            
+            _ => {},
+        }
+        
+        Vec::new()
+    }
+    
+    // Returns the text of an open document, or reads it from disk
+    fn get_text(&self, uri : &str) -> String {
+        match self.documents.get(uri) {
+            Some(text) => return text.clone(),
+            None => return fs::read_to_string(uri_to_path(uri)).unwrap_or_default(),
+        }
+    }
+    
+    // Runs the parser and type checker on the saved file
+    fn check_document(&mut self, uri : &str) -> Vec<Json> {
+        let path = uri_to_path(uri);
+        
+        // A bug in the parser shouldn't take the whole server down with it
+        let result = panic::catch_unwind(|| parser::get_ast(&path, get_arch(), true));
+        
+        let errors = match result {
+            Ok(Ok(_tree)) => Vec::new(),
+            Ok(Err(errors)) => errors,
+            Err(_e) => vec![syntax::file_error(&path, syntax::E_SEMANTIC, "The parser stopped unexpectedly.".to_string())],
+        };
+        
+        // Errors in a module are reported against the module's header
+        let mut by_file : Vec<(String, Vec<Json>)> = vec![(uri.to_string(), Vec::new())];
+        
+        for error in errors.iter() {
+            let file_uri = if error.file == path || error.file.len() == 0 {
+                uri.to_string()
+            } else {
+                path_to_uri(&error.file)
+            };
+            
+            let diagnostic = to_lsp_diagnostic(error, &file_uri);
+            
+            match by_file.iter_mut().find(|(f, _)| *f == file_uri) {
+                Some((_, list)) => list.push(diagnostic),
+                None => by_file.push((file_uri, vec![diagnostic])),
+            }
+        }
+        
+        let mut output : Vec<Json> = Vec::new();
+        let files : Vec<String> = by_file.iter().map(|(f, _)| f.clone()).collect();
+        
+        // Clear out files that had errors last time but don't now
+        if let Some(old_files) = self.published.get(uri) {
+            for old in old_files.iter() {
+                if !files.contains(old) {
+                    output.push(publish(old, Vec::new()));
+                }
+            }
+        }
+        
+        for (file_uri, diagnostics) in by_file {
+            output.push(publish(&file_uri, diagnostics));
+        }
+        
+        self.published.insert(uri.to_string(), files);
+        output
+    }
+    
+    // Finds the symbol under the cursor
+    fn symbol_at(&self, params : &Json) -> Option<(Symbol, usize, usize, usize)> {
+        let uri = params.path(&["textDocument", "uri"])?.as_str()?;
+        let line = params.path(&["position", "line"])?.as_i64()? as usize;
+        let character = params.path(&["position", "character"])?.as_i64()? as usize;
+        
+        let text = self.get_text(uri);
+        let name = index::word_at(&text, line, character)?;
+        
+        let path = uri_to_path(uri);
+        let idx = index::build_index(&path, &text, true);
+        let symbol = idx.find(&name)?.clone();
+        
+        // Work out where the word starts, so the hover range covers it
+        let chars : Vec<char> = text.lines().nth(line)?.chars().collect();
+        let mut start = character.min(chars.len());
+        
+        while start > 0 && index::is_name_char(chars[start - 1]) {
+            start -= 1;
+        }
+        
+        Some((symbol, line, start, start + name.chars().count()))
+    }
+    
+    fn definition(&self, params : &Json) -> Json {
+        let symbol = match self.symbol_at(params) {
+            Some((s, _, _, _)) => s,
+            None => return Json::Null,
+        };
+        
+        let end = symbol.col + symbol.name.chars().count();
+        
+        object(vec![
+            ("uri", string(&path_to_uri(&symbol.file))),
+            ("range", range(symbol.line, symbol.col, symbol.line, end)),
+        ])
+    }
+    
+    fn hover(&self, params : &Json) -> Json {
+        let (symbol, line, start, end) = match self.symbol_at(params) {
+            Some(s) => s,
+            None => return Json::Null,
+        };
+        
+        let value = format!("```lila\n{}\n```", symbol.detail);
+        
+        object(vec![
+            ("contents", object(vec![
+                ("kind", string("markdown")),
+                ("value", string(&value)),
+            ])),
+            ("range", range(line, start, line, end)),
+        ])
+    }
+    
+    // Completion offers every name from the file and the modules it uses
+    // The client filters the list as the user types
+    fn completion(&self, params : &Json) -> Json {
+        let uri = match params.path(&["textDocument", "uri"]).and_then(|u| u.as_str()) {
+            Some(u) => u,
+            None => return Json::Array(Vec::new()),
+        };
+        
+        let text = self.get_text(uri);
+        let idx = index::build_index(&uri_to_path(uri), &text, true);
+        
+        let mut items : Vec<Json> = Vec::new();
+        let mut seen : Vec<String> = Vec::new();
+        
+        for symbol in idx.symbols.iter() {
+            if seen.contains(&symbol.name) {
+                continue;
+            }
+            
+            seen.push(symbol.name.clone());
+            
+            // These are the CompletionItemKind values from the protocol
+            let kind = match symbol.kind {
+                SymbolKind::Function => 3,
+                SymbolKind::Constant => 21,
+                SymbolKind::Enum => 13,
+                SymbolKind::EnumValue => 20,
+            };
+            
+            items.push(object(vec![
+                ("label", string(&symbol.name)),
+                ("kind", number(kind)),
+                ("detail", string(&symbol.detail)),
+            ]));
+        }
+        
+        Json::Array(items)
+    }
+}
+
+fn initialize() -> Json {
+    object(vec![
+        ("capabilities", object(vec![
+            ("textDocumentSync", object(vec![
+                ("openClose", Json::Bool(true)),
+                ("change", number(1)),
+                ("save", object(vec![("includeText", Json::Bool(false))])),
+            ])),
+            ("definitionProvider", Json::Bool(true)),
+            ("hoverProvider", Json::Bool(true)),
+            ("completionProvider", object(vec![])),
+        ])),
+        ("serverInfo", object(vec![
+            ("name", string("lila-lsp")),
+            ("version", string(env!("CARGO_PKG_VERSION"))),
+        ])),
+    ])
+}
+
+fn error_response(id : Json, code : i64, msg : &str) -> Json {
+    object(vec![
+        ("jsonrpc", string("2.0")),
+        ("id", id),
+        ("error", object(vec![
+            ("code", number(code)),
+            ("message", string(msg)),
+        ])),
+    ])
+}
+
+fn publish(uri : &str, diagnostics : Vec<Json>) -> Json {
+    object(vec![
+        ("jsonrpc", string("2.0")),
+        ("method", string("textDocument/publishDiagnostics")),
+        ("params", object(vec![
+            ("uri", string(uri)),
+            ("diagnostics", Json::Array(diagnostics)),
+        ])),
+    ])
+}
+
+fn position(line : usize, character : usize) -> Json {
+    object(vec![
+        ("line", number(line as i64)),
+        ("character", number(character as i64)),
+    ])
+}
+
+fn range(line1 : usize, col1 : usize, line2 : usize, col2 : usize) -> Json {
+    object(vec![
+        ("start", position(line1, col1)),
+        ("end", position(line2, col2)),
+    ])
+}
+
+// Diagnostics count lines and columns from 1; the protocol counts from 0
+// Whole-file diagnostics go on the first line
+fn to_lsp_diagnostic(error : &Diagnostic, uri : &str) -> Json {
+    let line = (error.line_no.max(1) - 1) as usize;
+    let start = error.col_start.saturating_sub(1);
+    let end = error.col_end.saturating_sub(1).max(start);
+    
+    let severity = match error.severity {
+        Severity::Error => 1,
+        Severity::Warning => 2,
+        Severity::Note => 3,
+    };
+    
+    let mut related : Vec<Json> = Vec::new();
+    
+    for label in error.labels.iter() {
+        let label_line = (label.line_no.max(1) - 1) as usize;
+        let label_start = label.col_start.saturating_sub(1);
+        
+        related.push(object(vec![
+            ("location", object(vec![
+                ("uri", string(uri)),
+                ("range", range(label_line, label_start, label_line, label.col_end.saturating_sub(1).max(label_start))),
+            ])),
+            ("message", string(&label.message)),
+        ]));
+    }
+    
+    object(vec![
+        ("range", range(line, start, line, end)),
+        ("severity", number(severity)),
+        ("code", string(&error.code)),
+        ("source", string("lilac")),
+        ("message", string(&error.message)),
+        ("relatedInformation", Json::Array(related)),
+    ])
+}
+
+// File URIs percent-encode anything unusual in the path
+pub fn uri_to_path(uri : &str) -> String {
+    let path = uri.trim_start_matches("file://");
+    let bytes = path.as_bytes();
+    let mut output : Vec<u8> = Vec::new();
+    let mut i = 0;
+    
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let Ok(b) = u8::from_str_radix(&path[i + 1..i + 3], 16) {
+                output.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        
+        output.push(bytes[i]);
+        i += 1;
+    }
+    
+    String::from_utf8_lossy(&output).to_string()
+}
+
+pub fn path_to_uri(path : &str) -> String {
+    let full_path = match fs::canonicalize(path) {
+        Ok(p) => p.to_string_lossy().to_string(),
+        Err(_e) => path.to_string(),
+    };
+    
+    let mut uri = "file://".to_string();
+    
+    for b in full_path.bytes() {
+        match b {
+            b'a' ..= b'z' | b'A' ..= b'Z' | b'0' ..= b'9'
+            | b'/' | b'-' | b'_' | b'.' | b'~' => uri.push(b as char),
+            _ => uri.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    
+    uri
+}
@@ -0,0 +1,96 @@
+
+// This file is part of the Lila compiler
+// Copyright (C) 2020-2021 Patrick Flynn
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; version 2.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, write to the Free Software Foundation, Inc.,
+// 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+
+// Calling convention helpers for the native backends
+// LTAC numbers the integer and float arguments separately (arg2_val), which is the register
+// they go in. It also gives every argument its position in the call (arg1_val), so once the
+// registers run out, a backend can tell where the rest go on the stack. All three of our
+// targets pass those in 8-byte slots, in the order of the call.
+
+use std::collections::HashMap;
+
+use crate::ltac::{LtacInstr, LtacType, LtacArg};
+
+// The number of argument registers of each kind
+#[derive(Clone, Copy)]
+pub struct ArgRegs {
+    pub ints : i32,
+    pub floats : i32,
+}
+
+// Checks whether a pusharg or ldarg is passed in the float registers
+pub fn is_float_arg(instr : &LtacInstr) -> bool {
+    match &instr.instr_type {
+        LtacType::LdArgF32 | LtacType::LdArgF64 => return true,
+        _ => {},
+    }
+    
+    match (&instr.arg1, &instr.arg2) {
+        (LtacArg::F32(_), _) | (LtacArg::F64(_), _)
+        | (_, LtacArg::FltReg(_)) | (_, LtacArg::FltReg64(_)) => return true,
+        _ => return false,
+    }
+}
+
+// Returns the stack slot of an argument, or None if it goes in a register
+pub fn stack_arg_slot(instr : &LtacInstr, regs : ArgRegs) -> Option<i32> {
+    let pos = instr.arg2_val;
+    let (own_regs, other_regs) = if is_float_arg(instr) {
+        (regs.floats, regs.ints)
+    } else {
+        (regs.ints, regs.floats)
+    };
+    
+    if pos <= own_regs {
+        return None;
+    }
+    
+    // The arguments of the other kind before this one, and how many of those spilled
+    let others = instr.arg1_val - pos;
+    let other_slots = if others > other_regs { others - other_regs } else { 0 };
+    
+    Some(pos - 1 - own_regs + other_slots)
+}
+
+// Returns the space each function needs for the stack arguments of its calls, by name
+// This is kept at the bottom of the frame, and is rounded up to keep the stack aligned.
+pub fn stack_arg_space(code : &Vec<LtacInstr>, regs : ArgRegs) -> HashMap<String, i32> {
+    let mut space = HashMap::new();
+    let mut func_name = String::new();
+    
+    for instr in code.iter() {
+        match &instr.instr_type {
+            LtacType::Func => func_name = instr.name.clone(),
+            
+            LtacType::PushArg => {
+                if let Some(slot) = stack_arg_slot(instr, regs) {
+                    let size = (slot + 1) * 8;
+                    let size = (size + 15) / 16 * 16;
+                    
+                    let current = space.entry(func_name.clone()).or_insert(0);
+                    if size > *current {
+                        *current = size;
+                    }
+                }
+            },
+            
+            _ => {},
+        }
+    }
+    
+    space
+}
@@ -0,0 +1,96 @@
+
+// This file is part of the Lila compiler
+// Copyright (C) 2020-2021 Patrick Flynn
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; version 2.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, write to the Free Software Foundation, Inc.,
+// 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+
+// The interface between the driver and the native code generators
+// The x86_64, aarch64, riscv64 and example crates under compiler/ implement this, and
+// the driver picks one by its target name. The driver runs the parser and the LTAC passes
+// the backend asks for; the backend turns the result into assembly, and the toolchain
+// assembles and links it.
+// The other two crates there do not: the llvm crate works from LLIR rather than LTAC, and
+// the ltac crate only writes out the LTAC text for --emit=ltac. The driver calls them directly.
+
+use std::io;
+
+use parser::Arch;
+use crate::ltac::LtacFile;
+use crate::toolchain::{Toolchain, TargetInfo, ToolError, LinkJob};
+use crate::workspace::Workspace;
+
+// The LTAC passes a backend can ask for
+// They always run in this order, after the builtins are lowered
+#[derive(Clone, Copy, PartialEq)]
+pub enum Pass {
+    Risc,           // Split memory operands into separate loads and stores
+    Riscv,          // Lower the instructions RISC-V does not have
+}
+
+// Options which only some backends support
+#[derive(Clone, Copy, PartialEq)]
+pub enum Feature {
+    Pic,            // --pic
+    DebugInfo,      // -g
+    SharedLib,      // --lib
+}
+
+// The options for generating code
+pub struct CodeOptions {
+    pub pic : bool,
+    pub debug : bool,
+}
+
+// The options for linking
+pub struct LinkOptions {
+    pub use_c : bool,
+    pub use_corelib : bool,
+    pub is_lib : bool,
+    pub inc_start : bool,
+}
+
+pub trait Backend {
+    // The target name, as given to -march=
+    fn name(&self) -> &'static str;
+    
+    // The architecture the parser should build for
+    fn arch(&self) -> Arch;
+    
+    // The LTAC passes this backend needs
+    fn passes(&self) -> Vec<Pass>;
+    
+    fn supports(&self, feature : Feature) -> bool;
+    
+    // Writes <name>.asm in the workspace
+    fn compile(&self, ltac_file : &LtacFile, options : &CodeOptions, ws : &Workspace, name : &String) -> io::Result<()>;
+    
+    // What the toolchain needs to know to assemble and link for this target
+    fn target(&self) -> TargetInfo;
+    
+    // Assembles <name>.asm to <name>.o in the workspace
+    fn assemble(&self, tools : &Toolchain, ws : &Workspace, name : &String) -> Result<(), ToolError> {
+        tools.assemble(&ws.file(name, "asm"), &ws.file(name, "o"))
+    }
+    
+    // Links the program from object files and -l libraries
+    fn link(&self, tools : &Toolchain, all_names : &Vec<String>, output : &String, options : &LinkOptions) -> Result<(), ToolError> {
+        let mut job = LinkJob::new(all_names, output);
+        job.use_c = options.use_c;
+        job.use_corelib = options.use_corelib;
+        job.is_lib = options.is_lib;
+        job.inc_start = options.inc_start;
+        
+        tools.link(&self.target(), &job)
+    }
+}
@@ -0,0 +1,309 @@
+
+// This file is part of the Lila compiler
+// Copyright (C) 2020-2021 Patrick Flynn
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; version 2.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, write to the Free Software Foundation, Inc.,
+// 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+
+// The DWARF writer
+// The native backends all use the GNU assembler, so the debugging information is
+// written as assembly. The assembler builds the line table from the .file and .loc
+// directives; we write the .debug_info section, which describes the functions and
+// where their variables live on the stack.
+
+use std::env;
+
+use parser::ast::DataType;
+use crate::ltac::{LtacFile, LtacDebugFunc, LtacDebugVar, escape_string};
+
+// Abbreviation codes
+const ABBREV_CU : u8 = 1;
+const ABBREV_FUNC : u8 = 2;
+const ABBREV_VAR : u8 = 3;
+const ABBREV_PARAM : u8 = 4;
+const ABBREV_BASE_TYPE : u8 = 5;
+const ABBREV_PTR_TYPE : u8 = 6;
+const ABBREV_VOID_PTR_TYPE : u8 = 7;
+
+// Opcodes and constants
+const DW_OP_FBREG : u8 = 0x91;
+const DW_OP_BREG0 : u8 = 0x70;
+
+const DW_ATE_BOOLEAN : u8 = 0x02;
+const DW_ATE_FLOAT : u8 = 0x04;
+const DW_ATE_SIGNED : u8 = 0x05;
+const DW_ATE_SIGNED_CHAR : u8 = 0x06;
+const DW_ATE_UNSIGNED : u8 = 0x08;
+
+const DW_LANG_C99 : u16 = 0x0c;
+
+// Describes how a backend lays out its stack frames
+pub struct DwarfTarget {
+    pub frame_reg : u8,                     // The DWARF number of the frame pointer register
+    pub var_offset : fn(i32, i32) -> i32,   // (stack size, variable position) -> offset from the frame pointer
+}
+
+// The label at the end of a function
+pub fn func_end_label(name : &String) -> String {
+    format!(".Lfunc_end_{}", name)
+}
+
+// Written at the start of the text section
+pub fn write_text_start(file : &LtacFile) -> String {
+    let mut line = String::new();
+    line.push_str(".file 1 \"");
+    line.push_str(&escape_string(&file.source));
+    line.push_str("\"\n");
+    line.push_str(".Ltext0:\n");
+    line
+}
+
+// Written when the source line changes
+pub fn write_loc(line_no : i32) -> String {
+    format!("  .loc 1 {}\n", line_no)
+}
+
+// Written at the end of the text section, followed by the debug sections
+pub fn write_text_end(file : &LtacFile, target : &DwarfTarget) -> String {
+    let mut line = String::new();
+    line.push_str(".Letext0:\n\n");
+    line.push_str(&write_abbrev());
+    line.push_str(&write_info(file, target));
+    
+    // The assembler fills this section in from the .loc directives
+    line.push_str("\n.section .debug_line,\"\",@progbits\n");
+    line.push_str(".Ldebug_line0:\n");
+    line
+}
+
+// Writes the abbreviation table
+// Each entry is a tag, a children flag, and a list of attribute and form pairs
+fn write_abbrev() -> String {
+    let mut line = String::new();
+    line.push_str(".section .debug_abbrev,\"\",@progbits\n");
+    line.push_str(".Ldebug_abbrev0:\n");
+    
+    // DW_TAG_compile_unit: producer, language, name, comp_dir, low_pc, high_pc, stmt_list
+    line.push_str(&abbrev(ABBREV_CU, 0x11, true, &[(0x25, 0x08), (0x13, 0x05), (0x03, 0x08), (0x1b, 0x08),
+                    (0x11, 0x01), (0x12, 0x01), (0x10, 0x17)]));
+    
+    // DW_TAG_subprogram: name, decl_file, decl_line, external, low_pc, high_pc, frame_base
+    line.push_str(&abbrev(ABBREV_FUNC, 0x2e, true, &[(0x03, 0x08), (0x3a, 0x0b), (0x3b, 0x06), (0x3f, 0x19),
+                    (0x11, 0x01), (0x12, 0x01), (0x40, 0x18)]));
+    
+    // DW_TAG_variable and DW_TAG_formal_parameter: name, type, location
+    line.push_str(&abbrev(ABBREV_VAR, 0x34, false, &[(0x03, 0x08), (0x49, 0x13), (0x02, 0x18)]));
+    line.push_str(&abbrev(ABBREV_PARAM, 0x05, false, &[(0x03, 0x08), (0x49, 0x13), (0x02, 0x18)]));
+    
+    // DW_TAG_base_type: name, encoding, byte_size
+    line.push_str(&abbrev(ABBREV_BASE_TYPE, 0x24, false, &[(0x03, 0x08), (0x3e, 0x0b), (0x0b, 0x0b)]));
+    
+    // DW_TAG_pointer_type: byte_size, type (left out for void pointers)
+    line.push_str(&abbrev(ABBREV_PTR_TYPE, 0x0f, false, &[(0x0b, 0x0b), (0x49, 0x13)]));
+    line.push_str(&abbrev(ABBREV_VOID_PTR_TYPE, 0x0f, false, &[(0x0b, 0x0b)]));
+    
+    line.push_str("  .byte 0\n\n");
+    line
+}
+
+fn abbrev(code : u8, tag : u8, has_children : bool, attrs : &[(u8, u8)]) -> String {
+    let mut line = String::new();
+    line.push_str(&format!("  .uleb128 {}\n", code));
+    line.push_str(&format!("  .uleb128 0x{:x}\n", tag));
+    line.push_str(&format!("  .byte {}\n", has_children as u8));
+    
+    for (attr, form) in attrs.iter() {
+        line.push_str(&format!("  .uleb128 0x{:x}\n  .uleb128 0x{:x}\n", attr, form));
+    }
+    
+    line.push_str("  .byte 0\n  .byte 0\n");
+    line
+}
+
+// Writes the compile unit with its functions and types
+fn write_info(file : &LtacFile, target : &DwarfTarget) -> String {
+    let comp_dir = match env::current_dir() {
+        Ok(dir) => dir.to_string_lossy().to_string(),
+        Err(_e) => String::new(),
+    };
+    
+    let mut line = String::new();
+    line.push_str(".section .debug_info,\"\",@progbits\n");
+    line.push_str(".Ldebug_info0:\n");
+    line.push_str("  .4byte .Ldebug_info_end0 - .Ldebug_info_start0\n");
+    line.push_str(".Ldebug_info_start0:\n");
+    line.push_str("  .2byte 4\n");
+    line.push_str("  .4byte .Ldebug_abbrev0\n");
+    line.push_str("  .byte 8\n");
+    
+    line.push_str(&format!("  .uleb128 {}\n", ABBREV_CU));
+    line.push_str("  .string \"lilac\"\n");
+    line.push_str(&format!("  .2byte 0x{:x}\n", DW_LANG_C99));
+    line.push_str(&format!("  .string \"{}\"\n", escape_string(&file.source)));
+    line.push_str(&format!("  .string \"{}\"\n", escape_string(&comp_dir)));
+    line.push_str("  .8byte .Ltext0\n");
+    line.push_str("  .8byte .Letext0\n");
+    line.push_str("  .4byte .Ldebug_line0\n");
+    
+    line.push_str(&write_types());
+    
+    for func in file.debug.iter() {
+        line.push_str(&write_func(func, target));
+    }
+    
+    line.push_str("  .byte 0\n");
+    line.push_str(".Ldebug_info_end0:\n");
+    line
+}
+
+// The base types are always written, so variables can refer to them by name
+fn write_types() -> String {
+    let types : [(&str, u8, u8); 12] = [
+        ("byte", DW_ATE_SIGNED, 1),
+        ("ubyte", DW_ATE_UNSIGNED, 1),
+        ("short", DW_ATE_SIGNED, 2),
+        ("ushort", DW_ATE_UNSIGNED, 2),
+        ("int", DW_ATE_SIGNED, 4),
+        ("uint", DW_ATE_UNSIGNED, 4),
+        ("int64", DW_ATE_SIGNED, 8),
+        ("uint64", DW_ATE_UNSIGNED, 8),
+        ("float", DW_ATE_FLOAT, 4),
+        ("double", DW_ATE_FLOAT, 8),
+        ("char", DW_ATE_SIGNED_CHAR, 1),
+        ("bool", DW_ATE_BOOLEAN, 1),
+    ];
+    
+    let mut line = String::new();
+    
+    for (name, encoding, size) in types.iter() {
+        line.push_str(&format!(".Ldebug_type_{}:\n", name));
+        line.push_str(&format!("  .uleb128 {}\n", ABBREV_BASE_TYPE));
+        line.push_str(&format!("  .string \"{}\"\n", name));
+        line.push_str(&format!("  .byte 0x{:x}\n", encoding));
+        line.push_str(&format!("  .byte {}\n", size));
+        
+        // A pointer to each type, for arrays
+        line.push_str(&format!(".Ldebug_type_{}_ptr:\n", name));
+        line.push_str(&format!("  .uleb128 {}\n", ABBREV_PTR_TYPE));
+        line.push_str("  .byte 8\n");
+        line.push_str(&format!("  .4byte .Ldebug_type_{} - .Ldebug_info0\n", name));
+    }
+    
+    line.push_str(".Ldebug_type_void_ptr:\n");
+    line.push_str(&format!("  .uleb128 {}\n", ABBREV_VOID_PTR_TYPE));
+    line.push_str("  .byte 8\n");
+    line
+}
+
+fn write_func(func : &LtacDebugFunc, target : &DwarfTarget) -> String {
+    let mut line = String::new();
+    line.push_str(&format!("  .uleb128 {}\n", ABBREV_FUNC));
+    line.push_str(&format!("  .string \"{}\"\n", func.name));
+    line.push_str("  .byte 1\n");
+    line.push_str(&format!("  .4byte {}\n", func.line_no));
+    line.push_str(&format!("  .8byte {}\n", func.name));
+    line.push_str(&format!("  .8byte {}\n", func_end_label(&func.name)));
+    
+    // The frame base is the frame pointer register
+    line.push_str("  .uleb128 2\n");
+    line.push_str(&format!("  .byte 0x{:x}, 0\n", DW_OP_BREG0 + target.frame_reg));
+    
+    for var in func.vars.iter() {
+        line.push_str(&write_var(func, var, target));
+    }
+    
+    line.push_str("  .byte 0\n");
+    line
+}
+
+fn write_var(func : &LtacDebugFunc, var : &LtacDebugVar, target : &DwarfTarget) -> String {
+    let mut line = String::new();
+    
+    if var.is_param {
+        line.push_str(&format!("  .uleb128 {}\n", ABBREV_PARAM));
+    } else {
+        line.push_str(&format!("  .uleb128 {}\n", ABBREV_VAR));
+    }
+    
+    line.push_str(&format!("  .string \"{}\"\n", var.name));
+    line.push_str(&format!("  .4byte {} - .Ldebug_info0\n", type_label(var)));
+    
+    let mut expr = vec![DW_OP_FBREG];
+    expr.append(&mut sleb128((target.var_offset)(func.stack_size, var.pos)));
+    
+    let bytes : Vec<String> = expr.iter().map(|b| format!("0x{:x}", b)).collect();
+    line.push_str(&format!("  .uleb128 {}\n", expr.len()));
+    line.push_str(&format!("  .byte {}\n", bytes.join(", ")));
+    line
+}
+
+// Returns the label of the type entry for a variable
+// Arrays are pointers to their elements, and records are pointers to their memory
+fn type_label(var : &LtacDebugVar) -> String {
+    match &var.data_type {
+        DataType::Ptr => {
+            match type_name(&var.sub_type) {
+                Some(name) => return format!(".Ldebug_type_{}_ptr", name),
+                None => return ".Ldebug_type_void_ptr".to_string(),
+            }
+        },
+        
+        DataType::Str => return ".Ldebug_type_char_ptr".to_string(),
+        
+        data_type => {
+            match type_name(data_type) {
+                Some(name) => return format!(".Ldebug_type_{}", name),
+                None => return ".Ldebug_type_void_ptr".to_string(),
+            }
+        },
+    }
+}
+
+fn type_name(data_type : &DataType) -> Option<&str> {
+    match data_type {
+        DataType::Byte => return Some("byte"),
+        DataType::UByte => return Some("ubyte"),
+        DataType::Short => return Some("short"),
+        DataType::UShort => return Some("ushort"),
+        DataType::Int | DataType::Enum(_) => return Some("int"),
+        DataType::UInt => return Some("uint"),
+        DataType::Int64 => return Some("int64"),
+        DataType::UInt64 => return Some("uint64"),
+        DataType::Float => return Some("float"),
+        DataType::Double => return Some("double"),
+        DataType::Char => return Some("char"),
+        DataType::Bool => return Some("bool"),
+        _ => return None,
+    }
+}
+
+fn sleb128(value : i32) -> Vec<u8> {
+    let mut bytes : Vec<u8> = Vec::new();
+    let mut value = value as i64;
+    
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+        
+        if done {
+            bytes.push(byte);
+            break;
+        }
+        
+        bytes.push(byte | 0x80);
+    }
+    
+    bytes
+}
@@ -2,6 +2,11 @@
 // Import any local modules
 pub mod transform;
 pub mod ltac;
+pub mod dwarf;
+pub mod backend;
+pub mod abi;
+pub mod toolchain;
+pub mod workspace;
 
 mod risc;
 mod riscv;
@@ -11,38 +16,43 @@ mod ltac_array;
 mod ltac_flow;
 mod ltac_for;
 mod ltac_func;
+mod ltac_record;
 mod ltac_utils;
 mod ltac_var;
 
 use ltac::LtacFile;
 use parser::*;
+use parser::syntax::Diagnostic;
 
 // The main parse function
-pub fn parse(path : String, arch : Arch, include_core : bool) -> Result<LtacFile, ()> {
-    let tree = match get_ast(&path.to_string(), arch, include_core, false) {
+// On failure, the diagnostics are returned for the caller to print
+pub fn parse(path : String, arch : Arch, include_core : bool, checks : Checks) -> Result<LtacFile, Vec<Diagnostic>> {
+    let tree = match get_ast(&path.to_string(), arch, include_core) {
         Ok(tree) => tree,
-        Err(_e) => return Err(()),
+        Err(errors) => return Err(errors),
     };
     
     if tree.module.len() > 0 {
         match module::generate_module(&tree) {
             Ok(()) => {},
             Err(_e) => {
-                println!("Error generating module header");
-                return Err(());
+                let error = syntax::file_error(&path, syntax::E_MODULE, "Error generating module header".to_string());
+                return Err(vec![error]);
             },
         }
     }
     
-    let mut syntax = syntax::create_error_manager();
+    let mut syntax = syntax::create_error_manager(path.clone());
     let name = get_name(&path);
     
-    let mut ltac_builder = ltac_builder::new_ltac_builder(name.clone(), &mut syntax);
-    let ltac = match ltac_builder.build_ltac(&tree) {
+    let mut ltac_builder = ltac_builder::new_ltac_builder(name.clone(), &mut syntax, checks);
+    let mut ltac = match ltac_builder.build_ltac(&tree) {
         Ok(ltac) => ltac,
-        Err(_e) => return Err(()),
+        Err(errors) => return Err(errors),
     };
     
+    ltac.source = path;
+    
     Ok(ltac)
 }
 
@@ -15,6 +15,7 @@
 // with this program; if not, write to the Free Software Foundation, Inc.,
 // 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
 
+use parser::ast::DataType;
 
 // Represents a data entry type
 #[derive(Clone, PartialEq)]
@@ -22,6 +23,22 @@ pub enum LtacDataType {
     StringL,
     FloatL,
     DoubleL,
+    
+    // Global variables- the value is the initial value, or the size for Bss
+    GlobalB,
+    GlobalW,
+    GlobalDW,
+    GlobalQW,
+    GlobalStr,      // Points to a string constant; the value is the label
+    GlobalBss,
+    
+    // Constant tables- the value is the list of elements, separated by commas
+    // These are read-only, so they go in their own section
+    TableB,
+    TableW,
+    TableDW,
+    TableQW,
+    TableStr,       // Each element is the label of a string constant
 }
 
 // Represents an instruction type
@@ -103,6 +120,16 @@ pub enum LtacType {
     Bge,
     Bfg,        // Jump if float is greater
     Bfge,       // Jump if float is greater or equal
+    Bul,        // Jump if unsigned is less
+    Bule,       // Jump if unsigned is less or equal
+    Bug,        // Jump if unsigned is greater
+    Buge,       // Jump if unsigned is greater or equal
+    
+    // Jump tables
+    // The index in arg1 selects an entry in the table labeled by the name; each
+    // entry holds the offset from itself to its label
+    BrTable,
+    TableEntry,
     
     // Math operations
     I8Add,      U8Add,
@@ -194,15 +221,20 @@ pub enum LtacArg {
     F64(String),
     
     Ptr(i32),
-    PtrLcl(String)
+    PtrLcl(String),
+    
+    GlobalMem(String),
+    GlobalPtr(String),      // Loads the address stored in a global
 }
 
 // Represents an LTAC file
 #[derive(Clone)]
 pub struct LtacFile {
     pub name : String,
+    pub source : String,                // The path to the source file
     pub data : Vec<LtacData>,
     pub code : Vec<LtacInstr>,
+    pub debug : Vec<LtacDebugFunc>,
 }
 
 // Represents data for the ELF .data entry
@@ -224,6 +256,28 @@ pub struct LtacInstr {
     
     pub arg2 : LtacArg,
     pub arg2_val : i32,
+    
+    pub line_no : i32,          // The source line, or 0 if unknown
+}
+
+// Debugging information for a function
+// This is only written out with -g
+#[derive(Clone)]
+pub struct LtacDebugFunc {
+    pub name : String,
+    pub line_no : i32,
+    pub stack_size : i32,
+    pub vars : Vec<LtacDebugVar>,
+}
+
+// A local variable and its stack position
+#[derive(Clone)]
+pub struct LtacDebugVar {
+    pub name : String,
+    pub data_type : DataType,
+    pub sub_type : DataType,
+    pub pos : i32,
+    pub is_param : bool,
 }
 
 //=====================================
@@ -239,6 +293,36 @@ pub fn create_instr(instr_type : LtacType) -> LtacInstr {
         
         arg2 : LtacArg::Empty,
         arg2_val : 0,
+        
+        line_no : 0,
     }
 }
 
+
+// Sets the source line on the instructions from start that do not have one yet
+pub fn set_line_no(code : &mut Vec<LtacInstr>, start : usize, line_no : i32) {
+    for instr in code.iter_mut().skip(start) {
+        if instr.line_no == 0 {
+            instr.line_no = line_no;
+        }
+    }
+}
+
+// Escapes string data for a .string directive
+// The value holds the real bytes, so anything the assembler could misread is written in octal
+pub fn escape_string(val : &str) -> String {
+    let mut output = String::new();
+    
+    for byte in val.bytes() {
+        match byte {
+            b'\\' => output.push_str("\\\\"),
+            b'"' => output.push_str("\\\""),
+            b'\n' => output.push_str("\\n"),
+            b'\t' => output.push_str("\\t"),
+            0x20..=0x7E => output.push(byte as char),
+            _ => output.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    
+    output
+}
@@ -19,15 +19,19 @@
 use crate::ltac_builder::*;
 use crate::ltac;
 use crate::ltac::{LtacType, LtacInstr, LtacArg};
-use crate::ast::{DataType, AstStmt, AstArgType};
+use crate::ast;
+use crate::ast::{DataType, AstStmt, AstStmtType, AstArg, AstArgType, AstConst};
+use crate::ast_expr::{AstExpr, arg_expr};
+use crate::syntax::*;
 
 use crate::ltac_expr::*;
+use crate::ltac_utils::{size_for_type, local_label};
 
 // Assigns a value to an array
 pub fn build_array_assign(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
     let var : Var;
     match builder.vars.get(&line.name) {
-        Some(v) => var = v.clone(),
+        Some(v) => var = v.elements(),
         None => return false,
     }
     
@@ -37,11 +41,13 @@ pub fn build_array_assign(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
 }
 
 // An internal function to free any dynamic arrays in the current context
+// Constant tables are not in the heap, so they are skipped
 pub fn free_arrays(builder : &mut LtacBuilder, ignore : String) {
     for (name, var) in &builder.vars {
-        if var.data_type == DataType::Ptr && !var.is_param && *name != ignore {
+        if var.data_type == DataType::Ptr && !var.is_param && *name != ignore
+                && !builder.tables.contains_key(name) {
             let mut pusharg = ltac::create_instr(LtacType::PushArg);
-            pusharg.arg1 = LtacArg::Ptr(var.pos);
+            pusharg.arg1 = var.ptr();
             pusharg.arg2_val = 1;
             builder.file.code.push(pusharg);
             
@@ -53,9 +59,26 @@ pub fn free_arrays(builder : &mut LtacBuilder, ignore : String) {
 
 // Initializes a an array in the heap
 pub fn build_dyn_array(builder : &mut LtacBuilder, line : &AstStmt, var : &Var) -> bool {
-    let sub_args = &line.sub_args;
+    let mut sub_args = line.sub_args.clone();
     let mut code = true;
     
+    // A computed size is stored first, and then used like a variable
+    if sub_args.len() > 0 && !is_simple_index(builder, &sub_args) {
+        let temp = match &line.sub_expr {
+            Some(size) => build_expr_temp(builder, size, &DataType::Int),
+            
+            None => {
+                builder.syntax.ltac_error(line, "Invalid array size.".to_string());
+                return false;
+            },
+        };
+        
+        match temp {
+            Some(temp) => sub_args = vec![temp],
+            None => return false,
+        }
+    }
+    
     // Setup the store instruction, which holds the array size
     let mut size_instr = ltac::create_instr(LtacType::Mov);
     size_instr.arg1 = LtacArg::Mem(var.pos - 8);
@@ -70,7 +93,8 @@ pub fn build_dyn_array(builder : &mut LtacBuilder, line : &AstStmt, var : &Var)
         } else if var.sub_type == DataType::Short || var.sub_type == DataType::UShort {
             size = 2;
         } else if  var.sub_type == DataType::Int64 || var.sub_type == DataType::UInt64
-                || var.sub_type == DataType::Double || var.sub_type == DataType::Str {
+                || var.sub_type == DataType::Double || var.sub_type == DataType::Str
+                || matches!(var.sub_type, DataType::Proc(_)) {
             size = 8;
         }
         
@@ -85,21 +109,25 @@ pub fn build_dyn_array(builder : &mut LtacBuilder, line : &AstStmt, var : &Var)
         
         // Move the return register back to the variable
         instr = ltac::create_instr(LtacType::MovQ);
-        instr.arg1 = LtacArg::Mem(var.pos);
+        instr.arg1 = var.mem();
         instr.arg2 = LtacArg::RetRegI64;
         builder.file.code.push(instr);
         
         // Store the size
         size_instr.arg2 = LtacArg::I32(arg.u64_val as i32);
         builder.file.code.push(size_instr);
-        
+    
     // An array with a variable as the size
     } else if sub_args.len() == 1 && sub_args.last().unwrap().arg_type == AstArgType::Id {
         let arg = sub_args.last().unwrap();
-        let size = 1;
         let data_type : DataType;
         let pos : i32;
         
+        let mut size = size_for_type(&var.sub_type) as u32;
+        if size == 0 {
+            size = 4;
+        }
+        
         match &builder.vars.get(&arg.str_val) {
             Some(v) => {
                 data_type = v.data_type.clone();
@@ -107,18 +135,19 @@ pub fn build_dyn_array(builder : &mut LtacBuilder, line : &AstStmt, var : &Var)
             }
             
             None => {
-                builder.syntax.ltac_error(line, "Invalid identifier".to_string());
+                builder.syntax.ltac_error(line, "Invalid identifier".to_string()).code(E_UNKNOWN_NAME);
                 return false;
             },
         }
         
         if data_type != DataType::Int && data_type != DataType::UInt {
-            builder.syntax.ltac_error(line, "Array size can only be set with integer values or variables.".to_string());
+            builder.syntax.ltac_error(line, "Array size can only be set with integer values or variables.".to_string()).code(E_TYPE_MISMATCH);
             return false;
         }
         
         // Instruction syntax:
         // mov u32.r0, [pos]
+        // mov [size_pos], u32.r0
         // imul u32.r0, size
         // pusharg u32
         // call malloc
@@ -131,15 +160,15 @@ pub fn build_dyn_array(builder : &mut LtacBuilder, line : &AstStmt, var : &Var)
             instr.arg2 = LtacArg::Mem(pos);
             builder.file.code.push(instr.clone());
             
+            // Store the size
+            size_instr.arg2 = LtacArg::Reg32(0);
+            builder.file.code.push(size_instr);
+            
             instr = ltac::create_instr(LtacType::U32Mul);
             instr.arg1 = LtacArg::Reg32(0);
             instr.arg2 = LtacArg::U32(size);
             builder.file.code.push(instr.clone());
             
-            // Store the size
-            size_instr.arg2 = LtacArg::Reg32(0);
-            builder.file.code.push(size_instr);
-            
             // Prepare for the call
             instr = ltac::create_instr(LtacType::PushArg);
             instr.arg1 = LtacArg::Reg32(0);
@@ -154,7 +183,7 @@ pub fn build_dyn_array(builder : &mut LtacBuilder, line : &AstStmt, var : &Var)
             
             size_instr.arg2 = LtacArg::Reg32(0);
             builder.file.code.push(size_instr);
-        
+            
             // Prepare for the call
             instr = ltac::create_instr(LtacType::PushArg);
             instr.arg1 = LtacArg::Mem(pos);
@@ -168,10 +197,10 @@ pub fn build_dyn_array(builder : &mut LtacBuilder, line : &AstStmt, var : &Var)
         
         // Move the return register back to the variable
         instr = ltac::create_instr(LtacType::MovQ);
-        instr.arg1 = LtacArg::Mem(var.pos);
+        instr.arg1 = var.mem();
         instr.arg2 = LtacArg::RetRegI64;
         builder.file.code.push(instr);
-        
+    
     // Vector math
     } else if sub_args.len() == 0 && line.args.len() > 1 {
         code = build_i32array_vector_math(builder, line, var);
@@ -179,9 +208,153 @@ pub fn build_dyn_array(builder : &mut LtacBuilder, line : &AstStmt, var : &Var)
         //TODO
     }
     
+    // An array literal stores each of its values in turn
+    if code && line.args.len() == 1 && line.args[0].arg_type == AstArgType::ArrayL {
+        code = build_array_values(builder, line);
+    }
+    
     code
 }
 
+// Stores the values of an array literal
+// Each one is built as an assignment to its element
+fn build_array_values(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
+    for (i, value) in line.args[0].sub_args.iter().enumerate() {
+        let mut stmt = line.clone();
+        stmt.stmt_type = AstStmtType::ArrayAssign;
+        stmt.sub_args = vec![ast::create_int(i as u64)];
+        stmt.sub_expr = Some(AstExpr::Operand(ast::create_int(i as u64)));
+        stmt.args = value.sub_args.clone();
+        stmt.expr = Some(arg_expr(value));
+        
+        if !build_array_assign(builder, &stmt) {
+            return false;
+        }
+    }
+    
+    true
+}
+
+// Gives each constant table used in a function a local array
+// The array points to the table in the data section, so it is read like any other array
+pub fn build_table_vars(builder : &mut LtacBuilder, statements : &Vec<AstStmt>) {
+    let mut tables : Vec<AstConst> = builder.tables.values()
+        .filter(|t| block_uses(statements, &t.name))
+        .cloned()
+        .collect();
+    
+    // Keep the stack layout the same from one build to the next
+    tables.sort_by(|a, b| a.name.cmp(&b.name));
+    
+    for table in tables {
+        builder.stack_pos += 12;
+        
+        let var = Var {
+            pos : builder.stack_pos,
+            data_type : DataType::Ptr,
+            sub_type : table.data_type.clone(),
+            is_param : false,
+            global : String::new(),
+        };
+        
+        let mut instr = ltac::create_instr(LtacType::MovQ);
+        instr.arg1 = var.mem();
+        instr.arg2 = LtacArg::PtrLcl(table.name.clone());
+        builder.file.code.push(instr);
+        
+        // Store the size
+        instr = ltac::create_instr(LtacType::Mov);
+        instr.arg1 = LtacArg::Mem(var.pos - 8);
+        instr.arg2 = LtacArg::I32(table.values.len() as i32);
+        builder.file.code.push(instr);
+        
+        builder.add_var(table.name, var);
+    }
+}
+
+// Checks whether a block refers to a name anywhere in its arguments
+fn block_uses(statements : &Vec<AstStmt>, name : &String) -> bool {
+    statements.iter().any(|stmt| {
+        args_use(&stmt.args, name) || args_use(&stmt.sub_args, name) || block_uses(&stmt.sub_block, name)
+    })
+}
+
+fn args_use(args : &Vec<AstArg>, name : &String) -> bool {
+    args.iter().any(|arg| {
+        (arg.arg_type == AstArgType::Id && arg.str_val == *name) || args_use(&arg.sub_args, name)
+    })
+}
+
+// Checks an array index against the size stored before the array (--checks=bounds)
+// The index is loaded into the register first, unless it is already there. Strings and
+// typed pointers have no size, so only arrays are checked. lila_bounds_fail only prints the
+// message; the exit goes through the transform layer, so it is exit() with the C library.
+//
+// instr: mov r, index
+//        cmp r, [pos-8]
+//        bul ok
+//        call lila_bounds_fail(file, line, r, [pos-8])
+//        exit 1
+//    ok:
+pub fn bounds_check(builder : &mut LtacBuilder, name : &String, index : LtacArg, reg : i32) -> Vec<LtacInstr> {
+    let mut block : Vec<LtacInstr> = Vec::new();
+    
+    let var = match builder.vars.get(name) {
+        Some(v) if builder.checks.bounds() && v.data_type == DataType::Ptr && !v.is_global() => v.clone(),
+        _ => return block,
+    };
+    
+    if index != LtacArg::Reg32(reg) {
+        let mut mov = ltac::create_instr(LtacType::Mov);
+        mov.arg1 = LtacArg::Reg32(reg);
+        mov.arg2 = index;
+        block.push(mov);
+    }
+    
+    // Negative indexes are large unsigned values, so one comparison covers both ends
+    let mut cmp = ltac::create_instr(LtacType::U32Cmp);
+    cmp.arg1 = LtacArg::Reg32(reg);
+    cmp.arg2 = LtacArg::Mem(var.pos - 8);
+    block.push(cmp);
+    
+    let ok_label = local_label(builder);
+    
+    let mut br = ltac::create_instr(LtacType::Bul);
+    br.name = ok_label.clone();
+    block.push(br);
+    
+    let file = builder.build_string(builder.syntax.file.clone());
+    let args = [LtacArg::PtrLcl(file), LtacArg::I32(builder.syntax.current_ln_no),
+                LtacArg::Reg32(reg), LtacArg::Mem(var.pos - 8)];
+    
+    for (i, arg) in args.iter().enumerate() {
+        let mut push = ltac::create_instr(LtacType::PushArg);
+        push.arg1 = arg.clone();
+        push.arg1_val = i as i32;
+        push.arg2_val = i as i32 + 1;
+        
+        if let LtacArg::Mem(_) = arg {
+            push.arg2 = LtacArg::I32(0);
+        }
+        
+        block.push(push);
+    }
+    
+    let mut call = ltac::create_instr(LtacType::Call);
+    call.name = "lila_bounds_fail".to_string();
+    block.push(call);
+    
+    let mut exit = ltac::create_instr(LtacType::Exit);
+    exit.arg1 = LtacArg::I32(1);
+    block.push(exit);
+    
+    let mut lbl = ltac::create_instr(LtacType::Label);
+    lbl.name = ok_label;
+    block.push(lbl);
+    
+    block
+}
+
 // Builds integer vector math
 pub fn build_i32array_vector_math(builder : &mut LtacBuilder, line : &AstStmt, var : &Var) -> bool {
     let mut instr = ltac::create_instr(LtacType::MovI32Vec);
@@ -189,14 +362,14 @@ pub fn build_i32array_vector_math(builder : &mut LtacBuilder, line : &AstStmt, v
     
     // The last loaded memory position
     let mut last_pos = 0;
-
+    
     // TODO: This code is kind of awful
     for arg in line.args.iter() {
         match &arg.arg_type {
             AstArgType::Id => {
                 match &builder.vars.get(&arg.str_val) {
                     Some(v) => {
-                        instr.arg2 = LtacArg::Mem(v.pos);
+                        instr.arg2 = v.mem();
                         
                         if arg.sub_args.len() > 0 {
                             let first_arg = arg.sub_args.last().unwrap();
@@ -207,9 +380,13 @@ pub fn build_i32array_vector_math(builder : &mut LtacBuilder, line : &AstStmt, v
                                     instr.arg2 = LtacArg::MemOffsetImm(v.pos, offset);
                                 } else if first_arg.arg_type == AstArgType::Id {
                                     match &builder.vars.get(&first_arg.str_val) {
+                                        Some(v2) if v2.is_global() => {
+                                            builder.syntax.ltac_error(line, "Global variables cannot be used as array indexes.".to_string()).code(E_GLOBAL_INDEX);
+                                            return false;
+                                        },
                                         Some(v2) => instr.arg2 = LtacArg::MemOffsetMem(v.pos, v2.pos, 4),
                                         None => {
-                                            builder.syntax.ltac_error(line, "Invalid offset variable.".to_string());
+                                            builder.syntax.ltac_error(line, "Invalid offset variable.".to_string()).code(E_UNKNOWN_NAME);
                                             return false;
                                         },
                                     }
@@ -229,7 +406,7 @@ pub fn build_i32array_vector_math(builder : &mut LtacBuilder, line : &AstStmt, v
                     },
                     
                     None => {
-                        builder.syntax.ltac_error(line, "Invalid variable.".to_string());
+                        builder.syntax.ltac_error(line, "Invalid variable.".to_string()).code(E_UNKNOWN_NAME);
                         return false;
                     },
                 }
@@ -251,7 +428,7 @@ pub fn build_i32array_vector_math(builder : &mut LtacBuilder, line : &AstStmt, v
     
     // The final move instruction
     instr = ltac::create_instr(LtacType::MovI32Vec);
-    instr.arg1 = LtacArg::Mem(var.pos);
+    instr.arg1 = var.mem();
     instr.arg2 = LtacArg::Reg32(0);
     
     builder.file.code.push(instr.clone());
@@ -23,38 +23,91 @@ use crate::ast::*;
 use crate::ltac;
 use crate::ltac::*;
 use crate::syntax::*;
+use crate::Checks;
 
 use crate::ltac_array::*;
 use crate::ltac_flow::*;
 use crate::ltac_for::*;
 use crate::ltac_func::*;
+use crate::ltac_record::*;
+use crate::ltac_utils::*;
 use crate::ltac_var::*;
 
+// What a call needs to know about a function
+#[derive(Clone)]
+pub struct FuncSig {
+    pub params : Vec<DataType>,
+    pub is_variadic : bool,         // Arguments after the parameters are passed the C way
+}
+
 #[derive(Clone)]
 pub struct Var {
     pub pos : i32,
     pub data_type : DataType,
     pub sub_type : DataType,        // Only in the case of enums and pointers
     pub is_param : bool,
+    pub global : String,            // The symbol name for global variables
+}
+
+impl Var {
+    // Returns the memory location of the variable
+    pub fn mem(&self) -> LtacArg {
+        if self.global.len() > 0 {
+            return LtacArg::GlobalMem(self.global.clone());
+        }
+        
+        LtacArg::Mem(self.pos)
+    }
+    
+    // Returns the address held by the variable
+    pub fn ptr(&self) -> LtacArg {
+        if self.global.len() > 0 {
+            return LtacArg::GlobalPtr(self.global.clone());
+        }
+        
+        LtacArg::Ptr(self.pos)
+    }
+    
+    pub fn is_global(&self) -> bool {
+        self.global.len() > 0
+    }
+    
+    // Returns the variable as it is seen when indexed
+    // A typed pointer is indexed like an array of what it points to
+    pub fn elements(&self) -> Var {
+        let mut v = self.clone();
+        
+        if let DataType::Pointer(_) = self.data_type {
+            v.data_type = DataType::Ptr;
+        }
+        
+        v
+    }
 }
 
 #[derive(Clone)]
 pub struct LtacBuilder {
     pub file : LtacFile,
     pub syntax : ErrorManager,
+    pub checks : Checks,
     
     pub str_pos : i32,
     pub flt_pos : i32,
     
     // Function-related values
     pub functions : HashMap<String, DataType>,
+    pub signatures : HashMap<String, FuncSig>,
     pub current_func : String,
     pub current_type : DataType,
     pub current_sub_type : DataType,
     
     // Variable-related values
+    pub records : HashMap<String, AstRecord>,
+    pub procs : HashMap<String, AstProc>,
     pub enums : HashMap<String, AstEnum>,        // HashMap for easier searching
     pub vars : HashMap<String, Var>,
+    pub globals : HashMap<String, Var>,
+    pub tables : HashMap<String, AstConst>,
     pub stack_pos : i32,
     
     // For labels and blocks
@@ -70,22 +123,30 @@ pub struct LtacBuilder {
     pub end_labels : HashMap<i32, String>,       // Needed for break
 }
 
-pub fn new_ltac_builder(name : String, syntax : &mut ErrorManager) -> LtacBuilder {
+pub fn new_ltac_builder(name : String, syntax : &mut ErrorManager, checks : Checks) -> LtacBuilder {
     LtacBuilder {
         file : LtacFile {
             name : name,
+            source : String::new(),
             data : Vec::new(),
             code : Vec::new(),
+            debug : Vec::new(),
         },
         syntax : syntax.clone(),
+        checks : checks,
         str_pos : 0,
         flt_pos : 0,
         functions : HashMap::new(),
+        signatures : HashMap::new(),
         current_func : String::new(),
         current_type : DataType::Void,
         current_sub_type : DataType::None,
+        records : HashMap::new(),
+        procs : HashMap::new(),
         enums : HashMap::new(),
         vars : HashMap::new(),
+        globals : HashMap::new(),
+        tables : HashMap::new(),
         stack_pos : 0,
         block_layer : 0,
         label_stack : Vec::new(),
@@ -100,22 +161,156 @@ pub fn new_ltac_builder(name : String, syntax : &mut ErrorManager) -> LtacBuilde
 
 // The LTAC builder
 impl LtacBuilder {
-
+    
     // Builds the main LTAC file
-    pub fn build_ltac(&mut self, tree : &AstTree) -> Result<LtacFile, ()> {
+    pub fn build_ltac(&mut self, tree : &AstTree) -> Result<LtacFile, Vec<Diagnostic>> {
+        // Build the global variables
+        if !self.build_globals(tree) {
+            return Err(self.syntax.errors.clone());
+        }
+        
+        self.build_tables(tree);
+        
         // Build functions
         if !self.build_functions(tree) {
-            self.syntax.print_errors();
-            return Err(());
+            return Err(self.syntax.errors.clone());
         }
         
         Ok(self.file.clone())
     }
-
+    
+    // Adds the global variables to the data section
+    // Extern globals are only registered; they belong to another module
+    fn build_globals(&mut self, tree : &AstTree) -> bool {
+        for global in tree.globals.iter() {
+            let v = Var {
+                pos : 0,
+                data_type : global.data_type.clone(),
+                sub_type : DataType::None,
+                is_param : false,
+                global : global.name.clone(),
+            };
+            
+            self.globals.insert(global.name.clone(), v);
+            
+            if global.is_extern {
+                continue;
+            }
+            
+            let size = size_for_type(&global.data_type);
+            let mut data_type = LtacDataType::GlobalBss;
+            let mut val = size.to_string();
+            
+            let value = &global.value;
+            
+            match &value.arg_type {
+                AstArgType::StringL => {
+                    data_type = LtacDataType::GlobalStr;
+                    val = self.build_string(value.str_val.clone());
+                },
+                
+                AstArgType::CharL => {
+                    data_type = LtacDataType::GlobalB;
+                    val = (value.char_val as u8).to_string();
+                },
+                
+                AstArgType::BoolL => {
+                    data_type = LtacDataType::GlobalB;
+                    val = value.u8_val.to_string();
+                },
+                
+                AstArgType::ByteL | AstArgType::ShortL | AstArgType::IntL => {
+                    match size {
+                        1 => data_type = LtacDataType::GlobalB,
+                        2 => data_type = LtacDataType::GlobalW,
+                        4 => data_type = LtacDataType::GlobalDW,
+                        _ => data_type = LtacDataType::GlobalQW,
+                    }
+                    
+                    match &value.arg_type {
+                        AstArgType::ByteL => val = value.u8_val.to_string(),
+                        AstArgType::ShortL => val = value.u16_val.to_string(),
+                        _ => val = (value.u64_val as i64).to_string(),
+                    }
+                },
+                
+                _ => {},
+            }
+            
+            let data = LtacData {
+                data_type : data_type,
+                name : global.name.clone(),
+                val : val,
+            };
+            
+            self.file.data.push(data);
+        }
+        
+        true
+    }
+    
+    // Adds the constant tables to the data section
+    fn build_tables(&mut self, tree : &AstTree) {
+        for constant in tree.constants.iter() {
+            if constant.values.len() == 0 {
+                continue;
+            }
+            
+            let mut data_type = match size_for_type(&constant.data_type) {
+                1 => LtacDataType::TableB,
+                2 => LtacDataType::TableW,
+                4 => LtacDataType::TableDW,
+                _ => LtacDataType::TableQW,
+            };
+            
+            if constant.data_type == DataType::Str {
+                data_type = LtacDataType::TableStr;
+            }
+            
+            let mut values : Vec<String> = Vec::new();
+            
+            for value in constant.values.iter() {
+                match &value.arg_type {
+                    AstArgType::ByteL => values.push(value.u8_val.to_string()),
+                    AstArgType::ShortL => values.push(value.u16_val.to_string()),
+                    AstArgType::IntL => values.push((value.u64_val as i64).to_string()),
+                    AstArgType::CharL => values.push((value.char_val as u8).to_string()),
+                    AstArgType::StringL => values.push(self.build_string(value.str_val.clone())),
+                    
+                    // Floats are stored by their bits, like float literals
+                    AstArgType::FloatL if constant.data_type == DataType::Float => {
+                        values.push((value.f64_val as f32).to_bits().to_string());
+                    },
+                    
+                    AstArgType::FloatL => values.push(value.f64_val.to_bits().to_string()),
+                    
+                    _ => {},
+                }
+            }
+            
+            let data = LtacData {
+                data_type : data_type,
+                name : constant.name.clone(),
+                val : values.join(", "),
+            };
+            
+            self.file.data.push(data);
+            self.tables.insert(constant.name.clone(), constant.clone());
+        }
+    }
+    
     // Converts AST functions to LTAC functions
     // Make two passes; the first collects information, and the second does construction
     fn build_functions(&mut self, tree : &AstTree) -> bool {
-        // Collect information- for now, only names
+        // Collect information- the record types and function names
+        for record in tree.records.iter() {
+            self.records.insert(record.name.clone(), record.clone());
+        }
+        
+        for proc_type in tree.procs.iter() {
+            self.procs.insert(proc_type.name.clone(), proc_type.clone());
+        }
+        
         for func in tree.functions.iter() {
             let name = func.name.clone();
             let mut func_type = DataType::Void;
@@ -123,8 +318,15 @@ impl LtacBuilder {
             if func.data_type != DataType::None {
                 func_type = func.data_type.clone();
             }
-        
-            self.functions.insert(name, func_type);
+            
+            // Parameters declared after "..." are optional, so those functions are not variadic
+            let sig = FuncSig {
+                params : func.args.iter().map(|a| a.data_type.clone()).collect(),
+                is_variadic : func.is_variadic && func.fixed_args == func.args.len(),
+            };
+            
+            self.functions.insert(name.clone(), func_type);
+            self.signatures.insert(name, sig);
         }
         
         // Build everything
@@ -149,11 +351,22 @@ impl LtacBuilder {
                     Some(t) => self.current_type = t.clone(),
                     None => self.current_type = DataType::Void,
                 };
-            
+                
                 // Create the function and load the arguments
                 let mut fc = ltac::create_instr(LtacType::Func);
                 fc.name = func.name.clone();
                 fc.arg1_val = 0;
+                fc.line_no = func.line_no;
+                
+                self.file.debug.push(LtacDebugFunc {
+                    name : func.name.clone(),
+                    line_no : func.line_no,
+                    stack_size : 0,
+                    vars : Vec::new(),
+                });
+                
+                // Globals are visible everywhere, but locals of the same name shadow them
+                self.vars = self.globals.clone();
                 
                 let pos = self.file.code.len();        // The position of the code before we add anything
                 let mut arg_pos = 1;                   // Needed for function arguments
@@ -165,12 +378,14 @@ impl LtacBuilder {
                     flt_arg_pos = ret.2;
                 }
                 
+                build_table_vars(self, &func.statements);
+                
                 // Build the body and calculate the stack size
                 if !self.build_block(&func.statements) {
                     return false;
                 }
                 
-                if self.vars.len() > 0 {
+                if self.stack_pos > 0 {
                     let mut stack_size = 0;
                     while stack_size < (self.stack_pos + 1) {
                         stack_size = stack_size + 16;
@@ -180,6 +395,10 @@ impl LtacBuilder {
                     fc.arg2_val = self.stack_pos;    // At this point, only needed by Arm
                 }
                 
+                if let Some(debug) = self.file.debug.last_mut() {
+                    debug.stack_size = fc.arg1_val;
+                }
+                
                 self.file.code.insert(pos, fc);
                 self.stack_pos = 0;
                 self.vars.clear();
@@ -188,21 +407,27 @@ impl LtacBuilder {
         
         true
     }
-
+    
     // Builds function body
-    fn build_block(&mut self, statements : &Vec<AstStmt>) -> bool {
+    pub fn build_block(&mut self, statements : &Vec<AstStmt>) -> bool {
         let mut code = true;
-    
+        
         for line in statements {
+            let start = self.file.code.len();
+            self.syntax.set_data(&line);
+            
             match &line.stmt_type {
                 AstStmtType::VarDec => code = build_var_dec(self, &line, 0, 0).0,
                 AstStmtType::VarAssign => code = build_var_assign(self, &line),
                 AstStmtType::ArrayAssign => code = build_array_assign(self, &line),
-                AstStmtType::If => build_cond(self, &line),
-                AstStmtType::Elif => build_cond(self, &line),
-                AstStmtType::Else => build_cond(self, &line),
-                AstStmtType::While => build_while(self, &line),
-                AstStmtType::For => build_for_loop(self, &line),
+                AstStmtType::FieldAssign => code = build_field_assign(self, &line),
+                AstStmtType::If => code = build_cond(self, &line),
+                AstStmtType::Elif => code = build_cond(self, &line),
+                AstStmtType::Else => code = build_cond(self, &line),
+                AstStmtType::While => code = build_while(self, &line),
+                AstStmtType::For => code = build_for_loop(self, &line),
+                AstStmtType::Case => code = build_case(self, &line),
+                AstStmtType::When => {},
                 AstStmtType::Break => build_break(self),
                 AstStmtType::Continue => build_continue(self),
                 AstStmtType::FuncCall => code = build_func_call(self, &line),
@@ -211,11 +436,14 @@ impl LtacBuilder {
                 AstStmtType::End => code = build_end(self, &line),
             }
             
+            ltac::set_line_no(&mut self.file.code, start, line.line_no);
+            
             if !code {
                 break;
             }
             
-            if line.sub_block.len() > 0 {
+            // The case statement builds its own arms
+            if line.sub_block.len() > 0 && line.stmt_type != AstStmtType::Case {
                 code = self.build_block(&line.sub_block);
                 if !code {
                     break;
@@ -226,6 +454,21 @@ impl LtacBuilder {
         code
     }
     
+    // Adds a local variable, and records it for the debugger
+    pub fn add_var(&mut self, name : String, v : Var) {
+        if let Some(debug) = self.file.debug.last_mut() {
+            debug.vars.push(LtacDebugVar {
+                name : name.clone(),
+                data_type : v.data_type.clone(),
+                sub_type : v.sub_type.clone(),
+                pos : v.pos,
+                is_param : v.is_param,
+            });
+        }
+        
+        self.vars.insert(name, v);
+    }
+    
     // Searches for and returns a variable
     pub fn var_exists(&self, name : &String) -> bool {
         match &self.vars.get(name) {
@@ -255,7 +498,15 @@ impl LtacBuilder {
             _ => return Err(()),
         }
     }
-
+    
+    // Returns the procedure type of a variable, if we can call through it
+    pub fn get_proc(&self, name : &String) -> Option<&AstProc> {
+        match &self.vars.get(name) {
+            Some(Var { data_type : DataType::Proc(p), .. }) => return self.procs.get(p),
+            _ => return None,
+        }
+    }
+    
     // Builds a string and adds it to the data section
     pub fn build_string(&mut self, val : String) -> String {
         // Create the string name
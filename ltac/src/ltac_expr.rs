@@ -22,81 +22,132 @@ use std::mem;
 
 use crate::ast;
 use crate::ast::{DataType, AstStmt, AstArg, AstStmtType, AstArgType};
+use crate::ast_expr::{AstExpr, arg_expr, call_args, to_infix};
+use crate::syntax::*;
 use crate::ltac;
 use crate::ltac::{LtacType, LtacArg, LtacInstr};
 
 use crate::ltac_builder::*;
+use crate::ltac_array::bounds_check;
 use crate::ltac_func::*;
+use crate::ltac_record::*;
 use crate::ltac_utils::*;
+use crate::ltac_var::build_var_assign;
 
 // Builds assignments for numerical variables
 pub fn build_var_math(builder : &mut LtacBuilder, line : &AstStmt, var : &Var) -> bool {
     builder.syntax.set_data(line);
-
-    let args = &line.args;
-    let first_type = args.first().unwrap().arg_type.clone();
+    
+    // The values are computed in the element type for arrays
+    let data_type = match var.data_type {
+        DataType::Ptr => var.sub_type.clone(),
+        _ => var.data_type.clone(),
+    };
+    
+    // A computed index is stored before the value takes the registers
+    let mut sub_args = line.sub_args.clone();
+    
+    if sub_args.len() > 0 && !is_simple_index(builder, &sub_args) {
+        let index = match &line.sub_expr {
+            Some(index) => build_expr_temp(builder, index, &DataType::Int),
+            
+            None => {
+                builder.syntax.ltac_error2("Invalid array index.".to_string());
+                return false;
+            },
+        };
+        
+        match index {
+            Some(index) => sub_args = vec![index],
+            None => return false,
+        }
+    }
+    
+    let expr = match &line.expr {
+        Some(expr) => build_expr_spills(builder, expr, &data_type, false),
+        
+        None => {
+            builder.syntax.ltac_error2("Invalid expression.".to_string());
+            return false;
+        },
+    };
+    
+    let expr = match expr {
+        Some(expr) => expr,
+        None => return false,
+    };
+    
     let reg_no = 1;
     
-    if !build_var_expr(builder, args, var, reg_no) {
+    if !build_var_expr(builder, &expr, var, reg_no) {
         return false;
     }
     
     let mut instr : LtacInstr;
     
+    // A single value other than a variable or field is stored directly
+    let is_single = match &expr {
+        AstExpr::Operand(arg) => arg.arg_type != AstArgType::Id && arg.arg_type != AstArgType::Field,
+        _ => false,
+    };
+    
     //Store the result back
     // If it was a single assign (no math), compact the instructions
-    if line.args.len() == 1 && first_type != AstArgType::Id {
+    if is_single {
         let top = builder.file.code.pop().unwrap();
         
         instr = ltac::create_instr(top.instr_type);
-        instr.arg1 = LtacArg::Mem(var.pos);
+        instr.arg1 = var.mem();
         instr.arg2 = top.arg2;
         instr.arg2_val = top.arg2_val;
-        
+    
     } else {
         instr = mov_for_type(&var.data_type, &var.sub_type);
-        instr.arg1 = LtacArg::Mem(var.pos);
+        instr.arg1 = var.mem();
         instr.arg2 = reg_for_type(&var.data_type, &var.sub_type, reg_no);
     }
     
     // If we have an array, there's additional work
-    if line.sub_args.len() > 0 && var.data_type == DataType::Ptr {
-        let first_arg = line.sub_args.last().unwrap();
+    // A computed index is in a temporary by now, so the index is a number or a variable
+    if sub_args.len() == 1 && var.data_type == DataType::Ptr {
+        let first_arg = sub_args.last().unwrap();
         let mut offset_size = 4;
         
-        if var.sub_type == DataType::Byte|| var.sub_type == DataType::UByte {
+        if var.sub_type == DataType::Byte|| var.sub_type == DataType::UByte
+            || var.sub_type == DataType::Char {
             offset_size = 1;
         } else if var.sub_type == DataType::Short || var.sub_type == DataType::UShort {
             offset_size = 2;
         } else if var.sub_type == DataType::Int64 || var.sub_type == DataType::UInt64
-            || var.sub_type == DataType::Double || var.sub_type == DataType::Str {
+            || var.sub_type == DataType::Double || var.sub_type == DataType::Str
+            || matches!(var.sub_type, DataType::Proc(_)) {
             offset_size = 8;
         }
         
-        if line.sub_args.len() == 1 {
-            if first_arg.arg_type == AstArgType::IntL {
-                let offset = (first_arg.u64_val as i32) * offset_size;
-                instr.arg1 = LtacArg::MemOffsetImm(var.pos, offset);
-            } else if first_arg.arg_type == AstArgType::Id {
-                match builder.vars.get(&first_arg.str_val) {
-                    Some(v) => instr.arg1 = LtacArg::MemOffsetMem(var.pos, v.pos, offset_size),
-                    None => {
-                        builder.syntax.ltac_error2("Invalid offset variable.".to_string());
-                        return false;
-                    },
-                }
-            }
-        } else {
-            // We create a dummy variable so the positional math is done as integers
-            let var2 = Var {
-                pos : 0,
-                data_type : DataType::Int,
-                sub_type : DataType::None,
-                is_param : false,
+        // The value is in register 1, so register 0 is free for the bounds check
+        if first_arg.arg_type == AstArgType::IntL {
+            let offset = (first_arg.u64_val as i32) * offset_size;
+            instr.arg1 = LtacArg::MemOffsetImm(var.pos, offset);
+            
+            let check = bounds_check(builder, &line.name, LtacArg::I32(first_arg.u64_val as i32), 0);
+            builder.file.code.extend(check);
+        } else if first_arg.arg_type == AstArgType::Id {
+            let index_pos = match builder.vars.get(&first_arg.str_val) {
+                Some(v2) if v2.is_global() => {
+                    builder.syntax.ltac_error2("Global variables cannot be used as array indexes.".to_string()).code(E_GLOBAL_INDEX);
+                    return false;
+                },
+                Some(v) => v.pos,
+                None => {
+                    builder.syntax.ltac_error2("Invalid offset variable.".to_string()).code(E_UNKNOWN_NAME);
+                    return false;
+                },
             };
             
-            build_var_expr(builder, &line.sub_args, &var2, 0);
-            instr.arg1 = LtacArg::MemOffsetReg(var.pos, 0, offset_size);
+            instr.arg1 = LtacArg::MemOffsetMem(var.pos, index_pos, offset_size);
+            
+            let check = bounds_check(builder, &line.name, LtacArg::Mem(index_pos), 0);
+            builder.file.code.extend(check);
         }
     }
     
@@ -105,664 +156,914 @@ pub fn build_var_math(builder : &mut LtacBuilder, line : &AstStmt, var : &Var) -
     true
 }
 
-// TODO: I would eventually like to get rid of the "line" parameter
-// Doing so may require work in the ltac_builder module.
-fn build_var_expr(builder : &mut LtacBuilder, args : &Vec<AstArg>, var : &Var, reg_no : i32) -> bool {
+// Evaluates an expression into a temporary variable, and returns the argument to use in its place
+// The line of the statement being built is used for any errors
+pub fn build_expr_temp(builder : &mut LtacBuilder, expr : &AstExpr, data_type : &DataType) -> Option<AstArg> {
+    let mut size = size_for_type(data_type);
+    if size == 0 {
+        size = 4;
+    }
+    
+    builder.stack_pos += size;
+    let name = format!("$expr{}", builder.stack_pos);
+    
+    let var = Var {
+        pos : builder.stack_pos,
+        data_type : data_type.clone(),
+        sub_type : DataType::None,
+        is_param : false,
+        global : String::new(),
+    };
+    
+    builder.vars.insert(name.clone(), var);
+    
+    let mut stmt = ast::create_orphan_stmt(AstStmtType::VarAssign);
+    stmt.name = name.clone();
+    stmt.args = to_infix(expr);
+    stmt.expr = Some(expr.clone());
+    stmt.line = builder.syntax.current_ln.clone();
+    stmt.line_no = builder.syntax.current_ln_no;
+    stmt.col = builder.syntax.current_col;
+    
+    if !build_var_assign(builder, &stmt) {
+        return None;
+    }
+    
+    let mut arg = ast::create_arg(AstArgType::Id);
+    arg.str_val = name;
+    arg.data_type = data_type.clone();
+    Some(arg)
+}
 
-    // The control variable for negatives
-    let mut negate_next = false;
-    
-    let mut instr = mov_for_type(&var.data_type, &var.sub_type);
-    instr.arg1 = reg_for_type(&var.data_type, &var.sub_type, reg_no);
-    
-    // Control variables for sub-expressions
-    let mut is_sub_expr = false;
-    let mut sub_expr : Vec<AstArg> = Vec::new();
-    let mut layer = 1;
-    
-    for arg in args.iter() {
-        if is_sub_expr {
-            if arg.arg_type == AstArgType::OpLParen {
-                layer += 1;
-                sub_expr.push(arg.clone());
-                continue;
-            } else if arg.arg_type == AstArgType::OpRParen && layer > 1 {
-                layer -= 1;
-                sub_expr.push(arg.clone());
-                continue;
-            } else if arg.arg_type != AstArgType::OpRParen {
-                sub_expr.push(arg.clone());
-                continue;
-            }
-        }
+// Builds an expression argument into a temporary variable, with the code going to a separate block
+// This is for statements where the value is needed somewhere other than at the end of the code
+pub fn build_expr_block(builder : &mut LtacBuilder, arg : &AstArg, block : &mut Vec<LtacInstr>) -> Option<AstArg> {
+    let code = mem::take(&mut builder.file.code);
+    let temp = build_expr_temp(builder, &arg_expr(arg), &arg.data_type);
     
-        match &arg.arg_type {
-            // Parantheses
-            // Left paren- increment working register
-            AstArgType::OpLParen => is_sub_expr = true,
-            
-            // Right paren- decrement working register
-            AstArgType::OpRParen => {
-                build_var_expr(builder, &sub_expr, var, reg_no+1);
-                
-                instr.arg2 = reg_for_type(&var.data_type, &var.sub_type, reg_no+1);
-                builder.file.code.push(instr.clone());
+    block.extend(mem::replace(&mut builder.file.code, code));
+    temp
+}
+
+// Moves what the registers can't hold into temporary variables: compound values that are negated,
+// calls that aren't at the top of the tree (the call would overwrite the registers in use), and computed indexes
+fn build_expr_spills(builder : &mut LtacBuilder, expr : &AstExpr, data_type : &DataType, nested : bool) -> Option<AstExpr> {
+    match expr {
+        AstExpr::Operand(arg) if arg.arg_type == AstArgType::Expr => {
+            let temp = build_expr_temp(builder, &arg_expr(arg), data_type)?;
+            return Some(AstExpr::Operand(temp));
+        },
+        
+        AstExpr::Operand(arg) if nested && arg.arg_type == AstArgType::Id && is_call(builder, arg) => {
+            let temp = build_expr_temp(builder, expr, data_type)?;
+            return Some(AstExpr::Operand(temp));
+        },
+        
+        AstExpr::Call(_, _) if nested => {
+            let temp = build_expr_temp(builder, expr, data_type)?;
+            return Some(AstExpr::Operand(temp));
+        },
+        
+        AstExpr::Index(name, index) if builder.var_exists(&name.str_val) && !is_simple_expr(builder, index) => {
+            let temp = build_expr_temp(builder, index, &DataType::Int)?;
+            return Some(AstExpr::Index(name.clone(), Box::new(AstExpr::Operand(temp))));
+        },
+        
+        // Only plain values can be negated as they are loaded
+        AstExpr::Unary(AstArgType::OpNeg, value) => {
+            match &**value {
+                AstExpr::Operand(arg) if arg.sub_args.len() == 0 && arg.arg_type != AstArgType::Expr => {
+                    return Some(expr.clone());
+                },
                 
-                sub_expr = Vec::new();
-                is_sub_expr = false;
-            },
+                _ => {
+                    let temp = build_expr_temp(builder, value, data_type)?;
+                    return Some(AstExpr::Unary(AstArgType::OpNeg, Box::new(AstExpr::Operand(temp))));
+                },
+            }
+        },
         
-            // Assign byte literals
-            AstArgType::ByteL => {
-                if negate_next {
-                    builder.syntax.ltac_error2("Negation invalid for this type.".to_string());
-                    return false;
-                }
+        // A right side with operators of its own is built while the left side holds a register
+        AstExpr::Binary(op, lhs, rhs) => {
+            let is_group = matches!(**rhs, AstExpr::Binary(..));
             
-                if var.data_type == DataType::Byte || var.sub_type == DataType::Byte {
-                    instr.arg2 = LtacArg::Byte(arg.u8_val as i8);
-                } else if var.data_type == DataType::UByte || var.sub_type == DataType::UByte {
-                    instr.arg2 = LtacArg::UByte(arg.u8_val);
-                } else {
-                    builder.syntax.ltac_error2("Invalid use of byte literal.".to_string());
+            let lhs = build_expr_spills(builder, lhs, data_type, nested)?;
+            let rhs = build_expr_spills(builder, rhs, data_type, nested || is_group)?;
+            return Some(AstExpr::Binary(op.clone(), Box::new(lhs), Box::new(rhs)));
+        },
+        
+        _ => return Some(expr.clone()),
+    }
+}
+
+// Checks whether an argument calls a function; this follows the order used by the expression builder
+fn is_call(builder : &LtacBuilder, arg : &AstArg) -> bool {
+    if builder.get_proc(&arg.str_val).is_some() {
+        return !matches!(arg.data_type, DataType::Proc(_));
+    } else if builder.var_exists(&arg.str_val) {
+        return false;
+    }
+    
+    arg.str_val == "syscall" || builder.function_exists(&arg.str_val)
+}
+
+// Checks whether an array index can be used as a memory operand directly
+pub fn is_simple_index(builder : &LtacBuilder, sub_args : &Vec<AstArg>) -> bool {
+    if sub_args.len() != 1 {
+        return false;
+    }
+    
+    let index = sub_args.first().unwrap();
+    
+    match &index.arg_type {
+        AstArgType::IntL => return true,
+        AstArgType::Id => return index.sub_args.len() == 0 && builder.var_exists(&index.str_val),
+        _ => return false,
+    }
+}
+
+// The same check, for the index of an element in a tree
+fn is_simple_expr(builder : &LtacBuilder, index : &AstExpr) -> bool {
+    match index {
+        AstExpr::Operand(arg) => return is_simple_index(builder, &vec![arg.clone()]),
+        _ => return false,
+    }
+}
+
+// Builds an expression into a register
+// The left side goes in first; a right side with operators of its own is built in the
+// next register, and then the two are combined
+fn build_var_expr(builder : &mut LtacBuilder, expr : &AstExpr, var : &Var, reg_no : i32) -> bool {
+    match expr {
+        AstExpr::Binary(op, lhs, rhs) => {
+            if !build_var_expr(builder, lhs, var, reg_no) {
+                return false;
+            }
+            
+            let mut instr = match build_op(builder, op, var, reg_no) {
+                Some(instr) => instr,
+                None => return false,
+            };
+            
+            if let AstExpr::Binary(..) = **rhs {
+                if !build_var_expr(builder, rhs, var, reg_no + 1) {
                     return false;
                 }
                 
-                builder.file.code.push(instr.clone());
+                instr.arg2 = reg_for_type(&var.data_type, &var.sub_type, reg_no + 1);
+                builder.file.code.push(instr);
+                return true;
+            }
+            
+            return build_expr_operand(builder, rhs, var, reg_no, &mut instr);
+        },
+        
+        _ => {
+            let mut instr = mov_for_type(&var.data_type, &var.sub_type);
+            instr.arg1 = reg_for_type(&var.data_type, &var.sub_type, reg_no);
+            return build_expr_operand(builder, expr, var, reg_no, &mut instr);
+        },
+    }
+}
+
+// Builds a single value as the second operand of an instruction, and adds the instruction
+// A negated literal is negated here; a negated variable is subtracted from 0
+fn build_expr_operand(builder : &mut LtacBuilder, expr : &AstExpr, var : &Var, reg_no : i32, instr : &mut LtacInstr) -> bool {
+    let (arg, index, negate) = match expr {
+        AstExpr::Operand(arg) => (arg.clone(), None, false),
+        AstExpr::Index(name, index) => (name.clone(), Some(&**index), false),
+        
+        AstExpr::Unary(AstArgType::OpNeg, value) => match &**value {
+            AstExpr::Operand(arg) => (arg.clone(), None, true),
+            
+            _ => {
+                builder.syntax.ltac_error2("Invalid use of negation operator.".to_string()).code(E_INVALID_OP);
+                return false;
             },
+        },
+        
+        AstExpr::Call(name, args) => {
+            let mut arg = name.clone();
+            arg.sub_args = call_args(args);
+            (arg, None, false)
+        },
+        
+        _ => {
+            builder.syntax.ltac_error2("Invalid expression.".to_string());
+            return false;
+        },
+    };
+    
+    // The control variable for negatives
+    let mut negate_next = negate;
+    
+    match &arg.arg_type {
+        // Assign byte literals
+        AstArgType::ByteL => {
+            if negate_next {
+                builder.syntax.ltac_error2("Negation invalid for this type.".to_string()).code(E_INVALID_OP);
+                return false;
+            }
+            
+            if var.data_type == DataType::Byte || var.sub_type == DataType::Byte {
+                instr.arg2 = LtacArg::Byte(arg.u8_val as i8);
+            } else if var.data_type == DataType::UByte || var.sub_type == DataType::UByte {
+                instr.arg2 = LtacArg::UByte(arg.u8_val);
+            } else {
+                builder.syntax.ltac_error2("Invalid use of byte literal.".to_string()).code(E_TYPE_MISMATCH);
+                return false;
+            }
+            
+            builder.file.code.push(instr.clone());
+        },
+        
+        // Assign short literals
+        AstArgType::ShortL => {
+            if negate_next {
+                builder.syntax.ltac_error2("Negation invalid for this type.".to_string()).code(E_INVALID_OP);
+                return false;
+            }
             
-            // Assign short literals
-            AstArgType::ShortL => {
+            if var.data_type == DataType::Short || var.sub_type == DataType::Short {
+                instr.arg2 = LtacArg::I16(arg.u16_val as i16);
+            } else if var.data_type == DataType::UShort || var.sub_type == DataType::UShort {
+                instr.arg2 = LtacArg::U16(arg.u16_val);
+            } else {
+                builder.syntax.ltac_error2("Invalid use of short literal.".to_string()).code(E_TYPE_MISMATCH);
+                return false;
+            }
+            
+            builder.file.code.push(instr.clone());
+        },
+        
+        // ===============================================================
+        // Assign integer literals
+        
+        AstArgType::IntL => {
+            // Pointer offsets count elements, so they are scaled to bytes
+            if let DataType::Pointer(_) = var.data_type {
+                let mut val = (arg.u64_val as i64) * (size_for_type(&var.sub_type) as i64);
+                
                 if negate_next {
-                    builder.syntax.ltac_error2("Negation invalid for this type.".to_string());
-                    return false;
+                    val = val.wrapping_neg();
+                    negate_next = false;
                 }
                 
-                if var.data_type == DataType::Short || var.sub_type == DataType::Short {
-                    instr.arg2 = LtacArg::I16(arg.u16_val as i16);
-                } else if var.data_type == DataType::UShort || var.sub_type == DataType::UShort {
-                    instr.arg2 = LtacArg::U16(arg.u16_val);
-                } else {
-                    builder.syntax.ltac_error2("Invalid use of short literal.".to_string());
-                    return false;
-                }
-                    
+                instr.arg2 = LtacArg::I64(val);
                 builder.file.code.push(instr.clone());
-            },
-        
-            // ===============================================================
-            // Assign integer literals
-            
-            AstArgType::IntL => {
-                // Bytes
-                if var.data_type == DataType::Byte || var.data_type == DataType::Char
-                    || var.sub_type == DataType::Byte {
-                    let val = arg.u64_val as i32;
-                    
-                    // TODO: Why the hell is this an error? Its getting thrown when using char values
-                    /*if mem::size_of::<i8>() > (val as usize) {
-                        builder.syntax.ltac_error(&line, "Integer is too big to fit into byte.".to_string());
-                        return false;
-                    }*/
-                    
-                    let parts = unsafe { mem::transmute::<i32, [i8; 4]>(val) };
-                    let mut result = parts[0];
-                    
-                    if negate_next {
-                        result = -result;
-                        negate_next = false;
-                    }
-                    
-                    instr.arg2 = LtacArg::Byte(result);
-                    builder.file.code.push(instr.clone());
-                    
-                // UByte
-                } else if var.data_type == DataType::UByte || var.sub_type == DataType::UByte {
-                    let val = arg.u64_val as u32;
-                    
-                    // TODO: Why are we getting this error?
-                    /*if mem::size_of::<u8>() < (val as usize) {
-                        builder.syntax.ltac_error(&line, "Integer is too big to fit into ubyte.".to_string());
-                        return false;
-                    }*/
-                    
-                    let parts = unsafe { mem::transmute::<u32, [u8; 4]>(val) };
-                    let result = parts[0];
-                    
-                    instr.arg2 = LtacArg::UByte(result);
-                    builder.file.code.push(instr.clone());
-                    
-                // Short
-                } else if var.data_type == DataType::Short || var.sub_type == DataType::Short {
-                    let val = arg.u64_val as i32;
-                    
-                    /*if mem::size_of::<u16>() > (val as usize) {
-                        builder.syntax.ltac_error(&line, "Integer is too big to fit into short.".to_string());
-                        return false;
-                    }*/
-                    
-                    let parts = unsafe { mem::transmute::<i32, [i16; 2]>(val) };
-                    let mut result = parts[0];
-                    
-                    if negate_next {
-                        result = -result;
-                        negate_next = false;
-                    }
-                    
-                    instr.arg2 = LtacArg::I16(result);
-                    builder.file.code.push(instr.clone());
-                    
-                // UShort
-                } else if var.data_type == DataType::UShort || var.sub_type == DataType::UShort {
-                    let val = arg.u64_val as u32;
-                    
-                    /*if mem::size_of::<u16>() > (val as usize) {
-                        builder.syntax.ltac_error(&line, "Integer is too big to fit into ushort.".to_string());
-                        return false;
-                    }*/
-                    
-                    let parts = unsafe { mem::transmute::<u32, [u16; 2]>(val) };
-                    let result = parts[0];
-                    
-                    instr.arg2 = LtacArg::U16(result);
-                    builder.file.code.push(instr.clone());
-                    
-                // Integers and integer arrays
-                } else if var.data_type == DataType::Int || var.sub_type == DataType::Int {
-                    let mut val = arg.u64_val as i32;
-                    
-                    if negate_next {
-                        val = -val;
-                        negate_next = false;
-                    }
-                    
-                    instr.arg2 = LtacArg::I32(val);
-                    builder.file.code.push(instr.clone());
-                    
-                } else if var.data_type == DataType::UInt || var.sub_type == DataType::UInt {
-                    instr.arg2 = LtacArg::U32(arg.u64_val as u32);
-                    builder.file.code.push(instr.clone());
-                    
-                } else if var.data_type == DataType::Int64  || var.sub_type == DataType::Int64 {
-                    let mut val = arg.u64_val as i64;
-                    
-                    if negate_next {
-                        val = -val;
-                        negate_next = false;
-                    }
-                    
-                    instr.arg2 = LtacArg::I64(val);
-                    builder.file.code.push(instr.clone());
-                    
-                } else if var.data_type == DataType::UInt64 || var.sub_type == DataType::UInt64 {
-                    instr.arg2 = LtacArg::U64(arg.u64_val);
-                    builder.file.code.push(instr.clone());
-                    
-                // String arrays
-                // TODO: I'm not sure how good an idea this is
-                } else if var.data_type == DataType::Ptr && var.sub_type == DataType::Str {
-                    instr.arg2 = LtacArg::I64(0);
-                    builder.file.code.push(instr.clone());
-                    
-                // Invalid
-                } else {
-                    builder.syntax.ltac_error2("Invalid use of integer.".to_string());
-                    return false;
-                }
+            
+            // Bytes
+            } else if var.data_type == DataType::Byte || var.data_type == DataType::Char
+                || var.sub_type == DataType::Byte {
+                let val = arg.u64_val as i32;
+                
+                let parts = unsafe { mem::transmute::<i32, [i8; 4]>(val) };
+                let mut result = parts[0];
                 
-                // If the negate flag is still active at this point, we used it in the wrong place.
                 if negate_next {
-                    builder.syntax.ltac_error2("Negation invalid for this type.".to_string());
-                    return false;
-                }
-            },
-            
-            // ===============================================================
-            // Assign float literals
-            
-            AstArgType::FloatL => {
-                if var.data_type == DataType::Float || var.sub_type == DataType::Float {
-                    let name = builder.build_float(arg.f64_val, false, negate_next);
-                    instr.arg2 = LtacArg::F32(name);
-                    builder.file.code.push(instr.clone());
-                
-                } else if var.data_type == DataType::Double || var.sub_type == DataType::Double {
-                    let name = builder.build_float(arg.f64_val, true, negate_next);
-                    instr.arg2 = LtacArg::F64(name);
-                    builder.file.code.push(instr.clone());
-                    
-                } else {
-                    builder.syntax.ltac_error2("Invalid use of float literal.".to_string());
-                    return false;
+                    result = result.wrapping_neg();
+                    negate_next = false;
                 }
                 
-                negate_next = false;
-            },
-            
-            // ===============================================================
-            // Strings and characters
-            
-            AstArgType::CharL => {
-                if var.data_type == DataType::Char || var.data_type == DataType::Byte {
-                    instr.arg2 = LtacArg::Byte(arg.char_val as i8);
-                    builder.file.code.push(instr.clone());
-                    
-                } else {
-                    builder.syntax.ltac_error2("Invalid use of char literal.".to_string());
-                }
-            },
+                instr.arg2 = LtacArg::Byte(result);
+                builder.file.code.push(instr.clone());
             
-            AstArgType::StringL => {
-                let name = builder.build_string(arg.str_val.clone());
+            // UByte
+            } else if var.data_type == DataType::UByte || var.sub_type == DataType::UByte {
+                let val = arg.u64_val as u32;
                 
-                let mut instr2 = ltac::create_instr(LtacType::MovQ);
-                instr2.arg1 = LtacArg::Reg64(0);
-                instr2.arg2 = LtacArg::PtrLcl(name); 
-                builder.file.code.push(instr2);
+                let parts = unsafe { mem::transmute::<u32, [u8; 4]>(val) };
+                let result = parts[0];
                 
-                instr.arg2 = LtacArg::Reg64(0);
+                instr.arg2 = LtacArg::UByte(result);
                 builder.file.code.push(instr.clone());
-            },
-            
-            // ===============================================================
-            // Variables and functions
             
-            AstArgType::Id if builder.var_exists(&arg.str_val) => {
-                if !build_expr_var(builder, &arg, &var, reg_no, negate_next, &mut instr) {
-                    return false;
+            // Short
+            } else if var.data_type == DataType::Short || var.sub_type == DataType::Short {
+                let val = arg.u64_val as i32;
+                
+                let parts = unsafe { mem::transmute::<i32, [i16; 2]>(val) };
+                let mut result = parts[0];
+                
+                if negate_next {
+                    result = result.wrapping_neg();
+                    negate_next = false;
                 }
                 
-                negate_next = false;  
-            },
+                instr.arg2 = LtacArg::I16(result);
+                builder.file.code.push(instr.clone());
             
-            // System calls
-            AstArgType::Id if arg.str_val == "syscall" => {
+            // UShort
+            } else if var.data_type == DataType::UShort || var.sub_type == DataType::UShort {
+                let val = arg.u64_val as u32;
                 
-                let mut stmt = ast::create_orphan_stmt(AstStmtType::FuncCall);
-                stmt.name = arg.str_val.clone();
-                stmt.args = arg.sub_args.clone();
-                build_func_call(builder, &stmt);
+                let parts = unsafe { mem::transmute::<u32, [u16; 2]>(val) };
+                let result = parts[0];
                 
-                if var.data_type == DataType::Int || var.data_type == DataType::UInt {
-                    instr.arg2 = LtacArg::RetRegI32;
-                } else if var.data_type == DataType::Int64 || var.data_type == DataType::UInt64 {
-                    instr.arg2 = LtacArg::RetRegI64;
-                } else {
-                    builder.syntax.ltac_error2("You can only assign system call returns to integers.".to_string());
-                    return false;
+                instr.arg2 = LtacArg::U16(result);
+                builder.file.code.push(instr.clone());
+            
+            // Integers and integer arrays
+            } else if var.data_type == DataType::Int || var.sub_type == DataType::Int {
+                let mut val = arg.u64_val as i32;
+                
+                if negate_next {
+                    val = val.wrapping_neg();
+                    negate_next = false;
                 }
                 
+                instr.arg2 = LtacArg::I32(val);
                 builder.file.code.push(instr.clone());
-            },
             
-            // Function calls
-            AstArgType::Id if builder.function_exists(&arg.str_val) => {
-                if !build_expr_func_call(builder, &arg, &var, reg_no, &mut instr) {
-                    return false;
-                }
-            },
+            } else if var.data_type == DataType::UInt || var.sub_type == DataType::UInt {
+                instr.arg2 = LtacArg::U32(arg.u64_val as u32);
+                builder.file.code.push(instr.clone());
             
-            // Check enumerations, and throw an error if there is no such thing
-            AstArgType::Id => {
-                // Check enumerated values
-                // TODO: I don't like this
-                match var.data_type {
-                    DataType::Enum(ref name) => {
-                        match builder.clone().enums.get(name) {
-                            Some(t) => {
-                                let num = match t.values.get(&arg.str_val) {
-                                    Some(n) => *n,
-                                    None => 0,
-                                };
-                                
-                                instr.arg2 = LtacArg::I32(num);
-                            },
-                    
-                            None => instr.arg2 = LtacArg::Empty,
-                        }
-                    },
-                    
-                    _ => {},
-                }
+            } else if var.data_type == DataType::Int64  || var.sub_type == DataType::Int64 {
+                let mut val = arg.u64_val as i64;
                 
-                if instr.arg2 != LtacArg::Empty {
-                    builder.file.code.push(instr.clone());
-                    continue;
+                if negate_next {
+                    val = val.wrapping_neg();
+                    negate_next = false;
                 }
                 
-                // If we get to this point, throw an error
-                let mut msg = "Invalid function, constant, or variable name: ".to_string();
-                msg.push_str(&arg.str_val);
+                instr.arg2 = LtacArg::I64(val);
+                builder.file.code.push(instr.clone());
             
-                builder.syntax.ltac_error2(msg);
+            } else if var.data_type == DataType::UInt64 || var.sub_type == DataType::UInt64 {
+                instr.arg2 = LtacArg::U64(arg.u64_val);
+                builder.file.code.push(instr.clone());
+            
+            // String arrays
+            // TODO: I'm not sure how good an idea this is
+            } else if var.data_type == DataType::Ptr && var.sub_type == DataType::Str {
+                instr.arg2 = LtacArg::I64(0);
+                builder.file.code.push(instr.clone());
+            
+            // Invalid
+            } else {
+                builder.syntax.ltac_error2("Invalid use of integer.".to_string()).code(E_TYPE_MISMATCH);
                 return false;
-            },
+            }
             
-            // Sizeof statement
-            // To get the size, get the array variable, and the size is stored in the upper 4 bytes
+            // If the negate flag is still active at this point, we used it in the wrong place.
+            if negate_next {
+                builder.syntax.ltac_error2("Negation invalid for this type.".to_string()).code(E_INVALID_OP);
+                return false;
+            }
+        },
+        
+        // ===============================================================
+        // Assign float literals
+        
+        AstArgType::FloatL => {
+            if var.data_type == DataType::Float || var.sub_type == DataType::Float {
+                let name = builder.build_float(arg.f64_val, false, negate_next);
+                instr.arg2 = LtacArg::F32(name);
+                builder.file.code.push(instr.clone());
             
-            AstArgType::Sizeof => {
-                let name_arg = arg.sub_args.first().unwrap();
-                let array_var = match builder.get_var(&name_arg.str_val) {
-                    Ok(v) if v.data_type == DataType::Ptr => v,
-                    
-                    Ok(_v) => {
-                        builder.syntax.ltac_error2("Sizeof can only be used with arrays and strings.".to_string());
-                        return false;
-                    },
-                    
-                    Err(_e) => {
-                        builder.syntax.ltac_error2("Unknown array or string.".to_string());
-                        return false;
-                    },
-                };
-                
-                let pos = array_var.pos - 8;
-                let reg = reg_for_type(&var.data_type, &DataType::None, reg_no);
-                
-                let mut instr2 = mov_for_type(&var.data_type, &DataType::None);
-                instr2.arg1 = reg.clone();
-                instr2.arg2 = LtacArg::Mem(pos);
-                builder.file.code.push(instr2);
-                
-                instr.arg2 = reg;
+            } else if var.data_type == DataType::Double || var.sub_type == DataType::Double {
+                let name = builder.build_float(arg.f64_val, true, negate_next);
+                instr.arg2 = LtacArg::F64(name);
                 builder.file.code.push(instr.clone());
-            },
             
-            // Addrof statement
+            } else {
+                builder.syntax.ltac_error2("Invalid use of float literal.".to_string()).code(E_TYPE_MISMATCH);
+                return false;
+            }
+        },
+        
+        // ===============================================================
+        // Strings and characters
+        
+        AstArgType::CharL => {
+            if var.data_type == DataType::Char || var.data_type == DataType::Byte
+                || (var.data_type == DataType::Ptr && var.sub_type == DataType::Char) {
+                instr.arg2 = LtacArg::Byte(arg.char_val as i8);
+                builder.file.code.push(instr.clone());
             
-            AstArgType::AddrOf => {
-                let name_arg = arg.sub_args.first().unwrap();
-                let ref_var = match builder.get_var(&name_arg.str_val) {
-                    Ok(v) => v,
-                    
-                    Err(_e) => {
-                        builder.syntax.ltac_error2("Unknown variable reference.".to_string());
-                        return false;
-                    },
-                };
-                
-                let mut instr2 = ltac::create_instr(LtacType::LdAddr);
-                instr2.arg1 = LtacArg::Reg64(reg_no);
-                instr2.arg2 = LtacArg::Mem(ref_var.pos);
-                builder.file.code.push(instr2);
+            } else {
+                builder.syntax.ltac_error2("Invalid use of char literal.".to_string()).code(E_TYPE_MISMATCH);
+            }
+        },
+        
+        AstArgType::StringL => {
+            let name = builder.build_string(arg.str_val.clone());
+            
+            let mut instr2 = ltac::create_instr(LtacType::MovQ);
+            instr2.arg1 = LtacArg::Reg64(0);
+            instr2.arg2 = LtacArg::PtrLcl(name); 
+            builder.file.code.push(instr2);
+            
+            instr.arg2 = LtacArg::Reg64(0);
+            builder.file.code.push(instr.clone());
+        },
+        
+        // ===============================================================
+        // Variables and functions
+        
+        // Calls through a procedure variable
+        // The type checker gives these the return type; otherwise, the variable is a value
+        AstArgType::Id if builder.get_proc(&arg.str_val).is_some()
+                && !matches!(arg.data_type, DataType::Proc(_)) => {
+            if !build_expr_func_call(builder, &arg, &var, reg_no, instr) {
+                return false;
+            }
+        },
+        
+        AstArgType::Id if builder.var_exists(&arg.str_val) => {
+            if !build_expr_var(builder, &arg, index, var, reg_no, negate_next, instr) {
+                return false;
+            }
+        },
+        
+        // System calls
+        AstArgType::Id if arg.str_val == "syscall" => {
+            
+            let mut stmt = ast::create_orphan_stmt(AstStmtType::FuncCall);
+            stmt.name = arg.str_val.clone();
+            stmt.args = arg.sub_args.clone();
+            build_func_call(builder, &stmt);
+            
+            if var.data_type == DataType::Int || var.data_type == DataType::UInt {
+                instr.arg2 = LtacArg::RetRegI32;
+            } else if var.data_type == DataType::Int64 || var.data_type == DataType::UInt64 {
+                instr.arg2 = LtacArg::RetRegI64;
+            } else {
+                builder.syntax.ltac_error2("You can only assign system call returns to integers.".to_string()).code(E_TYPE_MISMATCH);
+                return false;
+            }
+            
+            builder.file.code.push(instr.clone());
+        },
+        
+        // Function calls
+        AstArgType::Id if builder.function_exists(&arg.str_val) => {
+            if !build_expr_func_call(builder, &arg, &var, reg_no, instr) {
+                return false;
+            }
+        },
+        
+        // Record fields
+        AstArgType::Field => {
+            if negate_next {
+                builder.syntax.ltac_error2("Negation invalid for record fields.".to_string()).code(E_INVALID_OP);
+                return false;
+            }
+            
+            if !build_expr_field(builder, &arg, instr) {
+                return false;
+            }
+        },
+        
+        // Check enumerations, and throw an error if there is no such thing
+        AstArgType::Id => {
+            // Check enumerated values
+            // TODO: I don't like this
+            match var.data_type {
+                DataType::Enum(ref name) => {
+                    match builder.clone().enums.get(name) {
+                        Some(t) => {
+                            let num = match t.values.get(&arg.str_val) {
+                                Some(n) => *n,
+                                None => 0,
+                            };
+                            
+                            instr.arg2 = LtacArg::I32(num);
+                        },
+                        
+                        None => instr.arg2 = LtacArg::Empty,
+                    }
+                },
                 
-                instr.arg2 = LtacArg::Reg64(reg_no);
+                _ => {},
+            }
+            
+            if instr.arg2 != LtacArg::Empty {
                 builder.file.code.push(instr.clone());
-            },
+                return true;
+            }
             
-            // Negate operator
-            // Basically, we set a control variable. That way, if the next AST node is a literal, we simply
-            // negate it here. If its a variable, we can create a subtraction operations
+            // If we get to this point, throw an error
+            let mut msg = "Invalid function, constant, or variable name: ".to_string();
+            msg.push_str(&arg.str_val);
             
-            AstArgType::OpNeg => negate_next = true,
+            builder.syntax.ltac_error2(msg).code(E_UNKNOWN_NAME);
+            return false;
+        },
+        
+        // Sizeof statement
+        // To get the size, get the array variable, and the size is stored in the upper 4 bytes
+        
+        AstArgType::Sizeof => {
+            let name_arg = arg.sub_args.first().unwrap();
             
-            // Addition
+            // Records have a size known at compile time
+            let mut record_name = String::new();
             
-            AstArgType::OpAdd => {
-                match var.data_type {
-                    DataType::Byte => instr = ltac::create_instr(LtacType::I8Add),
-                    DataType::UByte => instr = ltac::create_instr(LtacType::U8Add),
-                    DataType::Short => instr = ltac::create_instr(LtacType::I16Add),
-                    DataType::UShort => instr = ltac::create_instr(LtacType::U16Add),
-                    DataType::Int => instr = ltac::create_instr(LtacType::I32Add),
-                    DataType::UInt => instr = ltac::create_instr(LtacType::U32Add),
-                    DataType::Int64 => instr = ltac::create_instr(LtacType::I64Add),
-                    DataType::UInt64 => instr = ltac::create_instr(LtacType::U64Add),
-                    DataType::Float => instr = ltac::create_instr(LtacType::F32Add),
-                    DataType::Double => instr = ltac::create_instr(LtacType::F64Add),
-                    
-                    DataType::Ptr if var.sub_type == DataType::Byte => instr = ltac::create_instr(LtacType::I8Add),
-                    DataType::Ptr if var.sub_type == DataType::UByte => instr = ltac::create_instr(LtacType::U8Add),
-                    DataType::Ptr if var.sub_type == DataType::Short => instr = ltac::create_instr(LtacType::I16Add),
-                    DataType::Ptr if var.sub_type == DataType::UShort => instr = ltac::create_instr(LtacType::U16Add),
-                    DataType::Ptr if var.sub_type == DataType::Int => instr = ltac::create_instr(LtacType::I32Add),
-                    DataType::Ptr if var.sub_type == DataType::UInt => instr = ltac::create_instr(LtacType::U32Add),
-                    DataType::Ptr if var.sub_type == DataType::Int64 => instr = ltac::create_instr(LtacType::I64Add),
-                    DataType::Ptr if var.sub_type == DataType::UInt64 => instr = ltac::create_instr(LtacType::U64Add),
-                    DataType::Ptr if var.sub_type == DataType::Float => instr = ltac::create_instr(LtacType::F32Add),
-                    DataType::Ptr if var.sub_type == DataType::Double => instr = ltac::create_instr(LtacType::F64Add),
-                    
-                    _ => {
-                        builder.syntax.ltac_error2("Invalid use of addition operator.".to_string());
-                        return false;
-                    },
+            if builder.records.contains_key(&name_arg.str_val) {
+                record_name = name_arg.str_val.clone();
+            } else if let Ok(v) = builder.get_var(&name_arg.str_val) {
+                if let DataType::Record(name) = &v.data_type {
+                    record_name = name.clone();
                 }
-                
-                instr.arg1 = reg_for_type(&var.data_type, &var.sub_type, reg_no);
-            },
-            
-            // Subtraction
+            }
             
-            AstArgType::OpSub => {
+            if record_name.len() > 0 {
+                let size = record_size(builder, &record_name);
+                
                 match var.data_type {
-                    DataType::Byte => instr = ltac::create_instr(LtacType::I8Sub),
-                    DataType::Short => instr = ltac::create_instr(LtacType::I16Sub),
-                    DataType::Int => instr = ltac::create_instr(LtacType::I32Sub),
-                    DataType::Int64 => instr = ltac::create_instr(LtacType::I64Sub),
-                    DataType::Float => instr = ltac::create_instr(LtacType::F32Sub),
-                    DataType::Double => instr = ltac::create_instr(LtacType::F64Sub),
-                    
-                    DataType::Ptr if var.sub_type == DataType::Byte => instr = ltac::create_instr(LtacType::I8Sub),
-                    DataType::Ptr if var.sub_type == DataType::Short => instr = ltac::create_instr(LtacType::I16Sub),
-                    DataType::Ptr if var.sub_type == DataType::Int => instr = ltac::create_instr(LtacType::I32Sub),
-                    DataType::Ptr if var.sub_type == DataType::Int64 => instr = ltac::create_instr(LtacType::I64Sub),
-                    DataType::Ptr if var.sub_type == DataType::Float => instr = ltac::create_instr(LtacType::F32Sub),
-                    DataType::Ptr if var.sub_type == DataType::Double => instr = ltac::create_instr(LtacType::F64Sub),
-                    
-                    _ => {
-                        builder.syntax.ltac_error2("Invalid use of subtraction operator.".to_string());
-                        return false;
-                    },
+                    DataType::Int64 | DataType::UInt64 => instr.arg2 = LtacArg::I64(size as i64),
+                    _ => instr.arg2 = LtacArg::I32(size),
                 }
                 
-                instr.arg1 = reg_for_type(&var.data_type, &var.sub_type, reg_no);
-            },
-            
-            // Multiplication
+                builder.file.code.push(instr.clone());
+                return true;
+            }
             
-            AstArgType::OpMul => {
-                match var.data_type {
-                    DataType::Byte => instr = ltac::create_instr(LtacType::I8Mul),
-                    DataType::UByte => instr = ltac::create_instr(LtacType::U8Mul),
-                    DataType::Short => instr = ltac::create_instr(LtacType::I16Mul),
-                    DataType::UShort => instr = ltac::create_instr(LtacType::U16Mul),
-                    DataType::Int => instr = ltac::create_instr(LtacType::I32Mul),
-                    DataType::UInt => instr = ltac::create_instr(LtacType::U32Mul),
-                    DataType::Int64 => instr = ltac::create_instr(LtacType::I64Mul),
-                    DataType::UInt64 => instr = ltac::create_instr(LtacType::U64Mul),
-                    DataType::Float => instr = ltac::create_instr(LtacType::F32Mul),
-                    DataType::Double => instr = ltac::create_instr(LtacType::F64Mul),
-                    
-                    DataType::Ptr if var.sub_type == DataType::Byte => instr = ltac::create_instr(LtacType::I8Mul),
-                    DataType::Ptr if var.sub_type == DataType::UByte => instr = ltac::create_instr(LtacType::U8Mul),
-                    DataType::Ptr if var.sub_type == DataType::Short => instr = ltac::create_instr(LtacType::I16Mul),
-                    DataType::Ptr if var.sub_type == DataType::UShort => instr = ltac::create_instr(LtacType::U16Mul),
-                    DataType::Ptr if var.sub_type == DataType::Int => instr = ltac::create_instr(LtacType::I32Mul),
-                    DataType::Ptr if var.sub_type == DataType::UInt => instr = ltac::create_instr(LtacType::U32Mul),
-                    DataType::Ptr if var.sub_type == DataType::Int64 => instr = ltac::create_instr(LtacType::I64Mul),
-                    DataType::Ptr if var.sub_type == DataType::UInt64 => instr = ltac::create_instr(LtacType::U64Mul),
-                    DataType::Ptr if var.sub_type == DataType::Float => instr = ltac::create_instr(LtacType::F32Mul),
-                    DataType::Ptr if var.sub_type == DataType::Double => instr = ltac::create_instr(LtacType::F64Mul),
-                    
-                    _ => {
-                        builder.syntax.ltac_error2("Invalid use of multiplication operator.".to_string());
-                        return false;
-                    },
-                }
+            let array_var = match builder.get_var(&name_arg.str_val) {
+                Ok(v) if v.data_type == DataType::Ptr => v,
                 
-                instr.arg1 = reg_for_type(&var.data_type, &var.sub_type, reg_no);
-            },
+                Ok(_v) => {
+                    builder.syntax.ltac_error2("Sizeof can only be used with arrays and strings.".to_string());
+                    return false;
+                },
+                
+                Err(_e) => {
+                    builder.syntax.ltac_error2("Unknown array or string.".to_string()).code(E_UNKNOWN_NAME);
+                    return false;
+                },
+            };
             
-            // Division
+            let pos = array_var.pos - 8;
+            let reg = reg_for_type(&var.data_type, &DataType::None, reg_no);
             
-            AstArgType::OpDiv => {
-                match var.data_type {
-                    DataType::Byte => instr = ltac::create_instr(LtacType::I8Div),
-                    DataType::UByte => instr = ltac::create_instr(LtacType::U8Div),
-                    DataType::Short => instr = ltac::create_instr(LtacType::I16Div),
-                    DataType::UShort => instr = ltac::create_instr(LtacType::U16Div),
-                    DataType::Int => instr = ltac::create_instr(LtacType::I32Div),
-                    DataType::UInt => instr = ltac::create_instr(LtacType::U32Div),
-                    DataType::Int64 => instr = ltac::create_instr(LtacType::I64Div),
-                    DataType::UInt64 => instr = ltac::create_instr(LtacType::U64Div),
-                    DataType::Float => instr = ltac::create_instr(LtacType::F32Div),
-                    DataType::Double => instr = ltac::create_instr(LtacType::F64Div),
-                    
-                    DataType::Ptr if var.sub_type == DataType::Byte => instr = ltac::create_instr(LtacType::I8Div),
-                    DataType::Ptr if var.sub_type == DataType::UByte => instr = ltac::create_instr(LtacType::U8Div),
-                    DataType::Ptr if var.sub_type == DataType::Short => instr = ltac::create_instr(LtacType::I16Div),
-                    DataType::Ptr if var.sub_type == DataType::UShort => instr = ltac::create_instr(LtacType::U16Div),
-                    DataType::Ptr if var.sub_type == DataType::Int => instr = ltac::create_instr(LtacType::I32Div),
-                    DataType::Ptr if var.sub_type == DataType::UInt => instr = ltac::create_instr(LtacType::U32Div),
-                    DataType::Ptr if var.sub_type == DataType::Int64 => instr = ltac::create_instr(LtacType::I64Div),
-                    DataType::Ptr if var.sub_type == DataType::UInt64 => instr = ltac::create_instr(LtacType::U64Div),
-                    DataType::Ptr if var.sub_type == DataType::Float => instr = ltac::create_instr(LtacType::F32Div),
-                    DataType::Ptr if var.sub_type == DataType::Double => instr = ltac::create_instr(LtacType::F64Div),
-                    
-                    _ => {
-                        builder.syntax.ltac_error2("Invalid use of division operator.".to_string());
-                        return false;
-                    },
-                }
-                
-                instr.arg1 = reg_for_type(&var.data_type, &var.sub_type, reg_no);
-            },
+            let mut instr2 = mov_for_type(&var.data_type, &DataType::None);
+            instr2.arg1 = reg.clone();
+            instr2.arg2 = LtacArg::Mem(pos);
+            builder.file.code.push(instr2);
             
-            // Modulo
+            instr.arg2 = reg;
+            builder.file.code.push(instr.clone());
+        },
+        
+        // Addrof statement
+        // Functions have a symbol, so their address is loaded directly
+        
+        AstArgType::AddrOf if !builder.var_exists(&arg.sub_args[0].str_val)
+                && builder.function_exists(&arg.sub_args[0].str_val) => {
+            let mut instr2 = ltac::create_instr(LtacType::MovQ);
+            instr2.arg1 = LtacArg::Reg64(reg_no);
+            instr2.arg2 = LtacArg::PtrLcl(arg.sub_args[0].str_val.clone());
+            builder.file.code.push(instr2);
             
-            AstArgType::OpMod => {
-                match var.data_type {
-                    DataType::Byte => instr = ltac::create_instr(LtacType::I8Mod),
-                    DataType::UByte => instr = ltac::create_instr(LtacType::U8Mod),
-                    DataType::Short => instr = ltac::create_instr(LtacType::I16Mod),
-                    DataType::UShort => instr = ltac::create_instr(LtacType::U16Mod),
-                    DataType::Int => instr = ltac::create_instr(LtacType::I32Mod),
-                    DataType::UInt => instr = ltac::create_instr(LtacType::U32Mod),
-                    DataType::Int64 => instr = ltac::create_instr(LtacType::I64Mod),
-                    DataType::UInt64 => instr = ltac::create_instr(LtacType::U64Mod),
-                    
-                    DataType::Ptr if var.sub_type == DataType::Byte => instr = ltac::create_instr(LtacType::I8Mod),
-                    DataType::Ptr if var.sub_type == DataType::UByte => instr = ltac::create_instr(LtacType::U8Mod),
-                    DataType::Ptr if var.sub_type == DataType::Short => instr = ltac::create_instr(LtacType::I16Mod),
-                    DataType::Ptr if var.sub_type == DataType::UShort => instr = ltac::create_instr(LtacType::U16Mod),
-                    DataType::Ptr if var.sub_type == DataType::Int => instr = ltac::create_instr(LtacType::I32Mod),
-                    DataType::Ptr if var.sub_type == DataType::UInt => instr = ltac::create_instr(LtacType::U32Mod),
-                    DataType::Ptr if var.sub_type == DataType::Int64 => instr = ltac::create_instr(LtacType::I64Mod),
-                    DataType::Ptr if var.sub_type == DataType::UInt64 => instr = ltac::create_instr(LtacType::U64Mod),
-                    
-                    _ => {
-                        builder.syntax.ltac_error2("Modulo is only valid with integer values.".to_string());
-                        return false;
-                    },
-                }
+            instr.arg2 = LtacArg::Reg64(reg_no);
+            builder.file.code.push(instr.clone());
+        },
+        
+        AstArgType::AddrOf => {
+            let name_arg = arg.sub_args.first().unwrap();
+            let ref_var = match builder.get_var(&name_arg.str_val) {
+                Ok(v) => v,
                 
-                instr.arg1 = reg_for_type(&var.data_type, &var.sub_type, reg_no);
-            },
+                Err(_e) => {
+                    builder.syntax.ltac_error2("Unknown variable reference.".to_string()).code(E_UNKNOWN_NAME);
+                    return false;
+                },
+            };
             
-            // Logical AND
+            let mut instr2 = ltac::create_instr(LtacType::LdAddr);
+            instr2.arg1 = LtacArg::Reg64(reg_no);
+            instr2.arg2 = ref_var.mem();
+            builder.file.code.push(instr2);
             
-            AstArgType::OpAnd => {
-                match var.data_type {
-                    DataType::Char | DataType::Str
-                    | DataType::Ptr if var.sub_type == DataType::Char => {
-                        builder.syntax.ltac_error2("Invalid use of logical and.".to_string());
-                        return false;
-                    },
-                    
-                    _ => {},
-                }
+            instr.arg2 = LtacArg::Reg64(reg_no);
+            builder.file.code.push(instr.clone());
+        },
+        
+        AstArgType::Null => {
+            instr.arg2 = LtacArg::I64(0);
+            builder.file.code.push(instr.clone());
+        },
+        
+        _ => {
+            builder.syntax.ltac_error2("Invalid expression.".to_string());
+            return false;
+        },
+    }
+    
+    true
+}
+
+// Creates the instruction for a binary operator, which works on the given register
+fn build_op(builder : &mut LtacBuilder, op : &AstArgType, var : &Var, reg_no : i32) -> Option<LtacInstr> {
+    let mut instr : LtacInstr;
+    
+    match op {
+        // Addition
+        
+        AstArgType::OpAdd => {
+            match var.data_type {
+                DataType::Byte => instr = ltac::create_instr(LtacType::I8Add),
+                DataType::UByte => instr = ltac::create_instr(LtacType::U8Add),
+                DataType::Short => instr = ltac::create_instr(LtacType::I16Add),
+                DataType::UShort => instr = ltac::create_instr(LtacType::U16Add),
+                DataType::Int => instr = ltac::create_instr(LtacType::I32Add),
+                DataType::UInt => instr = ltac::create_instr(LtacType::U32Add),
+                DataType::Int64 => instr = ltac::create_instr(LtacType::I64Add),
+                DataType::UInt64 => instr = ltac::create_instr(LtacType::U64Add),
+                DataType::Float => instr = ltac::create_instr(LtacType::F32Add),
+                DataType::Double => instr = ltac::create_instr(LtacType::F64Add),
                 
-                instr = ltac::create_instr(LtacType::And);
-                instr.arg1 = reg_for_type(&var.data_type, &var.sub_type, reg_no);
-            },
+                DataType::Ptr if var.sub_type == DataType::Byte => instr = ltac::create_instr(LtacType::I8Add),
+                DataType::Ptr if var.sub_type == DataType::UByte => instr = ltac::create_instr(LtacType::U8Add),
+                DataType::Ptr if var.sub_type == DataType::Short => instr = ltac::create_instr(LtacType::I16Add),
+                DataType::Ptr if var.sub_type == DataType::UShort => instr = ltac::create_instr(LtacType::U16Add),
+                DataType::Ptr if var.sub_type == DataType::Int => instr = ltac::create_instr(LtacType::I32Add),
+                DataType::Ptr if var.sub_type == DataType::UInt => instr = ltac::create_instr(LtacType::U32Add),
+                DataType::Ptr if var.sub_type == DataType::Int64 => instr = ltac::create_instr(LtacType::I64Add),
+                DataType::Ptr if var.sub_type == DataType::UInt64 => instr = ltac::create_instr(LtacType::U64Add),
+                DataType::Ptr if var.sub_type == DataType::Float => instr = ltac::create_instr(LtacType::F32Add),
+                DataType::Ptr if var.sub_type == DataType::Double => instr = ltac::create_instr(LtacType::F64Add),
+                
+                DataType::Pointer(_) => instr = ltac::create_instr(LtacType::I64Add),
+                
+                _ => {
+                    builder.syntax.ltac_error2("Invalid use of addition operator.".to_string()).code(E_INVALID_OP);
+                    return None;
+                },
+            }
             
-            // Logical OR
+            instr.arg1 = reg_for_type(&var.data_type, &var.sub_type, reg_no);
+        },
+        
+        // Subtraction
+        
+        AstArgType::OpSub => {
+            match var.data_type {
+                DataType::Byte => instr = ltac::create_instr(LtacType::I8Sub),
+                DataType::Short => instr = ltac::create_instr(LtacType::I16Sub),
+                DataType::Int => instr = ltac::create_instr(LtacType::I32Sub),
+                DataType::Int64 => instr = ltac::create_instr(LtacType::I64Sub),
+                DataType::Float => instr = ltac::create_instr(LtacType::F32Sub),
+                DataType::Double => instr = ltac::create_instr(LtacType::F64Sub),
+                
+                DataType::Ptr if var.sub_type == DataType::Byte => instr = ltac::create_instr(LtacType::I8Sub),
+                DataType::Ptr if var.sub_type == DataType::Short => instr = ltac::create_instr(LtacType::I16Sub),
+                DataType::Ptr if var.sub_type == DataType::Int => instr = ltac::create_instr(LtacType::I32Sub),
+                DataType::Ptr if var.sub_type == DataType::Int64 => instr = ltac::create_instr(LtacType::I64Sub),
+                DataType::Ptr if var.sub_type == DataType::Float => instr = ltac::create_instr(LtacType::F32Sub),
+                DataType::Ptr if var.sub_type == DataType::Double => instr = ltac::create_instr(LtacType::F64Sub),
+                
+                DataType::Pointer(_) => instr = ltac::create_instr(LtacType::I64Sub),
+                
+                _ => {
+                    builder.syntax.ltac_error2("Invalid use of subtraction operator.".to_string()).code(E_INVALID_OP);
+                    return None;
+                },
+            }
             
-            AstArgType::OpOr => {
-                match var.data_type {
-                    DataType::Char | DataType::Str
-                    | DataType::Ptr if var.sub_type == DataType::Char => {
-                        builder.syntax.ltac_error2("Invalid use of logical or.".to_string());
-                        return false;
-                    },
-                    
-                    _ => {},
-                }
+            instr.arg1 = reg_for_type(&var.data_type, &var.sub_type, reg_no);
+        },
+        
+        // Multiplication
+        
+        AstArgType::OpMul => {
+            match var.data_type {
+                DataType::Byte => instr = ltac::create_instr(LtacType::I8Mul),
+                DataType::UByte => instr = ltac::create_instr(LtacType::U8Mul),
+                DataType::Short => instr = ltac::create_instr(LtacType::I16Mul),
+                DataType::UShort => instr = ltac::create_instr(LtacType::U16Mul),
+                DataType::Int => instr = ltac::create_instr(LtacType::I32Mul),
+                DataType::UInt => instr = ltac::create_instr(LtacType::U32Mul),
+                DataType::Int64 => instr = ltac::create_instr(LtacType::I64Mul),
+                DataType::UInt64 => instr = ltac::create_instr(LtacType::U64Mul),
+                DataType::Float => instr = ltac::create_instr(LtacType::F32Mul),
+                DataType::Double => instr = ltac::create_instr(LtacType::F64Mul),
                 
-                instr = ltac::create_instr(LtacType::Or);
-                instr.arg1 = reg_for_type(&var.data_type, &var.sub_type, reg_no);
-            },
+                DataType::Ptr if var.sub_type == DataType::Byte => instr = ltac::create_instr(LtacType::I8Mul),
+                DataType::Ptr if var.sub_type == DataType::UByte => instr = ltac::create_instr(LtacType::U8Mul),
+                DataType::Ptr if var.sub_type == DataType::Short => instr = ltac::create_instr(LtacType::I16Mul),
+                DataType::Ptr if var.sub_type == DataType::UShort => instr = ltac::create_instr(LtacType::U16Mul),
+                DataType::Ptr if var.sub_type == DataType::Int => instr = ltac::create_instr(LtacType::I32Mul),
+                DataType::Ptr if var.sub_type == DataType::UInt => instr = ltac::create_instr(LtacType::U32Mul),
+                DataType::Ptr if var.sub_type == DataType::Int64 => instr = ltac::create_instr(LtacType::I64Mul),
+                DataType::Ptr if var.sub_type == DataType::UInt64 => instr = ltac::create_instr(LtacType::U64Mul),
+                DataType::Ptr if var.sub_type == DataType::Float => instr = ltac::create_instr(LtacType::F32Mul),
+                DataType::Ptr if var.sub_type == DataType::Double => instr = ltac::create_instr(LtacType::F64Mul),
+                
+                _ => {
+                    builder.syntax.ltac_error2("Invalid use of multiplication operator.".to_string()).code(E_INVALID_OP);
+                    return None;
+                },
+            }
             
-            // Logical XOR
+            instr.arg1 = reg_for_type(&var.data_type, &var.sub_type, reg_no);
+        },
+        
+        // Division
+        
+        AstArgType::OpDiv => {
+            match var.data_type {
+                DataType::Byte => instr = ltac::create_instr(LtacType::I8Div),
+                DataType::UByte => instr = ltac::create_instr(LtacType::U8Div),
+                DataType::Short => instr = ltac::create_instr(LtacType::I16Div),
+                DataType::UShort => instr = ltac::create_instr(LtacType::U16Div),
+                DataType::Int => instr = ltac::create_instr(LtacType::I32Div),
+                DataType::UInt => instr = ltac::create_instr(LtacType::U32Div),
+                DataType::Int64 => instr = ltac::create_instr(LtacType::I64Div),
+                DataType::UInt64 => instr = ltac::create_instr(LtacType::U64Div),
+                DataType::Float => instr = ltac::create_instr(LtacType::F32Div),
+                DataType::Double => instr = ltac::create_instr(LtacType::F64Div),
+                
+                DataType::Ptr if var.sub_type == DataType::Byte => instr = ltac::create_instr(LtacType::I8Div),
+                DataType::Ptr if var.sub_type == DataType::UByte => instr = ltac::create_instr(LtacType::U8Div),
+                DataType::Ptr if var.sub_type == DataType::Short => instr = ltac::create_instr(LtacType::I16Div),
+                DataType::Ptr if var.sub_type == DataType::UShort => instr = ltac::create_instr(LtacType::U16Div),
+                DataType::Ptr if var.sub_type == DataType::Int => instr = ltac::create_instr(LtacType::I32Div),
+                DataType::Ptr if var.sub_type == DataType::UInt => instr = ltac::create_instr(LtacType::U32Div),
+                DataType::Ptr if var.sub_type == DataType::Int64 => instr = ltac::create_instr(LtacType::I64Div),
+                DataType::Ptr if var.sub_type == DataType::UInt64 => instr = ltac::create_instr(LtacType::U64Div),
+                DataType::Ptr if var.sub_type == DataType::Float => instr = ltac::create_instr(LtacType::F32Div),
+                DataType::Ptr if var.sub_type == DataType::Double => instr = ltac::create_instr(LtacType::F64Div),
+                
+                _ => {
+                    builder.syntax.ltac_error2("Invalid use of division operator.".to_string()).code(E_INVALID_OP);
+                    return None;
+                },
+            }
             
-            AstArgType::OpXor => {
-                match var.data_type {
-                    DataType::Char | DataType::Str
-                    | DataType::Ptr if var.sub_type == DataType::Char => {
-                        builder.syntax.ltac_error2("Invalid use of logical xor.".to_string());
-                        return false;
-                    },
-                    
-                    _ => {},
-                }
+            instr.arg1 = reg_for_type(&var.data_type, &var.sub_type, reg_no);
+        },
+        
+        // Modulo
+        
+        AstArgType::OpMod => {
+            match var.data_type {
+                DataType::Byte => instr = ltac::create_instr(LtacType::I8Mod),
+                DataType::UByte => instr = ltac::create_instr(LtacType::U8Mod),
+                DataType::Short => instr = ltac::create_instr(LtacType::I16Mod),
+                DataType::UShort => instr = ltac::create_instr(LtacType::U16Mod),
+                DataType::Int => instr = ltac::create_instr(LtacType::I32Mod),
+                DataType::UInt => instr = ltac::create_instr(LtacType::U32Mod),
+                DataType::Int64 => instr = ltac::create_instr(LtacType::I64Mod),
+                DataType::UInt64 => instr = ltac::create_instr(LtacType::U64Mod),
                 
-                instr = ltac::create_instr(LtacType::Xor);
-                instr.arg1 = reg_for_type(&var.data_type, &var.sub_type, reg_no);
-            },
+                DataType::Ptr if var.sub_type == DataType::Byte => instr = ltac::create_instr(LtacType::I8Mod),
+                DataType::Ptr if var.sub_type == DataType::UByte => instr = ltac::create_instr(LtacType::U8Mod),
+                DataType::Ptr if var.sub_type == DataType::Short => instr = ltac::create_instr(LtacType::I16Mod),
+                DataType::Ptr if var.sub_type == DataType::UShort => instr = ltac::create_instr(LtacType::U16Mod),
+                DataType::Ptr if var.sub_type == DataType::Int => instr = ltac::create_instr(LtacType::I32Mod),
+                DataType::Ptr if var.sub_type == DataType::UInt => instr = ltac::create_instr(LtacType::U32Mod),
+                DataType::Ptr if var.sub_type == DataType::Int64 => instr = ltac::create_instr(LtacType::I64Mod),
+                DataType::Ptr if var.sub_type == DataType::UInt64 => instr = ltac::create_instr(LtacType::U64Mod),
+                
+                _ => {
+                    builder.syntax.ltac_error2("Modulo is only valid with integer values.".to_string()).code(E_INVALID_OP);
+                    return None;
+                },
+            }
             
-            // Left shift
+            instr.arg1 = reg_for_type(&var.data_type, &var.sub_type, reg_no);
+        },
+        
+        // Logical AND
+        
+        AstArgType::OpAnd => {
+            match var.data_type {
+                DataType::Char | DataType::Str
+                | DataType::Ptr if var.sub_type == DataType::Char => {
+                    builder.syntax.ltac_error2("Invalid use of logical and.".to_string()).code(E_INVALID_OP);
+                    return None;
+                },
+                
+                _ => {},
+            }
             
-            AstArgType::OpLeftShift => {
-                match var.data_type {
-                    DataType::Char | DataType::Str
-                    | DataType::Ptr if var.sub_type == DataType::Char => {
-                        builder.syntax.ltac_error2("Invalid use of left shift.".to_string());
-                        return false;
-                    },
-                    
-                    _ => {},
-                }
+            instr = ltac::create_instr(LtacType::And);
+            instr.arg1 = reg_for_type(&var.data_type, &var.sub_type, reg_no);
+        },
+        
+        // Logical OR
+        
+        AstArgType::OpOr => {
+            match var.data_type {
+                DataType::Char | DataType::Str
+                | DataType::Ptr if var.sub_type == DataType::Char => {
+                    builder.syntax.ltac_error2("Invalid use of logical or.".to_string()).code(E_INVALID_OP);
+                    return None;
+                },
                 
-                instr = ltac::create_instr(LtacType::Lsh);
-                instr.arg1 = reg_for_type(&var.data_type, &var.sub_type, reg_no);
-            },
+                _ => {},
+            }
             
-            // Right shift
+            instr = ltac::create_instr(LtacType::Or);
+            instr.arg1 = reg_for_type(&var.data_type, &var.sub_type, reg_no);
+        },
+        
+        // Logical XOR
+        
+        AstArgType::OpXor => {
+            match var.data_type {
+                DataType::Char | DataType::Str
+                | DataType::Ptr if var.sub_type == DataType::Char => {
+                    builder.syntax.ltac_error2("Invalid use of logical xor.".to_string()).code(E_INVALID_OP);
+                    return None;
+                },
+                
+                _ => {},
+            }
             
-            AstArgType::OpRightShift => {
-                match var.data_type {
-                    DataType::Char | DataType::Str
-                    | DataType::Ptr if var.sub_type == DataType::Char => {
-                        builder.syntax.ltac_error2("Invalid use of right shift.".to_string());
-                        return false;
-                    },
-                    
-                    _ => {},
-                }
+            instr = ltac::create_instr(LtacType::Xor);
+            instr.arg1 = reg_for_type(&var.data_type, &var.sub_type, reg_no);
+        },
+        
+        // Left shift
+        
+        AstArgType::OpLeftShift => {
+            match var.data_type {
+                DataType::Char | DataType::Str
+                | DataType::Ptr if var.sub_type == DataType::Char => {
+                    builder.syntax.ltac_error2("Invalid use of left shift.".to_string()).code(E_INVALID_OP);
+                    return None;
+                },
                 
-                instr = ltac::create_instr(LtacType::Rsh);
-                instr.arg1 = reg_for_type(&var.data_type, &var.sub_type, reg_no);
-            },
+                _ => {},
+            }
             
-            _ => {},
-        }
+            instr = ltac::create_instr(LtacType::Lsh);
+            instr.arg1 = reg_for_type(&var.data_type, &var.sub_type, reg_no);
+        },
+        
+        // Right shift
+        
+        AstArgType::OpRightShift => {
+            match var.data_type {
+                DataType::Char | DataType::Str
+                | DataType::Ptr if var.sub_type == DataType::Char => {
+                    builder.syntax.ltac_error2("Invalid use of right shift.".to_string()).code(E_INVALID_OP);
+                    return None;
+                },
+                
+                _ => {},
+            }
+            
+            instr = ltac::create_instr(LtacType::Rsh);
+            instr.arg1 = reg_for_type(&var.data_type, &var.sub_type, reg_no);
+        },
+        
+        _ => {
+            builder.syntax.ltac_error2("Invalid expression.".to_string());
+            return None;
+        },
+    }
+    
+    Some(instr)
+}
+
+// Loads a pointer offset from a variable, and scales it by the size of the element
+// The type checker only allows 64-bit offsets, so there is no sign extension to worry about
+fn build_ptr_offset(builder : &mut LtacBuilder, v : &Var, var : &Var, reg_no : i32, instr : &mut LtacInstr) -> bool {
+    let reg = LtacArg::Reg64(reg_no + 1);
+    
+    let mut ld = ltac::create_instr(LtacType::MovQ);
+    ld.arg1 = reg.clone();
+    ld.arg2 = v.mem();
+    builder.file.code.push(ld);
+    
+    let size = size_for_type(&var.sub_type);
+    
+    if size > 1 {
+        let mut mul = ltac::create_instr(LtacType::I64Mul);
+        mul.arg1 = reg.clone();
+        mul.arg2 = LtacArg::I64(size as i64);
+        builder.file.code.push(mul);
     }
     
+    instr.arg2 = reg;
+    builder.file.code.push(instr.clone());
+    
     true
 }
 
 // Builds a variable reference within an expression
-pub fn build_expr_var(builder : &mut LtacBuilder, arg : &AstArg, var : &Var, reg_no : i32, negate_next : bool, instr : &mut LtacInstr) -> bool {
+// An element of an array comes with its index
+pub fn build_expr_var(builder : &mut LtacBuilder, arg : &AstArg, index : Option<&AstExpr>, var : &Var, reg_no : i32, negate_next : bool, instr : &mut LtacInstr) -> bool {
     let v = match builder.get_var(&arg.str_val) {
+        Ok(v) if index.is_some() => v.elements(),
         Ok(v) => v.clone(),
         Err(_e) => return false,    // This really shouldn't happen
     };
     
+    // An integer added to a pointer is a number of elements
+    if matches!(var.data_type, DataType::Pointer(_)) && matches!(v.data_type, DataType::Int64 | DataType::UInt64) {
+        return build_ptr_offset(builder, &v, var, reg_no, instr);
+    }
+    
     let zero = builder.build_float(0.0, false, false);      // I don't love having this here, but it won't work in the match
     let mut pop_float = true;
     
-    instr.arg2 = LtacArg::Mem(v.pos);
+    instr.arg2 = v.mem();
     
     let mut size = 1;
     if v.sub_type == DataType::Short || v.sub_type == DataType::UShort {
@@ -771,31 +1072,44 @@ pub fn build_expr_var(builder : &mut LtacBuilder, arg : &AstArg, var : &Var, reg
         || v.sub_type == DataType::Float {
         size = 4;
     } else if  v.sub_type == DataType::Int64 || v.sub_type == DataType::UInt64
-        || v.sub_type == DataType::Double || var.sub_type == DataType::Str {
+        || v.sub_type == DataType::Double || var.sub_type == DataType::Str
+        || matches!(v.sub_type, DataType::Proc(_)) {
         size = 8;
     }
     
-    if arg.sub_args.len() > 0 {
-        let first_arg = arg.sub_args.last().unwrap();
-        
-        if arg.sub_args.len() == 1 {
+    if let Some(index) = index {
+        // The element goes in the next register, so that one is free for the bounds check
+        if let AstExpr::Operand(first_arg) = index {
             if first_arg.arg_type == AstArgType::IntL {
                 let offset = (first_arg.u64_val as i32) * size;
                 instr.arg2 = LtacArg::MemOffsetImm(v.pos, offset);
+                
+                let check = bounds_check(builder, &arg.str_val, LtacArg::I32(first_arg.u64_val as i32), reg_no + 1);
+                builder.file.code.extend(check);
             } else if first_arg.arg_type == AstArgType::Id {
                 let mut instr2 = mov_for_type(&v.data_type, &v.sub_type);
                 
-                match builder.vars.get(&first_arg.str_val) {
-                    Some(v2) => instr2.arg2 = LtacArg::MemOffsetMem(v.pos, v2.pos, size),
+                let index_pos = match builder.vars.get(&first_arg.str_val) {
+                    Some(v2) if v2.is_global() => {
+                        builder.syntax.ltac_error2("Global variables cannot be used as array indexes.".to_string()).code(E_GLOBAL_INDEX);
+                        return false;
+                    },
+                    Some(v2) => v2.pos,
                     None => {
-                        builder.syntax.ltac_error2("Invalid offset variable.".to_string());
+                        builder.syntax.ltac_error2("Invalid offset variable.".to_string()).code(E_UNKNOWN_NAME);
                         return false;
                     },
                 };
                 
+                instr2.arg2 = LtacArg::MemOffsetMem(v.pos, index_pos, size);
+                
+                let check = bounds_check(builder, &arg.str_val, LtacArg::Mem(index_pos), reg_no + 1);
+                builder.file.code.extend(check);
+                
                 // Choose the proper registers
-                instr2.arg1 = reg_for_type(&v.data_type, &v.sub_type, reg_no);
-                instr.arg2 = reg_for_type(&v.data_type, &v.sub_type, reg_no);
+                // The element goes in the next register, since this one may hold the value so far
+                instr2.arg1 = reg_for_type(&v.data_type, &v.sub_type, reg_no + 1);
+                instr.arg2 = reg_for_type(&v.data_type, &v.sub_type, reg_no + 1);
                 
                 builder.file.code.push(instr2);
             }
@@ -806,9 +1120,13 @@ pub fn build_expr_var(builder : &mut LtacBuilder, arg : &AstArg, var : &Var, reg
                 data_type : DataType::Int,
                 sub_type : DataType::None,
                 is_param : false,
+                global : String::new(),
             };
             
-            build_var_expr(builder, &arg.sub_args, &var2, 0);
+            build_var_expr(builder, index, &var2, 0);
+            
+            let check = bounds_check(builder, &arg.str_val, LtacArg::Reg32(0), 0);
+            builder.file.code.extend(check);
             
             let mut instr2 = mov_for_type(&v.data_type, &v.sub_type);
             instr2.arg1 = reg_for_type(&v.data_type, &v.sub_type, 0);
@@ -861,7 +1179,7 @@ pub fn build_expr_var(builder : &mut LtacBuilder, arg : &AstArg, var : &Var, reg
             },
             
             DataType::Float => {
-                instr2.arg2 = LtacArg::F32(zero);
+                instr2.arg2 = LtacArg::F32(zero.clone());
                 builder.file.code.push(instr2.clone());
                 
                 instr2.instr_type = LtacType::F32Sub;
@@ -869,7 +1187,7 @@ pub fn build_expr_var(builder : &mut LtacBuilder, arg : &AstArg, var : &Var, reg
             },
             
             DataType::Double => {
-                instr2.arg2 = LtacArg::F64(zero);
+                instr2.arg2 = LtacArg::F64(zero.clone());
                 builder.file.code.push(instr2.clone());
                 
                 instr2.instr_type = LtacType::F64Sub;
@@ -877,19 +1195,20 @@ pub fn build_expr_var(builder : &mut LtacBuilder, arg : &AstArg, var : &Var, reg
             },
             
             _ => {
-                builder.syntax.ltac_error2("Invalid use of negation operator.".to_string());
+                builder.syntax.ltac_error2("Invalid use of negation operator.".to_string()).code(E_INVALID_OP);
                 return false;
             },
         }
         
         // Set the memory and push the second operand
-        instr2.arg2 = LtacArg::Mem(v.pos);
+        instr2.arg2 = v.mem();
         builder.file.code.push(instr2);
     }
     
-    // Pop the extra float we created at the top if we don't need it
+    // Remove the extra float we created at the top if we don't need it
+    // The bounds check may have added a string after it, so this isn't always the last item
     if pop_float {
-        builder.file.data.pop();
+        builder.file.data.retain(|d| d.name != zero);
     }
     
     // Add the instruction
@@ -899,37 +1218,41 @@ pub fn build_expr_var(builder : &mut LtacBuilder, arg : &AstArg, var : &Var, reg
 
 // Builds a function call within an expression
 pub fn build_expr_func_call(builder : &mut LtacBuilder, arg : &AstArg, var : &Var, reg_no : i32, instr : &mut LtacInstr) -> bool {
-    let t = match builder.get_function(&arg.str_val) {
-        Ok(t) => t.clone(),
-        Err(_e) => return false,
+    let t = match builder.get_proc(&arg.str_val) {
+        Some(p) => p.data_type.clone(),
+        
+        None => match builder.get_function(&arg.str_val) {
+            Ok(t) => t.clone(),
+            Err(_e) => return false,
+        },
     };
-
+    
     // First, push the current register
     let mut store = mov_for_type(&t, &DataType::None);        // TODO: Replace this
-    store.arg1 = LtacArg::Mem(var.pos);
+    store.arg1 = var.mem();
     store.arg2 = reg_for_type(&t, &DataType::None, reg_no);    // TODO: Replace this
     builder.file.code.push(store.clone());
-
+    
     // Create a statement to build the rest of the function call
     let mut stmt = ast::create_orphan_stmt(AstStmtType::FuncCall);
     stmt.name = arg.str_val.clone();
     stmt.args = arg.sub_args.clone();
     build_func_call(builder, &stmt);
-           
+    
     //Restore the current register
     store.arg1 = reg_for_type(&t, &DataType::None, reg_no);        // TODO: Replace this
-    store.arg2 = LtacArg::Mem(var.pos);
+    store.arg2 = var.mem();
     builder.file.code.push(store);
-
+    
     match t {
         DataType::Byte => instr.arg2 = LtacArg::RetRegI8,
-        DataType::UByte => instr.arg2 = LtacArg::RetRegU8,
+        DataType::UByte | DataType::Bool => instr.arg2 = LtacArg::RetRegU8,
         DataType::Short => instr.arg2 = LtacArg::RetRegI16,
         DataType::UShort => instr.arg2 = LtacArg::RetRegU16,
         DataType::Int => instr.arg2 = LtacArg::RetRegI32,
         DataType::UInt => instr.arg2 = LtacArg::RetRegU32,
         DataType::Int64 => instr.arg2 = LtacArg::RetRegI64,
-        DataType::UInt64 => instr.arg2 = LtacArg::RetRegU64,
+        DataType::UInt64 | DataType::Pointer(_) => instr.arg2 = LtacArg::RetRegU64,
         DataType::Float => instr.arg2 = LtacArg::RetRegF32,
         DataType::Double => instr.arg2 = LtacArg::RetRegF64,
         
@@ -938,7 +1261,7 @@ pub fn build_expr_func_call(builder : &mut LtacBuilder, arg : &AstArg, var : &Va
             return false;
         },
     }
-
+    
     // Add the line
     builder.file.code.push(instr.clone());
     true
@@ -16,9 +16,15 @@
 // 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
 
 use crate::ltac_builder::*;
+use crate::ltac_expr::build_expr_block;
+use crate::ltac_array::bounds_check;
+use crate::ltac_record::*;
 use crate::ltac_utils::*;
 
-use crate::ast::{DataType, AstStmt, AstStmtType, AstArgType};
+use crate::ast;
+use crate::ast::{DataType, AstStmt, AstStmtType, AstArg, AstArgType, AstCond};
+use crate::ast::{case_arms, arm_body, choice_ranges, is_dense, cond_tree};
+use crate::syntax::*;
 use crate::ltac;
 use crate::ltac::{LtacType, LtacInstr, LtacArg};
 
@@ -50,18 +56,19 @@ pub fn build_continue(builder : &mut LtacBuilder) {
     };
 }
 
-// Builds a conditional statement
-fn build_cmp(builder : &mut LtacBuilder, line : &AstStmt) -> Vec<LtacInstr> {
+// Builds a single comparison
+// The line is only used for errors; the arguments are the comparison itself
+fn build_cmp(builder : &mut LtacBuilder, line : &AstStmt, args : &[AstArg]) -> Vec<LtacInstr> {
     let mut block : Vec<LtacInstr> = Vec::new();
     let mut cmp = ltac::create_instr(LtacType::U32Cmp);
     
     // Build the conditional statement
-    let arg1 = match line.args.iter().nth(0) {
+    let arg1 = match args.iter().nth(0) {
         Some(a) => a,
         None => return block,
     };
     
-    let mut arg2 = match line.args.iter().nth(2) {
+    let mut arg2 = match args.iter().nth(2) {
         Some(a) => a,
         None => return block,
     };
@@ -72,7 +79,7 @@ fn build_cmp(builder : &mut LtacBuilder, line : &AstStmt) -> Vec<LtacInstr> {
     
     if arg2.arg_type == AstArgType::OpNeg {
         negate = true;
-        arg2 = match line.args.iter().nth(3) {
+        arg2 = match args.iter().nth(3) {
             Some(a) => a,
             None => return block,
         };
@@ -81,7 +88,7 @@ fn build_cmp(builder : &mut LtacBuilder, line : &AstStmt) -> Vec<LtacInstr> {
     // Although we assume its integer comparison by default, the first operand
     // determines the comparison type
     match &arg1.arg_type {
-        AstArgType::ByteL => {
+        AstArgType::ByteL | AstArgType::BoolL => {
             let mut mov = ltac::create_instr(LtacType::MovUB);
             mov.arg1 = LtacArg::Reg8(0);
             mov.arg2 = LtacArg::UByte(arg1.u8_val);
@@ -103,7 +110,7 @@ fn build_cmp(builder : &mut LtacBuilder, line : &AstStmt) -> Vec<LtacInstr> {
         
         AstArgType::IntL => {
             if negate {
-                let val : i32 = 0 - (arg1.u64_val as i32);
+                let val : i32 = (arg1.u64_val as i32).wrapping_neg();
                 
                 let mut mov = ltac::create_instr(LtacType::Mov);
                 mov.arg1 = LtacArg::Reg32(0);
@@ -139,30 +146,62 @@ fn build_cmp(builder : &mut LtacBuilder, line : &AstStmt) -> Vec<LtacInstr> {
             let mut instr2 = ltac::create_instr(LtacType::PushArg);
             instr2.arg1 = LtacArg::PtrLcl(name);
             instr2.arg2_val = 1;
-            builder.file.code.push(instr2);
+            block.push(instr2);
             
             cmp = ltac::create_instr(LtacType::StrCmp);
         },
         
+        // Array elements and dereferenced pointers
+        AstArgType::Id if arg1.sub_args.len() > 0 => {
+            let (cmp_type, reg, signed) = match build_cmp_element(builder, line, arg1, 0, &mut block) {
+                Some(e) => e,
+                None => return block,
+            };
+            
+            cmp = ltac::create_instr(cmp_type);
+            cmp.arg1 = reg;
+            signed_variant = signed;
+        },
+        
+        // Null is compared as an address
+        AstArgType::Null => {
+            let mut mov = ltac::create_instr(LtacType::MovUQ);
+            mov.arg1 = LtacArg::Reg64(0);
+            mov.arg2 = LtacArg::U64(0);
+            block.push(mov);
+            
+            cmp = ltac::create_instr(LtacType::U64Cmp);
+            cmp.arg1 = LtacArg::Reg64(0);
+        },
+        
         AstArgType::Id => {
             let mut mov = ltac::create_instr(LtacType::MovU);
             mov.arg1 = LtacArg::Reg32(0);
             
             match &builder.vars.get(&arg1.str_val) {
                 Some(v) => {
+                    // Strings checked against null are compared as addresses
+                    if v.data_type == DataType::Str && arg2.arg_type == AstArgType::Null {
+                        cmp.instr_type = LtacType::U64Cmp;
+                        cmp.arg1 = LtacArg::Reg64(0);
+                        
+                        mov = ltac::create_instr(LtacType::MovUQ);
+                        mov.arg1 = LtacArg::Reg64(0);
+                        mov.arg2 = v.mem();
+                        
                     // String comparisons
-                    if v.data_type == DataType::Str {
+                    } else if v.data_type == DataType::Str {
                         cmp = ltac::create_instr(LtacType::StrCmp);
                         
                         mov = ltac::create_instr(LtacType::PushArg);
-                        mov.arg1 = LtacArg::Ptr(v.pos);
+                        mov.arg1 = v.ptr();
                         mov.arg2_val = 1;
                         
                     // Float-32 comparisons
                     } else if v.data_type == DataType::Float {
                         mov = ltac::create_instr(LtacType::MovF32);
                         mov.arg1 = LtacArg::FltReg(0);
-                        mov.arg2 = LtacArg::Mem(v.pos);
+                        mov.arg2 = v.mem();
                         
                         cmp = ltac::create_instr(LtacType::F32Cmp);
                         cmp.arg1 = LtacArg::FltReg(0);
@@ -171,7 +210,7 @@ fn build_cmp(builder : &mut LtacBuilder, line : &AstStmt) -> Vec<LtacInstr> {
                     } else if v.data_type == DataType::Double {
                         mov = ltac::create_instr(LtacType::MovF64);
                         mov.arg1 = LtacArg::FltReg64(0);
-                        mov.arg2 = LtacArg::Mem(v.pos);
+                        mov.arg2 = v.mem();
                         
                         cmp = ltac::create_instr(LtacType::F64Cmp);
                         cmp.arg1 = LtacArg::FltReg64(0);
@@ -183,7 +222,7 @@ fn build_cmp(builder : &mut LtacBuilder, line : &AstStmt) -> Vec<LtacInstr> {
                         
                         mov = ltac::create_instr(LtacType::MovB);
                         mov.arg1 = LtacArg::Reg8(0);
-                        mov.arg2 = LtacArg::Mem(v.pos);
+                        mov.arg2 = v.mem();
                         
                         signed_variant = true;
                         
@@ -194,16 +233,16 @@ fn build_cmp(builder : &mut LtacBuilder, line : &AstStmt) -> Vec<LtacInstr> {
                         
                         mov = ltac::create_instr(LtacType::MovB);
                         mov.arg1 = LtacArg::Reg8(0);
-                        mov.arg2 = LtacArg::Mem(v.pos);
+                        mov.arg2 = v.mem();
                         
-                    // Unsigned byte comparisons
-                    } else if v.data_type == DataType::UByte {
+                    // Unsigned byte comparisons, which bools also use
+                    } else if v.data_type == DataType::UByte || v.data_type == DataType::Bool {
                         cmp.instr_type = LtacType::U8Cmp;
                         cmp.arg1 = LtacArg::Reg8(0);
                         
                         mov = ltac::create_instr(LtacType::MovUB);
                         mov.arg1 = LtacArg::Reg8(0);
-                        mov.arg2 = LtacArg::Mem(v.pos);
+                        mov.arg2 = v.mem();
                         
                     // Short comparisons
                     } else if v.data_type == DataType::Short {
@@ -212,7 +251,7 @@ fn build_cmp(builder : &mut LtacBuilder, line : &AstStmt) -> Vec<LtacInstr> {
                         
                         mov = ltac::create_instr(LtacType::MovW);
                         mov.arg1 = LtacArg::Reg16(0);
-                        mov.arg2 = LtacArg::Mem(v.pos);
+                        mov.arg2 = v.mem();
                         
                         signed_variant = true;
                     
@@ -223,7 +262,7 @@ fn build_cmp(builder : &mut LtacBuilder, line : &AstStmt) -> Vec<LtacInstr> {
                         
                         mov = ltac::create_instr(LtacType::MovUW);
                         mov.arg1 = LtacArg::Reg16(0);
-                        mov.arg2 = LtacArg::Mem(v.pos);
+                        mov.arg2 = v.mem();
                         
                     // Signed int64 comparisons
                     } else if v.data_type == DataType::Int64 {
@@ -232,16 +271,18 @@ fn build_cmp(builder : &mut LtacBuilder, line : &AstStmt) -> Vec<LtacInstr> {
                         
                         mov = ltac::create_instr(LtacType::MovQ);
                         mov.arg1 = LtacArg::Reg64(0);
-                        mov.arg2 = LtacArg::Mem(v.pos);
+                        mov.arg2 = v.mem();
+                        
+                        signed_variant = true;
                     
-                    // Unsigned int64 comparisons
-                    } else if v.data_type == DataType::UInt64 {
+                    // Unsigned int64 comparisons, which pointers also use
+                    } else if v.data_type == DataType::UInt64 || matches!(v.data_type, DataType::Pointer(_)) {
                         cmp.instr_type = LtacType::U64Cmp;
                         cmp.arg1 = LtacArg::Reg64(0);
                         
                         mov = ltac::create_instr(LtacType::MovUQ);
                         mov.arg1 = LtacArg::Reg64(0);
-                        mov.arg2 = LtacArg::Mem(v.pos);
+                        mov.arg2 = v.mem();
                         
                     // Integer comparisons
                     } else {
@@ -251,7 +292,7 @@ fn build_cmp(builder : &mut LtacBuilder, line : &AstStmt) -> Vec<LtacInstr> {
                             signed_variant = true;
                         }
                         
-                        mov.arg2 = LtacArg::Mem(v.pos);
+                        mov.arg2 = v.mem();
                         
                         cmp.arg1 = LtacArg::Reg32(0);
                     }
@@ -263,6 +304,72 @@ fn build_cmp(builder : &mut LtacBuilder, line : &AstStmt) -> Vec<LtacInstr> {
             }
         },
         
+        // Record fields (integers and characters only)
+        AstArgType::Field => {
+            let (var, offset, data_type) = match get_field(builder, &arg1) {
+                Ok(f) => f,
+                Err(_e) => return block,
+            };
+            
+            match data_type {
+                DataType::Byte => {
+                    cmp.instr_type = LtacType::I8Cmp;
+                    cmp.arg1 = LtacArg::Reg8(0);
+                    signed_variant = true;
+                },
+                
+                DataType::Char => {
+                    cmp.instr_type = LtacType::I8Cmp;
+                    cmp.arg1 = LtacArg::Reg8(0);
+                },
+                
+                DataType::UByte => {
+                    cmp.instr_type = LtacType::U8Cmp;
+                    cmp.arg1 = LtacArg::Reg8(0);
+                },
+                
+                DataType::Short => {
+                    cmp.instr_type = LtacType::I16Cmp;
+                    cmp.arg1 = LtacArg::Reg16(0);
+                    signed_variant = true;
+                },
+                
+                DataType::UShort => {
+                    cmp.instr_type = LtacType::U16Cmp;
+                    cmp.arg1 = LtacArg::Reg16(0);
+                },
+                
+                DataType::Int => {
+                    cmp.instr_type = LtacType::I32Cmp;
+                    cmp.arg1 = LtacArg::Reg32(0);
+                    signed_variant = true;
+                },
+                
+                DataType::UInt => cmp.arg1 = LtacArg::Reg32(0),
+                
+                DataType::Int64 => {
+                    cmp.instr_type = LtacType::I64Cmp;
+                    cmp.arg1 = LtacArg::Reg64(0);
+                    signed_variant = true;
+                },
+                
+                DataType::UInt64 => {
+                    cmp.instr_type = LtacType::U64Cmp;
+                    cmp.arg1 = LtacArg::Reg64(0);
+                },
+                
+                _ => {
+                    builder.syntax.ltac_error(line, "Only integer and character fields can be compared.".to_string()).code(E_TYPE_MISMATCH);
+                    return block;
+                },
+            }
+            
+            let mut mov = mov_for_type(&data_type, &DataType::None);
+            mov.arg1 = cmp.arg1.clone();
+            mov.arg2 = LtacArg::MemOffsetImm(var.pos, offset);
+            block.push(mov);
+        },
+        
         _ => {},
     }
     
@@ -271,7 +378,7 @@ fn build_cmp(builder : &mut LtacBuilder, line : &AstStmt) -> Vec<LtacInstr> {
             cmp.arg2 = LtacArg::Byte(arg2.char_val as i8);
         },
         
-        AstArgType::ByteL => {
+        AstArgType::ByteL | AstArgType::BoolL => {
             if signed_variant {
                 cmp.arg2 = LtacArg::Byte(arg2.u8_val as i8);
             } else {
@@ -291,7 +398,7 @@ fn build_cmp(builder : &mut LtacBuilder, line : &AstStmt) -> Vec<LtacInstr> {
             if signed_variant {
                 let mut val = arg2.u64_val as i64;
                 if negate {
-                    val = 0 - val;
+                    val = val.wrapping_neg();
                 }
                 
                 if cmp.instr_type == LtacType::I64Cmp {
@@ -326,26 +433,67 @@ fn build_cmp(builder : &mut LtacBuilder, line : &AstStmt) -> Vec<LtacInstr> {
             let mut instr2 = ltac::create_instr(LtacType::PushArg);
             instr2.arg1 = LtacArg::PtrLcl(name);
             instr2.arg2_val = 2;
-            builder.file.code.push(instr2); 
+            block.push(instr2);
+        },
+        
+        AstArgType::Field => {
+            let (var, offset, data_type) = match get_field(builder, &arg2) {
+                Ok(f) => f,
+                Err(_e) => return block,
+            };
+            
+            match data_type {
+                DataType::Byte | DataType::UByte | DataType::Char => cmp.arg2 = LtacArg::Reg8(1),
+                DataType::Short | DataType::UShort => cmp.arg2 = LtacArg::Reg16(1),
+                DataType::Int | DataType::UInt => cmp.arg2 = LtacArg::Reg32(1),
+                DataType::Int64 | DataType::UInt64 => cmp.arg2 = LtacArg::Reg64(1),
+                
+                _ => {
+                    builder.syntax.ltac_error(line, "Only integer and character fields can be compared.".to_string()).code(E_TYPE_MISMATCH);
+                    return block;
+                },
+            }
+            
+            let mut mov = mov_for_type(&data_type, &DataType::None);
+            mov.arg1 = cmp.arg2.clone();
+            mov.arg2 = LtacArg::MemOffsetImm(var.pos, offset);
+            block.push(mov);
+        },
+        
+        AstArgType::Id if arg2.sub_args.len() > 0 => {
+            match build_cmp_element(builder, line, arg2, 1, &mut block) {
+                Some((_, reg, _)) => cmp.arg2 = reg,
+                None => return block,
+            }
         },
         
+        AstArgType::Null => cmp.arg2 = LtacArg::U64(0),
+        
         AstArgType::Id => {
             let mut mov = ltac::create_instr(LtacType::Mov);
             mov.arg1 = LtacArg::Reg32(1);
             
             match &builder.vars.get(&arg2.str_val) {
                 Some(v) => {
+                    // Strings checked against null
+                    if v.data_type == DataType::Str && arg1.arg_type == AstArgType::Null {
+                        mov = ltac::create_instr(LtacType::MovUQ);
+                        mov.arg1 = LtacArg::Reg64(1);
+                        mov.arg2 = v.mem();
+                        
+                        cmp.arg2 = LtacArg::Reg64(1);
+                        
                     // Strings
-                    if v.data_type == DataType::Str {
+                    } else if v.data_type == DataType::Str {
                         mov = ltac::create_instr(LtacType::PushArg);
-                        mov.arg1 = LtacArg::Ptr(v.pos);
+                        mov.arg1 = v.ptr();
                         mov.arg2_val = 2;
                         
                     // Single-precision floats
                     } else if v.data_type == DataType::Float {
                         mov = ltac::create_instr(LtacType::MovF32);
                         mov.arg1 = LtacArg::FltReg(1);
-                        mov.arg2 = LtacArg::Mem(v.pos);
+                        mov.arg2 = v.mem();
                         
                         cmp.arg2 = LtacArg::FltReg(1);
                         
@@ -353,7 +501,7 @@ fn build_cmp(builder : &mut LtacBuilder, line : &AstStmt) -> Vec<LtacInstr> {
                     } else if v.data_type == DataType::Double {
                         mov = ltac::create_instr(LtacType::MovF64);
                         mov.arg1 = LtacArg::FltReg64(1);
-                        mov.arg2 = LtacArg::Mem(v.pos);
+                        mov.arg2 = v.mem();
                         
                         match cmp.arg1 {
                             LtacArg::FltReg(pos) => {
@@ -388,13 +536,13 @@ fn build_cmp(builder : &mut LtacBuilder, line : &AstStmt) -> Vec<LtacInstr> {
                             mov.arg1 = LtacArg::Empty;
                         }
                         
-                        cmp.arg2 = LtacArg::Mem(v.pos);
+                        cmp.arg2 = v.mem();
                     
-                    // Unsigned bytes
-                    } else if v.data_type == DataType::UByte {
+                    // Unsigned bytes and bools
+                    } else if v.data_type == DataType::UByte || v.data_type == DataType::Bool {
                         mov = ltac::create_instr(LtacType::MovUB);
                         mov.arg1 = LtacArg::Reg8(1);
-                        mov.arg2 = LtacArg::Mem(v.pos);
+                        mov.arg2 = v.mem();
                         
                         cmp.arg2 = LtacArg::Reg8(1);
                         
@@ -415,7 +563,7 @@ fn build_cmp(builder : &mut LtacBuilder, line : &AstStmt) -> Vec<LtacInstr> {
                             mov.arg1 = LtacArg::Empty;
                         }
                         
-                        cmp.arg2 = LtacArg::Mem(v.pos);
+                        cmp.arg2 = v.mem();
                         
                     // Shorts
                     } else if v.data_type == DataType::Short {
@@ -431,13 +579,13 @@ fn build_cmp(builder : &mut LtacBuilder, line : &AstStmt) -> Vec<LtacInstr> {
                             mov.arg1 = LtacArg::Empty;
                         }
                         
-                        cmp.arg2 = LtacArg::Mem(v.pos);
+                        cmp.arg2 = v.mem();
                     
                     // Unsigned short
                     } else if v.data_type == DataType::UShort {
                         mov = ltac::create_instr(LtacType::MovUW);
                         mov.arg1 = LtacArg::Reg16(1);
-                        mov.arg2 = LtacArg::Mem(v.pos);
+                        mov.arg2 = v.mem();
                         
                         cmp.arg2 = LtacArg::Reg16(1);
                         
@@ -456,12 +604,12 @@ fn build_cmp(builder : &mut LtacBuilder, line : &AstStmt) -> Vec<LtacInstr> {
                         
                         mov = ltac::create_instr(LtacType::MovQ);
                         mov.arg1 = LtacArg::Reg64(1);
-                        mov.arg2 = LtacArg::Mem(v.pos);
+                        mov.arg2 = v.mem();
                         
                         cmp.arg2 = LtacArg::Reg64(1);
                     
-                    // Unsigned int-64
-                    } else if v.data_type == DataType::UInt64 {
+                    // Unsigned int-64 and pointers
+                    } else if v.data_type == DataType::UInt64 || matches!(v.data_type, DataType::Pointer(_)) {
                         if arg1.arg_type == AstArgType::IntL {
                             block.pop();
                             let mut mov2 = ltac::create_instr(LtacType::MovUQ);
@@ -475,7 +623,7 @@ fn build_cmp(builder : &mut LtacBuilder, line : &AstStmt) -> Vec<LtacInstr> {
                         
                         mov = ltac::create_instr(LtacType::MovUQ);
                         mov.arg1 = LtacArg::Reg64(1);
-                        mov.arg2 = LtacArg::Mem(v.pos);
+                        mov.arg2 = v.mem();
                         
                         cmp.arg2 = LtacArg::Reg64(1);
                         
@@ -493,10 +641,10 @@ fn build_cmp(builder : &mut LtacBuilder, line : &AstStmt) -> Vec<LtacInstr> {
                             mov.arg1 = LtacArg::Empty;
                         }
                         
-                        cmp.arg2 = LtacArg::Mem(v.pos);
+                        cmp.arg2 = v.mem();
                         
                     } else {
-                        mov.arg2 = LtacArg::Mem(v.pos);
+                        mov.arg2 = v.mem();
                         
                         cmp.arg2 = LtacArg::Reg32(1);
                     }
@@ -517,8 +665,86 @@ fn build_cmp(builder : &mut LtacBuilder, line : &AstStmt) -> Vec<LtacInstr> {
     block
 }
 
+// Loads an array element or a dereferenced pointer for a comparison
+// Returns the comparison type, the register holding the value, and whether the value is signed
+fn build_cmp_element(builder : &mut LtacBuilder, line : &AstStmt, arg : &AstArg, reg_no : i32, block : &mut Vec<LtacInstr>) -> Option<(LtacType, LtacArg, bool)> {
+    let var = match builder.vars.get(&arg.str_val) {
+        Some(v) => v.elements(),
+        None => return None,
+    };
+    
+    // Strings are indexed by character
+    let elem_type = match var.data_type {
+        DataType::Str => DataType::Char,
+        _ => var.sub_type.clone(),
+    };
+    
+    let (cmp_type, signed) = match elem_type {
+        DataType::Byte => (LtacType::I8Cmp, true),
+        DataType::UByte => (LtacType::U8Cmp, false),
+        DataType::Char => (LtacType::I8Cmp, false),
+        DataType::Short => (LtacType::I16Cmp, true),
+        DataType::UShort => (LtacType::U16Cmp, false),
+        DataType::Int => (LtacType::I32Cmp, true),
+        DataType::UInt => (LtacType::U32Cmp, false),
+        DataType::Int64 => (LtacType::I64Cmp, true),
+        DataType::UInt64 => (LtacType::U64Cmp, false),
+        DataType::Float => (LtacType::F32Cmp, false),
+        DataType::Double => (LtacType::F64Cmp, false),
+        
+        _ => {
+            builder.syntax.ltac_error(line, "Only numbers and characters can be compared from an array.".to_string()).code(E_TYPE_MISMATCH);
+            return None;
+        },
+    };
+    
+    // Only constant and variable indexes are supported here
+    let size = size_for_type(&elem_type);
+    let index = arg.sub_args.first().unwrap();
+    let mem : LtacArg;
+    let index_arg : LtacArg;
+    
+    if arg.sub_args.len() == 1 && index.arg_type == AstArgType::IntL {
+        mem = LtacArg::MemOffsetImm(var.pos, (index.u64_val as i32) * size);
+        index_arg = LtacArg::I32(index.u64_val as i32);
+    } else if arg.sub_args.len() == 1 && index.arg_type == AstArgType::Id {
+        match builder.vars.get(&index.str_val) {
+            Some(v) if v.is_global() => {
+                builder.syntax.ltac_error(line, "Global variables cannot be used as array indexes.".to_string()).code(E_GLOBAL_INDEX);
+                return None;
+            },
+            
+            Some(v) => {
+                mem = LtacArg::MemOffsetMem(var.pos, v.pos, size);
+                index_arg = LtacArg::Mem(v.pos);
+            },
+            
+            None => {
+                builder.syntax.ltac_error(line, "Invalid offset variable.".to_string()).code(E_UNKNOWN_NAME);
+                return None;
+            },
+        }
+    } else {
+        builder.syntax.ltac_error(line, "Array indexes in conditions must be a number or a variable.".to_string()).code(E_INVALID_OP);
+        return None;
+    }
+    
+    // The element goes in this register, so it is free for the bounds check
+    let check = bounds_check(builder, &arg.str_val, index_arg, reg_no);
+    block.extend(check);
+    
+    let reg = reg_for_type(&elem_type, &DataType::None, reg_no);
+    
+    let mut mov = mov_for_type(&elem_type, &DataType::None);
+    mov.arg1 = reg.clone();
+    mov.arg2 = mem;
+    block.push(mov);
+    
+    Some((cmp_type, reg, signed))
+}
+
 // Builds an LTAC conditional block (specific for if-else)
-pub fn build_cond(builder : &mut LtacBuilder, line : &AstStmt) {
+pub fn build_cond(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
     if line.stmt_type == AstStmtType::If {
         builder.block_layer += 1;
         
@@ -532,7 +758,7 @@ pub fn build_cond(builder : &mut LtacBuilder, line : &AstStmt) {
             Some(lbl) => lbl.to_string(),
             None => {
                 // TODO: Error?
-                return;
+                return true;
             },
         };
         
@@ -553,58 +779,27 @@ pub fn build_cond(builder : &mut LtacBuilder, line : &AstStmt) {
         builder.label_map.remove(&builder.block_layer);
         
         if line.stmt_type == AstStmtType::Else {
-            return;
+            return true;
         }
     }
     
-    let cmp_block = build_cmp(builder, line);
-    for ln in cmp_block.iter() {
-        builder.file.code.push(ln.clone());
-    }
-    
-    // Add the instruction
-    let cmp = cmp_block.last().unwrap();
-    let cmp_type = cmp.instr_type.clone();
-    
-    // Now the operator
+    // Jump to the next part if the condition is false
     let name = create_label(builder);
+    let mut cmp_block : Vec<LtacInstr> = Vec::new();
     
-    let op = &line.args.iter().nth(1).unwrap();
-    let mut br = ltac::create_instr(LtacType::Br);
-    br.name = name;
+    if !build_cond_jump(builder, line, &name, false, &mut cmp_block) {
+        return false;
+    }
     
-    match &op.arg_type {
-        AstArgType::OpEq => br.instr_type = LtacType::Bne,
-        AstArgType::OpNeq => br.instr_type = LtacType::Be,
-        
-        AstArgType::OpLt 
-            if (cmp_type == LtacType::F32Cmp || cmp_type == LtacType::F64Cmp)
-            => br.instr_type = LtacType::Bfge,
-        AstArgType::OpLt => br.instr_type = LtacType::Bge,
-        
-        AstArgType::OpLte
-            if (cmp_type == LtacType::F32Cmp || cmp_type == LtacType::F64Cmp)
-            => br.instr_type = LtacType::Bfg,
-        AstArgType::OpLte => br.instr_type = LtacType::Bg,
-        
-        AstArgType::OpGt
-            if (cmp_type == LtacType::F32Cmp || cmp_type == LtacType::F64Cmp)
-            => br.instr_type = LtacType::Bfle,
-        AstArgType::OpGt => br.instr_type = LtacType::Ble,
-        
-        AstArgType::OpGte
-            if (cmp_type == LtacType::F32Cmp || cmp_type == LtacType::F64Cmp)
-            => br.instr_type = LtacType::Bfl,
-        AstArgType::OpGte => br.instr_type = LtacType::Bl,
-        
-        _ => {},
+    for ln in cmp_block.iter() {
+        builder.file.code.push(ln.clone());
     }
     
-    builder.file.code.push(br);
+    true
 }
 
 // Builds a while loop block
-pub fn build_while(builder : &mut LtacBuilder, line : &AstStmt) {
+pub fn build_while(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
     builder.block_layer += 1;
     builder.loop_layer += 1;
     
@@ -635,34 +830,422 @@ pub fn build_while(builder : &mut LtacBuilder, line : &AstStmt) {
     lbl2.name = cmp_label.clone();
     cmp_block.push(lbl2);
     
-    // Build the conditional statement
-    let block = build_cmp(builder, line);
-    for ln in block.iter() {
-        cmp_block.push(ln.clone());
+    // Loop again while the condition is true
+    if !build_cond_jump(builder, line, &loop_label, true, &mut cmp_block) {
+        return false;
+    }
+    
+    // The end label
+    let mut end_lbl = ltac::create_instr(LtacType::Label);
+    end_lbl.name = end_label.clone();
+    cmp_block.push(end_lbl);
+    
+    builder.code_stack.push(cmp_block);
+    
+    true
+}
+
+// Builds the jumps for a whole condition
+// "and" and "or" short-circuit: the right side is skipped once the left decides the result
+fn build_cond_jump(builder : &mut LtacBuilder, line : &AstStmt, label : &String, jump_if_true : bool, block : &mut Vec<LtacInstr>) -> bool {
+    let tree = match cond_tree(&line.args) {
+        Ok(t) => t,
+        Err(msg) => {
+            builder.syntax.ltac_error(line, msg).code(E_INVALID_OP);
+            return false;
+        },
+    };
+    
+    build_cond_tree(builder, line, &tree, label, jump_if_true, block)
+}
+
+fn build_cond_tree(builder : &mut LtacBuilder, line : &AstStmt, cond : &AstCond, label : &String, jump_if_true : bool, block : &mut Vec<LtacInstr>) -> bool {
+    match cond {
+        AstCond::Test(start, end) => return build_test_jump(builder, line, &line.args[*start..*end], label, jump_if_true, block),
+        AstCond::Not(c) => return build_cond_tree(builder, line, c, label, !jump_if_true, block),
+        _ => {},
+    }
+    
+    // Jumping out of an "and" when false, or out of an "or" when true, needs both sides to jump
+    // Otherwise, the left side skips the right when it decides the result
+    let (lhs, rhs, is_and) = match cond {
+        AstCond::And(lhs, rhs) => (lhs, rhs, true),
+        AstCond::Or(lhs, rhs) => (lhs, rhs, false),
+        _ => return false,
+    };
+    
+    if is_and != jump_if_true {
+        return build_cond_tree(builder, line, lhs, label, jump_if_true, block)
+            && build_cond_tree(builder, line, rhs, label, jump_if_true, block);
+    }
+    
+    let skip_label = local_label(builder);
+    
+    if !build_cond_tree(builder, line, lhs, &skip_label, !is_and, block)
+        || !build_cond_tree(builder, line, rhs, label, jump_if_true, block) {
+        return false;
+    }
+    
+    let mut lbl = ltac::create_instr(LtacType::Label);
+    lbl.name = skip_label;
+    block.push(lbl);
+    
+    true
+}
+
+// Builds the jump for a single comparison or bool value
+fn build_test_jump(builder : &mut LtacBuilder, line : &AstStmt, args : &[AstArg], label : &String, jump_if_true : bool, block : &mut Vec<LtacInstr>) -> bool {
+    let mut args = args.to_vec();
+    
+    // Compound values are computed into temporaries ahead of the comparison
+    for arg in args.iter_mut() {
+        if arg.arg_type == AstArgType::Expr {
+            match build_expr_block(builder, arg, block) {
+                Some(temp) => *arg = temp,
+                None => return false,
+            }
+        }
+    }
+    
+    if args.len() == 1 {
+        // A literal either always jumps, or never does
+        if args[0].arg_type == AstArgType::BoolL {
+            if (args[0].u8_val == 1) == jump_if_true {
+                let mut br = ltac::create_instr(LtacType::Br);
+                br.name = label.clone();
+                block.push(br);
+            }
+            
+            return true;
+        }
+        
+        // Bool values are compared with false
+        args.push(ast::create_arg(AstArgType::OpNeq));
+        args.push(ast::create_byte(0));
+    }
+    
+    let cmp_block = build_cmp(builder, line, &args);
+    
+    let cmp_type = match cmp_block.last() {
+        Some(c) => c.instr_type.clone(),
+        None => return false,
+    };
+    
+    for ln in cmp_block.iter() {
+        block.push(ln.clone());
+    }
+    
+    let mut op = args[1].arg_type.clone();
+    
+    if !jump_if_true {
+        op = match op {
+            AstArgType::OpEq => AstArgType::OpNeq,
+            AstArgType::OpNeq => AstArgType::OpEq,
+            AstArgType::OpLt => AstArgType::OpGte,
+            AstArgType::OpLte => AstArgType::OpGt,
+            AstArgType::OpGt => AstArgType::OpLte,
+            _ => AstArgType::OpLt,
+        };
     }
     
-    // Now the operator
-    let op = &line.args.iter().nth(1).unwrap();
+    let is_float = cmp_type == LtacType::F32Cmp || cmp_type == LtacType::F64Cmp;
+    
     let mut br = ltac::create_instr(LtacType::Br);
-    br.name = loop_label.clone();
+    br.name = label.clone();
     
-    match &op.arg_type {
+    match &op {
         AstArgType::OpEq => br.instr_type = LtacType::Be,
         AstArgType::OpNeq => br.instr_type = LtacType::Bne,
+        
+        AstArgType::OpLt if is_float => br.instr_type = LtacType::Bfl,
         AstArgType::OpLt => br.instr_type = LtacType::Bl,
+        
+        AstArgType::OpLte if is_float => br.instr_type = LtacType::Bfle,
         AstArgType::OpLte => br.instr_type = LtacType::Ble,
+        
+        AstArgType::OpGt if is_float => br.instr_type = LtacType::Bfg,
         AstArgType::OpGt => br.instr_type = LtacType::Bg,
+        
+        AstArgType::OpGte if is_float => br.instr_type = LtacType::Bfge,
         AstArgType::OpGte => br.instr_type = LtacType::Bge,
+        
         _ => {},
     }
     
-    cmp_block.push(br);
+    block.push(br);
+    true
+}
+
+// Builds an assignment to a bool variable
+// The condition decides which value gets stored
+pub fn build_bool_assign(builder : &mut LtacBuilder, line : &AstStmt, var : &Var) -> bool {
+    builder.syntax.set_data(line);
     
-    // The end label
-    let mut end_lbl = ltac::create_instr(LtacType::Label);
-    end_lbl.name = end_label.clone();
-    cmp_block.push(end_lbl);
+    // Literals are stored directly
+    if line.args.len() == 1 && line.args[0].arg_type == AstArgType::BoolL {
+        let mut mov = ltac::create_instr(LtacType::MovUB);
+        mov.arg1 = var.mem();
+        mov.arg2 = LtacArg::UByte(line.args[0].u8_val);
+        builder.file.code.push(mov);
+        
+        return true;
+    }
     
-    builder.code_stack.push(cmp_block);
+    let false_label = local_label(builder);
+    let end_label = local_label(builder);
+    let mut block : Vec<LtacInstr> = Vec::new();
+    
+    if !build_cond_jump(builder, line, &false_label, false, &mut block) {
+        return false;
+    }
+    
+    let mut mov = ltac::create_instr(LtacType::MovUB);
+    mov.arg1 = var.mem();
+    mov.arg2 = LtacArg::UByte(1);
+    block.push(mov.clone());
+    
+    let mut br = ltac::create_instr(LtacType::Br);
+    br.name = end_label.clone();
+    block.push(br);
+    
+    let mut lbl = ltac::create_instr(LtacType::Label);
+    lbl.name = false_label;
+    block.push(lbl.clone());
+    
+    mov.arg2 = LtacArg::UByte(0);
+    block.push(mov);
+    
+    lbl.name = end_label;
+    block.push(lbl);
+    
+    builder.file.code.append(&mut block);
+    true
+}
+
+// Builds a case statement
+// The selector is loaded once; dense choices then go through a jump table, and
+// the others through a tree of comparisons. Each arm ends with a jump to the end.
+pub fn build_case(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
+    let var = match builder.vars.get(&line.args[0].str_val) {
+        Some(v) => v.clone(),
+        None => {
+            builder.syntax.ltac_error(line, "Invalid variable.".to_string()).code(E_UNKNOWN_NAME);
+            return false;
+        },
+    };
+    
+    let data_type = var.data_type.clone();
+    
+    let values = match &data_type {
+        DataType::Enum(name) => builder.enums.get(name).map(|e| e.values.clone()),
+        _ => None,
+    };
+    
+    let reg = reg_for_type(&data_type, &DataType::None, 0);
+    
+    let mut mov = mov_for_type(&data_type, &DataType::None);
+    mov.arg1 = reg.clone();
+    mov.arg2 = var.mem();
+    builder.file.code.push(mov);
+    
+    // Each arm gets a label; "when others" is the default
+    let arms = case_arms(line);
+    let end_label = local_label(builder);
+    let mut default_label = end_label.clone();
+    let mut labels : Vec<String> = Vec::new();
+    let mut choices : Vec<(i64, i64, String)> = Vec::new();
+    
+    for arm in arms.iter() {
+        let label = local_label(builder);
+        
+        let ranges = match choice_ranges(arm, values.as_ref()) {
+            Ok(r) => r,
+            Err(msg) => {
+                builder.syntax.ltac_error(arm, msg);
+                return false;
+            },
+        };
+        
+        if ranges.len() == 0 {
+            default_label = label.clone();
+        }
+        
+        for (low, high) in ranges.iter() {
+            choices.push((*low, *high, label.clone()));
+        }
+        
+        labels.push(label);
+    }
+    
+    choices.sort();
+    
+    let ranges : Vec<(i64, i64)> = choices.iter().map(|(low, high, _)| (*low, *high)).collect();
+    
+    // The table index is computed with an immediate subtraction
+    if is_dense(&ranges) && ranges[0].0 >= i32::MIN as i64 && ranges[0].0 <= i32::MAX as i64 {
+        build_case_table(builder, &data_type, &reg, &choices, &default_label);
+    } else {
+        build_case_tree(builder, &data_type, &reg, &choices, &default_label);
+    }
+    
+    for (arm, label) in arms.iter().zip(labels.iter()) {
+        let mut lbl = ltac::create_instr(LtacType::Label);
+        lbl.name = label.clone();
+        builder.file.code.push(lbl);
+        
+        if !builder.build_block(&arm_body(arm).to_vec()) {
+            return false;
+        }
+        
+        let mut br = ltac::create_instr(LtacType::Br);
+        br.name = end_label.clone();
+        builder.file.code.push(br);
+    }
+    
+    let mut lbl = ltac::create_instr(LtacType::Label);
+    lbl.name = end_label;
+    builder.file.code.push(lbl);
+    
+    true
+}
+
+// Values outside the table go to the default; the rest are offset to start at zero
+fn build_case_table(builder : &mut LtacBuilder, data_type : &DataType, reg : &LtacArg, choices : &Vec<(i64, i64, String)>, default_label : &String) {
+    let min = choices[0].0;
+    let max = choices.last().unwrap().1;
+    
+    build_case_cmp(builder, data_type, reg, min, AstArgType::OpLt, default_label);
+    build_case_cmp(builder, data_type, reg, max, AstArgType::OpGt, default_label);
+    
+    if min != 0 {
+        let mut sub = match reg {
+            LtacArg::Reg8(_) => ltac::create_instr(LtacType::I8Sub),
+            LtacArg::Reg16(_) => ltac::create_instr(LtacType::I16Sub),
+            LtacArg::Reg64(_) => ltac::create_instr(LtacType::I64Sub),
+            _ => ltac::create_instr(LtacType::I32Sub),
+        };
+        
+        sub.arg1 = reg.clone();
+        sub.arg2 = case_imm(data_type, min);
+        builder.file.code.push(sub);
+    }
+    
+    let table_label = local_label(builder);
+    
+    let mut br = ltac::create_instr(LtacType::BrTable);
+    br.name = table_label.clone();
+    br.arg1 = reg.clone();
+    builder.file.code.push(br);
+    
+    let mut lbl = ltac::create_instr(LtacType::Label);
+    lbl.name = table_label;
+    builder.file.code.push(lbl);
+    
+    for value in min ..= max {
+        let mut entry = ltac::create_instr(LtacType::TableEntry);
+        
+        entry.name = match choices.iter().find(|(low, high, _)| value >= *low && value <= *high) {
+            Some((_, _, label)) => label.clone(),
+            None => default_label.clone(),
+        };
+        
+        builder.file.code.push(entry);
+    }
+}
+
+// Splits the sorted choices in half until only a few are left, then checks those in order
+// A value below the current choice cannot match any of the ones after it either.
+fn build_case_tree(builder : &mut LtacBuilder, data_type : &DataType, reg : &LtacArg, choices : &[(i64, i64, String)], default_label : &String) {
+    if choices.len() > 3 {
+        let mid = choices.len() / 2;
+        let left_label = local_label(builder);
+        
+        build_case_cmp(builder, data_type, reg, choices[mid].0, AstArgType::OpLt, &left_label);
+        build_case_tree(builder, data_type, reg, &choices[mid..], default_label);
+        
+        let mut lbl = ltac::create_instr(LtacType::Label);
+        lbl.name = left_label;
+        builder.file.code.push(lbl);
+        
+        build_case_tree(builder, data_type, reg, &choices[..mid], default_label);
+        return;
+    }
+    
+    for (low, high, label) in choices.iter() {
+        if low == high {
+            build_case_cmp(builder, data_type, reg, *low, AstArgType::OpEq, label);
+        } else {
+            build_case_cmp(builder, data_type, reg, *low, AstArgType::OpLt, default_label);
+            build_case_cmp(builder, data_type, reg, *high, AstArgType::OpLte, label);
+        }
+    }
+    
+    let mut br = ltac::create_instr(LtacType::Br);
+    br.name = default_label.clone();
+    builder.file.code.push(br);
+}
+
+// Compares the selector with a value, and branches to the label if the condition holds
+// Unsigned selectors use the unsigned branches
+fn build_case_cmp(builder : &mut LtacBuilder, data_type : &DataType, reg : &LtacArg, value : i64, op : AstArgType, label : &String) {
+    let signed = match data_type {
+        DataType::UByte | DataType::UShort | DataType::UInt | DataType::UInt64 => false,
+        _ => true,
+    };
+    
+    let mut cmp = match (reg, signed) {
+        (LtacArg::Reg8(_), true) => ltac::create_instr(LtacType::I8Cmp),
+        (LtacArg::Reg8(_), false) => ltac::create_instr(LtacType::U8Cmp),
+        (LtacArg::Reg16(_), true) => ltac::create_instr(LtacType::I16Cmp),
+        (LtacArg::Reg16(_), false) => ltac::create_instr(LtacType::U16Cmp),
+        (LtacArg::Reg64(_), true) => ltac::create_instr(LtacType::I64Cmp),
+        (LtacArg::Reg64(_), false) => ltac::create_instr(LtacType::U64Cmp),
+        (_, true) => ltac::create_instr(LtacType::I32Cmp),
+        (_, false) => ltac::create_instr(LtacType::U32Cmp),
+    };
+    
+    cmp.arg1 = reg.clone();
+    cmp.arg2 = case_imm(data_type, value);
+    
+    // Immediates are at most 32 bits; larger ones go through a register
+    if value < i32::MIN as i64 || value > i32::MAX as i64 {
+        let mut mov = ltac::create_instr(LtacType::MovQ);
+        mov.arg1 = LtacArg::Reg64(1);
+        mov.arg2 = cmp.arg2.clone();
+        builder.file.code.push(mov);
+        
+        cmp.arg2 = LtacArg::Reg64(1);
+    }
+    
+    builder.file.code.push(cmp);
+    
+    let mut br = ltac::create_instr(LtacType::Br);
+    br.name = label.clone();
+    
+    match (op, signed) {
+        (AstArgType::OpEq, _) => br.instr_type = LtacType::Be,
+        (AstArgType::OpLt, true) => br.instr_type = LtacType::Bl,
+        (AstArgType::OpLt, false) => br.instr_type = LtacType::Bul,
+        (AstArgType::OpLte, true) => br.instr_type = LtacType::Ble,
+        (AstArgType::OpLte, false) => br.instr_type = LtacType::Bule,
+        (AstArgType::OpGt, true) => br.instr_type = LtacType::Bg,
+        (AstArgType::OpGt, false) => br.instr_type = LtacType::Bug,
+        _ => {},
+    }
+    
+    builder.file.code.push(br);
 }
 
+// Returns an immediate of the selector's size
+fn case_imm(data_type : &DataType, value : i64) -> LtacArg {
+    match data_type {
+        DataType::Byte | DataType::Char => return LtacArg::Byte(value as i8),
+        DataType::UByte => return LtacArg::UByte(value as u8),
+        DataType::Short => return LtacArg::I16(value as i16),
+        DataType::UShort => return LtacArg::U16(value as u16),
+        DataType::UInt => return LtacArg::U32(value as u32),
+        DataType::Int64 => return LtacArg::I64(value),
+        DataType::UInt64 => return LtacArg::U64(value as u64),
+        _ => return LtacArg::I32(value as i32),
+    }
+}
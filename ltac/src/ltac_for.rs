@@ -16,14 +16,18 @@
 // 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
 
 use crate::ltac_builder::*;
+use crate::ltac_expr::build_expr_block;
 use crate::ltac_utils::*;
+use crate::ltac_var::build_var_assign;
 
-use crate::ast::{DataType, AstStmt, AstArgType};
+use crate::ast;
+use crate::ast::{DataType, AstStmt, AstStmtType, AstArgType};
+use crate::ast_expr::arg_expr;
 use crate::ltac;
 use crate::ltac::{LtacType, LtacInstr, LtacArg};
 
 // Builds a for loop block
-pub fn build_for_loop(builder : &mut LtacBuilder, line : &AstStmt) {
+pub fn build_for_loop(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
     builder.block_layer += 1;
     builder.loop_layer += 1;
     
@@ -32,14 +36,15 @@ pub fn build_for_loop(builder : &mut LtacBuilder, line : &AstStmt) {
     create_label2(builder, false);   // Add a loop label
     
     if line.args.len() == 4 {
-        build_range_for_loop(builder, line);
-    } else {
-        build_foreach_loop(builder, line);
+        return build_range_for_loop(builder, line);
     }
+    
+    build_foreach_loop(builder, line);
+    true
 }
 
 // Builds a range-based for loop
-fn build_range_for_loop(builder : &mut LtacBuilder, line : &AstStmt)  {
+fn build_range_for_loop(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
     let end_label = builder.label_stack.pop().unwrap();
     let loop_label = builder.label_stack.pop().unwrap();
     let cmp_label = builder.label_stack.pop().unwrap();
@@ -59,16 +64,17 @@ fn build_range_for_loop(builder : &mut LtacBuilder, line : &AstStmt)  {
         data_type : DataType::Int,
         sub_type : DataType::None,
         is_param : false,
+        global : String::new(),
     };
     
-    builder.vars.insert(name, index);
+    builder.add_var(name, index);
     
     // Determine the type of loop
     let start_pos = line.args.iter().nth(1).unwrap();
     let end_arg = line.args.iter().nth(3).unwrap();
     
     // Set the variable equal to the start
-    // TODO: Other types
+    // Anything other than a number is built as an assignment to the index
     match start_pos.arg_type {
         AstArgType::IntL => {
             let mut instr = ltac::create_instr(LtacType::Mov);
@@ -77,7 +83,24 @@ fn build_range_for_loop(builder : &mut LtacBuilder, line : &AstStmt)  {
             builder.file.code.push(instr);
         },
         
-        _ => {},
+        _ => {
+            let mut stmt = ast::create_orphan_stmt(AstStmtType::VarAssign);
+            stmt.name = index_var.str_val.clone();
+            stmt.line = line.line.clone();
+            stmt.line_no = line.line_no;
+            stmt.col = line.col;
+            
+            match start_pos.arg_type {
+                AstArgType::Expr => stmt.args = start_pos.sub_args.clone(),
+                _ => stmt.args.push(start_pos.clone()),
+            }
+            
+            stmt.expr = Some(arg_expr(start_pos));
+            
+            if !build_var_assign(builder, &stmt) {
+                return false;
+            }
+        },
     }
     
     // Start the loop
@@ -94,6 +117,16 @@ fn build_range_for_loop(builder : &mut LtacBuilder, line : &AstStmt)  {
     lbl2.name = cmp_label.clone();
     cmp_block.push(lbl2);
     
+    // A computed end is found again on each pass, before the counter takes the register
+    let mut end_arg = end_arg.clone();
+    
+    if end_arg.arg_type == AstArgType::Expr {
+        match build_expr_block(builder, &end_arg, &mut cmp_block) {
+            Some(temp) => end_arg = temp,
+            None => return false,
+        }
+    }
+    
     let mut mov2 = ltac::create_instr(LtacType::Mov);
     mov2.arg1 = LtacArg::Reg32(0);
     mov2.arg2 = LtacArg::Mem(pos);
@@ -122,11 +155,11 @@ fn build_range_for_loop(builder : &mut LtacBuilder, line : &AstStmt)  {
                 Ok(v) => v,
                 Err(_e) => {
                     // TODO: Syntax error
-                    return;
+                    return false;
                 },
             };
             
-            cmp_instr.arg2 = LtacArg::Mem(v.pos);
+            cmp_instr.arg2 = v.mem();
         },
         
         _ => {},
@@ -145,6 +178,7 @@ fn build_range_for_loop(builder : &mut LtacBuilder, line : &AstStmt)  {
     cmp_block.push(end_lbl);
     
     builder.code_stack.push(cmp_block);
+    true
 }
 
 // Builds a foreach loop
@@ -201,9 +235,10 @@ fn build_foreach_loop(builder : &mut LtacBuilder, line : &AstStmt)  {
         data_type : data_type.clone(),
         sub_type : DataType::None,
         is_param : false,
+        global : String::new(),
     };
     
-    builder.vars.insert(index_name, index);
+    builder.add_var(index_name, index);
     
     // Build another index variable to keep track of the size
     builder.stack_pos += 4;
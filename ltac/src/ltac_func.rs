@@ -20,8 +20,13 @@ use crate::ltac_builder::*;
 use crate::ltac;
 use crate::ltac::{LtacType, LtacArg};
 use crate::ast::{DataType, AstStmt, AstArgType};
+use crate::ast_expr::arg_expr;
+use crate::syntax::*;
 
 use crate::ltac_array::*;
+use crate::ltac_expr::build_expr_temp;
+use crate::ltac_record::*;
+use crate::ltac_utils::*;
 
 // Builds an LTAC function call
 pub fn build_func_call(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
@@ -34,24 +39,80 @@ pub fn build_func_call(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
     }
     
     // Represents the current argument position
+    // Integers and floats are counted separately (arg2_val), and each push also gets its
+    // position in the call (arg1_val) for the arguments passed on the stack.
     let mut arg_no : i32 = 1;
     let mut flt_arg_no : i32 = 1;
-
+    
+    // Calls through a procedure variable take the signature of the procedure type
+    // Floats passed to "..." are promoted to double, like in C
+    let proc_var = builder.get_proc(&line.name)
+        .map(|p| (builder.get_var(&line.name).unwrap().mem(), p.clone()));
+    
+    let sig = match &proc_var {
+        Some((_, p)) => Some(FuncSig {
+            params : p.args.iter().map(|a| a.data_type.clone()).collect(),
+            is_variadic : false,
+        }),
+        
+        None => builder.signatures.get(&line.name).cloned(),
+    };
+    
+    let is_variadic = sig.as_ref().map_or(false, |s| s.is_variadic);
+    let param_count = sig.as_ref().map_or(0, |s| s.params.len());
+    
+    let is_double = |i : usize| -> bool {
+        match &sig {
+            Some(s) if s.is_variadic && i >= s.params.len() => return true,
+            Some(s) => return s.params.get(i) == Some(&DataType::Double),
+            None => return false,
+        }
+    };
+    
+    // Only array parameters get the size after the pointer; a "ptr to" parameter (which is
+    // how C functions take arrays) and the "..." part just get the pointer
+    let is_array = |i : usize| -> bool {
+        match &sig {
+            Some(s) => return s.params.get(i) == Some(&DataType::Ptr),
+            None => return false,
+        }
+    };
+    
+    // Compound arguments are computed before anything is pushed
+    let mut args = line.args.clone();
+    
+    for (i, arg) in args.iter_mut().enumerate() {
+        if arg.arg_type == AstArgType::Expr {
+            // The value is worked out in the type of the parameter, where there is one
+            let data_type = match sig.as_ref().and_then(|s| s.params.get(i)) {
+                Some(t) => t.clone(),
+                None => arg.data_type.clone(),
+            };
+            
+            match build_expr_temp(builder, &arg_expr(arg), &data_type) {
+                Some(temp) => *arg = temp,
+                None => return false,
+            }
+        }
+    }
+    
     // Build the arguments
-    for arg in line.args.iter() {
+    for (i, arg) in args.iter().enumerate() {
         match &arg.arg_type {
             AstArgType::CharL => {
                 let mut push = ltac::create_instr(arg_type.clone());
                 push.arg1 = LtacArg::UByte(arg.char_val as u8);
+                push.arg1_val = arg_no + flt_arg_no - 1;
                 push.arg2_val = arg_no;
                 builder.file.code.push(push);
                 
                 arg_no += 1;
             },
             
-            AstArgType::ByteL => {
+            AstArgType::ByteL | AstArgType::BoolL => {
                 let mut push = ltac::create_instr(arg_type.clone());
                 push.arg1 = LtacArg::UByte(arg.u8_val);
+                push.arg1_val = arg_no + flt_arg_no - 1;
                 push.arg2_val = arg_no;
                 builder.file.code.push(push);
                 
@@ -61,6 +122,7 @@ pub fn build_func_call(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
             AstArgType::ShortL => {
                 let mut push = ltac::create_instr(arg_type.clone());
                 push.arg1 = LtacArg::U16(arg.u16_val);
+                push.arg1_val = arg_no + flt_arg_no - 1;
                 push.arg2_val = arg_no;
                 builder.file.code.push(push);
                 
@@ -69,7 +131,26 @@ pub fn build_func_call(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
             
             AstArgType::IntL => {
                 let mut push = ltac::create_instr(arg_type.clone());
-                push.arg1 = LtacArg::U32(arg.u64_val as u32);
+                
+                // A 32-bit move clears the upper half, so only wider values need all 64 bits
+                if arg.u64_val > u32::MAX as u64 {
+                    push.arg1 = LtacArg::U64(arg.u64_val);
+                } else {
+                    push.arg1 = LtacArg::U32(arg.u64_val as u32);
+                }
+                
+                push.arg1_val = arg_no + flt_arg_no - 1;
+                push.arg2_val = arg_no;
+                builder.file.code.push(push);
+                
+                arg_no += 1;
+            },
+            
+            // A 32-bit move clears the whole register, so this is a 64-bit zero
+            AstArgType::Null => {
+                let mut push = ltac::create_instr(arg_type.clone());
+                push.arg1 = LtacArg::U32(0);
+                push.arg1_val = arg_no + flt_arg_no - 1;
                 push.arg2_val = arg_no;
                 builder.file.code.push(push);
                 
@@ -77,14 +158,22 @@ pub fn build_func_call(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
             },
             
             AstArgType::FloatL if call_type == LtacType::Syscall => {
-                builder.syntax.ltac_error(line, "Only integers and strings are valid in system calls.".to_string());
+                builder.syntax.ltac_error(line, "Only integers and strings are valid in system calls.".to_string()).code(E_TYPE_MISMATCH);
                 return false;
             },
             
             AstArgType::FloatL => {
                 let mut push = ltac::create_instr(LtacType::PushArg);
-                let name = builder.build_float(arg.f64_val, false, false);
-                push.arg1 = LtacArg::F32(name);
+                
+                if is_double(i) {
+                    let name = builder.build_float(arg.f64_val, true, false);
+                    push.arg1 = LtacArg::F64(name);
+                } else {
+                    let name = builder.build_float(arg.f64_val, false, false);
+                    push.arg1 = LtacArg::F32(name);
+                }
+                
+                push.arg1_val = arg_no + flt_arg_no - 1;
                 push.arg2_val = flt_arg_no;
                 builder.file.code.push(push);
                 
@@ -96,6 +185,19 @@ pub fn build_func_call(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
                 
                 let mut push = ltac::create_instr(arg_type.clone());
                 push.arg1 = LtacArg::PtrLcl(name);
+                push.arg1_val = arg_no + flt_arg_no - 1;
+                push.arg2_val = arg_no;
+                builder.file.code.push(push);
+                
+                arg_no += 1;
+            },
+            
+            // The address of a function is pushed directly
+            AstArgType::AddrOf if !builder.var_exists(&arg.sub_args[0].str_val)
+                    && builder.function_exists(&arg.sub_args[0].str_val) => {
+                let mut push = ltac::create_instr(arg_type.clone());
+                push.arg1 = LtacArg::PtrLcl(arg.sub_args[0].str_val.clone());
+                push.arg1_val = arg_no + flt_arg_no - 1;
                 push.arg2_val = arg_no;
                 builder.file.code.push(push);
                 
@@ -108,18 +210,52 @@ pub fn build_func_call(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
                     Ok(v) => v,
                     
                     Err(_e) => {
-                        builder.syntax.ltac_error2("Unknown variable reference.".to_string());
+                        builder.syntax.ltac_error2("Unknown variable reference.".to_string()).code(E_UNKNOWN_NAME);
                         return false;
                     },
                 };
                 
                 let mut instr2 = ltac::create_instr(LtacType::LdAddr);
                 instr2.arg1 = LtacArg::Reg64(0);
-                instr2.arg2 = LtacArg::Mem(ref_var.pos);
+                instr2.arg2 = ref_var.mem();
                 builder.file.code.push(instr2);
                 
                 let mut push = ltac::create_instr(arg_type.clone());
                 push.arg1 = LtacArg::Reg64(0);
+                push.arg1_val = arg_no + flt_arg_no - 1;
+                push.arg2_val = arg_no;
+                builder.file.code.push(push);
+                
+                arg_no += 1;
+            },
+            
+            // Record fields are loaded into a register first
+            AstArgType::Field => {
+                let (var, offset, data_type) = match get_field(builder, &arg) {
+                    Ok(f) => f,
+                    Err(_e) => return false,
+                };
+                
+                // The type checker only lets integer and string fields through
+                // Bytes and shorts are extended to an int when they are pushed, like variables are
+                let (reg, extend) = match data_type {
+                    DataType::Byte | DataType::Char => (LtacArg::Reg8(0), LtacArg::Byte(0)),
+                    DataType::UByte => (LtacArg::Reg8(0), LtacArg::UByte(0)),
+                    DataType::Short => (LtacArg::Reg16(0), LtacArg::I16(0)),
+                    DataType::UShort => (LtacArg::Reg16(0), LtacArg::U16(0)),
+                    DataType::Int | DataType::UInt => (LtacArg::Reg32(0), LtacArg::Empty),
+                    _ => (LtacArg::Reg64(0), LtacArg::Empty),
+                };
+                
+                let mut instr = mov_for_type(&data_type, &DataType::None);
+                instr.arg1 = reg.clone();
+                instr.arg2 = LtacArg::MemOffsetImm(var.pos, offset);
+                builder.file.code.push(instr);
+                
+                let mut push = ltac::create_instr(arg_type.clone());
+                push.arg1 = reg;
+                push.arg2 = extend;
+                push.arg1_val = arg_no + flt_arg_no - 1;
                 push.arg2_val = arg_no;
                 builder.file.code.push(push);
                 
@@ -133,49 +269,69 @@ pub fn build_func_call(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
                 // Check variables
                 match &builder.vars.get(&arg.str_val) {
                     Some(v) => {
-                        push.arg1 = LtacArg::Mem(v.pos);
+                        push.arg1 = v.mem();
                         
                         if v.data_type == DataType::Byte || v.data_type == DataType::Char {
                             push.arg2 = LtacArg::Byte(0);
-                            
-                        } else if v.data_type == DataType::UByte {
+                        
+                        } else if v.data_type == DataType::UByte || v.data_type == DataType::Bool {
                             push.arg2 = LtacArg::UByte(0);
-                            
+                        
                         } else if v.data_type == DataType::Short {
                             push.arg2 = LtacArg::I16(0);
-                            
+                        
                         } else if v.data_type == DataType::UShort {
                             push.arg2 = LtacArg::U16(0);
-                            
+                        
                         } else if v.data_type == DataType::Ptr {
-                            push.arg1 = LtacArg::Ptr(v.pos);
+                            push.arg1 = v.ptr();
                             
                             // Push the size if we are not making a system call
-                            if arg_type != LtacType::KPushArg {
+                            if arg_type != LtacType::KPushArg && is_array(i) {
                                 let mut push2 = ltac::create_instr(LtacType::PushArg);
                                 push2.arg1 = LtacArg::Mem(v.pos - 8);
                                 push2.arg2 = LtacArg::I32(0);
+                                push2.arg1_val = arg_no + flt_arg_no;
                                 push2.arg2_val = arg_no + 1;
                                 builder.file.code.push(push2);
                             }
-                            
+                        
                         } else if v.data_type == DataType::Str {
-                            push.arg1 = LtacArg::Ptr(v.pos);
-                            
-                        } else if v.data_type == DataType::Int64 {
+                            push.arg1 = v.ptr();
+                        
+                        } else if let DataType::Record(_) = v.data_type {
+                            push.arg1 = v.ptr();
+                        
+                        } else if v.data_type == DataType::Int64 || matches!(v.data_type, DataType::Proc(_) | DataType::Pointer(_)) {
                             push.arg2 = LtacArg::I64(0);
-                            
+                        
                         } else if v.data_type == DataType::UInt64 {
                             push.arg2 = LtacArg::U64(0);
+                        
+                        } else if v.data_type == DataType::Float && is_variadic && i >= param_count {
+                            let mut mov = ltac::create_instr(LtacType::MovF32);
+                            mov.arg1 = LtacArg::FltReg(0);
+                            mov.arg2 = v.mem();
+                            builder.file.code.push(mov);
+                            
+                            let mut cvt = ltac::create_instr(LtacType::CvtF32F64);
+                            cvt.arg1 = LtacArg::FltReg64(0);
+                            cvt.arg2 = LtacArg::FltReg(0);
+                            builder.file.code.push(cvt);
                             
+                            push.arg1 = LtacArg::FltReg64(0);
+                            push.arg2 = LtacArg::FltReg64(flt_arg_no);
+                        
                         } else if v.data_type == DataType::Float {
                             push.arg2 = LtacArg::FltReg(flt_arg_no);
-                            
+                        
                         } else if v.data_type == DataType::Double {
                             push.arg2 = LtacArg::FltReg64(flt_arg_no);
                         }
                         
                         // For the proper registers
+                        push.arg1_val = arg_no + flt_arg_no - 1;
+                        
                         if v.data_type == DataType::Float || v.data_type == DataType::Double {
                             push.arg2_val = flt_arg_no;
                             flt_arg_no += 1;
@@ -183,7 +339,7 @@ pub fn build_func_call(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
                             push.arg2_val = arg_no;
                             arg_no += 1;
                             
-                            if v.data_type == DataType::Ptr && arg_type != LtacType::KPushArg {
+                            if v.data_type == DataType::Ptr && arg_type != LtacType::KPushArg && is_array(i) {
                                 arg_no += 1;
                             }
                         }
@@ -200,7 +356,7 @@ pub fn build_func_call(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
                 let mut msg = "Invalid constant or variable name: ".to_string();
                 msg.push_str(&arg.str_val);
                 
-                builder.syntax.ltac_error(line, msg);
+                builder.syntax.ltac_error(line, msg).code(E_UNKNOWN_NAME);
                 return false;
             },
             
@@ -209,35 +365,73 @@ pub fn build_func_call(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
     }
     
     // Build the call
+    // Variadic functions are told how many vector registers hold arguments
+    // Indirect calls have no name; the variable holding the address is in arg1
     let mut fc = ltac::create_instr(call_type);
-    fc.name = line.name.clone();
+    
+    match proc_var {
+        Some((mem, _)) => fc.arg1 = mem,
+        None => fc.name = line.name.clone(),
+    }
+    
+    if is_variadic {
+        fc.arg2 = LtacArg::I32(flt_arg_no - 1);
+    }
+    
     builder.file.code.push(fc);
     
     true
 }
 
 // Builds a function return
+// The type checker has already matched the value against the function type
 pub fn build_return(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
-    if line.args.len() > 0 && builder.current_type == DataType::Void {
-        let mut msg = "Cannot return value in void function: ".to_string();
-        msg.push_str(&builder.current_func);
-         
-        builder.syntax.ltac_error(line, msg);
-        return false;
+    // Anything other than a single value is computed into a temporary first,
+    // before the arrays it may use are freed
+    let mut args = line.args.clone();
+    
+    let is_value = match line.args.first() {
+        Some(arg) if line.args.len() == 1 => match &arg.arg_type {
+            AstArgType::ByteL | AstArgType::BoolL | AstArgType::CharL | AstArgType::ShortL
+            | AstArgType::IntL | AstArgType::FloatL | AstArgType::Null => true,
+            
+            AstArgType::Id => arg.sub_args.len() == 0 && builder.var_exists(&arg.str_val),
+            _ => false,
+        },
+        
+        _ => false,
+    };
+    
+    if args.len() > 0 && !is_value
+        && !matches!(builder.current_type, DataType::Ptr | DataType::Record(_) | DataType::Void) {
+        let data_type = builder.current_type.clone();
+        let value = match &line.expr {
+            Some(expr) => expr,
+            
+            None => {
+                builder.syntax.ltac_error(line, "Invalid expression.".to_string());
+                return false;
+            },
+        };
+        
+        match build_expr_temp(builder, value, &data_type) {
+            Some(temp) => args = vec![temp],
+            None => return false,
+        }
     }
     
     let mut to_ignore = String::new();
-    if line.args.len() == 1 {
-        let arg1 = line.args.first().unwrap();
+    if args.len() == 1 {
+        let arg1 = args.first().unwrap();
         if arg1.arg_type == AstArgType::Id {
             to_ignore = arg1.str_val.clone();
         }
     }
-
+    
     free_arrays(builder, to_ignore);
-
-    if line.args.len() == 1 {
-        let arg1 = line.args.first().unwrap();
+    
+    if args.len() == 1 {
+        let arg1 = args.first().unwrap();
         let mut mov = ltac::create_instr(LtacType::Mov);
         
         match &builder.current_type {
@@ -246,7 +440,7 @@ pub fn build_return(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
                 mov.arg1 = LtacArg::RetRegI8;
             },
             
-            DataType::UByte => {
+            DataType::UByte | DataType::Bool => {
                 mov = ltac::create_instr(LtacType::MovUB);
                 mov.arg1 = LtacArg::RetRegU8;
             },
@@ -271,7 +465,7 @@ pub fn build_return(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
                 mov.arg1 = LtacArg::RetRegI64;
             },
             
-            DataType::UInt64 => {
+            DataType::UInt64 | DataType::Pointer(_) => {
                 mov = ltac::create_instr(LtacType::MovUQ);
                 mov.arg1 = LtacArg::RetRegU64;
             },
@@ -296,8 +490,8 @@ pub fn build_return(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
         }
         
         match &arg1.arg_type {
-            AstArgType::ByteL => {
-                if builder.current_type == DataType::UByte {
+            AstArgType::ByteL | AstArgType::BoolL => {
+                if builder.current_type == DataType::UByte || builder.current_type == DataType::Bool {
                     mov.arg2 = LtacArg::UByte(arg1.u8_val);
                 } else {
                     mov.arg2 = LtacArg::Byte(arg1.u8_val as i8);
@@ -315,7 +509,7 @@ pub fn build_return(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
                     mov.arg2 = LtacArg::I16(arg1.u16_val as i16);
                 }
             },
-        
+            
             AstArgType::IntL => {
                 match builder.current_type {
                     DataType::Int => mov.arg2 = LtacArg::I32(arg1.u64_val as i32),
@@ -338,10 +532,11 @@ pub fn build_return(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
             }
             
             AstArgType::StringL => {},
+            AstArgType::Null => mov.arg2 = LtacArg::U64(0),
             
             AstArgType::Id => {
                 match builder.vars.get(&arg1.str_val) {
-                    Some(v) => mov.arg2 = LtacArg::Mem(v.pos),
+                    Some(v) => mov.arg2 = v.mem(),
                     None => {/* TODO: Syntax error */},
                 }
             },
@@ -350,7 +545,7 @@ pub fn build_return(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
         }
         
         builder.file.code.push(mov);
-    } else if line.args.len() > 1 {
+    } else if args.len() > 1 {
         // TODO
     }
     
@@ -369,18 +564,15 @@ pub fn build_exit(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
     
     if line.args.len() == 1 {
         //TODO
-    } else if line.args.len() > 1 {
-        builder.syntax.ltac_error(line, "You can only have one argument in the \"exit\" statement.".to_string());
-        return false;
     }
     
     builder.file.code.push(instr);
-
+    
     true
 }
 
 // Builds the end of a block
-pub fn build_end(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
+pub fn build_end(builder : &mut LtacBuilder, _line : &AstStmt) -> bool {
     if builder.block_layer == 0 {
         let last = builder.file.code.last().unwrap().clone();
         
@@ -399,16 +591,7 @@ pub fn build_end(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
         if last.instr_type != LtacType::Ret && last.instr_type != LtacType::Exit {
             free_arrays(builder, String::new());
             
-            // See if there was supposed to be a return instruction
-            if builder.current_type != DataType::Void {
-                let mut msg = "Expected return in function: ".to_string();
-                msg.push_str(&builder.current_func);
-                
-                builder.syntax.ltac_error(line, msg);
-                return false;
-            }
-            
-            // Otherwise, create a void instruction
+            // Non-void functions always end in a return (see the type checker)
             let ret = ltac::create_instr(LtacType::Ret);
             builder.file.code.push(ret);
         }
@@ -444,7 +627,7 @@ pub fn build_end(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
         
         builder.top_labels.remove(&builder.block_layer);
         builder.label_map.remove(&builder.block_layer);
-    
+        
         builder.block_layer -= 1;
         
         if builder.loop_layer > 0 {
@@ -0,0 +1,291 @@
+// This file is part of the Lila compiler
+// Copyright (C) 2020-2021 Patrick Flynn
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; version 2.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, write to the Free Software Foundation, Inc.,
+// 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+
+// Handles record types in the LTAC layer
+//
+// A record variable is always an 8-byte pointer to the record's memory. A local
+// record (one declared without an assignment) gets its memory from the stack, and
+// the pointer is set up at the declaration. Otherwise, the variable is a reference
+// to memory owned by someone else (a parameter, a heap allocation, etc).
+//
+// Fields are laid out in declaration order, each one aligned to its own size.
+
+use crate::ltac_builder::*;
+use crate::ast;
+use crate::ast::{DataType, AstStmt, AstStmtType, AstArg, AstArgType};
+use crate::syntax::*;
+use crate::ltac;
+use crate::ltac::{LtacType, LtacArg, LtacInstr};
+
+use crate::ltac_expr::*;
+use crate::ltac_func::*;
+use crate::ltac_utils::*;
+use crate::ltac_var::*;
+
+// Returns the size of a record, padded to its largest field
+pub fn record_size(builder : &LtacBuilder, name : &String) -> i32 {
+    let record = match builder.records.get(name) {
+        Some(r) => r,
+        None => return 0,
+    };
+    
+    let mut size = 0;
+    let mut align = 1;
+    
+    for field in record.fields.iter() {
+        let field_size = size_for_type(&field.data_type);
+        
+        if size % field_size != 0 {
+            size += field_size - (size % field_size);
+        }
+        
+        size += field_size;
+        
+        if field_size > align {
+            align = field_size;
+        }
+    }
+    
+    if size % align != 0 {
+        size += align - (size % align);
+    }
+    
+    size
+}
+
+// Returns the offset and type of a field within a record
+pub fn record_field(builder : &LtacBuilder, name : &String, field_name : &String) -> Result<(i32, DataType), ()> {
+    let record = match builder.records.get(name) {
+        Some(r) => r,
+        None => return Err(()),
+    };
+    
+    let mut offset = 0;
+    
+    for field in record.fields.iter() {
+        let field_size = size_for_type(&field.data_type);
+        
+        if offset % field_size != 0 {
+            offset += field_size - (offset % field_size);
+        }
+        
+        if field.name == *field_name {
+            return Ok((offset, field.data_type.clone()));
+        }
+        
+        offset += field_size;
+    }
+    
+    Err(())
+}
+
+// Resolves a field argument to the record variable, the field offset, and the field type
+pub fn get_field(builder : &mut LtacBuilder, arg : &AstArg) -> Result<(Var, i32, DataType), ()> {
+    let var = match builder.get_var(&arg.str_val) {
+        Ok(v) => v.clone(),
+        Err(_e) => {
+            let mut msg = "Invalid record variable: ".to_string();
+            msg.push_str(&arg.str_val);
+            
+            builder.syntax.ltac_error2(msg).code(E_UNKNOWN_NAME);
+            return Err(());
+        },
+    };
+    
+    let record_name = match &var.data_type {
+        DataType::Record(name) => name.clone(),
+        
+        _ => {
+            builder.syntax.ltac_error2("Only records have fields.".to_string());
+            return Err(());
+        },
+    };
+    
+    if arg.sub_args.len() != 1 {
+        builder.syntax.ltac_error2("Invalid field access.".to_string());
+        return Err(());
+    }
+    
+    let field_name = &arg.sub_args.first().unwrap().str_val;
+    
+    match record_field(builder, &record_name, field_name) {
+        Ok((offset, data_type)) => return Ok((var, offset, data_type)),
+        
+        Err(_e) => {
+            let mut msg = "Invalid field name: ".to_string();
+            msg.push_str(field_name);
+            
+            builder.syntax.ltac_error2(msg).code(E_UNKNOWN_NAME);
+            return Err(());
+        },
+    }
+}
+
+// Builds a record variable declaration or assignment
+pub fn build_record_assign(builder : &mut LtacBuilder, line : &AstStmt, var : &Var) -> bool {
+    builder.syntax.set_data(line);
+    
+    // A local record: reserve the memory on the stack and point the variable at it
+    if line.args.len() == 0 && line.stmt_type == AstStmtType::VarDec {
+        let name = match &var.data_type {
+            DataType::Record(name) => name.clone(),
+            _ => return false,
+        };
+        
+        builder.stack_pos += record_size(builder, &name);
+        
+        let mut instr = ltac::create_instr(LtacType::LdAddr);
+        instr.arg1 = LtacArg::Reg64(0);
+        instr.arg2 = LtacArg::Mem(builder.stack_pos);
+        builder.file.code.push(instr);
+        
+        instr = ltac::create_instr(LtacType::MovQ);
+        instr.arg1 = var.mem();
+        instr.arg2 = LtacArg::Reg64(0);
+        builder.file.code.push(instr);
+        
+        return true;
+    }
+    
+    if line.args.len() != 1 {
+        builder.syntax.ltac_error(line, "Records can only be assigned a record or an address.".to_string()).code(E_TYPE_MISMATCH);
+        return false;
+    }
+    
+    let arg = line.args.first().unwrap();
+    let mut instr = ltac::create_instr(LtacType::MovQ);
+    instr.arg1 = var.mem();
+    
+    match &arg.arg_type {
+        // Another record or an address stored in a 64-bit variable
+        AstArgType::Id if builder.var_exists(&arg.str_val) => {
+            let v = builder.get_var(&arg.str_val).unwrap().clone();
+            
+            if v.data_type != var.data_type && v.data_type != DataType::Int64
+                && v.data_type != DataType::UInt64 {
+                builder.syntax.ltac_error(line, "Invalid record assignment.".to_string());
+                return false;
+            }
+            
+            let mut instr2 = ltac::create_instr(LtacType::MovQ);
+            instr2.arg1 = LtacArg::Reg64(0);
+            instr2.arg2 = v.mem();
+            builder.file.code.push(instr2);
+            
+            instr.arg2 = LtacArg::Reg64(0);
+        },
+        
+        // A function returning an address (such as malloc)
+        AstArgType::Id if builder.function_exists(&arg.str_val) => {
+            let t = builder.get_function(&arg.str_val).unwrap().clone();
+            
+            if t != DataType::Int64 && t != DataType::UInt64 {
+                builder.syntax.ltac_error(line, "Records can only be assigned from functions returning an address.".to_string()).code(E_TYPE_MISMATCH);
+                return false;
+            }
+            
+            let mut stmt = ast::create_orphan_stmt(AstStmtType::FuncCall);
+            stmt.name = arg.str_val.clone();
+            stmt.args = arg.sub_args.clone();
+            
+            if !build_func_call(builder, &stmt) {
+                return false;
+            }
+            
+            instr.arg2 = LtacArg::RetRegI64;
+        },
+        
+        _ => {
+            builder.syntax.ltac_error(line, "Records can only be assigned a record or an address.".to_string()).code(E_TYPE_MISMATCH);
+            return false;
+        },
+    }
+    
+    builder.file.code.push(instr);
+    
+    true
+}
+
+// Builds an assignment to a record field
+// The expression is built against a scratch variable of the field type, and then
+// the final store is redirected to the field
+pub fn build_field_assign(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
+    builder.syntax.set_data(line);
+    
+    let mut field = ast::create_arg(AstArgType::Field);
+    field.str_val = line.name.clone();
+    field.sub_args = line.sub_args.clone();
+    
+    let (var, offset, data_type) = match get_field(builder, &field) {
+        Ok(f) => f,
+        Err(_e) => return false,
+    };
+    
+    builder.stack_pos += size_for_type(&data_type);
+    
+    let scratch = Var {
+        pos : builder.stack_pos,
+        data_type : data_type.clone(),
+        sub_type : DataType::None,
+        is_param : false,
+        global : String::new(),
+    };
+    
+    let mut stmt = line.clone();
+    stmt.sub_args = Vec::new();
+    
+    let code : bool;
+    
+    if data_type == DataType::Str {
+        code = build_str_assign(builder, &stmt, &scratch);
+    } else {
+        code = build_var_math(builder, &stmt, &scratch);
+    }
+    
+    if !code {
+        return false;
+    }
+    
+    let mut store = builder.file.code.pop().unwrap();
+    store.arg1 = LtacArg::MemOffsetImm(var.pos, offset);
+    
+    // Addresses have to go through a register so the store is the full 8 bytes
+    if data_type == DataType::Str && store.arg2 != LtacArg::Reg64(0) {
+        let mut instr = ltac::create_instr(LtacType::MovQ);
+        instr.arg1 = LtacArg::Reg64(0);
+        instr.arg2 = store.arg2.clone();
+        builder.file.code.push(instr);
+        
+        store.arg2 = LtacArg::Reg64(0);
+    }
+    
+    builder.file.code.push(store);
+    
+    true
+}
+
+// Loads a record field within an expression
+pub fn build_expr_field(builder : &mut LtacBuilder, arg : &AstArg, instr : &mut LtacInstr) -> bool {
+    let (var, offset, _) = match get_field(builder, arg) {
+        Ok(f) => f,
+        Err(_e) => return false,
+    };
+    
+    instr.arg2 = LtacArg::MemOffsetImm(var.pos, offset);
+    builder.file.code.push(instr.clone());
+    
+    true
+}
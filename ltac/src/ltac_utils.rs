@@ -49,6 +49,12 @@ pub fn create_label(builder : &mut LtacBuilder) -> String {
     name
 }
 
+// A label that isn't tied to a block
+pub fn local_label(builder : &mut LtacBuilder) -> String {
+    create_label2(builder, false);
+    builder.label_stack.pop().unwrap()
+}
+
 // A utility function to create a top-level label
 pub fn create_top_label(builder : &mut LtacBuilder) {
     let lbl_pos = builder.str_pos.to_string();
@@ -64,11 +70,13 @@ pub fn create_top_label(builder : &mut LtacBuilder) {
 pub fn size_for_type(data_type : &DataType) -> i32 {
     match data_type {
         DataType::Byte | DataType::UByte => 1,
-        DataType::Char => 1,
+        DataType::Char | DataType::Bool => 1,
         DataType::Short | DataType::UShort => 2,
         DataType::Int | DataType::UInt => 4,
         DataType::Int64 | DataType::UInt64 => 8,
         DataType::Str | DataType::Ptr => 8,
+        DataType::Record(_) | DataType::Proc(_) => 8,
+        DataType::Pointer(_) => 8,
         DataType::Float => 4,
         DataType::Double => 8,
         _ => 0,
@@ -83,6 +91,7 @@ pub fn mov_for_type(data_type : &DataType, sub_type : &DataType) -> LtacInstr {
         // Bytes
         DataType::Byte => instr = ltac::create_instr(LtacType::MovB),
         DataType::UByte => instr = ltac::create_instr(LtacType::MovUB),
+        DataType::Bool => instr = ltac::create_instr(LtacType::MovUB),
         
         DataType::Ptr if *sub_type == DataType::Byte => instr = ltac::create_instr(LtacType::MovB),
         DataType::Ptr if *sub_type == DataType::UByte => instr = ltac::create_instr(LtacType::MovUB),
@@ -118,8 +127,19 @@ pub fn mov_for_type(data_type : &DataType, sub_type : &DataType) -> LtacInstr {
         // String
         DataType::Char | DataType::Str => instr = ltac::create_instr(LtacType::MovB),
         
+        DataType::Ptr if *sub_type == DataType::Char => instr = ltac::create_instr(LtacType::MovB),
         DataType::Ptr if *sub_type == DataType::Str => instr = ltac::create_instr(LtacType::MovQ),
         
+        // Records
+        DataType::Record(_) => instr = ltac::create_instr(LtacType::MovQ),
+        
+        // Procedures
+        DataType::Proc(_) => instr = ltac::create_instr(LtacType::MovQ),
+        DataType::Ptr if matches!(sub_type, DataType::Proc(_)) => instr = ltac::create_instr(LtacType::MovQ),
+        
+        // Typed pointers
+        DataType::Pointer(_) => instr = ltac::create_instr(LtacType::MovQ),
+        
         _ => {},
     }
     
@@ -134,6 +154,7 @@ pub fn reg_for_type(data_type : &DataType, sub_type : &DataType, reg_no : i32) -
         // Byte
         DataType::Byte => arg = LtacArg::Reg8(reg_no),
         DataType::UByte => arg = LtacArg::Reg8(reg_no),
+        DataType::Bool => arg = LtacArg::Reg8(reg_no),
         
         DataType::Ptr
         if *sub_type == DataType::Byte || *sub_type == DataType::UByte => arg = LtacArg::Reg8(reg_no),
@@ -169,9 +190,22 @@ pub fn reg_for_type(data_type : &DataType, sub_type : &DataType, reg_no : i32) -
         // String
         DataType::Char | DataType::Str => arg = LtacArg::Reg8(reg_no),
         
+        DataType::Ptr
+        if *sub_type == DataType::Char => arg = LtacArg::Reg8(reg_no),
+        
         DataType::Ptr
         if *sub_type == DataType::Str => arg = LtacArg::Reg64(reg_no),
         
+        // Records
+        DataType::Record(_) => arg = LtacArg::Reg64(reg_no),
+        
+        // Procedures
+        DataType::Proc(_) => arg = LtacArg::Reg64(reg_no),
+        DataType::Ptr if matches!(sub_type, DataType::Proc(_)) => arg = LtacArg::Reg64(reg_no),
+        
+        // Typed pointers
+        DataType::Pointer(_) => arg = LtacArg::Reg64(reg_no),
+        
         _ => {},
     }
     
@@ -185,6 +219,7 @@ pub fn ldarg_for_type(data_type : &DataType, dest : LtacArg, pos : i32) -> LtacI
     match data_type {
         DataType::Byte => arg = ltac::create_instr(LtacType::LdArgI8),
         DataType::UByte => arg = ltac::create_instr(LtacType::LdArgU8),
+        DataType::Bool => arg = ltac::create_instr(LtacType::LdArgU8),
         
         DataType::Short => arg = ltac::create_instr(LtacType::LdArgI16),
         DataType::UShort => arg = ltac::create_instr(LtacType::LdArgU16),
@@ -199,6 +234,9 @@ pub fn ldarg_for_type(data_type : &DataType, dest : LtacArg, pos : i32) -> LtacI
         DataType::Double => arg = ltac::create_instr(LtacType::LdArgF64),
         
         DataType::Ptr | DataType::Str => arg = ltac::create_instr(LtacType::LdArgPtr),
+        DataType::Record(_) => arg = ltac::create_instr(LtacType::LdArgPtr),
+        DataType::Proc(_) => arg = ltac::create_instr(LtacType::LdArgPtr),
+        DataType::Pointer(_) => arg = ltac::create_instr(LtacType::LdArgPtr),
         
         _ => return arg,
     }
@@ -19,12 +19,15 @@
 use crate::ltac_builder::*;
 use crate::ast;
 use crate::ast::*;
+use crate::syntax::*;
 use crate::ltac;
 use crate::ltac::{LtacInstr, LtacType, LtacArg};
 
 use crate::ltac_expr::*;
 use crate::ltac_array::*;
 use crate::ltac_func::*;
+use crate::ltac_flow::build_bool_assign;
+use crate::ltac_record::*;
 use crate::ltac_utils::*;
 
 // Builds an LTAC variable declaration
@@ -44,9 +47,9 @@ pub fn build_var_dec(builder : &mut LtacBuilder, line : &AstStmt, arg_no_o : i32
         DataType::Byte | DataType::UByte => builder.stack_pos += 1,
         
         DataType::Short | DataType::UShort => builder.stack_pos += 2,
-    
+        
         DataType::Int | DataType::UInt => builder.stack_pos += 4,
-
+        
         DataType::Int64 | DataType::UInt64 => builder.stack_pos += 8,
         
         DataType::Float => builder.stack_pos += 4,
@@ -54,12 +57,22 @@ pub fn build_var_dec(builder : &mut LtacBuilder, line : &AstStmt, arg_no_o : i32
         
         DataType::Char => builder.stack_pos += 1,
         DataType::Str => builder.stack_pos += 8,
+        DataType::Bool => builder.stack_pos += 1,
         
         DataType::Ptr => builder.stack_pos += 12,
         
         // TODO: We will need better type detection
         DataType::Enum(_) => builder.stack_pos += 4,
         
+        // Records are pointers to their memory
+        DataType::Record(_) => builder.stack_pos += 8,
+        
+        // Procedures hold the address of a function
+        DataType::Proc(_) => builder.stack_pos += 8,
+        
+        // Typed pointers are a single address, without a size
+        DataType::Pointer(_) => builder.stack_pos += 8,
+        
         DataType::Void => {},
         
         // Do we need an error here? Really, it should never get to this pointer
@@ -76,15 +89,19 @@ pub fn build_var_dec(builder : &mut LtacBuilder, line : &AstStmt, arg_no_o : i32
         data_type : data_type,
         sub_type : sub_type,
         is_param : is_param,
+        global : String::new(),
     };
     
-    builder.vars.insert(name, v);
+    builder.add_var(name, v);
     
     // If we have a function argument, add the load instruction
     if is_param {
         let data_type = line.data_type.clone();
         let mem = LtacArg::Mem(builder.stack_pos);
-        let ld : LtacInstr;
+        let mut ld : LtacInstr;
+        
+        // The position among all the arguments, for those passed on the stack
+        let call_pos = arg_no + flt_arg_no - 1;
         
         if data_type == DataType::Float || data_type == DataType::Double {
             ld = ldarg_for_type(&data_type, mem, flt_arg_no);
@@ -97,6 +114,7 @@ pub fn build_var_dec(builder : &mut LtacBuilder, line : &AstStmt, arg_no_o : i32
             if data_type == DataType::Ptr {
                 let mut arg2 = ltac::create_instr(LtacType::LdArgI32);
                 arg2.arg1 = LtacArg::Mem(builder.stack_pos - 8);
+                arg2.arg1_val = call_pos + 1;
                 arg2.arg2_val = arg_no;
                 builder.file.code.push(arg2);
                 
@@ -104,6 +122,7 @@ pub fn build_var_dec(builder : &mut LtacBuilder, line : &AstStmt, arg_no_o : i32
             }
         }
         
+        ld.arg1_val = call_pos;
         builder.file.code.push(ld);
     } else {
         if !build_var_assign(builder, line) {
@@ -120,7 +139,7 @@ pub fn build_var_assign(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
     match builder.vars.get(&line.name) {
         Some(v) => var = v.clone(),
         None => {
-            builder.syntax.ltac_error(line, "Invalid variable.".to_string());
+            builder.syntax.ltac_error(line, "Invalid variable.".to_string()).code(E_UNKNOWN_NAME);
             return false;
         },
     }
@@ -129,8 +148,12 @@ pub fn build_var_assign(builder : &mut LtacBuilder, line : &AstStmt) -> bool {
     
     if var.data_type == DataType::Ptr {
         code = build_dyn_array(builder, &line, &var);
+    } else if let DataType::Record(_) = var.data_type {
+        code = build_record_assign(builder, &line, &var);
     } else if var.data_type == DataType::Str {
         code = build_str_assign(builder, &line, &var);
+    } else if var.data_type == DataType::Bool && (ast::is_cond(&line.args) || line.args[0].arg_type == AstArgType::BoolL) {
+        code = build_bool_assign(builder, &line, &var);
     } else {
         code = build_var_math(builder, &line, &var);
     }
@@ -146,7 +169,7 @@ pub fn build_str_assign(builder : &mut LtacBuilder, line : &AstStmt, var : &Var)
     if line.args.len() == 1 {
         let arg = line.args.first().unwrap();
         
-        instr.arg1 = LtacArg::Mem(var.pos);
+        instr.arg1 = var.mem();
         
         match &arg.arg_type {
             AstArgType::StringL => {
@@ -156,33 +179,46 @@ pub fn build_str_assign(builder : &mut LtacBuilder, line : &AstStmt, var : &Var)
             
             // Build an ID value based on a variable
             AstArgType::Id if builder.var_exists(&arg.str_val) => {
-                let v = match &builder.get_var(&arg.str_val) {
+                let v = match builder.get_var(&arg.str_val) {
+                    Ok(v) if arg.sub_args.len() > 0 => v.elements(),
                     Ok(v) => v.clone(),
                     Err(_e) => return false,
                 };
-            
+                
                 if v.data_type != DataType::Str && v.sub_type != DataType::Str
                         && v.sub_type != DataType::Byte && v.sub_type != DataType::UByte {
-                    builder.syntax.ltac_error(line, "You can only assign a string to a string.".to_string());
+                    builder.syntax.ltac_error(line, "You can only assign a string to a string.".to_string()).code(E_TYPE_MISMATCH);
                     return false;
                 } else if v.data_type == DataType::Ptr && v.sub_type == DataType::Str {
                     let mut instr2 = ltac::create_instr(LtacType::MovQ);
                     instr2.arg1 = LtacArg::Reg64(0);
-                    instr2.arg2 = LtacArg::Mem(v.pos);
+                    instr2.arg2 = v.mem();
                     
                     if arg.sub_args.len() > 0 {
                         let first_arg = arg.sub_args.last().unwrap();
                         let size = 8;
-                    
+                        
                         if arg.sub_args.len() == 1 {
                             if first_arg.arg_type == AstArgType::IntL {
                                 let offset = (first_arg.u64_val as i32) * size;
                                 instr2.arg2 = LtacArg::MemOffsetImm(v.pos, offset);
+                                
+                                let check = bounds_check(builder, &arg.str_val, LtacArg::I32(first_arg.u64_val as i32), 0);
+                                builder.file.code.extend(check);
                             } else if first_arg.arg_type == AstArgType::Id {
                                 match &builder.get_var(&first_arg.str_val) {
-                                    Ok(v2) => instr2.arg2 = LtacArg::MemOffsetMem(v.pos, v2.pos, size),
+                                    Ok(v2) if v2.is_global() => {
+                                        builder.syntax.ltac_error2("Global variables cannot be used as array indexes.".to_string()).code(E_GLOBAL_INDEX);
+                                        return false;
+                                    },
+                                    Ok(v2) => {
+                                        instr2.arg2 = LtacArg::MemOffsetMem(v.pos, v2.pos, size);
+                                        
+                                        let check = bounds_check(builder, &arg.str_val, LtacArg::Mem(v2.pos), 0);
+                                        builder.file.code.extend(check);
+                                    },
                                     Err(_e) => {
-                                        builder.syntax.ltac_error2("Invalid offset variable.".to_string());
+                                        builder.syntax.ltac_error2("Invalid offset variable.".to_string()).code(E_UNKNOWN_NAME);
                                         return false;
                                     },
                                 };
@@ -194,7 +230,7 @@ pub fn build_str_assign(builder : &mut LtacBuilder, line : &AstStmt, var : &Var)
                 } else {
                     let mut instr2 = ltac::create_instr(LtacType::MovQ);
                     instr2.arg1 = LtacArg::Reg64(0);
-                    instr2.arg2 = LtacArg::Mem(v.pos);
+                    instr2.arg2 = v.mem();
                     builder.file.code.push(instr2);
                 }
                 
@@ -206,7 +242,7 @@ pub fn build_str_assign(builder : &mut LtacBuilder, line : &AstStmt, var : &Var)
                     Some(t) => {
                         // TODO: Better detection with whether its byte or ubyte
                         if **t != DataType::Str && **t != DataType::Ptr {
-                            builder.syntax.ltac_error(line, "You can only assign string or byte arrays to string variables.".to_string());
+                            builder.syntax.ltac_error(line, "You can only assign string or byte arrays to string variables.".to_string()).code(E_TYPE_MISMATCH);
                             return false;
                         }
                         
@@ -220,14 +256,14 @@ pub fn build_str_assign(builder : &mut LtacBuilder, line : &AstStmt, var : &Var)
                     },
                     
                     None => {
-                        builder.syntax.ltac_error(line, "Invalid string variable.".to_string());
+                        builder.syntax.ltac_error(line, "Invalid string variable.".to_string()).code(E_UNKNOWN_NAME);
                         return false;
                     },
                 }
             },
             
             _ => {
-                builder.syntax.ltac_error(line, "Invalid string assignment.".to_string());
+                builder.syntax.ltac_error(line, "Invalid string assignment.".to_string()).code(E_TYPE_MISMATCH);
                 return false;
             },
         }
@@ -240,4 +276,4 @@ pub fn build_str_assign(builder : &mut LtacBuilder, line : &AstStmt, var : &Var)
     true
 }
 
-    
+
@@ -27,7 +27,7 @@ fn is_move(instr : &LtacType) -> bool {
         LtacType::MovQ | LtacType::MovUQ |
         LtacType::MovF32 | LtacType::MovF64
             => return true,
-            
+        
         _ => return false,
     }
 }
@@ -35,6 +35,7 @@ fn is_move(instr : &LtacType) -> bool {
 fn has_mem(arg : &LtacArg) -> bool {
     match arg {
         LtacArg::Mem(_n) => return true,
+        LtacArg::GlobalMem(_n) => return true,
         LtacArg::MemOffsetImm(_n1, _n2) => return true,
         LtacArg::MemOffsetMem(_n1, _n2, _n3) |
         LtacArg::MemOffsetReg(_n1, _n2, _n3) => return true,
@@ -116,13 +117,16 @@ fn reg_for_mov(instr : &LtacType, pos : i32) -> LtacArg {
 pub fn risc_optimize(file : &LtacFile) -> Result<LtacFile, ()> {
     let mut file2 = LtacFile {
         name : file.name.clone(),
+        source : file.source.clone(),
         data : file.data.clone(),
         code : Vec::new(),
+        debug : file.debug.clone(),
     };
     
     let code = file.code.clone();
     
     for line in code.iter() {
+        let start = file2.code.len();
         let mut instr2 = line.clone();
         
         if is_move(&line.instr_type) {
@@ -163,6 +167,8 @@ pub fn risc_optimize(file : &LtacFile) -> Result<LtacFile, ()> {
             
             file2.code.push(instr2);
         }
+        
+        ltac::set_line_no(&mut file2.code, start, line.line_no);
     }
     
     Ok(file2)
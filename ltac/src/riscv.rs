@@ -25,8 +25,10 @@ use crate::ltac::{LtacFile, LtacType, LtacArg};
 pub fn riscv_optimize(file : &LtacFile) -> Result<LtacFile, ()> {
     let mut file2 = LtacFile {
         name : file.name.clone(),
+        source : file.source.clone(),
         data : file.data.clone(),
         code : Vec::new(),
+        debug : file.debug.clone(),
     };
     
     let code = file.code.clone();
@@ -40,77 +42,32 @@ pub fn riscv_optimize(file : &LtacFile) -> Result<LtacFile, ()> {
         
         let line = code.iter().nth(index).unwrap();
         
-        let mut instr2 = line.clone();
-        let mut arg_count = 1;
+        let instr2 = line.clone();
         
-        // If we are making a printf call with a float argument, we have to do some
-        // special conversions.
+        // Floats passed to variadic functions (such as printf) go in the integer registers.
+        // The builder has already promoted them to double, and given us the argument position.
         if instr2.instr_type == LtacType::PushArg {
-            let flt_arg : bool;
-            
-            match &instr2.arg2 {
-                LtacArg::FltReg(_) => flt_arg = true,
-                LtacArg::FltReg64(_) => flt_arg = false,
+            let pos = match &instr2.arg1 {
+                LtacArg::FltReg64(pos) => *pos,
                 _ => {
-                    arg_count += 1;
-                    flt_arg = false;
+                    file2.code.push(instr2);
+                    continue;
                 },
-            }
-            
-            if !flt_arg {
-                file2.code.push(instr2);
-                continue;
-            }
-            
-            let mut function_name = String::new();
-            
-            for i in index .. code.len() {
-                let current = code.iter().nth(i).unwrap();
-                
-                if current.instr_type == LtacType::Call {
-                    function_name = current.name.clone();
-                    break;
-                } else if current.instr_type == LtacType::PushArg {
-                    match &current.arg2 {
-                        LtacArg::FltReg(_) | LtacArg::FltReg64(_) => {},
-                        _ => arg_count += 1,
-                    }
-                }
-            }
-            
-            if function_name != "printf" {
-                file2.code.push(instr2);
-                continue;
-            }
-            
-            // If we make it this far, we have found a printf call
-            // First, convert the pusharg to a regular f32.ld
-            instr2.instr_type = LtacType::LdF32;
-            let pos = match &instr2.arg2 {
-                LtacArg::FltReg(pos) => *pos,
-                _ => 0,
             };
             
-            file2.code.push(instr2);
-            
-            // Now, convert to a double
-            let mut cvt_instr = ltac::create_instr(LtacType::CvtF32F64);
-            cvt_instr.arg1 = LtacArg::FltReg64(pos);
-            cvt_instr.arg2 = LtacArg::FltReg(pos);
-            file2.code.push(cvt_instr);
-            
-            // Now, move it to an integer register
+            // Move it to an integer register
             let mut mv_instr = ltac::create_instr(LtacType::MovF64Int);
             mv_instr.arg1 = LtacArg::Reg32(0);
             mv_instr.arg2 = LtacArg::FltReg64(pos);
             file2.code.push(mv_instr);
             
-            // Finally, create the pusharg
+            // Then, create the pusharg
             let mut pusharg = ltac::create_instr(LtacType::PushArg);
             pusharg.arg1 = LtacArg::Reg32(0);
-            pusharg.arg2_val = arg_count + 1;
+            pusharg.arg1_val = instr2.arg1_val;
+            pusharg.arg2_val = instr2.arg1_val;
             file2.code.push(pusharg);
-            
+        
         // For some odd reason, you cannot move between float registers on RISC-V
         } else if instr2.instr_type == LtacType::MovF32 {
             match &instr2.arg2 {
@@ -126,6 +83,8 @@ pub fn riscv_optimize(file : &LtacFile) -> Result<LtacFile, ()> {
             
             if next_instr.instr_type == LtacType::StrF32 || next_instr.instr_type == LtacType::StrF64 {
                 next_instr.arg2 = instr2.arg1.clone();
+            } else if next_instr.instr_type == LtacType::CvtF32F64 {
+                next_instr.arg2 = instr2.arg2.clone();
             } else {
                 next_instr.arg1 = instr2.arg2.clone();
             }
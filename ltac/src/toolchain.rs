@@ -0,0 +1,266 @@
+
+// This file is part of the Lila compiler
+// Copyright (C) 2020-2021 Patrick Flynn
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; version 2.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, write to the Free Software Foundation, Inc.,
+// 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+
+// The external tools we assemble and link with
+// By default, we call as and ld directly. With --cc, we link through the C compiler
+// driver instead, which knows where the start files and the dynamic linker are.
+
+use std::env;
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+use std::process::Command;
+
+// What the linker needs to know about a target
+pub struct TargetInfo {
+    pub prefixes : &'static [&'static str],     // The prefixes of the cross tools, such as aarch64-linux-gnu
+    pub crt_dir : &'static str,                 // Where the C start files are
+    pub dynamic_linker : &'static str,
+    pub has_runtime : bool,                     // Whether lrt.o and the core library exist for this target
+}
+
+pub struct Toolchain {
+    pub assembler : String,
+    pub linker : String,
+    pub cc : String,
+    pub use_cc : bool,                          // Link with cc instead of ld
+    pub dynamic_linker : Option<String>,        // Overrides the target's dynamic linker
+    pub runtime : String,                       // The Lila start code
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToolError {
+    Missing,            // The tool could not be run at all
+    Failed,             // The tool ran, and reported errors
+}
+
+// The objects and libraries to link
+pub struct LinkJob {
+    pub objects : Vec<String>,
+    pub libs : Vec<String>,
+    pub output : String,
+    pub use_c : bool,
+    pub use_corelib : bool,
+    pub is_lib : bool,
+    pub inc_start : bool,
+}
+
+// Picks the tools for a target
+// For a cross target, we use the first prefix which has an assembler installed. If none
+// do, we still use the first one, so the error names the tool to install.
+pub fn detect(target : &TargetInfo, cross : bool) -> Toolchain {
+    let mut prefix = String::new();
+    
+    if cross && target.prefixes.len() > 0 {
+        let found = target.prefixes.iter().find(|p| in_path(&format!("{}-as", p)));
+        
+        prefix = match found {
+            Some(p) => format!("{}-", p),
+            None => format!("{}-", target.prefixes[0]),
+        };
+    }
+    
+    let cc = if cross { format!("{}gcc", prefix) } else { "cc".to_string() };
+    
+    Toolchain {
+        assembler : format!("{}as", prefix),
+        linker : format!("{}ld", prefix),
+        cc : cc,
+        use_cc : false,
+        dynamic_linker : None,
+        runtime : "/usr/lib/lila/lrt.o".to_string(),
+    }
+}
+
+// Checks whether a program can be found on the PATH
+fn in_path(name : &str) -> bool {
+    if name.contains('/') {
+        return Path::new(name).is_file();
+    }
+    
+    match env::var_os("PATH") {
+        Some(paths) => env::split_paths(&paths).any(|dir| dir.join(name).is_file()),
+        None => false,
+    }
+}
+
+impl Toolchain {
+    // Assembles one file
+    pub fn assemble(&self, asm_name : &String, obj_name : &String) -> Result<(), ToolError> {
+        let args = vec![asm_name.clone(), "-o".to_string(), obj_name.clone()];
+        self.run("assembler", "--as", &self.assembler, &args)
+    }
+    
+    // Links a program or a library
+    pub fn link(&self, target : &TargetInfo, job : &LinkJob) -> Result<(), ToolError> {
+        if self.use_cc {
+            let args = self.cc_args(target, job);
+            return self.run("C compiler", "--cc", &self.cc, &args);
+        }
+        
+        let mut args : Vec<String> = Vec::new();
+        args.push("-L./".to_string());
+        
+        // The C runtime provides its own start code
+        if job.use_c {
+            if !job.is_lib {
+                args.push(format!("{}/crt1.o", target.crt_dir));
+                args.push(format!("{}/crti.o", target.crt_dir));
+            }
+            
+            args.push("-lc".to_string());
+        } else if !job.is_lib && job.inc_start && target.has_runtime {
+            args.push(self.runtime.clone());
+        }
+        
+        args.push("-dynamic-linker".to_string());
+        
+        match &self.dynamic_linker {
+            Some(path) => args.push(path.clone()),
+            None => args.push(target.dynamic_linker.to_string()),
+        }
+        
+        args.extend(job.objects.iter().cloned());
+        
+        if job.is_lib {
+            args.push("-shared".to_string());
+        }
+        
+        args.extend(job.libs.iter().cloned());
+        args.push("-o".to_string());
+        args.push(job.output.clone());
+        
+        if job.use_corelib && target.has_runtime {
+            args.push("-llila".to_string());
+            args.push("-llila_core".to_string());
+        }
+        
+        if job.use_c && !job.is_lib {
+            args.push(format!("{}/crtn.o", target.crt_dir));
+        }
+        
+        self.run("linker", "--ld", &self.linker, &args)
+    }
+    
+    // The arguments for linking through the C compiler driver
+    // Without --use-c, we leave out the C library and start files like we do with ld
+    fn cc_args(&self, target : &TargetInfo, job : &LinkJob) -> Vec<String> {
+        let mut args : Vec<String> = Vec::new();
+        args.push("-L./".to_string());
+        
+        if job.is_lib {
+            args.push("-shared".to_string());
+        } else {
+            args.push("-no-pie".to_string());
+        }
+        
+        if !job.use_c {
+            args.push("-nostdlib".to_string());
+            
+            if !job.is_lib && job.inc_start && target.has_runtime {
+                args.push(self.runtime.clone());
+            }
+        } else {
+            // The C library has to come before ours, so that calls like printf go to it
+            args.push("-lc".to_string());
+        }
+        
+        if let Some(path) = &self.dynamic_linker {
+            args.push(format!("-Wl,-dynamic-linker,{}", path));
+        }
+        
+        args.extend(job.objects.iter().cloned());
+        args.extend(job.libs.iter().cloned());
+        args.push("-o".to_string());
+        args.push(job.output.clone());
+        
+        if job.use_corelib && target.has_runtime {
+            args.push("-llila".to_string());
+            args.push("-llila_core".to_string());
+        }
+        
+        args
+    }
+    
+    // Runs a tool, and prints what went wrong if it fails
+    // The command may have arguments of its own, such as "clang -c"
+    fn run(&self, what : &str, flag : &str, command : &String, args : &Vec<String>) -> Result<(), ToolError> {
+        let mut parts = command.split_whitespace();
+        
+        let program = match parts.next() {
+            Some(program) => program,
+            None => {
+                println!("Fatal: The {} command is empty.", what);
+                return Err(ToolError::Missing);
+            },
+        };
+        
+        let output = match Command::new(program).args(parts).args(args).output() {
+            Ok(output) => output,
+            Err(e) => {
+                if e.kind() == io::ErrorKind::NotFound {
+                    println!("Fatal: Could not find the {} \"{}\".", what, program);
+                    println!("Install it, or choose another one with {}=<command>.", flag);
+                } else {
+                    println!("Fatal: Could not run the {} \"{}\": {}", what, program, e);
+                }
+                
+                return Err(ToolError::Missing);
+            },
+        };
+        
+        if !output.status.success() {
+            io::stdout().write_all(&output.stdout).unwrap();
+            io::stderr().write_all(&output.stderr).unwrap();
+            
+            match output.status.code() {
+                Some(code) => println!("Fatal: The {} \"{}\" failed with exit code {}.", what, program, code),
+                None => println!("Fatal: The {} \"{}\" was killed.", what, program),
+            }
+            
+            return Err(ToolError::Failed);
+        }
+        
+        Ok(())
+    }
+}
+
+impl LinkJob {
+    // The -l libraries go after the objects
+    pub fn new(all_names : &Vec<String>, output : &String) -> LinkJob {
+        let mut objects : Vec<String> = Vec::new();
+        let mut libs : Vec<String> = Vec::new();
+        
+        for name in all_names.iter() {
+            if name.starts_with("-l") {
+                libs.push(name.clone());
+            } else {
+                objects.push(name.clone());
+            }
+        }
+        
+        LinkJob {
+            objects : objects,
+            libs : libs,
+            output : output.clone(),
+            use_c : false,
+            use_corelib : false,
+            is_lib : false,
+            inc_start : false,
+        }
+    }
+}
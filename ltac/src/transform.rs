@@ -20,31 +20,27 @@ use std::collections::HashMap;
 
 use crate::ltac;
 use crate::ltac::{LtacFile, LtacType, LtacArg};
-use parser::Arch;
 
 use crate::risc::*;
 use crate::riscv::*;
-
-// Architectures
-// 1-> x86-64
-// 2-> AArch64
-// 3-> Riscv64
+use crate::backend::Pass;
 
 // The main transformation function
-pub fn run(file : &LtacFile, arch : Arch, use_c : bool, risc_mode : bool) -> Result<LtacFile, ()> {
+// The builtins are always lowered; the other passes are the ones the backend asks for
+pub fn run(file : &LtacFile, use_c : bool, passes : &Vec<Pass>) -> Result<LtacFile, ()> {
     let mut file2 = match check_builtins(file, use_c) {
         Ok(ltac) => ltac,
         Err(_e) => return Err(()),
     };
     
-    if risc_mode || arch == Arch::AArch64 || arch == Arch::Riscv64 {
+    if passes.contains(&Pass::Risc) {
         file2 = match risc_optimize(&file2) {
             Ok(ltac) => ltac,
             Err(_e) => return Err(()),
         }
     }
     
-    if arch == Arch::Riscv64 {
+    if passes.contains(&Pass::Riscv) {
         file2 = match riscv_optimize(&file2) {
             Ok(ltac) => ltac,
             Err(_e) => return Err(()),
@@ -60,8 +56,10 @@ pub fn run(file : &LtacFile, arch : Arch, use_c : bool, risc_mode : bool) -> Res
 fn check_builtins(file : &LtacFile, use_c : bool) -> Result<LtacFile, ()> {
     let mut file2 = LtacFile {
         name : file.name.clone(),
+        source : file.source.clone(),
         data : file.data.clone(),
         code : Vec::new(),
+        debug : file.debug.clone(),
     };
     
     let code = file.code.clone();
@@ -70,6 +68,8 @@ fn check_builtins(file : &LtacFile, use_c : bool) -> Result<LtacFile, ()> {
     let mut mm_map : HashMap<i32, i32> = HashMap::new();
     
     for line in code.iter() {
+        let start = file2.code.len();
+        
         match &line.instr_type {
             
             // We have a separate exit type for two reasons
@@ -80,7 +80,7 @@ fn check_builtins(file : &LtacFile, use_c : bool) -> Result<LtacFile, ()> {
                 instr.arg1 = line.arg1.clone();
                 instr.arg2_val = 1;
                 file2.code.push(instr);
-                    
+                
                 if use_c {
                     instr = ltac::create_instr(LtacType::Call);
                     instr.name = "exit".to_string();
@@ -91,7 +91,7 @@ fn check_builtins(file : &LtacFile, use_c : bool) -> Result<LtacFile, ()> {
                     file2.code.push(instr);
                 }
             },
-        
+            
             LtacType::Malloc => {
                 if use_c {
                     let mut instr = ltac::create_instr(LtacType::Call);
@@ -167,6 +167,7 @@ fn check_builtins(file : &LtacFile, use_c : bool) -> Result<LtacFile, ()> {
             _ => file2.code.push(line.clone()),
         }
         
+        ltac::set_line_no(&mut file2.code, start, line.line_no);
         index += 1;
     }
     
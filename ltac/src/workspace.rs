@@ -0,0 +1,72 @@
+
+// This file is part of the Lila compiler
+// Copyright (C) 2020-2021 Patrick Flynn
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; version 2.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, write to the Free Software Foundation, Inc.,
+// 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+
+// The temporary directory for one run of the compiler
+// Each unit is written here as <name>.asm and <name>.o (plus <name>.ltac and so on when
+// asked for), where the name comes from unit_name(). Every run gets its own directory, so two builds can't clobber each
+// other's files. The directory is removed when we are done, unless we were told to keep it.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process;
+
+pub struct Workspace {
+    pub dir : PathBuf,
+    pub keep : bool,            // --save-temps
+}
+
+impl Workspace {
+    // Creates a new, empty directory under the system temp directory
+    pub fn create(keep : bool) -> io::Result<Workspace> {
+        let base = env::temp_dir();
+        let pid = process::id();
+        let mut n = 0;
+        
+        loop {
+            let dir = base.join(format!("lilac-{}-{}", pid, n));
+            
+            match fs::create_dir(&dir) {
+                Ok(()) => return Ok(Workspace { dir : dir, keep : keep }),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => n += 1,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    
+    // The path of a file for a unit, such as file("hello", "asm")
+    pub fn file(&self, name : &str, ext : &str) -> String {
+        self.dir.join(format!("{}.{}", name, ext)).to_string_lossy().into_owned()
+    }
+}
+
+// The name of a unit's files in the workspace
+// Two sources can have the same file name (a/x.ls and b/x.ls), so the unit's index goes first
+pub fn unit_name(index : usize, name : &str) -> String {
+    format!("{}-{}", index, name)
+}
+
+impl Drop for Workspace {
+    fn drop(&mut self) {
+        if self.keep {
+            println!("Note: The temporary files were kept in {}", self.dir.display());
+        } else {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+}
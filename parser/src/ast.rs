@@ -17,6 +17,7 @@
 
 use std::collections::HashMap;
 
+use crate::ast_expr::AstExpr;
 use crate::lex::*;
 use crate::Arch;
 
@@ -26,6 +27,7 @@ pub enum AstStmtType {
     VarDec,
     VarAssign,
     ArrayAssign,
+    FieldAssign,
     If,
     Elif,
     Else,
@@ -33,6 +35,8 @@ pub enum AstStmtType {
     For,
     Break,
     Continue,
+    Case,
+    When,
     FuncCall,
     Return,
     Exit,
@@ -49,9 +53,12 @@ pub enum AstArgType {
     FloatL,
     CharL,
     StringL,
+    BoolL,
     Id,
+    Field,
     Sizeof,
     AddrOf,
+    Null,
     OpRParen,
     OpLParen,
     OpNeg,
@@ -73,6 +80,11 @@ pub enum AstArgType {
     OpLeftShift,
     OpRightShift,
     Range,
+    And,
+    Or,
+    Not,
+    Expr,       // A compound value; the sub-arguments hold the expression in infix order
+    ArrayL,     // An array literal; each sub-argument is an Expr holding one value
 }
 
 // Represents our data types
@@ -92,8 +104,12 @@ pub enum DataType {
     Double,
     Char,
     Str,
+    Bool,
     Ptr,
     Enum(String),
+    Record(String),
+    Proc(String),       // Address of a function matching the named procedure type
+    Pointer(Box<DataType>),     // Typed pointer; the box holds the type pointed to
 }
 
 // Represents the top of an AST tree
@@ -103,6 +119,9 @@ pub struct AstTree {
     pub module : String,
     pub functions : Vec<AstFunc>,
     pub constants : Vec<AstConst>,
+    pub globals : Vec<AstGlobal>,
+    pub records : Vec<AstRecord>,
+    pub procs : Vec<AstProc>,
 }
 
 // Represents a function in a tree
@@ -113,7 +132,10 @@ pub struct AstFunc {
     pub args : Vec<AstStmt>,
     pub enums : Vec<AstEnum>,
     pub data_type : DataType,
+    pub is_variadic : bool,
+    pub fixed_args : usize,     // Arguments before "..."; any declared after it are optional
     pub line : String,
+    pub line_no : i32,
 }
 
 // Represents a constant
@@ -122,6 +144,21 @@ pub struct AstConst {
     pub name : String,
     pub data_type : DataType,
     pub value : AstArg,
+    pub values : Vec<AstArg>,       // The elements of a constant table; empty for a single value
+    pub negate : bool,              // A negative integer is kept as its magnitude, as a literal after '-' is
+    
+    pub line : String,
+    pub line_no : i32,
+}
+
+// Represents a global variable
+// If the variable is not initialized, the value has an arg type of None
+#[derive(Clone)]
+pub struct AstGlobal {
+    pub name : String,
+    pub data_type : DataType,
+    pub value : AstArg,
+    pub is_extern : bool,
     
     pub line : String,
     pub line_no : i32,
@@ -135,6 +172,25 @@ pub struct AstEnum {
     pub values : HashMap<String, i32>,
 }
 
+// Represents a record type
+// Each field is stored as a variable declaration
+#[derive(Clone)]
+pub struct AstRecord {
+    pub name : String,
+    pub fields : Vec<AstStmt>,
+    pub line : String,
+}
+
+// Represents a procedure type
+// The arguments are stored like function parameters
+#[derive(Clone)]
+pub struct AstProc {
+    pub name : String,
+    pub args : Vec<AstStmt>,
+    pub data_type : DataType,
+    pub line : String,
+}
+
 // Represents a statement
 #[derive(Debug, Clone)]
 pub struct AstStmt {
@@ -145,15 +201,23 @@ pub struct AstStmt {
     pub args : Vec<AstArg>,
     pub sub_block : Vec<AstStmt>,
     
+    // The trees of the arguments and sub-arguments, which the backends build from
+    // The arguments are the same expressions written out, for the type checker
+    pub expr : Option<AstExpr>,
+    pub sub_expr : Option<AstExpr>,
+    
     pub data_type : DataType,
     pub sub_type : DataType,
     
     pub line : String,
     pub line_no : i32,
+    pub col : usize,
 }
 
 // Represents an argument
 // Arguments are constants, variables, operators, etc
+// The data type is filled in by the type checker; operators keep None, and integer
+// literals with a suffix start out with its type
 #[derive(Debug, Clone)]
 pub struct AstArg {
     pub arg_type : AstArgType,
@@ -163,8 +227,10 @@ pub struct AstArg {
     pub u16_val : u16,
     pub u64_val : u64,
     pub f64_val : f64,
+    pub data_type : DataType,
     
     pub sub_args : Vec<AstArg>,
+    pub expr : Option<Box<AstExpr>>,     // The tree of an Expr argument
 }
 
 // Tree implementation
@@ -180,7 +246,19 @@ impl AstTree {
         for constant in self.constants.iter() {
             constant.print(false);
         }
-    
+        
+        for global in self.globals.iter() {
+            global.print();
+        }
+        
+        for record in self.records.iter() {
+            record.print();
+        }
+        
+        for proc_type in self.procs.iter() {
+            proc_type.print();
+        }
+        
         for func in self.functions.iter() {
             func.print();
         }
@@ -199,6 +277,36 @@ impl AstConst {
         print!("{} ", self.name);
         print!("{:?} ", self.data_type);
         
+        if self.values.len() > 0 {
+            print!("[ ");
+            for value in self.values.iter() {
+                value.print();
+            }
+            print!("]");
+        } else {
+            if self.negate {
+                print!("- ");
+            }
+            
+            self.value.print();
+        }
+        
+        println!("");
+    }
+}
+
+// Global variable implementation
+impl AstGlobal {
+    pub fn print(&self) {
+        if self.is_extern {
+            print!("  EXTERN GLOBAL ");
+        } else {
+            print!("  GLOBAL ");
+        }
+        
+        print!("{} ", self.name);
+        print!("{:?} ", self.data_type);
+        
         self.value.print();
         
         println!("");
@@ -219,6 +327,28 @@ impl AstEnum {
     }
 }
 
+// Record implementation
+impl AstRecord {
+    pub fn print(&self) {
+        println!("  RECORD {}", self.name);
+        
+        for field in self.fields.iter() {
+            println!("    FIELD {} {:?}", field.name, field.data_type);
+        }
+    }
+}
+
+// Procedure type implementation
+impl AstProc {
+    pub fn print(&self) {
+        println!("  PROC {} {:?}", self.name, self.data_type);
+        
+        for arg in self.args.iter() {
+            println!("    ARG {} {:?}", arg.name, arg.data_type);
+        }
+    }
+}
+
 // Function implementation
 impl AstFunc {
     pub fn print(&self) {
@@ -263,6 +393,7 @@ impl AstStmt {
             AstStmtType::VarDec => println!("VAR DEC {}", self.name),
             AstStmtType::VarAssign => println!("VAR ASSIGN {}", self.name),
             AstStmtType::ArrayAssign => println!("ARRAY ASSIGN {}", self.name),
+            AstStmtType::FieldAssign => println!("FIELD ASSIGN {}", self.name),
             AstStmtType::If => println!("IF"),
             AstStmtType::Elif => println!("ELIF"),
             AstStmtType::Else => println!("ELSE"),
@@ -270,6 +401,8 @@ impl AstStmt {
             AstStmtType::For => println!("FOR "),
             AstStmtType::Break => println!("BREAK"),
             AstStmtType::Continue => println!("CONTINUE"),
+            AstStmtType::Case => println!("CASE"),
+            AstStmtType::When => println!("WHEN"),
             AstStmtType::FuncCall => println!("FUNC CALL {}", self.name),
             AstStmtType::Return => println!("RETURN"),
             AstStmtType::Exit => println!("EXIT"),
@@ -315,11 +448,14 @@ impl AstArg {
             AstArgType::ShortL => print!("{} ", self.u16_val),
             AstArgType::IntL => print!("{} ", self.u64_val),
             AstArgType::FloatL => print!("{} ", self.f64_val),
-            AstArgType::CharL => print!("\'{}\' ", self.char_val),
-            AstArgType::StringL => print!("\"{}\" ", self.str_val),
+            AstArgType::CharL => print!("{:?} ", self.char_val),
+            AstArgType::StringL => print!("{:?} ", self.str_val),
+            AstArgType::BoolL => print!("{} ", self.u8_val == 1),
             AstArgType::Id => print!("{} ", self.str_val),
+            AstArgType::Field => print!("{}.", self.str_val),
             AstArgType::Sizeof => print!("SIZEOF "),
             AstArgType::AddrOf => print!("ADDR_OF "),
+            AstArgType::Null => print!("NULL "),
             AstArgType::OpLParen => print!("("),
             AstArgType::OpRParen => print!(")"),
             AstArgType::OpNeg => print!("-"),
@@ -341,6 +477,11 @@ impl AstArg {
             AstArgType::OpLeftShift => print!("<< "),
             AstArgType::OpRightShift => print!(">> "),
             AstArgType::Range => print!("RANGE "),
+            AstArgType::And => print!("AND "),
+            AstArgType::Or => print!("OR "),
+            AstArgType::Not => print!("NOT "),
+            AstArgType::Expr => print!("EXPR "),
+            AstArgType::ArrayL => print!("ARRAY "),
         }
         
         if self.sub_args.len() > 0 {
@@ -362,7 +503,10 @@ pub fn create_extern_func(name : String) -> AstFunc {
         args : Vec::new(),
         enums : Vec::new(),
         line : String::new(),
+        line_no : 0,
         data_type : DataType::None,
+        is_variadic : false,
+        fixed_args : 0,
     }
 }
 
@@ -374,7 +518,10 @@ pub fn create_func(name : String) -> AstFunc {
         args : Vec::new(),
         enums : Vec::new(),
         line : String::new(),
+        line_no : 0,
         data_type : DataType::None,
+        is_variadic : false,
+        fixed_args : 0,
     }
 }
 
@@ -387,11 +534,15 @@ pub fn create_stmt(stmt_type : AstStmtType, scanner : &mut Lex) -> AstStmt {
         args : Vec::new(),
         sub_block : Vec::new(),
         
+        expr : None,
+        sub_expr : None,
+        
         data_type : DataType::None,
         sub_type : DataType::None,
         
         line_no : scanner.get_line_no(),
         line : scanner.get_current_line(),
+        col : scanner.get_line_col(),
     }
 }
 
@@ -405,11 +556,15 @@ pub fn create_orphan_stmt(stmt_type : AstStmtType) -> AstStmt {
         args : Vec::new(),
         sub_block : Vec::new(),
         
+        expr : None,
+        sub_expr : None,
+        
         data_type : DataType::None,
         sub_type : DataType::None,
         
         line_no : 0,
         line : String::new(),
+        col : 1,
     }
 }
 
@@ -438,6 +593,36 @@ pub fn enum_exists(tree : &mut AstTree, to_find : String) -> bool {
     false
 }
 
+pub fn global_exists(tree : &AstTree, to_find : &String) -> bool {
+    for g in tree.globals.iter() {
+        if g.name == *to_find {
+            return true;
+        }
+    }
+    
+    false
+}
+
+pub fn record_exists(tree : &AstTree, to_find : &String) -> bool {
+    for r in tree.records.iter() {
+        if r.name == *to_find {
+            return true;
+        }
+    }
+    
+    false
+}
+
+pub fn proc_exists(tree : &AstTree, to_find : &String) -> bool {
+    for p in tree.procs.iter() {
+        if p.name == *to_find {
+            return true;
+        }
+    }
+    
+    false
+}
+
 pub fn create_byte(val : u8) -> AstArg {
     AstArg {
         arg_type : AstArgType::ByteL,
@@ -447,7 +632,9 @@ pub fn create_byte(val : u8) -> AstArg {
         u16_val : 0,
         u64_val : 0,
         f64_val : 0.0,
+        data_type : DataType::None,
         sub_args : Vec::new(),
+        expr : None,
     }
 }
 
@@ -460,7 +647,9 @@ pub fn create_short(val : u16) -> AstArg {
         u16_val : val,
         u64_val : 0,
         f64_val : 0.0,
+        data_type : DataType::None,
         sub_args : Vec::new(),
+        expr : None,
     }
 }
 
@@ -473,10 +662,19 @@ pub fn create_int(val : u64) -> AstArg {
         u16_val : 0,
         u64_val : val,
         f64_val : 0.0,
+        data_type : DataType::None,
         sub_args : Vec::new(),
+        expr : None,
     }
 }
 
+// An integer literal with a type suffix
+pub fn create_typed_int(val : u64, data_type : DataType) -> AstArg {
+    let mut arg = create_int(val);
+    arg.data_type = data_type;
+    arg
+}
+
 pub fn create_float(val : f64) -> AstArg {
     AstArg {
         arg_type : AstArgType::FloatL,
@@ -486,7 +684,9 @@ pub fn create_float(val : f64) -> AstArg {
         u16_val : 0,
         u64_val : 0,
         f64_val : val,
+        data_type : DataType::None,
         sub_args : Vec::new(),
+        expr : None,
     }
 }
 
@@ -499,7 +699,9 @@ pub fn create_char(val : char) -> AstArg {
         u16_val : 0,
         u64_val : 0,
         f64_val : 0.0,
+        data_type : DataType::None,
         sub_args : Vec::new(),
+        expr : None,
     }
 }
 
@@ -512,10 +714,19 @@ pub fn create_string(val : String) -> AstArg {
         u16_val : 0,
         u64_val : 0,
         f64_val : 0.0,
+        data_type : DataType::None,
         sub_args : Vec::new(),
+        expr : None,
     }
 }
 
+// Bools are stored as a byte, with 1 for true
+pub fn create_bool(val : bool) -> AstArg {
+    let mut arg = create_arg(AstArgType::BoolL);
+    arg.u8_val = val as u8;
+    arg
+}
+
 pub fn create_arg(arg_type : AstArgType) -> AstArg {
     AstArg {
         arg_type : arg_type,
@@ -525,7 +736,289 @@ pub fn create_arg(arg_type : AstArgType) -> AstArg {
         u16_val : 0,
         u64_val : 0,
         f64_val : 0.0,
+        data_type : DataType::None,
         sub_args : Vec::new(),
+        expr : None,
+    }
+}
+
+
+// Returns the arms of a case statement in order
+pub fn case_arms(case : &AstStmt) -> Vec<&AstStmt> {
+    let mut arms : Vec<&AstStmt> = Vec::new();
+    let mut next = case.sub_block.last();
+    
+    while let Some(when) = next {
+        if when.stmt_type != AstStmtType::When {
+            break;
+        }
+        
+        arms.push(when);
+        next = when.sub_block.last();
+    }
+    
+    arms
+}
+
+// Returns the body of a case arm, without the next arm or the end
+pub fn arm_body(when : &AstStmt) -> &[AstStmt] {
+    match when.sub_block.last() {
+        Some(last) if last.stmt_type == AstStmtType::When || last.stmt_type == AstStmtType::End => {
+            return &when.sub_block[..when.sub_block.len() - 1];
+        },
+        
+        _ => return &when.sub_block,
+    }
+}
+
+// Resolves the choices of a case arm to a list of (low, high) ranges
+// Single values are ranges of one; enumeration values are looked up in
+// the given table. An empty list is "when others".
+pub fn choice_ranges(when : &AstStmt, values : Option<&HashMap<String, i32>>) -> Result<Vec<(i64, i64)>, String> {
+    let mut ranges : Vec<(i64, i64)> = Vec::new();
+    let mut bounds : Vec<i64> = Vec::new();
+    let mut negate = false;
+    
+    for arg in when.args.iter() {
+        let val = match arg.arg_type {
+            AstArgType::ByteL => arg.u8_val as i64,
+            AstArgType::ShortL => arg.u16_val as i64,
+            AstArgType::IntL => arg.u64_val as i64,
+            AstArgType::CharL => arg.char_val as i64,
+            
+            AstArgType::Id => match values.and_then(|v| v.get(&arg.str_val)) {
+                Some(v) => *v as i64,
+                None => return Err(format!("Invalid choice: {}", arg.str_val)),
+            },
+            
+            AstArgType::OpNeg if !negate => {
+                negate = true;
+                continue;
+            },
+            
+            AstArgType::Range => continue,
+            
+            AstArgType::OpOr => {
+                ranges.push(choice_range(&bounds)?);
+                bounds.clear();
+                continue;
+            },
+            
+            _ => return Err("Invalid choice.".to_string()),
+        };
+        
+        if negate {
+            bounds.push(-val);
+        } else {
+            bounds.push(val);
+        }
+        
+        negate = false;
+    }
+    
+    if bounds.len() > 0 {
+        ranges.push(choice_range(&bounds)?);
+    }
+    
+    Ok(ranges)
+}
+
+fn choice_range(bounds : &Vec<i64>) -> Result<(i64, i64), String> {
+    match bounds.len() {
+        1 => return Ok((bounds[0], bounds[0])),
+        2 => return Ok((bounds[0], bounds[1])),
+        _ => return Err("Invalid range.".to_string()),
+    }
+}
+
+// Decides whether a case with these sorted ranges is lowered to a jump table
+// The table has to be small, and at least a third of it has to be used
+pub fn is_dense(ranges : &Vec<(i64, i64)>) -> bool {
+    if ranges.len() < 4 {
+        return false;
+    }
+    
+    let count = ranges.iter().fold(0i64, |n, (lo, hi)| n.saturating_add(hi.saturating_sub(*lo)).saturating_add(1));
+    let span = ranges.last().unwrap().1.saturating_sub(ranges[0].0).saturating_add(1);
+    
+    span <= 1024 && span <= count * 3
+}
+
+// A condition, split at its logical operators
+#[derive(Debug, Clone)]
+pub enum AstCond {
+    Test(usize, usize),     // The start and end of a comparison, or of a single bool value
+    Not(Box<AstCond>),
+    And(Box<AstCond>, Box<AstCond>),
+    Or(Box<AstCond>, Box<AstCond>),
+}
+
+// Builds the tree of a condition
+// "not" binds the tightest and "or" the loosest; parentheses group.
+pub fn cond_tree(args : &[AstArg]) -> Result<AstCond, String> {
+    let mut pos = 0;
+    let cond = cond_or(args, &mut pos)?;
+    
+    if pos < args.len() {
+        return Err(extra_error(args, pos));
     }
+    
+    Ok(cond)
 }
 
+fn cond_or(args : &[AstArg], pos : &mut usize) -> Result<AstCond, String> {
+    let mut cond = cond_and(args, pos)?;
+    
+    while *pos < args.len() && args[*pos].arg_type == AstArgType::Or {
+        *pos += 1;
+        let rhs = cond_and(args, pos)?;
+        cond = AstCond::Or(Box::new(cond), Box::new(rhs));
+    }
+    
+    Ok(cond)
+}
+
+fn cond_and(args : &[AstArg], pos : &mut usize) -> Result<AstCond, String> {
+    let mut cond = cond_not(args, pos)?;
+    
+    while *pos < args.len() && args[*pos].arg_type == AstArgType::And {
+        *pos += 1;
+        let rhs = cond_not(args, pos)?;
+        cond = AstCond::And(Box::new(cond), Box::new(rhs));
+    }
+    
+    Ok(cond)
+}
+
+fn cond_not(args : &[AstArg], pos : &mut usize) -> Result<AstCond, String> {
+    match args.get(*pos).map(|a| &a.arg_type) {
+        Some(AstArgType::Not) => {
+            *pos += 1;
+            let cond = cond_not(args, pos)?;
+            return Ok(AstCond::Not(Box::new(cond)));
+        },
+        
+        Some(AstArgType::OpLParen) => {
+            *pos += 1;
+            let cond = cond_or(args, pos)?;
+            
+            match args.get(*pos) {
+                Some(a) if a.arg_type == AstArgType::OpRParen => *pos += 1,
+                Some(_) => return Err(extra_error(args, *pos)),
+                None => return Err("Unbalanced parentheses in condition.".to_string()),
+            }
+            
+            return Ok(cond);
+        },
+        
+        _ => {},
+    }
+    
+    // A test runs up to the next logical operator or parenthesis
+    let start = *pos;
+    
+    while *pos < args.len() {
+        match &args[*pos].arg_type {
+            AstArgType::And | AstArgType::Or | AstArgType::Not
+            | AstArgType::OpLParen | AstArgType::OpRParen => break,
+            _ => *pos += 1,
+        }
+    }
+    
+    if *pos == start {
+        return Err("Expected a comparison or a bool value in condition.".to_string());
+    }
+    
+    Ok(AstCond::Test(start, *pos))
+}
+
+// Explains an argument left over after a condition
+// Anything but a logical operator after a group means it was grouping a value
+fn extra_error(args : &[AstArg], pos : usize) -> String {
+    if args[pos].arg_type == AstArgType::OpRParen {
+        return "Unbalanced parentheses in condition.".to_string();
+    }
+    
+    if pos > 0 && args[pos - 1].arg_type == AstArgType::OpRParen {
+        return "Parentheses can only group conditions.".to_string();
+    }
+    
+    "Expected \"and\" or \"or\" between conditions.".to_string()
+}
+
+// Checks whether the arguments hold a condition, rather than a value
+pub fn is_cond(args : &[AstArg]) -> bool {
+    args.iter().any(|a| matches!(a.arg_type,
+        AstArgType::OpEq | AstArgType::OpNeq
+        | AstArgType::OpLt | AstArgType::OpLte
+        | AstArgType::OpGt | AstArgType::OpGte
+        | AstArgType::And | AstArgType::Or | AstArgType::Not))
+}
+
+// The lowest and highest values an integer type can hold
+// Characters take both signed and unsigned bytes.
+pub fn int_range(data_type : &DataType) -> Option<(i64, u64)> {
+    match data_type {
+        DataType::Byte => return Some((i8::MIN as i64, i8::MAX as u64)),
+        DataType::UByte => return Some((0, u8::MAX as u64)),
+        DataType::Char => return Some((i8::MIN as i64, u8::MAX as u64)),
+        DataType::Short => return Some((i16::MIN as i64, i16::MAX as u64)),
+        DataType::UShort => return Some((0, u16::MAX as u64)),
+        DataType::Int => return Some((i32::MIN as i64, i32::MAX as u64)),
+        DataType::UInt => return Some((0, u32::MAX as u64)),
+        DataType::Int64 => return Some((i64::MIN, i64::MAX as u64)),
+        DataType::UInt64 => return Some((0, u64::MAX)),
+        _ => return None,
+    }
+}
+
+// Checks that an integer literal matches its suffix if it has one, and fits in a type
+pub fn check_int_literal(arg : &AstArg, negate : bool, data_type : &DataType) -> Result<(), String> {
+    if arg.data_type != DataType::None && arg.data_type != *data_type {
+        return Err(format!("Literal suffix {} does not match {}.",
+            type_name(&arg.data_type, &DataType::None), type_name(data_type, &DataType::None)));
+    }
+    
+    check_int_range(arg.u64_val, negate, data_type)
+}
+
+// Checks that an integer value fits in a type
+// A negated value is given by its magnitude and the negate flag.
+pub fn check_int_range(val : u64, negate : bool, data_type : &DataType) -> Result<(), String> {
+    let fits = match int_range(data_type) {
+        Some((low, _)) if negate => val <= low.unsigned_abs(),
+        Some((_, high)) => val <= high,
+        None => true,
+    };
+    
+    if fits {
+        return Ok(());
+    }
+    
+    let sign = if negate { "-" } else { "" };
+    Err(format!("Integer literal {}{} does not fit in {}.", sign, val, type_name(data_type, &DataType::None)))
+}
+
+// Returns the name of a type as it is written in Lila
+pub fn type_name(data_type : &DataType, sub_type : &DataType) -> String {
+    match data_type {
+        DataType::Byte => return "byte".to_string(),
+        DataType::UByte => return "ubyte".to_string(),
+        DataType::Short => return "short".to_string(),
+        DataType::UShort => return "ushort".to_string(),
+        DataType::Int => return "int".to_string(),
+        DataType::UInt => return "uint".to_string(),
+        DataType::Int64 => return "int64".to_string(),
+        DataType::UInt64 => return "uint64".to_string(),
+        DataType::Float => return "float".to_string(),
+        DataType::Double => return "double".to_string(),
+        DataType::Char => return "char".to_string(),
+        DataType::Str => return "str".to_string(),
+        DataType::Bool => return "bool".to_string(),
+        DataType::Ptr => return format!("{}[]", type_name(sub_type, &DataType::None)),
+        DataType::Enum(name) | DataType::Record(name) | DataType::Proc(name) => return name.clone(),
+        DataType::Pointer(pointee) if **pointee == DataType::Void => return "null".to_string(),
+        DataType::Pointer(pointee) => return format!("ptr to {}", type_name(pointee, &DataType::None)),
+        DataType::Void | DataType::None => return "void".to_string(),
+    }
+}
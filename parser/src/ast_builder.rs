@@ -16,8 +16,7 @@
 // 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
 
 // Import what we need
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::fs;
 use std::collections::HashMap;
 
 use crate::ast;
@@ -31,25 +30,25 @@ use crate::ast_flow::*;
 use crate::ast_var::*;
 use crate::module;
 use crate::module::*;
-use crate::syntax::ErrorManager;
+use crate::syntax::*;
 
 pub struct AstBuilder {
     pub scanner : Lex,
     pub tree : AstTree,
     pub global_consts : HashMap<String, AstConst>,
     pub current_block : Vec<AstStmt>,
-    pub keep_postfix : bool,
+    pub in_func : bool,
     pub syntax : ErrorManager,
 }
 
 impl AstBuilder {
-
+    
     pub fn get_token(&mut self) -> Token {
         return self.scanner.get_token();
     }
     
-    pub fn syntax_error(&mut self, msg : String) {
-        self.syntax.syntax_error(&mut self.scanner, msg);
+    pub fn syntax_error(&mut self, msg : String) -> &mut Diagnostic {
+        self.syntax.syntax_error(&mut self.scanner, msg)
     }
     
     pub fn add_stmt(&mut self, stmt : AstStmt) {
@@ -65,13 +64,16 @@ impl AstBuilder {
 // In Quik, each line is a self-contained expression; as a result, we read a line
 // and then lexically analyze and build an AST node from it
 //
-pub fn build_ast(path : String, arch : Arch, name : String, include_core : bool, keep_postfix : bool) -> Result<AstTree, ()> {   
+pub fn build_ast(path : String, arch : Arch, name : String, include_core : bool) -> Result<AstTree, Vec<Diagnostic>> {   
     let tree = AstTree {
         file_name : name,
         arch : arch,
         module : String::new(),
         functions : Vec::new(),
         constants : Vec::new(),
+        globals : Vec::new(),
+        records : Vec::new(),
+        procs : Vec::new(),
     };
     
     let mut builder = AstBuilder {
@@ -79,36 +81,28 @@ pub fn build_ast(path : String, arch : Arch, name : String, include_core : bool,
         tree : tree,
         global_consts : HashMap::new(),
         current_block : Vec::new(),
-        keep_postfix : keep_postfix,
-        syntax : syntax::create_error_manager(),
+        in_func : false,
+        syntax : syntax::create_error_manager(path.clone()),
     };
     
+    builder.scanner.set_file(path.clone());
+    
     // Open the file
-    let file = File::open(&path)
+    let source = fs::read_to_string(&path)
         .expect("Error: Unable to open input file.");
-    let reader = BufReader::new(file);
     
     // Include the core modules
     if include_core {
-        include_module("core.mem".to_string(), &mut builder);
-        include_module("core.string".to_string(), &mut builder);
-        include_module("core.io".to_string(), &mut builder);
+        for name in module::CORE_MODULES.iter() {
+            include_module(name.to_string(), &mut builder);
+        }
     }
     
-    // Read the thing line by line
-    let mut line_no = 0;
     let mut in_begin = false;
     
-    for line in reader.lines() {
-        let mut current = line.unwrap();
-        current = current.trim().to_string();
-        line_no += 1;
-        
-        if current.len() == 0 {
-            continue;
-        }
-        
-        builder.scanner.tokenize(current, line_no);
+    if let Err(msg) = builder.scanner.tokenize(&source) {
+        builder.syntax_error(msg);
+        return Err(builder.syntax.errors);
     }
     
     loop {
@@ -120,8 +114,7 @@ pub fn build_ast(path : String, arch : Arch, name : String, include_core : bool,
         }
         
         if !ret {
-            builder.syntax.print_errors();
-            return Err(());
+            return Err(builder.syntax.errors);
         }
     }
     
@@ -133,33 +126,25 @@ pub fn include_module(name : String, builder : &mut AstBuilder) -> bool {
     let path = module::get_module_path(&name);
     
     // Open the file
-    let file = match File::open(&path) {
-        Ok(f) => f,
+    let source = match fs::read_to_string(&path) {
+        Ok(s) => s,
         Err(_e) => {
-            println!("Invalid module: {}", name);
+            builder.syntax_error(format!("Invalid module: {}", name)).code(E_MODULE).whole_line();
             return false;
         },
     };
     
-    let reader = BufReader::new(file);
-    
-    // Read the thing line by line
-    let mut line_no = 0;
     let mut in_begin = false;
     
     let old_scanner = builder.scanner.clone();
+    let record_count = builder.tree.records.len();
+    let proc_count = builder.tree.procs.len();
     builder.scanner = create_lex();
+    builder.scanner.set_file(path.clone());
     
-    for line in reader.lines() {
-        let mut current = line.unwrap();
-        current = current.trim().to_string();
-        line_no += 1;
-        
-        if current.len() == 0 {
-            continue;
-        }
-        
-        builder.scanner.tokenize(current, line_no);
+    if let Err(msg) = builder.scanner.tokenize(&source) {
+        builder.syntax_error(msg);
+        return false;
     }
     
     loop {
@@ -175,6 +160,15 @@ pub fn include_module(name : String, builder : &mut AstBuilder) -> bool {
         }
     }
     
+    // Records from an included module belong to that module's header
+    for record in builder.tree.records[record_count..].iter_mut() {
+        record.line = String::new();
+    }
+    
+    for proc_type in builder.tree.procs[proc_count..].iter_mut() {
+        proc_type.line = String::new();
+    }
+    
     builder.scanner = old_scanner;
     
     true
@@ -193,22 +187,23 @@ fn build_line(in_begin : bool, builder : &mut AstBuilder) -> (bool, bool, bool)
         
         Token::Module => code = build_module(builder),
         Token::Use => code = build_use(builder),
-    
+        
         Token::Extern => {
             token = builder.scanner.get_token();
             match token {
-                Token::Func => {},
+                Token::Func => code = build_func(builder, true),
+                Token::Id(ref val) if !builder.in_func => code = build_global(builder, val.to_string(), true),
+                
                 _ => {
                     builder.syntax_error("Expected \"func\" keyword.".to_string());
                     return (false, false, false);
                 }
             }
-                
-            code = build_func(builder, true)
         },
         
         Token::Func => {
             in_code = false;
+            builder.in_func = true;
             code = build_func(builder, false);
         },
         
@@ -224,9 +219,21 @@ fn build_line(in_begin : bool, builder : &mut AstBuilder) -> (bool, bool, bool)
         
         Token::Return if in_code => code = build_return(builder),
         Token::Exit if in_code => code = build_exit(builder),
-        Token::End => build_end(builder),
+        Token::End => {
+            build_end(builder);
+            builder.in_func = false;
+        },
         Token::Const => code = build_const(builder),
         
+        Token::Type => {
+            if in_code {
+                builder.syntax_error("You cannot define a type in the code body.".to_string());
+                return (false, false, false);
+            } else {
+                code = build_type(builder);
+            }
+        },
+        
         Token::Enum => {
             if in_code {
                 builder.syntax_error("You cannot define an enum in the code body.".to_string());
@@ -236,12 +243,17 @@ fn build_line(in_begin : bool, builder : &mut AstBuilder) -> (bool, bool, bool)
             }
         },
         
+        // Anything declared outside of a function is a global variable
+        Token::Id(ref val) if !builder.in_func => code = build_global(builder, val.to_string(), false),
+        
         Token::Id(ref val) if in_code => code = build_id(builder, val.to_string()),
+        Token::OpMul if in_code => code = build_deref_assign(builder),
         Token::Id(ref val) => code = build_var_dec(builder, val.to_string()),
         
         Token::If if in_code => code = build_cond(builder, Token::If),
         Token::While if in_code => code = build_cond(builder, Token::While),
         Token::For if in_code => code = build_for_loop(builder),
+        Token::Case if in_code => code = build_case(builder),
         
         Token::Eof => {},
         Token::EoI => return (true, false, true),
@@ -261,93 +273,250 @@ fn build_line(in_begin : bool, builder : &mut AstBuilder) -> (bool, bool, bool)
 }
 
 // Builds a constant
+// Syntax: "const int name = value;" or "const name : int = value;"
+// A constant table gives its values in brackets: "const name : int[] = [1, 2, 3];"
 fn build_const(builder : &mut AstBuilder) -> bool {
+    let first_line = builder.scanner.get_line_no();
     let mut token = builder.get_token();
     let data_type : DataType;
-    let arg : AstArg;
+    let mut arg = ast::create_arg(AstArgType::None);
+    let mut values : Vec<AstArg> = Vec::new();
+    let mut is_table = false;
+    let mut negate = false;
     let name : String;
     
     match &token {
-        Token::Byte => data_type = DataType::Byte,
-        Token::UByte => data_type = DataType::UByte,
-        Token::Short => data_type = DataType::Short,
-        Token::UShort => data_type = DataType::UShort,
-        Token::Int => data_type = DataType::Int,
-        Token::UInt => data_type = DataType::UInt,
-        Token::Int64 => data_type = DataType::Int64,
-        Token::UInt64 => data_type = DataType::UInt64,
-        Token::Float => data_type = DataType::Float,
-        Token::Double => data_type = DataType::Double,
-        Token::Char => data_type = DataType::Char,
-        Token::TStr => data_type = DataType::Str,
-        
-        _ => {
-            builder.syntax_error("Expected data type.".to_string());
-            return false;
+        Token::Id(ref val) if builder.scanner.peek_token() == Token::Colon => {
+            name = val.to_string();
+            builder.get_token();
+            
+            token = builder.get_token();
+            data_type = const_type(&token);
+            
+            if data_type == DataType::None {
+                builder.syntax_error("Expected data type.".to_string()).code(E_EXPECTED_TYPE);
+                return false;
+            }
+            
+            // The type may be followed by "[]" for a table
+            token = builder.get_token();
+            
+            if token == Token::LBracket {
+                if builder.get_token() != Token::RBracket {
+                    builder.syntax_error("Expected \']\'; a constant table gets its size from its values.".to_string());
+                    return false;
+                }
+                
+                is_table = true;
+                token = builder.get_token();
+            }
         },
-    }
-    
-    token = builder.get_token();
-    
-    match &token {
-        Token::Id(ref val) => name = val.to_string(),
         
         _ => {
-            builder.syntax_error("Missing constant name.".to_string());
-            return false;
+            data_type = const_type(&token);
+            
+            if data_type == DataType::None {
+                builder.syntax_error("Expected data type.".to_string()).code(E_EXPECTED_TYPE);
+                return false;
+            }
+            
+            token = builder.get_token();
+            
+            match &token {
+                Token::Id(ref val) => name = val.to_string(),
+                
+                _ => {
+                    builder.syntax_error("Missing constant name.".to_string()).code(E_EXPECTED_NAME);
+                    return false;
+                },
+            }
+            
+            token = builder.get_token();
         },
     }
     
-    token = builder.get_token();
-    
     if token != Token::Assign {
         builder.syntax_error("Expected assignment operator.".to_string());
         return false;
     }
     
-    token = builder.get_token();
-    
-    match &token {
-        Token::ByteL(val) => arg = ast::create_byte(*val),
-        Token::ShortL(val) => arg = ast::create_short(*val),
-        Token::IntL(val) => arg = ast::create_int(*val),
-        Token::FloatL(val) => arg = ast::create_float(*val),
-        Token::CharL(val) => arg = ast::create_char(*val),
-        Token::StringL(ref val) => arg = ast::create_string(val.to_string()),
+    if is_table {
+        match build_const_table(builder, &data_type) {
+            Some(v) => values = v,
+            None => return false,
+        }
+    } else {
+        token = builder.get_token();
         
-        _ => {
-            builder.syntax_error("Constants can only be literal values.".to_string());
-            return false;
-        },
+        // Numbers may be negative
+        if token == Token::OpSub {
+            negate = true;
+            token = builder.get_token();
+        }
+        
+        match &token {
+            Token::ByteL(val) if !negate => arg = ast::create_byte(*val),
+            Token::ShortL(val) if !negate => arg = ast::create_short(*val),
+            Token::IntL(val, suffix) => arg = ast::create_typed_int(*val, suffix.clone()),
+            Token::FloatL(val) => arg = ast::create_float(if negate { -val } else { *val }),
+            Token::CharL(val) if !negate => arg = ast::create_char(*val),
+            Token::StringL(ref val) if !negate => arg = ast::create_string(val.to_string()),
+            
+            _ => {
+                builder.syntax_error("Constants can only be literal values.".to_string());
+                return false;
+            },
+        }
+        
+        if arg.arg_type == AstArgType::IntL {
+            if let Err(msg) = ast::check_int_literal(&arg, negate, &data_type) {
+                builder.syntax_error(msg).code(E_LITERAL_RANGE);
+                return false;
+            }
+        } else {
+            // Only integers keep the sign apart
+            negate = false;
+        }
     }
     
     let constant = AstConst {
         name : name.clone(),
         data_type : data_type,
         value : arg,
+        values : values,
+        negate : negate,
         
-        line_no : builder.scanner.get_line_no(),
-        line : builder.scanner.get_current_line(),
+        line_no : first_line,
+        line : builder.scanner.get_lines_from(first_line),
     };
     
-    //if layer == 0 {
+    // The same header may be included more than once
+    if !builder.global_consts.contains_key(&name) {
         builder.tree.constants.push(constant.clone());
-        builder.global_consts.insert(name, constant);
-    /*} else {
-        builder.syntax_error("Constants are not yet supported on the local level.".to_string());
-        return false;
-    }*/
+    }
+    
+    builder.global_consts.insert(name, constant);
     
     token = builder.get_token();
     
     if token != Token::Semicolon {
-        builder.syntax_error("Expected terminator.".to_string());
+        builder.syntax_error("Expected terminator.".to_string()).code(E_TERMINATOR);
         return false;
     }
     
     true
 }
 
+// Returns the type of a constant, or None if the token is not one
+fn const_type(token : &Token) -> DataType {
+    match token {
+        Token::Byte => return DataType::Byte,
+        Token::UByte => return DataType::UByte,
+        Token::Short => return DataType::Short,
+        Token::UShort => return DataType::UShort,
+        Token::Int => return DataType::Int,
+        Token::UInt => return DataType::UInt,
+        Token::Int64 => return DataType::Int64,
+        Token::UInt64 => return DataType::UInt64,
+        Token::Float => return DataType::Float,
+        Token::Double => return DataType::Double,
+        Token::Char => return DataType::Char,
+        Token::TStr => return DataType::Str,
+        _ => return DataType::None,
+    }
+}
+
+// Builds the values of a constant table
+// The values are literals of the table type, and may run over several lines
+fn build_const_table(builder : &mut AstBuilder, data_type : &DataType) -> Option<Vec<AstArg>> {
+    let mut values : Vec<AstArg> = Vec::new();
+    
+    if next_token(builder) != Token::LBracket {
+        builder.syntax_error("Expected \'[\' before the table values.".to_string());
+        return None;
+    }
+    
+    loop {
+        let mut token = next_token(builder);
+        let mut negate = false;
+        
+        if token == Token::OpSub {
+            negate = true;
+            token = next_token(builder);
+        }
+        
+        let value = match token {
+            Token::ByteL(val) if !negate && ast::int_range(data_type).is_some() => ast::create_byte(val),
+            Token::ShortL(val) if !negate && ast::int_range(data_type).is_some() => ast::create_short(val),
+            
+            Token::IntL(val, suffix) if ast::int_range(data_type).is_some() => {
+                let mut value = ast::create_typed_int(val, suffix);
+                
+                if let Err(msg) = ast::check_int_literal(&value, negate, data_type) {
+                    builder.syntax_error(msg).code(E_LITERAL_RANGE);
+                    return None;
+                }
+                
+                if negate {
+                    value.u64_val = (val as i64).wrapping_neg() as u64;
+                }
+                
+                value
+            },
+            
+            Token::FloatL(val) if *data_type == DataType::Float || *data_type == DataType::Double => {
+                if negate {
+                    ast::create_float(-val)
+                } else {
+                    ast::create_float(val)
+                }
+            },
+            
+            Token::CharL(val) if !negate && *data_type == DataType::Char => ast::create_char(val),
+            Token::StringL(ref val) if !negate && *data_type == DataType::Str => ast::create_string(val.to_string()),
+            
+            Token::RBracket if !negate && values.len() == 0 => {
+                builder.syntax_error("A constant table needs at least one value.".to_string());
+                return None;
+            },
+            
+            _ => {
+                let mut msg = "Invalid value for a table of ".to_string();
+                msg.push_str(&ast::type_name(data_type, &DataType::None));
+                msg.push_str(".");
+                
+                builder.syntax_error(msg).code(E_TYPE_MISMATCH);
+                return None;
+            },
+        };
+        
+        values.push(value);
+        
+        match next_token(builder) {
+            Token::Comma => {},
+            Token::RBracket => break,
+            
+            _ => {
+                builder.syntax_error("Expected \',\' or \']\' after a table value.".to_string());
+                return None;
+            },
+        }
+    }
+    
+    Some(values)
+}
+
+// Returns the next token, skipping line breaks
+fn next_token(builder : &mut AstBuilder) -> Token {
+    let mut token = builder.get_token();
+    
+    while token == Token::Eof {
+        token = builder.get_token();
+    }
+    
+    token
+}
+
 // Builds an enumeration
 fn build_enum(builder : &mut AstBuilder) -> bool {
     let mut token = builder.get_token();
@@ -358,7 +527,7 @@ fn build_enum(builder : &mut AstBuilder) -> bool {
         Token::Id(ref val) => name = val.to_string(),
         
         _ => {
-            builder.syntax_error("Expected enum name".to_string());
+            builder.syntax_error("Expected enum name".to_string()).code(E_EXPECTED_NAME);
             return false;
         },
     }
@@ -387,8 +556,7 @@ fn build_enum(builder : &mut AstBuilder) -> bool {
             },
             
             _ => {
-                println!("{:?}", token);
-                builder.syntax_error("Invalid enumeration -> Expected name".to_string());
+                builder.syntax_error("Invalid enumeration -> Expected name".to_string()).code(E_EXPECTED_NAME);
                 return false;
             },
         }
@@ -416,13 +584,184 @@ fn build_enum(builder : &mut AstBuilder) -> bool {
     true
 }
 
+// Builds a type declaration
+// This is either a record or a procedure type
+fn build_type(builder : &mut AstBuilder) -> bool {
+    let name : String;
+    
+    // Get the name
+    match builder.get_token() {
+        Token::Id(ref val) => name = val.to_string(),
+        
+        _ => {
+            builder.syntax_error("Expected type name.".to_string()).code(E_EXPECTED_NAME);
+            return false;
+        },
+    }
+    
+    if ast::record_exists(&builder.tree, &name) || ast::proc_exists(&builder.tree, &name) {
+        builder.syntax_error("Duplicate type declaration.".to_string()).code(E_DUPLICATE);
+        return false;
+    }
+    
+    let line = builder.scanner.get_current_line();
+    
+    if builder.get_token() != Token::Is {
+        builder.syntax_error("Expected \"is record\" or \"is func\" after the type name.".to_string());
+        return false;
+    }
+    
+    match builder.get_token() {
+        Token::Record => return build_record(builder, name, line),
+        Token::Func => return build_proc_type(builder, name, line),
+        
+        _ => {
+            builder.syntax_error("Expected \"is record\" or \"is func\" after the type name.".to_string());
+            return false;
+        },
+    }
+}
+
+// Builds a record type
+// Records are global, and are laid out by the LTAC and LLIR layers
+fn build_record(builder : &mut AstBuilder, name : String, line : String) -> bool {
+    let mut record = AstRecord {
+        name : name,
+        fields : Vec::new(),
+        line : line,
+    };
+    
+    // Read the fields until we hit "end"
+    let mut token = builder.get_token();
+    
+    while token != Token::End {
+        match token {
+            Token::Eof => {
+                token = builder.get_token();
+                continue;
+            },
+            
+            Token::Id(ref val) => {
+                let mut names : Vec<String> = vec![val.to_string()];
+                let mut field = ast::create_stmt(AstStmtType::VarDec, &mut builder.scanner);
+                
+                token = builder.get_token();
+                
+                while token == Token::Comma {
+                    match builder.get_token() {
+                        Token::Id(ref val) => names.push(val.to_string()),
+                        
+                        _ => {
+                            builder.syntax_error("Expected field name.".to_string()).code(E_EXPECTED_NAME);
+                            return false;
+                        },
+                    }
+                    
+                    token = builder.get_token();
+                }
+                
+                if token != Token::Colon {
+                    builder.syntax_error("Expected \':\' after field name.".to_string());
+                    return false;
+                }
+                
+                match builder.get_token() {
+                    Token::Byte => field.data_type = DataType::Byte,
+                    Token::UByte => field.data_type = DataType::UByte,
+                    Token::Short => field.data_type = DataType::Short,
+                    Token::UShort => field.data_type = DataType::UShort,
+                    Token::Int => field.data_type = DataType::Int,
+                    Token::UInt => field.data_type = DataType::UInt,
+                    Token::Int64 => field.data_type = DataType::Int64,
+                    Token::UInt64 => field.data_type = DataType::UInt64,
+                    Token::Float => field.data_type = DataType::Float,
+                    Token::Double => field.data_type = DataType::Double,
+                    Token::Char => field.data_type = DataType::Char,
+                    Token::TStr => field.data_type = DataType::Str,
+                    
+                    _ => {
+                        builder.syntax_error("Invalid field type.".to_string()).code(E_EXPECTED_TYPE);
+                        return false;
+                    },
+                }
+                
+                if builder.get_token() != Token::Semicolon {
+                    builder.syntax_error("Expected terminator.".to_string()).code(E_TERMINATOR);
+                    return false;
+                }
+                
+                for n in names.iter() {
+                    for f in record.fields.iter() {
+                        if f.name == *n {
+                            builder.syntax_error("Duplicate field name.".to_string())
+                                .code(E_DUPLICATE)
+                                .whole_line()
+                                .label(f, "first declared here");
+                            return false;
+                        }
+                    }
+                    
+                    field.name = n.to_string();
+                    record.fields.push(field.clone());
+                }
+            },
+            
+            Token::EoI => {
+                builder.syntax_error("Expected \"end\" after record.".to_string());
+                return false;
+            },
+            
+            _ => {
+                builder.syntax_error("Invalid token in record.".to_string());
+                return false;
+            },
+        }
+        
+        token = builder.get_token();
+    }
+    
+    if record.fields.len() == 0 {
+        builder.syntax_error("Records must have at least one field.".to_string());
+        return false;
+    }
+    
+    builder.tree.records.push(record);
+    
+    true
+}
+
 // Handles cases when an identifier is the first token
 pub fn build_id(builder : &mut AstBuilder, id_val : String) -> bool {
     // If the next token is an assignment, we have a variable assignment
     // If the next token is a parantheses, we have a function call
+    let id_span = builder.scanner.get_span();
     let token = builder.get_token();
     let code : bool;
     
+    // A dot in the name means we are assigning to a record field
+    if id_val.contains('.') {
+        match token {
+            Token::AddAssign | Token::SubAssign
+            | Token::MulAssign | Token::DivAssign
+            | Token::ModAssign
+            | Token::OpInc | Token::OpDec
+            | Token::Assign => return build_field_assign(builder, id_val, token),
+            
+            _ => {
+                builder.syntax_error("Invalid field assignment.".to_string());
+                return false;
+            },
+        }
+    }
+    
+    // Constants, including tables, are read-only
+    if builder.global_consts.contains_key(&id_val) && token != Token::LParen {
+        let error = builder.syntax_error("Constants cannot be assigned.".to_string());
+        error.col_start = id_span.0;
+        error.col_end = id_span.1;
+        return false;
+    }
+    
     match token {
         Token::AddAssign | Token::SubAssign
         | Token::MulAssign | Token::DivAssign
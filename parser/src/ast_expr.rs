@@ -0,0 +1,620 @@
+
+// This file is part of the Lila compiler
+// Copyright (C) 2020-2021 Patrick Flynn
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; version 2.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, write to the Free Software Foundation, Inc.,
+// 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+
+// Expression trees
+//
+// The statement builders collect an expression as a list of parts. These are parsed
+// into a tree by precedence climbing. The statement keeps the tree for the backends,
+// and the tree is also written back out as arguments in infix order, which is what
+// the type checker and the editor tools read.
+//
+// In infix order, anything on the right side of an operator is grouped. Operands that
+// cannot be a single argument, such as call arguments or the sides of a comparison,
+// become Expr arguments, which hold the tree of their value.
+
+use crate::ast;
+use crate::ast::{AstStmt, AstArg, AstArgType, DataType};
+
+// A piece of an expression before it is parsed
+#[derive(Debug, Clone)]
+pub enum ExprPart {
+    Arg(AstArg),
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Comma,
+}
+
+// Represents an expression
+#[derive(Debug, Clone)]
+pub enum AstExpr {
+    Operand(AstArg),
+    Call(AstArg, Vec<AstExpr>),         // The function name, and the arguments
+    Index(AstArg, Box<AstExpr>),        // The array name, and the index
+    Unary(AstArgType, Box<AstExpr>),    // Negation or "not"
+    Binary(AstArgType, Box<AstExpr>, Box<AstExpr>),
+}
+
+// An error message, and the position of the part it was found at
+pub type ExprError = (String, usize);
+
+// The binding power of binary operators
+// Bitwise operators bind tighter than comparisons, so "x & 1 == 0" needs no parentheses
+fn precedence(arg_type : &AstArgType) -> Option<u8> {
+    match arg_type {
+        AstArgType::Range => return Some(1),
+        AstArgType::Or => return Some(2),
+        AstArgType::And => return Some(3),
+        
+        AstArgType::OpEq | AstArgType::OpNeq
+        | AstArgType::OpLt | AstArgType::OpLte
+        | AstArgType::OpGt | AstArgType::OpGte => return Some(5),
+        
+        AstArgType::OpOr => return Some(6),
+        AstArgType::OpXor => return Some(7),
+        AstArgType::OpAnd => return Some(8),
+        AstArgType::OpLeftShift | AstArgType::OpRightShift => return Some(9),
+        AstArgType::OpAdd | AstArgType::OpSub => return Some(10),
+        AstArgType::OpMul | AstArgType::OpDiv | AstArgType::OpMod => return Some(11),
+        
+        _ => return None,
+    }
+}
+
+// "not" applies to a whole comparison
+const NOT_PREC : u8 = 5;
+
+fn is_comparison(arg_type : &AstArgType) -> bool {
+    precedence(arg_type) == Some(5)
+}
+
+fn is_logical(arg_type : &AstArgType) -> bool {
+    *arg_type == AstArgType::And || *arg_type == AstArgType::Or
+}
+
+pub fn is_operand(arg_type : &AstArgType) -> bool {
+    match arg_type {
+        AstArgType::ByteL | AstArgType::ShortL | AstArgType::IntL
+        | AstArgType::FloatL | AstArgType::CharL | AstArgType::StringL
+        | AstArgType::BoolL | AstArgType::Id | AstArgType::Field
+        | AstArgType::Sizeof | AstArgType::AddrOf | AstArgType::Null
+        | AstArgType::Expr => return true,
+        
+        _ => return false,
+    }
+}
+
+struct ExprParser<'a> {
+    parts : &'a [ExprPart],
+    pos : usize,
+}
+
+impl ExprParser<'_> {
+    
+    // Returns the binary operator at the current position, if there is one
+    fn peek_op(&self) -> Option<AstArgType> {
+        match self.parts.get(self.pos) {
+            Some(ExprPart::Arg(arg)) if precedence(&arg.arg_type).is_some() => return Some(arg.arg_type.clone()),
+            _ => return None,
+        }
+    }
+    
+    // Builds the error for a part that cannot come next
+    fn unexpected(&self) -> ExprError {
+        let msg = match self.parts.get(self.pos) {
+            Some(ExprPart::RParen) => "Unbalanced parentheses.",
+            Some(ExprPart::RBracket) => "Unbalanced brackets.",
+            Some(ExprPart::Comma) => "Commas can only separate call arguments.",
+            _ => "Expected an operator.",
+        };
+        
+        (msg.to_string(), self.pos)
+    }
+    
+    // Parses operators that bind at least as tightly as the given level
+    // Operators of the same level are left associative, so the right side starts one level up
+    fn parse_binary(&mut self, min_prec : u8) -> Result<AstExpr, ExprError> {
+        let mut lhs = self.parse_unary()?;
+        
+        while let Some(op) = self.peek_op() {
+            let prec = precedence(&op).unwrap();
+            
+            if prec < min_prec {
+                break;
+            }
+            
+            self.pos += 1;
+            let rhs = self.parse_binary(prec + 1)?;
+            
+            // "a < b < c" and "a .. b .. c" don't mean anything
+            if self.peek_op().map(|next| precedence(&next)) == Some(Some(prec)) {
+                if op == AstArgType::Range {
+                    return Err(("A range can only have a start and an end.".to_string(), self.pos));
+                } else if is_comparison(&op) {
+                    return Err(("Comparisons cannot be chained.".to_string(), self.pos));
+                }
+            }
+            
+            lhs = AstExpr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        
+        Ok(lhs)
+    }
+    
+    // A '-' where an operand should be is a negation, which binds the tightest
+    fn parse_unary(&mut self) -> Result<AstExpr, ExprError> {
+        let arg_type = match self.parts.get(self.pos) {
+            Some(ExprPart::Arg(arg)) => arg.arg_type.clone(),
+            _ => AstArgType::None,
+        };
+        
+        match arg_type {
+            AstArgType::OpSub | AstArgType::OpNeg => {
+                self.pos += 1;
+                let expr = self.parse_unary()?;
+                return Ok(AstExpr::Unary(AstArgType::OpNeg, Box::new(expr)));
+            },
+            
+            AstArgType::Not => {
+                self.pos += 1;
+                let expr = self.parse_binary(NOT_PREC)?;
+                return Ok(AstExpr::Unary(AstArgType::Not, Box::new(expr)));
+            },
+            
+            _ => return self.parse_primary(),
+        }
+    }
+    
+    // Parses a group, or an operand with any call or index after it
+    fn parse_primary(&mut self) -> Result<AstExpr, ExprError> {
+        let start = self.pos;
+        
+        match self.parts.get(self.pos) {
+            Some(ExprPart::LParen) => {
+                self.pos += 1;
+                let expr = self.parse_binary(0)?;
+                
+                match self.parts.get(self.pos) {
+                    Some(ExprPart::RParen) => self.pos += 1,
+                    Some(_) => return Err(self.unexpected()),
+                    None => return Err(("Unbalanced parentheses.".to_string(), start)),
+                }
+                
+                return Ok(expr);
+            },
+            
+            Some(ExprPart::Arg(arg)) if is_operand(&arg.arg_type) => {
+                let mut arg = arg.clone();
+                self.pos += 1;
+                
+                // A dereference already holds its index
+                if arg.arg_type == AstArgType::Id && arg.sub_args.len() == 1 {
+                    let index = arg.sub_args.remove(0);
+                    return Ok(AstExpr::Index(arg, Box::new(AstExpr::Operand(index))));
+                } else if arg.arg_type != AstArgType::Id {
+                    return Ok(AstExpr::Operand(arg));
+                }
+                
+                match self.parts.get(self.pos) {
+                    Some(ExprPart::LParen) => {
+                        self.pos += 1;
+                        let args = self.parse_list(start + 1)?;
+                        return Ok(AstExpr::Call(arg, args));
+                    },
+                    
+                    Some(ExprPart::LBracket) => {
+                        self.pos += 1;
+                        let index = self.parse_binary(0)?;
+                        
+                        match self.parts.get(self.pos) {
+                            Some(ExprPart::RBracket) => self.pos += 1,
+                            Some(_) => return Err(self.unexpected()),
+                            None => return Err(("Unbalanced brackets.".to_string(), start + 1)),
+                        }
+                        
+                        return Ok(AstExpr::Index(arg, Box::new(index)));
+                    },
+                    
+                    _ => return Ok(AstExpr::Operand(arg)),
+                }
+            },
+            
+            _ => return Err(("Expected an operand.".to_string(), self.pos)),
+        }
+    }
+    
+    // Parses call arguments, up to and including the closing parenthesis
+    fn parse_list(&mut self, lparen : usize) -> Result<Vec<AstExpr>, ExprError> {
+        let mut args : Vec<AstExpr> = Vec::new();
+        
+        if let Some(ExprPart::RParen) = self.parts.get(self.pos) {
+            self.pos += 1;
+            return Ok(args);
+        }
+        
+        loop {
+            args.push(self.parse_binary(0)?);
+            
+            match self.parts.get(self.pos) {
+                Some(ExprPart::Comma) => self.pos += 1,
+                Some(ExprPart::RParen) => {
+                    self.pos += 1;
+                    return Ok(args);
+                },
+                
+                Some(_) => return Err(self.unexpected()),
+                None => return Err(("Unbalanced parentheses.".to_string(), lparen)),
+            }
+        }
+    }
+}
+
+// Parses a whole expression
+pub fn parse_expr(parts : &[ExprPart]) -> Result<AstExpr, ExprError> {
+    let mut parser = ExprParser { parts : parts, pos : 0 };
+    let expr = parser.parse_binary(0)?;
+    
+    if parser.pos < parts.len() {
+        return Err(parser.unexpected());
+    }
+    
+    Ok(expr)
+}
+
+// Parses the arguments of a call statement
+// The closing parenthesis is not part of the list
+pub fn parse_args(parts : &[ExprPart]) -> Result<Vec<AstExpr>, ExprError> {
+    let mut parser = ExprParser { parts : parts, pos : 0 };
+    let mut args : Vec<AstExpr> = Vec::new();
+    
+    while parts.len() > 0 {
+        args.push(parser.parse_binary(0)?);
+        
+        match parts.get(parser.pos) {
+            Some(ExprPart::Comma) => parser.pos += 1,
+            Some(_) => return Err(parser.unexpected()),
+            None => break,
+        }
+    }
+    
+    Ok(args)
+}
+
+// Wraps an expression in a single argument
+// Once the leaves have their types, the argument gets the type of the whole value
+fn expr_arg(expr : &AstExpr) -> AstArg {
+    let mut arg = ast::create_arg(AstArgType::Expr);
+    arg.sub_args = to_infix(expr);
+    arg.data_type = expr_type(expr);
+    arg.expr = Some(Box::new(expr.clone()));
+    arg
+}
+
+fn group(args : &mut Vec<AstArg>, expr : &AstExpr) {
+    args.push(ast::create_arg(AstArgType::OpLParen));
+    args.append(&mut to_infix(expr));
+    args.push(ast::create_arg(AstArgType::OpRParen));
+}
+
+fn binary_prec(expr : &AstExpr) -> Option<u8> {
+    match expr {
+        AstExpr::Binary(op, _, _) => return precedence(op),
+        _ => return None,
+    }
+}
+
+// Writes out a negation
+// Only plain values can be negated directly; anything else is computed first
+fn negate(expr : &AstExpr) -> Vec<AstArg> {
+    let neg = ast::create_arg(AstArgType::OpNeg);
+    
+    match expr {
+        AstExpr::Operand(arg) if arg.sub_args.len() == 0 => return vec![neg, arg.clone()],
+        _ => return vec![neg, expr_arg(expr)],
+    }
+}
+
+// Writes out one side of a comparison or a range, which has to be a single value
+// A negative literal is allowed on the right of a comparison
+fn value_side(expr : &AstExpr, is_rhs : bool) -> Vec<AstArg> {
+    match expr {
+        AstExpr::Operand(_) => return to_infix(expr),
+        
+        AstExpr::Index(_, index) if matches!(**index, AstExpr::Operand(_)) => return to_infix(expr),
+        
+        AstExpr::Unary(AstArgType::OpNeg, value) if is_rhs => {
+            match &**value {
+                AstExpr::Operand(arg) if arg.arg_type == AstArgType::IntL
+                    || arg.arg_type == AstArgType::FloatL => return negate(value),
+                _ => {},
+            }
+        },
+        
+        _ => {},
+    }
+    
+    vec![expr_arg(expr)]
+}
+
+// Writes out one side of "and" or "or"
+fn logic_side(args : &mut Vec<AstArg>, expr : &AstExpr, grouped : bool) {
+    match expr {
+        AstExpr::Binary(op, _, _) if is_logical(op) => {
+            if grouped {
+                group(args, expr);
+            } else {
+                args.append(&mut to_infix(expr));
+            }
+        },
+        
+        AstExpr::Binary(op, _, _) if is_comparison(op) => args.append(&mut to_infix(expr)),
+        AstExpr::Unary(AstArgType::Not, _) => args.append(&mut to_infix(expr)),
+        
+        _ => args.append(&mut value_side(expr, false)),
+    }
+}
+
+// Writes out an expression in infix order
+pub fn to_infix(expr : &AstExpr) -> Vec<AstArg> {
+    let mut args : Vec<AstArg> = Vec::new();
+    
+    match expr {
+        AstExpr::Operand(arg) => args.push(arg.clone()),
+        
+        AstExpr::Call(name, call) => {
+            let mut arg = name.clone();
+            arg.sub_args = call_args(call);
+            args.push(arg);
+        },
+        
+        AstExpr::Index(name, index) => {
+            let mut arg = name.clone();
+            arg.sub_args = to_infix(index);
+            args.push(arg);
+        },
+        
+        AstExpr::Unary(AstArgType::OpNeg, value) => return negate(value),
+        
+        AstExpr::Unary(op, value) => {
+            args.push(ast::create_arg(op.clone()));
+            logic_side(&mut args, value, true);
+        },
+        
+        AstExpr::Binary(op, lhs, rhs) if is_comparison(op) || *op == AstArgType::Range => {
+            args.append(&mut value_side(lhs, false));
+            args.push(ast::create_arg(op.clone()));
+            args.append(&mut value_side(rhs, is_comparison(op)));
+        },
+        
+        AstExpr::Binary(op, lhs, rhs) if is_logical(op) => {
+            let prec = precedence(op);
+            logic_side(&mut args, lhs, binary_prec(lhs) < prec);
+            args.push(ast::create_arg(op.clone()));
+            logic_side(&mut args, rhs, binary_prec(rhs) <= prec);
+        },
+        
+        AstExpr::Binary(op, lhs, rhs) => {
+            let prec = precedence(op);
+            
+            if binary_prec(lhs).is_some() && binary_prec(lhs) < prec {
+                group(&mut args, lhs);
+            } else {
+                args.append(&mut to_infix(lhs));
+            }
+            
+            args.push(ast::create_arg(op.clone()));
+            
+            if binary_prec(rhs).is_some() {
+                group(&mut args, rhs);
+            } else {
+                args.append(&mut to_infix(rhs));
+            }
+        },
+    }
+    
+    args
+}
+
+// Writes out a condition
+// A lone call is a value to test, so it is computed like any other compound operand
+pub fn to_cond(expr : &AstExpr) -> Vec<AstArg> {
+    match expr {
+        AstExpr::Call(_, _) => return vec![expr_arg(expr)],
+        _ => return to_infix(expr),
+    }
+}
+
+// Writes out the arguments of a call
+// Each argument is a single value, so anything but a plain operand is an Expr argument
+pub fn call_args(call : &[AstExpr]) -> Vec<AstArg> {
+    let mut args : Vec<AstArg> = Vec::new();
+    
+    for expr in call.iter() {
+        match expr {
+            AstExpr::Operand(arg) if arg.arg_type != AstArgType::Id || arg.sub_args.len() == 0 => args.push(arg.clone()),
+            _ => args.push(expr_arg(expr)),
+        }
+    }
+    
+    args
+}
+
+// Writes out the values of an array literal
+// Each value is an Expr argument, so it can be built like the right side of an assignment
+pub fn array_values(values : &[AstExpr]) -> Vec<AstArg> {
+    values.iter().map(expr_arg).collect()
+}
+
+// Returns the tree of a single argument
+// Elements are only written out with a simple index, so that is all there is to read back
+pub fn arg_expr(arg : &AstArg) -> AstExpr {
+    if let Some(expr) = &arg.expr {
+        return (**expr).clone();
+    }
+    
+    if arg.arg_type == AstArgType::Id && arg.sub_args.len() == 1 {
+        let mut name = arg.clone();
+        let index = name.sub_args.remove(0);
+        return AstExpr::Index(name, Box::new(AstExpr::Operand(index)));
+    }
+    
+    AstExpr::Operand(arg.clone())
+}
+
+// Returns the tree of a value written out as one side of a comparison or a range
+// Each side is a single argument, which may be negated
+pub fn value_expr(args : &[AstArg]) -> Option<AstExpr> {
+    match args {
+        [arg] => return Some(arg_expr(arg)),
+        
+        [neg, arg] if neg.arg_type == AstArgType::OpNeg => {
+            return Some(AstExpr::Unary(AstArgType::OpNeg, Box::new(arg_expr(arg))));
+        },
+        
+        _ => return None,
+    }
+}
+
+// Returns the type of an expression once the type checker has been through it
+// As in the type checker, the first operand with a type of its own decides it, and
+// literals only do when there is nothing else
+pub fn expr_type(expr : &AstExpr) -> DataType {
+    let mut leaves : Vec<&AstArg> = Vec::new();
+    tree_leaves(expr, &mut leaves);
+    
+    for leaf in leaves.iter() {
+        match &leaf.arg_type {
+            AstArgType::Id | AstArgType::Field | AstArgType::Sizeof
+            | AstArgType::AddrOf | AstArgType::Null | AstArgType::Expr => return leaf.data_type.clone(),
+            _ => {},
+        }
+    }
+    
+    match leaves.iter().find(|leaf| leaf.data_type != DataType::None) {
+        Some(leaf) => return leaf.data_type.clone(),
+        None => return DataType::None,
+    }
+}
+
+// Collects the operands of a tree, left to right
+// Names come before their call arguments or index, as they do when written out
+fn tree_leaves<'a>(expr : &'a AstExpr, leaves : &mut Vec<&'a AstArg>) {
+    match expr {
+        AstExpr::Operand(arg) => leaves.push(arg),
+        
+        AstExpr::Call(name, args) => {
+            leaves.push(name);
+            
+            for arg in args.iter() {
+                tree_leaves(arg, leaves);
+            }
+        },
+        
+        AstExpr::Index(name, index) => {
+            leaves.push(name);
+            tree_leaves(index, leaves);
+        },
+        
+        AstExpr::Unary(_, value) => tree_leaves(value, leaves),
+        
+        AstExpr::Binary(_, lhs, rhs) => {
+            tree_leaves(lhs, leaves);
+            tree_leaves(rhs, leaves);
+        },
+    }
+}
+
+// Copies the types the type checker gave the arguments of a statement to its trees
+// The arguments were written out from the trees, so the operands come in the same order
+pub fn copy_types(stmt : &mut AstStmt) {
+    if let Some(expr) = stmt.expr.as_mut() {
+        copy_leaf_types(expr, &stmt.args);
+    }
+    
+    if let Some(expr) = stmt.sub_expr.as_mut() {
+        copy_leaf_types(expr, &stmt.sub_args);
+    }
+    
+    copy_arg_types(&mut stmt.args);
+    copy_arg_types(&mut stmt.sub_args);
+}
+
+// Copies the types to the trees of any Expr arguments
+fn copy_arg_types(args : &mut Vec<AstArg>) {
+    for arg in args.iter_mut() {
+        if let Some(expr) = arg.expr.as_mut() {
+            copy_leaf_types(expr, &arg.sub_args);
+        }
+        
+        copy_arg_types(&mut arg.sub_args);
+    }
+}
+
+fn copy_leaf_types(expr : &mut AstExpr, args : &[AstArg]) {
+    let mut types : Vec<DataType> = Vec::new();
+    arg_types(args, &mut types);
+    
+    let mut leaves : Vec<&mut AstArg> = Vec::new();
+    tree_leaves_mut(expr, &mut leaves);
+    
+    for (leaf, data_type) in leaves.into_iter().zip(types) {
+        leaf.data_type = data_type;
+    }
+}
+
+// Collects the types of the operands of written out arguments, in the same order as above
+fn arg_types(args : &[AstArg], types : &mut Vec<DataType>) {
+    for arg in args.iter() {
+        if arg.arg_type == AstArgType::Expr {
+            arg_types(&arg.sub_args, types);
+        } else if is_operand(&arg.arg_type) {
+            types.push(arg.data_type.clone());
+            
+            // Only a call or an index has operands of its own
+            if arg.arg_type == AstArgType::Id {
+                arg_types(&arg.sub_args, types);
+            }
+        }
+    }
+}
+
+fn tree_leaves_mut<'a>(expr : &'a mut AstExpr, leaves : &mut Vec<&'a mut AstArg>) {
+    match expr {
+        AstExpr::Operand(arg) => leaves.push(arg),
+        
+        AstExpr::Call(name, args) => {
+            leaves.push(name);
+            
+            for arg in args.iter_mut() {
+                tree_leaves_mut(arg, leaves);
+            }
+        },
+        
+        AstExpr::Index(name, index) => {
+            leaves.push(name);
+            tree_leaves_mut(index, leaves);
+        },
+        
+        AstExpr::Unary(_, value) => tree_leaves_mut(value, leaves),
+        
+        AstExpr::Binary(_, lhs, rhs) => {
+            tree_leaves_mut(lhs, leaves);
+            tree_leaves_mut(rhs, leaves);
+        },
+    }
+}
@@ -18,6 +18,7 @@
 use crate::ast;
 use crate::ast::*;
 use crate::lex::Token;
+use crate::syntax::*;
 
 use crate::ast_builder::*;
 use crate::ast_func::*;
@@ -39,6 +40,7 @@ fn build_block(builder : &mut AstBuilder, mut cond_stmt : AstStmt) -> bool {
             Token::If => code = build_cond(builder, Token::If),
             Token::While => code = build_cond(builder, Token::While),
             Token::For => code = build_for_loop(builder),
+            Token::Case => code = build_case(builder),
             Token::Eof => {},
             
             Token::End => {
@@ -57,12 +59,17 @@ fn build_block(builder : &mut AstBuilder, mut cond_stmt : AstStmt) -> bool {
                 break;
             },
             
+            Token::When if cond_stmt.stmt_type == AstStmtType::When => {
+                code = build_when(builder);
+                break;
+            },
+            
             Token::Break => {
                 let br = ast::create_stmt(AstStmtType::Break, &mut builder.scanner);
                 builder.add_stmt(br);
                 
                 if builder.get_token() != Token::Semicolon {
-                    builder.syntax_error("Expected terminator".to_string());
+                    builder.syntax_error("Expected terminator".to_string()).code(E_TERMINATOR);
                     return false;
                 }
             },
@@ -72,7 +79,7 @@ fn build_block(builder : &mut AstBuilder, mut cond_stmt : AstStmt) -> bool {
                 builder.add_stmt(cont);
                 
                 if builder.get_token() != Token::Semicolon {
-                    builder.syntax_error("Expected terminator".to_string());
+                    builder.syntax_error("Expected terminator".to_string()).code(E_TERMINATOR);
                     return false;
                 }
             },
@@ -133,7 +140,7 @@ pub fn build_for_loop(builder : &mut AstBuilder) -> bool {
         },
         
         _ => {
-            builder.syntax_error("Expected variable name.".to_string());
+            builder.syntax_error("Expected variable name.".to_string()).code(E_EXPECTED_NAME);
             return false;
         },
     }
@@ -153,3 +160,125 @@ pub fn build_for_loop(builder : &mut AstBuilder) -> bool {
     true
 }
 
+
+// Builds a case statement
+// Syntax: case <var> is
+//    when <choice> | <low> .. <high> => ...
+//    when others => ...
+// end
+//
+// The first arm is the only statement in the sub-block; like elif, each
+// arm holds the next one as the last statement of its own block.
+pub fn build_case(builder : &mut AstBuilder) -> bool {
+    let mut case = ast::create_stmt(AstStmtType::Case, &mut builder.scanner);
+    
+    match builder.get_token() {
+        Token::Id(ref val) if !val.contains('.') => {
+            let mut id = ast::create_arg(AstArgType::Id);
+            id.str_val = val.to_string();
+            case.args.push(id);
+        },
+        
+        _ => {
+            builder.syntax_error("Expected variable name.".to_string()).code(E_EXPECTED_NAME);
+            return false;
+        },
+    }
+    
+    if builder.get_token() != Token::Is {
+        builder.syntax_error("Expected \"is\".".to_string());
+        return false;
+    }
+    
+    let mut token = builder.get_token();
+    while token == Token::Eof {
+        token = builder.get_token();
+    }
+    
+    if token != Token::When {
+        builder.syntax_error("Expected \"when\".".to_string());
+        return false;
+    }
+    
+    let old_block = builder.current_block.clone();
+    builder.current_block.clear();
+    
+    let code = build_when(builder);
+    
+    case.sub_block = builder.current_block.clone();
+    builder.current_block = old_block;
+    builder.add_stmt(case);
+    
+    code
+}
+
+// Builds one arm of a case statement
+// The choices are literals, enum values, or ranges separated by '|'; an
+// arm without arguments is "when others"
+fn build_when(builder : &mut AstBuilder) -> bool {
+    let mut when = ast::create_stmt(AstStmtType::When, &mut builder.scanner);
+    let mut token = builder.get_token();
+    let mut last = Token::Unknown;
+    
+    if token == Token::Others {
+        token = builder.get_token();
+        last = Token::Others;
+    }
+    
+    while token != Token::FatArrow {
+        let operand = last == Token::Unknown || last == Token::OpOr || last == Token::Range;
+        
+        let arg = match token {
+            Token::ByteL(val) if operand => ast::create_byte(val),
+            Token::ShortL(val) if operand => ast::create_short(val),
+            Token::IntL(val, ref suffix) if operand => ast::create_typed_int(val, suffix.clone()),
+            Token::CharL(val) if operand => ast::create_char(val),
+            
+            Token::Id(ref val) if operand => match &builder.global_consts.get(val) {
+                Some(v) if v.values.len() == 0 => {
+                    if v.negate {
+                        when.args.push(ast::create_arg(AstArgType::OpNeg));
+                    }
+                    
+                    v.value.clone()
+                },
+                
+                _ => {
+                    let mut arg = ast::create_arg(AstArgType::Id);
+                    arg.str_val = val.to_string();
+                    arg
+                },
+            },
+            
+            Token::OpSub if operand => {
+                when.args.push(ast::create_arg(AstArgType::OpNeg));
+                token = builder.get_token();
+                continue;
+            },
+            
+            Token::OpOr if !operand && last != Token::Others => ast::create_arg(AstArgType::OpOr),
+            Token::Range if !operand && last != Token::Others => ast::create_arg(AstArgType::Range),
+            
+            Token::Eof | Token::EoI => {
+                builder.syntax_error("Expected \"=>\".".to_string());
+                return false;
+            },
+            
+            _ => {
+                builder.syntax_error("Invalid choice in case arm.".to_string());
+                return false;
+            },
+        };
+        
+        when.args.push(arg);
+        last = token.clone();
+        token = builder.get_token();
+    }
+    
+    if last == Token::Unknown || last == Token::OpOr || last == Token::Range {
+        builder.syntax_error("Expected a choice before \"=>\".".to_string());
+        return false;
+    }
+    
+    build_block(builder, when)
+}
@@ -18,9 +18,11 @@
 use crate::ast;
 use crate::ast::*;
 use crate::lex::Token;
+use crate::syntax::*;
 
 use crate::ast_builder::AstBuilder;
 use crate::ast_utils::*;
+use crate::ast_var::build_ptr_type;
 
 // A utility function for returning a type modifier from a token
 // NOTE: I don't know if we need a subtype, but if so, we'll have to go back and make adjustments
@@ -37,7 +39,7 @@ fn token_to_mod(token : &Token, is_array : bool) -> (DataType, DataType) {
         
         Token::UShort if is_array => return (DataType::Ptr, DataType::UShort),
         Token::UShort => return (DataType::UShort, DataType::None),
-    
+        
         Token::Int if is_array => return (DataType::Ptr, DataType::Int),
         Token::Int => return (DataType::Int, DataType::None),
         
@@ -55,6 +57,8 @@ fn token_to_mod(token : &Token, is_array : bool) -> (DataType, DataType) {
         Token::TStr if is_array => return (DataType::Ptr, DataType::Str),
         Token::TStr => return (DataType::Str, DataType::None),
         
+        Token::Bool => return (DataType::Bool, DataType::None),
+        
         _ => return (DataType::None, DataType::None),
     }
 }
@@ -62,10 +66,17 @@ fn token_to_mod(token : &Token, is_array : bool) -> (DataType, DataType) {
 // A helper function for the function declaration builder
 fn build_func_return(builder : &mut AstBuilder, func : &mut AstFunc) -> bool {
     let token = builder.get_token();
-    let (ret, _) = token_to_mod(&token, false);
+    let (mut ret, _) = token_to_mod(&token, false);
+    
+    if token == Token::Ptr {
+        ret = build_ptr_type(builder);
+        if ret == DataType::None {
+            return false;
+        }
+    }
     
     if ret == DataType::None {
-        builder.syntax_error("Invalid function return type.".to_string());
+        builder.syntax_error("Invalid function return type.".to_string()).code(E_EXPECTED_TYPE);
         return false;
     }
     
@@ -73,45 +84,9 @@ fn build_func_return(builder : &mut AstBuilder, func : &mut AstFunc) -> bool {
     true
 }
 
-// Builds a regular function declaration
-pub fn build_func(builder : &mut AstBuilder, is_extern : bool) -> bool {
-    // The first token should be the function name
-    let mut token = builder.get_token();
-    let name : String;
-    
-    match token {
-        Token::Id(ref val) => name = val.to_string(),
-        _ => {
-            builder.syntax_error("Expected function name.".to_string());
-            return false;
-        },
-    }
-    
-    let mut func : AstFunc;
-    
-    if is_extern {
-        func = ast::create_extern_func(name);
-    } else {
-        func = ast::create_func(name);
-        func.line = builder.scanner.get_current_line();
-    }
-    
-    // Check for arguments, and get them if so
-    token = builder.get_token();
-    
-    if token != Token::LParen {
-        if token == Token::Arrow {
-            let ret = build_func_return(builder, &mut func);
-            
-            if !ret {
-                return false;
-            }
-        }
-        
-        builder.tree.functions.push(func);
-        return true;
-    }
-    
+// Reads the argument list of a function declaration up to the closing ')'
+fn build_func_args(builder : &mut AstBuilder, func : &mut AstFunc) -> bool {
+    let mut token = Token::LParen;
     let mut last_token = Token::LParen;
     
     while token != Token::RParen && token != Token::Eof {
@@ -123,6 +98,8 @@ pub fn build_func(builder : &mut AstBuilder, is_extern : bool) -> bool {
             Token::Id(ref val) => arg.name = val.to_string(),
             
             Token::Any => {
+                func.is_variadic = true;
+                func.fixed_args = func.args.len();
                 token = builder.get_token();
                 
                 if token == Token::Comma || token == Token::RParen || token == Token::Eof {
@@ -143,7 +120,7 @@ pub fn build_func(builder : &mut AstBuilder, is_extern : bool) -> bool {
             },
             
             _ => {
-                builder.syntax_error("Expected function argument name.".to_string());
+                builder.syntax_error("Expected function argument name.".to_string()).code(E_EXPECTED_NAME);
                 return false;
             },
         }
@@ -159,6 +136,15 @@ pub fn build_func(builder : &mut AstBuilder, is_extern : bool) -> bool {
             return false;
         }
         
+        let mut ptr_type = DataType::None;
+        
+        if type_token == Token::Ptr {
+            ptr_type = build_ptr_type(builder);
+            if ptr_type == DataType::None {
+                return false;
+            }
+        }
+        
         token = builder.get_token();
         
         if token == Token::LBracket {
@@ -173,10 +159,36 @@ pub fn build_func(builder : &mut AstBuilder, is_extern : bool) -> bool {
             token = builder.get_token();
         }
         
-        let (val, sub_val) = token_to_mod(&type_token, is_array);
-    
+        let (mut val, mut sub_val) = token_to_mod(&type_token, is_array);
+        
+        // Records are passed by reference
+        match type_token {
+            Token::Id(ref name) if !is_array && ast::record_exists(&builder.tree, name) => {
+                val = DataType::Record(name.to_string());
+            },
+            
+            Token::Id(ref name) if ast::proc_exists(&builder.tree, name) => {
+                if is_array {
+                    val = DataType::Ptr;
+                    sub_val = DataType::Proc(name.to_string());
+                } else {
+                    val = DataType::Proc(name.to_string());
+                }
+            },
+            
+            Token::Ptr if !is_array => {
+                if let DataType::Pointer(ref pointee) = ptr_type {
+                    sub_val = (**pointee).clone();
+                }
+                
+                val = ptr_type;
+            },
+            
+            _ => {},
+        }
+        
         if val == DataType::None {
-            builder.syntax_error("Invalid or missing function argument type.".to_string());
+            builder.syntax_error("Invalid or missing function argument type.".to_string()).code(E_EXPECTED_TYPE);
             return false;
         }
         
@@ -190,6 +202,53 @@ pub fn build_func(builder : &mut AstBuilder, is_extern : bool) -> bool {
         }
     }
     
+    true
+}
+
+// Builds a regular function declaration
+pub fn build_func(builder : &mut AstBuilder, is_extern : bool) -> bool {
+    // The first token should be the function name
+    let mut token = builder.get_token();
+    let name : String;
+    
+    match token {
+        Token::Id(ref val) => name = val.to_string(),
+        _ => {
+            builder.syntax_error("Expected function name.".to_string()).code(E_EXPECTED_NAME);
+            return false;
+        },
+    }
+    
+    let mut func : AstFunc;
+    
+    if is_extern {
+        func = ast::create_extern_func(name);
+    } else {
+        func = ast::create_func(name);
+        func.line = builder.scanner.get_current_line();
+        func.line_no = builder.scanner.get_line_no();
+    }
+    
+    // Check for arguments, and get them if so
+    token = builder.get_token();
+    
+    if token != Token::LParen {
+        if token == Token::Arrow {
+            let ret = build_func_return(builder, &mut func);
+            
+            if !ret {
+                return false;
+            }
+        }
+        
+        builder.tree.functions.push(func);
+        return true;
+    }
+    
+    if !build_func_args(builder, &mut func) {
+        return false;
+    }
+    
     token = builder.get_token();
     
     if token == Token::Arrow {
@@ -205,6 +264,41 @@ pub fn build_func(builder : &mut AstBuilder, is_extern : bool) -> bool {
     true
 }
 
+// Builds a procedure type
+// The name has already been read; the argument list and return type follow "func"
+pub fn build_proc_type(builder : &mut AstBuilder, name : String, line : String) -> bool {
+    let mut func = ast::create_func(name);
+    
+    if builder.get_token() != Token::LParen {
+        builder.syntax_error("Expected \'(\' after \"func\".".to_string());
+        return false;
+    }
+    
+    if !build_func_args(builder, &mut func) {
+        return false;
+    }
+    
+    if func.is_variadic {
+        builder.syntax_error("Procedure types cannot be variadic.".to_string());
+        return false;
+    }
+    
+    if builder.get_token() == Token::Arrow && !build_func_return(builder, &mut func) {
+        return false;
+    }
+    
+    let proc_type = AstProc {
+        name : func.name,
+        args : func.args,
+        data_type : func.data_type,
+        line : line,
+    };
+    
+    builder.tree.procs.push(proc_type);
+    
+    true
+}
+
 // Builds a return statement
 pub fn build_return(builder : &mut AstBuilder) -> bool {
     let mut ret = ast::create_stmt(AstStmtType::Return, &mut builder.scanner);
@@ -255,9 +349,9 @@ pub fn build_func_call(builder : &mut AstBuilder, id_val : String) -> bool {
     }
     
     let token = builder.get_token();
-        
+    
     if token != Token::Semicolon {
-        builder.syntax_error("Expected terminator".to_string());
+        builder.syntax_error("Expected terminator".to_string()).code(E_TERMINATOR);
         return false;
     }
     
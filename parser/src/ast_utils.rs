@@ -18,6 +18,7 @@
 use crate::ast;
 use crate::ast::*;
 use crate::ast_builder::*;
+use crate::ast_expr::*;
 use crate::ast_var::*;
 use crate::lex::Token;
 
@@ -43,421 +44,284 @@ fn is_operator(token : Token) -> bool {
         Token::OpOr |
         Token::OpXor |
         Token::OpLeftShift |
-        Token::OpRightShift => return true,
+        Token::OpRightShift |
+        Token::And |
+        Token::Or |
+        Token::Not => return true,
         
         _ => return false,
     }
 }
 
 // A common function for building statement arguments
-// TODO: If there's a way to not make parts of this so repetative, that would be great
+// The tokens are collected as the parts of an expression and parsed into a tree. The
+// statement keeps the tree, and holds the arguments written out from it.
 pub fn build_args(builder : &mut AstBuilder, stmt : &mut AstStmt, end : Token) -> bool {
-    let mut token = builder.get_token();
-    let mut args : Vec<AstArg> = Vec::new();
-    let mut last = Token::Unknown;
+    let (parts, spans) = match build_parts(builder, &end) {
+        Some(p) => p,
+        None => return false,
+    };
     
-    let mut current_arg = ast::create_arg(AstArgType::Id);
-    let mut in_array = false;
+    if parts.len() == 0 {
+        return true;
+    }
     
-    while token != end {
-        match token {
-            Token::ByteL(val) => {
-                let arg = ast::create_byte(val);
-                
-                if in_array {
-                    current_arg.sub_args.push(arg);
-                } else {
-                    args.push(arg);
-                }
-            },
-            
-            Token::ShortL(val) => {
-                let arg = ast::create_short(val);
-                
-                if in_array {
-                    current_arg.sub_args.push(arg);
-                } else {
-                    args.push(arg);
-                }
-            },
-            
-            Token::IntL(val) => {
-                let arg = ast::create_int(val);
-                
-                if in_array {
-                    current_arg.sub_args.push(arg);
-                } else {
-                    args.push(arg);
-                }
-            },
-            
-            Token::FloatL(val) => {
-                let arg = ast::create_float(val);
-                
-                if in_array {
-                    current_arg.sub_args.push(arg);
-                } else {
-                    args.push(arg);
-                }
-            },
-            
-            Token::CharL(val) => {
-                let arg = ast::create_char(val);
-                
-                if in_array {
-                    current_arg.sub_args.push(arg);
-                } else {
-                    args.push(arg);
-                }
-            },
+    // Call statements hold a list of arguments
+    if end == Token::RParen {
+        match parse_args(&parts) {
+            Ok(call) => stmt.args.append(&mut call_args(&call)),
             
-            Token::StringL(ref val) => {
-                let arg = ast::create_string(val.to_string());
-                
-                if in_array {
-                    current_arg.sub_args.push(arg);
-                } else {
-                    args.push(arg);
-                }
+            Err((msg, pos)) => {
+                expr_error(builder, msg, pos, &spans);
+                return false;
             },
+        }
+        
+        return true;
+    }
+    
+    let expr = match parse_expr(&parts) {
+        Ok(expr) => expr,
+        
+        Err((msg, pos)) => {
+            expr_error(builder, msg, pos, &spans);
+            return false;
+        },
+    };
+    
+    match end {
+        Token::RBracket => {
+            stmt.sub_args.append(&mut to_infix(&expr));
+            stmt.sub_expr = Some(expr);
+        },
+        
+        Token::Eof => stmt.args.append(&mut to_cond(&expr)),
+        
+        // Compound assignments already hold "x +", so any operation after that is grouped
+        // The statement builder puts the tree for the whole value together
+        _ => {
+            let mut args = to_infix(&expr);
+            
+            if stmt.args.len() > 0 && matches!(expr, AstExpr::Binary(..)) {
+                args.insert(0, ast::create_arg(AstArgType::OpLParen));
+                args.push(ast::create_arg(AstArgType::OpRParen));
+            }
+            
+            stmt.args.append(&mut args);
+            stmt.expr = Some(expr);
+        },
+    }
+    
+    true
+}
+
+// Builds an array literal ("[1, 2, 3]") once the opening bracket has been read
+// The values can run over several lines
+pub fn build_array_literal(builder : &mut AstBuilder) -> Option<AstArg> {
+    let (parts, spans) = build_parts(builder, &Token::RBracket)?;
+    
+    if parts.len() == 0 {
+        builder.syntax_error("An array literal needs at least one value.".to_string());
+        return None;
+    }
+    
+    match parse_args(&parts) {
+        Ok(values) => {
+            let mut arg = ast::create_arg(AstArgType::ArrayL);
+            arg.sub_args = array_values(&values);
+            return Some(arg);
+        },
+        
+        Err((msg, pos)) => {
+            expr_error(builder, msg, pos, &spans);
+            return None;
+        },
+    }
+}
+
+// The parts of an expression, and the span of each part
+type ExprParts = (Vec<ExprPart>, Vec<(usize, usize)>);
+
+// Collects the tokens of an expression as parts, up to the end token
+// The span of each part is kept for error messages
+fn build_parts(builder : &mut AstBuilder, end : &Token) -> Option<ExprParts> {
+    let mut token = builder.get_token();
+    let mut parts : Vec<ExprPart> = Vec::new();
+    let mut spans : Vec<(usize, usize)> = Vec::new();
+    let mut last = Token::Unknown;
+    
+    // The end token only ends the arguments outside of parentheses and brackets
+    // A condition always ends with its line
+    let mut depth = 0;
+    
+    while token != *end || (depth > 0 && *end != Token::Eof) {
+        let span = builder.scanner.get_span();
+        
+        let part = match token {
+            Token::ByteL(val) => ExprPart::Arg(ast::create_byte(val)),
+            Token::ShortL(val) => ExprPart::Arg(ast::create_short(val)),
+            Token::IntL(val, ref suffix) => ExprPart::Arg(ast::create_typed_int(val, suffix.clone())),
+            Token::FloatL(val) => ExprPart::Arg(ast::create_float(val)),
+            Token::CharL(val) => ExprPart::Arg(ast::create_char(val)),
+            Token::StringL(ref val) => ExprPart::Arg(ast::create_string(val.to_string())),
+            Token::BoolL(val) => ExprPart::Arg(ast::create_bool(val)),
             
             Token::Id(ref val) => {
-                /*let mut arg = ast::create_arg(AstArgType::Id);
-                arg.str_val = val.to_string();*/
+                // Tables are read like arrays, so only single values are substituted
                 let arg = match &builder.global_consts.get(val) {
-                    Some(v) => v.value.clone(),
+                    Some(v) if v.values.len() == 0 => {
+                        // A negative constant reads as its literal after a '-'
+                        if v.negate {
+                            parts.push(ExprPart::Arg(ast::create_arg(AstArgType::OpSub)));
+                            spans.push(span);
+                        }
+                        
+                        v.value.clone()
+                    },
+                    
+                    None if val.contains('.') => build_field_arg(val),
                     
-                    None => {
+                    _ => {
                         let mut arg = ast::create_arg(AstArgType::Id);
                         arg.str_val = val.to_string();
                         arg
                     },
                 };
                 
-                if in_array {
-                    current_arg.sub_args.push(arg);
-                } else {
-                    args.push(arg);
-                }
+                ExprPart::Arg(arg)
             },
             
             Token::Sizeof => {
                 let arg = build_sizeof(&mut builder.scanner, &mut builder.syntax);
                 
                 if arg.arg_type == AstArgType::None {
-                    return false;
+                    return None;
                 }
                 
-                if in_array {
-                    current_arg.sub_args.push(arg);
-                } else {
-                    args.push(arg);
-                }
+                ExprPart::Arg(arg)
             },
             
+            Token::Null => ExprPart::Arg(ast::create_arg(AstArgType::Null)),
+            
             Token::AddrOf => {
                 let arg = build_addrof(&mut builder.scanner, &mut builder.syntax);
                 
                 if arg.arg_type == AstArgType::None {
-                    return false;
+                    return None;
                 }
                 
-                if in_array {
-                    current_arg.sub_args.push(arg);
-                } else {
-                    args.push(arg);
-                }
+                ExprPart::Arg(arg)
             },
             
-            Token::OpAdd => {
-                let arg = ast::create_arg(AstArgType::OpAdd);
+            // A '*' where an operand should be is a dereference
+            // It reads element zero of the pointer, the same as an array index
+            Token::OpMul if last == Token::Unknown || last == Token::LParen
+                    || last == Token::LBracket || is_operator(last.clone()) => {
+                token = builder.get_token();
                 
-                if in_array {
-                    current_arg.sub_args.push(arg);
-                } else {
-                    args.push(arg);
-                }
-            },
-            
-            Token::OpSub => {
-                let arg : AstArg;
-                if last == Token::Unknown || is_operator(last) {
-                    arg = ast::create_arg(AstArgType::OpNeg);
-                } else {
-                    arg = ast::create_arg(AstArgType::OpSub);
-                }
+                let mut arg = ast::create_arg(AstArgType::Id);
                 
-                if in_array {
-                    current_arg.sub_args.push(arg);
-                } else {
-                    args.push(arg);
+                match token {
+                    Token::Id(ref val) if !val.contains('.') => arg.str_val = val.to_string(),
+                    
+                    _ => {
+                        builder.syntax_error("Only a pointer variable can be dereferenced here.".to_string());
+                        return None;
+                    },
                 }
-            },
-            
-            Token::OpMul => {
-                let arg = ast::create_arg(AstArgType::OpMul);
                 
-                if in_array {
-                    current_arg.sub_args.push(arg);
-                } else {
-                    args.push(arg);
+                arg.sub_args.push(ast::create_int(0));
+                ExprPart::Arg(arg)
+            },
+            
+            // Whether a '-' is a negation is decided by the expression parser
+            Token::OpAdd => ExprPart::Arg(ast::create_arg(AstArgType::OpAdd)),
+            Token::OpSub => ExprPart::Arg(ast::create_arg(AstArgType::OpSub)),
+            Token::OpMul => ExprPart::Arg(ast::create_arg(AstArgType::OpMul)),
+            Token::OpDiv => ExprPart::Arg(ast::create_arg(AstArgType::OpDiv)),
+            Token::OpMod => ExprPart::Arg(ast::create_arg(AstArgType::OpMod)),
+            Token::OpEq => ExprPart::Arg(ast::create_arg(AstArgType::OpEq)),
+            Token::OpNeq => ExprPart::Arg(ast::create_arg(AstArgType::OpNeq)),
+            Token::OpLt => ExprPart::Arg(ast::create_arg(AstArgType::OpLt)),
+            Token::OpLte => ExprPart::Arg(ast::create_arg(AstArgType::OpLte)),
+            Token::OpGt => ExprPart::Arg(ast::create_arg(AstArgType::OpGt)),
+            Token::OpGte => ExprPart::Arg(ast::create_arg(AstArgType::OpGte)),
+            Token::OpAnd => ExprPart::Arg(ast::create_arg(AstArgType::OpAnd)),
+            Token::OpOr => ExprPart::Arg(ast::create_arg(AstArgType::OpOr)),
+            Token::OpXor => ExprPart::Arg(ast::create_arg(AstArgType::OpXor)),
+            Token::OpLeftShift => ExprPart::Arg(ast::create_arg(AstArgType::OpLeftShift)),
+            Token::OpRightShift => ExprPart::Arg(ast::create_arg(AstArgType::OpRightShift)),
+            Token::Range => ExprPart::Arg(ast::create_arg(AstArgType::Range)),
+            Token::And => ExprPart::Arg(ast::create_arg(AstArgType::And)),
+            Token::Or => ExprPart::Arg(ast::create_arg(AstArgType::Or)),
+            Token::Not => ExprPart::Arg(ast::create_arg(AstArgType::Not)),
+            
+            Token::LParen => {
+                depth += 1;
+                ExprPart::LParen
+            },
+            
+            Token::LBracket => {
+                depth += 1;
+                ExprPart::LBracket
+            },
+            
+            // An extra closing token is reported by the parser
+            Token::RParen | Token::RBracket => {
+                if depth > 0 {
+                    depth -= 1;
                 }
-            },
-            
-            Token::OpDiv => {
-                let arg = ast::create_arg(AstArgType::OpDiv);
                 
-                if in_array {
-                    current_arg.sub_args.push(arg);
+                if token == Token::RParen {
+                    ExprPart::RParen
                 } else {
-                    args.push(arg);
+                    ExprPart::RBracket
                 }
             },
             
-            Token::OpMod => {
-                let arg = ast::create_arg(AstArgType::OpMod);
-                
-                if in_array {
-                    current_arg.sub_args.push(arg);
-                } else {
-                    args.push(arg);
-                }
-            },
-            
-            Token::OpEq => {
-                let arg = ast::create_arg(AstArgType::OpEq);
-                args.push(arg);
-            },
-            
-            Token::OpNeq => {
-                let arg = ast::create_arg(AstArgType::OpNeq);
-                args.push(arg);
-            },
+            Token::Comma => ExprPart::Comma,
             
-            Token::OpLt => {
-                let arg = ast::create_arg(AstArgType::OpLt);
-                args.push(arg);
+            Token::Eof => {
+                token = builder.get_token();
+                continue;
             },
             
-            Token::OpLte => {
-                let arg = ast::create_arg(AstArgType::OpLte);
-                args.push(arg);
-            },
-            
-            Token::OpGt => {
-                let arg = ast::create_arg(AstArgType::OpGt);
-                args.push(arg);
-            },
-            
-            Token::OpGte => {
-                let arg = ast::create_arg(AstArgType::OpGte);
-                args.push(arg);
-            },
-            
-            Token::OpAnd => {
-                let arg = ast::create_arg(AstArgType::OpAnd);
-                args.push(arg);
-            },
-            
-            Token::OpOr => {
-                let arg = ast::create_arg(AstArgType::OpOr);
-                args.push(arg);
-            },
-            
-            Token::OpXor => {
-                let arg = ast::create_arg(AstArgType::OpXor);
-                args.push(arg);
-            },
-            
-            Token::OpLeftShift => {
-                let arg = ast::create_arg(AstArgType::OpLeftShift);
-                args.push(arg);
-            },
-            
-            Token::OpRightShift => {
-                let arg = ast::create_arg(AstArgType::OpRightShift);
-                args.push(arg);
-            },
-            
-            Token::Range => {
-                let arg = ast::create_arg(AstArgType::Range);
-                args.push(arg);
-            },
-            
-            Token::LBracket | Token::LParen => {
-                in_array = true;
-                
-                let arg = args.pop().unwrap();
-                current_arg = arg;
-            },
-            
-            Token::RBracket | Token::RParen => {
-                in_array = false;
-                args.push(current_arg.clone());
-            },
-            
-            Token::Comma => {},
-            Token::Eof => {},
-            
             _ => {
                 builder.syntax_error("Invalid token in expression.".to_string());
-                return false;
+                return None;
             },
-        }
-    
+        };
+        
+        parts.push(part);
+        spans.push(span);
+        
         last = token.clone();
         token = builder.get_token();
     }
     
-    for arg in args.iter() {
-        if end == Token::RBracket {
-            stmt.sub_args.push(arg.clone());
-        } else {
-            stmt.args.push(arg.clone());
-        }
-    }
-    
-    true
+    Some((parts, spans))
 }
 
-// Checks the order of operations in an expression
-pub fn check_operations(original_args : &Vec<AstArg>, keep_postfix : bool) -> Vec<AstArg> {
-    if original_args.len() < 4 && !keep_postfix {
-        return original_args.to_vec();
-    }
-
-    let mut args : Vec<AstArg> = Vec::new();
-    let mut operations : Vec<AstArg> = Vec::new();
+// Reports an expression error, pointing at the part the parser stopped at
+fn expr_error(builder : &mut AstBuilder, msg : String, pos : usize, spans : &Vec<(usize, usize)>) {
+    let error = builder.syntax_error(msg);
     
-    for arg in original_args.iter() {
-        match arg.arg_type {
-            AstArgType::OpAdd |
-            AstArgType::OpSub |
-            AstArgType::OpMul |
-            AstArgType::OpDiv |
-            AstArgType::OpMod |
-            AstArgType::OpAnd | AstArgType::OpOr | AstArgType::OpXor |
-            AstArgType::OpLeftShift | AstArgType::OpRightShift => {
-                loop {
-                    if operations.len() == 0 {
-                        break;
-                    }
-                    
-                    let top_type = &operations.last().unwrap().arg_type;
-                    
-                    if arg.arg_type == AstArgType::OpAdd || arg.arg_type == AstArgType::OpSub {
-                        let top = operations.pop().unwrap();
-                        args.push(top);
-                    } else if arg.arg_type == AstArgType::OpAnd || arg.arg_type == AstArgType::OpOr || arg.arg_type == AstArgType::OpXor ||
-                                arg.arg_type == AstArgType::OpLeftShift || arg.arg_type == AstArgType::OpRightShift {
-                        let top = operations.pop().unwrap();
-                        args.push(top);
-                    } else {
-                        match top_type {
-                            AstArgType::OpAdd | AstArgType::OpSub => {
-                                break;
-                            },
-                            
-                            AstArgType::OpAnd | AstArgType::OpOr | AstArgType::OpXor |
-                            AstArgType::OpLeftShift | AstArgType::OpRightShift => {
-                                break;  
-                            },
-                            
-                            _ => {
-                                let top = operations.pop().unwrap();
-                                args.push(top);
-                            },
-                        }
-                    }
-                }
-                
-                operations.push(arg.clone());
-            },
-            
-            _ => args.push(arg.clone()),
-        }
-    }
-    
-    while operations.len() > 0 {
-        let op = operations.pop().unwrap();
-        args.push(op.clone());
-    }
-    
-    if keep_postfix {
-        return args;
+    if let Some(span) = spans.get(pos) {
+        error.col_start = span.0;
+        error.col_end = span.1;
     }
+}
+
+// Builds a record field reference from a "name.field" identifier
+// The record variable is the string value, and the field is the only sub-argument
+pub fn build_field_arg(id_val : &String) -> AstArg {
+    let (name, field) = id_val.split_at(id_val.find('.').unwrap());
     
-    // Now, convert back to infix
-    let mut stack : Vec<Vec<AstArg>> = Vec::new();
-    let mut negate_next = false;
+    let mut field_arg = ast::create_arg(AstArgType::Id);
+    field_arg.str_val = field[1..].to_string();
     
-    for arg in args {
-        match arg.arg_type {
-            AstArgType::OpAdd |
-            AstArgType::OpSub |
-            AstArgType::OpMul |
-            AstArgType::OpDiv |
-            AstArgType::OpMod => {
-                let arg2 = stack.pop().unwrap();
-                let arg1 = stack.pop().unwrap();
-                let mut ln : Vec<AstArg> = Vec::new();
-                
-                let lparen = ast::create_arg(AstArgType::OpLParen);
-                let rparen = ast::create_arg(AstArgType::OpRParen);
-                
-                if arg1.len() > 2 {
-                    ln.push(lparen.clone());
-                    
-                    for a in arg1.iter() {
-                        ln.push(a.clone());
-                    }
-                    
-                    ln.push(rparen.clone());
-                } else {
-                    for a in arg1.iter() {
-                        ln.push(a.clone());
-                    }
-                }
-                
-                ln.push(arg.clone());
-                
-                if arg2.len() > 2 {
-                    ln.push(lparen);
-                    
-                    for a in arg2.iter() {
-                        ln.push(a.clone());
-                    }
-                    
-                    ln.push(rparen);
-                } else {
-                    for a in arg2.iter() {
-                        ln.push(a.clone());
-                    }
-                }
-                
-                stack.push(ln);
-            },
-            
-            AstArgType::OpNeg => negate_next = true,
-            
-            _ => {
-                let mut ln : Vec<AstArg> = Vec::new();
-                
-                if negate_next {
-                    let arg = ast::create_arg(AstArgType::OpNeg);
-                    ln.push(arg);
-                }
-                
-                ln.push(arg.clone());
-                stack.push(ln);
-                
-                negate_next = false;
-            },
-        }
-    }
-    
-    // TODO: This is dangerous
-    stack.pop().unwrap()
+    let mut arg = ast::create_arg(AstArgType::Field);
+    arg.str_val = name.to_string();
+    arg.sub_args.push(field_arg);
+    arg
 }
-
-
@@ -18,9 +18,10 @@
 use crate::ast;
 use crate::ast::*;
 use crate::lex::{Token, Lex};
-use crate::syntax::ErrorManager;
+use crate::syntax::*;
 
 use crate::ast_builder::AstBuilder;
+use crate::ast_expr::AstExpr;
 use crate::ast_utils::*;
 
 // Builds a variable declaration
@@ -43,7 +44,7 @@ pub fn build_var_dec(builder : &mut AstBuilder, name : String) -> bool {
             Token::Id(ref val) => extra_names.push(val.to_string()),
             
             _ => {
-                builder.syntax_error("Expected variable name.".to_string());
+                builder.syntax_error("Expected variable name.".to_string()).code(E_EXPECTED_NAME);
                 return false;
             },
         }
@@ -76,6 +77,22 @@ pub fn build_var_dec(builder : &mut AstBuilder, name : String) -> bool {
         Token::Double => dtype = DataType::Double,
         Token::Char => dtype = DataType::Char,
         Token::TStr => dtype = DataType::Str,
+        Token::Bool => dtype = DataType::Bool,
+        
+        Token::Ptr => {
+            dtype = build_ptr_type(builder);
+            if dtype == DataType::None {
+                return false;
+            }
+        },
+        
+        Token::Id(ref val) if ast::record_exists(&builder.tree, val) => {
+            dtype = DataType::Record(val.to_string());
+        },
+        
+        Token::Id(ref val) if ast::proc_exists(&builder.tree, val) => {
+            dtype = DataType::Proc(val.to_string());
+        },
         
         Token::Id(ref val) => {
             if !ast::enum_exists(&mut builder.tree, val.to_string()) {
@@ -87,17 +104,30 @@ pub fn build_var_dec(builder : &mut AstBuilder, name : String) -> bool {
         },
         
         _ => {
-            builder.syntax_error("Invalid type.".to_string());
+            builder.syntax_error("Invalid type.".to_string()).code(E_EXPECTED_TYPE);
             return false;
         },
     }
     
     // Check for arrays
+    // A record without an assignment gets its own storage
     token = builder.get_token();
+    let mut is_local_record = false;
     
     match token {
         Token::Assign => {},
         
+        Token::Semicolon => {
+            match dtype {
+                DataType::Record(_) => is_local_record = true,
+                
+                _ => {
+                    builder.syntax_error("Expected assignment operator.".to_string());
+                    return false;
+                },
+            }
+        },
+        
         Token::LBracket => {
             is_array = true;
             if !build_args(builder, &mut var_dec, Token::RBracket) {
@@ -114,19 +144,61 @@ pub fn build_var_dec(builder : &mut AstBuilder, name : String) -> bool {
     // If we have an array, make sure we have the proper syntax and end with the terminator
     // Otherwise, build the assignment
     if is_array {
+        if let DataType::Pointer(_) = dtype {
+            builder.syntax_error("Arrays of pointers are not supported.".to_string()).code(E_EXPECTED_TYPE);
+            return false;
+        }
+        
+        if dtype == DataType::Bool {
+            builder.syntax_error("Arrays of bool are not supported.".to_string()).code(E_EXPECTED_TYPE);
+            return false;
+        }
+        
         sub_type = dtype;
         dtype = DataType::Ptr;
         
-        if builder.get_token() != Token::Semicolon {
-            builder.syntax_error("Expected terminator.".to_string());
+        token = builder.get_token();
+        
+        // An array literal gives the size, or must match it
+        if token == Token::Assign {
+            if builder.get_token() != Token::LBracket {
+                builder.syntax_error("Arrays can only be initialized with an array literal.".to_string());
+                return false;
+            }
+            
+            let literal = match build_array_literal(builder) {
+                Some(arg) => arg,
+                None => return false,
+            };
+            
+            let count = literal.sub_args.len() as u64;
+            
+            if var_dec.sub_args.len() == 0 {
+                var_dec.sub_args.push(ast::create_int(count));
+                var_dec.sub_expr = Some(AstExpr::Operand(ast::create_int(count)));
+            } else if var_dec.sub_args.len() > 1 || var_dec.sub_args[0].arg_type != AstArgType::IntL
+                    || var_dec.sub_args[0].u64_val != count {
+                builder.syntax_error("The array literal does not match the array size.".to_string()).code(E_TYPE_MISMATCH);
+                return false;
+            }
+            
+            var_dec.args.push(literal);
+            token = builder.get_token();
+        }
+        
+        if token != Token::Semicolon {
+            builder.syntax_error("Expected terminator.".to_string()).code(E_TERMINATOR);
             return false;
         }
-    } else {
+    } else if !is_local_record {
         if !build_args(builder, &mut var_dec, Token::Semicolon) {
             return false;
         }
-        
-        var_dec.args = check_operations(&var_dec.args, builder.keep_postfix);
+    }
+    
+    // A pointer keeps the type it points to as the sub-type, like an array does
+    if let DataType::Pointer(ref pointee) = dtype {
+        sub_type = (**pointee).clone();
     }
     
     var_dec.data_type = dtype;
@@ -141,30 +213,56 @@ pub fn build_var_dec(builder : &mut AstBuilder, name : String) -> bool {
     true
 }
 
+// Builds the rest of a pointer type once "ptr" has been read
+// Pointers can only point to the built-in scalar types
+pub fn build_ptr_type(builder : &mut AstBuilder) -> DataType {
+    if builder.get_token() != Token::To {
+        builder.syntax_error("Expected \"to\" after \"ptr\".".to_string()).code(E_EXPECTED_TYPE);
+        return DataType::None;
+    }
+    
+    let pointee = match builder.get_token() {
+        Token::Byte => DataType::Byte,
+        Token::UByte => DataType::UByte,
+        Token::Short => DataType::Short,
+        Token::UShort => DataType::UShort,
+        Token::Int => DataType::Int,
+        Token::UInt => DataType::UInt,
+        Token::Int64 => DataType::Int64,
+        Token::UInt64 => DataType::UInt64,
+        Token::Float => DataType::Float,
+        Token::Double => DataType::Double,
+        Token::Char => DataType::Char,
+        Token::TStr => DataType::Str,
+        
+        _ => {
+            builder.syntax_error("Pointers can only point to numbers, characters, and strings.".to_string()).code(E_EXPECTED_TYPE);
+            return DataType::None;
+        },
+    };
+    
+    DataType::Pointer(Box::new(pointee))
+}
+
 // Builds a variable assignment
 fn build_var_assign_stmt(builder : &mut AstBuilder, var_assign : &mut AstStmt, name : String, assign_op : Token) -> bool {
     let mut check_end = false;
     
     match assign_op {
         Token::OpInc | Token::OpDec => {
-            let mut id_arg = ast::create_arg(AstArgType::Id);
-            if var_assign.stmt_type == AstStmtType::ArrayAssign {
-                id_arg.sub_args = var_assign.sub_args.clone();
-            }
+            let op = if assign_op == Token::OpInc {
+                AstArgType::OpAdd
+            } else {
+                AstArgType::OpSub
+            };
             
-            id_arg.str_val = name;
+            let (id_arg, target) = build_target(var_assign, name);
             var_assign.args.push(id_arg);
+            var_assign.args.push(ast::create_arg(op.clone()));
+            var_assign.args.push(ast::create_int(1));
             
-            if assign_op == Token::OpInc {
-                let op_arg = ast::create_arg(AstArgType::OpAdd);
-                var_assign.args.push(op_arg);
-            } else {
-                let op_arg = ast::create_arg(AstArgType::OpSub);
-                var_assign.args.push(op_arg);
-            }
-            
-            let num_arg = ast::create_int(1);
-            var_assign.args.push(num_arg);
+            let value = AstExpr::Operand(ast::create_int(1));
+            var_assign.expr = Some(AstExpr::Binary(op, Box::new(target), Box::new(value)));
             
             check_end = true;
         },
@@ -172,35 +270,26 @@ fn build_var_assign_stmt(builder : &mut AstBuilder, var_assign : &mut AstStmt, n
         Token::AddAssign | Token::SubAssign 
         | Token::MulAssign | Token::DivAssign 
         | Token::ModAssign => {
-            let mut id_arg = ast::create_arg(AstArgType::Id);
-            if var_assign.stmt_type == AstStmtType::ArrayAssign {
-                id_arg.sub_args = var_assign.sub_args.clone();
-            }
+            let op = match assign_op {
+                Token::AddAssign => AstArgType::OpAdd,
+                Token::SubAssign => AstArgType::OpSub,
+                Token::MulAssign => AstArgType::OpMul,
+                Token::DivAssign => AstArgType::OpDiv,
+                _ => AstArgType::OpMod,
+            };
             
-            id_arg.str_val = name;
+            let (id_arg, target) = build_target(var_assign, name);
             var_assign.args.push(id_arg);
-            
-            if assign_op == Token::AddAssign {
-                let op_arg = ast::create_arg(AstArgType::OpAdd);
-                var_assign.args.push(op_arg);
-            } else if assign_op == Token::SubAssign {
-                let op_arg = ast::create_arg(AstArgType::OpSub);
-                var_assign.args.push(op_arg);
-            } else if assign_op == Token::MulAssign {
-                let op_arg = ast::create_arg(AstArgType::OpMul);
-                var_assign.args.push(op_arg);
-            } else if assign_op == Token::DivAssign {
-                let op_arg = ast::create_arg(AstArgType::OpDiv);
-                var_assign.args.push(op_arg);
-            } else if assign_op == Token::ModAssign {
-                let op_arg = ast::create_arg(AstArgType::OpMod);
-                var_assign.args.push(op_arg);
-            }
+            var_assign.args.push(ast::create_arg(op.clone()));
             
             // Build the rest
             if !build_args(builder, var_assign, Token::Semicolon) {
                 return false;
             }
+            
+            if let Some(value) = var_assign.expr.take() {
+                var_assign.expr = Some(AstExpr::Binary(op, Box::new(target), Box::new(value)));
+            }
         },
         
         Token::Assign => {
@@ -218,7 +307,7 @@ fn build_var_assign_stmt(builder : &mut AstBuilder, var_assign : &mut AstStmt, n
     
     if check_end {
         if builder.get_token() != Token::Semicolon {
-            builder.syntax_error("Expected terminator.".to_string());
+            builder.syntax_error("Expected terminator.".to_string()).code(E_TERMINATOR);
             return false;
         }
     }
@@ -226,6 +315,34 @@ fn build_var_assign_stmt(builder : &mut AstBuilder, var_assign : &mut AstStmt, n
     true
 }
 
+// Builds the operand for the variable an increment or a compound assignment changes
+// The value starts with the variable, so it is returned both written out and as a tree
+fn build_target(var_assign : &AstStmt, name : String) -> (AstArg, AstExpr) {
+    let mut id_arg = ast::create_arg(AstArgType::Id);
+    id_arg.str_val = name;
+    
+    if var_assign.stmt_type == AstStmtType::FieldAssign {
+        id_arg.arg_type = AstArgType::Field;
+        id_arg.sub_args = var_assign.sub_args.clone();
+        
+        return (id_arg.clone(), AstExpr::Operand(id_arg));
+    }
+    
+    let target = match &var_assign.sub_expr {
+        Some(index) if var_assign.stmt_type == AstStmtType::ArrayAssign => {
+            AstExpr::Index(id_arg.clone(), Box::new(index.clone()))
+        },
+        
+        _ => AstExpr::Operand(id_arg.clone()),
+    };
+    
+    if var_assign.stmt_type == AstStmtType::ArrayAssign {
+        id_arg.sub_args = var_assign.sub_args.clone();
+    }
+    
+    (id_arg, target)
+}
+
 // Builds a variable assignment
 pub fn build_var_assign(builder : &mut AstBuilder, name : String, assign_op : Token) -> bool {
     let mut var_assign = ast::create_stmt(AstStmtType::VarAssign, &mut builder.scanner);
@@ -235,8 +352,6 @@ pub fn build_var_assign(builder : &mut AstBuilder, name : String, assign_op : To
         return false;
     }
     
-    var_assign.args = check_operations(&var_assign.args, builder.keep_postfix);
-    
     builder.add_stmt(var_assign);
     true
 }
@@ -258,13 +373,177 @@ pub fn build_array_assign(builder : &mut AstBuilder, id_val : String) -> bool {
         return false;
     }
     
-    array_assign.args = check_operations(&array_assign.args, builder.keep_postfix);
-    
     builder.add_stmt(array_assign);
     
     true
 }
 
+// Builds an assignment through a pointer ("*p = x;")
+// A dereference is element zero, so this is an array assignment with a fixed index
+pub fn build_deref_assign(builder : &mut AstBuilder) -> bool {
+    let mut deref_assign = ast::create_stmt(AstStmtType::ArrayAssign, &mut builder.scanner);
+    
+    let name = match builder.get_token() {
+        Token::Id(ref val) if !val.contains('.') => val.to_string(),
+        
+        _ => {
+            builder.syntax_error("Expected pointer name after \'*\'.".to_string()).code(E_EXPECTED_NAME);
+            return false;
+        },
+    };
+    
+    deref_assign.name = name.clone();
+    deref_assign.sub_args.push(ast::create_int(0));
+    deref_assign.sub_expr = Some(AstExpr::Operand(ast::create_int(0)));
+    
+    let assign_op = builder.get_token();
+    
+    if !build_var_assign_stmt(builder, &mut deref_assign, name, assign_op) {
+        return false;
+    }
+    
+    builder.add_stmt(deref_assign);
+    
+    true
+}
+
+// Builds a record field assignment
+pub fn build_field_assign(builder : &mut AstBuilder, id_val : String, assign_op : Token) -> bool {
+    let mut field_assign = ast::create_stmt(AstStmtType::FieldAssign, &mut builder.scanner);
+    
+    let field = build_field_arg(&id_val);
+    field_assign.name = field.str_val.clone();
+    field_assign.sub_args = field.sub_args.clone();
+    
+    if !build_var_assign_stmt(builder, &mut field_assign, field.str_val, assign_op) {
+        return false;
+    }
+    
+    builder.add_stmt(field_assign);
+    
+    true
+}
+
+// Builds a global variable declaration
+// Globals can only be initialized with literal values; otherwise they are zeroed
+pub fn build_global(builder : &mut AstBuilder, name : String, is_extern : bool) -> bool {
+    let mut token = builder.get_token();
+    
+    // An external declaration is either a function or a variable
+    if token != Token::Colon && is_extern {
+        builder.syntax_error("Expected \"func\" keyword.".to_string());
+        return false;
+    } else if token != Token::Colon {
+        builder.syntax_error("Expected \':\' after variable name.".to_string());
+        return false;
+    }
+    
+    let data_type : DataType;
+    token = builder.get_token();
+    
+    match token {
+        Token::Byte => data_type = DataType::Byte,
+        Token::UByte => data_type = DataType::UByte,
+        Token::Short => data_type = DataType::Short,
+        Token::UShort => data_type = DataType::UShort,
+        Token::Int => data_type = DataType::Int,
+        Token::UInt => data_type = DataType::UInt,
+        Token::Int64 => data_type = DataType::Int64,
+        Token::UInt64 => data_type = DataType::UInt64,
+        Token::Char => data_type = DataType::Char,
+        Token::TStr => data_type = DataType::Str,
+        Token::Bool => data_type = DataType::Bool,
+        
+        _ => {
+            builder.syntax_error("Invalid type for global variable.".to_string()).code(E_EXPECTED_TYPE);
+            return false;
+        },
+    }
+    
+    let mut value = ast::create_arg(AstArgType::None);
+    token = builder.get_token();
+    
+    if token == Token::Assign {
+        if is_extern {
+            builder.syntax_error("External variables cannot be initialized.".to_string());
+            return false;
+        }
+        
+        token = builder.get_token();
+        
+        // Negative integer literals
+        let mut negate = false;
+        
+        if token == Token::OpSub {
+            token = builder.get_token();
+            negate = true;
+            
+            if !matches!(token, Token::IntL(_, _)) {
+                builder.syntax_error("Globals can only be initialized with literal values.".to_string());
+                return false;
+            }
+        }
+        
+        match token {
+            Token::ByteL(val) => value = ast::create_byte(val),
+            Token::ShortL(val) => value = ast::create_short(val),
+            
+            Token::IntL(val, suffix) => {
+                value = ast::create_typed_int(val, suffix);
+                
+                if let Err(msg) = ast::check_int_literal(&value, negate, &data_type) {
+                    builder.syntax_error(msg).code(E_LITERAL_RANGE);
+                    return false;
+                }
+                
+                if negate {
+                    value.u64_val = (val as i64).wrapping_neg() as u64;
+                }
+            },
+            
+            Token::CharL(val) => value = ast::create_char(val),
+            Token::StringL(ref val) if data_type == DataType::Str => value = ast::create_string(val.to_string()),
+            Token::BoolL(val) if data_type == DataType::Bool => value = ast::create_bool(val),
+            
+            _ => {
+                builder.syntax_error("Globals can only be initialized with literal values.".to_string());
+                return false;
+            },
+        }
+        
+        token = builder.get_token();
+    }
+    
+    if token != Token::Semicolon {
+        builder.syntax_error("Expected terminator.".to_string()).code(E_TERMINATOR);
+        return false;
+    }
+    
+    // The same header may be included more than once
+    if ast::global_exists(&builder.tree, &name) {
+        if is_extern {
+            return true;
+        }
+        
+        builder.syntax_error("Duplicate global variable.".to_string()).code(E_DUPLICATE);
+        return false;
+    }
+    
+    let global = AstGlobal {
+        name : name,
+        data_type : data_type,
+        value : value,
+        is_extern : is_extern,
+        
+        line_no : builder.scanner.get_line_no(),
+        line : builder.scanner.get_current_line(),
+    };
+    
+    builder.tree.globals.push(global);
+    
+    true
+}
+
 // Builds a sizeof operation
 pub fn build_sizeof(scanner : &mut Lex, syntax : &mut ErrorManager) -> AstArg {
     let mut sizeof = ast::create_arg(AstArgType::Sizeof);
@@ -286,7 +565,7 @@ pub fn build_sizeof(scanner : &mut Lex, syntax : &mut ErrorManager) -> AstArg {
         },
         
         _ => {
-            syntax.syntax_error(scanner, "Expected variable name.".to_string());
+            syntax.syntax_error(scanner, "Expected variable name.".to_string()).code(E_EXPECTED_NAME);
             return ast::create_arg(AstArgType::None);
         },
     }
@@ -307,7 +586,7 @@ pub fn build_addrof(scanner : &mut Lex, syntax : &mut ErrorManager) -> AstArg {
         },
         
         _ => {
-            syntax.syntax_error(scanner, "Expected variable name.".to_string());
+            syntax.syntax_error(scanner, "Expected variable name.".to_string()).code(E_EXPECTED_NAME);
             return ast::create_arg(AstArgType::None);
         },
     }
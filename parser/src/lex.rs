@@ -15,6 +15,9 @@
 // with this program; if not, write to the Free Software Foundation, Inc.,
 // 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
 
+use std::rc::Rc;
+
+use crate::ast::DataType;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
@@ -26,6 +29,9 @@ pub enum Token {
     Use,
     
     Enum,
+    Type,
+    Is,
+    Record,
     
     Extern,
     Func,
@@ -41,6 +47,9 @@ pub enum Token {
     For,
     Break,
     Continue,
+    Case,
+    When,
+    Others,
     
     Const,
     Byte,
@@ -55,6 +64,7 @@ pub enum Token {
     Double,
     Char,
     TStr,
+    Bool,
     
     LParen,
     RParen,
@@ -72,11 +82,18 @@ pub enum Token {
     Comma,
     Semicolon,
     Arrow,
+    FatArrow,
     Range,
     In,
     Any,
     Sizeof,
     AddrOf,
+    Ptr,
+    To,
+    Null,
+    And,
+    Or,
+    Not,
     
     OpAdd,
     OpSub,
@@ -101,155 +118,477 @@ pub enum Token {
     Id(String),
     ByteL(u8),
     ShortL(u16),
-    IntL(u64),
+    IntL(u64, DataType),        // The value, and the type given by a suffix (or None)
     FloatL(f64),
     CharL(char),
     StringL(String),
+    BoolL(bool),
 }
 
-#[derive(Clone)]
-pub struct Line {
-    input : String,
-    line_no : i32,
-    all_tokens : Vec<Token>,
+// Where a token is in the source
+// Columns count characters and start at 1. The end column is one past the token on the line
+// where it starts, so a string running over several lines ends with its first line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub file : Rc<str>,
+    pub offset : usize,         // In bytes, from the start of the file
+    pub line_no : i32,
+    pub col : usize,
+    pub end_col : usize,
 }
 
 #[derive(Clone)]
 pub struct Lex {
-    current_input : String,
-    current_line_no : i32,
-    current_line : usize,
+    file : Rc<str>,
+    source : Rc<str>,
+    line_starts : Vec<usize>,
+    tokens : Vec<Token>,
+    spans : Vec<Span>,
     pos : usize,
+    current_span : Span,
+    
+    // The scanning position, and how far the lines and columns have been counted
     index : usize,
-    lines : Vec<Line>,
-    current_tokens : Vec<Token>,
+    mark : usize,
+    mark_line : i32,
+    mark_col : usize,
 }
 
 impl Lex {
+    // The text of the line holding the current token, without its indentation
     pub fn get_current_line(&mut self) -> String {
-        self.current_input.clone()
+        self.line_text(self.current_span.line_no).trim().to_string()
+    }
+    
+    // The text of the lines from the given one through the current token's
+    pub fn get_lines_from(&mut self, first : i32) -> String {
+        let mut lines : Vec<&str> = Vec::new();
+        
+        for line_no in first..=self.current_span.line_no {
+            lines.push(self.line_text(line_no).trim());
+        }
+        
+        lines.join("\n")
     }
     
     pub fn get_line_no(&mut self) -> i32 {
-        self.current_line_no
+        self.current_span.line_no
+    }
+    
+    // The column where the text of the current line starts
+    pub fn get_line_col(&mut self) -> usize {
+        let text = self.line_text(self.current_span.line_no);
+        text.chars().count() - text.trim_start().chars().count() + 1
+    }
+    
+    // The columns of the last token; the end is one past the token
+    pub fn get_span(&mut self) -> (usize, usize) {
+        (self.current_span.col, self.current_span.end_col)
+    }
+    
+    pub fn get_file(&mut self) -> String {
+        self.file.to_string()
+    }
+    
+    pub fn set_file(&mut self, file : String) {
+        self.file = Rc::from(file);
     }
 
-    pub fn tokenize(&mut self, input : String, line_no : i32) {
-        let mut line = Line {
-            input : input.clone(),
-            line_no : line_no,
-            all_tokens : Vec::new(),
-        };
+    fn line_text(&self, line_no : i32) -> &str {
+        if line_no < 1 || line_no as usize > self.line_starts.len() {
+            return "";
+        }
+        
+        let text = &self.source[self.line_starts[(line_no - 1) as usize]..];
+        
+        match text.find('\n') {
+            Some(end) => return &text[..end],
+            None => return text,
+        }
+    }
     
-        self.index = 0;
-        let mut current = String::new();
-        let mut in_quote = false;
+    // Scans a whole source file
+    // The tokens are grouped into lines, each starting with Eof. A line only ends at a line
+    // break outside of a string, so strings (and block comments) can run over several lines.
+    pub fn tokenize(&mut self, source : &str) -> Result<(), String> {
+        self.source = Rc::from(source);
+        
+        let bytes = source.as_bytes();
+        let length = bytes.len();
         
-        let length = input.len();
+        let mut new_line = true;
+        let mut in_word = false;
+        let mut start = 0;
+        
+        self.index = 0;
         
         while self.index < length {
-            let c = input.chars().nth(self.index).unwrap();
-            let c2 : char;
-            if self.index + 1 < length {
-                c2 = input.chars().nth(self.index+1).unwrap();
+            let c = bytes[self.index] as char;
+            let c2 = if self.index + 1 < length {
+                bytes[self.index + 1] as char
             } else {
-                c2 = '\0';
+                '\0'
+            };
+            
+            // Words are everything up to a symbol, a space, a quote or a comment
+            let is_word = !self.is_symbol(c) && !c.is_ascii_whitespace()
+                && c != '\"' && c != '\'' && c != '#';
+            
+            if is_word {
+                if !in_word {
+                    in_word = true;
+                    start = self.index;
+                }
+                
+                self.index += 1;
+                continue;
             }
             
-            // Return if we have a comment
+            if in_word {
+                in_word = false;
+                
+                let token = self.scan_word(start, self.index)?;
+                self.push_token(token, start, self.index, &mut new_line);
+            }
+            
+            // Line comments
             if c == '#' {
-                return;
+                while self.index < length && bytes[self.index] != b'\n' {
+                    self.index += 1;
+                }
+                
+                continue;
+            }
+        
+            // Block comments; one running over a line break also ends the line
+            if c == '/' && c2 == '*' {
+                let end = match source[self.index + 2..].find("*/") {
+                    Some(n) => self.index + 2 + n + 2,
+                    None => return self.scan_error(self.index, self.index + 2, "Unterminated comment."),
+                };
+                
+                if source[self.index..end].contains('\n') {
+                    new_line = true;
+                }
+                
+                self.index = end;
+                continue;
             }
             
             // Check to see if we have a char literal
             if c == '\'' {
-                let c = input.chars().nth(self.index+1).unwrap();
-                
-                let token = Token::CharL(c);
-                line.all_tokens.push(token);
+                let (val, end) = self.scan_char(self.index)?;
+                self.push_token(Token::CharL(val), self.index, end, &mut new_line);
                 
-                self.index += 3;
+                self.index = end;
                 continue;
             }
             
-            // Check to see if we are in a quote (string literal)
+            // String literals
             if c == '\"' {
-                if in_quote {
-                    in_quote = false;
-                    
-                    let token = Token::StringL(current);
-                    line.all_tokens.push(token);
-                    current = String::new();
-                } else {
-                    in_quote = true;
-                }
+                let (val, end) = self.scan_string(self.index)?;
+                self.push_token(Token::StringL(val), self.index, end, &mut new_line);
                 
-                self.index += 1;
+                self.index = end;
                 continue;
             }
             
-            if in_quote {
-                current.push(c);
-                self.index += 1;
-                continue;
-            }
-        
-            // Otherwise, do other checks
-            if self.is_symbol(c) {
-                if current.len() > 0 {
-                    let token = self.get_keyword(current);
-                    line.all_tokens.push(token);
-                    current = String::new();
-                }
+            if c == '\n' {
+                new_line = true;
+            } else if self.is_symbol(c) {
+                start = self.index;
                 
                 let symbol = self.get_symbol(c, c2);
-                line.all_tokens.push(symbol);
-            } else if c == ' ' || c == '\t' {
-                if current.len() > 0 {
-                    let token = self.get_keyword(current);
-                    line.all_tokens.push(token);
-                    current = String::new();
-                }
-            } else {
-                current.push(c);
+                self.push_token(symbol, start, self.index + 1, &mut new_line);
             }
             
             self.index += 1;
         }
         
-        if current.len() > 0 {
-            let token = self.get_keyword(current);
-            line.all_tokens.push(token);
+        if in_word {
+            let token = self.scan_word(start, length)?;
+            self.push_token(token, start, length, &mut new_line);
         }
         
-        self.lines.push(line);
+        self.count_to(length);
+        Ok(())
     }
     
-    pub fn get_token(&mut self) -> Token {
-        let token : Token;
+    // Scans a word, which is a number if it starts with a digit
+    fn scan_word(&mut self, start : usize, end : usize) -> Result<Token, String> {
+        if self.source.as_bytes()[start].is_ascii_digit() {
+            return self.scan_number(start, end);
+        }
+        
+        Ok(self.get_keyword(self.source[start..end].to_string()))
+    }
+    
+    // Scans a number: decimal, "0x" hex, "0o" octal or "0b" binary, with "_" allowed between digits
+    // An integer can end with the name of a type, which then becomes its type
+    fn scan_number(&mut self, start : usize, end : usize) -> Result<Token, String> {
+        let word = self.source[start..end].to_string();
+        let mut text = word.as_str();
+        let mut suffix = DataType::None;
+        
+        let suffixes = [
+            ("uint64", DataType::UInt64), ("int64", DataType::Int64),
+            ("ubyte", DataType::UByte), ("byte", DataType::Byte),
+            ("ushort", DataType::UShort), ("short", DataType::Short),
+            ("uint", DataType::UInt), ("int", DataType::Int),
+        ];
+        
+        for (name, data_type) in suffixes.iter() {
+            if text.len() > name.len() && text.ends_with(name) {
+                text = &text[..text.len() - name.len()];
+                suffix = data_type.clone();
+                break;
+            }
+        }
+        
+        let (radix, digits) = match text.get(..2) {
+            Some("0x") => (16, &text[2..]),
+            Some("0o") => (8, &text[2..]),
+            Some("0b") => (2, &text[2..]),
+            _ => (10, text),
+        };
+        
+        let digits = digits.replace('_', "");
+        
+        // Anything else starting with a digit has to be a float
+        if radix == 10 && suffix == DataType::None && !digits.bytes().all(|c| c.is_ascii_digit()) {
+            match word.replace('_', "").parse::<f64>() {
+                Ok(val) => return Ok(Token::FloatL(val)),
+                Err(_e) => return self.scan_error(start, end, "Invalid number."),
+            }
+        }
+        
+        if digits.len() == 0 || !digits.chars().all(|c| c.is_digit(radix)) {
+            return self.scan_error(start, end, "Invalid integer literal.");
+        }
+        
+        let val = match u64::from_str_radix(&digits, radix) {
+            Ok(val) => val,
+            Err(_e) => return self.scan_error(start, end, "Integer literal is too large."),
+        };
+        
+        // Plain hex literals are sized by their digits, so they work as bit patterns
+        if radix == 16 && suffix == DataType::None {
+            if digits.len() <= 2 {
+                return Ok(Token::ByteL(val as u8));
+            } else if digits.len() <= 4 {
+                return Ok(Token::ShortL(val as u16));
+            }
+        }
+        
+        Ok(Token::IntL(val, suffix))
+    }
+    
+    // Scans a character literal starting at its opening quote
+    // Returns the value and the position just past the closing quote
+    fn scan_char(&mut self, start : usize) -> Result<(char, usize), String> {
+        let (val, end) = match self.source[start + 1..].chars().next() {
+            Some('\\') => self.scan_escape(start + 1, false)?,
+            Some('\'') => return self.scan_error(start, start + 2, "Empty character literal."),
+            Some('\n') | None => return self.scan_error(start, start + 1, "Unterminated character literal."),
+            Some(val) => (val, start + 1 + val.len_utf8()),
+        };
+        
+        if !self.source[end..].starts_with('\'') {
+            return self.scan_error(start, end, "Unterminated character literal.");
+        }
+        
+        if val as u32 > 0xFF {
+            return self.scan_error(start, end + 1, "Character literal does not fit in a byte.");
+        }
+        
+        Ok((val, end + 1))
+    }
+    
+    // Scans a string literal starting at its opening quote
+    // Escapes are decoded, and a line break inside the string becomes part of its value
+    fn scan_string(&mut self, start : usize) -> Result<(String, usize), String> {
+        let mut val = String::new();
+        let mut pos = start + 1;
+        
+        loop {
+            let c = match self.source[pos..].chars().next() {
+                Some(c) => c,
+                None => return self.scan_error(start, start + 1, "Unterminated string."),
+            };
+            
+            match c {
+                '\"' => return Ok((val, pos + 1)),
+                
+                '\\' => {
+                    let (c, next) = self.scan_escape(pos, true)?;
+                    val.push(c);
+                    pos = next;
+                },
+                
+                '\r' if self.source[pos + 1..].starts_with('\n') => pos += 1,
+                
+                c => {
+                    val.push(c);
+                    pos += c.len_utf8();
+                },
+            }
+        }
+    }
+    
+    // Decodes an escape sequence starting at its backslash
+    // Returns the character and the position just past the sequence
+    fn scan_escape(&mut self, start : usize, in_string : bool) -> Result<(char, usize), String> {
+        let c = match self.source[start + 1..].chars().next() {
+            Some(c) => c,
+            None => return self.scan_error(start, start + 1, "Unknown escape sequence."),
+        };
         
-        if self.pos >= self.current_tokens.len() && self.current_line >= self.lines.len() {
-            token = Token::EoI;
-        } else if self.pos >= self.current_tokens.len() {
-            self.current_tokens = self.lines[self.current_line].all_tokens.clone();
+        match c {
+            'n' => return Ok(('\n', start + 2)),
+            't' => return Ok(('\t', start + 2)),
+            'r' => return Ok(('\r', start + 2)),
+            '0' => return Ok(('\0', start + 2)),
+            '\\' => return Ok(('\\', start + 2)),
+            '\"' => return Ok(('\"', start + 2)),
+            '\'' => return Ok(('\'', start + 2)),
             
-            self.pos = 0;
-            self.current_line += 1;
+            // \xHH is a single byte; in a string only ASCII fits, so \u{...} is needed above that
+            'x' => {
+                let digits = self.source.get(start + 2..start + 4).unwrap_or("");
+                
+                let val = match u32::from_str_radix(digits, 16) {
+                    Ok(val) if digits.len() == 2 && !digits.starts_with('+') => val,
+                    _ => return self.scan_error(start, start + 2, "Invalid hex escape; expected two hex digits."),
+                };
+                
+                if in_string && val > 0x7F {
+                    return self.scan_error(start, start + 4, "Hex escape out of range in a string; use \\u{...} instead.");
+                }
+                
+                return Ok((char::from_u32(val).unwrap(), start + 4));
+            },
+            
+            'u' => {
+                let end = match self.source[start + 2..].find('}') {
+                    Some(n) if self.source[start + 2..].starts_with('{') => start + 2 + n,
+                    _ => return self.scan_error(start, start + 2, "Invalid unicode escape; expected \\u{...}."),
+                };
+                
+                let digits = &self.source[start + 3..end];
+                let val = match u32::from_str_radix(digits, 16) {
+                    Ok(val) if digits.len() <= 6 && !digits.starts_with('+') => char::from_u32(val),
+                    _ => None,
+                };
+                
+                match val {
+                    Some(val) => return Ok((val, end + 1)),
+                    None => return self.scan_error(start, end + 1, "Invalid unicode escape."),
+                }
+            },
+            
+            c => return self.scan_error(start, start + 1 + c.len_utf8(), "Unknown escape sequence."),
+        }
+    }
+    
+    // Adds a token, starting a new line first if a line break came before it
+    fn push_token(&mut self, token : Token, start : usize, end : usize, new_line : &mut bool) {
+        if *new_line {
+            // The end of a line sits just past its last token
+            let eof = match self.spans.last() {
+                Some(span) => Span {
+                    col : span.end_col,
+                    end_col : span.end_col + 1,
+                    ..span.clone()
+                },
+                
+                None => Span {
+                    file : self.file.clone(),
+                    offset : 0,
+                    line_no : 0,
+                    col : 1,
+                    end_col : 2,
+                },
+            };
             
-            token = Token::Eof;
-        } else {
-            if self.pos == 0 && self.current_line > 0 {
-                self.current_input = self.lines[self.current_line-1].input.clone();
-                self.current_line_no = self.lines[self.current_line-1].line_no;
+            self.tokens.push(Token::Eof);
+            self.spans.push(eof);
+            *new_line = false;
+        }
+        
+        let span = self.span(start, end);
+        self.tokens.push(token);
+        self.spans.push(span);
+    }
+    
+    fn span(&mut self, start : usize, end : usize) -> Span {
+        let (line_no, col) = self.count_to(start);
+        let (end_line, mut end_col) = self.count_to(end);
+        
+        if end_line != line_no {
+            let text = &self.source[start..];
+            let first = &text[..text.find('\n').unwrap()];
+            end_col = col + first.trim_end_matches('\r').chars().count();
+        }
+        
+        Span {
+            file : self.file.clone(),
+            offset : start,
+            line_no : line_no,
+            col : col,
+            end_col : end_col,
+        }
+    }
+    
+    // Counts the lines and columns up to a position
+    // Positions only move forward, so each character is only counted once
+    fn count_to(&mut self, pos : usize) -> (i32, usize) {
+        let source = self.source.clone();
+        let bytes = source.as_bytes();
+        
+        while self.mark < pos && self.mark < bytes.len() {
+            if bytes[self.mark] == b'\n' {
+                self.mark_line += 1;
+                self.mark_col = 1;
+                self.line_starts.push(self.mark + 1);
+            } else if bytes[self.mark] & 0xC0 != 0x80 {
+                self.mark_col += 1;
             }
             
-            token = self.current_tokens[self.pos].clone();
-            self.pos += 1;
+            self.mark += 1;
+        }
+        
+        (self.mark_line, self.mark_col)
+    }
+    
+    // Points the current span at a problem found while scanning
+    fn scan_error<T>(&mut self, start : usize, end : usize, msg : &str) -> Result<T, String> {
+        self.current_span = self.span(start, end);
+        self.count_to(self.source.len());
+        
+        Err(msg.to_string())
+    }
+    
+    pub fn get_token(&mut self) -> Token {
+        if self.pos >= self.tokens.len() {
+            return Token::EoI;
         }
         
+        let token = self.tokens[self.pos].clone();
+        self.current_span = self.spans[self.pos].clone();
+        self.pos += 1;
+        
         token
     }
     
+    // Returns the next token without moving past it
+    pub fn peek_token(&self) -> Token {
+        if self.pos >= self.tokens.len() {
+            return Token::EoI;
+        }
+        
+        self.tokens[self.pos].clone()
+    }
+    
     // Checks to see if a given character is a symbol or part of one
     fn is_symbol(&self, c : char) -> bool {
         match c {
@@ -289,6 +628,9 @@ impl Lex {
                 if c2 == '=' {
                     self.index += 1;
                     return Token::OpEq;
+                } else if c2 == '>' {
+                    self.index += 1;
+                    return Token::FatArrow;
                 }
                 
                 return Token::Assign;
@@ -397,31 +739,11 @@ impl Lex {
     
     // Returns a keyword for a given buffer
     fn get_keyword(&self, current : String) -> Token {
-        // Check to see if we have a literal
-        // Start with hex
-        match current.get(..2) {
-            Some("0x") => {
-                let base = current.trim_start_matches("0x");
-                
-                if base.len() > 4 && base.len() <= 16 {
-                    return Token::IntL(u64::from_str_radix(base, 16).unwrap());
-                } else if base.len() == 4 || base.len() == 3 {
-                    return Token::ShortL(u16::from_str_radix(base, 16).unwrap());
-                } else {
-                    return Token::ByteL(u8::from_str_radix(base, 16).unwrap());
-                }
-            },
-            
-            _ => {},
-        }
-        
-        // Check other literals
-        if current.parse::<u64>().is_ok() {
-            return Token::IntL(current.parse::<u64>().unwrap());
-        } else if current.parse::<f64>().is_ok() {
+        // Floats can also start with a dot
+        if current.parse::<f64>().is_ok() {
             return Token::FloatL(current.parse::<f64>().unwrap());
         }
-    
+        
         // If not, it must be a keyword
         let token : Token;
         
@@ -431,6 +753,9 @@ impl Lex {
             "module" => token = Token::Module,
             "use" => token = Token::Use,
             "enum" => token = Token::Enum,
+            "type" => token = Token::Type,
+            "is" => token = Token::Is,
+            "record" => token = Token::Record,
             "extern" => token = Token::Extern,
             "func" => token = Token::Func,
             "begin" => token = Token::Begin,
@@ -450,6 +775,7 @@ impl Lex {
             "double" => token = Token::Double,
             "char" => token = Token::Char,
             "str" => token = Token::TStr,
+            "bool" => token = Token::Bool,
             "if" => token = Token::If,
             "elif" => token = Token::Elif,
             "else" => token = Token::Else,
@@ -458,7 +784,18 @@ impl Lex {
             "in" => token = Token::In,
             "break" => token = Token::Break,
             "continue" => token = Token::Continue,
+            "case" => token = Token::Case,
+            "when" => token = Token::When,
+            "others" => token = Token::Others,
             "sizeof" => token = Token::Sizeof,
+            "ptr" => token = Token::Ptr,
+            "to" => token = Token::To,
+            "null" => token = Token::Null,
+            "true" => token = Token::BoolL(true),
+            "false" => token = Token::BoolL(false),
+            "and" => token = Token::And,
+            "or" => token = Token::Or,
+            "not" => token = Token::Not,
             _ => token = Token::Id(current.clone()),
         };
         
@@ -468,12 +805,22 @@ impl Lex {
 
 pub fn create_lex() -> Lex {
     Lex {
-        current_line_no : 0,
-        current_input : String::new(),
-        current_line : 0,
+        file : Rc::from(""),
+        source : Rc::from(""),
+        line_starts : vec![0],
+        tokens : Vec::new(),
+        spans : Vec::new(),
         pos : 0,
+        current_span : Span {
+            file : Rc::from(""),
+            offset : 0,
+            line_no : 0,
+            col : 1,
+            end_col : 1,
+        },
         index : 0,
-        lines : Vec::new(),
-        current_tokens : Vec::new(),
+        mark : 0,
+        mark_line : 1,
+        mark_col : 1,
     }
 }
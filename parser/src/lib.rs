@@ -21,8 +21,10 @@ pub mod ast;
 pub mod llir;
 pub mod syntax;
 pub mod module;
+pub mod typeck;
 
 mod ast_builder;
+pub mod ast_expr;
 mod ast_func;
 mod ast_flow;
 mod ast_utils;
@@ -30,7 +32,9 @@ mod ast_var;
 mod lex;
 
 mod llir_builder;
+mod llir_flow;
 mod llir_func;
+mod llir_record;
 mod llir_var;
 
 #[derive(PartialEq, Clone, Copy)]
@@ -40,50 +44,77 @@ pub enum Arch {
     Riscv64,
 }
 
+// The runtime checks the builders add to the code (--checks)
+// "All" turns on every check there is; for now, that is only bounds checking
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Checks {
+    None,
+    Bounds,
+    All,
+}
+
+impl Checks {
+    pub fn bounds(&self) -> bool {
+        *self != Checks::None
+    }
+}
+
 // Import what we need
 use std::path::Path;
 
 use ast::AstTree;
 use llir::LLirFile;
+use syntax::Diagnostic;
 
 // Returns the ast
-pub fn get_ast(path : &String, arch : Arch, include_core : bool, keep_postfix : bool) -> Result<AstTree, ()> {
+// The tree is type checked before it is returned, so both backends get the same annotated tree
+// On failure, the diagnostics are returned for the caller to print
+pub fn get_ast(path : &String, arch : Arch, include_core : bool) -> Result<AstTree, Vec<Diagnostic>> {
     let name = get_name(path);
-    let tree = match ast_builder::build_ast(path.to_string(), arch, name.clone(), include_core, keep_postfix) {
+    let mut tree = match ast_builder::build_ast(path.to_string(), arch, name.clone(), include_core) {
         Ok(tree) => tree,
-        Err(_e) => return Err(()),
+        Err(errors) => return Err(errors),
     };
     
+    let mut syntax = syntax::create_error_manager(path.clone());
+    let mut checker = typeck::new_type_checker(&mut syntax);
+    
+    match checker.check(&mut tree) {
+        Ok(()) => {},
+        Err(errors) => return Err(errors),
+    }
+    
     Ok(tree)
 }
 
 // The parse function for the LLIR layer
 // This will eventually replace the function above
-pub fn parse2(path : String, arch : Arch, include_core : bool) -> Result<LLirFile, ()> {
-    let tree = match get_ast(&path.to_string(), arch, include_core, true) {
+pub fn parse2(path : String, arch : Arch, include_core : bool, checks : Checks) -> Result<LLirFile, Vec<Diagnostic>> {
+    let tree = match get_ast(&path.to_string(), arch, include_core) {
         Ok(tree) => tree,
-        Err(_e) => return Err(()),
+        Err(errors) => return Err(errors),
     };
     
     if tree.module.len() > 0 {
         match module::generate_module(&tree) {
             Ok(()) => {},
             Err(_e) => {
-                println!("Error generating module header");
-                return Err(());
+                let error = syntax::file_error(&path, syntax::E_MODULE, "Error generating module header".to_string());
+                return Err(vec![error]);
             },
         }
     }
     
-    let mut syntax = syntax::create_error_manager();
+    let mut syntax = syntax::create_error_manager(path.clone());
     let name = get_name(&path);
     
-    let mut llir_builder = llir_builder::new_llir_builder(name.clone(), &mut syntax);
-    let llir = match llir_builder.build_llir(&tree) {
+    let mut llir_builder = llir_builder::new_llir_builder(name.clone(), &mut syntax, checks);
+    let mut llir = match llir_builder.build_llir(&tree) {
         Ok(llir) => llir,
-        Err(_e) => return Err(()),
+        Err(errors) => return Err(errors),
     };
     
+    llir.source = path;
     Ok(llir)
 }
 
@@ -24,13 +24,18 @@ pub enum LLirType {
     
     // Base and system instructions
     Label,
-    Func,
+    Func,           // arg1: name, arg2: parameter types, arg3: fixed parameter count if variadic
     Extern,
     Ret,
+    Exit,           // arg1: exit code; through exit() with the C library, and sys_exit otherwise
+    
+    Global,         // arg1: name, arg2: initial value
+    ExternGlobal,
+    Table,          // arg1: name, arg2: the values; a read-only array, read like any other
     
     PushArg,
     KPushArg,
-    Call,
+    Call,           // arg1: name (or the address for indirect calls), arg2: arguments, arg3: destination register (if any)
     Syscall,
     
     // Integer arithmetic instructions
@@ -53,7 +58,12 @@ pub enum LLirType {
     MulF32,     MulF64,
     DivF32,     DivF64,
     
+    // Vector instructions
+    // These work on eight elements at once
+    VAddDW,
+    
     // Move instructions
+    // The move instructions convert the second operand to the instruction's data type
     Mov,
     Li,
     MovSX,      MovZX,
@@ -66,6 +76,7 @@ pub enum LLirType {
     AllocQW,
     AllocF32,
     AllocF64,
+    AllocRec,
     
     // Load and store instructions
     LdB,        UldB,
@@ -93,7 +104,9 @@ pub enum LLirType {
     LdArgF64,
     
     // Flow control
+    // The comparison instructions jump to the label in arg1 if the condition is true
     Jmp,
+    JmpTable,       // arg1: 64-bit index, arg2: label for each index, arg3: label if the index is outside the table
     CeqB,       CneqB,      // Equal / not equal
     CeqW,       CneqW,
     CeqDW,      CneqDW,
@@ -117,7 +130,14 @@ pub enum LLirType {
     CsgtB,      CugtB,      // Signed greater than / unsigned greater than
     CsgtW,      CugtW,
     CsgtDW,     CugtDW,
-    CsgtQW,     CugtQW
+    CsgtQW,     CugtQW,
+    
+    CeqF32,     CeqF64,     // Floating point comparisons
+    CneqF32,    CneqF64,
+    CltF32,     CltF64,
+    CleF32,     CleF64,
+    CgtF32,     CgtF64,
+    CgeF32,     CgeF64,
 }
 
 // Represents an LLIR instruction operand
@@ -126,13 +146,17 @@ pub enum LLirArg {
     None,
     
     Int(i64), UInt(u64),
+    Float(f64),
     
     Mem(String),
+    FieldMem(String, i32, LLirDataType),        // Record variable, field offset, field type
+    ElementMem(String, Box<LLirArg>, LLirDataType),     // Array variable, index, element type
     
     Label(String),
     StrLiteral(String),
     
     ArgList(Vec<LLirArg>),
+    TypeList(Vec<LLirDataType>),
     
     Reg(i32),
     ArgReg(i32),
@@ -147,6 +171,7 @@ pub enum LLirDataType {
     Word,       UWord,
     Int,        UInt,
     Int64,      UInt64,
+    Float,      Double,
     Str,
     Ptr
 }
@@ -159,12 +184,14 @@ pub struct LLirInstr {
     pub arg1 : LLirArg,
     pub arg2 : LLirArg,
     pub arg3 : LLirArg,
+    pub line_no : i32,          // The source line, or 0 if unknown
 }
 
 // Represents an LLIR file
 #[derive(Debug, Clone, PartialEq)]
 pub struct LLirFile {
     pub name        : String,
+    pub source      : String,
     pub strings     : HashMap<String, String>,
     pub code        : Vec<LLirInstr>,
 }
@@ -177,6 +204,17 @@ pub fn create_instr(instr_type : LLirType) -> LLirInstr {
         arg1 : LLirArg::None,
         arg2 : LLirArg::None,
         arg3 : LLirArg::None,
+        line_no : 0,
+    }
+}
+
+// Sets the source line of the instructions added since start
+// Instructions which already have a line keep it
+pub fn set_line_no(code : &mut Vec<LLirInstr>, start : usize, line_no : i32) {
+    for instr in code.iter_mut().skip(start) {
+        if instr.line_no == 0 {
+            instr.line_no = line_no;
+        }
     }
 }
 
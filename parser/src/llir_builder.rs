@@ -21,8 +21,11 @@ use crate::ast::*;
 use crate::llir;
 use crate::llir::*;
 use crate::syntax::*;
+use crate::Checks;
 
+use crate::llir_flow::*;
 use crate::llir_func::*;
+use crate::llir_record::*;
 use crate::llir_var::*;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -30,80 +33,235 @@ pub struct Var {
     pub name : String,
     pub data_type : LLirDataType,
     pub sub_type : LLirDataType,
+    pub record : String,
+    pub proc_type : String,     // La procedura tipo, por variabloj tra kiuj ni alvokas
+    pub is_typed_ptr : bool,     // Tipigita montrilo; la subtipo estas la celata tipo, sed ĝi ne estas tabelo
+}
+
+// La subskribo de funkcio
+// Variadaj funkcioj estas tiuj kiuj akceptas pliajn argumentojn laŭ la C maniero
+#[derive(Debug, Clone, PartialEq)]
+pub struct Func {
+    pub data_type : LLirDataType,
+    pub params : Vec<Var>,
+    pub is_variadic : bool,
 }
 
 pub struct LLirBuilder {
     pub file : LLirFile,
     pub syntax : ErrorManager,
+    pub checks : Checks,
     
     pub reg_pos : i32,
     pub str_pos : i32,
+    pub label_pos : i32,
     
     pub vars : Vec<Var>,
+    pub records : HashMap<String, AstRecord>,
+    pub funcs : HashMap<String, Func>,
+    pub procs : HashMap<String, Func>,
+    pub enums : Vec<AstEnum>,
+    
+    pub func_type : LLirDataType,
+    pub block_layer : i32,
+    pub loop_labels : Vec<(String, String)>,     // Continue label, break label
 }
 
-pub fn new_llir_builder(name : String, syntax : &mut ErrorManager) -> LLirBuilder {
+pub fn new_llir_builder(name : String, syntax : &mut ErrorManager, checks : Checks) -> LLirBuilder {
     LLirBuilder {
         file : LLirFile {
             name : name,
+            source : String::new(),
             strings : HashMap::new(),
             code : Vec::new(),
         },
         syntax : syntax.clone(),
+        checks : checks,
         
         reg_pos : 0,
         str_pos : 0,
+        label_pos : 0,
         
         vars : Vec::new(),
+        records : HashMap::new(),
+        funcs : HashMap::new(),
+        procs : HashMap::new(),
+        enums : Vec::new(),
+        
+        func_type : LLirDataType::Void,
+        block_layer : 0,
+        loop_labels : Vec::new(),
     }
 }
 
 impl LLirBuilder {
-
+    
     // Konstruas la ĉefan LLIR dosieron.
-    pub fn build_llir(&mut self, tree : &AstTree) -> Result<LLirFile, ()> {
+    pub fn build_llir(&mut self, tree : &AstTree) -> Result<LLirFile, Vec<Diagnostic>> {
         // Cache the constants
         /*if !self.build_global_constants(tree) {
-            self.syntax.print_errors();
-            return Err(());
+            return Err(self.syntax.errors.clone());
         }*/
         
+        // Kolekti la rekordojn.
+        for record in tree.records.iter() {
+            self.records.insert(record.name.clone(), record.clone());
+        }
+        
+        // Kolekti la funkciajn subskribojn, por ke ni povu alvoki funkciojn difinitajn poste.
+        // Parametroj deklaritaj post "..." estas nedevigaj, do tiuj funkcioj ne estas variadaj.
+        for func in tree.functions.iter() {
+            let mut params : Vec<Var> = Vec::new();
+            
+            for arg in func.args.iter() {
+                params.push(create_var(&arg.name, &arg.data_type, &arg.sub_type));
+            }
+            
+            let (data_type, _) = ast_to_datatype(&func.data_type);
+            
+            let sig = Func {
+                data_type : data_type,
+                params : params,
+                is_variadic : func.is_variadic && func.fixed_args == func.args.len(),
+            };
+            
+            self.funcs.insert(func.name.clone(), sig);
+        }
+        
+        // Kolekti la procedurajn tipojn; ili havas subskribon kiel funkcioj.
+        for proc_type in tree.procs.iter() {
+            let mut params : Vec<Var> = Vec::new();
+            
+            for arg in proc_type.args.iter() {
+                params.push(create_var(&arg.name, &arg.data_type, &arg.sub_type));
+            }
+            
+            let (data_type, _) = ast_to_datatype(&proc_type.data_type);
+            
+            let sig = Func {
+                data_type : data_type,
+                params : params,
+                is_variadic : false,
+            };
+            
+            self.procs.insert(proc_type.name.clone(), sig);
+        }
+        
+        // Konstrui la mallokajn variablojn kaj la konstantajn tabelojn.
+        self.build_globals(tree);
+        self.build_tables(tree);
+        
         // Konstrui la funkciojn.
         if !self.build_functions(tree) {
-            self.syntax.print_errors();
-            return Err(());
+            return Err(self.syntax.errors.clone());
         }
         
         Ok(self.file.clone())
     }
     
+    // Konstrui la mallokajn variablojn.
+    // Ili estas videblaj en ĉiuj funkcioj.
+    fn build_globals(&mut self, tree : &AstTree) {
+        for global in tree.globals.iter() {
+            let (data_type, _) = ast_to_datatype(&global.data_type);
+            let mut instr = llir::create_instr(LLirType::Global);
+            
+            if global.is_extern {
+                instr = llir::create_instr(LLirType::ExternGlobal);
+            }
+            
+            instr.data_type = data_type.clone();
+            instr.arg1 = LLirArg::Label(global.name.clone());
+            
+            instr.arg2 = literal_value(&global.value, &data_type);
+            self.add_code(instr);
+            
+            let var = Var {
+                name : global.name.clone(),
+                data_type : data_type,
+                sub_type : LLirDataType::Void,
+                record : String::new(),
+                proc_type : String::new(),
+                is_typed_ptr : false,
+            };
+            
+            self.vars.push(var);
+        }
+    }
+    
+    // Konstrui la konstantajn tabelojn.
+    // Ili estas legataj kiel aliaj tabeloj; la grandeco estas en "<nomo>.size".
+    fn build_tables(&mut self, tree : &AstTree) {
+        for constant in tree.constants.iter() {
+            if constant.values.len() == 0 {
+                continue;
+            }
+            
+            let (data_type, _) = ast_to_datatype(&constant.data_type);
+            let values = constant.values.iter().map(|v| literal_value(v, &data_type)).collect();
+            
+            let mut instr = llir::create_instr(LLirType::Table);
+            instr.data_type = data_type.clone();
+            instr.arg1 = LLirArg::Label(constant.name.clone());
+            instr.arg2 = LLirArg::ArgList(values);
+            self.add_code(instr);
+            
+            let var = Var {
+                name : constant.name.clone(),
+                data_type : LLirDataType::Ptr,
+                sub_type : data_type,
+                record : String::new(),
+                proc_type : String::new(),
+                is_typed_ptr : false,
+            };
+            
+            self.vars.push(var);
+        }
+    }
+    
     // Konstrui la funkciojn.
     fn build_functions(&mut self, tree : &AstTree) -> bool {
+        let global_count = self.vars.len();
+        
         for func in tree.functions.iter() {
+            let mut def = llir::create_instr(LLirType::Func);
+            
+            if func.is_extern {
+                def = llir::create_instr(LLirType::Extern);
+            }
+            
+            let (data_type, _) = ast_to_datatype(&func.data_type);
+            def.data_type = data_type.clone();
+            def.arg1 = LLirArg::Label(func.name.clone());
+            
+            let sig = self.funcs.get(&func.name).unwrap().clone();
+            def.arg2 = LLirArg::TypeList(param_types(&sig.params));
+            
+            if sig.is_variadic {
+                def.arg3 = LLirArg::Int(func.fixed_args as i64);
+            }
+            
+            def.line_no = func.line_no;
+            self.add_code(def);
+            
             if func.is_extern {
-                let mut def = llir::create_instr(LLirType::Extern);
-                def.arg1 = LLirArg::Label(func.name.clone());
-                
-                def.data_type = LLirDataType::Void;
-                
-                self.add_code(def);
-            } else {
-                let mut def = llir::create_instr(LLirType::Func);
-                
-                if func.data_type != DataType::None {
-                    let (ft, _) = ast_to_datatype(&func.data_type);
-                    def.data_type = ft;
-                } else {
-                    def.data_type = LLirDataType::Void;
-                }
-                
-                def.arg1 = LLirArg::Label(func.name.clone());
-                self.add_code(def);
-                
-                // Konstrui la blokon.
-                if !self.build_block(&func.statements) {
-                    return false;
-                }        
+                continue;
+            }
+            
+            // Nur la mallokaj variabloj restas de la antaŭa funkcio
+            self.vars.truncate(global_count);
+            self.enums = func.enums.clone();
+            self.func_type = data_type;
+            self.block_layer = 0;
+            self.loop_labels.clear();
+            
+            let start = self.file.code.len();
+            build_func_params(self, &sig.params);
+            llir::set_line_no(&mut self.file.code, start, func.line_no);
+            
+            // Konstrui la blokon.
+            if !self.build_block(&func.statements) {
+                return false;
             }
         }
         
@@ -111,30 +269,34 @@ impl LLirBuilder {
     }
     
     // Konstrui la funkcion korpon.
-    fn build_block(&mut self, statements : &Vec<AstStmt>) -> bool {
+    // Elif kaj else estas konstruitaj de la if deklaro, kiu posedas ilin; same kun when kaj case.
+    pub fn build_block(&mut self, statements : &Vec<AstStmt>) -> bool {
         let mut code = true;
-    
+        
         for line in statements {
+            let start = self.file.code.len();
+            
             match &line.stmt_type {
                 AstStmtType::VarDec => code = build_var_dec(self, &line),
                 AstStmtType::VarAssign => code = build_var_assign(self, &line),
-                //AstStmtType::ArrayAssign => code = build_array_assign(self, &line),
-                //AstStmtType::If => build_cond(self, &line),
-                //AstStmtType::Elif => build_cond(self, &line),
-                //AstStmtType::Else => build_cond(self, &line),
-                //AstStmtType::While => build_while(self, &line),
-                //AstStmtType::For => build_for_loop(self, &line),
-                //AstStmtType::Break => build_break(self),
-                //AstStmtType::Continue => build_continue(self),
+                AstStmtType::ArrayAssign => code = build_array_assign(self, &line),
+                AstStmtType::FieldAssign => code = build_field_assign(self, &line),
+                AstStmtType::If => code = build_cond(self, &line),
+                AstStmtType::Elif | AstStmtType::Else => {},
+                AstStmtType::While => code = build_while(self, &line),
+                AstStmtType::For => code = build_for_loop(self, &line),
+                AstStmtType::Case => code = build_case(self, &line),
+                AstStmtType::When => {},
+                AstStmtType::Break => code = build_break(self, &line),
+                AstStmtType::Continue => code = build_continue(self, &line),
                 AstStmtType::FuncCall => code = build_func_call(self, &line),
                 AstStmtType::Return => code = build_return(self, &line),
-                //AstStmtType::Exit => code = build_exit(self, &line),
+                AstStmtType::Exit => code = build_exit(self, &line),
                 AstStmtType::End => code = build_end(self, &line),
-                
-                // TODO: Forigi post la super faritas.
-                _ => {},
             }
             
+            llir::set_line_no(&mut self.file.code, start, line.line_no);
+            
             if !code {
                 break;
             }
@@ -147,6 +309,45 @@ impl LLirBuilder {
     pub fn add_code(&mut self, code : LLirInstr) {
         self.file.code.push(code);
     }
+    
+    // Redonas variablon; la plej lasta deklaro gajnas
+    pub fn get_var(&self, name : &String) -> Option<Var> {
+        self.vars.iter().rev().find(|v| v.name == *name).cloned()
+    }
+    
+    // Redonas la subskribon de procedura variablo, se ni povas alvoki tra ĝi
+    pub fn get_proc(&self, name : &String) -> Option<Func> {
+        match self.get_var(name) {
+            Some(v) if v.proc_type.len() > 0 => return self.procs.get(&v.proc_type).cloned(),
+            _ => return None,
+        }
+    }
+    
+    // Redonas la valoron de enumeracia ero
+    pub fn get_enum_value(&self, name : &String) -> Option<i32> {
+        for e in self.enums.iter() {
+            if let Some(value) = e.values.get(name) {
+                return Some(*value);
+            }
+        }
+        
+        None
+    }
+    
+    // Redonas novan registron
+    pub fn new_reg(&mut self) -> LLirArg {
+        let reg = LLirArg::Reg(self.reg_pos);
+        self.reg_pos += 1;
+        reg
+    }
+    
+    // Redonas novan etikedon
+    pub fn new_label(&mut self) -> String {
+        let mut name = "L".to_string();
+        name.push_str(&self.label_pos.to_string());
+        self.label_pos += 1;
+        name
+    }
 }
 
 // Utilaj funkcioj
@@ -160,12 +361,82 @@ pub fn store_for_type(data_type : &LLirDataType) -> LLirInstr {
         LLirDataType::UInt => llir::create_instr(LLirType::UstrDW),
         LLirDataType::Int64 => llir::create_instr(LLirType::StrQW),
         LLirDataType::UInt64 => llir::create_instr(LLirType::UstrQW),
+        LLirDataType::Float => llir::create_instr(LLirType::StrF32),
+        LLirDataType::Double => llir::create_instr(LLirType::StrF64),
         LLirDataType::Str => llir::create_instr(LLirType::StrQW),
         LLirDataType::Ptr => llir::create_instr(LLirType::StrQW),
         _ => llir::create_instr(LLirType::Nop),
     }
 }
 
+pub fn load_for_type(data_type : &LLirDataType) -> LLirInstr {
+    match &data_type {
+        LLirDataType::Byte => llir::create_instr(LLirType::LdB),
+        LLirDataType::UByte => llir::create_instr(LLirType::UldB),
+        LLirDataType::Word => llir::create_instr(LLirType::LdW),
+        LLirDataType::UWord => llir::create_instr(LLirType::UldW),
+        LLirDataType::Int => llir::create_instr(LLirType::LdDW),
+        LLirDataType::UInt => llir::create_instr(LLirType::UldDW),
+        LLirDataType::Int64 => llir::create_instr(LLirType::LdQW),
+        LLirDataType::UInt64 => llir::create_instr(LLirType::UldQW),
+        LLirDataType::Float => llir::create_instr(LLirType::LdF32),
+        LLirDataType::Double => llir::create_instr(LLirType::LdF64),
+        LLirDataType::Str => llir::create_instr(LLirType::LdQW),
+        LLirDataType::Ptr => llir::create_instr(LLirType::LdQW),
+        _ => llir::create_instr(LLirType::Nop),
+    }
+}
+
+pub fn alloc_for_type(data_type : &LLirDataType) -> LLirInstr {
+    match &data_type {
+        LLirDataType::Byte | LLirDataType::UByte => llir::create_instr(LLirType::AllocB),
+        LLirDataType::Word | LLirDataType::UWord => llir::create_instr(LLirType::AllocW),
+        LLirDataType::Int | LLirDataType::UInt => llir::create_instr(LLirType::AllocDW),
+        LLirDataType::Float => llir::create_instr(LLirType::AllocF32),
+        LLirDataType::Double => llir::create_instr(LLirType::AllocF64),
+        _ => llir::create_instr(LLirType::AllocQW),
+    }
+}
+
+pub fn ldarg_for_type(data_type : &LLirDataType) -> LLirInstr {
+    match &data_type {
+        LLirDataType::Byte => llir::create_instr(LLirType::LdArgB),
+        LLirDataType::UByte => llir::create_instr(LLirType::ULdArgB),
+        LLirDataType::Word => llir::create_instr(LLirType::LdArgW),
+        LLirDataType::UWord => llir::create_instr(LLirType::ULdArgW),
+        LLirDataType::Int => llir::create_instr(LLirType::LdArgDW),
+        LLirDataType::UInt => llir::create_instr(LLirType::ULdArgDW),
+        LLirDataType::UInt64 => llir::create_instr(LLirType::ULdArgQW),
+        LLirDataType::Float => llir::create_instr(LLirType::LdArgF32),
+        LLirDataType::Double => llir::create_instr(LLirType::LdArgF64),
+        _ => llir::create_instr(LLirType::LdArgQW),
+    }
+}
+
+pub fn size_for_type(data_type : &LLirDataType) -> i32 {
+    match &data_type {
+        LLirDataType::Byte | LLirDataType::UByte => return 1,
+        LLirDataType::Word | LLirDataType::UWord => return 2,
+        LLirDataType::Int | LLirDataType::UInt
+        | LLirDataType::Float => return 4,
+        _ => return 8,
+    }
+}
+
+pub fn is_float(data_type : &LLirDataType) -> bool {
+    match &data_type {
+        LLirDataType::Float | LLirDataType::Double => return true,
+        _ => return false,
+    }
+}
+
+pub fn is_pointer(data_type : &LLirDataType) -> bool {
+    match &data_type {
+        LLirDataType::Str | LLirDataType::Ptr => return true,
+        _ => return false,
+    }
+}
+
 pub fn is_unsigned(data_type : &LLirDataType) -> bool {
     match &data_type {
         LLirDataType::UByte | LLirDataType::UWord
@@ -174,43 +445,97 @@ pub fn is_unsigned(data_type : &LLirDataType) -> bool {
     }
 }
 
+// Redonas la valoron de literalo, por mallokaj variabloj kaj tabeloj
+pub fn literal_value(value : &AstArg, data_type : &LLirDataType) -> LLirArg {
+    match &value.arg_type {
+        AstArgType::ByteL | AstArgType::BoolL => return LLirArg::UInt(value.u8_val as u64),
+        AstArgType::ShortL => return LLirArg::UInt(value.u16_val as u64),
+        AstArgType::CharL => return LLirArg::UInt(value.char_val as u64),
+        AstArgType::IntL if is_unsigned(data_type) => return LLirArg::UInt(value.u64_val),
+        AstArgType::IntL => return LLirArg::Int(value.u64_val as i64),
+        AstArgType::FloatL => return LLirArg::Float(value.f64_val),
+        AstArgType::StringL => return LLirArg::StrLiteral(value.str_val.clone()),
+        _ => return LLirArg::None,
+    }
+}
+
 // Return: Base Type, Sub Type
+// Arrays and records are both pointers; the element type comes from the statement's sub type
 pub fn ast_to_datatype(data_type : &DataType) -> (LLirDataType, LLirDataType) {
     match &data_type {
         DataType::Byte => return (LLirDataType::Byte, LLirDataType::Void),
         DataType::UByte => return (LLirDataType::UByte, LLirDataType::Void),
-        //AstModType::ByteDynArray => return (LLirDataType::Ptr, LLirDataType::Byte),
-        //AstModType::UByteDynArray => return (LLirDataType::Ptr, LLirDataType::UByte),
-        
         DataType::Short => return (LLirDataType::Word, LLirDataType::Void),
         DataType::UShort => return (LLirDataType::UWord, LLirDataType::Void),
-        //AstModType::ShortDynArray => return (LLirDataType::Ptr, LLirDataType::Word),
-        //AstModType::UShortDynArray => return (LLirDataType::Ptr, LLirDataType::UWord),
-        
         DataType::Int => return (LLirDataType::Int, LLirDataType::Void),
         DataType::UInt => return (LLirDataType::UInt, LLirDataType::Void),
-        //AstModType::IntDynArray => return (LLirDataType::Ptr, LLirDataType::Int),
-        //AstModType::UIntDynArray => return (LLirDataType::Ptr, LLirDataType::UInt),
-        
         DataType::Int64 => return (LLirDataType::Int64, LLirDataType::Void),
         DataType::UInt64 => return (LLirDataType::UInt64, LLirDataType::Void),
-        //AstModType::I64DynArray => return (LLirDataType::Ptr, LLirDataType::Int64),
-        //AstModType::U64DynArray => return (LLirDataType::Ptr, LLirDataType::UInt64),
-        
-        /*AstModType::Float => return (LLirDataType::Float, LLirDataType::Void),
-        AstModType::Double => return (LLirDataType::Double, LLirDataType::Void),
-        AstModType::FloatDynArray => return (LLirDataType::Ptr, LLirDataType::Float),
-        AstModType::DoubleDynArray => return (LLirDataType::Ptr, LLirDataType::Double),*/
-        
+        DataType::Float => return (LLirDataType::Float, LLirDataType::Void),
+        DataType::Double => return (LLirDataType::Double, LLirDataType::Void),
         DataType::Char => return (LLirDataType::Byte, LLirDataType::Void),
         DataType::Str => return (LLirDataType::Str, LLirDataType::Void),
-        //AstModType::StrDynArray => return (LLirDataType::Ptr, LLirDataType::Str),
-        //AstModType::Enum(_v) => return (LLirDataType::Int,  LLirDataType::Void),       // TODO: We will need better type detection
+        DataType::Bool => return (LLirDataType::UByte, LLirDataType::Void),
+        DataType::Ptr => return (LLirDataType::Ptr, LLirDataType::Void),
+        DataType::Enum(_v) => return (LLirDataType::Int,  LLirDataType::Void),
+        DataType::Record(_v) => return (LLirDataType::Ptr, LLirDataType::Void),
+        DataType::Proc(_v) => return (LLirDataType::Ptr, LLirDataType::Void),
+        DataType::Pointer(_v) => return (LLirDataType::Ptr, LLirDataType::Void),
         
+        // Do we need an error here? Really, it should never get to this pointer
         _ => return (LLirDataType::Void, LLirDataType::Void),
+    }
+}
+
+// Redonas la LLIR tipojn de la parametroj
+// Tabeloj pasas la montrilon kaj la grandecon
+pub fn param_types(params : &Vec<Var>) -> Vec<LLirDataType> {
+    let mut types : Vec<LLirDataType> = Vec::new();
+    
+    for param in params.iter() {
+        types.push(param.data_type.clone());
         
-        // Do we need an error here? Really, it should never get to this pointer
-        //AstModType::None => return (LLirDataType::Void, LLirDataType::Void),
+        if is_array(param) {
+            types.push(LLirDataType::Int);
+        }
     }
+    
+    types
 }
 
+pub fn is_array(var : &Var) -> bool {
+    var.data_type == LLirDataType::Ptr && var.sub_type != LLirDataType::Void && !var.is_typed_ptr
+}
+
+// Kreas variablon el AST tipoj
+pub fn create_var(name : &String, data_type : &DataType, sub_type : &DataType) -> Var {
+    let (base_type, _) = ast_to_datatype(data_type);
+    let mut var = Var {
+        name : name.clone(),
+        data_type : base_type,
+        sub_type : LLirDataType::Void,
+        record : String::new(),
+        proc_type : String::new(),
+        is_typed_ptr : false,
+    };
+    
+    match &data_type {
+        DataType::Ptr => {
+            let (sub, _) = ast_to_datatype(sub_type);
+            var.sub_type = sub;
+        },
+        
+        DataType::Record(name) => var.record = name.clone(),
+        DataType::Proc(name) => var.proc_type = name.clone(),
+        
+        DataType::Pointer(pointee) => {
+            let (sub, _) = ast_to_datatype(pointee);
+            var.sub_type = sub;
+            var.is_typed_ptr = true;
+        },
+        
+        _ => {},
+    }
+    
+    var
+}
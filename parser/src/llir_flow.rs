@@ -0,0 +1,695 @@
+
+// This file is part of the Lila compiler
+// Copyright (C) 2020-2021 Patrick Flynn
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; version 2.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, write to the Free Software Foundation, Inc.,
+// 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+
+// Handles conditionals and loops in LLIR
+//
+// LLIR has no separate compare and branch instructions; each comparison jumps to its
+// label if the condition is true, and otherwise falls through to the next instruction.
+
+use crate::ast;
+use crate::ast::{AstStmt, AstStmtType, AstArg, AstArgType, AstCond, DataType};
+use crate::ast::{case_arms, arm_body, choice_ranges, is_dense, cond_tree};
+use crate::ast_expr::{arg_expr, value_expr};
+use crate::llir;
+use crate::llir::*;
+use crate::llir_builder::*;
+use crate::llir_var::*;
+
+// Konstruas if deklaron kun ĉiuj ĝiaj elif kaj else partoj
+// Ĉiu parto estas la lasta deklaro en la bloko de la antaŭa parto.
+pub fn build_cond(builder : &mut LLirBuilder, line : &AstStmt) -> bool {
+    let end_label = builder.new_label();
+    let mut current = line;
+    
+    builder.block_layer += 1;
+    
+    loop {
+        let next_label = builder.new_label();
+        
+        if current.stmt_type != AstStmtType::Else {
+            if !build_cmp(builder, current, &next_label, false) {
+                return false;
+            }
+        }
+        
+        if !builder.build_block(&current.sub_block) {
+            return false;
+        }
+        
+        match current.sub_block.last() {
+            Some(next) if next.stmt_type == AstStmtType::Elif || next.stmt_type == AstStmtType::Else => {
+                build_jmp(builder, &end_label);
+                build_label(builder, &next_label);
+                current = next;
+            },
+            
+            _ => {
+                build_label(builder, &next_label);
+                break;
+            },
+        }
+    }
+    
+    build_label(builder, &end_label);
+    builder.block_layer -= 1;
+    
+    true
+}
+
+// Konstruas case deklaron
+// Densaj elektoj iĝas saltotabelo, kaj la aliaj iĝas arbo de komparoj.
+pub fn build_case(builder : &mut LLirBuilder, line : &AstStmt) -> bool {
+    builder.syntax.set_data(line);
+    
+    let data_type = operand_type(builder, &line.args[0]);
+    
+    let selector = match build_expr(builder, &arg_expr(&line.args[0]), &data_type) {
+        Ok(v) => v,
+        Err(_e) => return false,
+    };
+    
+    let values = match &line.data_type {
+        DataType::Enum(name) => builder.enums.iter().find(|e| e.name == *name).map(|e| e.values.clone()),
+        _ => None,
+    };
+    
+    // Ĉiu brako havas etikedon; "when others" estas la defaŭlto
+    let arms = case_arms(line);
+    let end_label = builder.new_label();
+    let mut default_label = end_label.clone();
+    let mut labels : Vec<String> = Vec::new();
+    let mut choices : Vec<(i64, i64, String)> = Vec::new();
+    
+    for arm in arms.iter() {
+        let label = builder.new_label();
+        
+        let ranges = match choice_ranges(arm, values.as_ref()) {
+            Ok(r) => r,
+            Err(msg) => {
+                builder.syntax.ltac_error(arm, msg);
+                return false;
+            },
+        };
+        
+        if ranges.len() == 0 {
+            default_label = label.clone();
+        }
+        
+        for (low, high) in ranges.iter() {
+            choices.push((*low, *high, label.clone()));
+        }
+        
+        labels.push(label);
+    }
+    
+    choices.sort();
+    
+    let ranges : Vec<(i64, i64)> = choices.iter().map(|(low, high, _)| (*low, *high)).collect();
+    
+    if is_dense(&ranges) {
+        build_case_table(builder, selector, &data_type, &choices, &default_label);
+    } else {
+        build_case_tree(builder, &selector, &data_type, &choices, &default_label);
+    }
+    
+    // La korpoj de la brakoj
+    builder.block_layer += 1;
+    
+    for (arm, label) in arms.iter().zip(labels.iter()) {
+        build_label(builder, label);
+        
+        if !builder.build_block(&arm_body(arm).to_vec()) {
+            return false;
+        }
+        
+        build_jmp(builder, &end_label);
+    }
+    
+    build_label(builder, &end_label);
+    builder.block_layer -= 1;
+    
+    true
+}
+
+// La indekso estas la elektilo minus la plej malgranda valoro, kiel int64
+// Tiel ĝi neniam superfluas, kaj valoroj ekster la tabelo iras al la defaŭlto.
+fn build_case_table(builder : &mut LLirBuilder, selector : LLirArg, data_type : &LLirDataType, choices : &Vec<(i64, i64, String)>, default_label : &String) {
+    let min = choices[0].0;
+    let max = choices.last().unwrap().1;
+    
+    let value = build_convert(builder, selector, data_type, &LLirDataType::Int64);
+    let index = builder.new_reg();
+    
+    let mut instr = llir::create_instr(LLirType::Sub);
+    instr.data_type = LLirDataType::Int64;
+    instr.arg1 = index.clone();
+    instr.arg2 = value;
+    instr.arg3 = LLirArg::Int(min);
+    builder.add_code(instr);
+    
+    let mut labels : Vec<LLirArg> = Vec::new();
+    
+    for value in min ..= max {
+        let label = match choices.iter().find(|(low, high, _)| value >= *low && value <= *high) {
+            Some((_, _, label)) => label.clone(),
+            None => default_label.clone(),
+        };
+        
+        labels.push(LLirArg::Label(label));
+    }
+    
+    let mut instr = llir::create_instr(LLirType::JmpTable);
+    instr.data_type = LLirDataType::Int64;
+    instr.arg1 = index;
+    instr.arg2 = LLirArg::ArgList(labels);
+    instr.arg3 = LLirArg::Label(default_label.clone());
+    builder.add_code(instr);
+}
+
+// Duone dividas la ordigitajn elektojn ĝis restas nur kelkaj
+// Tiam ni komparas ilin laŭorde; valoro sub la nuna elekto ne troviĝas en la sekvaj.
+fn build_case_tree(builder : &mut LLirBuilder, selector : &LLirArg, data_type : &LLirDataType, choices : &[(i64, i64, String)], default_label : &String) {
+    if choices.len() > 3 {
+        let mid = choices.len() / 2;
+        let left_label = builder.new_label();
+        
+        build_case_cmp(builder, AstArgType::OpLt, selector, choices[mid].0, data_type, &left_label);
+        build_case_tree(builder, selector, data_type, &choices[mid..], default_label);
+        
+        build_label(builder, &left_label);
+        build_case_tree(builder, selector, data_type, &choices[..mid], default_label);
+        return;
+    }
+    
+    for (low, high, label) in choices.iter() {
+        if low == high {
+            build_case_cmp(builder, AstArgType::OpEq, selector, *low, data_type, label);
+        } else {
+            build_case_cmp(builder, AstArgType::OpLt, selector, *low, data_type, default_label);
+            build_case_cmp(builder, AstArgType::OpLte, selector, *high, data_type, label);
+        }
+    }
+    
+    build_jmp(builder, default_label);
+}
+
+fn build_case_cmp(builder : &mut LLirBuilder, op : AstArgType, selector : &LLirArg, value : i64, data_type : &LLirDataType, label : &String) {
+    let mut instr = llir::create_instr(cmp_for_type(&op, data_type));
+    instr.data_type = data_type.clone();
+    instr.arg1 = LLirArg::Label(label.clone());
+    instr.arg2 = selector.clone();
+    instr.arg3 = LLirArg::Int(value);
+    builder.add_code(instr);
+}
+
+// Konstruas while buklon
+// La komparo estas post la korpo, same kiel en LTAC
+pub fn build_while(builder : &mut LLirBuilder, line : &AstStmt) -> bool {
+    let cmp_label = builder.new_label();
+    let loop_label = builder.new_label();
+    let end_label = builder.new_label();
+    
+    builder.block_layer += 1;
+    builder.loop_labels.push((cmp_label.clone(), end_label.clone()));
+    
+    build_jmp(builder, &cmp_label);
+    build_label(builder, &loop_label);
+    
+    if !builder.build_block(&line.sub_block) {
+        return false;
+    }
+    
+    build_label(builder, &cmp_label);
+    
+    if !build_cmp(builder, line, &loop_label, true) {
+        return false;
+    }
+    
+    build_label(builder, &end_label);
+    
+    builder.loop_labels.pop();
+    builder.block_layer -= 1;
+    
+    true
+}
+
+// Konstruas for buklon
+// Syntax: for <index> in <start> .. <end>, or: for <element> in <array>
+pub fn build_for_loop(builder : &mut LLirBuilder, line : &AstStmt) -> bool {
+    builder.syntax.set_data(line);
+    
+    let cmp_label = builder.new_label();
+    let cont_label = builder.new_label();
+    let end_label = builder.new_label();
+    
+    builder.block_layer += 1;
+    builder.loop_labels.push((cont_label.clone(), end_label.clone()));
+    
+    let code = match line.args.iter().position(|a| a.arg_type == AstArgType::Range) {
+        Some(range) => build_range_loop(builder, line, range, &cmp_label, &end_label),
+        None => build_foreach_loop(builder, line, &cmp_label, &end_label),
+    };
+    
+    if !code || !builder.build_block(&line.sub_block) {
+        return false;
+    }
+    
+    // Pliigi la indekson kaj reiri al la komparo
+    let index = match line.args.iter().position(|a| a.arg_type == AstArgType::Range) {
+        Some(_) => line.args[0].str_val.clone(),
+        None => index_name(&line.args[0].str_val),
+    };
+    
+    build_label(builder, &cont_label);
+    
+    let value = build_load(builder, LLirArg::Mem(index.clone()), &LLirDataType::Int);
+    let dest = builder.new_reg();
+    
+    let mut instr = llir::create_instr(LLirType::Add);
+    instr.data_type = LLirDataType::Int;
+    instr.arg1 = dest.clone();
+    instr.arg2 = value;
+    instr.arg3 = LLirArg::Int(1);
+    builder.add_code(instr);
+    
+    build_store(builder, LLirArg::Mem(index), dest, &LLirDataType::Int);
+    build_jmp(builder, &cmp_label);
+    build_label(builder, &end_label);
+    
+    builder.loop_labels.pop();
+    builder.block_layer -= 1;
+    
+    true
+}
+
+// La indekso estas nova int variablo, kaj la fino ne estas inkluzivita
+fn build_range_loop(builder : &mut LLirBuilder, line : &AstStmt, range : usize, cmp_label : &String, end_label : &String) -> bool {
+    let name = line.args[0].str_val.clone();
+    
+    let (start_expr, end_expr) = match (value_expr(&line.args[1..range]), value_expr(&line.args[range+1..])) {
+        (Some(start), Some(end)) => (start, end),
+        
+        _ => {
+            builder.syntax.ltac_error(line, "Invalid range.".to_string());
+            return false;
+        },
+    };
+    
+    let mut instr = llir::create_instr(LLirType::AllocDW);
+    instr.data_type = LLirDataType::Int;
+    instr.arg1 = LLirArg::Label(name.clone());
+    builder.add_code(instr);
+    
+    builder.vars.push(Var {
+        name : name.clone(),
+        data_type : LLirDataType::Int,
+        sub_type : LLirDataType::Void,
+        record : String::new(),
+        proc_type : String::new(),
+        is_typed_ptr : false,
+    });
+    
+    let start = match build_expr(builder, &start_expr, &LLirDataType::Int) {
+        Ok(s) => s,
+        Err(_e) => return false,
+    };
+    
+    build_store(builder, LLirArg::Mem(name.clone()), start, &LLirDataType::Int);
+    build_label(builder, cmp_label);
+    
+    let index = build_load(builder, LLirArg::Mem(name), &LLirDataType::Int);
+    
+    let end = match build_expr(builder, &end_expr, &LLirDataType::Int) {
+        Ok(e) => e,
+        Err(_e) => return false,
+    };
+    
+    let mut instr = llir::create_instr(LLirType::CsgeDW);
+    instr.data_type = LLirDataType::Int;
+    instr.arg1 = LLirArg::Label(end_label.clone());
+    instr.arg2 = index;
+    instr.arg3 = end;
+    builder.add_code(instr);
+    
+    true
+}
+
+// La elemento havas la tipon de la tabelo, kaj kaŝita variablo tenas la indekson
+fn build_foreach_loop(builder : &mut LLirBuilder, line : &AstStmt, cmp_label : &String, end_label : &String) -> bool {
+    let name = line.args[0].str_val.clone();
+    let index_name = index_name(&name);
+    
+    let array = match line.args.get(1) {
+        Some(a) => match builder.get_var(&a.str_val) {
+            Some(v) if is_array(&v) => v,
+            _ => {
+                builder.syntax.ltac_error(line, "Expected array in for loop.".to_string());
+                return false;
+            },
+        },
+        
+        None => return false,
+    };
+    
+    let mut size_name = array.name.clone();
+    size_name.push_str(".size");
+    
+    // La elemento kaj la indekso
+    let data_type = array.sub_type.clone();
+    
+    let mut instr = alloc_for_type(&data_type);
+    instr.data_type = data_type.clone();
+    instr.arg1 = LLirArg::Label(name.clone());
+    builder.add_code(instr);
+    
+    let mut instr = llir::create_instr(LLirType::AllocDW);
+    instr.data_type = LLirDataType::Int;
+    instr.arg1 = LLirArg::Label(index_name.clone());
+    builder.add_code(instr);
+    
+    build_store(builder, LLirArg::Mem(index_name.clone()), LLirArg::Int(0), &LLirDataType::Int);
+    
+    builder.vars.push(Var {
+        name : name.clone(),
+        data_type : data_type.clone(),
+        sub_type : LLirDataType::Void,
+        record : String::new(),
+        proc_type : String::new(),
+        is_typed_ptr : false,
+    });
+    
+    // Kompari la indekson kun la grandeco
+    build_label(builder, cmp_label);
+    
+    let index = build_load(builder, LLirArg::Mem(index_name), &LLirDataType::Int);
+    let size = build_load(builder, LLirArg::Mem(size_name), &LLirDataType::Int);
+    
+    let mut instr = llir::create_instr(LLirType::CsgeDW);
+    instr.data_type = LLirDataType::Int;
+    instr.arg1 = LLirArg::Label(end_label.clone());
+    instr.arg2 = index.clone();
+    instr.arg3 = size;
+    builder.add_code(instr);
+    
+    // Ŝarĝi la elementon
+    let index = build_convert(builder, index, &LLirDataType::Int, &LLirDataType::Int64);
+    let element = LLirArg::ElementMem(array.name.clone(), Box::new(index), data_type.clone());
+    let value = build_load(builder, element, &data_type);
+    
+    build_store(builder, LLirArg::Mem(name), value, &data_type);
+    
+    true
+}
+
+fn index_name(name : &String) -> String {
+    let mut index_name = name.clone();
+    index_name.push_str(".index");
+    index_name
+}
+
+// Break out of a current loop
+pub fn build_break(builder : &mut LLirBuilder, _line : &AstStmt) -> bool {
+    if let Some((_, end_label)) = builder.loop_labels.last().cloned() {
+        build_jmp(builder, &end_label);
+    }
+    
+    true
+}
+
+// Continue through the rest of the loop
+pub fn build_continue(builder : &mut LLirBuilder, _line : &AstStmt) -> bool {
+    if let Some((cont_label, _)) = builder.loop_labels.last().cloned() {
+        build_jmp(builder, &cont_label);
+    }
+    
+    true
+}
+
+// Konstruas kondiĉon kiu saltas al la etikedo
+// Kun if, ni saltas se la kondiĉo estas malvera; kun bukloj, se ĝi estas vera.
+fn build_cmp(builder : &mut LLirBuilder, line : &AstStmt, label : &String, jump_if_true : bool) -> bool {
+    builder.syntax.set_data(line);
+    
+    let tree = match cond_tree(&line.args) {
+        Ok(t) => t,
+        Err(msg) => {
+            builder.syntax.ltac_error(line, msg);
+            return false;
+        },
+    };
+    
+    build_cond_tree(builder, line, &tree, label, jump_if_true)
+}
+
+// "and" kaj "or" mallongigas: la dekstra flanko estas preterlasita kiam la maldekstra decidas
+fn build_cond_tree(builder : &mut LLirBuilder, line : &AstStmt, cond : &AstCond, label : &String, jump_if_true : bool) -> bool {
+    let (lhs, rhs, is_and) = match cond {
+        AstCond::Test(start, end) => return build_test(builder, line, &line.args[*start..*end], label, jump_if_true),
+        AstCond::Not(c) => return build_cond_tree(builder, line, c, label, !jump_if_true),
+        AstCond::And(lhs, rhs) => (lhs, rhs, true),
+        AstCond::Or(lhs, rhs) => (lhs, rhs, false),
+    };
+    
+    // Ambaŭ flankoj saltas al la sama etikedo
+    if is_and != jump_if_true {
+        return build_cond_tree(builder, line, lhs, label, jump_if_true)
+            && build_cond_tree(builder, line, rhs, label, jump_if_true);
+    }
+    
+    let skip_label = builder.new_label();
+    
+    if !build_cond_tree(builder, line, lhs, &skip_label, !is_and)
+        || !build_cond_tree(builder, line, rhs, label, jump_if_true) {
+        return false;
+    }
+    
+    build_label(builder, &skip_label);
+    true
+}
+
+// Konstruas unu komparon aŭ bulean valoron
+fn build_test(builder : &mut LLirBuilder, line : &AstStmt, args : &[AstArg], label : &String, jump_if_true : bool) -> bool {
+    let mut args = args.to_vec();
+    
+    if args.len() == 1 {
+        // Laŭvorto ĉiam saltas, aŭ neniam
+        if args[0].arg_type == AstArgType::BoolL {
+            if (args[0].u8_val == 1) == jump_if_true {
+                build_jmp(builder, label);
+            }
+            
+            return true;
+        }
+        
+        // Bulea valoro estas komparita kun false
+        args.push(ast::create_arg(AstArgType::OpNeq));
+        args.push(ast::create_byte(0));
+    }
+    
+    let pos = match args.iter().position(|a| is_cmp_op(&a.arg_type)) {
+        Some(p) => p,
+        None => {
+            builder.syntax.ltac_error(line, "Invalid conditional statement.".to_string());
+            return false;
+        },
+    };
+    
+    let lhs = args[..pos].to_vec();
+    let rhs = args[pos+1..].to_vec();
+    
+    let (lhs_expr, rhs_expr) = match (value_expr(&lhs), value_expr(&rhs)) {
+        (Some(lhs), Some(rhs)) => (lhs, rhs),
+        
+        _ => {
+            builder.syntax.ltac_error(line, "Invalid conditional statement.".to_string());
+            return false;
+        },
+    };
+    
+    // La unua operando kiu ne estas laŭvorto donas la tipon
+    let mut data_type = match cmp_type(builder, &lhs) {
+        Some(t) => t,
+        None => match cmp_type(builder, &rhs) {
+            Some(t) => t,
+            None => match lhs.iter().find(|a| a.arg_type != AstArgType::OpNeg) {
+                Some(a) => operand_type(builder, a),
+                None => LLirDataType::Int,
+            },
+        },
+    };
+    
+    // Ĉeno komparita kun null estas komparita kiel adreso
+    let is_null = |a : &Vec<AstArg>| a.len() == 1 && a[0].arg_type == AstArgType::Null;
+    
+    if data_type == LLirDataType::Str && (is_null(&lhs) || is_null(&rhs)) {
+        data_type = LLirDataType::Ptr;
+    }
+    
+    let mut op = args[pos].arg_type.clone();
+    
+    if !jump_if_true {
+        op = invert_op(&op);
+    }
+    
+    let lval = match build_expr(builder, &lhs_expr, &data_type) {
+        Ok(v) => v,
+        Err(_e) => return false,
+    };
+    
+    let rval = match build_expr(builder, &rhs_expr, &data_type) {
+        Ok(v) => v,
+        Err(_e) => return false,
+    };
+    
+    let mut instr = llir::create_instr(cmp_for_type(&op, &data_type));
+    instr.data_type = data_type.clone();
+    instr.arg1 = LLirArg::Label(label.clone());
+    instr.arg2 = lval;
+    instr.arg3 = rval;
+    
+    // Ĉenoj estas komparitaj per strcmp, kiu redonas 1 se ili estas egalaj
+    if data_type == LLirDataType::Str {
+        let dest = builder.new_reg();
+        
+        let mut call = llir::create_instr(LLirType::Call);
+        call.data_type = LLirDataType::Int;
+        call.arg1 = LLirArg::Label("strcmp".to_string());
+        call.arg2 = LLirArg::ArgList(vec![instr.arg2.clone(), instr.arg3.clone()]);
+        call.arg3 = dest.clone();
+        builder.add_code(call);
+        
+        instr = llir::create_instr(cmp_for_type(&op, &LLirDataType::Int));
+        instr.data_type = LLirDataType::Int;
+        instr.arg1 = LLirArg::Label(label.clone());
+        instr.arg2 = dest;
+        instr.arg3 = LLirArg::Int(1);
+    }
+    
+    builder.add_code(instr);
+    true
+}
+
+// Stokas la rezulton de kondiĉo en bulea variablo
+pub fn build_bool_assign(builder : &mut LLirBuilder, var : &Var, line : &AstStmt) -> bool {
+    let false_label = builder.new_label();
+    let end_label = builder.new_label();
+    
+    if !build_cmp(builder, line, &false_label, false) {
+        return false;
+    }
+    
+    build_store(builder, LLirArg::Mem(var.name.clone()), LLirArg::UInt(1), &var.data_type);
+    build_jmp(builder, &end_label);
+    
+    build_label(builder, &false_label);
+    build_store(builder, LLirArg::Mem(var.name.clone()), LLirArg::UInt(0), &var.data_type);
+    
+    build_label(builder, &end_label);
+    true
+}
+
+// Redonas la tipon de la unua operando kiu ne estas laŭvorto
+fn cmp_type(builder : &mut LLirBuilder, args : &Vec<AstArg>) -> Option<LLirDataType> {
+    for arg in args.iter() {
+        match &arg.arg_type {
+            AstArgType::Id | AstArgType::Field | AstArgType::Sizeof
+            | AstArgType::AddrOf | AstArgType::Expr => return Some(operand_type(builder, arg)),
+            
+            _ => {},
+        }
+    }
+    
+    None
+}
+
+fn is_cmp_op(arg_type : &AstArgType) -> bool {
+    match &arg_type {
+        AstArgType::OpEq | AstArgType::OpNeq
+        | AstArgType::OpLt | AstArgType::OpLte
+        | AstArgType::OpGt | AstArgType::OpGte => return true,
+        _ => return false,
+    }
+}
+
+fn invert_op(op : &AstArgType) -> AstArgType {
+    match &op {
+        AstArgType::OpEq => return AstArgType::OpNeq,
+        AstArgType::OpNeq => return AstArgType::OpEq,
+        AstArgType::OpLt => return AstArgType::OpGte,
+        AstArgType::OpLte => return AstArgType::OpGt,
+        AstArgType::OpGt => return AstArgType::OpLte,
+        _ => return AstArgType::OpLt,
+    }
+}
+
+// Redonas la komparan instrukcion por la operatoro kaj la tipo
+fn cmp_for_type(op : &AstArgType, data_type : &LLirDataType) -> LLirType {
+    match (op, data_type) {
+        (AstArgType::OpEq, LLirDataType::Float) => return LLirType::CeqF32,
+        (AstArgType::OpNeq, LLirDataType::Float) => return LLirType::CneqF32,
+        (AstArgType::OpLt, LLirDataType::Float) => return LLirType::CltF32,
+        (AstArgType::OpLte, LLirDataType::Float) => return LLirType::CleF32,
+        (AstArgType::OpGt, LLirDataType::Float) => return LLirType::CgtF32,
+        (_, LLirDataType::Float) => return LLirType::CgeF32,
+        
+        (AstArgType::OpEq, LLirDataType::Double) => return LLirType::CeqF64,
+        (AstArgType::OpNeq, LLirDataType::Double) => return LLirType::CneqF64,
+        (AstArgType::OpLt, LLirDataType::Double) => return LLirType::CltF64,
+        (AstArgType::OpLte, LLirDataType::Double) => return LLirType::CleF64,
+        (AstArgType::OpGt, LLirDataType::Double) => return LLirType::CgtF64,
+        (_, LLirDataType::Double) => return LLirType::CgeF64,
+        
+        _ => {},
+    }
+    
+    // La kolumnoj estas la grandecoj: byte, word, dword, qword
+    let pos = match size_for_type(data_type) {
+        1 => 0,
+        2 => 1,
+        4 => 2,
+        _ => 3,
+    };
+    
+    let signed = !is_unsigned(data_type) && !is_pointer(data_type);
+    
+    let types = match (op, signed) {
+        (AstArgType::OpEq, _) => [LLirType::CeqB, LLirType::CeqW, LLirType::CeqDW, LLirType::CeqQW],
+        (AstArgType::OpNeq, _) => [LLirType::CneqB, LLirType::CneqW, LLirType::CneqDW, LLirType::CneqQW],
+        (AstArgType::OpLt, true) => [LLirType::CsltB, LLirType::CsltW, LLirType::CsltDW, LLirType::CsltQW],
+        (AstArgType::OpLt, false) => [LLirType::CultB, LLirType::CultW, LLirType::CultDW, LLirType::CultQW],
+        (AstArgType::OpLte, true) => [LLirType::CsleB, LLirType::CsleW, LLirType::CsleDW, LLirType::CsleQW],
+        (AstArgType::OpLte, false) => [LLirType::CuleB, LLirType::CuleW, LLirType::CuleDW, LLirType::CuleQW],
+        (AstArgType::OpGt, true) => [LLirType::CsgtB, LLirType::CsgtW, LLirType::CsgtDW, LLirType::CsgtQW],
+        (AstArgType::OpGt, false) => [LLirType::CugtB, LLirType::CugtW, LLirType::CugtDW, LLirType::CugtQW],
+        (_, true) => [LLirType::CsgeB, LLirType::CsgeW, LLirType::CsgeDW, LLirType::CsgeQW],
+        (_, false) => [LLirType::CugeB, LLirType::CugeW, LLirType::CugeDW, LLirType::CugeQW],
+    };
+    
+    types[pos].clone()
+}
+
+fn build_jmp(builder : &mut LLirBuilder, label : &String) {
+    let mut instr = llir::create_instr(LLirType::Jmp);
+    instr.arg1 = LLirArg::Label(label.clone());
+    builder.add_code(instr);
+}
+
+fn build_label(builder : &mut LLirBuilder, label : &String) {
+    let mut instr = llir::create_instr(LLirType::Label);
+    instr.arg1 = LLirArg::Label(label.clone());
+    builder.add_code(instr);
+}
@@ -15,79 +15,290 @@
 // with this program; if not, write to the Free Software Foundation, Inc.,
 // 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
 
-use crate::ast::{AstStmt, AstArgType};
+use crate::ast::{AstStmt, AstArg, AstArgType};
+use crate::ast_expr::{AstExpr, arg_expr};
 use crate::llir;
 use crate::llir::*;
 use crate::llir_builder::*;
+use crate::llir_var::*;
 
-// Konstruas reveno aserto.
-pub fn build_return(builder : &mut LLirBuilder, line : &AstStmt) -> bool {
-    let mut instr = llir::create_instr(LLirType::Ret);
+// Ŝarĝas la parametrojn al lokaj variabloj
+pub fn build_func_params(builder : &mut LLirBuilder, params : &Vec<Var>) {
+    let mut pos = 0;
     
-    if line.args.len() == 1 {
-        let arg = line.args.first().unwrap();
-        match arg.arg_type {
-            // TODO: Ni bezonas tipdetekton.
-            AstArgType::IntL => instr.arg1 = LLirArg::Int(arg.u64_val as i64),
+    for param in params.iter() {
+        let mut instr = alloc_for_type(&param.data_type);
+        instr.data_type = param.data_type.clone();
+        instr.arg1 = LLirArg::Label(param.name.clone());
+        builder.add_code(instr);
+        
+        let mut instr = ldarg_for_type(&param.data_type);
+        instr.data_type = param.data_type.clone();
+        instr.arg1 = LLirArg::Mem(param.name.clone());
+        instr.arg2 = LLirArg::ArgReg(pos);
+        builder.add_code(instr);
+        
+        pos += 1;
+        
+        // Tabeloj ankaŭ havas la grandecon
+        if is_array(param) {
+            let mut size_name = param.name.clone();
+            size_name.push_str(".size");
             
-            // TODO: Tipdetekton
-            AstArgType::Id => {
-                let mut instr2 = llir::create_instr(LLirType::LdDW);
-                instr2.arg1 = LLirArg::Reg(builder.reg_pos);
-                instr2.arg2 = LLirArg::Mem(arg.str_val.clone());
-                builder.add_code(instr2);
-                
-                instr.arg1 = LLirArg::Reg(builder.reg_pos);
-                
-                builder.reg_pos += 1;
-            },
+            let mut instr = llir::create_instr(LLirType::AllocDW);
+            instr.data_type = LLirDataType::Int;
+            instr.arg1 = LLirArg::Label(size_name.clone());
+            builder.add_code(instr);
+            
+            let mut instr = llir::create_instr(LLirType::LdArgDW);
+            instr.data_type = LLirDataType::Int;
+            instr.arg1 = LLirArg::Mem(size_name);
+            instr.arg2 = LLirArg::ArgReg(pos);
+            builder.add_code(instr);
             
-            _ => {},
+            pos += 1;
         }
-    } else {
-        // TODO: Konstrui esprimon
+        
+        builder.vars.push(param.clone());
+    }
+}
+
+// Konstruas reveno aserto.
+pub fn build_return(builder : &mut LLirBuilder, line : &AstStmt) -> bool {
+    builder.syntax.set_data(line);
+    let mut instr = llir::create_instr(LLirType::Ret);
+    
+    if let Some(expr) = &line.expr {
+        let data_type = builder.func_type.clone();
+        
+        instr.arg1 = match build_expr(builder, expr, &data_type) {
+            Ok(v) => v,
+            Err(_e) => return false,
+        };
+        
+        instr.data_type = data_type;
     }
     
     builder.add_code(instr);
     true
 }
 
-pub fn build_end(builder : &mut LLirBuilder, _line : &AstStmt) -> bool {
-    let instr = llir::create_instr(LLirType::Ret);
+// Konstruas eliron el la programo
+pub fn build_exit(builder : &mut LLirBuilder, line : &AstStmt) -> bool {
+    builder.syntax.set_data(line);
+    let mut code = LLirArg::Int(0);
+    
+    if let Some(expr) = &line.expr {
+        code = match build_expr(builder, expr, &LLirDataType::Int) {
+            Ok(v) => v,
+            Err(_e) => return false,
+        };
+    }
+    
+    // La malantaŭo elektas la funkcion, ĉar nur ĝi scias ĉu ni uzas la C bibliotekon
+    let mut instr = llir::create_instr(LLirType::Exit);
+    instr.arg1 = code;
     builder.add_code(instr);
     
     true
 }
 
+// Nur la fino de la funkcio revenas; la aliaj finoj apartenas al blokoj
+pub fn build_end(builder : &mut LLirBuilder, _line : &AstStmt) -> bool {
+    if builder.block_layer > 0 {
+        return true;
+    }
+    
+    match builder.file.code.last() {
+        Some(last) if last.instr_type == LLirType::Ret => {},
+        
+        _ => {
+            let instr = llir::create_instr(LLirType::Ret);
+            builder.add_code(instr);
+        },
+    }
+    
+    true
+}
+
 // Konstruas funkcion alvokon
 pub fn build_func_call(builder : &mut LLirBuilder, line : &AstStmt) -> bool {
-    let args = &line.args;
+    builder.syntax.set_data(line);
+    
+    if line.name == "syscall" {
+        return build_syscall(builder, &line.args, false).is_ok();
+    }
+    
+    build_call(builder, &line.name, &line.args, false).is_ok()
+}
+
+// Konstruas la alvokon kaj redonas la registron kun la rezulto
+// Argumentoj estas konvertitaj al la tipoj de la parametroj. Mankantaj nedevigaj
+// argumentoj estas nuloj, kaj pliaj argumentoj de variadaj funkcioj estas promociitaj
+// kiel en C.
+pub fn build_call(builder : &mut LLirBuilder, name : &String, args : &Vec<AstArg>, has_dest : bool) -> Result<LLirArg, ()> {
+    // Nerektaj alvokoj trairas proceduran variablon
+    let proc_sig = builder.get_proc(name);
+    let is_indirect = proc_sig.is_some();
+    
+    let (data_type, params) = match proc_sig.or_else(|| builder.funcs.get(name).cloned()) {
+        Some(f) => (f.data_type.clone(), f.params.clone()),
+        None => (LLirDataType::Void, Vec::new()),
+    };
+    
     let mut arg_list : Vec<LLirArg> = Vec::new();
+    let mut pos = 0;
+    let mut negate = false;
     
-    for arg in args {
-        match &arg.arg_type {
-            AstArgType::StringL => {
-                arg_list.push(LLirArg::StrLiteral(arg.str_val.clone()));
+    for arg in args.iter() {
+        if arg.arg_type == AstArgType::OpNeg {
+            negate = true;
+            continue;
+        }
+        
+        let mut value : LLirArg;
+        
+        match params.get(pos) {
+            // Tabeloj pasas la montrilon kaj la grandecon
+            Some(param) if is_array(param) => {
+                let mut size_name = arg.str_val.clone();
+                size_name.push_str(".size");
+                
+                value = build_load(builder, LLirArg::Mem(arg.str_val.clone()), &LLirDataType::Ptr);
+                arg_list.push(value);
+                
+                value = build_load(builder, LLirArg::Mem(size_name), &LLirDataType::Int);
             },
             
-            AstArgType::Id => {
-                arg_list.push(LLirArg::Mem(arg.str_val.clone()));
+            Some(param) => {
+                if negate {
+                    value = build_expr(builder, &negate_expr(arg), &param.data_type)?;
+                } else {
+                    value = build_operand(builder, arg, &param.data_type)?;
+                }
+                
+                // La tipo de nerekta alvoko venas de la argumentoj, do konstantoj bezonas tipon
+                if is_indirect {
+                    value = build_typed_const(builder, value, &param.data_type);
+                }
             },
             
-            // TODO: Switch to unsigned
-            AstArgType::IntL => {
-                arg_list.push(LLirArg::UInt(arg.u64_val));
+            None => {
+                let arg_type = promote_type(&operand_type(builder, arg));
+                
+                if negate {
+                    value = build_expr(builder, &negate_expr(arg), &arg_type)?;
+                } else {
+                    value = build_operand(builder, arg, &arg_type)?;
+                }
+                
+                // Konstantoj bezonas tipon por la variada parto
+                value = build_typed_const(builder, value, &arg_type);
             },
-            
-            _ => {},
         }
+        
+        arg_list.push(value);
+        negate = false;
+        pos += 1;
     }
-
+    
+    // La nedevigaj parametroj
+    while pos < params.len() {
+        arg_list.push(LLirArg::Int(0));
+        
+        if is_array(&params[pos]) {
+            arg_list.push(LLirArg::Int(0));
+        }
+        
+        pos += 1;
+    }
+    
     let mut instr = llir::create_instr(LLirType::Call);
-    instr.arg1 = LLirArg::Label(line.name.clone());
+    instr.data_type = data_type.clone();
     instr.arg2 = LLirArg::ArgList(arg_list);
     
+    if is_indirect {
+        instr.arg1 = build_load(builder, LLirArg::Mem(name.clone()), &LLirDataType::Ptr);
+    } else {
+        instr.arg1 = LLirArg::Label(name.clone());
+    }
+    
+    if has_dest && data_type != LLirDataType::Void {
+        instr.arg3 = builder.new_reg();
+    }
+    
+    let dest = instr.arg3.clone();
     builder.add_code(instr);
-    true
+    
+    Ok(dest)
 }
 
+// Ŝargas konstanton en registron de la donita tipo
+// Aliaj valoroj jam havas tipon
+fn build_typed_const(builder : &mut LLirBuilder, value : LLirArg, data_type : &LLirDataType) -> LLirArg {
+    match &value {
+        LLirArg::Int(_) | LLirArg::UInt(_) | LLirArg::Float(_) => {
+            let dest = builder.new_reg();
+            
+            let mut instr = llir::create_instr(LLirType::Li);
+            instr.data_type = data_type.clone();
+            instr.arg1 = dest.clone();
+            instr.arg2 = value;
+            builder.add_code(instr);
+            
+            return dest;
+        },
+        
+        _ => return value,
+    }
+}
+
+// Konstruas sistemvokon
+// Ĉiuj argumentoj kaj la rezulto estas 64-bitaj
+pub fn build_syscall(builder : &mut LLirBuilder, args : &Vec<AstArg>, has_dest : bool) -> Result<LLirArg, ()> {
+    let mut arg_list : Vec<LLirArg> = Vec::new();
+    let mut negate = false;
+    
+    for arg in args.iter() {
+        if arg.arg_type == AstArgType::OpNeg {
+            negate = true;
+            continue;
+        }
+        
+        let value = if negate {
+            build_expr(builder, &negate_expr(arg), &LLirDataType::Int64)?
+        } else {
+            build_operand(builder, arg, &LLirDataType::Int64)?
+        };
+        
+        arg_list.push(value);
+        negate = false;
+    }
+    
+    let mut instr = llir::create_instr(LLirType::Syscall);
+    instr.data_type = LLirDataType::Int64;
+    instr.arg2 = LLirArg::ArgList(arg_list);
+    
+    if has_dest {
+        instr.arg3 = builder.new_reg();
+    }
+    
+    let dest = instr.arg3.clone();
+    builder.add_code(instr);
+    
+    Ok(dest)
+}
+
+// La C promocioj por variadaj argumentoj
+fn promote_type(data_type : &LLirDataType) -> LLirDataType {
+    match &data_type {
+        LLirDataType::Byte | LLirDataType::Word => return LLirDataType::Int,
+        LLirDataType::UByte | LLirDataType::UWord => return LLirDataType::UInt,
+        LLirDataType::Float => return LLirDataType::Double,
+        _ => return data_type.clone(),
+    }
+}
+
+fn negate_expr(arg : &AstArg) -> AstExpr {
+    AstExpr::Unary(AstArgType::OpNeg, Box::new(arg_expr(arg)))
+}
@@ -0,0 +1,180 @@
+
+// This file is part of the Lila compiler
+// Copyright (C) 2020-2021 Patrick Flynn
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; version 2.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, write to the Free Software Foundation, Inc.,
+// 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+
+// Handles record types in LLIR
+//
+// Like in LTAC, a record variable is a pointer to the record's memory, and the
+// fields are laid out in declaration order with each one aligned to its own size.
+// Field operands carry the byte offset so the backend can address them directly.
+
+use crate::ast::{AstStmt, AstArg};
+use crate::syntax::*;
+use crate::llir;
+use crate::llir::*;
+use crate::llir_builder::*;
+use crate::llir_var::*;
+
+// Konstruas lokan rekordon
+// Kun komenca valoro (ekz. de malloc aŭ alia rekordo), la variablo nur tenas la montrilon
+pub fn build_record_dec(builder : &mut LLirBuilder, line : &AstStmt, name : &String) -> bool {
+    if line.args.len() > 0 {
+        let var = create_var(&line.name, &line.data_type, &line.sub_type);
+        
+        let mut instr = llir::create_instr(LLirType::AllocQW);
+        instr.data_type = LLirDataType::Ptr;
+        instr.arg1 = LLirArg::Label(line.name.clone());
+        builder.add_code(instr);
+        
+        builder.vars.push(var.clone());
+        return build_assign(builder, &var, line);
+    }
+    
+    let size = record_size(builder, name);
+    
+    let mut instr = llir::create_instr(LLirType::AllocRec);
+    instr.data_type = LLirDataType::Ptr;
+    instr.arg1 = LLirArg::Label(line.name.clone());
+    instr.arg2 = LLirArg::Int(size as i64);
+    builder.add_code(instr);
+    
+    let var = Var {
+        name : line.name.clone(),
+        data_type : LLirDataType::Ptr,
+        sub_type : LLirDataType::Void,
+        record : name.clone(),
+        proc_type : String::new(),
+        is_typed_ptr : false,
+    };
+    
+    builder.vars.push(var);
+    
+    true
+}
+
+// Redonas la grandecon de rekordo
+pub fn record_size(builder : &LLirBuilder, name : &String) -> i32 {
+    let record = match builder.records.get(name) {
+        Some(r) => r,
+        None => return 0,
+    };
+    
+    let mut size = 0;
+    let mut align = 1;
+    
+    for field in record.fields.iter() {
+        let (data_type, _) = ast_to_datatype(&field.data_type);
+        let field_size = size_for_type(&data_type);
+        
+        if size % field_size != 0 {
+            size += field_size - (size % field_size);
+        }
+        
+        size += field_size;
+        
+        if field_size > align {
+            align = field_size;
+        }
+    }
+    
+    if size % align != 0 {
+        size += align - (size % align);
+    }
+    
+    size
+}
+
+// Konvertas kampon al LLIR operando
+pub fn get_field(builder : &mut LLirBuilder, var_name : &String, sub_args : &Vec<AstArg>) -> Result<LLirArg, ()> {
+    let record_name = match builder.get_var(var_name) {
+        Some(v) if v.record.len() > 0 => v.record.clone(),
+        
+        _ => {
+            let mut msg = "Invalid record variable: ".to_string();
+            msg.push_str(var_name);
+            
+            builder.syntax.ltac_error2(msg).code(E_UNKNOWN_NAME);
+            return Err(());
+        },
+    };
+    
+    let field_name = match sub_args.first() {
+        Some(arg) => arg.str_val.clone(),
+        None => {
+            builder.syntax.ltac_error2("Invalid field access.".to_string());
+            return Err(());
+        },
+    };
+    
+    let fields = match builder.records.get(&record_name) {
+        Some(r) => r.fields.clone(),
+        None => return Err(()),
+    };
+    
+    let mut offset = 0;
+    
+    for field in fields.iter() {
+        let (data_type, _) = ast_to_datatype(&field.data_type);
+        let field_size = size_for_type(&data_type);
+        
+        if offset % field_size != 0 {
+            offset += field_size - (offset % field_size);
+        }
+        
+        if field.name == field_name {
+            return Ok(LLirArg::FieldMem(var_name.clone(), offset, data_type));
+        }
+        
+        offset += field_size;
+    }
+    
+    let mut msg = "Invalid field name: ".to_string();
+    msg.push_str(&field_name);
+    
+    builder.syntax.ltac_error2(msg).code(E_UNKNOWN_NAME);
+    Err(())
+}
+
+// Konstruas kampan asignon
+pub fn build_field_assign(builder : &mut LLirBuilder, line : &AstStmt) -> bool {
+    builder.syntax.set_data(line);
+    
+    let field = match get_field(builder, &line.name, &line.sub_args) {
+        Ok(f) => f,
+        Err(_e) => return false,
+    };
+    
+    let data_type = match &field {
+        LLirArg::FieldMem(_, _, data_type) => data_type.clone(),
+        _ => return false,
+    };
+    
+    let expr = match &line.expr {
+        Some(e) => e,
+        None => {
+            builder.syntax.ltac_error(line, "Invalid expression.".to_string());
+            return false;
+        },
+    };
+    
+    let value = match build_expr(builder, expr, &data_type) {
+        Ok(v) => v,
+        Err(_e) => return false,
+    };
+    
+    build_store(builder, field, value, &data_type);
+    true
+}
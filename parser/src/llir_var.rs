@@ -15,204 +15,757 @@
 // with this program; if not, write to the Free Software Foundation, Inc.,
 // 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
 
-use crate::ast::{DataType, AstStmt, AstArgType};
+use crate::ast;
+use crate::ast::{DataType, AstStmt, AstArg, AstArgType};
+use crate::ast_expr::{AstExpr, arg_expr, call_args};
+use crate::syntax::*;
 use crate::llir;
 use crate::llir::*;
 use crate::llir_builder::*;
+use crate::llir_flow::build_bool_assign;
+use crate::llir_func::*;
+use crate::llir_record::*;
 
 // Builds an LLIR variable declaration
-// Note for array sizes:
-//    Array sizes are 12 bytes long.
-//    The first 8 bytes hold the pointer, and the second 4 hold the size
+// Note for arrays:
+//    The variable holds a pointer to the elements, and the size is kept in a
+//    hidden variable named "<name>.size"
 //
 pub fn build_var_dec(builder : &mut LLirBuilder, line : &AstStmt) -> bool {
-    let name = line.name.clone();
-    let data_type : LLirDataType;
-    let sub_type = LLirDataType::Void;
+    builder.syntax.set_data(line);
+    let var = create_var(&line.name, &line.data_type, &line.sub_type);
     
     match &line.data_type {
-        DataType::Byte | DataType::Char => data_type = LLirDataType::Byte,
-        DataType::UByte => data_type = LLirDataType::UByte,
-        
-        DataType::Short => data_type = LLirDataType::Word,
-        DataType::UShort => data_type = LLirDataType::UWord,
-        
-        DataType::Int => data_type = LLirDataType::Int,
-        DataType::UInt => data_type = LLirDataType::UInt,
-        
-        DataType::Int64 => data_type = LLirDataType::Int64,
-        DataType::UInt64 => data_type = LLirDataType::UInt64,
-        
-        DataType::Str => data_type = LLirDataType::Str,
-        
-        _ => return false,
+        DataType::Record(record) => return build_record_dec(builder, line, record),
+        DataType::Ptr => return build_array_dec(builder, line, var),
+        DataType::None | DataType::Void => return false,
+        _ => {},
     }
     
     // Krei la alloc instrukion
-    let instr_type : LLirType;
+    let mut instr = alloc_for_type(&var.data_type);
+    instr.data_type = var.data_type.clone();
+    instr.arg1 = LLirArg::Label(var.name.clone());
+    builder.add_code(instr);
     
-    match &data_type {
-        LLirDataType::Byte | LLirDataType::UByte => instr_type = LLirType::AllocB,
-        LLirDataType::Word | LLirDataType::UWord => instr_type = LLirType::AllocW,
-        LLirDataType::Int | LLirDataType::UInt => instr_type = LLirType::AllocDW,
-        LLirDataType::Int64 | LLirDataType::UInt64 
-        | LLirDataType::Str => instr_type = LLirType::AllocQW,
-        LLirDataType::Ptr => instr_type = LLirType::AllocArr,
-        
-        _ => instr_type = LLirType::Nop,
+    // Puŝi la variablon
+    builder.vars.push(var.clone());
+    
+    if line.args.len() == 0 {
+        return true;
+    } else if ast::is_cond(&line.args) {
+        return build_bool_assign(builder, &var, line);
     }
     
-    let mut instr = llir::create_instr(instr_type);
-    instr.data_type = data_type.clone();
-    instr.arg1 = LLirArg::Label(name.clone());
+    build_assign(builder, &var, line)
+}
+
+// Konstruas tabelon sur la stako
+fn build_array_dec(builder : &mut LLirBuilder, line : &AstStmt, var : Var) -> bool {
+    let size = match &line.sub_expr {
+        Some(expr) => match build_expr(builder, expr, &LLirDataType::Int) {
+            Ok(s) => s,
+            Err(_e) => return false,
+        },
+        
+        None => LLirArg::Int(0),
+    };
+    
+    let mut instr = llir::create_instr(LLirType::AllocArr);
+    instr.data_type = var.sub_type.clone();
+    instr.arg1 = LLirArg::Label(var.name.clone());
+    instr.arg2 = size.clone();
     builder.add_code(instr);
     
-    // Puŝi la variablon
-    let var = Var {
-        name : name.clone(),
-        data_type : data_type,
-        sub_type : sub_type,
-    };
+    let mut size_name = var.name.clone();
+    size_name.push_str(".size");
     
-    if !build_expr(builder, line, &var) {
-        return false;
+    let mut instr = llir::create_instr(LLirType::AllocDW);
+    instr.data_type = LLirDataType::Int;
+    instr.arg1 = LLirArg::Label(size_name.clone());
+    builder.add_code(instr);
+    
+    build_store(builder, LLirArg::Mem(size_name), size, &LLirDataType::Int);
+    
+    // Tabela literalo stokas ĉiun valoron en sia ero
+    if line.args.len() == 1 && line.args[0].arg_type == AstArgType::ArrayL {
+        for (i, value) in line.args[0].sub_args.iter().enumerate() {
+            let value = match build_expr(builder, &arg_expr(value), &var.sub_type) {
+                Ok(v) => v,
+                Err(_e) => return false,
+            };
+            
+            let element = LLirArg::ElementMem(var.name.clone(), Box::new(LLirArg::Int(i as i64)), var.sub_type.clone());
+            build_store(builder, element, value, &var.sub_type);
+        }
     }
     
     builder.vars.push(var);
-    
     true
 }
 
 pub fn build_var_assign(builder : &mut LLirBuilder, line : &AstStmt) -> bool {
-    let vars = builder.vars.clone();
-    for v in &vars {
-        if v.name == line.name {
-            return build_expr(builder, line, &v);
-        }
+    builder.syntax.set_data(line);
+    
+    let var = match builder.get_var(&line.name) {
+        Some(v) => v,
+        None => {
+            let mut msg = "Unknown variable: ".to_string();
+            msg.push_str(&line.name);
+            
+            builder.syntax.ltac_error(line, msg).code(E_UNKNOWN_NAME);
+            return false;
+        },
+    };
+    
+    if is_array(&var) && line.args.len() > 1 {
+        return build_vector_math(builder, &var, line);
+    } else if ast::is_cond(&line.args) {
+        return build_bool_assign(builder, &var, line);
     }
     
-    false
+    build_assign(builder, &var, line)
 }
 
-// Konstrui variablon esprimon
-pub fn build_expr(builder : &mut LLirBuilder, line : &AstStmt, var : &Var) -> bool {
-    let args = &line.args;
-    let mut stack : Vec<LLirArg> = Vec::new();
+// Konstruas la esprimon kaj stokas ĝin en la variablo
+pub fn build_assign(builder : &mut LLirBuilder, var : &Var, line : &AstStmt) -> bool {
+    let value = match build_stmt_expr(builder, line, &var.data_type) {
+        Ok(v) => v,
+        Err(_e) => return false,
+    };
     
-    for arg in args.iter() {
-        match &arg.arg_type {
-            AstArgType::IntL => {
-                let intl : LLirArg;
-                
-                if is_unsigned(&var.data_type) {
-                    intl = LLirArg::UInt(arg.u64_val);
-                } else {
-                    intl = LLirArg::Int(arg.u64_val as i64);
-                }
-                
-                stack.push(intl);
-            },
+    build_store(builder, LLirArg::Mem(var.name.clone()), value, &var.data_type);
+    true
+}
+
+// Konstruas tabelan asignon
+pub fn build_array_assign(builder : &mut LLirBuilder, line : &AstStmt) -> bool {
+    builder.syntax.set_data(line);
+    
+    let var = match builder.get_var(&line.name) {
+        Some(v) => v,
+        None => {
+            let mut msg = "Unknown array: ".to_string();
+            msg.push_str(&line.name);
             
-            AstArgType::Id => {
-                let id = LLirArg::Mem(arg.str_val.clone());
-                stack.push(id);
+            builder.syntax.ltac_error(line, msg).code(E_UNKNOWN_NAME);
+            return false;
+        },
+    };
+    
+    let index = match &line.sub_expr {
+        Some(expr) => expr,
+        None => {
+            builder.syntax.ltac_error(line, "Invalid array index.".to_string());
+            return false;
+        },
+    };
+    
+    let element = match build_element(builder, &var, index) {
+        Ok(e) => e,
+        Err(_e) => return false,
+    };
+    
+    let data_type = element_type(&var);
+    
+    let value = match build_stmt_expr(builder, line, &data_type) {
+        Ok(v) => v,
+        Err(_e) => return false,
+    };
+    
+    build_store(builder, element, value, &data_type);
+    true
+}
+
+// Konstruas vektoran adicion
+// Ĉiu operando estas la komenco de ok eroj; ekz: numbers_sum = numbers + numbers[8];
+fn build_vector_math(builder : &mut LLirBuilder, var : &Var, line : &AstStmt) -> bool {
+    let mut values : Vec<&AstExpr> = Vec::new();
+    
+    if let Some(expr) = &line.expr {
+        if !vector_values(expr, &mut values) {
+            builder.syntax.ltac_error(line, "Only addition is supported with vectors.".to_string());
+            return false;
+        }
+    }
+    
+    let mut operands : Vec<LLirArg> = Vec::new();
+    
+    for value in values {
+        let (name, index) = match value {
+            AstExpr::Operand(arg) if arg.arg_type == AstArgType::Id => (&arg.str_val, None),
+            AstExpr::Index(arg, index) => (&arg.str_val, Some(&**index)),
+            
+            _ => {
+                builder.syntax.ltac_error(line, "Vector operands must be arrays.".to_string());
+                return false;
+            },
+        };
+        
+        let src = match builder.get_var(name) {
+            Some(v) if v.data_type == LLirDataType::Ptr => v,
+            _ => {
+                builder.syntax.ltac_error(line, "Vector operands must be arrays.".to_string());
+                return false;
+            },
+        };
+        
+        match index {
+            Some(index) => match build_element(builder, &src, index) {
+                Ok(e) => operands.push(e),
+                Err(_e) => return false,
             },
             
-            AstArgType::OpAdd | AstArgType::OpSub
-            | AstArgType::OpMul | AstArgType::OpDiv | AstArgType::OpMod
-            | AstArgType::OpAnd | AstArgType::OpOr | AstArgType::OpXor
-            | AstArgType::OpLeftShift | AstArgType::OpRightShift
-            if stack.len() >= 2 => {
-                let arg2 = stack.pop().unwrap();
-                let arg1 = stack.pop().unwrap();
-                
-                let dest = LLirArg::Reg(builder.reg_pos);
-                builder.reg_pos += 1;
-                
-                //let mut instr = llir::create_instr(LLirType::Add);
-                let mut instr : LLirInstr;
-                
-                match &arg.arg_type {
-                    AstArgType::OpAdd => instr = llir::create_instr(LLirType::Add),
-                    AstArgType::OpSub => instr = llir::create_instr(LLirType::Sub),
-                    
-                    AstArgType::OpMul if is_unsigned(&var.data_type)
-                        => instr = llir::create_instr(LLirType::UMul),
-                    AstArgType::OpMul => instr = llir::create_instr(LLirType::Mul),
-                    
-                    AstArgType::OpDiv if is_unsigned(&var.data_type)
-                        => instr = llir::create_instr(LLirType::UDiv),
-                    AstArgType::OpDiv => instr = llir::create_instr(LLirType::Div),
-                    
-                    AstArgType::OpMod if is_unsigned(&var.data_type)
-                        => instr = llir::create_instr(LLirType::URem),
-                    AstArgType::OpMod => instr = llir::create_instr(LLirType::Rem),
-                    
-                    AstArgType::OpAnd => instr = llir::create_instr(LLirType::And),
-                    AstArgType::OpOr => instr = llir::create_instr(LLirType::Or),
-                    AstArgType::OpXor => instr = llir::create_instr(LLirType::Xor),
-                    AstArgType::OpLeftShift => instr = llir::create_instr(LLirType::Lsh),
-                    AstArgType::OpRightShift => instr = llir::create_instr(LLirType::Rsh),
+            None => operands.push(LLirArg::Mem(src.name.clone())),
+        }
+    }
+    
+    if operands.len() < 2 {
+        builder.syntax.ltac_error(line, "Invalid vector expression.".to_string());
+        return false;
+    }
+    
+    let dest = LLirArg::Mem(var.name.clone());
+    let mut lval = operands.remove(0);
+    
+    for rval in operands {
+        let mut instr = llir::create_instr(LLirType::VAddDW);
+        instr.data_type = var.sub_type.clone();
+        instr.arg1 = dest.clone();
+        instr.arg2 = lval;
+        instr.arg3 = rval;
+        builder.add_code(instr);
+        
+        lval = dest.clone();
+    }
+    
+    true
+}
+
+// Kolektas la operandojn de vektora adicio, de maldekstre dekstren
+fn vector_values<'a>(expr : &'a AstExpr, values : &mut Vec<&'a AstExpr>) -> bool {
+    match expr {
+        AstExpr::Binary(AstArgType::OpAdd, lhs, rhs) => return vector_values(lhs, values) && vector_values(rhs, values),
+        AstExpr::Binary(..) | AstExpr::Unary(..) => return false,
+        
+        _ => {
+            values.push(expr);
+            return true;
+        },
+    }
+}
+
+// Konstruas la esprimon de aserto
+fn build_stmt_expr(builder : &mut LLirBuilder, line : &AstStmt, data_type : &LLirDataType) -> Result<LLirArg, ()> {
+    match &line.expr {
+        Some(expr) => return build_expr(builder, expr, data_type),
+        None => {
+            builder.syntax.ltac_error(line, "Invalid expression.".to_string());
+            return Err(());
+        },
+    }
+}
+
+// Konstruas esprimon el ĝia arbo
+// Redonas la operandon kiu tenas la rezulton, konvertitan al la donita tipo
+pub fn build_expr(builder : &mut LLirBuilder, expr : &AstExpr, data_type : &LLirDataType) -> Result<LLirArg, ()> {
+    match expr {
+        AstExpr::Operand(arg) => return build_operand(builder, arg, data_type),
+        
+        // La nomo de alvoko tenas la argumentojn, same kiel en alvoka aserto
+        AstExpr::Call(name, args) => {
+            let mut arg = name.clone();
+            arg.sub_args = call_args(args);
+            return build_id(builder, &arg, data_type);
+        },
+        
+        AstExpr::Index(name, index) => {
+            let var = match builder.get_var(&name.str_val) {
+                Some(v) => v,
+                None => {
+                    let mut msg = "Unknown array: ".to_string();
+                    msg.push_str(&name.str_val);
                     
-                    // We should never get to this point
-                    _ => return false,
+                    builder.syntax.ltac_error2(msg).code(E_UNKNOWN_NAME);
+                    return Err(());
+                },
+            };
+            
+            let element = build_element(builder, &var, index)?;
+            let elem_type = element_type(&var);
+            
+            let value = build_load(builder, element, &elem_type);
+            return Ok(build_convert(builder, value, &elem_type, data_type));
+        },
+        
+        AstExpr::Unary(AstArgType::OpNeg, value) => {
+            let value = build_expr(builder, value, data_type)?;
+            return Ok(build_negate(builder, value, data_type));
+        },
+        
+        // Montrila aritmetiko; la tipkontrolilo permesas nur "p + n" kaj "p - n"
+        AstExpr::Binary(op, lhs, rhs) if *data_type == LLirDataType::Ptr => {
+            if let AstExpr::Operand(arg) = &**lhs {
+                if let Some(var) = builder.get_var(&arg.str_val) {
+                    if var.is_typed_ptr {
+                        return build_ptr_math(builder, &var, op, rhs);
+                    }
                 }
+            }
+            
+            return build_arith(builder, op, lhs, rhs, data_type);
+        },
+        
+        AstExpr::Binary(op, lhs, rhs) => return build_arith(builder, op, lhs, rhs, data_type),
+        
+        _ => {
+            builder.syntax.ltac_error2("Invalid expression.".to_string());
+            return Err(());
+        },
+    }
+}
+
+// Konstruas aritmetikan operacion; la maldekstra flanko estas kalkulata unue
+fn build_arith(builder : &mut LLirBuilder, op : &AstArgType, lhs : &AstExpr, rhs : &AstExpr, data_type : &LLirDataType) -> Result<LLirArg, ()> {
+    let arg1 = build_expr(builder, lhs, data_type)?;
+    let arg2 = build_expr(builder, rhs, data_type)?;
+    
+    let instr_type = arith_for_type(op, data_type);
+    
+    if instr_type == LLirType::Nop {
+        builder.syntax.ltac_error2("Invalid operator for this type.".to_string());
+        return Err(());
+    }
+    
+    let dest = builder.new_reg();
+    
+    let mut instr = llir::create_instr(instr_type);
+    instr.data_type = data_type.clone();
+    instr.arg1 = dest.clone();
+    instr.arg2 = arg1;
+    instr.arg3 = arg2;
+    builder.add_code(instr);
+    
+    Ok(dest)
+}
+
+// Konstruas montrilan aritmetikon (p + n)
+// La deŝovo estas nombrata laŭ eroj, do la rezulto estas la adreso de alia ero
+fn build_ptr_math(builder : &mut LLirBuilder, var : &Var, op : &AstArgType, offset : &AstExpr) -> Result<LLirArg, ()> {
+    let mut index = build_expr(builder, offset, &LLirDataType::Int64)?;
+    
+    if *op == AstArgType::OpSub {
+        index = build_negate(builder, index, &LLirDataType::Int64);
+    }
+    
+    let dest = builder.new_reg();
+    
+    let mut instr = llir::create_instr(LLirType::LdAddr);
+    instr.data_type = LLirDataType::Ptr;
+    instr.arg1 = dest.clone();
+    instr.arg2 = LLirArg::ElementMem(var.name.clone(), Box::new(index), var.sub_type.clone());
+    builder.add_code(instr);
+    
+    Ok(dest)
+}
+
+// Konstruas unu operandon de esprimo
+pub fn build_operand(builder : &mut LLirBuilder, arg : &AstArg, data_type : &LLirDataType) -> Result<LLirArg, ()> {
+    match &arg.arg_type {
+        AstArgType::ByteL => return Ok(int_literal(arg.u8_val as u64, data_type)),
+        AstArgType::ShortL => return Ok(int_literal(arg.u16_val as u64, data_type)),
+        AstArgType::CharL => return Ok(int_literal(arg.char_val as u64, data_type)),
+        AstArgType::IntL => return Ok(int_literal(arg.u64_val, data_type)),
+        AstArgType::FloatL => return Ok(LLirArg::Float(arg.f64_val)),
+        AstArgType::StringL => return Ok(LLirArg::StrLiteral(arg.str_val.clone())),
+        AstArgType::BoolL => return Ok(int_literal(arg.u8_val as u64, data_type)),
+        AstArgType::Id => return build_id(builder, arg, data_type),
+        
+        AstArgType::Field => {
+            let field = get_field(builder, &arg.str_val, &arg.sub_args)?;
+            
+            let field_type = match &field {
+                LLirArg::FieldMem(_, _, field_type) => field_type.clone(),
+                _ => return Err(()),
+            };
+            
+            let value = build_load(builder, field, &field_type);
+            return Ok(build_convert(builder, value, &field_type, data_type));
+        },
+        
+        AstArgType::Sizeof => return build_sizeof(builder, arg, data_type),
+        
+        // Kunmetita valoro; ĝi estas kalkulata en sia propra tipo
+        AstArgType::Expr if arg.expr.is_some() => {
+            let expr_type = operand_type(builder, arg);
+            
+            let value = build_expr(builder, &arg_expr(arg), &expr_type)?;
+            return Ok(build_convert(builder, value, &expr_type, data_type));
+        },
+        
+        // Nula montrilo
+        AstArgType::Null => return Ok(LLirArg::Int(0)),
+        
+        // La adreso de la variablo mem
+        AstArgType::AddrOf => {
+            let name = match arg.sub_args.first() {
+                Some(a) => a.str_val.clone(),
+                None => return Err(()),
+            };
+            
+            // Funkcioj havas simbolon, do ni prenas ĝian adreson rekte
+            if builder.get_var(&name).is_none() && builder.funcs.contains_key(&name) {
+                let dest = builder.new_reg();
                 
+                let mut instr = llir::create_instr(LLirType::LdAddr);
+                instr.data_type = LLirDataType::Ptr;
                 instr.arg1 = dest.clone();
-                instr.arg2 = arg1;
-                instr.arg3 = arg2;
+                instr.arg2 = LLirArg::Label(name);
                 builder.add_code(instr);
                 
-                stack.push(dest);
-            },
+                return Ok(build_convert(builder, dest, &LLirDataType::Ptr, data_type));
+            }
             
-            _ => {
-                println!("Syntax error- build_expr");
-                return false;
-            },
+            if builder.get_var(&name).is_none() {
+                builder.syntax.ltac_error2("Unknown variable reference.".to_string()).code(E_UNKNOWN_NAME);
+                return Err(());
+            }
+            
+            let dest = builder.new_reg();
+            
+            let mut instr = llir::create_instr(LLirType::LdAddr);
+            instr.data_type = LLirDataType::Int64;
+            instr.arg1 = dest.clone();
+            instr.arg2 = LLirArg::Mem(name);
+            builder.add_code(instr);
+            
+            return Ok(build_convert(builder, dest, &LLirDataType::Int64, data_type));
+        },
+        
+        _ => {
+            builder.syntax.ltac_error2("Invalid expression.".to_string());
+            return Err(());
+        },
+    }
+}
+
+// Konstruas identigilon: variablon, tabelan eron, funkcian alvokon, aŭ enumeracian valoron
+fn build_id(builder : &mut LLirBuilder, arg : &AstArg, data_type : &LLirDataType) -> Result<LLirArg, ()> {
+    let name = &arg.str_val;
+    
+    // Nerektaj alvokoj; la tipkontrolilo donas al ili la revenan tipon
+    if let Some(func) = builder.get_proc(name) {
+        if !matches!(arg.data_type, DataType::Proc(_)) {
+            let value = build_call(builder, name, &arg.sub_args, true)?;
+            return Ok(build_convert(builder, value, &func.data_type, data_type));
         }
     }
     
-    if stack.len() >= 1 {
-        let dest = stack.pop().unwrap();
+    if let Some(var) = builder.get_var(name) {
+        if arg.sub_args.len() == 1 {
+            return build_expr(builder, &arg_expr(arg), data_type);
+        }
         
-        let mut instr = store_for_type(&var.data_type);
-        instr.data_type = var.data_type.clone();
-        instr.arg1 = LLirArg::Mem(var.name.clone());
-        instr.arg2 = dest;
-        builder.add_code(instr);
+        let value = build_load(builder, LLirArg::Mem(name.clone()), &var.data_type);
+        return Ok(build_convert(builder, value, &var.data_type, data_type));
     }
     
-    true
+    if name == "syscall" {
+        let value = build_syscall(builder, &arg.sub_args, true)?;
+        return Ok(build_convert(builder, value, &LLirDataType::Int64, data_type));
+    }
+    
+    if let Some(func) = builder.funcs.get(name) {
+        let ret_type = func.data_type.clone();
+        let value = build_call(builder, name, &arg.sub_args, true)?;
+        return Ok(build_convert(builder, value, &ret_type, data_type));
+    }
+    
+    if let Some(value) = builder.get_enum_value(name) {
+        return Ok(LLirArg::Int(value as i64));
+    }
+    
+    let mut msg = "Unknown variable or function: ".to_string();
+    msg.push_str(name);
+    
+    builder.syntax.ltac_error2(msg).code(E_UNKNOWN_NAME);
+    Err(())
 }
 
-// Konstrui variablon esprimon
-/*pub fn build_expr(builder : &mut LLirBuilder, line : &AstStmt, var : &Var) -> bool {
-    let args = &line.args;
+// Konstruas sizeof
+// Rekordoj havas konatan grandecon; tabeloj tenas ĝin en kaŝita variablo
+fn build_sizeof(builder : &mut LLirBuilder, arg : &AstArg, data_type : &LLirDataType) -> Result<LLirArg, ()> {
+    let name = match arg.sub_args.first() {
+        Some(a) => a.str_val.clone(),
+        None => return Err(()),
+    };
+    
+    if builder.records.contains_key(&name) {
+        return Ok(LLirArg::Int(record_size(builder, &name) as i64));
+    }
+    
+    match builder.get_var(&name) {
+        Some(v) if v.record.len() > 0 => return Ok(LLirArg::Int(record_size(builder, &v.record) as i64)),
+        
+        Some(v) if v.data_type == LLirDataType::Ptr => {
+            let mut size_name = name.clone();
+            size_name.push_str(".size");
+            
+            let value = build_load(builder, LLirArg::Mem(size_name), &LLirDataType::Int);
+            return Ok(build_convert(builder, value, &LLirDataType::Int, data_type));
+        },
+        
+        Some(_v) => {
+            builder.syntax.ltac_error2("Sizeof can only be used with arrays and strings.".to_string());
+            return Err(());
+        },
+        
+        None => {
+            builder.syntax.ltac_error2("Unknown array or string.".to_string()).code(E_UNKNOWN_NAME);
+            return Err(());
+        },
+    }
+}
 
-    // Se ni havas unu argumento kaj ĝis laŭvorto, ni nur povas stoki.
-    if args.len() == 1 {
-        let first = args.first().unwrap();
+// Redonas la propran tipon de operando
+// Ĝi estas uzata kiam nenio alia donas la tipon, ekz. en variadaj argumentoj kaj kondiĉoj
+pub fn operand_type(builder : &mut LLirBuilder, arg : &AstArg) -> LLirDataType {
+    match &arg.arg_type {
+        AstArgType::IntL if arg.u64_val > (i32::MAX as u64) => return LLirDataType::Int64,
+        AstArgType::ByteL | AstArgType::ShortL
+        | AstArgType::CharL | AstArgType::IntL => return LLirDataType::Int,
+        AstArgType::FloatL => return LLirDataType::Double,
+        AstArgType::StringL => return LLirDataType::Str,
+        AstArgType::BoolL => return LLirDataType::UByte,
+        AstArgType::Sizeof => return LLirDataType::Int,
+        AstArgType::AddrOf => return LLirDataType::Int64,
+        AstArgType::Null => return LLirDataType::Ptr,
         
-        let mut instr = store_for_type(&var.data_type);
-        instr.data_type = var.data_type.clone();
-        instr.arg1 = LLirArg::Mem(var.name.clone());
+        AstArgType::Expr => {
+            match ast_to_datatype(&arg.data_type).0 {
+                LLirDataType::Void => return LLirDataType::Int,
+                data_type => return data_type,
+            }
+        },
         
-        match &first.arg_type {
-            AstArgType::IntL => {
-                if is_unsigned(&var.data_type) {
-                    instr.arg2 = LLirArg::UInt(first.u64_val);
-                } else {
-                    instr.arg2 = LLirArg::Int(first.u64_val as i64);
+        AstArgType::Field => {
+            match get_field(builder, &arg.str_val, &arg.sub_args) {
+                Ok(LLirArg::FieldMem(_, _, data_type)) => return data_type,
+                _ => return LLirDataType::Int,
+            }
+        },
+        
+        AstArgType::Id => {
+            if let Some(func) = builder.get_proc(&arg.str_val) {
+                if !matches!(arg.data_type, DataType::Proc(_)) {
+                    return func.data_type;
                 }
-            },
+            }
             
-            _ => {},
-        }
+            if let Some(var) = builder.get_var(&arg.str_val) {
+                if arg.sub_args.len() > 0 {
+                    return element_type(&var);
+                }
+                
+                return var.data_type;
+            }
+            
+            if let Some(func) = builder.funcs.get(&arg.str_val) {
+                return func.data_type.clone();
+            }
+            
+            if arg.str_val == "syscall" {
+                return LLirDataType::Int64;
+            }
+            
+            return LLirDataType::Int;
+        },
         
-        builder.add_code(instr);
+        _ => return LLirDataType::Int,
     }
+}
 
-    true
-}*/
+// Konvertas tabelan indekson al LLIR operando
+pub fn build_element(builder : &mut LLirBuilder, var : &Var, index_expr : &AstExpr) -> Result<LLirArg, ()> {
+    let index = build_expr(builder, index_expr, &LLirDataType::Int64)?;
+    
+    if builder.checks.bounds() && is_array(var) {
+        build_bounds_check(builder, var, &index);
+    }
+    
+    Ok(LLirArg::ElementMem(var.name.clone(), Box::new(index), element_type(var)))
+}
 
+// Kontrolas la indekson kontraŭ la grandeco de la tabelo (--checks=bounds)
+// Negativa indekso estas granda sen signo, do unu komparo sufiĉas. lila_bounds_fail nur presas la mesaĝon, kaj poste ni finas la programon.
+fn build_bounds_check(builder : &mut LLirBuilder, var : &Var, index : &LLirArg) {
+    let mut size_name = var.name.clone();
+    size_name.push_str(".size");
+    
+    let size = build_load(builder, LLirArg::Mem(size_name), &LLirDataType::Int);
+    let size64 = build_convert(builder, size.clone(), &LLirDataType::Int, &LLirDataType::Int64);
+    let ok_label = builder.new_label();
+    
+    let mut instr = llir::create_instr(LLirType::CultQW);
+    instr.data_type = LLirDataType::Int64;
+    instr.arg1 = LLirArg::Label(ok_label.clone());
+    instr.arg2 = index.clone();
+    instr.arg3 = size64;
+    builder.add_code(instr);
+    
+    let index32 = build_convert(builder, index.clone(), &LLirDataType::Int64, &LLirDataType::Int);
+    let file = LLirArg::StrLiteral(builder.syntax.file.clone());
+    let line = LLirArg::Int(builder.syntax.current_ln_no as i64);
+    
+    let mut instr = llir::create_instr(LLirType::Call);
+    instr.arg1 = LLirArg::Label("lila_bounds_fail".to_string());
+    instr.arg2 = LLirArg::ArgList(vec![file, line, index32, size]);
+    builder.add_code(instr);
+    
+    let mut instr = llir::create_instr(LLirType::Exit);
+    instr.arg1 = LLirArg::Int(1);
+    builder.add_code(instr);
+    
+    let mut instr = llir::create_instr(LLirType::Label);
+    instr.arg1 = LLirArg::Label(ok_label);
+    builder.add_code(instr);
+}
+
+// Ĉenoj estas tabeloj de signoj
+pub fn element_type(var : &Var) -> LLirDataType {
+    if var.data_type == LLirDataType::Str {
+        return LLirDataType::Byte;
+    }
+    
+    var.sub_type.clone()
+}
+
+// Konstruas negativan valoron
+fn build_negate(builder : &mut LLirBuilder, value : LLirArg, data_type : &LLirDataType) -> LLirArg {
+    match &value {
+        LLirArg::Int(val) => return LLirArg::Int(val.wrapping_neg()),
+        LLirArg::UInt(val) => return LLirArg::Int((*val as i64).wrapping_neg()),
+        LLirArg::Float(val) => return LLirArg::Float(0.0 - *val),
+        _ => {},
+    }
+    
+    let (instr_type, zero) = match &data_type {
+        LLirDataType::Float => (LLirType::SubF32, LLirArg::Float(0.0)),
+        LLirDataType::Double => (LLirType::SubF64, LLirArg::Float(0.0)),
+        _ => (LLirType::Sub, LLirArg::Int(0)),
+    };
+    
+    let dest = builder.new_reg();
+    
+    let mut instr = llir::create_instr(instr_type);
+    instr.data_type = data_type.clone();
+    instr.arg1 = dest.clone();
+    instr.arg2 = zero;
+    instr.arg3 = value;
+    builder.add_code(instr);
+    
+    dest
+}
+
+// Konvertas valoron de unu tipo al alia
+// Konstantoj ne bezonas konverton; ili prenas la tipon de la instrukcio kiu uzas ilin
+pub fn build_convert(builder : &mut LLirBuilder, value : LLirArg, from : &LLirDataType, to : &LLirDataType) -> LLirArg {
+    if from == to || *to == LLirDataType::Void {
+        return value;
+    }
+    
+    match &value {
+        LLirArg::Int(_) | LLirArg::UInt(_) | LLirArg::Float(_)
+        | LLirArg::StrLiteral(_) => return value,
+        _ => {},
+    }
+    
+    let instr_type : LLirType;
+    
+    if is_float(from) || is_float(to) || is_pointer(from) || is_pointer(to) {
+        instr_type = LLirType::Mov;
+    } else if size_for_type(from) == size_for_type(to) {
+        return value;
+    } else if is_unsigned(from) {
+        instr_type = LLirType::MovZX;
+    } else {
+        instr_type = LLirType::MovSX;
+    }
+    
+    let dest = builder.new_reg();
+    
+    let mut instr = llir::create_instr(instr_type);
+    instr.data_type = to.clone();
+    instr.arg1 = dest.clone();
+    instr.arg2 = value;
+    builder.add_code(instr);
+    
+    dest
+}
+
+// Ŝarĝas memoron al nova registro
+pub fn build_load(builder : &mut LLirBuilder, mem : LLirArg, data_type : &LLirDataType) -> LLirArg {
+    let dest = builder.new_reg();
+    
+    let mut instr = load_for_type(data_type);
+    instr.data_type = data_type.clone();
+    instr.arg1 = dest.clone();
+    instr.arg2 = mem;
+    builder.add_code(instr);
+    
+    dest
+}
+
+pub fn build_store(builder : &mut LLirBuilder, mem : LLirArg, value : LLirArg, data_type : &LLirDataType) {
+    let mut instr = store_for_type(data_type);
+    instr.data_type = data_type.clone();
+    instr.arg1 = mem;
+    instr.arg2 = value;
+    builder.add_code(instr);
+}
+
+fn int_literal(value : u64, data_type : &LLirDataType) -> LLirArg {
+    if is_float(data_type) {
+        return LLirArg::Float(value as f64);
+    } else if is_unsigned(data_type) {
+        return LLirArg::UInt(value);
+    }
+    
+    LLirArg::Int(value as i64)
+}
+
+fn arith_for_type(op : &AstArgType, data_type : &LLirDataType) -> LLirType {
+    match (op, data_type) {
+        (AstArgType::OpAdd, LLirDataType::Float) => return LLirType::AddF32,
+        (AstArgType::OpSub, LLirDataType::Float) => return LLirType::SubF32,
+        (AstArgType::OpMul, LLirDataType::Float) => return LLirType::MulF32,
+        (AstArgType::OpDiv, LLirDataType::Float) => return LLirType::DivF32,
+        
+        (AstArgType::OpAdd, LLirDataType::Double) => return LLirType::AddF64,
+        (AstArgType::OpSub, LLirDataType::Double) => return LLirType::SubF64,
+        (AstArgType::OpMul, LLirDataType::Double) => return LLirType::MulF64,
+        (AstArgType::OpDiv, LLirDataType::Double) => return LLirType::DivF64,
+        
+        (_, LLirDataType::Float) | (_, LLirDataType::Double) => return LLirType::Nop,
+        
+        (AstArgType::OpAdd, _) => return LLirType::Add,
+        (AstArgType::OpSub, _) => return LLirType::Sub,
+        
+        (AstArgType::OpMul, dt) if is_unsigned(dt) => return LLirType::UMul,
+        (AstArgType::OpMul, _) => return LLirType::Mul,
+        
+        (AstArgType::OpDiv, dt) if is_unsigned(dt) => return LLirType::UDiv,
+        (AstArgType::OpDiv, _) => return LLirType::Div,
+        
+        (AstArgType::OpMod, dt) if is_unsigned(dt) => return LLirType::URem,
+        (AstArgType::OpMod, _) => return LLirType::Rem,
+        
+        (AstArgType::OpAnd, _) => return LLirType::And,
+        (AstArgType::OpOr, _) => return LLirType::Or,
+        (AstArgType::OpXor, _) => return LLirType::Xor,
+        (AstArgType::OpLeftShift, _) => return LLirType::Lsh,
+        (AstArgType::OpRightShift, _) => return LLirType::Rsh,
+        
+        _ => return LLirType::Nop,
+    }
+}
@@ -26,12 +26,16 @@ use std::path::Path;
 use crate::Arch;
 use crate::ast_builder::{AstBuilder, include_module};
 use crate::ast::*;
-use crate::lex::Token;
+use crate::lex::{Token, create_lex};
+use crate::syntax::*;
+
+// The modules included in every program unless the core library is disabled
+pub const CORE_MODULES : [&str; 3] = ["core.mem", "core.string", "core.io"];
 
 // Builds a "module" declaration
 pub fn build_module(builder : &mut AstBuilder) -> bool {
     if builder.tree.module.len() > 0 {
-        builder.syntax_error("Duplicate module declarations.".to_string());
+        builder.syntax_error("Duplicate module declarations.".to_string()).code(E_DUPLICATE);
         return false;
     }
     
@@ -40,13 +44,13 @@ pub fn build_module(builder : &mut AstBuilder) -> bool {
     match token {
         Token::Id(ref val) => builder.tree.module = val.clone(),
         _ => {
-            builder.syntax_error("Module names must be an identifier.".to_string());
+            builder.syntax_error("Module names must be an identifier.".to_string()).code(E_EXPECTED_NAME);
             return false;
         },
     }
     
     if builder.get_token() != Token::Semicolon {
-        builder.syntax_error("Expecting terminator".to_string());
+        builder.syntax_error("Expecting terminator".to_string()).code(E_TERMINATOR);
         return false;
     }
     
@@ -62,7 +66,7 @@ pub fn build_use(b : &mut AstBuilder) -> bool {
     match token {
         Token::Id(ref val) => module = val.clone(),
         _ => {
-            b.syntax.syntax_error(&mut b.scanner, "Module names must be an identifier.".to_string());
+            b.syntax.syntax_error(&mut b.scanner, "Module names must be an identifier.".to_string()).code(E_EXPECTED_NAME);
             return false;
         },
     }
@@ -75,14 +79,14 @@ pub fn build_use(b : &mut AstBuilder) -> bool {
         let arch_str = match token {
             Token::StringL(ref val) => val.clone(),
             _ => {
-                b.syntax.syntax_error(&mut b.scanner, "Expected string with architecture type.".to_string());
+                b.syntax.syntax_error(&mut b.scanner, "Expected string with architecture type.".to_string()).code(E_MODULE);
                 return false;
             },
         };
         
         token = b.scanner.get_token();
         if token != Token::Semicolon {
-            b.syntax.syntax_error(&mut b.scanner, "Expecting terminator".to_string());
+            b.syntax.syntax_error(&mut b.scanner, "Expecting terminator".to_string()).code(E_TERMINATOR);
             return false;
         }
         
@@ -92,7 +96,7 @@ pub fn build_use(b : &mut AstBuilder) -> bool {
             "riscv64" => Arch::Riscv64,
             
             _ => {
-                b.syntax.syntax_error(&mut b.scanner, "Invalid architecture".to_string());
+                b.syntax.syntax_error(&mut b.scanner, "Invalid architecture".to_string()).code(E_MODULE);
                 return false;
             },
         };
@@ -101,7 +105,7 @@ pub fn build_use(b : &mut AstBuilder) -> bool {
             do_include = false;
         }
     } else if token != Token::Semicolon {
-        b.syntax.syntax_error(&mut b.scanner, "Expecting terminator".to_string());
+        b.syntax.syntax_error(&mut b.scanner, "Expecting terminator".to_string()).code(E_TERMINATOR);
         return false;
     }
     
@@ -112,6 +116,53 @@ pub fn build_use(b : &mut AstBuilder) -> bool {
     true
 }
 
+// Returns the modules a source file uses, without building it
+// Nothing else is checked here; the modules have to be known before their headers exist
+pub fn find_uses(path : &String, arch : Arch) -> io::Result<Vec<String>> {
+    let source = fs::read_to_string(path)?;
+    
+    // Scanning errors are reported when the file is built; the tokens before one are still searched
+    let mut scanner = create_lex();
+    let _ = scanner.tokenize(&source);
+    
+    let mut uses : Vec<String> = Vec::new();
+    let mut token = scanner.get_token();
+    
+    while token != Token::EoI {
+        if token != Token::Use {
+            token = scanner.get_token();
+            continue;
+        }
+        
+        let name = match scanner.get_token() {
+            Token::Id(ref val) => val.clone(),
+            _ => String::new(),
+        };
+        
+        token = scanner.get_token();
+        let mut do_include = true;
+        
+        // use <name> if "<arch>";
+        if token == Token::If {
+            let arch2 = match scanner.get_token() {
+                Token::StringL(ref val) if val == "x86_64" => Some(Arch::X86_64),
+                Token::StringL(ref val) if val == "aarch64" => Some(Arch::AArch64),
+                Token::StringL(ref val) if val == "riscv64" => Some(Arch::Riscv64),
+                _ => None,
+            };
+            
+            do_include = arch2 == Some(arch);
+            token = scanner.get_token();
+        }
+        
+        if do_include && name.len() > 0 && !uses.contains(&name) {
+            uses.push(name);
+        }
+    }
+    
+    Ok(uses)
+}
+
 // Builds a module path and performs various checks
 pub fn get_module_path(name : &String) -> String {
     let mut path = name.replace("default", "");
@@ -184,6 +235,58 @@ pub fn generate_module(tree : &AstTree) -> io::Result<()> {
         line.push_str("\n");
     }
     
+    // Global variables are declared as external; the module owns the storage
+    for g in tree.globals.iter() {
+        if g.is_extern {
+            continue;
+        }
+        
+        let decl = match g.line.find('=') {
+            Some(pos) => &g.line[..pos],
+            None => g.line.trim_end_matches(';'),
+        };
+        
+        line.push_str("extern ");
+        line.push_str(decl.trim());
+        line.push_str(";\n");
+    }
+    
+    // Write out the record types so the functions can use them
+    for record in tree.records.iter() {
+        if record.line.len() == 0 {
+            continue;
+        }
+        
+        line.push_str(&record.line);
+        line.push_str("\n");
+        
+        let mut last_line = String::new();
+        
+        for field in record.fields.iter() {
+            if field.line == last_line {
+                continue;
+            }
+            
+            line.push_str("    ");
+            line.push_str(&field.line);
+            line.push_str("\n");
+            
+            last_line = field.line.clone();
+        }
+        
+        line.push_str("end\n\n");
+    }
+    
+    // Procedure types fit on one line
+    for proc_type in tree.procs.iter() {
+        if proc_type.line.len() == 0 {
+            continue;
+        }
+        
+        line.push_str(&proc_type.line);
+        line.push_str("\n");
+    }
+    
     // Now iterate through each function
     for func in tree.functions.iter() {
         if func.line.len() == 0 {
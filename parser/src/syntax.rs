@@ -15,79 +15,299 @@
 // with this program; if not, write to the Free Software Foundation, Inc.,
 // 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
 
-
 use crate::lex::*;
 use crate::ast::AstStmt;
 
+// Stable error codes
+// These are listed in docs/errors.md; never reuse or renumber a code
+pub const E_SYNTAX : &str = "E0001";            // Generic parse error
+pub const E_TERMINATOR : &str = "E0002";        // Missing ';'
+pub const E_EXPECTED_NAME : &str = "E0003";     // Missing or invalid identifier
+pub const E_EXPECTED_TYPE : &str = "E0004";     // Missing or invalid data type
+pub const E_DUPLICATE : &str = "E0005";         // Duplicate declaration
+pub const E_LITERAL_RANGE : &str = "E0006";     // Literal does not fit in its type
+pub const E_MODULE : &str = "E0007";            // Invalid module or use statement
+
+pub const E_SEMANTIC : &str = "E0100";          // Generic semantic error
+pub const E_UNKNOWN_NAME : &str = "E0101";      // Unknown variable, array or function
+pub const E_TYPE_MISMATCH : &str = "E0102";     // Value invalid for the target type
+pub const E_INVALID_OP : &str = "E0103";        // Operator invalid for the operands
+pub const E_GLOBAL_INDEX : &str = "E0104";      // Global used as an array index
+pub const E_ARG_COUNT : &str = "E0105";         // Wrong number of call arguments
+pub const E_INDEX_RANGE : &str = "E0106";       // Constant index outside the array
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ErrorFormat {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+// A secondary location attached to a diagnostic
 #[derive(Clone)]
-pub struct SyntaxError {
+pub struct Label {
     pub line_no : i32,
     pub line : String,
+    pub line_col : usize,       // The column where the line text starts
+    pub col_start : usize,
+    pub col_end : usize,
     pub message : String,
 }
 
+// Columns start at 1; col_end points one past the span
+#[derive(Clone)]
+pub struct Diagnostic {
+    pub severity : Severity,
+    pub code : String,
+    pub message : String,
+    pub file : String,
+    pub line_no : i32,
+    pub line : String,
+    pub line_col : usize,
+    pub col_start : usize,
+    pub col_end : usize,
+    pub labels : Vec<Label>,
+}
+
+impl Diagnostic {
+    pub fn code(&mut self, code : &str) -> &mut Diagnostic {
+        self.code = code.to_string();
+        self
+    }
+    
+    pub fn severity(&mut self, severity : Severity) -> &mut Diagnostic {
+        self.severity = severity;
+        self
+    }
+    
+    // Underlines the whole line instead of the last token
+    pub fn whole_line(&mut self) -> &mut Diagnostic {
+        self.col_start = self.line_col;
+        self.col_end = self.line_col + self.line.chars().count();
+        self
+    }
+    
+    // Points at another statement, such as a previous declaration
+    pub fn label(&mut self, stmt : &AstStmt, msg : &str) -> &mut Diagnostic {
+        let label = Label {
+            line_no : stmt.line_no,
+            line : stmt.line.clone(),
+            line_col : stmt.col,
+            col_start : stmt.col,
+            col_end : stmt.col + stmt.line.chars().count(),
+            message : msg.to_string(),
+        };
+        
+        self.labels.push(label);
+        self
+    }
+    
+    pub fn severity_name(&self) -> &str {
+        match self.severity {
+            Severity::Error => return "error",
+            Severity::Warning => return "warning",
+            Severity::Note => return "note",
+        }
+    }
+    
+    // Renders the diagnostic with the source line and a caret underline
+    pub fn render(&self) -> String {
+        let severity = self.severity_name();
+        
+        let mut output = format!("{}[{}]: {}\n", severity, self.code, self.message);
+        
+        if self.line.len() == 0 {
+            output.push_str(&format!(" --> {}\n", self.file));
+            return output;
+        }
+        
+        let mut width = self.line_no.to_string().len();
+        for label in self.labels.iter() {
+            width = width.max(label.line_no.to_string().len());
+        }
+        
+        let gutter = " ".repeat(width);
+        output.push_str(&format!("{}--> {}:{}:{}\n", gutter, self.file, self.line_no, self.col_start));
+        output.push_str(&format!("{} |\n", gutter));
+        output.push_str(&render_snippet(width, self.line_no, &self.line, self.line_col,
+                        self.col_start, self.col_end, '^', ""));
+        
+        for label in self.labels.iter() {
+            output.push_str(&format!("{} |\n", gutter));
+            output.push_str(&render_snippet(width, label.line_no, &label.line, label.line_col,
+                            label.col_start, label.col_end, '-', &label.message));
+        }
+        
+        output
+    }
+    
+    // Renders the diagnostic as a single line of JSON
+    // Whole-file diagnostics have a line and column of 0
+    pub fn to_json(&self) -> String {
+        let mut labels : Vec<String> = Vec::new();
+        
+        for label in self.labels.iter() {
+            labels.push(format!("{{\"line\":{},\"column\":{},\"end_column\":{},\"message\":{}}}",
+                label.line_no, label.col_start, label.col_end, json_string(&label.message)));
+        }
+        
+        format!("{{\"file\":{},\"line\":{},\"column\":{},\"end_column\":{},\"code\":{},\"severity\":{},\"message\":{},\"labels\":[{}]}}",
+            json_string(&self.file), self.line_no, self.col_start, self.col_end,
+            json_string(&self.code), json_string(self.severity_name()),
+            json_string(&self.message), labels.join(","))
+    }
+}
+
+// Renders a single source line and its underline
+fn render_snippet(width : usize, line_no : i32, line : &String, line_col : usize,
+                    col_start : usize, col_end : usize, mark : char, msg : &str) -> String {
+    let start = col_start.saturating_sub(line_col);
+    let length = col_end.saturating_sub(col_start).max(1);
+    
+    let mut underline = " ".repeat(start);
+    underline.push_str(&mark.to_string().repeat(length));
+    
+    if msg.len() > 0 {
+        underline.push(' ');
+        underline.push_str(msg);
+    }
+    
+    let mut output = format!("{:>width$} | {}\n", line_no, line, width = width);
+    output.push_str(&format!("{} | {}\n", " ".repeat(width), underline));
+    output
+}
+
 #[derive(Clone)]
 pub struct ErrorManager {
-    pub errors : Vec<SyntaxError>,
+    pub errors : Vec<Diagnostic>,
+    pub file : String,
     pub current_ln : String,
     pub current_ln_no : i32,
+    pub current_col : usize,
 }
 
-pub fn create_error_manager() -> ErrorManager {
+pub fn create_error_manager(file : String) -> ErrorManager {
     ErrorManager {
         errors : Vec::new(),
+        file : file,
         current_ln : String::new(),
         current_ln_no : 0,
+        current_col : 1,
     }
 }
 
 impl ErrorManager {
 
     // Called when the AST is being built
-    pub fn syntax_error(&mut self, scanner : &mut Lex, msg : String) {
-        let error = SyntaxError {
+    // The span is the last token read; the file is whichever file the scanner is reading
+    pub fn syntax_error(&mut self, scanner : &mut Lex, msg : String) -> &mut Diagnostic {
+        let (col_start, col_end) = scanner.get_span();
+        
+        let error = Diagnostic {
+            severity : Severity::Error,
+            code : E_SYNTAX.to_string(),
+            message : msg,
+            file : scanner.get_file(),
             line_no : scanner.get_line_no(),
             line : scanner.get_current_line(),
-            message : msg,
+            line_col : scanner.get_line_col(),
+            col_start : col_start,
+            col_end : col_end,
+            labels : Vec::new(),
         };
         
         self.errors.push(error);
+        self.errors.last_mut().unwrap()
     }
     
     // Called when the AST is being translated to the LTAC
-    pub fn ltac_error(&mut self, stmt : &AstStmt, msg : String) {
-        let error = SyntaxError {
-            line_no : stmt.line_no,
-            line : stmt.line.clone(),
-            message : msg,
-        };
-        
-        self.errors.push(error);
+    pub fn ltac_error(&mut self, stmt : &AstStmt, msg : String) -> &mut Diagnostic {
+        let line = stmt.line.clone();
+        let col = stmt.col;
+        self.stmt_error(stmt.line_no, line, col, msg)
     }
     
-    pub fn ltac_error2(&mut self, msg : String) {
-        let error = SyntaxError {
-            line_no : self.current_ln_no,
-            line : self.current_ln.clone(),
+    pub fn ltac_error2(&mut self, msg : String) -> &mut Diagnostic {
+        let line = self.current_ln.clone();
+        let col = self.current_col;
+        self.stmt_error(self.current_ln_no, line, col, msg)
+    }
+    
+    // Semantic errors underline the whole statement
+    fn stmt_error(&mut self, line_no : i32, line : String, col : usize, msg : String) -> &mut Diagnostic {
+        let error = Diagnostic {
+            severity : Severity::Error,
+            code : E_SEMANTIC.to_string(),
             message : msg,
+            file : self.file.clone(),
+            line_no : line_no,
+            line_col : col,
+            col_start : col,
+            col_end : col + line.chars().count(),
+            line : line,
+            labels : Vec::new(),
         };
         
         self.errors.push(error);
+        self.errors.last_mut().unwrap()
     }
     
     // Set the current line to make it easier to call LTAC errors
     pub fn set_data(&mut self, stmt : &AstStmt) {
         self.current_ln = stmt.line.clone();
         self.current_ln_no = stmt.line_no;
+        self.current_col = stmt.col;
     }
-    
-    // Called to print any syntax errors
-    pub fn print_errors(&mut self) {
-        for error in self.errors.iter() {
-            println!("Syntax Error: {}", error.message);
-            println!(" -> [{}] {}", error.line_no, error.line);
-            println!("");
+}
+
+// Prints a list of diagnostics to stderr
+pub fn print_errors(errors : &Vec<Diagnostic>, format : ErrorFormat) {
+    for error in errors.iter() {
+        match format {
+            ErrorFormat::Text => eprintln!("{}", error.render()),
+            ErrorFormat::Json => eprintln!("{}", error.to_json()),
         }
     }
 }
 
+// Creates a diagnostic that belongs to a whole file rather than a line
+pub fn file_error(file : &String, code : &str, msg : String) -> Diagnostic {
+    Diagnostic {
+        severity : Severity::Error,
+        code : code.to_string(),
+        message : msg,
+        file : file.clone(),
+        line_no : 0,
+        line : String::new(),
+        line_col : 0,
+        col_start : 0,
+        col_end : 0,
+        labels : Vec::new(),
+    }
+}
+
+// Escapes a string for use in JSON output
+pub fn json_string(input : &str) -> String {
+    let mut output = "\"".to_string();
+    
+    for c in input.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\t' => output.push_str("\\t"),
+            '\r' => output.push_str("\\r"),
+            c if (c as u32) < 0x20 => output.push_str(&format!("\\u{:04x}", c as u32)),
+            c => output.push(c),
+        }
+    }
+    
+    output.push('"');
+    output
+}
@@ -0,0 +1,1901 @@
+// This file is part of the Lila compiler
+// Copyright (C) 2020-2021 Patrick Flynn
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; version 2.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, write to the Free Software Foundation, Inc.,
+// 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+
+// The type checker
+// This runs over the AST before either backend sees it. Every identifier is resolved,
+// and each operand gets its data type filled in so the LTAC and LLIR layers both work
+// from the same checked tree. The checks read the arguments written out from each
+// expression, and the types are then copied to the expression trees.
+
+use std::collections::HashMap;
+
+use crate::ast::*;
+use crate::ast_expr;
+use crate::syntax::*;
+
+// Represents a variable in the current scope
+#[derive(Clone)]
+struct TypeVar {
+    data_type : DataType,
+    sub_type : DataType,
+    is_global : bool,
+    length : Option<u64>,       // The number of elements, for arrays with a literal size
+}
+
+// Represents a function signature
+#[derive(Clone)]
+struct TypeFunc {
+    args : Vec<AstStmt>,
+    data_type : DataType,
+    is_variadic : bool,
+    min_args : usize,
+}
+
+pub struct TypeChecker {
+    pub syntax : ErrorManager,
+    
+    functions : HashMap<String, TypeFunc>,
+    procs : HashMap<String, TypeFunc>,
+    records : HashMap<String, AstRecord>,
+    globals : HashMap<String, TypeVar>,
+    
+    // Function-related values
+    current_func : String,
+    current_type : DataType,
+    enums : HashMap<String, AstEnum>,
+    vars : HashMap<String, TypeVar>,
+}
+
+pub fn new_type_checker(syntax : &mut ErrorManager) -> TypeChecker {
+    TypeChecker {
+        syntax : syntax.clone(),
+        functions : HashMap::new(),
+        procs : HashMap::new(),
+        records : HashMap::new(),
+        globals : HashMap::new(),
+        current_func : String::new(),
+        current_type : DataType::Void,
+        enums : HashMap::new(),
+        vars : HashMap::new(),
+    }
+}
+
+impl TypeChecker {
+    
+    // Checks the entire tree
+    // The first pass collects the signatures, the second checks each function body
+    pub fn check(&mut self, tree : &mut AstTree) -> Result<(), Vec<Diagnostic>> {
+        for record in tree.records.iter() {
+            self.records.insert(record.name.clone(), record.clone());
+        }
+        
+        // Procedure types are checked like the functions they point to
+        for proc_type in tree.procs.iter() {
+            let mut proc_ret = DataType::Void;
+            
+            if proc_type.data_type != DataType::None {
+                proc_ret = proc_type.data_type.clone();
+            }
+            
+            let p = TypeFunc {
+                args : proc_type.args.clone(),
+                data_type : proc_ret,
+                is_variadic : false,
+                min_args : proc_type.args.len(),
+            };
+            
+            self.procs.insert(proc_type.name.clone(), p);
+        }
+        
+        for global in tree.globals.iter() {
+            let v = TypeVar {
+                data_type : global.data_type.clone(),
+                sub_type : DataType::None,
+                is_global : true,
+                length : None,
+            };
+            
+            self.globals.insert(global.name.clone(), v);
+        }
+        
+        // Constant tables are read like arrays
+        for constant in tree.constants.iter() {
+            if constant.values.len() == 0 {
+                continue;
+            }
+            
+            let v = TypeVar {
+                data_type : DataType::Ptr,
+                sub_type : constant.data_type.clone(),
+                is_global : false,
+                length : Some(constant.values.len() as u64),
+            };
+            
+            self.globals.insert(constant.name.clone(), v);
+        }
+        
+        for func in tree.functions.iter() {
+            let mut func_type = DataType::Void;
+            
+            if func.data_type != DataType::None {
+                func_type = func.data_type.clone();
+            }
+            
+            let f = TypeFunc {
+                args : func.args.clone(),
+                data_type : func_type,
+                is_variadic : func.is_variadic,
+                min_args : if func.is_variadic { func.fixed_args } else { func.args.len() },
+            };
+            
+            self.functions.insert(func.name.clone(), f);
+        }
+        
+        for func in tree.functions.iter_mut() {
+            if func.is_extern {
+                continue;
+            }
+            
+            if !self.check_func(func) {
+                return Err(self.syntax.errors.clone());
+            }
+        }
+        
+        Ok(())
+    }
+    
+    // Checks a function body
+    fn check_func(&mut self, func : &mut AstFunc) -> bool {
+        self.current_func = func.name.clone();
+        self.current_type = self.functions.get(&func.name).unwrap().data_type.clone();
+        
+        self.enums.clear();
+        
+        for e in func.enums.iter() {
+            self.enums.insert(e.name.clone(), e.clone());
+        }
+        
+        // Globals are visible everywhere, but locals of the same name shadow them
+        self.vars = self.globals.clone();
+        
+        for arg in func.args.iter() {
+            self.declare(&arg.name, &arg.data_type, &arg.sub_type);
+        }
+        
+        if !self.check_block(&mut func.statements) {
+            return false;
+        }
+        
+        // The last statement is always the end of the function, so a function with a
+        // return type must return right before it
+        if self.current_type != DataType::Void {
+            let count = func.statements.len();
+            let mut has_return = false;
+            
+            if count > 1 {
+                match &func.statements[count - 2].stmt_type {
+                    AstStmtType::Return | AstStmtType::Exit => has_return = true,
+                    _ => {},
+                }
+            }
+            
+            if !has_return {
+                let mut msg = "Expected return in function: ".to_string();
+                msg.push_str(&self.current_func);
+                
+                match func.statements.last() {
+                    Some(end) => self.syntax.ltac_error(end, msg),
+                    None => self.syntax.ltac_error2(msg),
+                };
+                
+                return false;
+            }
+        }
+        
+        true
+    }
+    
+    // Checks a block of statements
+    fn check_block(&mut self, statements : &mut Vec<AstStmt>) -> bool {
+        for line in statements.iter_mut() {
+            self.syntax.set_data(line);
+            
+            let code = match &line.stmt_type {
+                AstStmtType::VarDec => self.check_var_dec(line),
+                AstStmtType::VarAssign => self.check_var_assign(line),
+                AstStmtType::ArrayAssign => self.check_array_assign(line),
+                AstStmtType::FieldAssign => self.check_field_assign(line),
+                AstStmtType::If | AstStmtType::Elif | AstStmtType::While => self.check_cond(line),
+                AstStmtType::For => self.check_for(line),
+                AstStmtType::Case => self.check_case(line),
+                AstStmtType::FuncCall => self.check_func_call(&line.name, &mut line.args),
+                AstStmtType::Return => self.check_return(line),
+                AstStmtType::Exit => self.check_exit(line),
+                
+                AstStmtType::Else | AstStmtType::When | AstStmtType::Break
+                | AstStmtType::Continue | AstStmtType::End => true,
+            };
+            
+            if !code {
+                return false;
+            }
+            
+            ast_expr::copy_types(line);
+            
+            if line.sub_block.len() > 0 && !self.check_block(&mut line.sub_block) {
+                return false;
+            }
+        }
+        
+        true
+    }
+    
+    // Adds a variable to the current scope
+    fn declare(&mut self, name : &String, data_type : &DataType, sub_type : &DataType) {
+        let v = TypeVar {
+            data_type : data_type.clone(),
+            sub_type : sub_type.clone(),
+            is_global : false,
+            length : None,
+        };
+        
+        self.vars.insert(name.clone(), v);
+    }
+    
+    // ===============================================================
+    // Variables
+    
+    fn check_var_dec(&mut self, line : &mut AstStmt) -> bool {
+        self.declare(&line.name, &line.data_type, &line.sub_type);
+        
+        // With a literal size, constant indexes can be checked here
+        if line.data_type == DataType::Ptr && line.sub_args.len() == 1 && line.sub_args[0].arg_type == AstArgType::IntL {
+            let size = line.sub_args[0].u64_val;
+            self.vars.get_mut(&line.name).unwrap().length = Some(size);
+        }
+        
+        self.check_var_assign(line)
+    }
+    
+    // Checks an assignment to a whole variable
+    // Arrays, records, and strings each have their own rules
+    fn check_var_assign(&mut self, line : &mut AstStmt) -> bool {
+        let var = match self.vars.get(&line.name) {
+            Some(v) => v.clone(),
+            None => {
+                self.syntax.ltac_error2("Invalid variable.".to_string()).code(E_UNKNOWN_NAME);
+                return false;
+            },
+        };
+        
+        line.data_type = var.data_type.clone();
+        line.sub_type = var.sub_type.clone();
+        
+        match &var.data_type {
+            DataType::Ptr => return self.check_array_init(line),
+            DataType::Record(_) => return self.check_record_assign(line, &var),
+            DataType::Str => return self.check_str_assign(&mut line.args),
+            DataType::Pointer(_) => return self.check_ptr_expr(&mut line.args, &var.data_type),
+            DataType::Bool if line.args.len() > 1 || is_cond(&line.args) => return self.check_condition(&mut line.args),
+            _ => return self.check_expr(&mut line.args, &var.data_type),
+        }
+    }
+    
+    // Checks an assignment to a single array element
+    fn check_array_assign(&mut self, line : &mut AstStmt) -> bool {
+        let var = match self.vars.get(&line.name) {
+            Some(v) => v.clone(),
+            None => {
+                self.syntax.ltac_error2("Invalid variable.".to_string()).code(E_UNKNOWN_NAME);
+                return false;
+            },
+        };
+        
+        let elem_type = match &var.data_type {
+            DataType::Ptr | DataType::Pointer(_) => var.sub_type.clone(),
+            DataType::Str => DataType::Char,
+            
+            _ => {
+                let mut msg = "Cannot index a variable that is not an array: ".to_string();
+                msg.push_str(&line.name);
+                
+                self.syntax.ltac_error2(msg).code(E_TYPE_MISMATCH);
+                return false;
+            },
+        };
+        
+        line.data_type = var.data_type.clone();
+        line.sub_type = var.sub_type.clone();
+        
+        if !self.check_index(&mut line.sub_args) || !self.check_bounds(&var, &line.sub_args) {
+            return false;
+        }
+        
+        self.check_expr(&mut line.args, &elem_type)
+    }
+    
+    // Checks an assignment to a record field
+    fn check_field_assign(&mut self, line : &mut AstStmt) -> bool {
+        let data_type = match self.field_type(&line.name, &line.sub_args) {
+            Ok(t) => t,
+            Err(_e) => return false,
+        };
+        
+        line.data_type = data_type.clone();
+        
+        if data_type == DataType::Str {
+            return self.check_str_assign(&mut line.args);
+        }
+        
+        self.check_expr(&mut line.args, &data_type)
+    }
+    
+    // Checks the size of an array, or vector math on an array
+    // An array literal also needs each of its values to fit the element type
+    fn check_array_init(&mut self, line : &mut AstStmt) -> bool {
+        if line.args.len() == 1 && line.args[0].arg_type == AstArgType::ArrayL {
+            for value in line.args[0].sub_args.iter_mut() {
+                if !self.check_expr(&mut value.sub_args, &line.sub_type) {
+                    return false;
+                }
+            }
+        }
+        
+        if line.sub_args.len() == 1 {
+            let arg = line.sub_args.first_mut().unwrap();
+            
+            match &arg.arg_type {
+                AstArgType::IntL => {
+                    arg.data_type = DataType::Int;
+                    return true;
+                },
+                
+                AstArgType::Id if arg.sub_args.len() == 0 && self.vars.contains_key(&arg.str_val) => {
+                    let v = self.vars.get(&arg.str_val).unwrap().clone();
+                    
+                    if v.data_type == DataType::Int || v.data_type == DataType::UInt {
+                        arg.data_type = v.data_type.clone();
+                        return true;
+                    }
+                    
+                    self.syntax.ltac_error2("Array size can only be set with integer values or variables.".to_string()).code(E_TYPE_MISMATCH);
+                    return false;
+                },
+                
+                _ => {},
+            }
+        }
+        
+        // Anything else is computed as an integer
+        if line.sub_args.len() > 0 {
+            return self.check_expr(&mut line.sub_args, &DataType::Int);
+        }
+        
+        // Vector math: only whole arrays and addition
+        if line.args.len() > 1 {
+            for arg in line.args.iter_mut() {
+                match &arg.arg_type {
+                    AstArgType::Id if arg.sub_args.len() == 0 => {
+                        match self.vars.get(&arg.str_val) {
+                            Some(v) if v.data_type == DataType::Ptr => {
+                                arg.data_type = DataType::Ptr;
+                            },
+                            
+                            Some(_v) => {
+                                let mut msg = "Only arrays can be used in vector math: ".to_string();
+                                msg.push_str(&arg.str_val);
+                                
+                                self.syntax.ltac_error2(msg).code(E_TYPE_MISMATCH);
+                                return false;
+                            },
+                            
+                            None => {
+                                self.syntax.ltac_error2("Invalid variable.".to_string()).code(E_UNKNOWN_NAME);
+                                return false;
+                            },
+                        }
+                    },
+                    
+                    // A single element is added to every element of the vector
+                    AstArgType::Id if self.vars.contains_key(&arg.str_val) => {
+                        if self.var_type(arg).is_err() {
+                            return false;
+                        }
+                    },
+                    
+                    AstArgType::OpAdd => {},
+                    
+                    _ => {
+                        self.syntax.ltac_error2("Invalid expression for vector math.".to_string()).code(E_INVALID_OP);
+                        return false;
+                    },
+                }
+            }
+            
+            return true;
+        }
+        
+        self.syntax.ltac_error2("Arrays can only be assigned a size or vector math.".to_string()).code(E_TYPE_MISMATCH);
+        false
+    }
+    
+    // Checks a string assignment
+    // These can only be a single string value
+    fn check_str_assign(&mut self, args : &mut Vec<AstArg>) -> bool {
+        if args.len() != 1 {
+            self.syntax.ltac_error2("Invalid string assignment.".to_string()).code(E_TYPE_MISMATCH);
+            return false;
+        }
+        
+        let arg = args.first_mut().unwrap();
+        
+        match &arg.arg_type {
+            AstArgType::StringL => {
+                arg.data_type = DataType::Str;
+                return true;
+            },
+            
+            AstArgType::Id if self.vars.contains_key(&arg.str_val) => {
+                let (data_type, sub_type) = match self.var_type(arg) {
+                    Ok(t) => t,
+                    Err(_e) => return false,
+                };
+                
+                if data_type != DataType::Str && sub_type != DataType::Str
+                        && sub_type != DataType::Byte && sub_type != DataType::UByte {
+                    self.syntax.ltac_error2("You can only assign a string to a string.".to_string()).code(E_TYPE_MISMATCH);
+                    return false;
+                }
+                
+                return true;
+            },
+            
+            AstArgType::Id if self.functions.contains_key(&arg.str_val) => {
+                let t = self.functions.get(&arg.str_val).unwrap().data_type.clone();
+                
+                if t != DataType::Str && t != DataType::Ptr {
+                    self.syntax.ltac_error2("You can only assign string or byte arrays to string variables.".to_string()).code(E_TYPE_MISMATCH);
+                    return false;
+                }
+                
+                arg.data_type = t;
+                return self.check_func_call(&arg.str_val, &mut arg.sub_args);
+            },
+            
+            AstArgType::Id => {
+                self.syntax.ltac_error2("Invalid string variable.".to_string()).code(E_UNKNOWN_NAME);
+                return false;
+            },
+            
+            _ => {
+                self.syntax.ltac_error2("Invalid string assignment.".to_string()).code(E_TYPE_MISMATCH);
+                return false;
+            },
+        }
+    }
+    
+    // Checks a record assignment
+    // Records can hold another record of the same type, or an address
+    fn check_record_assign(&mut self, line : &mut AstStmt, var : &TypeVar) -> bool {
+        if line.args.len() == 0 && line.stmt_type == AstStmtType::VarDec {
+            return true;
+        }
+        
+        if line.args.len() != 1 {
+            self.syntax.ltac_error2("Records can only be assigned a record or an address.".to_string()).code(E_TYPE_MISMATCH);
+            return false;
+        }
+        
+        let arg = line.args.first_mut().unwrap();
+        
+        match &arg.arg_type {
+            AstArgType::Id if self.vars.contains_key(&arg.str_val) => {
+                let (data_type, _) = match self.var_type(arg) {
+                    Ok(t) => t,
+                    Err(_e) => return false,
+                };
+                
+                if data_type != var.data_type && data_type != DataType::Int64
+                        && data_type != DataType::UInt64 {
+                    self.syntax.ltac_error2("Invalid record assignment.".to_string()).code(E_TYPE_MISMATCH);
+                    return false;
+                }
+                
+                return true;
+            },
+            
+            AstArgType::Id if self.functions.contains_key(&arg.str_val) => {
+                let t = self.functions.get(&arg.str_val).unwrap().data_type.clone();
+                
+                if t != DataType::Int64 && t != DataType::UInt64 {
+                    self.syntax.ltac_error2("Records can only be assigned from functions returning an address.".to_string()).code(E_TYPE_MISMATCH);
+                    return false;
+                }
+                
+                arg.data_type = t;
+                return self.check_func_call(&arg.str_val, &mut arg.sub_args);
+            },
+            
+            _ => {
+                self.syntax.ltac_error2("Records can only be assigned a record or an address.".to_string()).code(E_TYPE_MISMATCH);
+                return false;
+            },
+        }
+    }
+    
+    // ===============================================================
+    // Expressions
+    
+    // Checks an expression against the type it is assigned to
+    // For arrays, this is the element type
+    fn check_expr(&mut self, args : &mut Vec<AstArg>, data_type : &DataType) -> bool {
+        let mut negate_next = false;
+        
+        for arg in args.iter_mut() {
+            match &arg.arg_type {
+                AstArgType::OpLParen | AstArgType::OpRParen => {},
+                
+                AstArgType::OpNeg => negate_next = true,
+                
+                AstArgType::ByteL => {
+                    if negate_next {
+                        self.syntax.ltac_error2("Negation invalid for this type.".to_string()).code(E_INVALID_OP);
+                        return false;
+                    }
+                    
+                    if *data_type != DataType::Byte && *data_type != DataType::UByte {
+                        self.syntax.ltac_error2("Invalid use of byte literal.".to_string()).code(E_TYPE_MISMATCH);
+                        return false;
+                    }
+                    
+                    arg.data_type = data_type.clone();
+                },
+                
+                AstArgType::ShortL => {
+                    if negate_next {
+                        self.syntax.ltac_error2("Negation invalid for this type.".to_string()).code(E_INVALID_OP);
+                        return false;
+                    }
+                    
+                    if *data_type != DataType::Short && *data_type != DataType::UShort {
+                        self.syntax.ltac_error2("Invalid use of short literal.".to_string()).code(E_TYPE_MISMATCH);
+                        return false;
+                    }
+                    
+                    arg.data_type = data_type.clone();
+                },
+                
+                // Integer literals take on the type they are assigned to
+                AstArgType::IntL => {
+                    if !is_integer(data_type) && *data_type != DataType::Str {
+                        self.syntax.ltac_error2("Invalid use of integer.".to_string()).code(E_TYPE_MISMATCH);
+                        return false;
+                    }
+                    
+                    if negate_next && !is_signed(data_type) {
+                        self.syntax.ltac_error2("Negation invalid for this type.".to_string()).code(E_INVALID_OP);
+                        return false;
+                    }
+                    
+                    if let Err(msg) = check_int_literal(arg, negate_next, data_type) {
+                        self.syntax.ltac_error2(msg).code(E_LITERAL_RANGE);
+                        return false;
+                    }
+                    
+                    arg.data_type = data_type.clone();
+                    negate_next = false;
+                },
+                
+                AstArgType::FloatL => {
+                    if *data_type != DataType::Float && *data_type != DataType::Double {
+                        self.syntax.ltac_error2("Invalid use of float literal.".to_string()).code(E_TYPE_MISMATCH);
+                        return false;
+                    }
+                    
+                    arg.data_type = data_type.clone();
+                    negate_next = false;
+                },
+                
+                AstArgType::CharL => {
+                    match data_type {
+                        DataType::Char | DataType::Byte | DataType::UByte => arg.data_type = DataType::Char,
+                        
+                        _ => {
+                            self.syntax.ltac_error2("Invalid use of char literal.".to_string()).code(E_TYPE_MISMATCH);
+                            return false;
+                        },
+                    }
+                },
+                
+                AstArgType::StringL => {
+                    if *data_type != DataType::Str {
+                        self.syntax.ltac_error2("Invalid use of string literal.".to_string()).code(E_TYPE_MISMATCH);
+                        return false;
+                    }
+                    
+                    arg.data_type = DataType::Str;
+                },
+                
+                AstArgType::BoolL => {
+                    if *data_type != DataType::Bool {
+                        self.syntax.ltac_error2("Invalid use of bool literal.".to_string()).code(E_TYPE_MISMATCH);
+                        return false;
+                    }
+                    
+                    arg.data_type = DataType::Bool;
+                },
+                
+                // Calls through a procedure variable
+                // Without arguments, the variable is only a value if a procedure is expected
+                AstArgType::Id if self.is_proc_call(arg, data_type) => {
+                    let t = self.proc_var(&arg.str_val).unwrap().data_type;
+                    
+                    if t == DataType::Void {
+                        let mut msg = "Procedure does not return a value: ".to_string();
+                        msg.push_str(&arg.str_val);
+                        
+                        self.syntax.ltac_error2(msg).code(E_TYPE_MISMATCH);
+                        return false;
+                    }
+                    
+                    if !self.check_value(&arg.str_val, data_type, &t, &DataType::None) {
+                        return false;
+                    }
+                    
+                    arg.data_type = t;
+                    
+                    if !self.check_func_call(&arg.str_val, &mut arg.sub_args) {
+                        return false;
+                    }
+                },
+                
+                // Variables
+                AstArgType::Id if self.vars.contains_key(&arg.str_val) => {
+                    let (value_type, sub_type) = match self.var_type(arg) {
+                        Ok(t) => t,
+                        Err(_e) => return false,
+                    };
+                    
+                    if negate_next && !is_signed(&value_type) && !is_float(&value_type) {
+                        self.syntax.ltac_error2("Invalid use of negation operator.".to_string()).code(E_INVALID_OP);
+                        return false;
+                    }
+                    
+                    if !self.check_value(&arg.str_val, data_type, &value_type, &sub_type) {
+                        return false;
+                    }
+                    
+                    negate_next = false;
+                },
+                
+                // System calls
+                AstArgType::Id if arg.str_val == "syscall" => {
+                    if !is_integer(data_type) || *data_type == DataType::Char {
+                        self.syntax.ltac_error2("You can only assign system call returns to integers.".to_string()).code(E_TYPE_MISMATCH);
+                        return false;
+                    }
+                    
+                    arg.data_type = DataType::Int64;
+                    
+                    if !self.check_syscall(&mut arg.sub_args) {
+                        return false;
+                    }
+                },
+                
+                // Function calls
+                AstArgType::Id if self.functions.contains_key(&arg.str_val) => {
+                    let t = self.functions.get(&arg.str_val).unwrap().data_type.clone();
+                    
+                    if t == DataType::Void {
+                        let mut msg = "Function does not return a value: ".to_string();
+                        msg.push_str(&arg.str_val);
+                        
+                        self.syntax.ltac_error2(msg).code(E_TYPE_MISMATCH);
+                        return false;
+                    }
+                    
+                    if !self.check_value(&arg.str_val, data_type, &t, &DataType::None) {
+                        return false;
+                    }
+                    
+                    arg.data_type = t;
+                    
+                    if !self.check_func_call(&arg.str_val, &mut arg.sub_args) {
+                        return false;
+                    }
+                },
+                
+                // Enumerated values can only be assigned to their own enumeration
+                AstArgType::Id => {
+                    if let DataType::Enum(name) = data_type {
+                        if let Some(e) = self.enums.get(name) {
+                            if e.values.contains_key(&arg.str_val) {
+                                arg.data_type = data_type.clone();
+                                continue;
+                            }
+                        }
+                    }
+                    
+                    let mut msg = "Invalid function, constant, or variable name: ".to_string();
+                    msg.push_str(&arg.str_val);
+                    
+                    self.syntax.ltac_error2(msg).code(E_UNKNOWN_NAME);
+                    return false;
+                },
+                
+                AstArgType::Field => {
+                    if negate_next {
+                        self.syntax.ltac_error2("Negation invalid for record fields.".to_string()).code(E_INVALID_OP);
+                        return false;
+                    }
+                    
+                    let field_type = match self.field_type(&arg.str_val, &arg.sub_args) {
+                        Ok(t) => t,
+                        Err(_e) => return false,
+                    };
+                    
+                    if !self.check_value(&arg.str_val, data_type, &field_type, &DataType::None) {
+                        return false;
+                    }
+                    
+                    arg.data_type = field_type;
+                },
+                
+                AstArgType::Null => {
+                    if !matches!(data_type, DataType::Pointer(_)) {
+                        self.syntax.ltac_error2("Only pointers can be null.".to_string()).code(E_TYPE_MISMATCH);
+                        return false;
+                    }
+                    
+                    arg.data_type = data_type.clone();
+                },
+                
+                AstArgType::AddrOf if self.is_func_addr(arg) => {
+                    if !self.check_func_addr(arg, data_type) {
+                        return false;
+                    }
+                },
+                
+                AstArgType::Sizeof | AstArgType::AddrOf => {
+                    let (value_type, _) = match self.operand_type(arg) {
+                        Ok(t) => t,
+                        Err(_e) => return false,
+                    };
+                    
+                    if !self.check_value(&arg.str_val, data_type, &value_type, &DataType::None) {
+                        return false;
+                    }
+                },
+                
+                AstArgType::Expr => {
+                    if negate_next && !is_signed(data_type) && !is_float(data_type) {
+                        self.syntax.ltac_error2("Invalid use of negation operator.".to_string()).code(E_INVALID_OP);
+                        return false;
+                    }
+                    
+                    if !self.check_expr(&mut arg.sub_args, data_type) {
+                        return false;
+                    }
+                    
+                    arg.data_type = data_type.clone();
+                    negate_next = false;
+                },
+                
+                // Operators depend on the type being assigned to
+                AstArgType::OpAdd => {
+                    if !is_numeric(data_type) {
+                        self.syntax.ltac_error2("Invalid use of addition operator.".to_string()).code(E_INVALID_OP);
+                        return false;
+                    }
+                },
+                
+                AstArgType::OpSub => {
+                    if !is_signed(data_type) && !is_float(data_type) || *data_type == DataType::Char {
+                        self.syntax.ltac_error2("Invalid use of subtraction operator.".to_string()).code(E_INVALID_OP);
+                        return false;
+                    }
+                },
+                
+                AstArgType::OpMul => {
+                    if !is_numeric(data_type) {
+                        self.syntax.ltac_error2("Invalid use of multiplication operator.".to_string()).code(E_INVALID_OP);
+                        return false;
+                    }
+                },
+                
+                AstArgType::OpDiv => {
+                    if !is_numeric(data_type) {
+                        self.syntax.ltac_error2("Invalid use of division operator.".to_string()).code(E_INVALID_OP);
+                        return false;
+                    }
+                },
+                
+                AstArgType::OpMod => {
+                    if !is_numeric(data_type) || is_float(data_type) {
+                        self.syntax.ltac_error2("Modulo is only valid with integer values.".to_string()).code(E_INVALID_OP);
+                        return false;
+                    }
+                },
+                
+                AstArgType::OpAnd | AstArgType::OpOr | AstArgType::OpXor
+                | AstArgType::OpLeftShift | AstArgType::OpRightShift => {
+                    if !is_integer(data_type) {
+                        let msg = match &arg.arg_type {
+                            AstArgType::OpAnd => "Invalid use of logical and.",
+                            AstArgType::OpOr => "Invalid use of logical or.",
+                            AstArgType::OpXor => "Invalid use of logical xor.",
+                            AstArgType::OpLeftShift => "Invalid use of left shift.",
+                            _ => "Invalid use of right shift.",
+                        };
+                        
+                        self.syntax.ltac_error2(msg.to_string()).code(E_INVALID_OP);
+                        return false;
+                    }
+                },
+                
+                _ => {
+                    self.syntax.ltac_error2("Invalid operator in expression.".to_string()).code(E_INVALID_OP);
+                    return false;
+                },
+            }
+        }
+        
+        true
+    }
+    
+    // Checks an assignment to a pointer
+    // Besides a single value, a pointer can be moved by an integer number of elements
+    fn check_ptr_expr(&mut self, args : &mut Vec<AstArg>, data_type : &DataType) -> bool {
+        if args.len() != 3 {
+            return self.check_expr(args, data_type);
+        }
+        
+        let mut valid = args[1].arg_type == AstArgType::OpAdd || args[1].arg_type == AstArgType::OpSub;
+        
+        valid = valid && args[0].arg_type == AstArgType::Id && args[0].sub_args.len() == 0
+            && self.vars.contains_key(&args[0].str_val)
+            && self.operand_type(&mut args[0]).map(|(t, _)| t) == Ok(data_type.clone());
+        
+        let offset = &mut args[2];
+        valid = valid && offset.sub_args.len() == 0
+            && (offset.arg_type == AstArgType::IntL
+                || (offset.arg_type == AstArgType::Id && self.vars.contains_key(&offset.str_val)));
+        
+        // Offset variables are 64-bit so they never need to be sign-extended
+        // A narrower integer gets its own message, since it looks like it should work
+        if valid {
+            match self.operand_type(offset) {
+                Ok(_t) if offset.arg_type == AstArgType::IntL => return true,
+                Ok((DataType::Int64, _)) | Ok((DataType::UInt64, _)) => return true,
+                
+                Ok((t, _)) if int_range(&t).is_some() => {
+                    let msg = format!("Pointer offset {} is {}; only an integer literal or an int64 or uint64 variable can be added to a pointer.",
+                        offset.str_val, type_name(&t, &DataType::None));
+                    self.syntax.ltac_error2(msg).code(E_TYPE_MISMATCH);
+                    return false;
+                },
+                
+                Ok(_t) => {},
+                Err(_e) => return false,
+            }
+        }
+        
+        let msg = "Pointers can only be assigned a pointer, or a pointer plus or minus an integer literal or 64-bit integer.";
+        self.syntax.ltac_error2(msg.to_string()).code(E_TYPE_MISMATCH);
+        false
+    }
+    
+    // Makes sure a value can be stored in the given type
+    fn check_value(&mut self, name : &String, data_type : &DataType, value_type : &DataType, sub_type : &DataType) -> bool {
+        if is_compatible(data_type, value_type, sub_type) {
+            return true;
+        }
+        
+        if *value_type == DataType::Ptr {
+            let mut msg = "Array used without an index: ".to_string();
+            msg.push_str(name);
+            
+            self.syntax.ltac_error2(msg).code(E_TYPE_MISMATCH);
+            return false;
+        }
+        
+        let msg = format!("Type mismatch: expected {}, found {}.",
+            type_name(data_type, &DataType::None), type_name(value_type, sub_type));
+        self.syntax.ltac_error2(msg).code(E_TYPE_MISMATCH);
+        false
+    }
+    
+    // Returns the type of a variable reference
+    // An indexed array gives its element type
+    fn var_type(&mut self, arg : &mut AstArg) -> Result<(DataType, DataType), ()> {
+        let v = self.vars.get(&arg.str_val).unwrap().clone();
+        
+        if arg.sub_args.len() == 0 {
+            arg.data_type = v.data_type.clone();
+            return Ok((v.data_type, v.sub_type));
+        }
+        
+        // Strings are indexed by character
+        // Dereferencing a pointer is the same as indexing it
+        let elem_type = match &v.data_type {
+            DataType::Ptr | DataType::Pointer(_) => v.sub_type.clone(),
+            DataType::Str => DataType::Char,
+            
+            _ => {
+                let mut msg = "Cannot index a variable that is not an array: ".to_string();
+                msg.push_str(&arg.str_val);
+                
+                self.syntax.ltac_error2(msg).code(E_TYPE_MISMATCH);
+                return Err(());
+            },
+        };
+        
+        if !self.check_index(&mut arg.sub_args) || !self.check_bounds(&v, &arg.sub_args) {
+            return Err(());
+        }
+        
+        arg.data_type = elem_type.clone();
+        Ok((elem_type, DataType::None))
+    }
+    
+    // Checks an array index
+    fn check_index(&mut self, args : &mut Vec<AstArg>) -> bool {
+        if args.len() == 1 && args[0].arg_type == AstArgType::Id {
+            match self.vars.get(&args[0].str_val) {
+                Some(v) if v.is_global => {
+                    self.syntax.ltac_error2("Global variables cannot be used as array indexes.".to_string()).code(E_GLOBAL_INDEX);
+                    return false;
+                },
+                
+                Some(v) if !is_integer(&v.data_type) => {
+                    self.syntax.ltac_error2("Array indexes must be integers.".to_string()).code(E_TYPE_MISMATCH);
+                    return false;
+                },
+                
+                Some(v) => {
+                    args[0].data_type = v.data_type.clone();
+                    return true;
+                },
+                
+                None => {
+                    self.syntax.ltac_error2("Invalid offset variable.".to_string()).code(E_UNKNOWN_NAME);
+                    return false;
+                },
+            }
+        }
+        
+        self.check_expr(args, &DataType::Int)
+    }
+    
+    // Checks a constant index against an array of a known size
+    // Other indexes are left to the runtime checks
+    fn check_bounds(&mut self, var : &TypeVar, args : &Vec<AstArg>) -> bool {
+        let length = match var.length {
+            Some(length) => length,
+            None => return true,
+        };
+        
+        let (sign, index) = match args.as_slice() {
+            [arg] if arg.arg_type == AstArgType::IntL => ("", arg.u64_val),
+            [neg, arg] if neg.arg_type == AstArgType::OpNeg && arg.arg_type == AstArgType::IntL => ("-", arg.u64_val),
+            _ => return true,
+        };
+        
+        if index < length && (sign.is_empty() || index == 0) {
+            return true;
+        }
+        
+        let msg = format!("Array index {}{} is out of bounds for an array of {} elements.", sign, index, length);
+        self.syntax.ltac_error2(msg).code(E_INDEX_RANGE);
+        false
+    }
+    
+    // Returns the type of a field, and checks that the field exists
+    fn field_type(&mut self, name : &String, sub_args : &Vec<AstArg>) -> Result<DataType, ()> {
+        let var = match self.vars.get(name) {
+            Some(v) => v.clone(),
+            None => {
+                let mut msg = "Invalid record variable: ".to_string();
+                msg.push_str(name);
+                
+                self.syntax.ltac_error2(msg).code(E_UNKNOWN_NAME);
+                return Err(());
+            },
+        };
+        
+        let record = match &var.data_type {
+            DataType::Record(r) => self.records.get(r).unwrap().clone(),
+            
+            _ => {
+                self.syntax.ltac_error2("Only records have fields.".to_string());
+                return Err(());
+            },
+        };
+        
+        if sub_args.len() != 1 {
+            self.syntax.ltac_error2("Invalid field access.".to_string());
+            return Err(());
+        }
+        
+        let field_name = &sub_args.first().unwrap().str_val;
+        
+        for field in record.fields.iter() {
+            if field.name == *field_name {
+                return Ok(field.data_type.clone());
+            }
+        }
+        
+        let mut msg = "Invalid field name: ".to_string();
+        msg.push_str(field_name);
+        
+        self.syntax.ltac_error2(msg).code(E_UNKNOWN_NAME);
+        Err(())
+    }
+    
+    // Returns the type of a single operand outside of an assignment
+    // This is used for conditions and function arguments
+    fn operand_type(&mut self, arg : &mut AstArg) -> Result<(DataType, DataType), ()> {
+        let data_type : DataType;
+        
+        match &arg.arg_type {
+            AstArgType::ByteL => data_type = DataType::Byte,
+            AstArgType::ShortL => data_type = DataType::Short,
+            AstArgType::IntL if arg.data_type != DataType::None => return Ok((arg.data_type.clone(), DataType::None)),
+            AstArgType::IntL => data_type = DataType::Int,
+            AstArgType::FloatL => data_type = DataType::Float,
+            AstArgType::CharL => data_type = DataType::Char,
+            AstArgType::StringL => data_type = DataType::Str,
+            AstArgType::BoolL => data_type = DataType::Bool,
+            
+            AstArgType::Id if self.vars.contains_key(&arg.str_val) => return self.var_type(arg),
+            
+            AstArgType::Id => {
+                for (name, e) in self.enums.iter() {
+                    if e.values.contains_key(&arg.str_val) {
+                        arg.data_type = DataType::Enum(name.clone());
+                        return Ok((arg.data_type.clone(), DataType::None));
+                    }
+                }
+                
+                let mut msg = "Invalid constant or variable name: ".to_string();
+                msg.push_str(&arg.str_val);
+                
+                self.syntax.ltac_error2(msg).code(E_UNKNOWN_NAME);
+                return Err(());
+            },
+            
+            AstArgType::Field => {
+                data_type = match self.field_type(&arg.str_val, &arg.sub_args) {
+                    Ok(t) => t,
+                    Err(_e) => return Err(()),
+                };
+            },
+            
+            // Records have a size known at compile time; otherwise, we need an array
+            AstArgType::Sizeof => {
+                let name = arg.sub_args.first().unwrap().str_val.clone();
+                
+                if !self.records.contains_key(&name) {
+                    match self.vars.get(&name) {
+                        Some(v) if v.data_type == DataType::Ptr => {},
+                        Some(v) if matches!(v.data_type, DataType::Record(_)) => {},
+                        
+                        Some(_v) => {
+                            self.syntax.ltac_error2("Sizeof can only be used with arrays and strings.".to_string());
+                            return Err(());
+                        },
+                        
+                        None => {
+                            self.syntax.ltac_error2("Unknown array or string.".to_string()).code(E_UNKNOWN_NAME);
+                            return Err(());
+                        },
+                    }
+                }
+                
+                data_type = DataType::Int;
+            },
+            
+            AstArgType::AddrOf => {
+                let name = &arg.sub_args.first().unwrap().str_val;
+                
+                // Function addresses are checked against the parameter by the caller
+                if !self.vars.contains_key(name) && !self.functions.contains_key(name) {
+                    self.syntax.ltac_error2("Unknown variable reference.".to_string()).code(E_UNKNOWN_NAME);
+                    return Err(());
+                }
+                
+                // Scalar variables give a typed pointer; anything else is a plain address
+                data_type = match self.vars.get(name) {
+                    Some(v) if is_pointee(&v.data_type) => DataType::Pointer(Box::new(v.data_type.clone())),
+                    _ => DataType::Int64,
+                };
+            },
+            
+            AstArgType::Null => data_type = DataType::Pointer(Box::new(DataType::Void)),
+            
+            // Compound values are checked against the type they turn out to have
+            AstArgType::Expr => {
+                if is_cond(&arg.sub_args) {
+                    self.syntax.ltac_error2("Conditions can only be used in flow statements and bool assignments.".to_string()).code(E_INVALID_OP);
+                    return Err(());
+                }
+                
+                data_type = self.expr_type(&mut arg.sub_args)?;
+                
+                let valid = match &data_type {
+                    DataType::Pointer(_) => self.check_ptr_expr(&mut arg.sub_args, &data_type),
+                    _ => self.check_expr(&mut arg.sub_args, &data_type),
+                };
+                
+                if !valid {
+                    return Err(());
+                }
+            },
+            
+            _ => {
+                self.syntax.ltac_error2("Invalid operand.".to_string()).code(E_INVALID_OP);
+                return Err(());
+            },
+        }
+        
+        arg.data_type = data_type.clone();
+        Ok((data_type, DataType::None))
+    }
+    
+    // Returns the type of an expression outside of an assignment
+    // The first value with a type of its own decides it; literals only do when there is nothing else
+    fn expr_type(&mut self, args : &mut Vec<AstArg>) -> Result<DataType, ()> {
+        let mut literal_type = DataType::None;
+        
+        for arg in args.iter_mut() {
+            match &arg.arg_type {
+                AstArgType::Id if self.is_proc_call(arg, &DataType::None) => {
+                    return Ok(self.proc_var(&arg.str_val).unwrap().data_type);
+                },
+                
+                AstArgType::Id if !self.vars.contains_key(&arg.str_val) && arg.str_val == "syscall" => {
+                    return Ok(DataType::Int64);
+                },
+                
+                AstArgType::Id if !self.vars.contains_key(&arg.str_val) && self.functions.contains_key(&arg.str_val) => {
+                    return Ok(self.functions.get(&arg.str_val).unwrap().data_type.clone());
+                },
+                
+                AstArgType::Id | AstArgType::Field | AstArgType::Sizeof
+                | AstArgType::AddrOf | AstArgType::Null => return Ok(self.operand_type(arg)?.0),
+                
+                AstArgType::Expr => return self.expr_type(&mut arg.sub_args),
+                
+                _ if literal_type != DataType::None => {},
+                
+                AstArgType::IntL if arg.data_type != DataType::None => literal_type = arg.data_type.clone(),
+                AstArgType::IntL if arg.u64_val > (i32::MAX as u64) => literal_type = DataType::Int64,
+                AstArgType::IntL => literal_type = DataType::Int,
+                AstArgType::ByteL => literal_type = DataType::Byte,
+                AstArgType::ShortL => literal_type = DataType::Short,
+                AstArgType::FloatL => literal_type = DataType::Float,
+                AstArgType::CharL => literal_type = DataType::Char,
+                AstArgType::StringL => literal_type = DataType::Str,
+                AstArgType::BoolL => literal_type = DataType::Bool,
+                
+                _ => {},
+            }
+        }
+        
+        if literal_type == DataType::None {
+            self.syntax.ltac_error2("Invalid operand.".to_string()).code(E_INVALID_OP);
+            return Err(());
+        }
+        
+        Ok(literal_type)
+    }
+    
+    // ===============================================================
+    // Functions
+    
+    // Checks a function call, including the number and types of arguments
+    fn check_func_call(&mut self, name : &String, args : &mut Vec<AstArg>) -> bool {
+        if name == "syscall" {
+            return self.check_syscall(args);
+        }
+        
+        if !self.check_call_args(args) {
+            return false;
+        }
+        
+        // Undeclared functions are assumed to come from the C library, so only
+        // declared functions have their signatures checked
+        let func = match self.proc_var(name) {
+            Some(p) => p,
+            None => match self.functions.get(name) {
+                Some(f) => f.clone(),
+                None => return true,
+            },
+        };
+        
+        let count = func.args.len();
+        
+        if args.len() < func.min_args || (args.len() > count && !func.is_variadic) {
+            let mut msg = format!("Function {} expects {} argument", name, func.min_args);
+            
+            if func.min_args != 1 {
+                msg.push_str("s");
+            }
+            
+            if func.is_variadic {
+                msg.push_str(" or more");
+            }
+            
+            if args.len() == 1 {
+                msg.push_str(", but 1 was given.");
+            } else {
+                msg.push_str(&format!(", but {} were given.", args.len()));
+            }
+            
+            self.syntax.ltac_error2(msg).code(E_ARG_COUNT);
+            return false;
+        }
+        
+        // Optional arguments after "..." are checked only if they were given
+        for (i, (param, arg)) in func.args.iter().zip(args.iter_mut()).enumerate() {
+            if matches!(param.data_type, DataType::Proc(_)) && self.is_func_addr(arg) {
+                if !self.check_func_addr(arg, &param.data_type) {
+                    return false;
+                }
+                
+                continue;
+            }
+            
+            // Literals also have to fit the parameter
+            if arg.arg_type == AstArgType::IntL {
+                if let Err(msg) = check_int_range(arg.u64_val, false, &param.data_type) {
+                    self.syntax.ltac_error2(msg).code(E_LITERAL_RANGE);
+                    return false;
+                }
+            }
+            
+            // Float literals take on the width of the parameter
+            if is_float(&param.data_type) && is_float_literal(arg) {
+                arg.data_type = param.data_type.clone();
+            }
+            
+            let sub_type = self.arg_sub_type(arg);
+            
+            if is_compatible(&param.data_type, &arg.data_type, &sub_type) {
+                continue;
+            }
+            
+            let msg = format!("Invalid argument {} to {}: expected {}, found {}.", i + 1, name,
+                type_name(&param.data_type, &param.sub_type), type_name(&arg.data_type, &sub_type));
+            
+            self.syntax.ltac_error2(msg).code(E_TYPE_MISMATCH);
+            return false;
+        }
+        
+        true
+    }
+    
+    // Returns the signature of a procedure variable
+    fn proc_var(&self, name : &String) -> Option<TypeFunc> {
+        match self.vars.get(name) {
+            Some(TypeVar { data_type : DataType::Proc(p), .. }) => return self.procs.get(p).cloned(),
+            _ => return None,
+        }
+    }
+    
+    // Checks whether an operand calls through a procedure variable
+    fn is_proc_call(&self, arg : &AstArg, data_type : &DataType) -> bool {
+        if self.proc_var(&arg.str_val).is_none() {
+            return false;
+        }
+        
+        arg.sub_args.len() > 0 || !matches!(data_type, DataType::Proc(_))
+    }
+    
+    // Checks whether an operand takes the address of a function
+    // Variables shadow functions of the same name
+    fn is_func_addr(&self, arg : &AstArg) -> bool {
+        if arg.arg_type != AstArgType::AddrOf {
+            return false;
+        }
+        
+        let name = &arg.sub_args.first().unwrap().str_val;
+        !self.vars.contains_key(name) && self.functions.contains_key(name)
+    }
+    
+    // Checks a function address against the type it is assigned to
+    // The function must have exactly the signature of the procedure type
+    fn check_func_addr(&mut self, arg : &mut AstArg, data_type : &DataType) -> bool {
+        let name = arg.sub_args.first().unwrap().str_val.clone();
+        let func = self.functions.get(&name).unwrap().clone();
+        
+        let proc_name = match data_type {
+            DataType::Proc(p) => p.clone(),
+            
+            DataType::Int64 | DataType::UInt64 => {
+                arg.data_type = DataType::Int64;
+                return true;
+            },
+            
+            _ => {
+                let msg = format!("Type mismatch: expected {}, found the address of {}.",
+                    type_name(data_type, &DataType::None), name);
+                self.syntax.ltac_error2(msg).code(E_TYPE_MISMATCH);
+                return false;
+            },
+        };
+        
+        let proc_type = self.procs.get(&proc_name).unwrap();
+        let mut matches = !func.is_variadic
+            && func.data_type == proc_type.data_type
+            && func.args.len() == proc_type.args.len();
+        
+        for (a, b) in func.args.iter().zip(proc_type.args.iter()) {
+            if a.data_type != b.data_type || a.sub_type != b.sub_type {
+                matches = false;
+            }
+        }
+        
+        if !matches {
+            let msg = format!("Function {} does not match the procedure type {}.", name, proc_name);
+            self.syntax.ltac_error2(msg).code(E_TYPE_MISMATCH);
+            return false;
+        }
+        
+        arg.data_type = data_type.clone();
+        true
+    }
+    
+    // Checks each argument of a call
+    // Arguments are single values; anything compound is an Expr argument
+    fn check_call_args(&mut self, args : &mut Vec<AstArg>) -> bool {
+        for arg in args.iter_mut() {
+            match &arg.arg_type {
+                AstArgType::Field => {
+                    match self.operand_type(arg) {
+                        Ok((t, _)) if is_integer(&t) || t == DataType::Str => {},
+                        
+                        Ok(_t) => {
+                            self.syntax.ltac_error2("Only integer and string fields can be passed directly.".to_string()).code(E_TYPE_MISMATCH);
+                            return false;
+                        },
+                        
+                        Err(_e) => return false,
+                    }
+                },
+                
+                AstArgType::ByteL | AstArgType::ShortL | AstArgType::IntL
+                | AstArgType::FloatL | AstArgType::CharL | AstArgType::StringL
+                | AstArgType::BoolL | AstArgType::Id | AstArgType::Sizeof | AstArgType::AddrOf
+                | AstArgType::Null | AstArgType::Expr => {
+                    if self.operand_type(arg).is_err() {
+                        return false;
+                    }
+                },
+                
+                _ => {
+                    self.syntax.ltac_error2("Invalid function argument.".to_string()).code(E_INVALID_OP);
+                    return false;
+                },
+            }
+        }
+        
+        true
+    }
+    
+    // Returns the element type of an array argument
+    fn arg_sub_type(&self, arg : &AstArg) -> DataType {
+        if arg.arg_type == AstArgType::Id && arg.data_type == DataType::Ptr {
+            if let Some(v) = self.vars.get(&arg.str_val) {
+                return v.sub_type.clone();
+            }
+        }
+        
+        DataType::None
+    }
+    
+    // System calls only take integers and strings
+    fn check_syscall(&mut self, args : &mut Vec<AstArg>) -> bool {
+        if !self.check_call_args(args) {
+            return false;
+        }
+        
+        for arg in args.iter() {
+            if is_float(&arg.data_type) {
+                self.syntax.ltac_error2("Only integers and strings are valid in system calls.".to_string()).code(E_TYPE_MISMATCH);
+                return false;
+            }
+        }
+        
+        true
+    }
+    
+    // Checks a return statement against the function type
+    fn check_return(&mut self, line : &mut AstStmt) -> bool {
+        line.data_type = self.current_type.clone();
+        
+        if line.args.len() > 0 && self.current_type == DataType::Void {
+            let mut msg = "Cannot return value in void function: ".to_string();
+            msg.push_str(&self.current_func);
+            
+            self.syntax.ltac_error2(msg);
+            return false;
+        }
+        
+        if line.args.len() == 0 && self.current_type != DataType::Void {
+            let mut msg = "Expected return value in function: ".to_string();
+            msg.push_str(&self.current_func);
+            
+            self.syntax.ltac_error2(msg);
+            return false;
+        }
+        
+        match &self.current_type {
+            DataType::Void => return true,
+            DataType::Str => return self.check_str_assign(&mut line.args),
+            DataType::Pointer(_) => {
+                let data_type = self.current_type.clone();
+                return self.check_ptr_expr(&mut line.args, &data_type);
+            },
+            
+            t => {
+                let data_type = t.clone();
+                return self.check_expr(&mut line.args, &data_type);
+            },
+        }
+    }
+    
+    fn check_exit(&mut self, line : &mut AstStmt) -> bool {
+        if line.args.len() > 1 {
+            self.syntax.ltac_error2("You can only have one argument in the \"exit\" statement.".to_string());
+            return false;
+        }
+        
+        self.check_expr(&mut line.args, &DataType::Int)
+    }
+    
+    // ===============================================================
+    // Flow control
+    
+    // Checks the condition of an if, elif, or while statement
+    fn check_cond(&mut self, line : &mut AstStmt) -> bool {
+        self.check_condition(&mut line.args)
+    }
+    
+    // Conditions are comparisons and bool values, joined by "and", "or", and "not"
+    // Bool variables are assigned conditions too
+    fn check_condition(&mut self, args : &mut Vec<AstArg>) -> bool {
+        let tree = match cond_tree(args) {
+            Ok(t) => t,
+            Err(msg) => {
+                self.syntax.ltac_error2(msg).code(E_INVALID_OP);
+                return false;
+            },
+        };
+        
+        self.check_tests(&tree, args)
+    }
+    
+    fn check_tests(&mut self, cond : &AstCond, args : &mut Vec<AstArg>) -> bool {
+        match cond {
+            AstCond::Test(start, end) => return self.check_test(&mut args[*start..*end]),
+            AstCond::Not(c) => return self.check_tests(c, args),
+            AstCond::And(lhs, rhs) | AstCond::Or(lhs, rhs) => return self.check_tests(lhs, args) && self.check_tests(rhs, args),
+        }
+    }
+    
+    // A single comparison, or a bool value
+    fn check_test(&mut self, args : &mut [AstArg]) -> bool {
+        if args.len() == 1 {
+            match self.cond_operand_type(&mut args[0]) {
+                Ok((DataType::Bool, _)) => return true,
+                
+                Ok(_t) => {
+                    self.syntax.ltac_error2("Conditions must use a comparison operator.".to_string()).code(E_INVALID_OP);
+                    return false;
+                },
+                
+                Err(_e) => return false,
+            }
+        }
+        
+        // Negative numbers on the right have a separate negation operator
+        let mut rval = 2;
+        
+        if args.len() == 4 && args[2].arg_type == AstArgType::OpNeg {
+            rval = 3;
+        }
+        
+        if args.len() != rval + 1 {
+            self.syntax.ltac_error2("Invalid condition.".to_string()).code(E_INVALID_OP);
+            return false;
+        }
+        
+        match &args[1].arg_type {
+            AstArgType::OpEq | AstArgType::OpNeq
+            | AstArgType::OpLt | AstArgType::OpLte
+            | AstArgType::OpGt | AstArgType::OpGte => {},
+            
+            _ => {
+                self.syntax.ltac_error2("Conditions must use a comparison operator.".to_string()).code(E_INVALID_OP);
+                return false;
+            },
+        }
+        
+        let (type1, sub_type1) = match self.cond_operand_type(&mut args[0]) {
+            Ok(t) => t,
+            Err(_e) => return false,
+        };
+        
+        let (type2, sub_type2) = match self.cond_operand_type(&mut args[rval]) {
+            Ok(t) => t,
+            Err(_e) => return false,
+        };
+        
+        let is_eq = args[1].arg_type == AstArgType::OpEq || args[1].arg_type == AstArgType::OpNeq;
+        
+        let valid = (is_integer(&type1) && is_integer(&type2))
+            || (type1 == DataType::Bool && type2 == DataType::Bool && is_eq)
+            || (is_float(&type1) && is_float(&type2)
+                && (type1 == type2 || is_float_literal(&args[0]) || is_float_literal(&args[rval])))
+            || (type1 == DataType::Str && type2 == DataType::Str)
+            || is_same_pointer(&type1, &type2)
+            || (is_eq && is_str_null(&type1, &type2));
+        
+        if type1 == DataType::Bool && type2 == DataType::Bool && !is_eq {
+            self.syntax.ltac_error2("Bools can only be compared with \"==\" and \"!=\".".to_string()).code(E_INVALID_OP);
+            return false;
+        } else if !valid {
+            let msg = format!("Cannot compare {} with {}.",
+                type_name(&type1, &sub_type1), type_name(&type2, &sub_type2));
+            
+            self.syntax.ltac_error2(msg).code(E_TYPE_MISMATCH);
+            return false;
+        }
+        
+        true
+    }
+    
+    // Returns the type of a value in a condition
+    fn cond_operand_type(&mut self, arg : &mut AstArg) -> Result<(DataType, DataType), ()> {
+        if arg.arg_type == AstArgType::Field {
+            let t = self.operand_type(arg)?;
+            
+            if !is_integer(&t.0) {
+                self.syntax.ltac_error2("Only integer and character fields can be compared.".to_string()).code(E_TYPE_MISMATCH);
+                return Err(());
+            }
+            
+            return Ok(t);
+        }
+        
+        self.operand_type(arg)
+    }
+    
+    // Checks a for loop, and declares its index variable
+    // Syntax: for <index> in <start> .. <end> | for <index> in <array>
+    fn check_for(&mut self, line : &mut AstStmt) -> bool {
+        let args = &mut line.args;
+        let index_name = args[0].str_val.clone();
+        
+        // Range loops
+        if args.len() == 4 && args[2].arg_type == AstArgType::Range {
+            for i in [1, 3].iter() {
+                match self.operand_type(&mut args[*i]) {
+                    Ok((t, _)) if is_integer(&t) => {},
+                    
+                    Ok(_t) => {
+                        self.syntax.ltac_error2("Loop ranges can only use integers.".to_string()).code(E_TYPE_MISMATCH);
+                        return false;
+                    },
+                    
+                    Err(_e) => return false,
+                }
+            }
+            
+            self.declare(&index_name, &DataType::Int, &DataType::None);
+            args[0].data_type = DataType::Int;
+            
+            return true;
+        }
+        
+        // For-each loops
+        if args.len() != 2 || args[1].arg_type != AstArgType::Id || args[1].sub_args.len() > 0 {
+            self.syntax.ltac_error2("Invalid for loop.".to_string());
+            return false;
+        }
+        
+        let array = match self.vars.get(&args[1].str_val) {
+            Some(v) => v.clone(),
+            None => {
+                self.syntax.ltac_error2("Invalid variable.".to_string()).code(E_UNKNOWN_NAME);
+                return false;
+            },
+        };
+        
+        if array.data_type != DataType::Ptr {
+            let mut msg = "For-each loops can only be used with arrays: ".to_string();
+            msg.push_str(&args[1].str_val);
+            
+            self.syntax.ltac_error2(msg).code(E_TYPE_MISMATCH);
+            return false;
+        }
+        
+        self.declare(&index_name, &array.sub_type, &DataType::None);
+        args[0].data_type = array.sub_type.clone();
+        args[1].data_type = DataType::Ptr;
+        
+        true
+    }
+    
+    // Checks a case statement
+    // The choices have to fit the selector, no value can be matched twice, and
+    // without "when others" every value of an enumeration has to be matched
+    fn check_case(&mut self, line : &mut AstStmt) -> bool {
+        let var = match self.vars.get(&line.args[0].str_val) {
+            Some(v) => v.clone(),
+            None => {
+                self.syntax.ltac_error2("Invalid variable.".to_string()).code(E_UNKNOWN_NAME);
+                return false;
+            },
+        };
+        
+        if !is_integer(&var.data_type) {
+            self.syntax.ltac_error2("Case statements can only select on integers, characters, or enumerations.".to_string()).code(E_TYPE_MISMATCH);
+            return false;
+        }
+        
+        line.data_type = var.data_type.clone();
+        line.args[0].data_type = var.data_type.clone();
+        
+        let values = match &var.data_type {
+            DataType::Enum(name) => self.enums.get(name).map(|e| e.values.clone()),
+            
+            _ => None,
+        };
+        
+        let (min, max) = case_bounds(&var.data_type);
+        let arms = case_arms(line);
+        let mut ranges : Vec<(i64, i64)> = Vec::new();
+        let mut has_others = false;
+        
+        for (i, arm) in arms.iter().enumerate() {
+            self.syntax.set_data(arm);
+            
+            if arm.args.len() == 0 {
+                if i + 1 != arms.len() {
+                    self.syntax.ltac_error2("\"when others\" must be the last arm.".to_string());
+                    return false;
+                }
+                
+                has_others = true;
+                continue;
+            }
+            
+            for arg in arm.args.iter() {
+                let valid = match (&arg.arg_type, &var.data_type) {
+                    (AstArgType::OpNeg, _) | (AstArgType::OpOr, _) | (AstArgType::Range, _) => true,
+                    (AstArgType::Id, DataType::Enum(_)) => true,
+                    // Char parameters are ubyte, so chars work on any byte
+                    (AstArgType::CharL, DataType::Char | DataType::Byte | DataType::UByte) => true,
+                    (AstArgType::CharL, _) | (AstArgType::Id, _) => false,
+                    (AstArgType::IntL, t) => is_numeric(t) && arg.u64_val <= i64::MAX as u64,
+                    (AstArgType::ByteL, t) | (AstArgType::ShortL, t) => is_numeric(t),
+                    _ => false,
+                };
+                
+                if !valid {
+                    self.syntax.ltac_error2("Invalid choice for the selector type.".to_string()).code(E_TYPE_MISMATCH);
+                    return false;
+                }
+            }
+            
+            let choices = match choice_ranges(arm, values.as_ref()) {
+                Ok(c) => c,
+                Err(msg) => {
+                    self.syntax.ltac_error2(msg).code(E_TYPE_MISMATCH);
+                    return false;
+                },
+            };
+            
+            for (low, high) in choices.iter() {
+                if *low < min || *high > max || *low > max || *high < min {
+                    self.syntax.ltac_error2("Choice out of range for the selector type.".to_string()).code(E_LITERAL_RANGE);
+                    return false;
+                }
+                
+                if low > high {
+                    self.syntax.ltac_error2("Empty range: the low bound is greater than the high bound.".to_string());
+                    return false;
+                }
+                
+                if ranges.iter().any(|(l, h)| low <= h && high >= l) {
+                    self.syntax.ltac_error2("Duplicate choice: a value is already matched by another arm.".to_string()).code(E_DUPLICATE);
+                    return false;
+                }
+                
+                ranges.push((*low, *high));
+            }
+        }
+        
+        self.syntax.set_data(line);
+        
+        // Enumerations have to be covered completely
+        if let Some(values) = values {
+            if !has_others {
+                let mut missing : Vec<(&i32, &String)> = values.iter()
+                    .filter(|(_, v)| !ranges.iter().any(|(l, h)| **v as i64 >= *l && **v as i64 <= *h))
+                    .map(|(name, v)| (v, name))
+                    .collect();
+                
+                if missing.len() > 0 {
+                    missing.sort();
+                    
+                    let mut msg = "Case does not cover all enumeration values; missing:".to_string();
+                    for (_, name) in missing.iter() {
+                        msg.push(' ');
+                        msg.push_str(name);
+                    }
+                    
+                    self.syntax.ltac_error2(msg).code(E_SEMANTIC);
+                    return false;
+                }
+            }
+        }
+        
+        true
+    }
+}
+
+// ===============================================================
+// Utility functions
+
+// The values a case choice can have for a given selector type
+// Unsigned 64-bit choices are kept in the signed range
+fn case_bounds(data_type : &DataType) -> (i64, i64) {
+    match data_type {
+        DataType::Byte | DataType::Char => return (i8::MIN as i64, i8::MAX as i64),
+        DataType::UByte => return (0, u8::MAX as i64),
+        DataType::Short => return (i16::MIN as i64, i16::MAX as i64),
+        DataType::UShort => return (0, u16::MAX as i64),
+        DataType::UInt => return (0, u32::MAX as i64),
+        DataType::Int64 => return (i64::MIN, i64::MAX),
+        DataType::UInt64 => return (0, i64::MAX),
+        _ => return (i32::MIN as i64, i32::MAX as i64),
+    }
+}
+
+// Integers, characters, and enumerations all share registers
+fn is_integer(data_type : &DataType) -> bool {
+    match data_type {
+        DataType::Byte | DataType::UByte
+        | DataType::Short | DataType::UShort
+        | DataType::Int | DataType::UInt
+        | DataType::Int64 | DataType::UInt64
+        | DataType::Char | DataType::Enum(_) => return true,
+        _ => return false,
+    }
+}
+
+fn is_signed(data_type : &DataType) -> bool {
+    match data_type {
+        DataType::Byte | DataType::Short
+        | DataType::Int | DataType::Int64 | DataType::Char => return true,
+        _ => return false,
+    }
+}
+
+fn is_float(data_type : &DataType) -> bool {
+    match data_type {
+        DataType::Float | DataType::Double => return true,
+        _ => return false,
+    }
+}
+
+// Checks whether an argument is a float literal, or is worked out from float literals alone
+// These have no width of their own, so they go with either float type
+fn is_float_literal(arg : &AstArg) -> bool {
+    match &arg.arg_type {
+        AstArgType::FloatL => return true,
+        
+        AstArgType::Expr => {
+            return arg.sub_args.iter().all(|a| is_float_literal(a) || !ast_expr::is_operand(&a.arg_type));
+        },
+        
+        _ => return false,
+    }
+}
+
+// The types a pointer can point to
+fn is_pointee(data_type : &DataType) -> bool {
+    match data_type {
+        DataType::Enum(_) => return false,
+        _ => return is_integer(data_type) || is_float(data_type) || *data_type == DataType::Str,
+    }
+}
+
+// Pointers can be compared with pointers of the same type, or with null
+fn is_same_pointer(type1 : &DataType, type2 : &DataType) -> bool {
+    let null = DataType::Pointer(Box::new(DataType::Void));
+    
+    match (type1, type2) {
+        (DataType::Pointer(_), DataType::Pointer(_)) => return type1 == type2 || *type1 == null || *type2 == null,
+        _ => return false,
+    }
+}
+
+// A string is a pointer too, so it can be checked against null
+fn is_str_null(type1 : &DataType, type2 : &DataType) -> bool {
+    let null = DataType::Pointer(Box::new(DataType::Void));
+    
+    match (type1, type2) {
+        (DataType::Str, t) | (t, DataType::Str) => return *t == null,
+        _ => return false,
+    }
+}
+
+// The types that have math instructions
+fn is_numeric(data_type : &DataType) -> bool {
+    match data_type {
+        DataType::Char | DataType::Enum(_) => return false,
+        _ => return is_integer(data_type) || is_float(data_type),
+    }
+}
+
+// Checks whether a value can be stored in a given type
+// 64-bit integers can also hold addresses
+fn is_compatible(data_type : &DataType, value_type : &DataType, sub_type : &DataType) -> bool {
+    let is_address = match value_type {
+        DataType::Str | DataType::Ptr | DataType::Record(_) | DataType::Proc(_) => true,
+        DataType::Pointer(_) => true,
+        _ => false,
+    };
+    
+    match data_type {
+        DataType::Int64 | DataType::UInt64 => return is_integer(value_type) || is_address,
+        
+        // Neither backend converts between float widths, so they have to match
+        DataType::Float | DataType::Double => return value_type == data_type,
+        
+        
+        DataType::Str => {
+            return *value_type == DataType::Str
+                || *value_type == DataType::Int64 || *value_type == DataType::UInt64
+                || (*value_type == DataType::Ptr
+                    && (*sub_type == DataType::Byte || *sub_type == DataType::UByte));
+        },
+        
+        DataType::Record(_) => {
+            return value_type == data_type
+                || *value_type == DataType::Int64 || *value_type == DataType::UInt64;
+        },
+        
+        // Only whole arrays can be passed as arrays; the element type is checked by the caller
+        DataType::Ptr => return *value_type == DataType::Ptr,
+        DataType::Proc(_) => return value_type == data_type,
+        DataType::Bool => return *value_type == DataType::Bool,
+        
+        // Null has a pointer type of its own that goes with any pointer
+        // An array gives a pointer to its first element
+        DataType::Pointer(pointee) => {
+            return value_type == data_type || *value_type == DataType::Pointer(Box::new(DataType::Void))
+                || (*value_type == DataType::Ptr && *sub_type == **pointee);
+        },
+        
+        _ if is_integer(data_type) => return is_integer(value_type),
+        _ => return false,
+    }
+}
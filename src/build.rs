@@ -1,114 +0,0 @@
-
-// This file is part of the Lila compiler
-// Copyright (C) 2020-2021 Patrick Flynn
-//
-// This program is free software; you can redistribute it and/or modify
-// it under the terms of the GNU General Public License as published by
-// the Free Software Foundation; version 2.
-//
-// This program is distributed in the hope that it will be useful,
-// but WITHOUT ANY WARRANTY; without even the implied warranty of
-// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
-// GNU General Public License for more details.
-//
-// You should have received a copy of the GNU General Public License along
-// with this program; if not, write to the Free Software Foundation, Inc.,
-// 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
-
-// Eventually, I want all backends to use these functions
-
-use std::io;
-use std::io::prelude::*;
-use std::process::Command;
-
-// Assemble a file
-pub fn assemble(name : &String, no_link : bool) {
-    // Create all the names
-    let mut asm_name = "/tmp/".to_string();
-    asm_name.push_str(name);
-    asm_name.push_str(".asm");
-    
-    let mut obj_name = "/tmp/".to_string();
-    if no_link {
-        obj_name = "./".to_string();
-    }
-    
-    obj_name.push_str(name);
-    obj_name.push_str(".o");
-
-    // Assemble
-    let asm = Command::new("as")
-        .args(&[&asm_name, "-o", &obj_name])
-        .output()
-        .expect("Fatal: Assembly failed.");
-        
-    if !asm.status.success() {
-        io::stdout().write_all(&asm.stdout).unwrap();
-        io::stderr().write_all(&asm.stderr).unwrap();
-    }
-}
- 
-// Link everything
-pub fn link(all_names : &Vec<String>, output : &String, use_corelib : bool, is_lib : bool, inc_start : bool) {
-    let mut names : Vec<String> = Vec::new();
-    let mut libs : Vec<String> = Vec::new();
-    
-    for name in all_names.iter() {
-        if name.ends_with(".o") {
-            names.push(name.clone());
-        } else if name.starts_with("-l") {
-            libs.push(name.clone());
-        } else {
-            let mut obj_name = "/tmp/".to_string();
-            obj_name.push_str(&name);
-            obj_name.push_str(".o");
-            names.push(obj_name);
-        }
-    }
-    
-    // Link
-    //let ld : Output;
-    let mut args : Vec<&str> = Vec::new();
-    args.push("-L./");
-    
-    if !is_lib && inc_start {
-        args.push("/usr/lib/lila/lrt.o");
-    }
-    
-    args.push("-dynamic-linker");
-    args.push("/lib64/ld-linux-x86-64.so.2");
-        
-    for name in names.iter() {
-        args.push(&name);
-    }
-        
-    if is_lib {
-        args.push("-shared");
-    }
-    
-    for lib in libs.iter() {
-        args.push(lib);
-    }
-        
-    args.push("-o");
-    args.push(output);
-    
-    if use_corelib {
-        args.push("-llila");
-        
-        if use_corelib {
-            args.push("-llila_core");
-        }
-    }
-    
-    let ld = Command::new("ld")
-        .args(args.as_slice())
-        .output()
-        .expect("Fatal: Linking failed.");
-    
-    if !ld.status.success() {
-        io::stdout().write_all(&ld.stdout).unwrap();
-        io::stderr().write_all(&ld.stderr).unwrap();
-    }
-}
-
@@ -17,28 +17,67 @@
 
 
 use std::env;
+use std::fs;
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
 use std::process;
 
 use parser;
-use parser::Arch;
+use parser::{Arch, Checks};
+use parser::syntax;
+use parser::syntax::ErrorFormat;
 use ltac;
 use ltac::transform;
+use ltac::backend::{Backend, Pass, Feature, CodeOptions, LinkOptions};
+use ltac::toolchain;
+use ltac::toolchain::{Toolchain, ToolError};
+use ltac::workspace::{Workspace, unit_name};
 
-mod build;
+mod project;
+mod targets;
 
-#[cfg(target_arch = "x86_64")]
-fn get_arch() -> Arch {
-    Arch::X86_64
-}
+// Exit codes
+const EXIT_SOURCE : i32 = 1;        // Syntax or semantic errors in the source
+const EXIT_USAGE : i32 = 2;         // Invalid command line
+const EXIT_BACKEND : i32 = 3;       // Code generation or assembly failed
+const EXIT_LINK : i32 = 4;          // Linking failed
+const EXIT_TOOL : i32 = 5;          // The assembler or linker could not be run
 
-#[cfg(target_arch = "aarch64")]
-fn get_arch() -> Arch {
-    Arch::AArch64
+// The files --emit can write
+#[derive(Clone, Copy, PartialEq)]
+enum Artifact {
+    Asm,
+    Obj,
+    Ltac,
+    Llir,
+    LlvmIr,
+    LlvmBc,
+    Exe,
 }
 
-#[cfg(target_arch = "riscv64")]
-fn get_arch() -> Arch {
-    Arch::Riscv64
+// The options which apply to every translation unit
+struct Settings {
+    backend : Box<dyn Backend>,
+    tools : Toolchain,
+    ws : Workspace,
+    arch : Arch,
+    use_c : bool,
+    use_corelib : bool,
+    pic : bool,
+    checks : Checks,
+    risc_mode : bool,
+    use_llvm : bool,
+    llvm_debug : bool,
+    debug_info : bool,
+    emit : Vec<Artifact>,
+    opt_level : u32,
+    error_format : ErrorFormat,
+    output : String,
+    has_output : bool,
+    one_unit : bool,        // Emitted files are named after -o when there is only one source
+    link_lib : bool,
+    inc_start : bool,
 }
 
 // TODO: Is there a better way to do this?
@@ -51,48 +90,69 @@ fn run() -> i32 {
     let mut args : Vec<String> = env::args().collect();
     args.remove(0);
     
+    // lilac build <main file> compiles a whole program
+    let build_mode = args.len() > 0 && args[0] == "build";
+    
+    if build_mode {
+        args.remove(0);
+    }
+    
     if args.is_empty() {
         println!("Fatal: No input file specified.");
-        return 2;
+        return EXIT_USAGE;
     }
     
     let mut print_ast = false;
-    let mut print_ltac = false;
     let mut use_c = false;
     let mut use_corelib = true;
     let mut link_lib = false;
-    let mut no_link = false;
     let mut inc_start = true;
     let mut pic = false;
+    let mut checks = Checks::None;
     let mut risc_mode = false;      // This is a dev feature to allow us to work on the RISC optimizer on x86
     let mut use_llvm = false;
     let mut llvm_debug = false;
-    let mut arch = get_arch();
+    let mut debug_info = false;
+    let mut emit : Vec<Artifact> = Vec::new();
+    let mut save_temps = false;
+    let mut opt_level : Option<u32> = None;
+    let mut target = targets::HOST_TARGET.to_string();
+    let mut error_format = ErrorFormat::Text;
     let mut inputs : Vec<String> = Vec::new();
     let mut output : String = "a.out".to_string();
+    let mut has_output = false;
+    
+    // The toolchain overrides
+    let mut as_cmd : Option<String> = None;
+    let mut ld_cmd : Option<String> = None;
+    let mut cc_cmd : Option<String> = None;
+    let mut use_cc = false;
+    let mut dynamic_linker : Option<String> = None;
+    let mut runtime : Option<String> = None;
     
     let mut next_output = false;
     
     for arg in args {
         if next_output {
             output = arg.clone();
+            has_output = true;
             next_output = false;
             continue;
         }
-    
+        
         match arg.as_ref() {
             "--ast" => print_ast = true,
-            "--ltac" => print_ltac = true,
+            "--ltac" => emit.push(Artifact::Ltac),
             "--use-c" => use_c = true,
-            "--lib" => {
-                link_lib = true;
-                pic = true;
-            },
+            "--lib" => link_lib = true,
             "--pic" => pic = true,
             "--risc" => risc_mode = true,
-            "--no-link" => no_link = true,
+            "--no-link" => emit.push(Artifact::Obj),
+            "--save-temps" => save_temps = true,
             "--no-start" => inc_start = false,
             "-o" => next_output = true,
+            "-g" => debug_info = true,
+            "--cc" => use_cc = true,
             
             "--no-corelib" => {
                 use_corelib = false;
@@ -101,8 +161,6 @@ fn run() -> i32 {
                 println!("Certain core functions may not work.");
             },
             
-            "-march=riscv64" => arch = Arch::Riscv64,
-            
             "--llvm" => use_llvm = true,
             
             "--llvm-debug" => {
@@ -110,36 +168,248 @@ fn run() -> i32 {
                 llvm_debug = true;
             },
             
+            "-O0" => opt_level = Some(0),
+            "-O1" => opt_level = Some(1),
+            "-O2" => opt_level = Some(2),
+            "-O3" => opt_level = Some(3),
+            
+            "--checks=none" => checks = Checks::None,
+            "--checks=bounds" => checks = Checks::Bounds,
+            "--checks=all" => checks = Checks::All,
+            
+            "--error-format=text" => error_format = ErrorFormat::Text,
+            "--error-format=json" => error_format = ErrorFormat::Json,
+            
             "-h" | "--help" => {
                 help();
                 return 0;
             },
             
+            _ if arg.starts_with("-march=") => target = arg["-march=".len()..].to_string(),
+            _ if arg.starts_with("--as=") => as_cmd = Some(arg["--as=".len()..].to_string()),
+            _ if arg.starts_with("--ld=") => ld_cmd = Some(arg["--ld=".len()..].to_string()),
+            _ if arg.starts_with("--dynamic-linker=") => dynamic_linker = Some(arg["--dynamic-linker=".len()..].to_string()),
+            _ if arg.starts_with("--lrt=") => runtime = Some(arg["--lrt=".len()..].to_string()),
+            
+            _ if arg.starts_with("--cc=") => {
+                cc_cmd = Some(arg["--cc=".len()..].to_string());
+                use_cc = true;
+            },
+            
+            _ if arg.starts_with("--checks=") => {
+                println!("Fatal: Unknown checks: {}", arg);
+                return EXIT_USAGE;
+            },
+            
+            _ if arg.starts_with("--error-format=") => {
+                println!("Fatal: Unknown error format: {}", arg);
+                return EXIT_USAGE;
+            },
+            
+            _ if arg.starts_with("--emit=") => {
+                for kind in arg["--emit=".len()..].split(',') {
+                    match parse_artifact(kind) {
+                        Some(artifact) => emit.push(artifact),
+                        None => {
+                            println!("Fatal: Unknown output type: {}", kind);
+                            return EXIT_USAGE;
+                        },
+                    }
+                }
+            },
+            
+            _ if arg.starts_with("-O") => {
+                println!("Fatal: Unknown optimization level: {}", arg);
+                return EXIT_USAGE;
+            },
+            
             _ => inputs.push(arg.clone()),
         }
     }
     
+    if !use_llvm && opt_level.is_some() {
+        println!("Fatal: -O is only supported with --llvm.");
+        return EXIT_USAGE;
+    }
+    
+    // The checks report errors through the core library
+    if checks != Checks::None && !use_corelib {
+        println!("Fatal: --checks cannot be used with --no-corelib.");
+        return EXIT_USAGE;
+    }
+    
+    for artifact in emit.iter() {
+        let msg = match artifact {
+            Artifact::Llir | Artifact::LlvmIr | Artifact::LlvmBc if !use_llvm => "is only supported with --llvm",
+            Artifact::Ltac if use_llvm => "is not supported with --llvm",
+            _ => continue,
+        };
+        
+        println!("Fatal: --emit={} {}.", artifact_name(*artifact), msg);
+        return EXIT_USAGE;
+    }
+    
+    // With no --emit, we build a program
+    if emit.is_empty() {
+        emit.push(Artifact::Exe);
+    }
+    
+    let backend = match targets::get_backend(&target) {
+        Some(backend) => backend,
+        None => {
+            println!("Fatal: Unknown target: {}", target);
+            println!("The targets are: {}", targets::target_names().join(", "));
+            return EXIT_USAGE;
+        },
+    };
+    
+    let arch = backend.arch();
+    
+    // Use the cross tools when we are not building for this machine
+    let mut tools = toolchain::detect(&backend.target(), target != targets::HOST_TARGET);
+    tools.use_cc = use_cc;
+    tools.dynamic_linker = dynamic_linker;
+    
+    if let Some(cmd) = as_cmd {
+        tools.assembler = cmd;
+    }
+    
+    if let Some(cmd) = ld_cmd {
+        tools.linker = cmd;
+    }
+    
+    if let Some(cmd) = cc_cmd {
+        tools.cc = cmd;
+    }
+    
+    if let Some(path) = runtime {
+        tools.runtime = path;
+    }
+    
+    // LLVM builds for the machine we are on, and handles the options itself
+    if use_llvm && target != targets::HOST_TARGET {
+        println!("Fatal: The LLVM backend can only build for {}.", targets::HOST_TARGET);
+        return EXIT_USAGE;
+    }
+    
+    if !use_llvm {
+        let features = [(pic, Feature::Pic, "--pic"), (debug_info, Feature::DebugInfo, "-g"),
+                        (link_lib, Feature::SharedLib, "--lib")];
+        
+        for (used, feature, flag) in features.iter() {
+            if *used && !backend.supports(*feature) {
+                println!("Fatal: The {} backend does not support {}.", backend.name(), flag);
+                return EXIT_USAGE;
+            }
+        }
+    }
+    
+    // Libraries need position independent code where we have it
+    if link_lib && backend.supports(Feature::Pic) {
+        pic = true;
+    }
+    
     if print_ast {
         let input = inputs.last().unwrap();
-        let ast = match parser::get_ast(&input, arch, use_corelib, use_llvm) {
+        let ast = match parser::get_ast(&input, arch, use_corelib) {
             Ok(ast) => ast,
-            Err(_e) => return 1,
+            Err(errors) => {
+                syntax::print_errors(&errors, error_format);
+                return EXIT_SOURCE;
+            },
         };
         
         ast.print();
         return 0;
     }
     
+    let ws = match Workspace::create(save_temps) {
+        Ok(ws) => ws,
+        Err(e) => {
+            println!("Fatal: Unable to create a temporary directory: {}", e);
+            return EXIT_BACKEND;
+        },
+    };
+    
+    let units = inputs.iter().filter(|i| !i.starts_with("-l") && !i.ends_with(".o")).count();
+    
+    let settings = Settings {
+        backend : backend,
+        tools : tools,
+        ws : ws,
+        arch : arch,
+        use_c : use_c,
+        use_corelib : use_corelib,
+        pic : pic,
+        checks : checks,
+        risc_mode : risc_mode,
+        use_llvm : use_llvm,
+        llvm_debug : llvm_debug,
+        debug_info : debug_info,
+        emit : emit,
+        opt_level : opt_level.unwrap_or(0),
+        error_format : error_format,
+        output : output.clone(),
+        has_output : has_output,
+        one_unit : units == 1,
+        link_lib : link_lib,
+        inc_start : inc_start,
+    };
+    
+    if build_mode {
+        if settings.emit != [Artifact::Exe] {
+            println!("Fatal: --emit, --no-link and --ltac cannot be used with build.");
+            return EXIT_USAGE;
+        }
+        
+        return project::build(&inputs, &settings);
+    }
+    
     let mut all_names : Vec<String> = Vec::new();
     
-    if use_llvm {
-        let input = inputs.last().unwrap();
-        let llir = match parser::parse2(input.clone(), arch, use_corelib) {
+    for (i, input) in inputs.into_iter().enumerate() {
+        if input.starts_with("-l") || input.ends_with(".o") {
+            all_names.push(input);
+            continue;
+        }
+        
+        match compile_unit(&input, i, &settings) {
+            Ok(name) => all_names.push(settings.ws.file(&name, "o")),
+            Err(code) => return code,
+        }
+    }
+    
+    // With --emit, we only write the requested files
+    if !settings.emit.contains(&Artifact::Exe) {
+        return 0;
+    }
+    
+    link(&all_names, &output, &settings)
+}
+
+// Compiles one source file
+// Everything is written to the workspace as <unit name>.<ext>, and the files asked for with
+// --emit are copied out. On success, the unit name is returned
+fn compile_unit(input : &String, index : usize, settings : &Settings) -> Result<String, i32> {
+    let arch = settings.arch;
+    let ws = &settings.ws;
+    
+    // Both assembly and objects need the assembly, and linking needs the objects
+    let need_obj = settings.emit.contains(&Artifact::Obj) || settings.emit.contains(&Artifact::Exe);
+    let need_asm = need_obj || settings.emit.contains(&Artifact::Asm);
+    let name : String;
+    let unit : String;
+    
+    if settings.use_llvm {
+        let llir = match parser::parse2(input.clone(), arch, settings.use_corelib, settings.checks) {
             Ok(llir) => llir,
-            Err(_e) => return 1,
+            Err(errors) => {
+                syntax::print_errors(&errors, settings.error_format);
+                return Err(EXIT_SOURCE);
+            },
         };
         
-        if llvm_debug {
+        if settings.llvm_debug {
             println!("Name: {}", llir.name);
             for ln in llir.code.iter() {
                 println!("{:?}", ln);
@@ -147,69 +417,207 @@ fn run() -> i32 {
             println!("");
         }
         
-        llvm::compile(&llir, llvm_debug).expect("LLVM Codegen failed with unknown error.");
-        all_names.push(llir.name.clone());
+        unit = unit_name(index, &llir.name);
         
-        build::assemble(&llir.name, no_link);
-        build::link(&all_names, &output, use_corelib, link_lib, inc_start);
-        return 0;
-    }
-    
-    for input in inputs {
-        if input.starts_with("-l") || input.ends_with(".o") {
-            all_names.push(input);
-            continue;
+        if settings.emit.contains(&Artifact::Llir) && write_llir(&llir, &ws.file(&unit, "llir")).is_err() {
+            println!("Fatal: Unable to write the LLIR for {}", llir.name);
+            return Err(EXIT_BACKEND);
         }
-    
+        
+        // The LLVM files are written straight from the module
+        // Objects go through the assembler like the other backends
+        let mut outputs : Vec<(llvm::Emit, &str)> = Vec::new();
+        
+        if settings.emit.contains(&Artifact::LlvmIr) {
+            outputs.push((llvm::Emit::LlvmIr, "ll"));
+        }
+        
+        if settings.emit.contains(&Artifact::LlvmBc) {
+            outputs.push((llvm::Emit::LlvmBc, "bc"));
+        }
+        
+        if need_asm {
+            outputs.push((llvm::Emit::Asm, "asm"));
+        }
+        
+        for (emit, ext) in outputs {
+            let options = llvm::Options {
+                emit : emit,
+                output : ws.file(&unit, ext),
+                opt_level : settings.opt_level,
+                use_c : settings.use_c,
+                debug_info : settings.debug_info,
+                debug : settings.llvm_debug,
+            };
+            
+            if let Err(msg) = llvm::compile(&llir, &options) {
+                println!("Fatal: {}", msg);
+                return Err(EXIT_BACKEND);
+            }
+        }
+        
+        name = llir.name;
+    } else {
         // Build the LTAC portion
-        let mut ltac = match ltac::parse(input, arch, use_corelib) {
+        let mut ltac = match ltac::parse(input.clone(), arch, settings.use_corelib, settings.checks) {
             Ok(ltac) => ltac,
-            Err(_e) => return 1,
+            Err(errors) => {
+                syntax::print_errors(&errors, settings.error_format);
+                return Err(EXIT_SOURCE);
+            },
         };
         
         // Do any needed transformations or optimizations
-        ltac = match transform::run(&ltac, arch, use_c, risc_mode) {
+        let backend = &settings.backend;
+        let mut passes = backend.passes();
+        
+        if settings.risc_mode && !passes.contains(&Pass::Risc) {
+            passes.insert(0, Pass::Risc);
+        }
+        
+        ltac = match transform::run(&ltac, settings.use_c, &passes) {
             Ok(ltac) => ltac,
-            Err(_e) => return 1,
+            Err(_e) => return Err(EXIT_BACKEND),
         };
         
-        all_names.push(ltac.name.clone());
-        
         // Now compile
-        if print_ltac {
-            ltac_printer::compile(&ltac).expect("LTAC Codegen failed with unknown error."); 
-        } else if arch == Arch::X86_64 {
-            x86_64::compile(&ltac, pic).expect("Codegen failed with unknown error.");
-            build::assemble(&ltac.name, no_link);
-        } else if arch == Arch::AArch64 {
-            aarch64::compile(&ltac).expect("Codegen failed with unknown error.");
-            aarch64::build_asm(&ltac.name, no_link);
-        } else if arch == Arch::Riscv64 {
-            riscv64::compile(&ltac).expect("Codegen failed with unknown error.");
-            riscv64::build_asm(&ltac.name, no_link);
-        } else {
-            // TODO
-        }
-    }
-    
-    // Link
-    if !no_link && !print_ltac {
-        if arch == Arch::X86_64 {
-            build::link(&all_names, &output, use_corelib, link_lib, inc_start);
-        } else if arch == Arch::AArch64 {
-            aarch64::link(&all_names, &output, use_c, link_lib);
-        } else if arch == Arch::Riscv64 {
-            riscv64::link(&all_names, &output, use_c, link_lib);
-        }
-    }
-    
-    0
+        let mut code = true;
+        unit = unit_name(index, &ltac.name);
+        
+        if settings.emit.contains(&Artifact::Ltac) {
+            code = ltac_printer::compile(&ltac, &ws.file(&unit, "ltac")).is_ok();
+        }
+        
+        if code && need_asm {
+            let options = CodeOptions {
+                pic : settings.pic,
+                debug : settings.debug_info,
+            };
+            
+            code = backend.compile(&ltac, &options, ws, &unit).is_ok();
+        }
+        
+        if !code {
+            println!("Fatal: Code generation failed for {}", ltac.name);
+            return Err(EXIT_BACKEND);
+        }
+        
+        name = ltac.name;
+    }
+    
+    if need_obj {
+        if let Err(e) = settings.backend.assemble(&settings.tools, ws, &unit) {
+            return Err(tool_exit(e, EXIT_BACKEND));
+        }
+    }
+    
+    // Copy out what was asked for
+    for artifact in settings.emit.iter() {
+        let (from, to) = match artifact {
+            Artifact::Asm => ("asm", "s"),
+            Artifact::Obj => ("o", "o"),
+            Artifact::Ltac => ("ltac", "ltac"),
+            Artifact::Llir => ("llir", "llir"),
+            Artifact::LlvmIr => ("ll", "ll"),
+            Artifact::LlvmBc => ("bc", "bc"),
+            Artifact::Exe => continue,
+        };
+        
+        let path = emit_path(settings, &name, to);
+        
+        if fs::copy(ws.file(&unit, from), &path).is_err() {
+            println!("Fatal: Unable to write {}", path);
+            return Err(EXIT_BACKEND);
+        }
+    }
+    
+    Ok(unit)
+}
+
+// Where an emitted file goes
+// With one source file, it is named after -o (so -o foo --emit=asm writes foo.s). Otherwise,
+// each unit gets a file named after it, in the directory of -o.
+fn emit_path(settings : &Settings, name : &String, ext : &str) -> String {
+    let output = Path::new(&settings.output);
+    
+    let path = if settings.has_output && settings.one_unit {
+        output.with_extension(ext)
+    } else {
+        output.with_file_name(format!("{}.{}", name, ext))
+    };
+    
+    path.to_string_lossy().into_owned()
+}
+
+// Writes the LLIR of a unit, one instruction per line
+fn write_llir(llir : &parser::llir::LLirFile, path : &String) -> io::Result<()> {
+    let mut file = fs::File::create(path)?;
+    writeln!(file, "Name: {}", llir.name)?;
+    
+    for ln in llir.code.iter() {
+        writeln!(file, "{:?}", ln)?;
+    }
+    
+    Ok(())
+}
+
+// The --emit type for a name
+fn parse_artifact(name : &str) -> Option<Artifact> {
+    match name {
+        "asm" => return Some(Artifact::Asm),
+        "obj" => return Some(Artifact::Obj),
+        "ltac" => return Some(Artifact::Ltac),
+        "llir" => return Some(Artifact::Llir),
+        "llvm-ir" => return Some(Artifact::LlvmIr),
+        "llvm-bc" => return Some(Artifact::LlvmBc),
+        "exe" => return Some(Artifact::Exe),
+        _ => return None,
+    }
+}
+
+fn artifact_name(artifact : Artifact) -> &'static str {
+    match artifact {
+        Artifact::Asm => return "asm",
+        Artifact::Obj => return "obj",
+        Artifact::Ltac => return "ltac",
+        Artifact::Llir => return "llir",
+        Artifact::LlvmIr => return "llvm-ir",
+        Artifact::LlvmBc => return "llvm-bc",
+        Artifact::Exe => return "exe",
+    }
+}
+
+// The exit code for a tool which failed
+// A tool which could not be run at all gets its own code, so scripts can tell it apart
+fn tool_exit(error : ToolError, failed : i32) -> i32 {
+    match error {
+        ToolError::Missing => return EXIT_TOOL,
+        ToolError::Failed => return failed,
+    }
+}
+
+// Links the object files, which are either names of compiled units or paths
+fn link(all_names : &Vec<String>, output : &String, settings : &Settings) -> i32 {
+    let options = LinkOptions {
+        use_c : settings.use_c,
+        use_corelib : settings.use_corelib,
+        is_lib : settings.link_lib,
+        inc_start : settings.inc_start,
+    };
+    
+    match settings.backend.link(&settings.tools, all_names, output, &options) {
+        Ok(()) => return 0,
+        Err(e) => return tool_exit(e, EXIT_LINK),
+    }
 }
 
 // Displays compiler help
 fn help() {
     println!("lilac version 0.1");
     println!("");
+    println!("Usage: lilac [options] <inputs>");
+    println!("       lilac build [options] <main file> \t Build the program and the modules it uses.");
+    println!("");
     println!("--ast \t\t Print a textual representation of the AST");
     println!("--ltac \t\t Save the LTAC IR to a file.");
     println!("--use-c \t Link to C start-up files and the C standard library.");
@@ -219,6 +627,19 @@ fn help() {
     println!("--no-start \t Do not link with start files.");
     println!("-l<lib> \t Link to a certain library.");
     println!("-o <name> \t Specify the output name.");
+    println!("-g \t\t Generate debugging information.");
+    println!("-march=<target> Build for another target ({}).", targets::target_names().join(", "));
+    println!("--as=<command> \t Use another assembler.");
+    println!("--ld=<command> \t Use another linker.");
+    println!("--cc[=<command>] Link through the C compiler driver instead of ld.");
+    println!("--dynamic-linker=<path> Set the dynamic linker of the program.");
+    println!("--lrt=<path> \t Use another Lila start file.");
+    println!("--llvm \t\t Use the LLVM backend.");
+    println!("--emit=<types> \t Write any of asm, obj, ltac, llir, llvm-ir, llvm-bc and exe, separated by commas.");
+    println!("--checks=<checks> Add runtime checks: none (the default), bounds or all.");
+    println!("--save-temps \t Keep the temporary files.");
+    println!("-O<level> \t Set the optimization level, from -O0 to -O3 (LLVM only).");
+    println!("--error-format=json \t Print errors as JSON, one object per line.");
     println!("-h, --help \t Display this message and exit.");
     println!("");
 }
@@ -0,0 +1,229 @@
+
+// This file is part of the Lila compiler
+// Copyright (C) 2020-2021 Patrick Flynn
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; version 2.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, write to the Free Software Foundation, Inc.,
+// 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+
+// The "lilac build" mode
+// Starting from the main file, this finds the modules it uses that have sources in the
+// project, and compiles each of them once. Units whose source and imported headers have
+// not changed since the last build are skipped.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use parser;
+use parser::module;
+
+use crate::*;
+
+// The build directory, relative to the project root (the directory of the main file)
+const BUILD_DIR : &str = ".lilac-build";
+
+// A source file in the project
+struct Unit {
+    key : String,           // The module name, or the file name for the main file
+    path : String,
+    uses : Vec<String>,
+}
+
+// Builds a whole program
+pub fn build(inputs : &Vec<String>, settings : &Settings) -> i32 {
+    let mut main_file = String::new();
+    let mut extra : Vec<String> = Vec::new();
+    
+    let cwd = env::current_dir().unwrap_or_default();
+    
+    // Object files and libraries are passed through to the linker
+    for input in inputs.iter() {
+        if input.starts_with("-l") {
+            extra.push(input.clone());
+        } else if input.ends_with(".o") {
+            extra.push(cwd.join(input).to_string_lossy().into_owned());
+        } else if main_file.len() > 0 {
+            println!("Fatal: build takes one main file; {} and {} were given.", main_file, input);
+            return EXIT_USAGE;
+        } else {
+            main_file = input.clone();
+        }
+    }
+    
+    if main_file.len() == 0 {
+        println!("Fatal: No main file specified.");
+        return EXIT_USAGE;
+    }
+    
+    let main_path = match fs::canonicalize(&main_file) {
+        Ok(path) => path,
+        Err(_e) => {
+            println!("Fatal: Unable to open {}", main_file);
+            return EXIT_USAGE;
+        },
+    };
+    
+    // Module paths and headers are relative to the project root
+    let root = main_path.parent().unwrap();
+    let output = cwd.join(&settings.output).to_string_lossy().into_owned();
+    
+    if env::set_current_dir(root).is_err() || fs::create_dir_all(BUILD_DIR).is_err() {
+        println!("Fatal: Unable to create the build directory in {}", root.display());
+        return EXIT_BACKEND;
+    }
+    
+    let main_name = main_path.file_name().unwrap().to_string_lossy().into_owned();
+    let main_key = parser::get_name(&main_name);
+    
+    // Find all the units; dependencies come before the units that use them, and the main file is last
+    let mut units : Vec<Unit> = Vec::new();
+    let mut stack : Vec<String> = Vec::new();
+    
+    if let Err(msg) = add_unit(main_key, main_name, settings, &mut units, &mut stack) {
+        println!("Fatal: {}", msg);
+        return EXIT_SOURCE;
+    }
+    
+    let mut all_names : Vec<String> = Vec::new();
+    
+    for (i, unit) in units.iter().enumerate() {
+        let obj_path = format!("{}/{}.o", BUILD_DIR, unit.key);
+        let hash_path = format!("{}/{}.hash", BUILD_DIR, unit.key);
+        
+        let hash = unit_hash(unit, settings);
+        let old_hash = fs::read_to_string(&hash_path).unwrap_or_default();
+        
+        // A module which is up to date still needs its header for the units after it
+        let mut fresh = hash.len() > 0 && hash == old_hash && Path::new(&obj_path).exists();
+        
+        if i + 1 < units.len() && !Path::new(&module::get_module_path(&unit.key)).exists() {
+            fresh = false;
+        }
+        
+        if !fresh {
+            println!("Compiling {}", unit.path);
+            
+            let name = match compile_unit(&unit.path, i, settings) {
+                Ok(name) => name,
+                Err(code) => return code,
+            };
+            
+            // The hash is taken again, since building a module rewrites its header
+            let obj_name = settings.ws.file(&name, "o");
+            
+            if fs::copy(&obj_name, &obj_path).is_err() || fs::write(&hash_path, unit_hash(unit, settings)).is_err() {
+                println!("Fatal: Unable to write {}", obj_path);
+                return EXIT_BACKEND;
+            }
+        }
+        
+        all_names.push(root.join(&obj_path).to_string_lossy().into_owned());
+    }
+    
+    all_names.append(&mut extra);
+    link(&all_names, &output, settings)
+}
+
+// Adds a unit after the units it depends on
+// Modules without a source in the project (such as the core library) only have a header
+fn add_unit(key : String, path : String, settings : &Settings, units : &mut Vec<Unit>, stack : &mut Vec<String>) -> Result<(), String> {
+    if stack.contains(&key) {
+        stack.push(key);
+        return Err(format!("Circular module dependency: {}", stack.join(" -> ")));
+    }
+    
+    if units.iter().any(|u| u.key == key) {
+        return Ok(());
+    }
+    
+    let uses = match module::find_uses(&path, settings.arch) {
+        Ok(uses) => uses,
+        Err(_e) => return Err(format!("Unable to open {}", path)),
+    };
+    
+    stack.push(key.clone());
+    
+    for name in uses.iter() {
+        if let Some(source) = find_source(name) {
+            add_unit(name.clone(), source, settings, units, stack)?;
+        }
+    }
+    
+    stack.pop();
+    
+    units.push(Unit {
+        key : key,
+        path : path,
+        uses : uses,
+    });
+    
+    Ok(())
+}
+
+// Returns the source of a module: a.b.c is in a/b/c.ls
+fn find_source(name : &String) -> Option<String> {
+    let mut path = name.trim_start_matches("default.").replace(".", "/");
+    path.push_str(".ls");
+    
+    if Path::new(&path).exists() {
+        return Some(path);
+    }
+    
+    None
+}
+
+// Hashes everything a unit's object depends on: the options, the source, and the headers it imports
+fn unit_hash(unit : &Unit, settings : &Settings) -> String {
+    let source = match fs::read(&unit.path) {
+        Ok(source) => source,
+        Err(_e) => return String::new(),
+    };
+    
+    let options = format!("{} {} {} {} {:?} {} {} {} {} {} {}", settings.backend.name(), settings.use_c, settings.use_corelib,
+        settings.pic, settings.checks, settings.risc_mode, settings.use_llvm, settings.debug_info, settings.opt_level,
+        settings.tools.assembler, env!("CARGO_PKG_VERSION"));
+    
+    let mut hash = fnv_hash(0xcbf29ce484222325, options.as_bytes());
+    hash = fnv_hash(hash, &source);
+    
+    let mut headers = unit.uses.clone();
+    
+    if settings.use_corelib {
+        for name in module::CORE_MODULES.iter() {
+            headers.push(name.to_string());
+        }
+    }
+    
+    for name in headers.iter() {
+        hash = fnv_hash(hash, name.as_bytes());
+        
+        if let Ok(header) = fs::read(module::get_module_path(name)) {
+            hash = fnv_hash(hash, &header);
+        }
+    }
+    
+    format!("{:016x}", hash)
+}
+
+// The 64-bit FNV-1a hash
+// We need the same value from one run to the next, which the standard hasher does not promise
+fn fnv_hash(start : u64, data : &[u8]) -> u64 {
+    let mut hash = start;
+    
+    for b in data.iter() {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    
+    hash
+}
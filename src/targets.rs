@@ -0,0 +1,49 @@
+
+// This file is part of the Lila compiler
+// Copyright (C) 2020-2021 Patrick Flynn
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; version 2.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, write to the Free Software Foundation, Inc.,
+// 51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+
+// The registry of native backends
+// To add a backend, add its crate to Cargo.toml and put it in the list below.
+
+use ltac::backend::Backend;
+
+// The target we build for by default
+#[cfg(target_arch = "x86_64")]
+pub const HOST_TARGET : &str = "x86_64";
+
+#[cfg(target_arch = "aarch64")]
+pub const HOST_TARGET : &str = "aarch64";
+
+#[cfg(target_arch = "riscv64")]
+pub const HOST_TARGET : &str = "riscv64";
+
+fn all_backends() -> Vec<Box<dyn Backend>> {
+    vec![
+        Box::new(x86_64::X86Backend),
+        Box::new(aarch64::AArch64Backend),
+        Box::new(riscv64::Riscv64Backend),
+    ]
+}
+
+// Returns the backend for a target name
+pub fn get_backend(name : &str) -> Option<Box<dyn Backend>> {
+    all_backends().into_iter().find(|b| b.name() == name)
+}
+
+// Returns the names of all the targets, for error messages
+pub fn target_names() -> Vec<&'static str> {
+    all_backends().iter().map(|b| b.name()).collect()
+}
@@ -13,7 +13,7 @@ function run_test() {
             fi
             
             echo "#!/bin/bash" >> ERROR_TEST.sh
-            echo "cargo run $entry --use-c" >> ERROR_TEST.sh
+            echo "$lilac $entry --use-c --llvm $4" >> ERROR_TEST.sh
             chmod 777 ERROR_TEST.sh
             ./test.py $entry  ./ERROR_TEST.sh "error"
             
@@ -25,11 +25,11 @@ function run_test() {
             rm ERROR_TEST.sh
         else
             if [[ $2 == "sys" ]] ; then
-                cargo run $entry $3 -o $name --llvm
+                $lilac $entry $3 -o $name --llvm
             elif [[ $2 == "sys2" ]] ; then
-                cargo run $entry $3 -o $name --no-start --llvm
+                $lilac $entry $3 -o $name --no-start --llvm
             elif [[ $2 == "clib" ]] ; then
-                cargo run $entry --use-c $3 -o $name --llvm
+                $lilac $entry --use-c $3 -o $name --llvm
             fi
         
     	    ./test.py $entry ./$name ""
@@ -39,21 +39,129 @@ function run_test() {
         	fi
         	
         	rm ./$name
-        	rm /tmp/$name.o
-        	rm /tmp/$name.asm
     	fi
     	
     	test_count=$((test_count+1))
     done
 }
 
+# Compiles all the sources under a directory into one program
+# The first source (by path) has the expected output
+function run_units_test() {
+    for dir in $1
+    do
+        name=`basename $dir`
+        files=`find $dir -name "*.ls" | sort`
+        first=`echo "$files" | head -1`
+        
+        $lilac $files -o $name --llvm
+        
+        ./test.py $first ./$name ""
+        
+        if [[ $? != 0 ]] ; then
+            exit 1
+        fi
+        
+        rm ./$name
+        test_count=$((test_count+1))
+    done
+}
+
+# Builds a project from its main file; building it again must not recompile anything
+function run_build_test() {
+    for entry in $1
+    do
+        dir=`dirname $entry`
+        name=`basename $dir`
+        
+        rm -rf $dir/.lilac-build
+        $lilac build $entry --llvm -o $name
+        
+        ./test.py $entry ./$name ""
+        
+        if [[ $? != 0 ]] ; then
+            exit 1
+        fi
+        
+        rebuilt=`$lilac build $entry --llvm -o $name | grep Compiling`
+        
+        if [[ $rebuilt != "" ]] ; then
+            echo "Unexpected rebuild: $rebuilt"
+            exit 1
+        fi
+        
+        rm ./$name
+        rm -r $dir/.lilac-build
+        find $dir -name "*.lh" -delete
+        
+        test_count=$((test_count+1))
+    done
+}
+
+# Build once and call the compiler directly, since cargo run repeats the build warnings
+# on stderr, and the error tests read stderr
+cargo build
+
+if [[ $? != 0 ]] ; then
+    exit 1
+fi
+
+lilac=./target/debug/lilac
+
 flags=""
 
-echo "Running all tests..."
+echo "Running all tests through LLVM..."
 echo ""
 
 run_test 'test/basic/*.ls' 'sys' $flags
+run_test 'test/int/*.ls' 'sys' $flags
+run_test 'test/int64/*.ls' 'sys' $flags
+run_test 'test/byte/*.ls' 'sys' $flags
+run_test 'test/short/*.ls' 'sys' $flags
+run_test 'test/float/*.ls' 'clib' $flags
+run_test 'test/clib/*.ls' 'clib' $flags
+run_test 'test/char/*.ls' 'sys' $flags
+run_test 'test/string/*.ls' 'sys' $flags
+
+run_test 'test/assign/*.ls' 'sys' $flags
+run_test 'test/ooop/*.ls' 'sys' $flags
+run_test 'test/loop/*.ls' 'sys' $flags
+run_test 'test/mem/*.ls' 'sys' $flags
+run_test 'test/const/*.ls' 'sys' $flags
+run_test 'test/func/*.ls' 'sys' $flags
+run_test 'test/enum/*.ls' 'sys' $flags
+run_test 'test/record/*.ls' 'sys' $flags
+run_test 'test/global/*.ls' 'sys' $flags
+run_test 'test/ptr/*.ls' 'clib' $flags
+run_test 'test/case/*.ls' 'clib' $flags
+run_test 'test/bool/*.ls' 'clib' $flags
+run_test 'test/expr/*.ls' 'clib' $flags
+run_test 'test/bounds/*.ls' 'sys' "--checks=bounds"
+run_test 'test/bounds/*.ls' 'clib' "--checks=bounds"
+
+run_test 'test/errors/*.ls' 'sys' "error"
+run_test 'test/errors/ltac/*.ls' "sys" "error"
+run_test 'test/errors/typeck/*.ls' "sys" "error"
+run_test 'test/errors/json/*.ls' "sys" "error" "--error-format=json"
+
+run_test 'test/vector/*.ls' 'clib'
+run_test 'test/syscall/x86-64/*.ls' 'sys2'
 
+run_units_test 'test/units/*'
+run_build_test 'test/build/*/main.ls'
+
+# Run the programs again with the optimizer, since it relies on the
+# runtime library following the calling convention
+flags="-O2"
+
+run_test 'test/basic/*.ls' 'sys' $flags
+run_test 'test/int/*.ls' 'sys' $flags
+run_test 'test/int64/*.ls' 'sys' $flags
+run_test 'test/string/*.ls' 'sys' $flags
+run_test 'test/loop/*.ls' 'sys' $flags
+run_test 'test/record/*.ls' 'sys' $flags
+run_test 'test/global/*.ls' 'sys' $flags
+run_test 'test/float/*.ls' 'clib' $flags
 
 echo ""
 echo "$test_count tests passed successfully."
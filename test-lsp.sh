@@ -0,0 +1,19 @@
+#!/bin/bash
+
+echo "Running the language server tests..."
+echo ""
+
+cargo build -p lila-lsp
+
+if [[ $? != 0 ]] ; then
+    exit 1
+fi
+
+./test/lsp/lsp_test.py ./target/debug/lila-lsp
+
+if [[ $? != 0 ]] ; then
+    exit 1
+fi
+
+echo ""
+echo "Done"
@@ -13,7 +13,7 @@ function run_test() {
             fi
             
             echo "#!/bin/bash" >> ERROR_TEST.sh
-            echo "cargo run $entry --use-c" >> ERROR_TEST.sh
+            echo "$lilac $entry --use-c" >> ERROR_TEST.sh
             chmod 777 ERROR_TEST.sh
             ./test.py $entry  ./ERROR_TEST.sh "error"
             
@@ -25,9 +25,9 @@ function run_test() {
             rm ERROR_TEST.sh
         else
             if [[ $2 == "sys" ]] ; then
-                cargo run $entry $3 -o $name
+                $lilac $entry $3 -o $name
             elif [[ $2 == "clib" ]] ; then
-                cargo run $entry --use-c $3 -o $name
+                $lilac $entry --use-c $3 -o $name
             fi
         
     	    ./test.py $entry ./$name ""
@@ -37,14 +37,22 @@ function run_test() {
         	fi
         	
         	rm ./$name
-        	rm /tmp/$name.o
-        	rm /tmp/$name.asm
     	fi
     	
     	test_count=$((test_count+1))
     done
 }
 
+# Build once and call the compiler directly, since cargo run repeats the build warnings
+# on stderr, and the error tests read stderr
+cargo build
+
+if [[ $? != 0 ]] ; then
+    exit 1
+fi
+
+lilac=./target/debug/lilac
+
 flags=""
 
 echo "Running all tests..."
@@ -68,6 +76,7 @@ run_test 'test/const/*.ls' 'clib' $flags
 
 run_test 'test/errors/*.ls' 'clib' "error"
 run_test 'test/errors/ltac/*.ls' "clib" "error"
+run_test 'test/errors/typeck/*.ls' "clib" "error"
 
 #run_test 'test/vector/*.ls' 'clib'
 #run_test 'test/syscall/x86-64/*.ls' 'sys'
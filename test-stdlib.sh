@@ -22,8 +22,6 @@ function run_test() {
     	fi
     	
     	rm ./$name
-    	rm /tmp/$name.o
-    	rm /tmp/$name.asm
     	
     	test_count=$((test_count+1))
     done
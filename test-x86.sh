@@ -13,7 +13,7 @@ function run_test() {
             fi
             
             echo "#!/bin/bash" >> ERROR_TEST.sh
-            echo "cargo run $entry --use-c" >> ERROR_TEST.sh
+            echo "$lilac $entry --use-c $4" >> ERROR_TEST.sh
             chmod 777 ERROR_TEST.sh
             ./test.py $entry  ./ERROR_TEST.sh "error"
             
@@ -25,11 +25,11 @@ function run_test() {
             rm ERROR_TEST.sh
         else
             if [[ $2 == "sys" ]] ; then
-                cargo run $entry $3 -o $name
+                $lilac $entry $3 -o $name
             elif [[ $2 == "sys2" ]] ; then
-                cargo run $entry $3 -o $name --no-start
+                $lilac $entry $3 -o $name --no-start
             elif [[ $2 == "clib" ]] ; then
-                cargo run $entry --use-c $3 -o $name
+                $lilac $entry --use-c $3 -o $name
             fi
         
     	    ./test.py $entry ./$name ""
@@ -39,14 +39,104 @@ function run_test() {
         	fi
         	
         	rm ./$name
-        	rm /tmp/$name.o
-        	rm /tmp/$name.asm
     	fi
     	
     	test_count=$((test_count+1))
     done
 }
 
+# Compiles all the sources under a directory into one program
+# The first source (by path) has the expected output
+function run_units_test() {
+    for dir in $1
+    do
+        name=`basename $dir`
+        files=`find $dir -name "*.ls" | sort`
+        first=`echo "$files" | head -1`
+        
+        $lilac $files -o $name
+        
+        ./test.py $first ./$name ""
+        
+        if [[ $? != 0 ]] ; then
+            exit 1
+        fi
+        
+        rm ./$name
+        test_count=$((test_count+1))
+    done
+}
+
+# Builds a project from its main file; building it again must not recompile anything
+function run_build_test() {
+    for entry in $1
+    do
+        dir=`dirname $entry`
+        name=`basename $dir`
+        
+        rm -rf $dir/.lilac-build
+        $lilac build $entry -o $name
+        
+        ./test.py $entry ./$name ""
+        
+        if [[ $? != 0 ]] ; then
+            exit 1
+        fi
+        
+        rebuilt=`$lilac build $entry -o $name | grep Compiling`
+        
+        if [[ $rebuilt != "" ]] ; then
+            echo "Unexpected rebuild: $rebuilt"
+            exit 1
+        fi
+        
+        rm ./$name
+        rm -r $dir/.lilac-build
+        find $dir -name "*.lh" -delete
+        
+        test_count=$((test_count+1))
+    done
+}
+
+# Runs the compiler with the given flags, and then a command that checks what it wrote
+# The output of both is compared, along with the exit code of the last one
+function run_driver_test() {
+    for entry in $1
+    do
+        name=`basename $entry .ls`
+        
+        if [ -f ./DRIVER_TEST.sh ] ; then
+            rm DRIVER_TEST.sh
+        fi
+        
+        echo "#!/bin/bash" >> DRIVER_TEST.sh
+        echo "$lilac $entry $2 -o $name" >> DRIVER_TEST.sh
+        echo "$3" >> DRIVER_TEST.sh
+        chmod 777 DRIVER_TEST.sh
+        ./test.py $entry ./DRIVER_TEST.sh "driver"
+        
+        if [[ $? != 0 ]] ; then
+            rm DRIVER_TEST.sh
+            exit 1
+        fi
+        
+        rm DRIVER_TEST.sh
+        rm -rf $name $name.*
+        
+        test_count=$((test_count+1))
+    done
+}
+
+# Build once and call the compiler directly, since cargo run repeats the build warnings
+# on stderr, and the error tests read stderr
+cargo build
+
+if [[ $? != 0 ]] ; then
+    exit 1
+fi
+
+lilac=./target/debug/lilac
+
 flags=""
 
 echo "Running all tests..."
@@ -57,7 +147,8 @@ run_test 'test/int/*.ls' 'sys' $flags
 run_test 'test/int64/*.ls' 'sys' $flags
 run_test 'test/byte/*.ls' 'sys' $flags
 run_test 'test/short/*.ls' 'sys' $flags
-#run_test 'test/float/*.ls' 'clib' $flags
+run_test 'test/float/*.ls' 'clib' $flags
+run_test 'test/clib/*.ls' 'clib' $flags
 run_test 'test/char/*.ls' 'sys' $flags
 run_test 'test/string/*.ls' 'sys' $flags
 
@@ -68,14 +159,62 @@ run_test 'test/mem/*.ls' 'sys' $flags
 run_test 'test/const/*.ls' 'sys' $flags
 run_test 'test/func/*.ls' 'sys' $flags
 run_test 'test/enum/*.ls' 'sys' $flags
+run_test 'test/record/*.ls' 'sys' $flags
+run_test 'test/global/*.ls' 'sys' $flags
+run_test 'test/ptr/*.ls' 'clib' $flags
+run_test 'test/case/*.ls' 'clib' $flags
+run_test 'test/bool/*.ls' 'clib' $flags
+run_test 'test/expr/*.ls' 'clib' $flags
+run_test 'test/bounds/*.ls' 'sys' "--checks=bounds"
+run_test 'test/bounds/*.ls' 'clib' "--checks=bounds"
 
 run_test 'test/errors/*.ls' 'sys' "error"
 run_test 'test/errors/ltac/*.ls' "sys" "error"
+run_test 'test/errors/typeck/*.ls' "sys" "error"
+run_test 'test/errors/json/*.ls' "sys" "error" "--error-format=json"
 
 #run_test 'test/vector/*.ls' 'clib'
 run_test 'test/syscall/x86-64/*.ls' 'sys2'
 
+run_units_test 'test/units/*'
+run_build_test 'test/build/*/main.ls'
+
+# Only the assembly is written, so every backend can be checked here
+# The core library is only built for this machine
+debug_check="grep -E '^\\.file|\\.loc|^\\.section \\.debug|\\.string \"(main|x)\"' debug1.s | sed 's/^ *//'"
+
+for target in x86_64 aarch64 riscv64
+do
+    run_driver_test 'test/driver/debug1.ls' "-g --emit=asm -march=$target --no-corelib" "$debug_check"
+done
+
+run_driver_test 'test/driver/target1.ls' "-march=sparc" ""
+run_driver_test 'test/driver/target2.ls' "-march=aarch64 --pic --emit=asm" ""
+
+run_driver_test 'test/driver/tool1.ls' "--as=lilac-no-such-as" ""
+run_driver_test 'test/driver/tool2.ls' "--as=false" ""
+run_driver_test 'test/driver/tool3.ls' "--ld=false" ""
+
+# Linking through cc with the C library must still call its printf
+run_driver_test 'test/driver/cc1.ls' "--cc --use-c" "./cc1"
+
+run_driver_test 'test/driver/emit1.ls' "--emit=asm,obj,ltac" "ls emit1*"
+run_driver_test 'test/driver/emit2.ls' "--llvm --emit=llir,obj" "ls emit2*"
+
+# The compiler says where it kept the files, which is different each time
+save_check="temps=\`sed -n 's/^Note: The temporary files were kept in //p' save1.log\`; ls \$temps; ls save1; rm -r \$temps"
+run_driver_test 'test/driver/save1.ls' "--save-temps > save1.log" "$save_check"
+
 echo ""
 echo "$test_count tests passed successfully."
+echo ""
+
+# The same tests must also pass through the LLVM backend
+./test-llvm.sh
+
+if [[ $? != 0 ]] ; then
+    exit 1
+fi
+
 echo "Done"
 
@@ -33,7 +33,12 @@ with open(test_file) as reader:
 		elif in_output:
 			output.append(ln[1:])
 			
-result = subprocess.run([bin_file], stdout=subprocess.PIPE)
+# Diagnostics are written to stderr
+# The driver tests check them along with the exit code
+if test_type == "error" or test_type == "driver":
+	result = subprocess.run([bin_file], stdout=subprocess.PIPE, stderr=subprocess.STDOUT)
+else:
+	result = subprocess.run([bin_file], stdout=subprocess.PIPE)
 cmd_output = result.stdout.decode('utf-8').split('\n')
 cmd_output.remove('')
 rc = result.returncode
@@ -0,0 +1,115 @@
+#!/usr/bin/python3
+
+# Runs a short editing session against the language server over stdio
+# Syntax: lsp_test.py <lila-lsp binary>
+
+import json
+import os
+import subprocess
+import sys
+
+if len(sys.argv) != 2:
+	print("Syntax: lsp_test.py <lila-lsp binary>")
+	exit(1)
+
+here = os.path.dirname(os.path.abspath(__file__))
+server = subprocess.Popen([sys.argv[1]], stdin=subprocess.PIPE, stdout=subprocess.PIPE)
+next_id = 0
+
+def send(msg):
+	body = json.dumps(msg).encode("utf-8")
+	server.stdin.write(b"Content-Length: " + str(len(body)).encode() + b"\r\n\r\n" + body)
+	server.stdin.flush()
+
+def receive():
+	length = 0
+	while True:
+		line = server.stdout.readline().decode().strip()
+		if line == "":
+			break
+		if line.startswith("Content-Length:"):
+			length = int(line[15:])
+		elif not line.startswith("Content-Type:"):
+			print("Unexpected output from the server: " + line)
+			server.kill()
+			exit(1)
+	return json.loads(server.stdout.read(length))
+
+def request(method, params):
+	global next_id
+	next_id += 1
+	send({"jsonrpc": "2.0", "id": next_id, "method": method, "params": params})
+	return receive()["result"]
+
+def notify(method, params):
+	send({"jsonrpc": "2.0", "method": method, "params": params})
+
+def open_file(name):
+	path = os.path.join(here, name)
+	uri = "file://" + path
+	with open(path) as reader:
+		text = reader.read()
+	notify("textDocument/didOpen", {"textDocument": {"uri": uri, "languageId": "lila", "version": 1, "text": text}})
+	return uri, receive()
+
+def check(name, condition):
+	print("[TEST] lsp: " + name)
+	if not condition:
+		print("Fail")
+		server.kill()
+		exit(1)
+
+result = request("initialize", {"processId": None, "rootUri": None, "capabilities": {}})
+check("initialize", result["capabilities"]["definitionProvider"] == True)
+notify("initialized", {})
+
+# Diagnostics
+uri, diags = open_file("symbols.ls")
+check("no diagnostics", diags["params"]["uri"] == uri and diags["params"]["diagnostics"] == [])
+
+bad_uri, diags = open_file("errors.ls")
+found = diags["params"]["diagnostics"]
+check("diagnostics", len(found) == 1 and found[0]["code"] == "E0105" and found[0]["range"]["start"]["line"] == 10)
+
+# The parser must not write anything of its own between the messages
+enum_uri, diags = open_file("enum.ls")
+found = diags["params"]["diagnostics"]
+check("diagnostics for an enum", len(found) == 1 and found[0]["code"] == "E0003")
+
+notify("textDocument/didSave", {"textDocument": {"uri": bad_uri}})
+diags = receive()
+check("diagnostics on save", diags["params"]["uri"] == bad_uri and len(diags["params"]["diagnostics"]) == 1)
+
+# Go-to-definition (the call to add on line 15, the constant, and an enum value)
+pos = lambda line, col: {"textDocument": {"uri": uri}, "position": {"line": line, "character": col}}
+
+result = request("textDocument/definition", pos(14, 13))
+check("definition of a function", result["uri"] == uri and result["range"]["start"] == {"line": 4, "character": 5})
+
+result = request("textDocument/definition", pos(14, 17))
+check("definition of a constant", result["range"]["start"] == {"line": 0, "character": 10})
+
+result = request("textDocument/definition", pos(11, 18))
+check("definition of an enum value", result["range"]["start"]["line"] == 10)
+
+# Functions from the core library come from the module headers
+result = request("textDocument/completion", pos(14, 0))
+labels = [item["label"] for item in result]
+check("completion", "add" in labels and "LIMIT" in labels and "malloc" in labels)
+
+mod_uri, diags = open_file("modules.ls")
+mod_pos = {"textDocument": {"uri": mod_uri}, "position": {"line": 1, "character": 18}}
+
+result = request("textDocument/definition", mod_pos)
+check("definition in a module", result["uri"].endswith("/core/mem.lh"))
+
+result = request("textDocument/hover", mod_pos)
+check("hover in a module", "extern func malloc(size:int) -> int64" in result["contents"]["value"])
+
+# Hover
+result = request("textDocument/hover", pos(14, 13))
+check("hover", "func add(x:int, y:int) -> int" in result["contents"]["value"])
+
+request("shutdown", None)
+notify("exit", None)
+check("exit", server.wait() == 0)